@@ -0,0 +1,102 @@
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton, EditInteractionResponse,
+    GuildId, ReactionType, RoleId,
+};
+use serenity::client::Context;
+
+use crate::commands::PoiseResult;
+use crate::common::{LogResult, UserData};
+use crate::helpers::ButtonCustomId;
+use crate::settings;
+
+/// Content of an access message, posted by `/access post`
+pub fn access_message(role_id: RoleId, text: Option<&str>) -> String {
+    let text = text.unwrap_or("Press the button to get access to the soundboard, or to give it up");
+    format!("{text}\nGrants <@&{role_id}>")
+}
+
+/// Button toggling the soundboard user role of whoever presses it
+pub fn access_action_row() -> CreateActionRow {
+    CreateActionRow::Buttons(vec![CreateButton::new(ButtonCustomId::ToggleAccess)
+        .label("Soundboard access")
+        .emoji(ReactionType::Unicode("🔊".into()))
+        .style(ButtonStyle::Primary)])
+}
+
+/// Whether toggling a member's soundboard user role grants it. Members with the role give it up
+pub fn grants(member_roles: &[RoleId], role_id: RoleId) -> bool {
+    !member_roles.contains(&role_id)
+}
+
+/// Give or take the soundboard user role of whoever pressed an access button. The interaction
+/// must already be acknowledged
+pub async fn toggle(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+    guild_id: GuildId,
+) -> PoiseResult {
+    let member = component
+        .member
+        .as_ref()
+        .ok_or("Access button pressed outside of a guild")?;
+
+    let content = match settings::user_role(&data.settings_table(), guild_id) {
+        None => "Soundboard access is open to everyone, there's no role to get".to_string(),
+        Some(role_id) => {
+            let grant = grants(&member.roles, role_id);
+            let reason = "Soundboard access button";
+            let result = match grant {
+                true => {
+                    ctx.http
+                        .add_member_role(guild_id, member.user.id, role_id, Some(reason))
+                        .await
+                }
+                false => {
+                    ctx.http
+                        .remove_member_role(guild_id, member.user.id, role_id, Some(reason))
+                        .await
+                }
+            };
+            // role decides play permission, which is cached
+            data.authorizer.invalidate(guild_id, member.user.id);
+
+            match (
+                result.log_err_msg("Failed toggling soundboard user role"),
+                grant,
+            ) {
+                (Ok(()), true) => format!("You now have <@&{role_id}>"),
+                (Ok(()), false) => format!("You no longer have <@&{role_id}>"),
+                (Err(_), _) => {
+                    "Failed changing your role. The bot needs Manage Roles, and a role above it"
+                        .to_string()
+                }
+            }
+        }
+    };
+
+    component
+        .edit_response(ctx, EditInteractionResponse::new().content(content))
+        .await
+        .log_err_msg("Failed replying to access button")
+        .ok();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn access_test() {
+        let role_id = RoleId::new(5);
+        assert!(grants(&[RoleId::new(1)], role_id));
+        assert!(!grants(&[RoleId::new(1), role_id], role_id));
+
+        assert_eq!(
+            access_message(role_id, Some("Want sounds?")),
+            "Want sounds?\nGrants <@&5>"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use chrono::{DateTime, Datelike, FixedOffset, Timelike, Utc, Weekday};
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+/// Heatmap cells, from no plays to the busiest hour
+const LEVELS: [&str; 5] = ["⬛", "🟦", "🟩", "🟨", "🟥"];
+
+/// Plays per weekday (monday first) & hour of day
+#[derive(Debug, Default, PartialEq)]
+pub struct Heatmap([[u32; 24]; 7]);
+
+impl Heatmap {
+    /// Bucket play times by their weekday & hour in timezone
+    pub fn from_times(times: &[DateTime<Utc>], timezone: FixedOffset) -> Self {
+        let mut heatmap = Self::default();
+        for time in times.iter().map(|time| time.with_timezone(&timezone)) {
+            let day = time.weekday().num_days_from_monday() as usize;
+            heatmap.0[day][time.hour() as usize] += 1;
+        }
+
+        heatmap
+    }
+
+    pub fn total(&self) -> u32 {
+        self.0.iter().flatten().sum()
+    }
+
+    fn max(&self) -> u32 {
+        self.0.iter().flatten().copied().max().unwrap_or(0)
+    }
+
+    /// Weekday & hour with most plays. None without plays
+    pub fn busiest_hour(&self) -> Option<(Weekday, u32)> {
+        let max = self.max();
+        (0..7)
+            .flat_map(|day| (0..24).map(move |hour| (day, hour)))
+            .find(|(day, hour)| max > 0 && self.0[*day][*hour] == max)
+            .map(|(day, hour)| (WEEKDAYS[day], hour as u32))
+    }
+
+    /// Weekday with most plays. None without plays
+    pub fn busiest_day(&self) -> Option<Weekday> {
+        (0..7)
+            .map(|day| (day, self.0[day].iter().sum::<u32>()))
+            .filter(|(_, plays)| *plays > 0)
+            .max_by_key(|(day, plays)| (*plays, std::cmp::Reverse(*day)))
+            .map(|(day, _)| WEEKDAYS[day])
+    }
+
+    /// Row of emoji per weekday, one per hour from midnight to 11pm. Busier hours are "warmer" colors
+    pub fn render(&self) -> String {
+        let max = self.max();
+        let level = |plays: u32| match plays {
+            0 => LEVELS[0],
+            _ => LEVELS[(plays * (LEVELS.len() as u32 - 1)).div_ceil(max) as usize],
+        };
+
+        let mut text = String::new();
+        for (day, hours) in self.0.iter().enumerate() {
+            let cells: String = hours.iter().map(|plays| level(*plays)).collect();
+            text.push_str(&format!("`{}` {cells}\n", WEEKDAYS[day]));
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn heatmap_test() {
+        // 2024-01-01 is a monday
+        let at = |day: u32, hour: u32| Utc.with_ymd_and_hms(2024, 1, day, hour, 30, 0).unwrap();
+        let utc = FixedOffset::east_opt(0).unwrap();
+        let heatmap = Heatmap::from_times(&[at(1, 22), at(8, 22), at(1, 9), at(3, 23)], utc);
+
+        assert_eq!(heatmap.total(), 4);
+        assert_eq!(heatmap.0[0][22], 2);
+        assert_eq!(heatmap.0[2][23], 1);
+        assert_eq!(heatmap.busiest_hour(), Some((Weekday::Mon, 22)));
+        assert_eq!(heatmap.busiest_day(), Some(Weekday::Mon));
+
+        let text = heatmap.render();
+        let rows: Vec<_> = text.lines().collect();
+        assert_eq!(rows.len(), 7);
+        assert!(rows[0].starts_with("`Mon` "));
+        assert_eq!(rows[0].chars().filter(|c| *c == '🟥').count(), 1);
+        assert_eq!(rows[6].chars().filter(|c| *c == '⬛').count(), 24);
+
+        // 23:00 UTC wednesday is 01:00 thursday at +02:00
+        let plus_two = FixedOffset::east_opt(2 * 60 * 60).unwrap();
+        let shifted = Heatmap::from_times(&[at(3, 23)], plus_two);
+        assert_eq!(shifted.busiest_hour(), Some((Weekday::Thu, 1)));
+
+        let empty = Heatmap::default();
+        assert_eq!(empty.busiest_hour(), None);
+        assert_eq!(empty.busiest_day(), None);
+    }
+}
@@ -0,0 +1,915 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::async_trait;
+use axum::body::Bytes;
+use axum::extract::multipart::Field;
+use axum::extract::{
+    ConnectInfo, DefaultBodyLimit, FromRequestParts, Multipart, Path, Request, State,
+};
+use axum::http::{header, request::Parts, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::Stream;
+use serde::{Deserialize, Serialize};
+use serenity::all::{Cache, CreateMessage, GuildId, Http, HttpError, UserId};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::auth::SoundboardPermissions;
+use crate::collision;
+use crate::commands::PoiseError;
+use crate::common::{LogResult, UserData};
+use crate::db::{
+    usage_day, ApiKeyRow, ApiRequestOutcome, ApiScope, ApiUsageTable, Tags, UniqueAudioTableCol,
+};
+use crate::errors::{AudioError, AuthError, FeatureError, UploadError};
+use crate::features::{self, FeatureFlag};
+use crate::helpers;
+use crate::ingest::{Ingest, IngestSource, IngestTarget};
+use crate::playback::PlaybackEvent;
+use crate::rate_limit::RateLimiter;
+use crate::uploads::{UploadOwner, Uploads};
+
+/// Biggest chunk of a resumable upload
+pub const MAX_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Biggest body of requests without a file, ie: starting an upload
+const MAX_JSON_SIZE: usize = 16 * 1024;
+
+/// Biggest text field of a sound form, ie: `name` & `tags`
+const MAX_FORM_FIELD_SIZE: usize = 1024;
+
+/// Bytes of a sound form besides its file, ie: boundaries, headers & text fields
+const MAX_FORM_OVERHEAD: u64 = 16 * 1024;
+
+/// Header with the byte offset a chunk of a resumable upload starts at
+const UPLOAD_OFFSET_HEADER: &str = "upload-offset";
+
+/// Route of playback events. The only route taking its key from the query string
+const EVENTS_PATH: &str = "/api/events";
+
+/// What HTTP API handlers share. Data is separate from the bot's, but shares its config,
+/// database & playback
+pub struct ApiState {
+    pub data: UserData,
+    pub http: Arc<Http>,
+    pub cache: Arc<Cache>,
+    pub uploads: Uploads,
+    pub limiter: RateLimiter,
+}
+
+type SharedState = Arc<ApiState>;
+
+/// Serve HTTP API on address, until the bot stops
+pub fn spawn(address: SocketAddr, state: ApiState) {
+    tokio::spawn(async move {
+        let listener = match tokio::net::TcpListener::bind(address).await {
+            Ok(listener) => listener,
+            Err(err) => {
+                log::error!("Failed binding HTTP API to {address} - {err}");
+                return;
+            }
+        };
+
+        log::info!("HTTP API listening on {address}");
+        let app = router(Arc::new(state)).into_make_service_with_connect_info::<SocketAddr>();
+        axum::serve(listener, app)
+            .await
+            .log_err_msg("HTTP API stopped")
+            .ok();
+    });
+}
+
+fn router(state: SharedState) -> Router {
+    // the scope is layered last on upload routes, so it is checked before permissions
+    let upload_scope = middleware::from_fn_with_state(ApiScope::Upload, require_scope);
+    let manage_sounds = middleware::from_fn_with_state(state.clone(), require_manage_sounds);
+
+    Router::new()
+        // file size is checked while reading the form, against the configured max
+        .route(
+            "/api/sounds",
+            post(upload_sound)
+                .layer(DefaultBodyLimit::disable())
+                .route_layer(manage_sounds.clone())
+                .route_layer(upload_scope.clone()),
+        )
+        .route(
+            "/api/uploads",
+            post(start_upload)
+                .route_layer(manage_sounds.clone())
+                .route_layer(upload_scope.clone()),
+        )
+        .route(
+            "/api/uploads/:id",
+            get(upload_status)
+                .patch(upload_chunk)
+                .delete(cancel_upload)
+                .layer(DefaultBodyLimit::max(MAX_CHUNK_SIZE))
+                .route_layer(manage_sounds)
+                .route_layer(upload_scope),
+        )
+        .route(
+            EVENTS_PATH,
+            get(playback_events).route_layer(middleware::from_fn_with_state(
+                ApiScope::Events,
+                require_scope,
+            )),
+        )
+        .layer(DefaultBodyLimit::max(MAX_JSON_SIZE))
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .with_state(state)
+}
+
+/// New API key. Shown to its creator once, only its hash is stored
+pub fn new_key() -> String {
+    format!("sb_{}", helpers::uuid_v4_str().replace('-', ""))
+}
+
+/// sha256 of key as lowercase hex string
+pub fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Error response, as `{"error": "..."}`
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    message: String,
+    /// Seconds to wait before retrying, sent as `Retry-After` header
+    retry_after: Option<u64>,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            retry_after: None,
+        }
+    }
+
+    fn unauthorized() -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "Missing or unknown API key.")
+    }
+
+    fn rate_limited(wait: Duration) -> Self {
+        let seconds = wait.as_secs_f64().ceil().max(1.0) as u64;
+        Self {
+            retry_after: Some(seconds),
+            ..Self::new(
+                StatusCode::TOO_MANY_REQUESTS,
+                format!("Too many requests. Retry in {seconds} seconds."),
+            )
+        }
+    }
+
+    fn payload_too_large(max_size: u64) -> Self {
+        Self::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Request body is over {max_size} bytes."),
+        )
+    }
+
+    fn bad_request(err: impl ToString) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, err.to_string())
+    }
+}
+
+fn upload_error_status(err: &UploadError) -> StatusCode {
+    match err {
+        UploadError::NotFound => StatusCode::NOT_FOUND,
+        UploadError::OffsetMismatch { .. } | UploadError::Busy => StatusCode::CONFLICT,
+        UploadError::PastEnd { .. } | UploadError::MissingField { .. } => StatusCode::BAD_REQUEST,
+        UploadError::TooManyUploads { .. } => StatusCode::TOO_MANY_REQUESTS,
+    }
+}
+
+impl<E: Into<PoiseError>> From<E> for ApiError {
+    fn from(err: E) -> Self {
+        let err: PoiseError = err.into();
+        let status = if let Some(err) = err.downcast_ref::<UploadError>() {
+            upload_error_status(err)
+        } else if let Some(AudioError::FileTooLarge { .. }) = err.downcast_ref::<AudioError>() {
+            StatusCode::PAYLOAD_TOO_LARGE
+        } else if err.is::<FeatureError>() {
+            StatusCode::SERVICE_UNAVAILABLE
+        } else if err.is::<AuthError>() {
+            StatusCode::FORBIDDEN
+        } else if err.is::<std::io::Error>()
+            || err.is::<rusqlite::Error>()
+            || err.is::<r2d2::Error>()
+            || err.is::<serenity::Error>()
+        {
+            log::error!("HTTP API request failed - {err}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            // validation errors, ie: naming rules, moderation & taken names
+            StatusCode::UNPROCESSABLE_ENTITY
+        };
+
+        Self::new(status, err.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({ "error": self.message });
+        let mut response = (self.status, Json(body)).into_response();
+        if let Some(seconds) = self.retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(seconds));
+        }
+        response
+    }
+}
+
+/// API key a request authenticated with, from its `Authorization: Bearer <key>` header.
+/// Requests act as the member who created the key, in the key's guild
+#[derive(Debug, Clone)]
+pub struct ApiCaller {
+    pub key_id: i64,
+    pub key_name: String,
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+    pub scopes: Vec<ApiScope>,
+}
+
+impl From<ApiKeyRow> for ApiCaller {
+    fn from(row: ApiKeyRow) -> Self {
+        Self {
+            key_id: row.id,
+            key_name: row.name,
+            guild_id: GuildId::new(row.guild_id),
+            user_id: UserId::new(row.user_id),
+            scopes: row.scopes,
+        }
+    }
+}
+
+impl ApiCaller {
+    fn upload_owner(&self) -> UploadOwner {
+        UploadOwner {
+            key_id: self.key_id,
+            guild_id: self.guild_id,
+            user_id: self.user_id,
+        }
+    }
+}
+
+/// Set by the `authenticate` middleware
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiCaller {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<ApiCaller>()
+            .cloned()
+            .ok_or_else(ApiError::unauthorized)
+    }
+}
+
+/// Middleware finding the API key of every request, and holding it to the rate limit. Requests
+/// with a missing or unknown key are limited by client address instead, so guessing keys is slow.
+/// Behind a reverse proxy, that's the proxy's address
+async fn authenticate(
+    State(state): State<SharedState>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    features::check(&state.data, FeatureFlag::WebApi)?;
+
+    let key = request_key(&request);
+    let table = state.data.api_keys_table();
+    let row = match key {
+        Some(key) => table.find_by_hash(&hash_key(key.trim()))?,
+        None => None,
+    };
+
+    let per_minute = state.data.config.get().api_rate_limit;
+    let Some(row) = row else {
+        // unknown keys are limited by address, so guessing keys is slow
+        let address = format!("address:{}", address.ip());
+        rate_limit(&state, &address, per_minute)?;
+        return Err(ApiError::unauthorized());
+    };
+
+    let now = chrono::Utc::now();
+    let allowed = rate_limit(&state, &format!("key:{}", row.id), per_minute)
+        .and_then(|_| check_quota(&state.data.api_usage_table(), &row, now));
+    let (key_id, guild_id) = (row.id, row.guild_id);
+    // a connection borrowed across the request would keep this future from being Send
+    let record = |outcome| {
+        state
+            .data
+            .api_usage_table()
+            .record(key_id, guild_id, now, outcome)
+            .log_err_msg("Failed recording api usage")
+            .ok();
+    };
+    if let Err(err) = allowed {
+        record(ApiRequestOutcome::Rejected);
+        return Err(err);
+    }
+
+    table.set_last_used(row.id).ok();
+    request.extensions_mut().insert(ApiCaller::from(row));
+
+    let response = next.run(request).await;
+    record(match response.status().is_success() {
+        true => ApiRequestOutcome::Success,
+        false => ApiRequestOutcome::Failure,
+    });
+    Ok(response)
+}
+
+fn rate_limit(state: &ApiState, limited_by: &str, per_minute: u32) -> Result<(), ApiError> {
+    state
+        .limiter
+        .check(limited_by, per_minute, Instant::now())
+        .map_err(|wait| {
+            log::warn!("HTTP API rate limit hit by {limited_by}");
+            ApiError::rate_limited(wait)
+        })
+}
+
+/// Refuse requests of key once it made its daily quota of requests, until the next UTC day
+fn check_quota(
+    usage: &ApiUsageTable,
+    row: &ApiKeyRow,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<(), ApiError> {
+    let Some(quota) = row.daily_quota else {
+        return Ok(());
+    };
+    if usage.requests_on(row.id, now)? < quota {
+        return Ok(());
+    }
+
+    log::warn!("HTTP API daily quota of {quota} hit by key {}", row.id);
+    let wait = (usage_day(now) + chrono::Duration::days(1) - now)
+        .to_std()
+        .unwrap_or_default();
+    Err(ApiError {
+        retry_after: Some(wait.as_secs().max(1)),
+        ..ApiError::new(
+            StatusCode::TOO_MANY_REQUESTS,
+            format!(
+                "API key `{}` used its daily quota of {quota} requests.",
+                row.name
+            ),
+        )
+    })
+}
+
+/// Key of request, from its `Authorization: Bearer <key>` header. Browser sources can't send
+/// headers with EventSource, so playback events also take it from the query string. Other routes
+/// don't, so keys don't end up in access logs & browser history
+fn request_key(request: &Request) -> Option<&str> {
+    let bearer = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match request.uri().path() {
+        EVENTS_PATH => bearer.or_else(|| request.uri().query().and_then(query_key)),
+        _ => bearer,
+    }
+}
+
+/// `key` parameter of query string. Keys are url safe, so aren't decoded
+fn query_key(query: &str) -> Option<&str> {
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("key="))
+        .filter(|key| !key.is_empty())
+}
+
+/// Middleware refusing requests of API keys without the scope
+async fn require_scope(
+    State(scope): State<ApiScope>,
+    caller: ApiCaller,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if !caller.scopes.contains(&scope) {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            format!("API key `{}` lacks the `{scope}` scope.", caller.key_name),
+        ));
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Middleware refusing requests of API keys whose member can't manage sounds anymore, ie: they
+/// were banned, lost the moderator role, or left the guild since creating the key
+async fn require_manage_sounds(
+    State(state): State<SharedState>,
+    caller: ApiCaller,
+    request: Request,
+    next: Next,
+) -> Result<Response, ApiError> {
+    caller_permissions(&state, &caller)
+        .await?
+        .check_manage_sounds()?;
+
+    Ok(next.run(request).await)
+}
+
+/// Soundboard permissions of the member who created the API key, as they are now
+async fn caller_permissions(
+    state: &ApiState,
+    caller: &ApiCaller,
+) -> Result<SoundboardPermissions, ApiError> {
+    let cached = state
+        .cache
+        .guild(caller.guild_id)
+        .and_then(|guild| guild.members.get(&caller.user_id).cloned());
+    let member = match cached {
+        Some(member) => member,
+        // members are only cached once they're seen, ie: in voice
+        None => match state.http.get_member(caller.guild_id, caller.user_id).await {
+            Ok(member) => member,
+            Err(serenity::Error::Http(HttpError::UnsuccessfulRequest(response)))
+                if response.status_code.as_u16() == 404 =>
+            {
+                return Err(ApiError::new(
+                    StatusCode::FORBIDDEN,
+                    format!(
+                        "The creator of API key `{}` isn't a member of the server anymore.",
+                        caller.key_name
+                    ),
+                ));
+            }
+            Err(err) => return Err(err.into()),
+        },
+    };
+
+    // Manage Server is a server wide permission, channel overwrites don't change it
+    #[allow(deprecated)]
+    let manage_guild = state
+        .cache
+        .guild(caller.guild_id)
+        .is_some_and(|guild| guild.member_permissions(&member).manage_guild());
+
+    Ok(state
+        .data
+        .authorizer
+        .permissions(&state.data, caller.guild_id, &member, manage_guild))
+}
+
+#[derive(Debug, Serialize)]
+struct SoundResponse {
+    id: i64,
+    name: String,
+    tags: Vec<String>,
+}
+
+fn remove_temp_file(path: &path::Path) {
+    if path.exists() {
+        std::fs::remove_file(path)
+            .log_err_msg("Failed removing upload temp file")
+            .ok();
+    }
+}
+
+/// Add sound from an uploaded temp file, as the API key's member. The temp file is moved or
+/// removed
+async fn add_sound(
+    state: &ApiState,
+    caller: &ApiCaller,
+    name: &str,
+    tags: String,
+    path: path::PathBuf,
+) -> Result<Response, ApiError> {
+    let prepared = async {
+        let name = collision::resolve_name(&state.data, Some(caller.guild_id), name)?;
+        let author = state.http.get_user(caller.user_id).await?;
+        Ok::<_, PoiseError>((name, author))
+    }
+    .await;
+    let (name, author) = match prepared {
+        Ok(prepared) => prepared,
+        Err(err) => {
+            remove_temp_file(&path);
+            return Err(err.into());
+        }
+    };
+
+    log::info!(
+        "Adding sound `{name}` uploaded with api key {}. guild_id: {}",
+        caller.key_id,
+        caller.guild_id
+    );
+    let target = IngestTarget::New {
+        name: &name,
+        tags: Tags::from(tags),
+        author: &author,
+    };
+    Ingest::new(&state.data)
+        .run(IngestSource::TempFile(path), target)
+        .await?;
+
+    let row = state
+        .data
+        .audio_table()
+        .find_audio_row(UniqueAudioTableCol::Name(name.clone()))
+        .ok_or("Uploaded sound not found")?;
+
+    helpers::send_audit_message(
+        state.http.as_ref(),
+        &state.data,
+        Some(caller.guild_id),
+        CreateMessage::new().content(format!(
+            "Sound `{name}` uploaded by <@{}> with API key `{}`",
+            caller.user_id, caller.key_name
+        )),
+    )
+    .await;
+
+    let sound = SoundResponse {
+        id: row.id,
+        name: row.name,
+        tags: row.tags.to_vec(),
+    };
+    Ok((StatusCode::CREATED, Json(sound)).into_response())
+}
+
+/// `POST /api/sounds` - add sound from a multipart form with `name`, `file` & optional `tags`
+async fn upload_sound(
+    State(state): State<SharedState>,
+    caller: ApiCaller,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Response, ApiError> {
+    let max_size = state.data.config.get().max_audio_file_size;
+    // refuse forms too big to hold an allowed file before reading them
+    let content_length = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if content_length.is_some_and(|length| length > max_size + MAX_FORM_OVERHEAD) {
+        return Err(ApiError::payload_too_large(max_size + MAX_FORM_OVERHEAD));
+    }
+
+    let path = std::env::temp_dir().join(format!("{}.mp3", helpers::uuid_v4_str()));
+
+    let (name, tags) = match read_sound_form(&mut multipart, &path, max_size).await {
+        Ok(form) => form,
+        Err(err) => {
+            remove_temp_file(&path);
+            return Err(err);
+        }
+    };
+
+    add_sound(&state, &caller, &name, tags, path).await
+}
+
+/// Read name & tags of sound form, writing its file to path
+async fn read_sound_form(
+    multipart: &mut Multipart,
+    path: &path::Path,
+    max_size: u64,
+) -> Result<(String, String), ApiError> {
+    let (mut name, mut tags, mut has_file) = (None, String::new(), false);
+
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(ApiError::bad_request)?
+    {
+        match field.name() {
+            Some("name") => name = Some(read_text_field(&mut field).await?),
+            Some("tags") => tags = read_text_field(&mut field).await?,
+            Some("file") => {
+                let mut file = tokio::fs::File::create(path).await?;
+                let mut size = 0;
+                while let Some(chunk) = field.chunk().await.map_err(ApiError::bad_request)? {
+                    size += chunk.len() as u64;
+                    if size > max_size {
+                        return Err(AudioError::FileTooLarge { size, max_size }.into());
+                    }
+                    file.write_all(&chunk).await?;
+                }
+                file.flush().await?;
+                has_file = true;
+            }
+            _ => {}
+        }
+    }
+
+    if !has_file {
+        return Err(UploadError::MissingField { field: "file" }.into());
+    }
+    let name = name.ok_or(UploadError::MissingField { field: "name" })?;
+    Ok((name, tags))
+}
+
+/// Read text field of a form, up to `MAX_FORM_FIELD_SIZE` bytes
+async fn read_text_field(field: &mut Field<'_>) -> Result<String, ApiError> {
+    let mut text = vec![];
+    while let Some(chunk) = field.chunk().await.map_err(ApiError::bad_request)? {
+        if text.len() + chunk.len() > MAX_FORM_FIELD_SIZE {
+            return Err(ApiError::payload_too_large(MAX_FORM_FIELD_SIZE as u64));
+        }
+        text.extend_from_slice(&chunk);
+    }
+    String::from_utf8(text).map_err(ApiError::bad_request)
+}
+
+#[derive(Debug, Deserialize)]
+struct StartUpload {
+    name: String,
+    #[serde(default)]
+    tags: String,
+    /// Bytes of the whole file
+    size: u64,
+}
+
+/// `POST /api/uploads` - start a resumable upload, sent in chunks with `PATCH /api/uploads/{id}`
+async fn start_upload(
+    State(state): State<SharedState>,
+    caller: ApiCaller,
+    Json(upload): Json<StartUpload>,
+) -> Result<Response, ApiError> {
+    let max_size = state.data.config.get().max_audio_file_size;
+    if upload.size == 0 {
+        return Err(ApiError::bad_request(
+            "Upload size must be more than 0 bytes.",
+        ));
+    }
+    if upload.size > max_size {
+        return Err(AudioError::FileTooLarge {
+            size: upload.size,
+            max_size,
+        }
+        .into());
+    }
+    // fail before any data is sent. Checked again once the upload is done
+    collision::resolve_name(&state.data, Some(caller.guild_id), &upload.name)?;
+
+    let status =
+        state
+            .uploads
+            .start(caller.upload_owner(), upload.name, upload.tags, upload.size)?;
+    Ok((StatusCode::CREATED, Json(status)).into_response())
+}
+
+/// `GET /api/uploads/{id}` - offset to resume upload from
+async fn upload_status(
+    State(state): State<SharedState>,
+    caller: ApiCaller,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    let status = state.uploads.status(caller.key_id, &id)?;
+    Ok(Json(status).into_response())
+}
+
+/// `PATCH /api/uploads/{id}` - append chunk at the offset in the `Upload-Offset` header. The
+/// sound is added with the last chunk
+async fn upload_chunk(
+    State(state): State<SharedState>,
+    caller: ApiCaller,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let offset: u64 = headers
+        .get(UPLOAD_OFFSET_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .ok_or_else(|| ApiError::bad_request("Missing Upload-Offset header."))?;
+
+    let chunk = state
+        .uploads
+        .start_chunk(caller.key_id, &id, offset, body.len() as u64)?;
+    let written = write_chunk(&chunk.path, offset, &body).await;
+    let finished = chunk.finish(match written {
+        Ok(_) => body.len() as u64,
+        Err(_) => 0,
+    });
+    written?;
+
+    match finished {
+        Some(session) => {
+            log::info!("Finished upload {id} of `{}`", session.name);
+            add_sound(&state, &caller, &session.name, session.tags, session.path).await
+        }
+        None => Ok(Json(state.uploads.status(caller.key_id, &id)?).into_response()),
+    }
+}
+
+/// Write chunk at offset, dropping anything past it from an earlier failed write
+async fn write_chunk(path: &path::Path, offset: u64, chunk: &[u8]) -> std::io::Result<()> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)
+        .await?;
+    file.set_len(offset).await?;
+    file.seek(std::io::SeekFrom::Start(offset)).await?;
+    file.write_all(chunk).await?;
+    file.flush().await
+}
+
+/// `DELETE /api/uploads/{id}` - drop an unfinished upload
+async fn cancel_upload(
+    State(state): State<SharedState>,
+    caller: ApiCaller,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    match state.uploads.cancel(caller.key_id, &id) {
+        true => Ok(StatusCode::NO_CONTENT),
+        false => Err(UploadError::NotFound.into()),
+    }
+}
+
+/// `GET /api/events` - playback events of the key's guild, as server-sent events named by their
+/// `type`, ie: `play_start`. Events come with the requester's display name, for stream overlays
+async fn playback_events(
+    State(state): State<SharedState>,
+    caller: ApiCaller,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    log::info!("Streaming playback events to api key {}", caller.key_id);
+    let receiver = state.data.playback.subscribe();
+    let names: HashMap<UserId, Option<String>> = HashMap::new();
+
+    let stream = futures::stream::unfold(
+        (receiver, names, state, caller.guild_id),
+        |(mut receiver, mut names, state, guild_id)| async move {
+            let event = loop {
+                match receiver.recv().await {
+                    Ok(event) if event.guild_id() == guild_id => break event,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(skipped)) => {
+                        log::warn!("Playback event stream skipped {skipped} events");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            };
+
+            let data = event_json(&state, &mut names, &event).await;
+            let sse = Event::default().event(event.kind()).data(data.to_string());
+            Some((Ok(sse), (receiver, names, state, guild_id)))
+        },
+    );
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Event as json, with `requested_by_name` added to tracks played by a member. Names are
+/// looked up once per stream
+async fn event_json(
+    state: &ApiState,
+    names: &mut HashMap<UserId, Option<String>>,
+    event: &PlaybackEvent,
+) -> serde_json::Value {
+    let mut json = serde_json::to_value(event).unwrap_or_default();
+    let (PlaybackEvent::PlayStart {
+        guild_id,
+        requested_by: Some(user_id),
+        ..
+    }
+    | PlaybackEvent::PlayEnd {
+        guild_id,
+        requested_by: Some(user_id),
+        ..
+    }) = event
+    else {
+        return json;
+    };
+
+    if !names.contains_key(user_id) {
+        let name = state
+            .http
+            .get_member(*guild_id, *user_id)
+            .await
+            .log_err_msg("Failed getting requester of played track")
+            .ok()
+            .map(|member| member.display_name().to_string());
+        names.insert(*user_id, name);
+    }
+    json["requested_by_name"] = names[user_id].clone().into();
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::{ModerationError, NamingError};
+
+    #[test]
+    fn api_key_test() {
+        let key = new_key();
+        assert!(key.starts_with("sb_"));
+        assert_eq!(key.len(), 35);
+        assert_ne!(new_key(), key);
+
+        let hash = hash_key(&key);
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, hash_key(&key));
+        assert_eq!(
+            hash_key("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[tokio::test]
+    async fn write_chunk_test() {
+        let path = std::env::temp_dir().join(format!("{}.mp3", helpers::uuid_v4_str()));
+        write_chunk(&path, 0, b"abcd").await.unwrap();
+        write_chunk(&path, 4, b"efg").await.unwrap();
+        // chunk resent after a dropped connection replaces what was written past its offset
+        write_chunk(&path, 4, b"EF").await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"abcdEF");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn query_key_test() {
+        assert_eq!(query_key("key=sb_abc"), Some("sb_abc"));
+        assert_eq!(query_key("theme=dark&key=sb_abc"), Some("sb_abc"));
+        assert_eq!(query_key("key="), None);
+        assert_eq!(query_key("monkey=1"), None);
+
+        let request = |uri: &str| {
+            Request::builder()
+                .uri(uri)
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+        assert_eq!(
+            request_key(&request("/api/events?key=sb_abc")),
+            Some("sb_abc")
+        );
+        assert_eq!(request_key(&request("/api/uploads?key=sb_abc")), None);
+        let mut bearer = request("/api/uploads");
+        bearer.headers_mut().insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer sb_abc"),
+        );
+        assert_eq!(request_key(&bearer), Some("sb_abc"));
+    }
+
+    #[test]
+    fn rate_limited_response_test() {
+        let response = ApiError::rate_limited(Duration::from_millis(1500)).into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+    }
+
+    #[test]
+    fn api_error_status_test() {
+        let status = |err: PoiseError| ApiError::from(err).status;
+        assert_eq!(
+            status(UploadError::OffsetMismatch { offset: 1, sent: 0 }.into()),
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            status(
+                AudioError::FileTooLarge {
+                    size: 2,
+                    max_size: 1
+                }
+                .into()
+            ),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+        assert_eq!(
+            status(NamingError::Empty.into()),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            status(AuthError::NotModerator.into()),
+            StatusCode::FORBIDDEN
+        );
+        assert_eq!(
+            status(ModerationError::Unavailable.into()),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+        assert_eq!(
+            status(std::io::Error::other("disk full").into()),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
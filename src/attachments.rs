@@ -0,0 +1,162 @@
+use std::time::Duration;
+
+use crate::export::ExportFile;
+
+/// Most attachments discord allows on one message
+pub const MESSAGE_ATTACHMENTS_LIMIT: usize = 10;
+
+/// Lowest bitrate previews are compressed to. Lower than this, sounds aren't worth listening to
+const MIN_PREVIEW_BITRATE_KBPS: u32 = 16;
+
+/// Highest bitrate previews are compressed to, about the quality sounds are stored at
+const MAX_PREVIEW_BITRATE_KBPS: u32 = 128;
+
+/// Share of an upload kept free for mp3 headers & frame overhead
+const PREVIEW_SIZE_HEADROOM: f64 = 0.9;
+
+/// Bitrate previews lasting `duration` altogether can be encoded at to fit in `max_size` bytes.
+/// None when they're too long to fit even at the lowest bitrate
+pub fn preview_bitrate_kbps(duration: Duration, max_size: u64) -> Option<u32> {
+    let secs = duration.as_secs_f64().max(1.0);
+    let kbps = (max_size as f64 * 8.0 * PREVIEW_SIZE_HEADROOM / secs / 1000.0).floor() as u32;
+
+    match kbps >= MIN_PREVIEW_BITRATE_KBPS {
+        true => Some(kbps.min(MAX_PREVIEW_BITRATE_KBPS)),
+        false => None,
+    }
+}
+
+/// Group files into messages whose attachments fit in `max_size` bytes altogether. Files larger
+/// than `max_size` are split at line ends into parts, ie: `plays.part1.csv`, which join back into
+/// the file in order
+pub fn split_into_messages(files: Vec<ExportFile>, max_size: u64) -> Vec<Vec<ExportFile>> {
+    let mut messages: Vec<Vec<ExportFile>> = vec![];
+    let mut size = 0;
+
+    for file in files
+        .into_iter()
+        .flat_map(|file| split_file(file, max_size))
+    {
+        let full = match messages.last() {
+            Some(message) => {
+                message.len() >= MESSAGE_ATTACHMENTS_LIMIT
+                    || size + file.content.len() as u64 > max_size
+            }
+            None => true,
+        };
+        if full {
+            messages.push(vec![]);
+            size = 0;
+        }
+
+        size += file.content.len() as u64;
+        messages.last_mut().unwrap().push(file);
+    }
+
+    messages
+}
+
+fn split_file(file: ExportFile, max_size: u64) -> Vec<ExportFile> {
+    let max_size = max_size.max(1) as usize;
+    if file.content.len() <= max_size {
+        return vec![file];
+    }
+
+    let (stem, extension) = match file.file_name.rsplit_once('.') {
+        Some((stem, extension)) => (stem.to_string(), format!(".{extension}")),
+        None => (file.file_name.clone(), String::new()),
+    };
+
+    let mut parts = vec![];
+    let mut rest = file.content.as_slice();
+    while !rest.is_empty() {
+        let mut end = rest.len().min(max_size);
+        // cut after the last full line, unless a line alone is too long
+        if end < rest.len() {
+            if let Some(newline) = rest[..end].iter().rposition(|byte| *byte == b'\n') {
+                end = newline + 1;
+            }
+        }
+
+        let (part, remaining) = rest.split_at(end);
+        parts.push(ExportFile {
+            file_name: format!("{stem}.part{}{extension}", parts.len() + 1),
+            content: part.to_vec(),
+        });
+        rest = remaining;
+    }
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_export_file(file_name: &str, content: &str) -> ExportFile {
+        ExportFile {
+            file_name: file_name.into(),
+            content: content.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn preview_bitrate_test() {
+        // 60s in 1MB fits ~120kbps
+        assert_eq!(
+            preview_bitrate_kbps(Duration::from_secs(60), 1_000_000),
+            Some(120)
+        );
+        assert_eq!(
+            preview_bitrate_kbps(Duration::from_secs(1), 1_000_000),
+            Some(MAX_PREVIEW_BITRATE_KBPS)
+        );
+        assert_eq!(
+            preview_bitrate_kbps(Duration::from_secs(3600), 1_000_000),
+            None
+        );
+    }
+
+    #[test]
+    fn split_into_messages_test() {
+        let files = vec![
+            make_export_file("a.csv", "id\n1\n2\n"),
+            make_export_file("plays.csv", "id,user\n1,2\n3,4\n5,6\n"),
+            make_export_file("b.json", "{}"),
+        ];
+
+        let messages = split_into_messages(files, 10);
+        let names: Vec<Vec<_>> = messages
+            .iter()
+            .map(|message| message.iter().map(|file| file.file_name.as_str()).collect())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                vec!["a.csv"],
+                vec!["plays.part1.csv"],
+                vec!["plays.part2.csv"],
+                vec!["plays.part3.csv", "b.json"],
+            ]
+        );
+
+        // parts are split at line ends, and join back into the file
+        let parts: Vec<u8> = messages[1..]
+            .iter()
+            .flatten()
+            .filter(|file| file.file_name.starts_with("plays"))
+            .flat_map(|file| file.content.clone())
+            .collect();
+        assert_eq!(parts, b"id,user\n1,2\n3,4\n5,6\n");
+        assert_eq!(messages[1][0].content, b"id,user\n");
+
+        let messages = split_into_messages(
+            (0..12)
+                .map(|index| make_export_file(&format!("{index}.csv"), "1"))
+                .collect(),
+            100,
+        );
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].len(), MESSAGE_ATTACHMENTS_LIMIT);
+    }
+}
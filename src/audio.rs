@@ -1,24 +1,33 @@
 use std::ffi::OsStr;
 use std::fs;
-use std::io::Write;
+use std::io::{Read, Write};
 use std::ops::Deref;
 use std::path;
+use std::sync::Arc;
 
 use futures::StreamExt;
 use rusqlite::types::FromSql;
 use rusqlite::ToSql;
+use serenity::all::UserId;
 use serenity::async_trait;
+use sha2::{Digest, Sha256};
 
-use songbird::tracks::{PlayMode, TrackHandle};
+use songbird::input::Input;
+use songbird::tracks::{PlayMode, Track, TrackHandle};
+use songbird::Call;
+use tokio_util::sync::CancellationToken;
 
+use symphonia::core::audio::SampleBuffer;
 use symphonia::core::codecs;
-use symphonia::core::formats::FormatOptions;
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
 use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
 
 use crate::commands::PoiseError;
 use crate::common::LogResult;
+use crate::errors::{AudioError, OperationError};
 use crate::helpers::{self};
 
 pub async fn wait_for_audio_track_end(track_handle: &TrackHandle) {
@@ -36,9 +45,27 @@ pub async fn wait_for_audio_track_end(track_handle: &TrackHandle) {
     }
 }
 
+/// Data attached to every track the bot plays
+pub struct TrackMeta {
+    /// Member who asked for the track, None for sounds nobody asked for, ie: guild events
+    pub requested_by: Option<UserId>,
+}
+
+/// Start playing input in call, attributed to the member who asked for it. Every track is started
+/// here, so every track carries [`TrackMeta`]
+pub fn play_track(call: &mut Call, input: Input, requested_by: Option<UserId>) -> TrackHandle {
+    call.play(Track::new_with_data(
+        input,
+        Arc::new(TrackMeta { requested_by }),
+    ))
+}
+
 #[async_trait]
 pub trait TrackHandleHelper {
     async fn wait_for_end(&self);
+
+    /// Member who asked for track, if a member did
+    fn requested_by(&self) -> Option<UserId>;
 }
 
 #[async_trait]
@@ -46,6 +73,10 @@ impl TrackHandleHelper for TrackHandle {
     async fn wait_for_end(&self) {
         wait_for_audio_track_end(&self).await;
     }
+
+    fn requested_by(&self) -> Option<UserId> {
+        self.data::<TrackMeta>().requested_by
+    }
 }
 
 pub struct AudioDir(path::PathBuf);
@@ -73,8 +104,10 @@ impl IntoIterator for AudioDir {
     }
 }
 
+#[derive(Clone)]
 pub struct AudioFileValidator {
     max_dur: std::time::Duration,
+    max_size: u64,
     reject_uuid_files: bool,
 }
 
@@ -82,6 +115,7 @@ impl Default for AudioFileValidator {
     fn default() -> Self {
         Self {
             max_dur: crate::config::default_max_audio_file_duration(),
+            max_size: crate::config::default_max_audio_file_size(),
             reject_uuid_files: true,
         }
     }
@@ -97,12 +131,18 @@ impl AudioFileValidator {
         self
     }
 
+    pub fn max_file_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
     pub fn reject_uuid_files(mut self, reject: bool) -> Self {
         self.reject_uuid_files = reject;
         self
     }
 
-    pub fn validate(&self, path: impl AsRef<path::Path>) -> Result<(), PoiseError> {
+    /// Check file exists, is within the size limit, and isn't named like a sound added via command
+    pub fn check_file(&self, path: impl AsRef<path::Path>) -> Result<(), PoiseError> {
         let path = path.as_ref();
         log::info!("Validating audio file: {}", path.to_string_lossy());
 
@@ -114,6 +154,16 @@ impl AudioFileValidator {
             return Err("Audio file path isn't a file".into()).log_err();
         }
 
+        let size = fs::metadata(path).log_err()?.len();
+        if size > self.max_size {
+            return Err(AudioError::FileTooLarge {
+                size,
+                max_size: self.max_size,
+            }
+            .into())
+            .log_err();
+        }
+
         if self.reject_uuid_files {
             let stem = path.file_stem().ok_or("File missing stem")?;
             let stem = stem.to_string_lossy();
@@ -125,7 +175,12 @@ impl AudioFileValidator {
             }
         }
 
-        let track_info = probe_audio_track(&path).log_err()?;
+        Ok(())
+    }
+
+    /// Probe audio track, checking its codec and that it's within the duration limit
+    pub fn check_track(&self, path: impl AsRef<path::Path>) -> Result<AudioTrackInfo, PoiseError> {
+        let track_info = probe_audio_track(path.as_ref()).log_err()?;
         let track_dur = &track_info.duration;
 
         if track_dur > &self.max_dur {
@@ -134,7 +189,7 @@ impl AudioFileValidator {
             return Err(format!("Audio track is {track_dur:.2}s long. This exceeds the max duration of {max_dur:.2}s").into()).log_err();
         }
 
-        Ok(())
+        Ok(track_info)
     }
 }
 
@@ -154,7 +209,7 @@ impl std::iter::Iterator for AudioDirIter {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct AudioFile(path::PathBuf);
 
 impl AudioFile {
@@ -184,11 +239,79 @@ impl AudioFile {
             .into()
     }
 
+    /// get file name with file extension
+    pub fn file_name_lossy(&self) -> String {
+        self.0
+            .file_name()
+            .unwrap_or(OsStr::new(""))
+            .to_string_lossy()
+            .into()
+    }
+
     pub fn audio_title(&self) -> String {
         let stem = self.file_stem();
         let stem = stem.replace("_", " ").replace("-", " ");
         helpers::title_case(stem)
     }
+
+    /// get file size and modified time, without reading the file
+    pub fn stat(&self) -> Result<AudioFileStat, PoiseError> {
+        let metadata = fs::metadata(self.as_path()).log_err_msg(format!(
+            "Failed to read metadata of audio file {}",
+            self.0.to_string_lossy()
+        ))?;
+
+        Ok(AudioFileStat {
+            size: metadata.len(),
+            modified_at: metadata.modified()?.into(),
+        })
+    }
+
+    /// get file stat and content hash. Reads the entire file
+    pub fn info(&self) -> Result<AudioFileInfo, PoiseError> {
+        Ok(AudioFileInfo {
+            stat: self.stat()?,
+            hash: hash_file(self.as_path())?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioFileStat {
+    pub size: u64,
+    pub modified_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioFileInfo {
+    pub stat: AudioFileStat,
+    /// hex encoded sha256 of file contents
+    pub hash: String,
+}
+
+/// sha256 of file contents as lowercase hex string
+pub fn hash_file(path: impl AsRef<path::Path>) -> Result<String, PoiseError> {
+    let path = path.as_ref();
+    let mut file = fs::File::open(path).log_err_msg(format!(
+        "Failed to open file for hashing {}",
+        path.to_string_lossy()
+    ))?;
+
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
 }
 
 impl Deref for AudioFile {
@@ -226,18 +349,6 @@ impl ToSql for AudioFile {
     }
 }
 
-pub trait RemoveAudioFile {
-    fn remove_audio_file(&mut self, audio_file: &AudioFile);
-}
-
-impl RemoveAudioFile for Vec<AudioFile> {
-    fn remove_audio_file(&mut self, audio_file: &AudioFile) {
-        if let Some(index) = self.iter().position(|f| f == audio_file) {
-            self.remove(index);
-        }
-    }
-}
-
 pub struct AudioTrackInfo {
     pub duration: std::time::Duration,
 }
@@ -247,23 +358,7 @@ pub fn probe_audio_track(audio_file: impl AsRef<path::Path>) -> Result<AudioTrac
 
     log::info!("Probing audio-track: {}", path.to_string_lossy());
 
-    let file: fs::File = std::fs::File::open(path).log_err()?;
-    let mss = MediaSourceStream::new(Box::new(file), Default::default());
-    let mut hint = Hint::default();
-    hint.with_extension("mp3");
-
-    // Use the default probe to identify the format
-    let probed = symphonia::default::get_probe()
-        .format(
-            &hint,
-            mss,
-            &FormatOptions::default(),
-            &MetadataOptions::default(),
-        )
-        .log_err_msg("Failed to probe format")?;
-
-    // Get the format reader
-    let format = probed.format;
+    let format = open_audio_format(path)?;
 
     // Get the default track
     let track = format
@@ -299,8 +394,87 @@ pub fn probe_audio_track(audio_file: impl AsRef<path::Path>) -> Result<AudioTrac
     })
 }
 
-/// download audio url to temp dir (audio file is uuid4 name)
-pub async fn download_audio_url_temp(url: impl AsRef<str>) -> Result<path::PathBuf, PoiseError> {
+/// Format reader of mp3 file
+fn open_audio_format(path: &path::Path) -> Result<Box<dyn FormatReader>, PoiseError> {
+    let file: fs::File = std::fs::File::open(path).log_err()?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::default();
+    hint.with_extension("mp3");
+
+    // Use the default probe to identify the format
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .log_err_msg("Failed to probe format")?;
+
+    Ok(probed.format)
+}
+
+/// Decode audio file to mono samples. Returns samples and their sample rate
+pub fn decode_audio_mono(
+    audio_file: impl AsRef<path::Path>,
+) -> Result<(Vec<f32>, u32), PoiseError> {
+    let mut format = open_audio_format(audio_file.as_ref())?;
+    let track = format
+        .default_track()
+        .ok_or("No audio track found")
+        .log_err()?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .ok_or("Couldn't find audio track sample rate")?;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &codecs::DecoderOptions::default())
+        .log_err_msg("Failed to create audio decoder")?;
+
+    let mut samples = vec![];
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(err))
+                if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+            {
+                break
+            }
+            Err(err) => return Err(err.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        // corrupt frames are skipped, like players do
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend(
+            buffer
+                .samples()
+                .chunks_exact(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+    }
+
+    Ok((samples, sample_rate))
+}
+
+/// download audio url to temp dir (audio file is uuid4 name).
+/// Download is streamed to disk, and aborted once it exceeds `max_size` bytes or is cancelled
+pub async fn download_audio_url_temp(
+    url: impl AsRef<str>,
+    max_size: u64,
+    cancel: &CancellationToken,
+) -> Result<path::PathBuf, PoiseError> {
     let url = url.as_ref();
     log::info!("Downloading audio url - {url}");
 
@@ -328,6 +502,13 @@ pub async fn download_audio_url_temp(url: impl AsRef<str>) -> Result<path::PathB
         }
     }
 
+    // reject early when server reports size. Size is still checked while streaming, since header can be absent or wrong
+    if let Some(size) = response.content_length() {
+        if size > max_size {
+            return Err(AudioError::FileTooLarge { size, max_size }.into()).log_err();
+        }
+    }
+
     let uuid = helpers::uuid_v4_str();
     let file_name = format!("{uuid}.mp3");
     let audio_file_path = std::env::temp_dir().join(file_name.as_str());
@@ -340,20 +521,214 @@ pub async fn download_audio_url_temp(url: impl AsRef<str>) -> Result<path::PathB
         .await
         .log_err_msg("Failed HTTP GET on url")?;
 
+    let mut downloaded: u64 = 0;
     let mut stream = response.bytes_stream();
     while let Some(item) = stream.next().await {
-        let chunk = item
-            .or(Err(format!("Error while downloading file")))
-            .log_err()?;
+        if cancel.is_cancelled() {
+            remove_partial_download(&audio_file_path);
+            return Err(OperationError::Cancelled.into());
+        }
 
-        file.write_all(&chunk)
-            .or(Err(format!("Error while writing to file")))
-            .log_err()?;
+        let chunk = match item {
+            Ok(chunk) => chunk,
+            Err(_) => {
+                remove_partial_download(&audio_file_path);
+                return Err("Error while downloading file".into()).log_err();
+            }
+        };
+
+        downloaded += chunk.len() as u64;
+        if downloaded > max_size {
+            remove_partial_download(&audio_file_path);
+            return Err(AudioError::FileTooLarge {
+                size: downloaded,
+                max_size,
+            }
+            .into())
+            .log_err();
+        }
+
+        if file.write_all(&chunk).is_err() {
+            remove_partial_download(&audio_file_path);
+            return Err("Error while writing to file".into()).log_err();
+        }
     }
 
     Ok(audio_file_path)
 }
 
+/// ffmpeg arguments joining two audio files into an mp3. Crossfades them when `crossfade` is set
+pub fn merge_ffmpeg_args(
+    first: &path::Path,
+    second: &path::Path,
+    output: &path::Path,
+    crossfade: Option<std::time::Duration>,
+) -> Vec<std::ffi::OsString> {
+    let filter = match crossfade {
+        Some(crossfade) => format!("[0:a][1:a]acrossfade=d={:.3}[out]", crossfade.as_secs_f64()),
+        None => "[0:a][1:a]concat=n=2:v=0:a=1[out]".to_string(),
+    };
+
+    [
+        OsStr::new("-hide_banner"),
+        OsStr::new("-loglevel"),
+        OsStr::new("error"),
+        OsStr::new("-i"),
+        first.as_os_str(),
+        OsStr::new("-i"),
+        second.as_os_str(),
+        OsStr::new("-filter_complex"),
+        OsStr::new(&filter),
+        OsStr::new("-map"),
+        OsStr::new("[out]"),
+        OsStr::new("-codec:a"),
+        OsStr::new("libmp3lame"),
+        OsStr::new("-q:a"),
+        OsStr::new("2"),
+        output.as_os_str(),
+    ]
+    .into_iter()
+    .map(|arg| arg.to_os_string())
+    .collect()
+}
+
+/// ffmpeg arguments cutting audio file down to the part between `start` and `end` (or end of file)
+pub fn trim_ffmpeg_args(
+    input: &path::Path,
+    output: &path::Path,
+    start: std::time::Duration,
+    end: Option<std::time::Duration>,
+) -> Vec<std::ffi::OsString> {
+    let mut args: Vec<std::ffi::OsString> = vec![
+        "-hide_banner".into(),
+        "-loglevel".into(),
+        "error".into(),
+        "-i".into(),
+        input.into(),
+        "-ss".into(),
+        format!("{:.3}", start.as_secs_f64()).into(),
+    ];
+    if let Some(end) = end {
+        args.extend(["-to".into(), format!("{:.3}", end.as_secs_f64()).into()]);
+    }
+    args.extend([
+        "-codec:a".into(),
+        "libmp3lame".into(),
+        "-q:a".into(),
+        "2".into(),
+        output.into(),
+    ]);
+
+    args
+}
+
+/// Join two audio files with ffmpeg, into an mp3 in temp dir (audio file is uuid4 name)
+pub async fn merge_audio_files_temp(
+    ffmpeg_path: &path::Path,
+    first: &path::Path,
+    second: &path::Path,
+    crossfade: Option<std::time::Duration>,
+) -> Result<path::PathBuf, PoiseError> {
+    log::info!(
+        "Merging audio files {} and {}",
+        first.to_string_lossy(),
+        second.to_string_lossy(),
+    );
+
+    run_ffmpeg_temp(ffmpeg_path, |output| {
+        merge_ffmpeg_args(first, second, output, crossfade)
+    })
+    .await
+}
+
+/// Trim audio file with ffmpeg, into an mp3 in temp dir (audio file is uuid4 name)
+pub async fn trim_audio_file_temp(
+    ffmpeg_path: &path::Path,
+    input: &path::Path,
+    start: std::time::Duration,
+    end: Option<std::time::Duration>,
+) -> Result<path::PathBuf, PoiseError> {
+    log::info!(
+        "Trimming audio file {}. start: {start:?}, end: {end:?}",
+        input.to_string_lossy()
+    );
+
+    run_ffmpeg_temp(ffmpeg_path, |output| {
+        trim_ffmpeg_args(input, output, start, end)
+    })
+    .await
+}
+
+/// ffmpeg arguments re-encoding audio file as a mono mp3 at a bitrate, ie: a smaller preview
+pub fn compress_ffmpeg_args(
+    input: &path::Path,
+    output: &path::Path,
+    bitrate_kbps: u32,
+) -> Vec<std::ffi::OsString> {
+    vec![
+        "-hide_banner".into(),
+        "-loglevel".into(),
+        "error".into(),
+        "-i".into(),
+        input.into(),
+        "-ac".into(),
+        "1".into(),
+        "-codec:a".into(),
+        "libmp3lame".into(),
+        "-b:a".into(),
+        format!("{bitrate_kbps}k").into(),
+        output.into(),
+    ]
+}
+
+/// Re-encode audio file with ffmpeg at a lower bitrate, into an mp3 in temp dir (audio file is uuid4 name)
+pub async fn compress_audio_file_temp(
+    ffmpeg_path: &path::Path,
+    input: &path::Path,
+    bitrate_kbps: u32,
+) -> Result<path::PathBuf, PoiseError> {
+    log::info!(
+        "Compressing audio file {} to {bitrate_kbps}kbps",
+        input.to_string_lossy()
+    );
+
+    run_ffmpeg_temp(ffmpeg_path, |output| {
+        compress_ffmpeg_args(input, output, bitrate_kbps)
+    })
+    .await
+}
+
+/// Run ffmpeg with arguments for given output file in temp dir. Returns output file
+async fn run_ffmpeg_temp(
+    ffmpeg_path: &path::Path,
+    args: impl FnOnce(&path::Path) -> Vec<std::ffi::OsString>,
+) -> Result<path::PathBuf, PoiseError> {
+    let output = std::env::temp_dir().join(format!("{}.mp3", helpers::uuid_v4_str()));
+
+    let result = tokio::process::Command::new(ffmpeg_path)
+        .args(args(&output))
+        .output()
+        .await
+        .log_err_msg("Failed running ffmpeg")?;
+
+    if !result.status.success() {
+        remove_partial_download(&output);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        return Err(format!("ffmpeg failed - {}", stderr.trim()).into()).log_err();
+    }
+
+    Ok(output)
+}
+
+fn remove_partial_download(path: &path::Path) {
+    std::fs::remove_file(path)
+        .log_err_msg(format!(
+            "Failed to remove partial download {}",
+            path.to_string_lossy()
+        ))
+        .ok();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -363,4 +738,71 @@ mod tests {
         let f = AudioFile::new(path::PathBuf::from("/tmp/once-Upon a_time.mp3"));
         assert_eq!("Once Upon A Time", f.audio_title());
     }
+
+    #[test]
+    fn hash_file_test() {
+        let file_path = std::env::temp_dir().join(format!("{}.mp3", helpers::uuid_v4_str()));
+        std::fs::write(&file_path, b"abc").unwrap();
+
+        let hash = hash_file(&file_path).unwrap();
+        assert_eq!(
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            hash
+        );
+
+        std::fs::remove_file(&file_path).unwrap();
+    }
+
+    #[test]
+    fn merge_ffmpeg_args_test() {
+        let (first, second, output) = (
+            path::Path::new("/tmp/a.mp3"),
+            path::Path::new("/tmp/b.mp3"),
+            path::Path::new("/tmp/out.mp3"),
+        );
+
+        let args = merge_ffmpeg_args(first, second, output, None);
+        assert!(args.contains(&"[0:a][1:a]concat=n=2:v=0:a=1[out]".into()));
+        assert_eq!(args.last(), Some(&"/tmp/out.mp3".into()));
+
+        let args = merge_ffmpeg_args(
+            first,
+            second,
+            output,
+            Some(std::time::Duration::from_millis(250)),
+        );
+        assert!(args.contains(&"[0:a][1:a]acrossfade=d=0.250[out]".into()));
+    }
+
+    #[test]
+    fn trim_ffmpeg_args_test() {
+        let (input, output) = (
+            path::Path::new("/tmp/a.mp3"),
+            path::Path::new("/tmp/out.mp3"),
+        );
+
+        let args = trim_ffmpeg_args(input, output, std::time::Duration::from_millis(1500), None);
+        assert!(args.windows(2).any(|pair| pair == ["-ss", "1.500"]));
+        assert!(!args.contains(&"-to".into()));
+
+        let args = trim_ffmpeg_args(
+            input,
+            output,
+            std::time::Duration::ZERO,
+            Some(std::time::Duration::from_secs(2)),
+        );
+        assert!(args.windows(2).any(|pair| pair == ["-to", "2.000"]));
+        assert_eq!(args.last(), Some(&"/tmp/out.mp3".into()));
+    }
+
+    #[test]
+    fn compress_ffmpeg_args_test() {
+        let args = compress_ffmpeg_args(
+            path::Path::new("/tmp/a.mp3"),
+            path::Path::new("/tmp/out.mp3"),
+            48,
+        );
+        assert!(args.windows(2).any(|pair| pair == ["-b:a", "48k"]));
+        assert_eq!(args.last(), Some(&"/tmp/out.mp3".into()));
+    }
 }
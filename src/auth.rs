@@ -0,0 +1,634 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serenity::all::{ChannelId, GuildId, Member, Permissions, RoleId, UserId};
+use serenity::async_trait;
+
+use crate::commands::{PoiseContext, PoiseError};
+use crate::common::{LogResult, UserData};
+use crate::db::{
+    AudioTableRow, DbPool, PlayHistoryTable, SettingsTable, SoundRestrictionRow,
+    SoundRestrictionsTable,
+};
+use crate::errors::{AudioError, AuthError};
+use crate::settings::GuildSettings;
+use crate::{event_mode, quiet_hours, settings};
+
+/// A member's effective soundboard permissions, computed from guild settings,
+/// the member's roles, bans, and plays in the last 24 hours.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SoundboardPermissions {
+    /// Ban reason, when banned
+    pub ban: Option<String>,
+    pub is_moderator: bool,
+    /// Member can manage the guild, so may override protected sounds
+    pub is_admin: bool,
+    /// Role required to play, when member doesn't have it
+    pub missing_user_role: Option<RoleId>,
+    /// Plays left today. None when unlimited
+    pub plays_remaining: Option<u32>,
+    pub daily_play_quota: Option<u32>,
+    /// Moderator role is configured. Without one, anyone can manage sounds
+    pub moderator_role_set: bool,
+    /// Member has the priority role, so their sounds are queued ahead of regular sounds
+    pub priority_queue: bool,
+    /// Unix time guild's quiet hours end, while they refuse playback
+    pub quiet_hours_end: Option<i64>,
+    /// Roles of member, for sounds restricted to roles
+    pub member_roles: Vec<RoleId>,
+}
+
+impl SoundboardPermissions {
+    pub fn compute(
+        member_roles: &[RoleId],
+        manage_guild: bool,
+        ban: Option<String>,
+        user_role: Option<RoleId>,
+        moderator_role: Option<RoleId>,
+        daily_play_quota: Option<u32>,
+        plays_today: u32,
+    ) -> Self {
+        let is_moderator =
+            manage_guild || moderator_role.is_some_and(|role| member_roles.contains(&role));
+        let missing_user_role =
+            user_role.filter(|role| !is_moderator && !member_roles.contains(role));
+
+        Self {
+            ban,
+            is_moderator,
+            is_admin: manage_guild,
+            missing_user_role,
+            plays_remaining: daily_play_quota.map(|quota| quota.saturating_sub(plays_today)),
+            daily_play_quota,
+            moderator_role_set: moderator_role.is_some(),
+            priority_queue: false,
+            quiet_hours_end: None,
+            member_roles: member_roles.to_vec(),
+        }
+    }
+
+    pub fn check_banned(&self) -> Result<(), AuthError> {
+        match &self.ban {
+            Some(reason) => Err(AuthError::Banned {
+                reason: reason.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn check_play(&self) -> Result<(), AuthError> {
+        self.check_banned()?;
+
+        if let Some(role) = self.missing_user_role {
+            return Err(AuthError::MissingUserRole {
+                role_id: role.get(),
+            });
+        }
+
+        // moderators aren't held to quiet hours or quotas
+        if let Some(ends_at) = self.quiet_hours_end.filter(|_| !self.is_moderator) {
+            return Err(AuthError::QuietHours { ends_at });
+        }
+
+        match (self.plays_remaining, self.daily_play_quota) {
+            (Some(0), Some(quota)) if !self.is_moderator => Err(AuthError::QuotaExceeded { quota }),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_manage_sounds(&self) -> Result<(), AuthError> {
+        self.check_banned()?;
+
+        match self.is_moderator || !self.moderator_role_set {
+            true => Ok(()),
+            false => Err(AuthError::NotModerator),
+        }
+    }
+
+    pub fn check_moderator(&self) -> Result<(), AuthError> {
+        match self.is_moderator {
+            true => Ok(()),
+            false => Err(AuthError::NotModerator),
+        }
+    }
+
+    pub fn check_manage_guild(&self) -> Result<(), AuthError> {
+        match self.is_admin {
+            true => Ok(()),
+            false => Err(AuthError::MissingManageGuild),
+        }
+    }
+
+    /// Protected sounds can only be deleted, renamed, or bulk edited by admins forcing it
+    pub fn check_unprotected(
+        &self,
+        name: &str,
+        protected: bool,
+        force: bool,
+    ) -> Result<(), AuthError> {
+        match (protected, force) {
+            (false, _) => Ok(()),
+            (true, true) if self.is_admin => Ok(()),
+            (true, true) => Err(AuthError::NotAdmin),
+            (true, false) => Err(AuthError::SoundProtected { name: name.into() }),
+        }
+    }
+}
+
+/// When sound played last at `last_played_at` can be played again, while its cooldown lasts
+pub fn cooldown_ready_at(
+    cooldown: Option<u32>,
+    last_played_at: Option<chrono::DateTime<chrono::Utc>>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    let cooldown = cooldown.filter(|cooldown| *cooldown > 0)?;
+    let ready_at = last_played_at? + chrono::Duration::seconds(cooldown as i64);
+    (ready_at > now).then_some(ready_at)
+}
+
+/// Sound isn't on cooldown in guild. Checked alongside member's quota on every play, except for
+/// moderators, who aren't held to cooldowns. Cooldowns are lifted in event mode
+pub fn check_sound_cooldown(
+    settings_table: &SettingsTable,
+    table: &PlayHistoryTable,
+    guild_id: GuildId,
+    row: &AudioTableRow,
+) -> Result<(), AudioError> {
+    if row.cooldown.is_none() || event_mode::is_active(settings_table, guild_id) {
+        return Ok(());
+    }
+
+    let last_played_at = table
+        .last_played_at(guild_id.get(), row.id)
+        .log_err()
+        .ok()
+        .flatten();
+    match cooldown_ready_at(row.cooldown, last_played_at, chrono::Utc::now()) {
+        Some(ready_at) => Err(AudioError::SoundCooldown {
+            name: row.name.clone(),
+            ready_at: ready_at.timestamp(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Member may play a sound with `restrictions`. Sounds without restrictions can be played by
+/// everyone, restricted ones only by the roles & users they're restricted to
+pub fn may_play_restricted(
+    restrictions: &[SoundRestrictionRow],
+    user_id: Option<UserId>,
+    member_roles: &[RoleId],
+) -> bool {
+    restrictions.is_empty()
+        || restrictions.iter().any(|restriction| {
+            restriction
+                .user_id
+                .is_some_and(|id| user_id.is_some_and(|user_id| user_id.get() == id))
+                || restriction
+                    .role_id
+                    .is_some_and(|id| member_roles.contains(&RoleId::new(id)))
+        })
+}
+
+/// Sound isn't restricted to roles or users member isn't one of. Checked alongside sound
+/// cooldowns, so moderators can play every sound
+pub fn check_sound_access(
+    table: &SoundRestrictionsTable,
+    guild_id: GuildId,
+    row: &AudioTableRow,
+    user_id: Option<UserId>,
+    member_roles: &[RoleId],
+) -> Result<(), AudioError> {
+    let restrictions = table
+        .sound_rows(guild_id.get(), row.id)
+        .log_err()
+        .unwrap_or_default();
+    match may_play_restricted(&restrictions, user_id, member_roles) {
+        true => Ok(()),
+        false => Err(AudioError::SoundRestricted {
+            name: row.name.clone(),
+        }),
+    }
+}
+
+/// Ids of guild's restricted sounds member can't play, hidden from boards they ask for.
+/// Moderators see every sound
+pub fn hidden_sound_ids(
+    table: &SoundRestrictionsTable,
+    guild_id: GuildId,
+    user_id: UserId,
+    permissions: &SoundboardPermissions,
+) -> Result<HashSet<i64>, PoiseError> {
+    if permissions.is_moderator {
+        return Ok(HashSet::new());
+    }
+
+    let mut restrictions: HashMap<i64, Vec<SoundRestrictionRow>> = HashMap::new();
+    for row in table.rows(guild_id.get())? {
+        restrictions.entry(row.audio_id).or_default().push(row);
+    }
+
+    Ok(restrictions
+        .into_iter()
+        .filter(|(_, rows)| !may_play_restricted(rows, Some(user_id), &permissions.member_roles))
+        .map(|(audio_id, _)| audio_id)
+        .collect())
+}
+
+/// When a channel that played sounds at `played_at` (oldest first, within the last hour) can play
+/// again, while it's at its hourly cap
+pub fn channel_cap_ready_at(
+    cap: u32,
+    played_at: &[chrono::DateTime<chrono::Utc>],
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    // the channel can play again once enough of its plays are older than an hour
+    let index = played_at.len().checked_sub(cap as usize)?;
+    let ready_at = *played_at.get(index)? + chrono::Duration::hours(1);
+    (ready_at > now).then_some(ready_at)
+}
+
+/// Channel hasn't played its hourly cap of sounds, from everyone together. Checked alongside
+/// sound cooldowns, so moderators aren't held to it either. Lifted in event mode
+pub fn check_channel_cap(
+    settings_table: &SettingsTable,
+    table: &PlayHistoryTable,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<(), AudioError> {
+    let Some(cap) = settings::channel_hourly_cap(settings_table, guild_id) else {
+        return Ok(());
+    };
+
+    let now = chrono::Utc::now();
+    let played_at = table
+        .channel_played_at_since(
+            guild_id.get(),
+            channel_id.get(),
+            now - chrono::Duration::hours(1),
+        )
+        .log_err()
+        .unwrap_or_default();
+    match channel_cap_ready_at(cap, &played_at, now) {
+        Some(ready_at) => Err(AudioError::ChannelCapReached {
+            cap,
+            ready_at: ready_at.timestamp(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Member may play sound in channel: it isn't restricted to others, on cooldown, or past the
+/// channel's hourly cap. Moderators skip these checks, so callers only run them for others. Like
+/// the checks themselves, failing to read the database doesn't stop a play
+pub fn check_can_play(
+    db_pool: &DbPool,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    row: &AudioTableRow,
+    user_id: Option<UserId>,
+    member_roles: &[RoleId],
+) -> Result<(), AudioError> {
+    let (Ok(restrictions_conn), Ok(settings_conn), Ok(play_history_conn)) =
+        (db_pool.get(), db_pool.get(), db_pool.get())
+    else {
+        log::error!("Failed getting database connections for play checks");
+        return Ok(());
+    };
+    let restrictions_table = SoundRestrictionsTable::new(restrictions_conn);
+    let settings_table = SettingsTable::new(settings_conn);
+    let play_history_table = PlayHistoryTable::new(play_history_conn);
+
+    check_sound_access(&restrictions_table, guild_id, row, user_id, member_roles)?;
+    check_sound_cooldown(&settings_table, &play_history_table, guild_id, row)?;
+    check_channel_cap(&settings_table, &play_history_table, guild_id, channel_id)
+}
+
+/// Computes member permissions once and caches them briefly, so several checks
+/// during one interaction don't each hit the database.
+pub struct Authorizer {
+    ttl: Duration,
+    cache: Mutex<HashMap<(GuildId, UserId), (Instant, SoundboardPermissions)>>,
+}
+
+impl Default for Authorizer {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(30),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Authorizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Member's permissions, from cache if computed within the last few seconds.
+    /// `manage_guild` is whether member has the Manage Server permission
+    pub fn permissions(
+        &self,
+        data: &UserData,
+        guild_id: GuildId,
+        member: &Member,
+        manage_guild: bool,
+    ) -> SoundboardPermissions {
+        let key = (guild_id, member.user.id);
+
+        if let Some((computed_at, permissions)) = self.cache.lock().unwrap().get(&key) {
+            if computed_at.elapsed() < self.ttl {
+                return permissions.clone();
+            }
+        }
+
+        let permissions = Self::compute(data, guild_id, member, manage_guild);
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), permissions.clone()));
+
+        permissions
+    }
+
+    /// Drop cached permissions of member, ie: after they played a sound, or were banned
+    pub fn invalidate(&self, guild_id: GuildId, user_id: UserId) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.remove(&(guild_id, user_id));
+        cache.retain(|_, (computed_at, _)| computed_at.elapsed() < self.ttl);
+    }
+
+    fn compute(
+        data: &UserData,
+        guild_id: GuildId,
+        member: &Member,
+        manage_guild: bool,
+    ) -> SoundboardPermissions {
+        let settings = GuildSettings::load(&data.settings_table(), guild_id);
+        let user_id = member.user.id;
+
+        let ban = data
+            .banned_users_table()
+            .find_ban(guild_id.get(), user_id.get())
+            .log_err()
+            .ok()
+            .flatten();
+
+        let daily_play_quota = settings.daily_play_quota();
+        let plays_today = match daily_play_quota {
+            Some(_) => data
+                .play_history_table()
+                .count_user_plays_since(
+                    guild_id.get(),
+                    user_id.get(),
+                    chrono::Utc::now() - chrono::Duration::days(1),
+                )
+                .log_err()
+                .unwrap_or(0),
+            None => 0,
+        };
+
+        let priority_queue = settings
+            .priority_role
+            .is_some_and(|role| member.roles.contains(&role));
+
+        let quiet_hours_end =
+            quiet_hours::refused_until(&settings).map(|ends_at| ends_at.timestamp());
+
+        SoundboardPermissions {
+            priority_queue,
+            quiet_hours_end,
+            ..SoundboardPermissions::compute(
+                &member.roles,
+                manage_guild,
+                ban,
+                settings.user_role,
+                settings.moderator_role,
+                daily_play_quota,
+                plays_today,
+            )
+        }
+    }
+}
+
+#[async_trait]
+pub trait AuthContext {
+    /// Command author's soundboard permissions in the guild the command was run in
+    async fn permissions(&self) -> Result<SoundboardPermissions, PoiseError>;
+
+    /// Soundboard permissions of any member of the guild the command was run in
+    fn member_permissions(&self, member: &Member) -> Result<SoundboardPermissions, PoiseError>;
+}
+
+#[async_trait]
+impl<'a> AuthContext for PoiseContext<'a> {
+    async fn permissions(&self) -> Result<SoundboardPermissions, PoiseError> {
+        let member = self
+            .author_member()
+            .await
+            .ok_or("Failed getting command author member")?;
+
+        self.member_permissions(&member)
+    }
+
+    fn member_permissions(&self, member: &Member) -> Result<SoundboardPermissions, PoiseError> {
+        let guild_id = self.guild_id().ok_or("Permissions require a guild")?;
+
+        // interaction members carry their permissions, others are computed from cache
+        let manage_guild = match member.permissions {
+            Some(permissions) => permissions.manage_guild(),
+            None => self.guild().is_some_and(|guild| {
+                guild
+                    .channels
+                    .get(&self.channel_id())
+                    .map_or(Permissions::empty(), |channel| {
+                        guild.user_permissions_in(channel, member)
+                    })
+                    .manage_guild()
+            }),
+        };
+
+        Ok(self
+            .data()
+            .authorizer
+            .permissions(self.data(), guild_id, member, manage_guild))
+    }
+}
+
+/// Command check. Author may play sounds
+pub async fn check_play(ctx: PoiseContext<'_>) -> Result<bool, PoiseError> {
+    ctx.permissions().await?.check_play()?;
+    Ok(true)
+}
+
+/// Command check. Author may add, edit, and remove sounds
+pub async fn check_manage_sounds(ctx: PoiseContext<'_>) -> Result<bool, PoiseError> {
+    ctx.permissions().await?.check_manage_sounds()?;
+    Ok(true)
+}
+
+/// Command check. Author is a soundboard moderator
+pub async fn check_moderator(ctx: PoiseContext<'_>) -> Result<bool, PoiseError> {
+    ctx.permissions().await?.check_moderator()?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cooldown_ready_at_test() {
+        let now = chrono::Utc::now();
+        let minute_ago = now - chrono::Duration::minutes(1);
+
+        assert_eq!(cooldown_ready_at(None, Some(minute_ago), now), None);
+        assert_eq!(cooldown_ready_at(Some(0), Some(minute_ago), now), None);
+        assert_eq!(cooldown_ready_at(Some(300), None, now), None);
+        assert_eq!(cooldown_ready_at(Some(30), Some(minute_ago), now), None);
+        assert_eq!(
+            cooldown_ready_at(Some(300), Some(minute_ago), now),
+            Some(minute_ago + chrono::Duration::minutes(5))
+        );
+    }
+
+    #[test]
+    fn channel_cap_ready_at_test() {
+        let now = chrono::Utc::now();
+        let played_at: Vec<_> = [50, 30, 10]
+            .map(|minutes| now - chrono::Duration::minutes(minutes))
+            .into();
+
+        assert_eq!(channel_cap_ready_at(4, &played_at, now), None);
+        assert_eq!(
+            channel_cap_ready_at(3, &played_at, now),
+            Some(played_at[0] + chrono::Duration::hours(1))
+        );
+        assert_eq!(
+            channel_cap_ready_at(1, &played_at, now),
+            Some(played_at[2] + chrono::Duration::hours(1))
+        );
+        assert_eq!(channel_cap_ready_at(2, &[], now), None);
+    }
+
+    #[test]
+    fn may_play_restricted_test() {
+        let restriction = |role_id, user_id| SoundRestrictionRow {
+            id: 1,
+            guild_id: 1,
+            audio_id: 2,
+            role_id,
+            user_id,
+        };
+        let user_id = Some(UserId::new(3));
+        let role = RoleId::new(4);
+
+        assert!(may_play_restricted(&[], None, &[]));
+
+        let restrictions = [restriction(Some(4), None), restriction(None, Some(5))];
+        assert!(may_play_restricted(&restrictions, user_id, &[role]));
+        assert!(!may_play_restricted(&restrictions, user_id, &[]));
+        assert!(may_play_restricted(
+            &restrictions,
+            Some(UserId::new(5)),
+            &[]
+        ));
+        assert!(!may_play_restricted(&restrictions, None, &[]));
+    }
+
+    #[test]
+    fn compute_permissions_test() {
+        let user_role = RoleId::new(1);
+        let moderator_role = RoleId::new(2);
+
+        let regular = SoundboardPermissions::compute(&[], false, None, None, None, None, 0);
+        assert!(regular.check_play().is_ok());
+        assert!(regular.check_manage_sounds().is_ok());
+        assert!(regular.check_moderator().is_err());
+
+        let missing_role = SoundboardPermissions::compute(
+            &[],
+            false,
+            None,
+            Some(user_role),
+            Some(moderator_role),
+            None,
+            0,
+        );
+        assert_eq!(
+            missing_role.check_play(),
+            Err(AuthError::MissingUserRole { role_id: 1 })
+        );
+        assert_eq!(
+            missing_role.check_manage_sounds(),
+            Err(AuthError::NotModerator)
+        );
+
+        let over_quota = SoundboardPermissions::compute(
+            &[user_role],
+            false,
+            None,
+            Some(user_role),
+            None,
+            Some(5),
+            7,
+        );
+        assert_eq!(over_quota.plays_remaining, Some(0));
+        assert_eq!(
+            over_quota.check_play(),
+            Err(AuthError::QuotaExceeded { quota: 5 })
+        );
+
+        let moderator = SoundboardPermissions::compute(
+            &[moderator_role],
+            false,
+            None,
+            Some(user_role),
+            Some(moderator_role),
+            Some(5),
+            7,
+        );
+        assert!(moderator.check_play().is_ok());
+        assert!(moderator.check_manage_sounds().is_ok());
+
+        let quiet_hours = SoundboardPermissions {
+            quiet_hours_end: Some(1700000000),
+            ..regular.clone()
+        };
+        assert_eq!(
+            quiet_hours.check_play(),
+            Err(AuthError::QuietHours {
+                ends_at: 1700000000
+            })
+        );
+        let quiet_hours_moderator = SoundboardPermissions {
+            quiet_hours_end: Some(1700000000),
+            ..moderator.clone()
+        };
+        assert!(quiet_hours_moderator.check_play().is_ok());
+
+        let banned =
+            SoundboardPermissions::compute(&[], true, Some("spam".into()), None, None, None, 0);
+        assert!(matches!(banned.check_play(), Err(AuthError::Banned { .. })));
+
+        assert!(regular.check_unprotected("airhorn", false, false).is_ok());
+        assert_eq!(
+            moderator.check_unprotected("airhorn", true, false),
+            Err(AuthError::SoundProtected {
+                name: "airhorn".into()
+            })
+        );
+        assert_eq!(
+            moderator.check_unprotected("airhorn", true, true),
+            Err(AuthError::NotAdmin)
+        );
+        assert!(banned.is_admin);
+        assert!(banned.check_manage_guild().is_ok());
+        assert_eq!(
+            moderator.check_manage_guild(),
+            Err(AuthError::MissingManageGuild)
+        );
+        assert!(banned.check_unprotected("airhorn", true, true).is_ok());
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use chrono::{Datelike, NaiveDate};
+use serenity::all::{Cache, ChannelId, GuildId, Http, UserId};
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::db::{
+    AudioTable, BirthdayRow, BirthdaysTable, DbPool, SettingsTable, UniqueAudioTableCol,
+};
+use crate::playback::{Playback, QueuedTrack};
+use crate::settings::{self, GuildSettings};
+use crate::{quiet_hours, themes};
+
+/// Parse a birthday, as `MM-DD` or `YYYY-MM-DD`. Only the day is kept, as `MM-DD`
+pub fn parse_day(value: &str) -> Option<String> {
+    match NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d") {
+        Ok(date) => Some(date.format("%m-%d").to_string()),
+        Err(_) => themes::parse_day(value),
+    }
+}
+
+/// Whether a birthday on day (`MM-DD`) falls on date. Leap day birthdays fall on 02-28 outside
+/// leap years
+pub fn falls_on(day: &str, date: NaiveDate) -> bool {
+    let leap_year = NaiveDate::from_ymd_opt(date.year(), 2, 29).is_some();
+    let day = match day {
+        "02-29" if !leap_year => "02-28",
+        day => day,
+    };
+    date.format("%m-%d").to_string() == day
+}
+
+/// Birthdays falling on date that weren't celebrated yet this year
+pub fn due(rows: Vec<BirthdayRow>, date: NaiveDate) -> Vec<BirthdayRow> {
+    rows.into_iter()
+        .filter(|row| row.celebrated_year != Some(date.year()) && falls_on(&row.day, date))
+        .collect()
+}
+
+/// Celebrate birthdays falling on today, in each guild's timezone, of members in voice. The
+/// greeting is posted, and the sound plays when the bot is in the member's voice channel. Members
+/// who aren't in voice are celebrated once they join, as long as it's still their birthday.
+/// Returns number of birthdays celebrated
+pub async fn celebrate(
+    http: &Http,
+    cache: &Cache,
+    db_pool: &DbPool,
+    playback: &Arc<Playback>,
+) -> Result<usize, PoiseError> {
+    let birthdays_table = BirthdaysTable::new(db_pool.get()?);
+    let settings_table = SettingsTable::new(db_pool.get()?);
+    let audio_table = AudioTable::new(db_pool.get()?);
+
+    let mut celebrated = 0;
+    for guild_id in birthdays_table.guild_ids()? {
+        let guild_id = GuildId::new(guild_id);
+        let today = chrono::Utc::now()
+            .with_timezone(&settings::timezone(&settings_table, guild_id))
+            .date_naive();
+
+        for row in due(birthdays_table.rows(guild_id.get())?, today) {
+            let user_id = UserId::new(row.user_id);
+            let Some(voice_channel_id) = voice_channel(cache, guild_id, user_id) else {
+                continue;
+            };
+
+            let sound = audio_table.find_audio_row(UniqueAudioTableCol::Id(row.audio_id));
+            let greeting = format!("🎂 Happy birthday <@{user_id}>!");
+            let greeting = match &sound {
+                Some(sound) => format!("{greeting} 🔊 `{}`", sound.name),
+                None => greeting,
+            };
+            ChannelId::new(row.channel_id)
+                .say(http, greeting)
+                .await
+                .log_err_msg(format!(
+                    "Failed sending birthday greeting. guild_id: {guild_id}"
+                ))
+                .ok();
+
+            let in_channel = playback.voice_channel(guild_id).await == Some(voice_channel_id);
+            let refused =
+                quiet_hours::refused_until(&GuildSettings::load(&settings_table, guild_id))
+                    .is_some();
+            if let Some(sound) = sound.filter(|_| in_channel && !refused) {
+                let track = QueuedTrack::from_row(&sound, ChannelId::new(row.channel_id))
+                    .requested_by(user_id);
+                playback
+                    .enqueue(guild_id, track)
+                    .await
+                    .log_err_msg(format!(
+                        "Failed playing birthday sound. guild_id: {guild_id}"
+                    ))
+                    .ok();
+            }
+
+            birthdays_table.set_celebrated(row.id, today.year())?;
+            log::info!("Celebrated birthday of user {user_id}. guild_id: {guild_id}");
+            celebrated += 1;
+        }
+    }
+
+    Ok(celebrated)
+}
+
+/// Voice channel member is in, in guild
+fn voice_channel(cache: &Cache, guild_id: GuildId, user_id: UserId) -> Option<ChannelId> {
+    cache
+        .guild(guild_id)?
+        .voice_states
+        .get(&user_id)
+        .and_then(|voice_state| voice_state.channel_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_birthday_row(day: &str, celebrated_year: Option<i32>) -> BirthdayRow {
+        BirthdayRow {
+            id: 1,
+            guild_id: 1,
+            user_id: 2,
+            day: day.into(),
+            audio_id: 3,
+            channel_id: 4,
+            celebrated_year,
+        }
+    }
+
+    #[test]
+    fn parse_day_test() {
+        assert_eq!(parse_day("1990-10-16"), Some("10-16".into()));
+        assert_eq!(parse_day("3-7"), Some("03-07".into()));
+        assert_eq!(parse_day("02-29"), Some("02-29".into()));
+        assert_eq!(parse_day("13-01"), None);
+    }
+
+    #[test]
+    fn due_test() {
+        let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        assert!(falls_on("10-16", date(2026, 10, 16)));
+        assert!(!falls_on("10-16", date(2026, 10, 17)));
+        assert!(falls_on("02-29", date(2026, 2, 28)));
+        assert!(!falls_on("02-29", date(2028, 2, 28)));
+        assert!(falls_on("02-29", date(2028, 2, 29)));
+
+        let rows = vec![
+            make_birthday_row("10-16", None),
+            make_birthday_row("10-16", Some(2026)),
+            make_birthday_row("10-16", Some(2025)),
+            make_birthday_row("10-17", None),
+        ];
+        let due = due(rows, date(2026, 10, 16));
+        assert_eq!(due.len(), 2);
+        assert_eq!(due[1].celebrated_year, Some(2025));
+    }
+}
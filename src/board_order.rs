@@ -0,0 +1,219 @@
+use serenity::all::{
+    ComponentInteraction, Context, CreateActionRow, CreateButton, EditInteractionResponse,
+};
+
+use crate::commands::{PoiseError, PoiseResult};
+use crate::common::{LogResult, UserData};
+use crate::db::{AudioTable, BoardLayout, BoardOrderTable, UniqueAudioTableCol};
+use crate::errors::BoardOrderError;
+use crate::helpers::ButtonCustomId;
+
+/// Sounds listed before & after the sound being reordered
+const NEIGHBOURS: usize = 2;
+
+/// Step a sound takes on its board, from the buttons of `/board reorder`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoardMove {
+    Top,
+    Up,
+    Down,
+    Bottom,
+}
+
+impl BoardMove {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Top => "top",
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::Bottom => "bottom",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "top" => Some(Self::Top),
+            "up" => Some(Self::Up),
+            "down" => Some(Self::Down),
+            "bottom" => Some(Self::Bottom),
+            _ => None,
+        }
+    }
+
+    /// Board position (1 based) sound at position moves to, on a board of len sounds
+    fn position(&self, position: usize, len: usize) -> usize {
+        match self {
+            Self::Top => 1,
+            Self::Up => position.saturating_sub(1).max(1),
+            Self::Down => (position + 1).min(len),
+            Self::Bottom => len,
+        }
+    }
+}
+
+/// Position (1 based) of sound among the board's sound ids
+fn position_of(
+    ids: &[i64],
+    audio_id: i64,
+    name: &str,
+    board: BoardLayout,
+) -> Result<usize, BoardOrderError> {
+    ids.iter()
+        .position(|id| *id == audio_id)
+        .map(|index| index + 1)
+        .ok_or(BoardOrderError::NotOnBoard {
+            name: name.into(),
+            board: board.to_string(),
+        })
+}
+
+/// Move sound to position (1 based) among ids. Positions past the end move it last
+fn reorder(ids: &mut Vec<i64>, from: usize, to: usize) {
+    let audio_id = ids.remove(from - 1);
+    ids.insert(to.clamp(1, ids.len() + 1) - 1, audio_id);
+}
+
+/// Move sound to position (1 based) of user's board, and save the board's order. Returns ids of
+/// the board's sounds in their new order
+pub fn move_sound(
+    table: &BoardOrderTable,
+    board: BoardLayout,
+    user_id: u64,
+    audio_id: i64,
+    name: &str,
+    position: usize,
+) -> Result<Vec<i64>, PoiseError> {
+    let mut ids = table.board_audio_ids(board, user_id)?;
+    let from = position_of(&ids, audio_id, name, board)?;
+    log::info!("Moving `{name}` from {from} to {position} on {board} board. user_id: {user_id}");
+
+    reorder(&mut ids, from, position);
+    table.set_order(board, user_id, &ids)?;
+    Ok(ids)
+}
+
+/// Reply of `/board reorder`: where sound is on the board among its neighbours, and buttons moving it
+pub fn reorder_reply(
+    audio_table: &AudioTable,
+    board: BoardLayout,
+    ids: &[i64],
+    audio_id: i64,
+    name: &str,
+) -> Result<(String, Vec<CreateActionRow>), BoardOrderError> {
+    let position = position_of(ids, audio_id, name, board)?;
+    let len = ids.len();
+
+    let mut content = format!("`{name}` is at position {position}/{len} of the {board} board");
+    let first = position.saturating_sub(NEIGHBOURS).max(1);
+    let last = (position + NEIGHBOURS).min(len);
+    for (index, id) in ids.iter().enumerate().take(last).skip(first - 1) {
+        let line = match *id == audio_id {
+            true => format!("**{}. `{name}`**", index + 1),
+            false => {
+                let neighbour = audio_table
+                    .find_audio_row(UniqueAudioTableCol::Id(*id))
+                    .map_or("?".into(), |row| row.name);
+                format!("{}. `{neighbour}`", index + 1)
+            }
+        };
+        content.push('\n');
+        content.push_str(&line);
+    }
+
+    let button = |board_move: BoardMove, label: &str, disabled: bool| {
+        CreateButton::new(ButtonCustomId::MoveBoardSound(audio_id, board, board_move))
+            .label(label)
+            .disabled(disabled)
+    };
+    let components = vec![CreateActionRow::Buttons(vec![
+        button(BoardMove::Top, "⏫ Top", position == 1),
+        button(BoardMove::Up, "🔼 Up", position == 1),
+        button(BoardMove::Down, "🔽 Down", position == len),
+        button(BoardMove::Bottom, "⏬ Bottom", position == len),
+    ])];
+
+    Ok((content, components))
+}
+
+/// Move sound a step on the board of whoever pressed a `/board reorder` button, and update the reply
+pub async fn move_button(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+    audio_id: i64,
+    board: BoardLayout,
+    board_move: BoardMove,
+) -> PoiseResult {
+    let user_id = component.user.id.get();
+    let response = match data
+        .audio_table()
+        .find_audio_row(UniqueAudioTableCol::Id(audio_id))
+    {
+        Some(row) => {
+            let table = data.board_order_table();
+            let ids = table.board_audio_ids(board, user_id)?;
+            let from = position_of(&ids, audio_id, &row.name, board)?;
+            let ids = move_sound(
+                &table,
+                board,
+                user_id,
+                audio_id,
+                &row.name,
+                board_move.position(from, ids.len()),
+            )?;
+            let (content, components) =
+                reorder_reply(&data.audio_table(), board, &ids, audio_id, &row.name)?;
+            EditInteractionResponse::new()
+                .content(content)
+                .components(components)
+        }
+        None => EditInteractionResponse::new()
+            .content("Sound no longer exists")
+            .components(vec![]),
+    };
+
+    component
+        .edit_response(ctx, response)
+        .await
+        .log_err_msg("Failed updating board reorder reply")
+        .ok();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn board_move_test() {
+        assert_eq!(BoardMove::Up.position(1, 5), 1);
+        assert_eq!(BoardMove::Up.position(3, 5), 2);
+        assert_eq!(BoardMove::Down.position(5, 5), 5);
+        assert_eq!(BoardMove::Bottom.position(2, 5), 5);
+        assert_eq!(
+            BoardMove::parse(BoardMove::Top.as_str()),
+            Some(BoardMove::Top)
+        );
+        assert_eq!(BoardMove::parse("sideways"), None);
+    }
+
+    #[test]
+    fn reorder_test() {
+        let mut ids = vec![1, 2, 3, 4];
+        reorder(&mut ids, 4, 1);
+        assert_eq!(ids, vec![4, 1, 2, 3]);
+        reorder(&mut ids, 1, 3);
+        assert_eq!(ids, vec![1, 2, 4, 3]);
+        reorder(&mut ids, 2, 99);
+        assert_eq!(ids, vec![1, 4, 3, 2]);
+
+        assert_eq!(position_of(&ids, 3, "c", BoardLayout::Full), Ok(3));
+        assert_eq!(
+            position_of(&ids, 9, "z", BoardLayout::Favorites),
+            Err(BoardOrderError::NotOnBoard {
+                name: "z".into(),
+                board: "favorites".into()
+            })
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use serenity::all::{Colour, CreateEmbed, CreateEmbedFooter, GuildId};
+
+use crate::commands::PoiseError;
+use crate::common::{LogResult, UserData};
+use crate::db::SettingsTable;
+
+/// Guild settings holding branding. Not `/settings` keys, they're set by `/branding`
+const COLOR_KEY: &str = "embed_color";
+const FOOTER_KEY: &str = "embed_footer";
+const ICON_KEY: &str = "embed_icon";
+
+/// Longest footer text discord allows
+pub const FOOTER_MAX_LEN: usize = 2048;
+
+/// Color, footer text, and footer icon of a guild's embeds
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Branding {
+    pub color: Option<Colour>,
+    pub footer: Option<String>,
+    pub icon_url: Option<String>,
+}
+
+/// Parse a hex color, ie: `#5865F2`, `5865f2`, or `0x5865F2`
+pub fn parse_color(value: &str) -> Option<Colour> {
+    let value = value.trim();
+    let hex = value
+        .strip_prefix('#')
+        .or(value.strip_prefix("0x"))
+        .unwrap_or(value);
+
+    match hex.len() == 6 {
+        true => u32::from_str_radix(hex, 16).ok().map(Colour::new),
+        false => None,
+    }
+}
+
+/// Parse an icon url. Discord only shows icons served over http(s)
+pub fn parse_icon_url(value: &str) -> Option<String> {
+    let value = value.trim();
+    let url = reqwest::Url::parse(value).ok()?;
+    matches!(url.scheme(), "http" | "https").then(|| value.to_string())
+}
+
+impl Branding {
+    /// Branding of guild. Stored values that are no longer valid are ignored
+    pub fn load(table: &SettingsTable, guild_id: GuildId) -> Self {
+        let get = |key: &str| {
+            table
+                .get_guild_setting(guild_id.get(), key)
+                .log_err()
+                .ok()
+                .flatten()
+        };
+
+        Self {
+            color: get(COLOR_KEY).and_then(|value| parse_color(&value)),
+            footer: get(FOOTER_KEY),
+            icon_url: get(ICON_KEY).and_then(|value| parse_icon_url(&value)),
+        }
+    }
+
+    /// Branding of guild, or no branding outside of guilds, ie: DMs
+    pub fn of(data: &UserData, guild_id: Option<GuildId>) -> Self {
+        guild_id
+            .map(|guild_id| Self::load(&data.settings_table(), guild_id))
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Embed in guild's color, with guild's footer
+    pub fn apply(&self, embed: CreateEmbed) -> CreateEmbed {
+        self.apply_with_footer(embed, None)
+    }
+
+    /// Embed in guild's color, with its own footer text ahead of the guild's footer
+    pub fn apply_with_footer(&self, mut embed: CreateEmbed, text: Option<String>) -> CreateEmbed {
+        if let Some(color) = self.color {
+            embed = embed.colour(color);
+        }
+
+        let text = match (text, &self.footer) {
+            (Some(text), Some(footer)) => Some(format!("{text} • {footer}")),
+            (text, footer) => text.or(footer.clone()),
+        };
+        // footers need text, so an icon on its own gets a zero width space
+        let text = match (text, &self.icon_url) {
+            (None, Some(_)) => Some("\u{200b}".to_string()),
+            (text, _) => text,
+        };
+        let Some(text) = text else {
+            return embed;
+        };
+
+        let mut footer = CreateEmbedFooter::new(text);
+        if let Some(icon_url) = &self.icon_url {
+            footer = footer.icon_url(icon_url);
+        }
+        embed.footer(footer)
+    }
+}
+
+/// Set or clear guild's embed color
+pub fn set_color(
+    table: &SettingsTable,
+    guild_id: GuildId,
+    color: Option<Colour>,
+) -> Result<(), PoiseError> {
+    set(table, guild_id, COLOR_KEY, color.map(|color| color.hex()))
+}
+
+/// Set or clear guild's embed footer text
+pub fn set_footer(
+    table: &SettingsTable,
+    guild_id: GuildId,
+    footer: Option<String>,
+) -> Result<(), PoiseError> {
+    set(table, guild_id, FOOTER_KEY, footer)
+}
+
+/// Set or clear guild's embed footer icon
+pub fn set_icon_url(
+    table: &SettingsTable,
+    guild_id: GuildId,
+    icon_url: Option<String>,
+) -> Result<(), PoiseError> {
+    set(table, guild_id, ICON_KEY, icon_url)
+}
+
+/// Clear all of guild's branding
+pub fn reset(table: &SettingsTable, guild_id: GuildId) -> Result<(), PoiseError> {
+    for key in [COLOR_KEY, FOOTER_KEY, ICON_KEY] {
+        set(table, guild_id, key, None)?;
+    }
+    Ok(())
+}
+
+fn set(
+    table: &SettingsTable,
+    guild_id: GuildId,
+    key: &str,
+    value: Option<String>,
+) -> Result<(), PoiseError> {
+    match value {
+        Some(value) => table.set_guild_setting(guild_id.get(), key, &value)?,
+        None => table.delete_guild_setting(guild_id.get(), key)?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::db::Table;
+
+    #[test]
+    fn parse_color_test() {
+        assert_eq!(parse_color("#5865F2"), Some(Colour::new(0x5865F2)));
+        assert_eq!(parse_color(" 5865f2 "), Some(Colour::new(0x5865F2)));
+        assert_eq!(parse_color("0x000000"), Some(Colour::new(0)));
+        assert_eq!(parse_color("#FFF"), None);
+        assert_eq!(parse_color("blurple"), None);
+
+        assert!(parse_icon_url("https://example.com/icon.png").is_some());
+        assert!(parse_icon_url("ftp://example.com/icon.png").is_none());
+        assert!(parse_icon_url("icon.png").is_none());
+    }
+
+    #[test]
+    fn branding_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let table = SettingsTable::new(db_pool.get().unwrap());
+        table.create_table();
+
+        let guild_id = GuildId::new(1);
+        assert!(Branding::load(&table, guild_id).is_empty());
+
+        set_color(&table, guild_id, parse_color("#5865F2")).unwrap();
+        set_footer(&table, guild_id, Some("Hosted by Example".into())).unwrap();
+        set_icon_url(
+            &table,
+            guild_id,
+            Some("https://example.com/icon.png".into()),
+        )
+        .unwrap();
+        let branding = Branding::load(&table, guild_id);
+        assert_eq!(branding.color, Some(Colour::new(0x5865F2)));
+        assert!(Branding::load(&table, GuildId::new(2)).is_empty());
+
+        let embed = serde_json::to_value(
+            branding.apply_with_footer(CreateEmbed::new(), Some("2 writes failed".into())),
+        )
+        .unwrap();
+        assert_eq!(embed["color"], 0x5865F2);
+        assert_eq!(
+            embed["footer"]["text"],
+            "2 writes failed • Hosted by Example"
+        );
+        assert_eq!(embed["footer"]["icon_url"], "https://example.com/icon.png");
+
+        // own footer is kept without branding
+        let embed = serde_json::to_value(
+            Branding::default().apply_with_footer(CreateEmbed::new(), Some("note".into())),
+        )
+        .unwrap();
+        assert_eq!(embed["footer"]["text"], "note");
+        assert!(embed.get("color").is_none());
+
+        reset(&table, guild_id).unwrap();
+        assert!(Branding::load(&table, guild_id).is_empty());
+    }
+}
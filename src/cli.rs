@@ -0,0 +1,124 @@
+use std::path;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::db::{AudioTable, PlayHistoryTable, Table};
+use crate::export::{self, ExportFormat};
+use crate::metadata;
+
+const USAGE: &str = "\
+Usage: soundboard-bot [COMMAND]
+
+Runs the bot when no command is given.
+
+Options:
+  --dry-run                           Play sounds in a simulated voice channel, reading commands from stdin.
+                                      No bot token or Discord connection needed
+
+Commands:
+  import-metadata <file> [--dry-run] [--force]
+                                      Bulk set names, tags, emojis, and volumes of sounds from a CSV or JSON file.
+                                      Protected sounds are only changed with --force
+  export-stats [--format csv|json] [--guild <id>] [--out <dir>]
+                                      Write play history & sound library metadata files";
+
+/// Run command line command, if one was given. Returns None when bot should run as usual
+pub fn run(
+    args: &[String],
+    db_pool: &r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>,
+) -> Option<anyhow::Result<()>> {
+    let command = args.first()?;
+    let args = &args[1..];
+
+    let result = match command.as_str() {
+        "import-metadata" => import_metadata(args, db_pool),
+        "export-stats" => export_stats(args, db_pool),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => Err(anyhow::anyhow!("Unknown command '{command}'\n\n{USAGE}")),
+    };
+
+    Some(result)
+}
+
+fn import_metadata(
+    args: &[String],
+    db_pool: &r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>,
+) -> anyhow::Result<()> {
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let force = args.iter().any(|arg| arg == "--force");
+    let file = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .map(path::PathBuf::from)
+        .ok_or(anyhow::anyhow!("Missing metadata file\n\n{USAGE}"))?;
+
+    let format = metadata::MetadataFormat::from_file_name(&file.to_string_lossy())
+        .map_err(anyhow::Error::msg)?;
+    let content = std::fs::read(&file)?;
+
+    // migrate audio table, in case bot hasn't run since columns were added
+    AudioTable::new(db_pool.get()?).create_table();
+
+    let (report, failed_writes) = metadata::import_metadata(
+        db_pool,
+        &content,
+        format,
+        dry_run,
+        force,
+        &CancellationToken::new(),
+    )
+    .map_err(|err| anyhow::anyhow!("{err}"))?;
+
+    print!("{report}");
+    match dry_run {
+        true => println!("Dry run - no changes were written"),
+        false if failed_writes > 0 => println!("Failed writes: {failed_writes}"),
+        false => println!("Metadata imported"),
+    }
+
+    Ok(())
+}
+
+/// Value following option, ie: `json` of `--format json`
+fn option_value<'a>(args: &'a [String], option: &str) -> anyhow::Result<Option<&'a str>> {
+    match args.iter().position(|arg| arg == option) {
+        Some(index) => args
+            .get(index + 1)
+            .map(|value| Some(value.as_str()))
+            .ok_or(anyhow::anyhow!("Missing value of {option}\n\n{USAGE}")),
+        None => Ok(None),
+    }
+}
+
+fn export_stats(
+    args: &[String],
+    db_pool: &r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>,
+) -> anyhow::Result<()> {
+    let format = match option_value(args, "--format")? {
+        Some(format) => ExportFormat::from_name(format).map_err(anyhow::Error::msg)?,
+        None => ExportFormat::default(),
+    };
+    let guild_id = option_value(args, "--guild")?
+        .map(|guild_id| guild_id.parse::<u64>())
+        .transpose()?;
+    let out_dir = path::PathBuf::from(option_value(args, "--out")?.unwrap_or("."));
+
+    // tables may not exist yet, if bot hasn't run since they were added
+    AudioTable::new(db_pool.get()?).create_table();
+    PlayHistoryTable::new(db_pool.get()?).create_table();
+
+    let files = export::export_stats(db_pool, guild_id, format, &CancellationToken::new())
+        .map_err(|err| anyhow::anyhow!("{err}"))?;
+
+    std::fs::create_dir_all(&out_dir)?;
+    for file in files {
+        let file_path = out_dir.join(&file.file_name);
+        std::fs::write(&file_path, &file.content)?;
+        println!("Wrote {}", file_path.to_string_lossy());
+    }
+
+    Ok(())
+}
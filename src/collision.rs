@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton, CreateMessage,
+    EditInteractionResponse, GuildId, User,
+};
+use serenity::client::Context;
+
+use crate::commands::{PoiseError, PoiseResult};
+use crate::common::{LogResult, UserData};
+use crate::config::Config;
+use crate::db::{AudioTable, Tags, UniqueAudioTableCol};
+use crate::helpers::{self, ButtonCustomId};
+use crate::ingest::{Ingest, IngestSource, IngestTarget};
+use crate::{naming, settings};
+
+/// How long an uploader has to choose what happens to a sound named like an existing one
+const PENDING_IMPORT_TTL: Duration = Duration::from_secs(15 * 60);
+
+/// What happens when a new sound is named like an existing sound
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NameCollision {
+    /// Sound isn't added
+    #[default]
+    Reject,
+    /// Sound is added with a number appended to its name, ie: `airhorn (2)`
+    Suffix,
+    /// Uploader chooses to replace the existing sound's audio, add a suffixed sound, or cancel
+    Overwrite,
+}
+
+impl NameCollision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Reject => "reject",
+            Self::Suffix => "suffix",
+            Self::Overwrite => "overwrite",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "reject" => Some(Self::Reject),
+            "suffix" => Some(Self::Suffix),
+            "overwrite" => Some(Self::Overwrite),
+            _ => None,
+        }
+    }
+}
+
+/// Name no sound has yet, made by appending ` (2)`, ` (3)`, etc to name. Name is shortened as needed
+/// to stay within the button label length
+pub fn suffixed_name(table: &AudioTable, config: &Config, name: &str) -> String {
+    let is_taken = |name: &str| {
+        table
+            .find_audio_row(UniqueAudioTableCol::Name(name.into()))
+            .is_some()
+    };
+
+    (2..)
+        .map(|number| naming::numbered(config, name, number))
+        .find(|name| !is_taken(name))
+        .expect("Infinite suffixes")
+}
+
+/// Name a new sound is added under in guild, following the naming rules. Errors when a sound
+/// already has the name, unless guild suffixes colliding names
+pub fn resolve_name(
+    data: &UserData,
+    guild_id: Option<GuildId>,
+    name: &str,
+) -> Result<String, PoiseError> {
+    let config = data.config.get();
+    let name = naming::normalize(&config, name)?;
+    let name = name.as_str();
+    let table = data.audio_table();
+    if table
+        .find_audio_row(UniqueAudioTableCol::Name(name.into()))
+        .is_none()
+    {
+        return Ok(name.into());
+    }
+
+    let policy = guild_id
+        .map(|guild_id| settings::name_collision(&data.settings_table(), guild_id))
+        .unwrap_or_default();
+    match policy {
+        NameCollision::Suffix => Ok(suffixed_name(&table, &config, name)),
+        NameCollision::Reject | NameCollision::Overwrite => {
+            Err(format!("Can't add sound `{name}`. It already exists").into()).log_err()
+        }
+    }
+}
+
+/// Sound from `/sounds add` waiting for its uploader to choose what happens to its name
+pub struct PendingImport {
+    pub name: String,
+    pub url: String,
+    pub tags: Tags,
+    pub author: User,
+    /// Sound that already has the name
+    pub existing_id: i64,
+    created_at: Instant,
+}
+
+impl PendingImport {
+    pub fn new(name: String, url: String, tags: Tags, author: User, existing_id: i64) -> Self {
+        Self {
+            name,
+            url,
+            tags,
+            author,
+            existing_id,
+            created_at: Instant::now(),
+        }
+    }
+}
+
+/// Imports waiting on their uploader, by the id carried in their choice buttons
+#[derive(Default)]
+pub struct PendingImports {
+    next_id: AtomicI64,
+    imports: Mutex<HashMap<i64, PendingImport>>,
+}
+
+impl PendingImports {
+    /// Returns id of pending import. Expired imports are dropped
+    pub fn insert(&self, import: PendingImport) -> i64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut imports = self.imports.lock().unwrap();
+        imports.retain(|_, import| import.created_at.elapsed() < PENDING_IMPORT_TTL);
+        imports.insert(id, import);
+        id
+    }
+
+    /// Remove pending import, if it's the user's and hasn't expired
+    pub fn take(&self, id: i64, user: &User) -> Result<PendingImport, &'static str> {
+        let mut imports = self.imports.lock().unwrap();
+        match imports.get(&id) {
+            Some(import) if import.created_at.elapsed() >= PENDING_IMPORT_TTL => {
+                imports.remove(&id);
+                Err("This choice expired. Add the sound again")
+            }
+            Some(import) if import.author.id != user.id => {
+                Err("Only whoever added the sound can choose")
+            }
+            Some(_) => Ok(imports.remove(&id).expect("Pending import exists")),
+            None => Err("This choice expired. Add the sound again"),
+        }
+    }
+}
+
+/// Overwrite, keep both & cancel buttons of a pending import
+pub fn choice_action_row(id: i64) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(ButtonCustomId::ResolveCollision(
+            id,
+            NameCollision::Overwrite,
+        ))
+        .label("Overwrite")
+        .style(ButtonStyle::Danger),
+        CreateButton::new(ButtonCustomId::ResolveCollision(id, NameCollision::Suffix))
+            .label("Keep Both"),
+        CreateButton::new(ButtonCustomId::ResolveCollision(id, NameCollision::Reject))
+            .label("Cancel"),
+    ])
+}
+
+/// Add or cancel pending import the way its uploader chose. The interaction must already be acknowledged
+pub async fn resolve_pending(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+    id: i64,
+    choice: NameCollision,
+) -> PoiseResult {
+    let reply = |content: String| async move {
+        component
+            .edit_response(
+                ctx,
+                EditInteractionResponse::new()
+                    .content(content)
+                    .components(vec![]),
+            )
+            .await
+            .log_err_msg("Failed replying to name collision choice")
+            .ok();
+    };
+
+    let import = match data.pending_imports.take(id, &component.user) {
+        Ok(import) => import,
+        Err(reason) => {
+            reply(reason.into()).await;
+            return Ok(());
+        }
+    };
+
+    let existing = data
+        .audio_table()
+        .find_audio_row(UniqueAudioTableCol::Id(import.existing_id));
+
+    let result = match (choice, existing) {
+        (NameCollision::Reject, _) => {
+            reply(format!("Cancelled adding `{}`", import.name)).await;
+            return Ok(());
+        }
+        (NameCollision::Overwrite, Some(mut row)) => overwrite(data, &mut row, &import)
+            .await
+            .map(|_| format!("Overwrote sound `{}`", row.name)),
+        // existing sound was removed or renamed since, the name may be free now
+        (NameCollision::Overwrite, None) | (NameCollision::Suffix, _) => {
+            let name = match data
+                .audio_table()
+                .find_audio_row(UniqueAudioTableCol::Name(import.name.clone()))
+            {
+                Some(_) => suffixed_name(&data.audio_table(), &data.config.get(), &import.name),
+                None => import.name.clone(),
+            };
+            let target = IngestTarget::New {
+                name: &name,
+                tags: Tags::from(import.tags.to_vec()),
+                author: &import.author,
+            };
+            Ingest::new(data)
+                .run(IngestSource::Url(import.url.clone()), target)
+                .await
+                .map(|_| format!("Added sound `{name}` to soundboard"))
+        }
+    };
+
+    match result {
+        Ok(content) => {
+            reply(content.clone()).await;
+            helpers::send_audit_message(
+                ctx,
+                data,
+                component.guild_id,
+                CreateMessage::new().content(format!("{content} by <@{}>", component.user.id)),
+            )
+            .await;
+        }
+        Err(err) => reply(format!("Failed adding `{}` - {err}", import.name)).await,
+    }
+
+    Ok(())
+}
+
+/// Replace existing sound's audio with the pending import's, and its tags when new ones were given
+async fn overwrite(
+    data: &UserData,
+    row: &mut crate::db::AudioTableRow,
+    import: &PendingImport,
+) -> Result<(), PoiseError> {
+    Ingest::new(data)
+        .run(
+            IngestSource::Url(import.url.clone()),
+            IngestTarget::Replace(row),
+        )
+        .await?;
+
+    if !import.tags.is_empty() {
+        row.tags = Tags::from(import.tags.to_vec());
+        data.audio_table().update_audio_row(&*row)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::audio::AudioFile;
+    use crate::db::{AudioTableRowInsert, Table};
+    use crate::vars;
+
+    #[test]
+    fn suffixed_name_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let table = AudioTable::new(db_pool.get().unwrap());
+        table.create_table();
+
+        for name in ["airhorn", "airhorn (2)"] {
+            table
+                .insert_audio_row(AudioTableRowInsert {
+                    name: name.into(),
+                    tags: Tags::new(),
+                    audio_file: AudioFile::new(format!("/tmp/{name}.mp3").into()),
+                    created_at: chrono::Utc::now(),
+                    author_id: None,
+                    author_name: None,
+                    author_global_name: None,
+                    file_hash: None,
+                    file_size: None,
+                    file_modified_at: None,
+                })
+                .unwrap();
+        }
+
+        let config = Config::default();
+        assert_eq!(suffixed_name(&table, &config, "airhorn"), "airhorn (3)");
+        assert_eq!(suffixed_name(&table, &config, "bruh"), "bruh (2)");
+
+        let long_name = "x".repeat(vars::BTN_LABEL_MAX_LEN);
+        let suffixed = suffixed_name(&table, &config, &long_name);
+        assert_eq!(suffixed.chars().count(), vars::BTN_LABEL_MAX_LEN);
+        assert!(suffixed.ends_with(" (2)"));
+
+        assert_eq!(NameCollision::parse("Suffix"), Some(NameCollision::Suffix));
+        assert_eq!(NameCollision::parse("rename"), None);
+    }
+}
@@ -0,0 +1,946 @@
+use std::collections::HashMap;
+
+use poise::CreateReply;
+use serenity::all::CreateMessage;
+
+use crate::{
+    activity, api, auth, branding,
+    common::LogResult,
+    db::{self, ApiScope},
+    errors::OperationError,
+    export,
+    features::{self, FeatureFlag},
+    guild_data,
+    helpers::{self, poise_check_msg, LongRunning},
+    registration, retention, settings,
+    telemetry::{self, TelemetryReport},
+    version,
+};
+
+use super::{PoiseCommand, PoiseContext, PoiseResult};
+
+/// Top level commands of this module, see [`super::all`]
+pub fn commands() -> Vec<PoiseCommand> {
+    vec![
+        echo(),
+        register(),
+        command_registration(),
+        metrics(),
+        telemetry(),
+        reload(),
+        features(),
+        jobs(),
+        database(),
+        export(),
+        stats(),
+        guilddata(),
+        apikey(),
+        cleanup(),
+        version(),
+    ]
+}
+
+#[poise::command(prefix_command, guild_only)]
+pub async fn ping(ctx: PoiseContext<'_>) -> PoiseResult {
+    poise_check_msg(ctx.say("pong!").await);
+    Ok(())
+}
+
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn echo(
+    ctx: PoiseContext<'_>,
+    #[description = "Text to echo back"] text: Option<String>,
+) -> PoiseResult {
+    let response = format!("Echo: '{}'", text.unwrap_or("".into()));
+    poise_check_msg(ctx.say(response).await);
+
+    Ok(())
+}
+
+/// Register slash commands globally or in this guild, or clear them
+#[poise::command(slash_command, prefix_command, owners_only)]
+pub async fn register(
+    ctx: PoiseContext<'_>,
+    #[description = "Where to register commands. Defaults to this guild, or global outside guilds"]
+    scope: Option<registration::RegisterScope>,
+    #[description = "Remove commands registered there instead"]
+    #[flag]
+    clear: bool,
+) -> PoiseResult {
+    let scope = scope.unwrap_or(match ctx.guild_id() {
+        Some(_) => registration::RegisterScope::Guild,
+        None => registration::RegisterScope::Global,
+    });
+    ctx.defer().await?;
+
+    let reply = match registration::register_scope(
+        ctx.http(),
+        &ctx.framework().options().commands,
+        scope,
+        ctx.guild_id(),
+        clear,
+    )
+    .await
+    {
+        Ok(names) => {
+            CreateReply::default().embed(registration::register_embed(scope, clear, &names))
+        }
+        Err(err) => {
+            log::error!("Failed registering commands {scope} - {err}");
+            CreateReply::default().content(format!("Failed registering commands {scope} - {err}"))
+        }
+    };
+
+    poise_check_msg(ctx.send(reply.ephemeral(true)).await);
+    Ok(())
+}
+
+/// Switch between global & per-guild slash command registration, removing commands of the other mode
+#[poise::command(slash_command, prefix_command, owners_only, rename = "registration")]
+pub async fn command_registration(
+    ctx: PoiseContext<'_>,
+    #[description = "Register commands globally, per guild, or leave them as they are"]
+    mode: registration::CommandRegistration,
+) -> PoiseResult {
+    ctx.defer().await?;
+
+    let guild_ids = ctx.cache().guilds();
+    let text = match ctx
+        .data()
+        .registrar
+        .switch(
+            ctx.http(),
+            &ctx.framework().options().commands,
+            &guild_ids,
+            mode,
+        )
+        .await
+    {
+        Ok(summary) => format!(
+            "{summary}\nSet `DISCORD_BOT_COMMAND_REGISTRATION={mode}` to keep this mode after a restart"
+        ),
+        Err(err) => {
+            log::error!("Failed switching command registration to {mode} - {err}");
+            format!("Failed switching command registration to {mode} - {err}")
+        }
+    };
+
+    poise_check_msg(ctx.reply(text).await);
+    Ok(())
+}
+
+/// Running version, build, uptime & counters. Optionally checks GitHub for a newer release
+#[poise::command(slash_command, prefix_command)]
+pub async fn version(
+    ctx: PoiseContext<'_>,
+    #[description = "Check GitHub for a newer release"] check_updates: Option<bool>,
+) -> PoiseResult {
+    let data = ctx.data();
+    if check_updates.unwrap_or(false) {
+        ctx.defer().await?;
+    }
+
+    let built = version::build_time().map_or("unknown".into(), |built| {
+        format!("<t:{}:f>", built.timestamp())
+    });
+    let (calls, failures) = data
+        .metrics
+        .command_metrics()
+        .iter()
+        .fold((0, 0), |(calls, failures), (_, command)| {
+            (calls + command.calls, failures + command.failures)
+        });
+    let sounds = data.audio_table().count_rows().log_err().unwrap_or(0);
+
+    let mut embed = serenity::all::CreateEmbed::new()
+        .title(format!("Soundboard Bot v{}", version::VERSION))
+        .field("Commit", format!("`{}`", version::GIT_COMMIT), true)
+        .field("Built", built, true)
+        .field(
+            "Uptime",
+            version::format_uptime(data.metrics.uptime()),
+            true,
+        )
+        .field("Guilds", ctx.cache().guild_count().to_string(), true)
+        .field("Sounds", sounds.to_string(), true)
+        .field(
+            "Playing In",
+            format!("{} guilds", data.playback.playing_guilds()),
+            true,
+        )
+        .field("Commands", format!("{calls} run, {failures} failed"), true)
+        .field(
+            "Tracks Played",
+            data.playback.tracks_started().to_string(),
+            true,
+        );
+
+    if check_updates.unwrap_or(false) {
+        let update = match version::latest_release().await {
+            Ok(release) if release.is_newer() => {
+                format!("[{}]({}) is available", release.tag_name, release.html_url)
+            }
+            Ok(_) => "Up to date".into(),
+            Err(err) => format!("Couldn't check for updates - {err}"),
+        };
+        embed = embed.field("Update", update, false);
+    }
+
+    let embed = branding::Branding::of(data, ctx.guild_id()).apply(embed);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[poise::command(prefix_command, owners_only)]
+pub async fn metrics(ctx: PoiseContext<'_>) -> PoiseResult {
+    let metrics = ctx.data().metrics.command_metrics();
+    let api_usage = ctx
+        .data()
+        .api_usage_table()
+        .usage_since(None, chrono::Utc::now())?;
+
+    if metrics.is_empty() && api_usage.is_empty() {
+        poise_check_msg(ctx.reply("No commands recorded yet").await);
+        return Ok(());
+    }
+
+    let mut text = format!(
+        "{:<24} {:>7} {:>7} {:>8} {:>8}\n",
+        "command", "calls", "errors", "avg ms", "max ms"
+    );
+    for (name, command) in metrics {
+        text.push_str(&format!(
+            "{:<24} {:>7} {:>7} {:>8} {:>8}\n",
+            name,
+            command.calls,
+            command.failures,
+            command.avg_duration().as_millis(),
+            command.max_duration.as_millis()
+        ));
+    }
+
+    if !api_usage.is_empty() {
+        text.push_str(&format!(
+            "\n{:<24} {:>20} {:>8} {:>7} {:>8}\n",
+            "api key (today)", "guild", "requests", "errors", "rejected"
+        ));
+    }
+    for key in api_usage {
+        let name = key
+            .key_name
+            .unwrap_or_else(|| format!("#{} (revoked)", key.key_id));
+        text.push_str(&format!(
+            "{:<24} {:>20} {:>8} {:>7} {:>8}\n",
+            name, key.guild_id, key.requests, key.failures, key.rejected
+        ));
+    }
+
+    poise_check_msg(ctx.reply(format!("```\n{text}```")).await);
+    Ok(())
+}
+
+/// Show whether telemetry is on, and the report it sends daily
+#[poise::command(prefix_command, owners_only)]
+pub async fn telemetry(ctx: PoiseContext<'_>) -> PoiseResult {
+    let config = ctx.data().config.get();
+    let report = TelemetryReport::collect(&ctx.data().db_pool, ctx.cache().guild_count())?;
+
+    let status = match (config.telemetry, telemetry::endpoint(&config)) {
+        (false, _) => "Telemetry is off. Nothing is sent".to_string(),
+        (true, None) => {
+            "Telemetry is on, but `DISCORD_BOT_TELEMETRY_URL` isn't set. Nothing is sent"
+                .to_string()
+        }
+        (true, Some(url)) => format!("Telemetry is on. This report is sent daily to <{url}>"),
+    };
+    let json = serde_json::to_string_pretty(&report)?;
+
+    poise_check_msg(ctx.reply(format!("{status}\n```json\n{json}\n```")).await);
+    Ok(())
+}
+
+/// Reload config from environment & dotenv file, without restarting the bot
+#[poise::command(slash_command, prefix_command, owners_only)]
+pub async fn reload(ctx: PoiseContext<'_>) -> PoiseResult {
+    log::info!("Reloading config...");
+
+    let text = match ctx.data().config.reload() {
+        Ok(warnings) if warnings.is_empty() => "Config reloaded".to_string(),
+        Ok(warnings) => {
+            let warnings: String = warnings.iter().map(|w| format!("- {w}\n")).collect();
+            format!("Config reloaded, with warnings:\n{warnings}")
+        }
+        Err(err) => {
+            log::error!("Failed reloading config - {err}");
+            format!("Failed reloading config. Previous config still in use.\n```\n{err}\n```")
+        }
+    };
+
+    poise_check_msg(ctx.reply(text).await);
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    owners_only,
+    subcommands("list_features", "set_feature")
+)]
+pub async fn features(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!(
+        "/features command shouldn't be invoked directly. It should just house sub commands"
+    );
+    Ok(())
+}
+
+#[poise::command(slash_command, prefix_command, owners_only, rename = "list")]
+pub async fn list_features(ctx: PoiseContext<'_>) -> PoiseResult {
+    let text: String = FeatureFlag::ALL
+        .iter()
+        .map(|flag| {
+            let state = match features::enabled(ctx.data(), *flag) {
+                true => "on",
+                false => "off",
+            };
+            format!("`{flag}`: `{state}` - {}\n", flag.description())
+        })
+        .collect();
+
+    poise_check_msg(ctx.reply(text).await);
+    Ok(())
+}
+
+/// Turn a bot wide feature on or off, without redeploying
+#[poise::command(slash_command, prefix_command, owners_only, rename = "set")]
+pub async fn set_feature(
+    ctx: PoiseContext<'_>,
+    #[description = "Feature flag"] flag: FeatureFlag,
+    #[description = "Whether feature is turned on"] enabled: bool,
+) -> PoiseResult {
+    ctx.data()
+        .feature_flags_table()
+        .set_flag(flag.key(), enabled, ctx.author().id.get())?;
+
+    let state = match enabled {
+        true => "on",
+        false => "off",
+    };
+    log::warn!("Feature `{flag}` turned {state} by {}", ctx.author().id);
+
+    poise_check_msg(ctx.reply(format!("Feature `{flag}` turned {state}")).await);
+    Ok(())
+}
+
+#[poise::command(slash_command, prefix_command, owners_only, subcommands("list_jobs"))]
+pub async fn jobs(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/jobs command shouldn't be invoked directly. It should just house sub commands");
+    Ok(())
+}
+
+/// Display scheduled, running, and recently finished background jobs
+#[poise::command(slash_command, prefix_command, owners_only, rename = "list")]
+pub async fn list_jobs(ctx: PoiseContext<'_>) -> PoiseResult {
+    let rows = ctx.data().jobs_table().rows(20)?;
+    if rows.is_empty() {
+        poise_check_msg(ctx.reply("No jobs scheduled").await);
+        return Ok(());
+    }
+
+    let text: String = rows
+        .iter()
+        .map(|row| {
+            let guild = row
+                .guild_id
+                .map_or("bot wide".to_string(), |id| format!("guild `{id}`"));
+            let every = row
+                .interval_secs
+                .map_or(String::new(), |secs| format!(", every {}m", secs / 60));
+            let error = row
+                .last_error
+                .as_ref()
+                .map_or(String::new(), |err| format!(" - last error: {err}"));
+
+            format!(
+                "`{}` `{}` ({guild}): `{}`, runs <t:{}:R>{every}, {} runs{error}\n",
+                row.id,
+                row.kind,
+                row.status.as_str(),
+                row.run_at.timestamp(),
+                row.attempts,
+            )
+        })
+        .collect();
+
+    poise_check_msg(ctx.reply(text).await);
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    owners_only,
+    subcommands("database_maintenance")
+)]
+pub async fn database(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!(
+        "/database command shouldn't be invoked directly. It should just house sub commands"
+    );
+    Ok(())
+}
+
+/// Check integrity of the database, and vacuum & analyze it. Also runs weekly
+#[poise::command(
+    slash_command,
+    prefix_command,
+    owners_only,
+    rename = "maintenance",
+    custom_data = "LongRunning"
+)]
+pub async fn database_maintenance(
+    ctx: PoiseContext<'_>,
+    #[description = "Rebuild database file to reclaim free space. Defaults to true. Locks the database while running"]
+    vacuum: Option<bool>,
+) -> PoiseResult {
+    let reply = ctx.reply("Running database maintenance...").await?;
+
+    let db_pool = ctx.data().db_pool.clone();
+    let vacuum = vacuum.unwrap_or(true);
+    let report = tokio::task::spawn_blocking(move || db::maintenance::run(&db_pool.get()?, vacuum))
+        .await??;
+    log::info!("{report} - run by {}", ctx.author().id);
+
+    let problems: String = report
+        .integrity_errors
+        .iter()
+        .take(10)
+        .map(|problem| format!("\n- {problem}"))
+        .collect();
+    reply
+        .edit(
+            ctx,
+            CreateReply::default().content(format!("{report}{problems}")),
+        )
+        .await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, prefix_command, guild_only, subcommands("export_stats"))]
+pub async fn export(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/export command shouldn't be invoked directly. It should just house sub commands");
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "stats",
+    custom_data = "LongRunning",
+    check = "auth::check_moderator"
+)]
+pub async fn export_stats(
+    ctx: PoiseContext<'_>,
+    #[description = "File format. Defaults to csv"] format: Option<export::ExportFormat>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let operation = ctx.data().operations.start();
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content("Exporting stats...")
+                .components(vec![operation.cancel_action_row()])
+                .ephemeral(true),
+        )
+        .await?;
+
+    let db_pool = ctx.data().db_pool.clone();
+    let cancel = operation.token.clone();
+    let exported = tokio::task::spawn_blocking(move || {
+        export::export_stats(
+            &db_pool,
+            Some(guild_id.get()),
+            format.unwrap_or_default(),
+            &cancel,
+        )
+    })
+    .await?;
+    drop(operation);
+
+    let content = match &exported {
+        Ok(_) => "Export complete",
+        Err(err) if err.is::<OperationError>() => "Export cancelled",
+        Err(_) => "Export failed",
+    };
+    reply
+        .edit(
+            ctx,
+            CreateReply::default().content(content).components(vec![]),
+        )
+        .await
+        .log_err_msg("Failed to edit export reply")
+        .ok();
+
+    let files = match exported {
+        Ok(files) => files,
+        Err(err) if err.is::<OperationError>() => return Ok(()),
+        Err(err) => return Err(err),
+    };
+
+    helpers::send_export_files(
+        ctx,
+        "Play history of this server, and sound library metadata",
+        files,
+    )
+    .await
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    check = "auth::check_manage_sounds",
+    subcommands(
+        "create_api_key",
+        "list_api_keys",
+        "revoke_api_key",
+        "api_key_quota",
+        "api_key_stats"
+    )
+)]
+pub async fn apikey(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/apikey command shouldn't be invoked directly. It should just house sub commands");
+    Ok(())
+}
+
+/// Create a key for the HTTP API, acting as you in this server. The key is only shown once
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "create",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn create_api_key(
+    ctx: PoiseContext<'_>,
+    #[description = "Name of key, ie: what uses it"]
+    #[max_length = 32]
+    name: String,
+    #[description = "Space separated scopes the key gets, ie: `upload`. Defaults to every scope"]
+    scopes: Option<String>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let name = name.trim();
+
+    let scopes = match scopes.as_deref().map(ApiScope::parse) {
+        None => ApiScope::ALL.to_vec(),
+        Some(Ok(scopes)) if !scopes.is_empty() => scopes,
+        Some(Ok(_)) | Some(Err(_)) => {
+            let text = format!(
+                "Scopes must be some of: `{}`",
+                ApiScope::join(&ApiScope::ALL)
+            );
+            poise_check_msg(helpers::guild_reply(ctx, text).await);
+            return Ok(());
+        }
+    };
+
+    let key = api::new_key();
+    let created = ctx.data().api_keys_table().insert_row(
+        guild_id.get(),
+        ctx.author().id.get(),
+        name,
+        &api::hash_key(&key),
+        &scopes,
+    )?;
+    let content = match created {
+        true => format!(
+            "API key `{name}` created. Send it as `Authorization: Bearer {key}`. It won't be shown again"
+        ),
+        false => format!("An API key named `{name}` already exists"),
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+
+    if created {
+        helpers::send_audit_message(
+            ctx.http(),
+            ctx.data(),
+            Some(guild_id),
+            CreateMessage::new().content(format!(
+                "API key `{name}` created by <@{}>",
+                ctx.author().id
+            )),
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "list",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn list_api_keys(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let rows = ctx.data().api_keys_table().rows(guild_id.get())?;
+
+    let text = match rows.is_empty() {
+        true => "No API keys. Create one with `/apikey create`".to_string(),
+        false => rows
+            .iter()
+            .map(|row| {
+                let last_used = row.last_used_at.map_or("never used".to_string(), |at| {
+                    format!("last used <t:{}:R>", at.timestamp())
+                });
+                format!(
+                    "`{}` of <@{}>, scopes `{}`, {last_used}\n",
+                    row.name,
+                    row.user_id,
+                    ApiScope::join(&row.scopes)
+                )
+            })
+            .collect(),
+    };
+
+    ctx.send(CreateReply::default().content(text).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "revoke",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn revoke_api_key(
+    ctx: PoiseContext<'_>,
+    #[description = "Name of key"]
+    #[max_length = 32]
+    name: String,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let name = name.trim();
+
+    let revoked = ctx
+        .data()
+        .api_keys_table()
+        .delete_row(guild_id.get(), name)?;
+    let content = match revoked {
+        true => format!("API key `{name}` revoked"),
+        false => format!("No API key named `{name}`"),
+    };
+    poise_check_msg(helpers::guild_reply(ctx, content).await);
+
+    if revoked {
+        helpers::send_audit_message(
+            ctx.http(),
+            ctx.data(),
+            Some(guild_id),
+            CreateMessage::new().content(format!(
+                "API key `{name}` revoked by <@{}>",
+                ctx.author().id
+            )),
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+/// Limit requests an API key can make a day (UTC), so one integration can't hog the bot
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "quota",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn api_key_quota(
+    ctx: PoiseContext<'_>,
+    #[description = "Name of key"]
+    #[max_length = 32]
+    name: String,
+    #[description = "Requests the key can make a day. 0 removes the quota"] requests: u64,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let name = name.trim();
+    let quota = (requests > 0).then_some(requests);
+
+    let found = ctx
+        .data()
+        .api_keys_table()
+        .set_daily_quota(guild_id.get(), name, quota)?;
+    let content = match (found, quota) {
+        (false, _) => format!("No API key named `{name}`"),
+        (true, Some(quota)) => format!("API key `{name}` can make {quota} requests a day"),
+        (true, None) => format!("API key `{name}` daily quota removed"),
+    };
+    poise_check_msg(helpers::guild_reply(ctx, content.clone()).await);
+
+    if found {
+        helpers::send_audit_message(
+            ctx.http(),
+            ctx.data(),
+            Some(guild_id),
+            CreateMessage::new().content(format!("{content}, set by <@{}>", ctx.author().id)),
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+/// Requests made with this server's API keys, busiest key first
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "stats",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn api_key_stats(
+    ctx: PoiseContext<'_>,
+    #[description = "Days to count, including today. Defaults to 7"]
+    #[min = 1]
+    #[max = 90]
+    days: Option<u32>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let days = days.unwrap_or(7);
+    let now = chrono::Utc::now();
+
+    let usage_table = ctx.data().api_usage_table();
+    let since = now - chrono::Duration::days(days as i64 - 1);
+    let usage = usage_table.usage_since(Some(guild_id.get()), since)?;
+    let today: HashMap<i64, u64> = usage_table
+        .usage_since(Some(guild_id.get()), now)?
+        .into_iter()
+        .map(|usage| (usage.key_id, usage.requests))
+        .collect();
+    let quotas: HashMap<i64, u64> = ctx
+        .data()
+        .api_keys_table()
+        .rows(guild_id.get())?
+        .into_iter()
+        .filter_map(|row| Some((row.id, row.daily_quota?)))
+        .collect();
+
+    if usage.is_empty() {
+        let text = format!("No API requests in the last {days} days");
+        poise_check_msg(helpers::guild_reply(ctx, text).await);
+        return Ok(());
+    }
+
+    let mut text = format!("API usage of the last {days} days:\n");
+    for key in usage {
+        let name = match &key.key_name {
+            Some(name) => format!("`{name}`"),
+            None => format!("`#{}` (revoked)", key.key_id),
+        };
+        let requests_today = today.get(&key.key_id).copied().unwrap_or_default();
+        let today = match quotas.get(&key.key_id) {
+            Some(quota) => format!("{requests_today} of {quota} today"),
+            None => format!("{requests_today} today"),
+        };
+        text.push_str(&format!(
+            "{name}: {} requests, {} failed, {} rejected. {today}\n",
+            key.requests, key.failures, key.rejected
+        ));
+    }
+
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("export_guild_data", "delete_guild_data")
+)]
+pub async fn guilddata(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!(
+        "/guilddata command shouldn't be invoked directly. It should just house sub commands"
+    );
+    Ok(())
+}
+
+/// Export everything the bot stores about this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "export",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn export_guild_data(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content("Exporting server data...")
+                .ephemeral(true),
+        )
+        .await?;
+
+    let db_pool = ctx.data().db_pool.clone();
+    let exported =
+        tokio::task::spawn_blocking(move || guild_data::export(&db_pool, guild_id)).await?;
+
+    let content = match &exported {
+        Ok(_) => "Export complete",
+        Err(_) => "Export failed",
+    };
+    reply
+        .edit(ctx, CreateReply::default().content(content))
+        .await
+        .log_err_msg("Failed to edit guild data export reply")
+        .ok();
+
+    helpers::send_export_files(
+        ctx,
+        "Settings, bans, play history, sound requests, and other data of this server",
+        vec![exported?],
+    )
+    .await
+}
+
+/// Delete everything the bot stores about this server, once confirmed
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "delete",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn delete_guild_data(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    ctx.send(
+        CreateReply::default()
+            .content(
+                "Delete all of this server's settings, bans, play history & stats, sound requests, \
+                and scheduled jobs? This can't be undone. Sounds are shared by every server, so they're kept",
+            )
+            .components(vec![guild_data::confirm_delete_action_row(guild_id)])
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    subcommands("activity_stats")
+)]
+pub async fn stats(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/stats command shouldn't be invoked directly. It should just house sub commands");
+    Ok(())
+}
+
+/// Heatmap of plays per weekday & hour of day
+#[poise::command(slash_command, prefix_command, guild_only, rename = "activity")]
+pub async fn activity_stats(
+    ctx: PoiseContext<'_>,
+    #[description = "Plays of the last this many days. Defaults to 30"]
+    #[min = 1]
+    #[max = 3650]
+    days: Option<u32>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let days = days.unwrap_or(30);
+    let since = chrono::Utc::now() - chrono::Duration::days(days as i64);
+
+    let times = ctx
+        .data()
+        .play_history_table()
+        .played_at_since(guild_id.get(), since)?;
+    let timezone = settings::timezone(&ctx.data().settings_table(), guild_id);
+    let heatmap = activity::Heatmap::from_times(&times, timezone);
+
+    let mut embed = serenity::all::CreateEmbed::new()
+        .title(format!("Soundboard activity of the last {days} days"))
+        .description(heatmap.render())
+        .field("Plays", heatmap.total().to_string(), true);
+    if let Some((day, hour)) = heatmap.busiest_hour() {
+        embed = embed.field("Busiest Hour", format!("{day} {hour:02}:00"), true);
+    }
+    if let Some(day) = heatmap.busiest_day() {
+        embed = embed.field("Busiest Day", day.to_string(), true);
+    }
+    let embed = branding::Branding::of(ctx.data(), Some(guild_id)).apply_with_footer(
+        embed,
+        Some(format!(
+            "Hours run from midnight (left) to 11pm (right), UTC{timezone}"
+        )),
+    );
+
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Delete the bot's old soundboards & play messages in this channel
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES"
+)]
+pub async fn cleanup(
+    ctx: PoiseContext<'_>,
+    #[description = "Delete messages older than this many hours. Defaults to the message_retention setting"]
+    #[max = 8760]
+    older_than: Option<u32>,
+    #[description = "Newest messages to keep. Defaults to the message_retention_count setting"]
+    keep: Option<u32>,
+    #[description = "Count the messages that would be deleted, without deleting them"]
+    #[flag]
+    dry_run: bool,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    ctx.defer_ephemeral().await?;
+
+    let mut policy = retention::RetentionPolicy::guild(&ctx.data().settings_table(), guild_id);
+    if let Some(hours) = older_than {
+        policy.max_age = Some(chrono::Duration::hours(hours as i64));
+    }
+    if let Some(keep) = keep {
+        policy.keep = Some(keep);
+    }
+    // nothing configured or given, clean up everything
+    if policy.keeps_all() {
+        policy.keep = Some(0);
+    }
+
+    let deleted = retention::cleanup(
+        ctx.http(),
+        &ctx.data().db_pool,
+        guild_id,
+        Some(ctx.channel_id()),
+        policy,
+        dry_run,
+    )
+    .await?;
+
+    log::info!(
+        "Cleaned up {deleted} bot messages. guild_id: {guild_id}, channel_id: {}, dry_run: {dry_run}",
+        ctx.channel_id()
+    );
+    let content = match dry_run {
+        true => format!("Would delete {deleted} old bot messages (dry run)"),
+        false => format!("Deleted {deleted} old bot messages"),
+    };
+    ctx.send(CreateReply::default().content(content).ephemeral(true))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,642 @@
+use poise::CreateReply;
+use serenity::all::CreateMessage;
+
+use crate::{
+    access,
+    auth::{self, AuthContext},
+    board_order, branding,
+    db::{self, BoardLayout, Tags},
+    errors, event_mode,
+    helpers::{self, poise_check_msg},
+    hotbar, themes,
+};
+
+use super::{PoiseCommand, PoiseContext, PoiseError, PoiseResult};
+
+/// Top level commands of this module, see [`super::all`]
+pub fn commands() -> Vec<PoiseCommand> {
+    vec![
+        hotbar(),
+        themes(),
+        branding(),
+        board(),
+        eventmode(),
+        access(),
+    ]
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("show_hotbar", "set_hotbar", "clear_hotbar")
+)]
+pub async fn hotbar(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/hotbar command shouldn't be invoked directly. It should just house sub commands");
+    Ok(())
+}
+
+/// Display your hotbar of up to 9 sounds, a board only you can see
+#[poise::command(slash_command, guild_only, rename = "show")]
+pub async fn show_hotbar(ctx: PoiseContext<'_>) -> PoiseResult {
+    let data = ctx.data();
+    let audio_table = data.audio_table();
+    // slots of deleted sounds show as empty
+    let slots: Vec<_> = data
+        .user_prefs_table()
+        .hotbar(ctx.author().id.get())?
+        .into_iter()
+        .filter_map(|(slot, audio_id)| {
+            audio_table
+                .find_audio_row(db::UniqueAudioTableCol::Id(audio_id))
+                .map(|row| (slot, row))
+        })
+        .collect();
+    let (content, components) = hotbar::board(&slots);
+
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .components(components)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Put a sound in a slot of your hotbar
+#[poise::command(slash_command, guild_only, rename = "set")]
+pub async fn set_hotbar(
+    ctx: PoiseContext<'_>,
+    #[description = "Hotbar slot, from 1 to 9"]
+    #[min = 1]
+    #[max = 9]
+    slot: u32,
+    #[description = "Sound to put in the slot"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    sound: String,
+) -> PoiseResult {
+    let row = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(sound.clone()))
+        .ok_or(errors::AudioError::AudioTrackNotFound { track: sound })?;
+    let slot = slot.clamp(1, hotbar::HOTBAR_SLOTS);
+
+    ctx.data()
+        .user_prefs_table()
+        .set_hotbar_slot(ctx.author().id.get(), slot, Some(row.id))?;
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!("Hotbar slot {slot} set to `{}`", row.name))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Empty a slot of your hotbar
+#[poise::command(slash_command, guild_only, rename = "clear")]
+pub async fn clear_hotbar(
+    ctx: PoiseContext<'_>,
+    #[description = "Hotbar slot, from 1 to 9"]
+    #[min = 1]
+    #[max = 9]
+    slot: u32,
+) -> PoiseResult {
+    ctx.data()
+        .user_prefs_table()
+        .set_hotbar_slot(ctx.author().id.get(), slot, None)?;
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!("Hotbar slot {slot} cleared"))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    subcommands("list_themes", "add_theme", "remove_theme")
+)]
+pub async fn themes(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/themes command shouldn't be invoked directly. It should just house sub commands");
+    Ok(())
+}
+
+/// Switch guild's featured theme right away, instead of waiting on the theme job
+fn featured_theme_text(
+    ctx: PoiseContext<'_>,
+    guild_id: serenity::all::GuildId,
+) -> Result<String, PoiseError> {
+    let data = ctx.data();
+    themes::switch_guild_theme(&data.board_themes_table(), &data.settings_table(), guild_id)?;
+
+    Ok(
+        match data.board_themes_table().active_tag(guild_id.get())? {
+            Some(tag) => format!("Featured on the board today: `{tag}`"),
+            None => "No theme is featured today".to_string(),
+        },
+    )
+}
+
+#[poise::command(slash_command, prefix_command, guild_only, rename = "list")]
+pub async fn list_themes(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let rows = ctx.data().board_themes_table().rows(guild_id.get())?;
+
+    let text = match rows.is_empty() {
+        true => "No themes scheduled. Add one with `/themes add`".to_string(),
+        false => rows
+            .iter()
+            .map(|row| {
+                let featured = if row.active { " ✨ featured" } else { "" };
+                format!(
+                    "`{}` `{}` {} to {}{featured}\n",
+                    row.id, row.tag, row.start_day, row.end_day
+                )
+            })
+            .collect(),
+    };
+
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+/// Feature sounds tagged with tag at the top of the board every year, between two days
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "add",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn add_theme(
+    ctx: PoiseContext<'_>,
+    #[description = "Tag of sounds to feature"]
+    #[max_length = 32]
+    tag: String,
+    #[description = "First day featured, as MM-DD (ie: 10-01)"] start: String,
+    #[description = "Last day featured, as MM-DD (ie: 10-31)"] end: String,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let tag = Tags::clean_tag(&tag);
+    let (Some(start), Some(end)) = (themes::parse_day(&start), themes::parse_day(&end)) else {
+        poise_check_msg(
+            helpers::guild_reply(ctx, "Days must be a month & day, as `MM-DD` (ie: `10-31`)").await,
+        );
+        return Ok(());
+    };
+    if tag.is_empty() || tag.contains(char::is_whitespace) {
+        poise_check_msg(helpers::guild_reply(ctx, "Theme must be a single tag").await);
+        return Ok(());
+    }
+
+    let id = ctx
+        .data()
+        .board_themes_table()
+        .insert_row(guild_id.get(), &tag, &start, &end)?;
+    let featured = featured_theme_text(ctx, guild_id)?;
+
+    poise_check_msg(
+        helpers::guild_reply(
+            ctx,
+            format!("Added theme {id}: `{tag}` from {start} to {end}. {featured}"),
+        )
+        .await,
+    );
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "remove",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn remove_theme(
+    ctx: PoiseContext<'_>,
+    #[description = "Id of theme, from /themes list"] id: i64,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let text = match ctx
+        .data()
+        .board_themes_table()
+        .delete_row(guild_id.get(), id)?
+    {
+        true => format!(
+            "Removed theme {id}. {}",
+            featured_theme_text(ctx, guild_id)?
+        ),
+        false => format!("No theme {id} found"),
+    };
+
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands(
+        "show_branding",
+        "branding_color",
+        "branding_footer",
+        "branding_icon",
+        "reset_branding"
+    )
+)]
+pub async fn branding(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!(
+        "/branding command shouldn't be invoked directly. It should just house sub commands"
+    );
+    Ok(())
+}
+
+/// Show how the bot's embeds look in this server
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "show",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn show_branding(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let branding = branding::Branding::load(&ctx.data().settings_table(), guild_id);
+
+    let embed = serenity::all::CreateEmbed::new()
+        .title("Branding")
+        .description(match branding.is_empty() {
+            true => "Not set, embeds have discord's default look",
+            false => "Embeds in this server look like this one",
+        })
+        .field(
+            "Color",
+            branding
+                .color
+                .map_or("-".to_string(), |color| format!("`#{}`", color.hex())),
+            true,
+        )
+        .field("Footer", branding.footer.as_deref().unwrap_or("-"), true)
+        .field("Icon", branding.icon_url.as_deref().unwrap_or("-"), true);
+
+    ctx.send(
+        CreateReply::default()
+            .embed(branding.apply(embed))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Set color of the bot's embeds. Leave out color to clear it
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "color",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn branding_color(
+    ctx: PoiseContext<'_>,
+    #[description = "Hex color, ie: #5865F2"] color: Option<String>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let color = match color.as_deref().map(branding::parse_color) {
+        Some(None) => {
+            poise_check_msg(
+                helpers::guild_reply(ctx, "Color must be a hex color, ie: `#5865F2`").await,
+            );
+            return Ok(());
+        }
+        Some(Some(color)) => Some(color),
+        None => None,
+    };
+    branding::set_color(&ctx.data().settings_table(), guild_id, color)?;
+
+    let text = match color {
+        Some(color) => format!("Embed color set to `#{}`", color.hex()),
+        None => "Embed color cleared".to_string(),
+    };
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+/// Set footer text of the bot's embeds. Leave out text to clear it
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "footer",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn branding_footer(
+    ctx: PoiseContext<'_>,
+    #[description = "Footer text, ie: Hosted by Example"]
+    #[max_length = 200]
+    text: Option<String>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let text = text
+        .map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty());
+
+    if text
+        .as_ref()
+        .is_some_and(|text| text.chars().count() > branding::FOOTER_MAX_LEN)
+    {
+        poise_check_msg(
+            helpers::guild_reply(
+                ctx,
+                format!(
+                    "Footer can be at most {} characters",
+                    branding::FOOTER_MAX_LEN
+                ),
+            )
+            .await,
+        );
+        return Ok(());
+    }
+    branding::set_footer(&ctx.data().settings_table(), guild_id, text.clone())?;
+
+    let reply = match text {
+        Some(text) => format!("Embed footer set to `{text}`"),
+        None => "Embed footer cleared".to_string(),
+    };
+    poise_check_msg(helpers::guild_reply(ctx, reply).await);
+    Ok(())
+}
+
+/// Set icon shown in the footer of the bot's embeds. Leave out url to clear it
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "icon",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn branding_icon(
+    ctx: PoiseContext<'_>,
+    #[description = "Image url, ie: https://example.com/icon.png"] url: Option<String>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let icon_url = match url.as_deref().map(branding::parse_icon_url) {
+        Some(None) => {
+            poise_check_msg(helpers::guild_reply(ctx, "Icon must be an http(s) image url").await);
+            return Ok(());
+        }
+        Some(Some(icon_url)) => Some(icon_url),
+        None => None,
+    };
+    branding::set_icon_url(&ctx.data().settings_table(), guild_id, icon_url.clone())?;
+
+    let text = match icon_url {
+        Some(icon_url) => format!("Embed icon set to <{icon_url}>"),
+        None => "Embed icon cleared".to_string(),
+    };
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+/// Clear color, footer, and icon of the bot's embeds
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "reset",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn reset_branding(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    branding::reset(&ctx.data().settings_table(), guild_id)?;
+
+    poise_check_msg(helpers::guild_reply(ctx, "Embed branding reset").await);
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, subcommands("reorder_board"))]
+pub async fn board(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/board command shouldn't be invoked directly. It should just house sub commands");
+    Ok(())
+}
+
+/// Move a sound on the board, then nudge it further with buttons
+#[poise::command(slash_command, guild_only, rename = "reorder")]
+pub async fn reorder_board(
+    ctx: PoiseContext<'_>,
+    #[description = "Sound to move"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    sound: String,
+    #[description = "Board position to move sound to. Defaults to where it is"]
+    #[min = 1]
+    position: Option<usize>,
+    #[description = "Board to reorder. Defaults to your board layout"] board: Option<BoardLayout>,
+) -> PoiseResult {
+    let data = ctx.data();
+    let user_id = ctx.author().id.get();
+    let board = match board {
+        Some(board) => board,
+        None => data.user_prefs_table().get_prefs(user_id)?.board,
+    };
+    // the full board's order is shared, favorites boards are ordered by their user
+    if board == BoardLayout::Full {
+        ctx.permissions().await?.check_manage_sounds()?;
+    }
+
+    let row = data
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(sound.clone()))
+        .ok_or(errors::AudioError::AudioTrackNotFound { track: sound })?;
+
+    let table = data.board_order_table();
+    let ids = match position {
+        Some(position) => {
+            board_order::move_sound(&table, board, user_id, row.id, &row.name, position)?
+        }
+        None => table.board_audio_ids(board, user_id)?,
+    };
+    let (content, components) =
+        board_order::reorder_reply(&data.audio_table(), board, &ids, row.id, &row.name)?;
+
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .components(components)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    check = "auth::check_moderator",
+    subcommands("enable_event_mode", "disable_event_mode", "event_mode_status")
+)]
+pub async fn eventmode(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!(
+        "/eventmode command shouldn't be invoked directly. It should just house sub commands"
+    );
+    Ok(())
+}
+
+/// Lift cooldowns & play quotas, and raise the overlapping sound limit, for a community event
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "enable",
+    check = "auth::check_moderator"
+)]
+pub async fn enable_event_mode(
+    ctx: PoiseContext<'_>,
+    #[description = "How long event mode lasts, ie: 90m, 3h, 1d. Up to 7 days"] duration: String,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let Some(duration) = event_mode::parse_duration(&duration)
+        .filter(|duration| *duration <= event_mode::MAX_EVENT_DURATION)
+    else {
+        poise_check_msg(
+            helpers::guild_reply(
+                ctx,
+                "Duration must be minutes, hours, or days up to 7 days (ie: `90m`, `3h`, `1d`)",
+            )
+            .await,
+        );
+        return Ok(());
+    };
+
+    let until = chrono::Utc::now() + duration;
+    let data = ctx.data();
+    event_mode::enable(&data.settings_table(), &data.jobs_table(), guild_id, until)?;
+    log::info!("Event mode enabled until {until}. guild_id: {guild_id}");
+
+    let text = format!(
+        "Event mode enabled until <t:{}:f>. Cooldowns, play quotas, and queue limits are lifted, and up to {} sounds can overlap",
+        until.timestamp(),
+        event_mode::EVENT_MAX_OVERLAPPING_TRACKS
+    );
+    poise_check_msg(helpers::guild_reply(ctx, &text).await);
+
+    helpers::send_audit_message(
+        ctx.http(),
+        data,
+        ctx.guild_id(),
+        CreateMessage::new().content(format!("{text} by <@{}>", ctx.author().id)),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// End event mode early
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "disable",
+    check = "auth::check_moderator"
+)]
+pub async fn disable_event_mode(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    if !event_mode::disable(&ctx.data().settings_table(), guild_id)? {
+        poise_check_msg(helpers::guild_reply(ctx, "Event mode isn't on").await);
+        return Ok(());
+    }
+    log::info!("Event mode disabled. guild_id: {guild_id}");
+
+    let text = "Event mode disabled. Cooldowns, play quotas, and queue limits are back";
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+
+    helpers::send_audit_message(
+        ctx.http(),
+        ctx.data(),
+        ctx.guild_id(),
+        CreateMessage::new().content(format!("{text} by <@{}>", ctx.author().id)),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Whether event mode is on, and when it ends
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "status",
+    check = "auth::check_moderator"
+)]
+pub async fn event_mode_status(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let text = match event_mode::active_until(&ctx.data().settings_table(), guild_id) {
+        Some(until) => format!("Event mode is on until <t:{}:f>", until.timestamp()),
+        None => "Event mode is off".to_string(),
+    };
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_ROLES",
+    subcommands("post_access")
+)]
+pub async fn access(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/access command shouldn't be invoked directly. It should just house sub commands");
+    Ok(())
+}
+
+/// Post a message with a button members press to get or give up the soundboard user role
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "post",
+    required_permissions = "MANAGE_ROLES"
+)]
+pub async fn post_access(
+    ctx: PoiseContext<'_>,
+    #[description = "Text of the message"]
+    #[max_length = 1000]
+    text: Option<String>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let Some(role_id) = crate::settings::user_role(&ctx.data().settings_table(), guild_id) else {
+        poise_check_msg(
+            helpers::guild_reply(
+                ctx,
+                "Set a soundboard user role first, with `/settings set user_role {role}`",
+            )
+            .await,
+        );
+        return Ok(());
+    };
+
+    // not tracked as a bot message, so message retention doesn't delete it
+    poise_check_msg(
+        ctx.send(
+            CreateReply::default()
+                .content(access::access_message(role_id, text.as_deref()))
+                .components(vec![access::access_action_row()])
+                .allowed_mentions(serenity::all::CreateAllowedMentions::new()),
+        )
+        .await,
+    );
+    Ok(())
+}
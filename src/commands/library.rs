@@ -0,0 +1,1946 @@
+use poise::{CreateReply, Modal};
+use serenity::all::{CreateAttachment, CreateMessage};
+use tokio::sync::watch;
+
+use crate::{
+    attachments, audio,
+    auth::{self, AuthContext},
+    branding, collision,
+    common::LogResult,
+    db::{self, AudioTable, BoardLayout, Tags},
+    errors::{self, AuthError, OperationError},
+    features::{self, FeatureFlag},
+    fingerprint,
+    helpers::{self, poise_check_msg, LongRunning},
+    ingest, metadata, naming, packs, posted_boards, purge, recent, reconcile, requests, scan,
+    search, settings, shuffle, suggest, trim, undo, vars,
+};
+
+use super::{PoiseAppContext, PoiseCommand, PoiseContext, PoiseError, PoiseResult};
+
+/// Top level commands of this module, see [`super::all`]
+pub fn commands() -> Vec<PoiseCommand> {
+    vec![
+        sounds(),
+        undo_operation(),
+        scan(),
+        search_sounds(),
+        suggest(),
+        request_sound(),
+        packs(),
+    ]
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    subcommands(
+        "add_sound",
+        "remove_sound",
+        "protect_sound",
+        "cooldown_sound",
+        "restrict_sound",
+        "unrestrict_sound",
+        "purge_sounds",
+        "display_sounds",
+        "random_button_board",
+        "recent_sounds",
+        "edit_sound",
+        "set_join_audio",
+        "set_leave_audio",
+        "favorite_sound",
+        "merge_sounds",
+        "replace_sound",
+        "trim_sound",
+        "stale_sounds",
+        "reconcile_sound",
+        "sound_info",
+        "import_metadata",
+        "display_help"
+    )
+)]
+pub async fn sounds(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/sounds command shouldn't be invoked direclty. It should just house sub commands");
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only, custom_data = "LongRunning")]
+pub async fn scan(
+    ctx: PoiseContext<'_>,
+    #[description = "Report changes without writing to database"]
+    #[flag]
+    dry_run: bool,
+) -> PoiseResult {
+    log::info!("Scanning audio files... dry_run: {dry_run}");
+
+    let operation = ctx.data().operations.start();
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content("Scanning audio files...")
+                .components(vec![operation.cancel_action_row()]),
+        )
+        .await?;
+
+    let branding = branding::Branding::of(ctx.data(), ctx.guild_id());
+
+    // rows already in database, so unchanged audio files can be skipped
+    let mut known_rows = vec![];
+    let paginator = db::AudioTablePaginator::builder(ctx.data().db_connection()).build();
+    for page in paginator {
+        known_rows.extend(page.log_err()?);
+    }
+
+    let config = ctx.data().config.get();
+    let scanner = scan::AudioDirScanner::new()
+        .max_audio_duration(config.max_audio_file_duration)
+        .max_file_size(config.max_audio_file_size)
+        .concurrency(config.scan_concurrency);
+
+    let (progress_tx, mut progress_rx) = watch::channel(scan::ScanProgress::default());
+
+    // edit reply with latest progress, at most once per second
+    let report_progress = async {
+        while progress_rx.changed().await.is_ok() {
+            let progress = progress_rx.borrow_and_update().clone();
+            reply
+                .edit(
+                    ctx,
+                    CreateReply::default().content(format!("Scanning audio files... {progress}")),
+                )
+                .await
+                .log_err_msg("Failed to edit scan progress reply")
+                .ok();
+
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+    };
+
+    let (report, _) = tokio::join!(
+        scanner.scan(
+            ctx.data().read_audio_dir(),
+            known_rows,
+            progress_tx,
+            &operation.token
+        ),
+        report_progress
+    );
+    drop(operation);
+
+    let Ok(report) = report else {
+        reply
+            .edit(
+                ctx,
+                CreateReply::default()
+                    .content("Scan cancelled - no changes were written")
+                    .components(vec![]),
+            )
+            .await
+            .log_err_msg("Failed to edit scan reply")
+            .ok();
+
+        log::info!("Scan cancelled");
+        return Ok(());
+    };
+
+    log::info!(
+        "Scan found {} new, {} changed, {} renamed, {} removed audio files",
+        report.new.len(),
+        report.changed.len(),
+        report.renamed.len(),
+        report.removed.len()
+    );
+
+    if dry_run {
+        let embed = report
+            .summary_embed(0, &branding)
+            .title("Scan Summary (dry run)");
+        reply
+            .edit(
+                ctx,
+                CreateReply::default()
+                    .content("Scan complete (dry run) - no changes were written")
+                    .embed(embed)
+                    .components(vec![]),
+            )
+            .await
+            .log_err_msg("Failed to edit scan reply")
+            .ok();
+
+        log::info!("Scan complete (dry run)");
+        return Ok(());
+    }
+
+    let table = AudioTable::new(ctx.data().db_connection());
+    let name_collision = ctx
+        .guild_id()
+        .map(|guild_id| settings::name_collision(&ctx.data().settings_table(), guild_id))
+        .unwrap_or_default();
+    let failed_writes = report.apply(&table, &ctx.data().config.get(), name_collision);
+    let embed = report.summary_embed(failed_writes, &branding);
+
+    reply
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content("Scan complete")
+                .embed(embed.clone())
+                .components(vec![]),
+        )
+        .await
+        .log_err_msg("Failed to edit scan reply")
+        .ok();
+
+    helpers::send_audit_message(
+        ctx.http(),
+        ctx.data(),
+        ctx.guild_id(),
+        CreateMessage::new().embed(embed),
+    )
+    .await;
+
+    log::info!("Scan complete");
+    Ok(())
+}
+
+#[derive(Debug, poise::Modal)]
+#[name = "Add Sound"]
+struct AddSoundModal {
+    #[name = "Name"] // Field name by default
+    #[placeholder = "Use The Force Luke"] // No placeholder by default
+    #[min_length = 3] // No length restriction by default (so, 1-4000 chars)
+    #[max_length = 80] // Same as max button label len (crate::vars::BTN_LABEL_MAX_LEN)
+    name: String,
+
+    #[name = "Tags"] // Field name by default
+    #[placeholder = "star Wars jedi luke skywalker force episode iv"] // No placeholder by default
+    #[max_length = 1024]
+    tags: Option<String>,
+
+    #[name = "Audio URL"]
+    #[placeholder = "www.example.com/use-the-force.mp3"]
+    #[max_length = 2048]
+    url: String,
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "add",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn add_sound(ctx: PoiseAppContext<'_>) -> PoiseResult {
+    let data = AddSoundModal::execute(ctx)
+        .await?
+        .ok_or("AddSoundModal not set")
+        .log_err()?;
+
+    let tags: Tags = match data.tags {
+        Some(val) => Tags::from(val),
+        None => Tags::new(),
+    };
+
+    let name = naming::normalize(&ctx.data().config.get(), &data.name)?;
+
+    // let uploader choose what happens to a name that's taken, when the server asks first
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let policy = settings::name_collision(&ctx.data().settings_table(), guild_id);
+    let existing = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(name.clone()));
+    if let (Some(existing), collision::NameCollision::Overwrite) = (existing, policy) {
+        let content = format!(
+            "A sound named `{}` already exists. Overwrite its audio, or keep both?",
+            existing.name
+        );
+        let id = ctx
+            .data()
+            .pending_imports
+            .insert(collision::PendingImport::new(
+                name,
+                data.url,
+                tags,
+                ctx.author().clone(),
+                existing.id,
+            ));
+
+        ctx.send(
+            CreateReply::default()
+                .content(content)
+                .components(vec![collision::choice_action_row(id)])
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let name = collision::resolve_name(ctx.data(), Some(guild_id), &name)?;
+    let operation = ctx.data().operations.start();
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content(format!("Adding sound `{name}`..."))
+                .components(vec![operation.cancel_action_row()]),
+        )
+        .await?;
+
+    let (progress_tx, mut progress_rx) = watch::channel(ingest::IngestStage::default());
+
+    // edit reply with latest stage, at most once per second
+    let report_progress = async {
+        while progress_rx.changed().await.is_ok() {
+            let stage = *progress_rx.borrow_and_update();
+            reply
+                .edit(
+                    ctx.into(),
+                    CreateReply::default().content(format!("Adding sound `{name}`... {stage}")),
+                )
+                .await
+                .log_err_msg("Failed to edit add sound progress reply")
+                .ok();
+
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+    };
+
+    let ingest = ingest::Ingest::new(ctx.data())
+        .progress(progress_tx)
+        .cancel(operation.token.clone());
+    let ingest_sound = async {
+        let target = ingest::IngestTarget::New {
+            name: &name,
+            tags,
+            author: ctx.author(),
+        };
+        let result = ingest
+            .run(ingest::IngestSource::Url(data.url), target)
+            .await;
+        drop(ingest);
+        result
+    };
+    let (result, _) = tokio::join!(ingest_sound, report_progress);
+    drop(operation);
+
+    let content = match &result {
+        Ok(_) => format!("Added sound `{name}` to soundboard"),
+        Err(err) if err.is::<OperationError>() => format!("Cancelled adding sound `{name}`"),
+        Err(_) => format!("Failed adding sound `{name}`"),
+    };
+    reply
+        .edit(
+            ctx.into(),
+            CreateReply::default().content(content).components(vec![]),
+        )
+        .await
+        .log_err_msg("Failed to edit add sound reply")
+        .ok();
+
+    match result {
+        Err(err) if !err.is::<OperationError>() => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "remove",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn remove_sound(
+    ctx: PoiseContext<'_>,
+    #[rename = "track"]
+    #[description = "Track to play"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+    #[description = "Remove the sound even if it's protected. Server admins only"]
+    #[flag]
+    force: bool,
+    #[description = "Reject future uploads of the sound's audio, even renamed or re-encoded"]
+    #[flag]
+    ban: bool,
+) -> PoiseResult {
+    log::info!("Removing audio track - {audio_track_name}. ban: {ban}");
+    let row = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()));
+    let protected = row.as_ref().is_some_and(|row| row.protected);
+    ctx.permissions()
+        .await?
+        .check_unprotected(&audio_track_name, protected, force)?;
+
+    let Some(row) = row else {
+        log::info!("Can't delete non-existent audio track - {audio_track_name}");
+        poise_check_msg(
+            ctx.reply(format!("Removed audio track `{audio_track_name}`"))
+                .await,
+        );
+        return Ok(());
+    };
+
+    let mut banned_sound_id = None;
+    if ban {
+        let path = row.audio_file.as_path_buf();
+        let fingerprint = tokio::task::spawn_blocking(move || fingerprint::fingerprint_file(path))
+            .await?
+            .log_err_msg("Failed fingerprinting banned sound")
+            .ok();
+        banned_sound_id = Some(ctx.data().banned_sounds_table().insert_row(
+            &row.name,
+            row.file_hash.as_deref(),
+            fingerprint.as_ref(),
+            ctx.author().id.get(),
+        )?);
+    }
+
+    undo::delete_sound(
+        &ctx.data().db_pool,
+        &ctx.data().config.get().audio_dir,
+        &row,
+        ctx.guild_id(),
+        ctx.author().id,
+        banned_sound_id,
+    )?;
+    let text = match ban {
+        true => format!("Removed & banned audio track `{audio_track_name}`"),
+        false => format!("Removed audio track `{audio_track_name}`"),
+    };
+    poise_check_msg(ctx.reply(text).await);
+
+    log::info!("Audio track removed {audio_track_name}");
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "protect",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn protect_sound(
+    ctx: PoiseContext<'_>,
+    #[rename = "track"]
+    #[description = "Sound to protect"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+    #[description = "Protect the sound from being deleted, renamed, or bulk edited. Defaults to true"]
+    protected: Option<bool>,
+) -> PoiseResult {
+    let protected = protected.unwrap_or(true);
+    log::info!("Setting protected: {protected} for audio track - {audio_track_name}");
+
+    let row = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()))
+        .ok_or_else(|| errors::AudioError::AudioTrackNotFound {
+            track: audio_track_name.clone(),
+        })?;
+    ctx.data().audio_table().set_protected(row.id, protected)?;
+
+    let text = match protected {
+        true => format!("Sound `{}` protected", row.name),
+        false => format!("Sound `{}` unprotected", row.name),
+    };
+    poise_check_msg(ctx.reply(&text).await);
+
+    helpers::send_audit_message(
+        ctx.http(),
+        ctx.data(),
+        ctx.guild_id(),
+        CreateMessage::new().content(format!("{text} by <@{}>", ctx.author().id)),
+    )
+    .await;
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "cooldown",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn cooldown_sound(
+    ctx: PoiseContext<'_>,
+    #[rename = "track"]
+    #[description = "Sound to set the cooldown of"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+    #[description = "Seconds after a play before the sound can be played again. 0 removes the cooldown"]
+    #[max = 86400]
+    seconds: u32,
+) -> PoiseResult {
+    log::info!("Setting cooldown: {seconds}s for audio track - {audio_track_name}");
+
+    let row = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()))
+        .ok_or_else(|| errors::AudioError::AudioTrackNotFound {
+            track: audio_track_name.clone(),
+        })?;
+    let cooldown = (seconds > 0).then_some(seconds);
+    ctx.data().audio_table().set_cooldown(row.id, cooldown)?;
+
+    let text = match cooldown {
+        Some(seconds) => format!("Sound `{}` cooldown set to {seconds} seconds", row.name),
+        None => format!("Sound `{}` cooldown removed", row.name),
+    };
+    poise_check_msg(helpers::guild_reply(ctx, &text).await);
+
+    helpers::send_audit_message(
+        ctx.http(),
+        ctx.data(),
+        ctx.guild_id(),
+        CreateMessage::new().content(format!("{text} by <@{}>", ctx.author().id)),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Only let a role or member play a sound. Restrict it again to let more roles or members play it
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "restrict-to",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn restrict_sound(
+    ctx: PoiseContext<'_>,
+    #[rename = "track"]
+    #[description = "Sound to restrict"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+    #[description = "Role allowed to play the sound"] role: Option<serenity::all::Role>,
+    #[description = "Member allowed to play the sound"] user: Option<serenity::all::User>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    log::info!("Restricting audio track - {audio_track_name}. guild_id: {guild_id}");
+
+    if role.is_none() && user.is_none() {
+        poise_check_msg(
+            helpers::guild_reply(ctx, "Pick a role or member to restrict the sound to").await,
+        );
+        return Ok(());
+    }
+
+    let row = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()))
+        .ok_or_else(|| errors::AudioError::AudioTrackNotFound {
+            track: audio_track_name.clone(),
+        })?;
+
+    let table = ctx.data().sound_restrictions_table();
+    if let Some(role) = &role {
+        table.insert_row(guild_id.get(), row.id, Some(role.id.get()), None)?;
+    }
+    if let Some(user) = &user {
+        table.insert_row(guild_id.get(), row.id, None, Some(user.id.get()))?;
+    }
+
+    let allowed: Vec<_> = table
+        .sound_rows(guild_id.get(), row.id)?
+        .iter()
+        .filter_map(
+            |restriction| match (restriction.role_id, restriction.user_id) {
+                (Some(role_id), _) => Some(format!("<@&{role_id}>")),
+                (None, Some(user_id)) => Some(format!("<@{user_id}>")),
+                (None, None) => None,
+            },
+        )
+        .collect();
+    let text = format!(
+        "Sound `{}` restricted to {} and moderators",
+        row.name,
+        allowed.join(", ")
+    );
+    poise_check_msg(helpers::guild_reply(ctx, &text).await);
+
+    helpers::send_audit_message(
+        ctx.http(),
+        ctx.data(),
+        Some(guild_id),
+        CreateMessage::new().content(format!("{text} by <@{}>", ctx.author().id)),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Delete every sound matching filters, after previewing how many match
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "purge",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn purge_sounds(
+    ctx: PoiseContext<'_>,
+    #[description = "Delete sounds with this tag"]
+    #[max_length = 32]
+    tag: Option<String>,
+    #[description = "Delete sounds whose names start with this"]
+    #[max_length = 32]
+    prefix: Option<String>,
+    #[description = "Delete sounds not played in this server for this many days"]
+    #[min = 1]
+    #[max = 3650]
+    unplayed_days: Option<u32>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let tag = tag.map(Tags::clean_tag).filter(|tag| !tag.is_empty());
+    let prefix = prefix
+        .map(|prefix| prefix.trim().to_string())
+        .filter(|prefix| !prefix.is_empty());
+    let mut spec = purge::PurgeSpec {
+        tag,
+        prefix,
+        unplayed_days,
+        count: 0,
+        ids_hash: String::new(),
+    };
+    if !spec.has_filter() {
+        poise_check_msg(
+            helpers::guild_reply(ctx, "Pick a tag, prefix, or unplayed_days to purge by").await,
+        );
+        return Ok(());
+    }
+    log::info!("Previewing purge of sounds {}", spec.filter_text());
+
+    let rows = spec.rows(ctx.data(), guild_id)?;
+    if rows.is_empty() {
+        let text = format!("No unprotected sounds {}", spec.filter_text());
+        poise_check_msg(helpers::guild_reply(ctx, text).await);
+        return Ok(());
+    }
+    spec.count = rows.len();
+    spec.ids_hash = purge::hash_ids(&rows);
+
+    let names: Vec<_> = rows.iter().map(|row| format!("`{}`", row.name)).collect();
+    let shown = names.len().min(purge::PREVIEW_NAMES_LIMIT);
+    let mut preview = names[..shown].join(", ");
+    if names.len() > shown {
+        preview.push_str(&format!(" and {} more", names.len() - shown));
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!(
+                "Delete {} sounds {}? {preview}\nProtected sounds are kept. `/undo` brings them back \
+                within {} minutes",
+                spec.count,
+                spec.filter_text(),
+                undo::UNDO_WINDOW_MINUTES
+            ))
+            .components(vec![purge::confirm_action_row(spec)])
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Let everyone play a restricted sound again
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "unrestrict",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn unrestrict_sound(
+    ctx: PoiseContext<'_>,
+    #[rename = "track"]
+    #[description = "Sound to lift the restrictions of"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    log::info!("Unrestricting audio track - {audio_track_name}. guild_id: {guild_id}");
+
+    let row = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()))
+        .ok_or_else(|| errors::AudioError::AudioTrackNotFound {
+            track: audio_track_name.clone(),
+        })?;
+
+    let lifted = ctx
+        .data()
+        .sound_restrictions_table()
+        .delete_rows(guild_id.get(), row.id)?;
+    if lifted == 0 {
+        poise_check_msg(
+            helpers::guild_reply(ctx, format!("Sound `{}` isn't restricted", row.name)).await,
+        );
+        return Ok(());
+    }
+
+    let text = format!("Sound `{}` can be played by everyone again", row.name);
+    poise_check_msg(helpers::guild_reply(ctx, &text).await);
+
+    helpers::send_audit_message(
+        ctx.http(),
+        ctx.data(),
+        Some(guild_id),
+        CreateMessage::new().content(format!("{text} by <@{}>", ctx.author().id)),
+    )
+    .await;
+
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "display")]
+pub async fn display_sounds(
+    ctx: PoiseContext<'_>,
+    #[description = "Delete the soundboard after this many minutes"]
+    #[min = 1]
+    #[max = 1440]
+    ttl: Option<u32>,
+) -> PoiseResult {
+    log::info!("List sounds buttons as ActionRows grid...");
+
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let prefs = ctx
+        .data()
+        .user_prefs_table()
+        .get_prefs(ctx.author().id.get())
+        .log_err()?;
+
+    let reply = ctx
+        .reply(format!("Displaying sounds ({} board)...", prefs.board))
+        .await?;
+    let reply_id = reply.message().await?.id;
+    ctx.data()
+        .track_bot_messages(guild_id, ctx.channel_id(), &[reply_id]);
+
+    let permissions = ctx.permissions().await?;
+    let hidden = auth::hidden_sound_ids(
+        &ctx.data().sound_restrictions_table(),
+        guild_id,
+        ctx.author().id,
+        &permissions,
+    )?;
+    let message_ids = helpers::send_soundboard(
+        ctx.http(),
+        ctx.data(),
+        guild_id,
+        ctx.channel_id(),
+        &prefs,
+        &hidden,
+    )
+    .await?;
+    if let Some(message_ids) = &message_ids {
+        ctx.data()
+            .track_bot_messages(guild_id, ctx.channel_id(), message_ids);
+    }
+
+    match (&message_ids, ttl) {
+        (Some(message_ids), Some(ttl)) => {
+            let ttl = std::time::Duration::from_secs(ttl as u64 * 60);
+            helpers::delete_messages_after(
+                ctx.serenity_context().http.clone(),
+                ctx.channel_id(),
+                message_ids.clone(),
+                ttl,
+            );
+        }
+        // boards meant to stay are refreshed when the bot starts
+        (Some(message_ids), None) => posted_boards::record(
+            ctx.data(),
+            guild_id,
+            ctx.channel_id(),
+            ctx.author().id,
+            message_ids,
+            &hidden,
+        ),
+        (None, _) => {}
+    }
+
+    if message_ids.is_none() && prefs.board == BoardLayout::Favorites {
+        poise_check_msg(
+            ctx.reply("No favorite sounds yet. Add some with `/sounds favorite`")
+                .await,
+        );
+    }
+
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "random-button")]
+pub async fn random_button_board(
+    ctx: PoiseContext<'_>,
+    #[description = "Number of random sounds on the board"]
+    #[min = 1]
+    #[max = 20]
+    count: Option<u32>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let count = count
+        .unwrap_or(shuffle::DEFAULT_SHUFFLE_COUNT)
+        .clamp(1, shuffle::MAX_SHUFFLE_COUNT);
+    log::info!("Displaying shuffle board of {count} random sounds...");
+
+    let rows = ctx.data().audio_table().random_rows(count as u64)?;
+    let (content, components) = shuffle::board(&rows, count);
+
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content(content)
+                .components(components),
+        )
+        .await?;
+    let reply_id = reply.message().await?.id;
+    ctx.data()
+        .track_bot_messages(guild_id, ctx.channel_id(), &[reply_id]);
+
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "recent")]
+pub async fn recent_sounds(
+    ctx: PoiseContext<'_>,
+    #[description = "Number of newest sounds on the board"]
+    #[min = 1]
+    #[max = 25]
+    count: Option<u32>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let count = count
+        .unwrap_or(recent::DEFAULT_RECENT_COUNT)
+        .clamp(1, recent::MAX_RECENT_COUNT);
+    log::info!("Displaying board of {count} newest sounds...");
+
+    let rows = ctx.data().audio_table().recent_rows(count as u64)?;
+    let (content, components) = recent::board(&rows);
+
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content(content)
+                .components(components)
+                .allowed_mentions(serenity::all::CreateAllowedMentions::new()),
+        )
+        .await?;
+    let reply_id = reply.message().await?.id;
+    ctx.data()
+        .track_bot_messages(guild_id, ctx.channel_id(), &[reply_id]);
+
+    Ok(())
+}
+
+#[derive(Debug, poise::Modal)]
+#[name = "Edit Sound"]
+struct EditSoundModal {
+    #[name = "Name"]
+    #[min_length = 3] // No length restriction by default (so, 1-4000 chars)
+    #[max_length = 80] // Same as max button label len (crate::vars::BTN_LABEL_MAX_LEN)
+    name: String,
+    #[name = "Tags"]
+    #[max_length = 1024]
+    tags: Option<String>,
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "edit",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn edit_sound(
+    ctx: PoiseAppContext<'_>,
+    #[description = "Audio track to edit"]
+    #[rename = "track"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+    #[description = "Allow renaming the sound even if it's protected. Server admins only"]
+    #[flag]
+    force: bool,
+) -> PoiseResult {
+    log::info!("Editing audio track - {audio_track_name}");
+
+    let table = ctx.data().audio_table();
+
+    let mut row = table
+        .find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()))
+        .ok_or(format!("Unable to locate audio track '{audio_track_name}'"))
+        .log_err()?;
+
+    let data = EditSoundModal::execute_with_defaults(
+        ctx,
+        EditSoundModal {
+            name: audio_track_name.clone(),
+            tags: Some(row.tags.to_string()),
+        },
+    )
+    .await?;
+
+    match data {
+        Some(data) => {
+            log::debug!("{data:?}");
+            let tags: Tags = match data.tags {
+                Some(val) => Tags::from(val),
+                None => Tags::new(),
+            };
+
+            let name = naming::normalize(&ctx.data().config.get(), &data.name)?;
+            if name != row.name {
+                PoiseContext::from(ctx)
+                    .permissions()
+                    .await?
+                    .check_unprotected(&row.name, row.protected, force)?;
+            }
+
+            let previous = undo::SoundMetadata::from(&row);
+            row.name = name;
+            row.tags = tags;
+
+            table.update_audio_row(&row).log_err()?;
+
+            if let Some(kind) = undo::LibraryOpKind::of_edit(&previous, &row) {
+                undo::record_edit(
+                    &ctx.data().db_pool,
+                    kind,
+                    ctx.guild_id(),
+                    ctx.author().id,
+                    vec![previous],
+                );
+            }
+        }
+        None => log::info!("No audo track to update"),
+    }
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "undo",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn undo_operation(ctx: PoiseContext<'_>) -> PoiseResult {
+    let undone = undo::undo_latest(&ctx.data().db_pool)?;
+    log::info!("Undid {undone}");
+
+    let text = format!("Undid {undone}");
+    poise_check_msg(ctx.reply(&text).await);
+
+    helpers::send_audit_message(
+        ctx.http(),
+        ctx.data(),
+        ctx.guild_id(),
+        CreateMessage::new().content(format!("{text} by <@{}>", ctx.author().id)),
+    )
+    .await;
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "join-audio",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn set_join_audio(
+    ctx: PoiseContext<'_>,
+    #[description = "Audio track name"]
+    #[rename = "track"]
+    #[autocomplete = "helpers::autocomplete_opt_audio_track_name"]
+    audio_track_name: String,
+) -> PoiseResult {
+    log::info!("Setting join audio: {audio_track_name:?}");
+
+    let table = ctx.data().settings_table();
+    let mut settings = table.get_settings().log_err()?;
+
+    match audio_track_name.as_str() {
+        "NONE" => {
+            settings.join_audio = None;
+            table.update_settings(&settings).log_err()?;
+            poise_check_msg(ctx.reply(format!("Bot join audio disabled")).await);
+        }
+        val => {
+            settings.join_audio = Some(val.into());
+            table.update_settings(&settings).log_err()?;
+            poise_check_msg(ctx.reply(format!("Bot join audio set to {val}")).await);
+        }
+    }
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "leave-audio",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn set_leave_audio(
+    ctx: PoiseContext<'_>,
+    #[description = "Audio track name"]
+    #[rename = "track"]
+    #[autocomplete = "helpers::autocomplete_opt_audio_track_name"]
+    audio_track_name: String,
+) -> PoiseResult {
+    log::info!("Setting leave audio: {audio_track_name:?}");
+
+    let table = ctx.data().settings_table();
+    let mut settings = table.get_settings().log_err()?;
+
+    match audio_track_name.as_str() {
+        "NONE" => {
+            settings.leave_audio = None;
+            table.update_settings(&settings).log_err()?;
+            poise_check_msg(ctx.reply(format!("Bot leave audio disabled")).await);
+        }
+        val => {
+            settings.leave_audio = Some(val.into());
+            table.update_settings(&settings).log_err()?;
+            poise_check_msg(ctx.reply(format!("Bot leave audio set to {val}")).await);
+        }
+    }
+
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "help")]
+pub async fn display_help(ctx: PoiseContext<'_>) -> PoiseResult {
+    let version = vars::VERSION;
+    let config = ctx.data().config.get();
+    let prefix = config.command_prefix.as_str();
+    let text = format!(
+        "\
+# Soundboard Bot v{version}
+[GitHub Repo](https://github.com/krywicki/discord-soundboard-bot)
+Bot for playing sounds in voice chat.
+## Slash Commands
+- `/play {{track}} [overlap]` - Play sound track in voice channel. Queued if another track is playing, unless overlapping it
+- `/replay` - Play the sound played last in the server again
+- `/pause` - Pause what's playing. Queued sounds wait until resumed
+- `/resume` - Resume paused sounds
+- `/nowplaying` - Displays what's playing and who requested it, with pause, resume, & 🔁 replay buttons
+- `/seek {{timestamp}}` - Jump to a time in what's playing, ie: `90`, `1:30`
+- `/queue`
+  - `/queue list` - Displays what's playing and the numbered queue
+  - `/queue move {{from}} {{to}}` - Moves a queued sound to another position
+  - `/queue remove {{position}}` - Removes a sound from the queue
+- `/ambience`
+  - `/ambience play {{track}} [layer] [volume]` - Loops a sound as a named ambience layer (ie: `rain`), under the queue & other layers
+  - `/ambience volume {{layer}} {{volume}}` - Sets the volume of an ambience layer
+  - `/ambience stop [layer]` - Stops an ambience layer, or all of them
+  - `/ambience list` - Displays the ambience layers playing
+- `/sounds`
+  - `/sounds add` - Opens form to add sounds. A name that's taken is handled by the `name_collision` setting
+  - `/sounds remove {{track}} [force] [ban]` - Removes sound. Protected sounds need `force`, from a server admin. `ban` rejects future uploads of its audio
+  - `/sounds protect {{track}} [protected]` - Protects sound from being deleted, renamed, or bulk edited (server admins only)
+  - `/sounds cooldown {{track}} {{seconds}}` - Sets seconds after a play before the sound can be played again in the server. `0` removes the cooldown. Moderators aren't held to cooldowns
+  - `/sounds restrict-to {{track}} {{role?}} {{user?}}` - Only lets a role or member play the sound. Moderators can play every sound
+  - `/sounds unrestrict {{track}}` - Lets everyone play a restricted sound again
+  - `/sounds edit {{track}} [force]` - Opens form to edit sound track. Renaming a protected sound needs `force`, from a server admin
+  - `/sounds display [ttl]` - Displays a button grid of sounds that can be played in voice channel, using your preferred board. Its 🔍 button searches sounds. With `ttl`, the board is deleted after that many minutes
+  - `/sounds random-button [count]` - Displays a board of `count` (default 10) random sounds. Its 🔀 button re-rolls the sounds
+  - `/sounds recent [count]` - Displays a board of the `count` (default 10) newest sounds, with who added each and when
+  - `/sounds join-audio {{track}}` - Set/Unset sound track to play when bot joins voice channel
+  - `/sounds leave-audio {{track}}` - Set/Unset sound track to play when bot leaves voice channel
+  - `/sounds favorite {{track}}` - Add/Remove sound track from your favorites
+  - `/sounds replace {{track}} [file] [url]` - Swaps audio of a sound for a new mp3 file or link, keeping its tags, stats, and buttons
+  - `/sounds trim {{track}} [start_ms] [end_ms]` - Previews a sound cut down to start/end, and trims it once confirmed. Requires ffmpeg. Previews too large to upload are compressed to a lower bitrate
+  - `/sounds merge {{a}} {{b}} [name] [crossfade_ms]` - Joins two sounds (optionally crossfaded) into a new sound with the tags of both. Requires ffmpeg
+  - `/sounds stale [days]` - Lists sounds not played in a while (or ever), with buttons to play or delete them
+  - `/sounds purge {{tag?}} {{prefix?}} {{unplayed_days?}}` - Deletes every unprotected sound matching the filters, after a preview & confirmation button
+  - `/sounds reconcile [dry_run]` - Lists sounds whose audio file is missing, and audio files that aren't sounds, with buttons to remove those sounds or import those files
+  - `/sounds info {{track}}` - Shows details of a sound, with its total plays, plays in the last 7 days, and top players
+  - `/sounds import {{file}} [dry_run] [force]` - Bulk set names, tags, emojis, and volumes of sounds from a CSV or JSON file. Protected sounds are skipped without `force`, from a server admin
+- `/undo` - Reverses the latest delete, rename, retag, bulk edit, or purge of the library, if made in the last 30 minutes. Run again to step further back
+- `/search {{query}}` - Search sounds by name, tag, or transcript. Many matches are shown as a board of sound buttons
+- `/suggest` - Suggests sounds you haven't played, that are popular with members who play what you play
+- `/hotbar`
+  - `/hotbar show` - Displays your hotbar, a board of up to 9 sounds only you can see
+  - `/hotbar set {{slot}} {{sound}}` - Puts a sound in slot 1-9 of your hotbar
+  - `/hotbar clear {{slot}}` - Empties a slot of your hotbar
+- `/request {{request}} [name]` - Request a sound be added, by link to an audio file or description. Moderators approve or deny it
+- `/export stats [format]` - Download play history of the server & sound library metadata as CSV or JSON files. Exports too large to upload are split into parts over several messages. Moderators only
+- `/stats activity [days]` - Heatmap of plays per weekday & hour, to see when the soundboard is used
+- `/cleanup [older_than] [keep] [dry_run]` - Deletes the bot's old soundboards & play messages in this channel
+- `/version [check_updates]` - Display bot version, commit, build time, uptime & counters. Optionally checks GitHub for a newer release
+- `/prefs`
+  - `/prefs show` - Displays your preferences
+  - `/prefs set [board] [page_size] [volume] [captions]` - Sets preferred board (`full` or `favorites`), buttons per soundboard message, personal volume, and whether the names of sounds played in your voice channel are direct messaged to you
+  - `/prefs reset` - Resets your preferences to defaults
+- `/settings`
+  - `/settings list` - Displays all guild settings and their values
+  - `/settings get {{key}}` - Displays a guild setting
+  - `/settings set {{key}} {{value}}` - Sets a guild setting (`volume`, `idle_timeout`, `ephemeral_replies`, `audit_channel`, `locale`, `user_role`, `moderator_role`, `daily_play_quota`, `channel_hourly_cap`, `request_channel`, `overlap_playback`, `max_overlapping_tracks`, `max_queue_length`, `max_queue_duration`, `priority_role`, `timezone`, `quiet_hours`, `quiet_hours_volume`, `message_retention`, `message_retention_count`, `play_history_retention`, `play_feedback`, `name_collision`, `now_playing_channel`, `voice_commands`, `speech_pause`, `crossfade`). Requires Manage Server permission
+  - `/settings reset {{key}}` - Resets a guild setting to its default. Requires Manage Server permission
+  - `/settings channel-volume {{channel}} [volume]` - Sets volume of a voice channel, overriding the `volume` setting there. Leave out `volume` to clear it. Requires Manage Server permission
+  - `/settings export` - Exports settings, channel volumes, sound restrictions, and board themes of the server as a JSON file. Requires Manage Server permission
+  - `/settings import {{file}} [dry_run]` - Replaces the server's settings with a `/settings export` file, ie: to clone a server or restore one after a reset. Roles & channels missing from the server are matched by name, or skipped. Requires Manage Server permission
+- `/themes`
+  - `/themes list` - Displays themes scheduled for the board, and which is featured today
+  - `/themes add {{tag}} {{start}} {{end}}` - Features sounds tagged with `tag` at the top of the full board every year from `start` to `end` (`MM-DD`), ie: `spooky` from `10-01` to `10-31`. Requires Manage Server permission
+  - `/themes remove {{id}}` - Removes a scheduled theme. Requires Manage Server permission
+- `/birthday`
+  - `/birthday set {{user}} {{date}} {{sound}}` - Greets a member and plays a sound every year on their birthday or anniversary, once they're in voice. Requires Manage Server permission
+  - `/birthday remove {{user}}` - Stops celebrating a member. Requires Manage Server permission
+  - `/birthday list` - Displays birthdays set in the server
+- `/branding`
+  - `/branding show` - Shows the color, footer, and icon of the bot's embeds. Requires Manage Server permission
+  - `/branding color [color]` - Sets the color of the bot's embeds, ie: `#5865F2`. Requires Manage Server permission
+  - `/branding footer [text]` - Sets footer text of the bot's embeds. Requires Manage Server permission
+  - `/branding icon [url]` - Sets footer icon of the bot's embeds. Requires Manage Server permission
+  - `/branding reset` - Clears embed branding. Requires Manage Server permission
+- `/eventsounds`
+  - `/eventsounds set {{event}} {{track}} [cue]` - Plays a sound when a member joins, someone boosts the server, or a poll ends, if the bot is in voice. With `cue`, posts a text cue otherwise. Requires Manage Server permission
+  - `/eventsounds clear {{event}}` - Stops playing a sound for an event. Requires Manage Server permission
+  - `/eventsounds list` - Displays the sound played for each event. Requires Manage Server permission
+- `/board reorder {{sound}} {{position}} {{board}}` - Moves a sound on the full board, or on your favorites board, with buttons to nudge it further. Reordering the full board requires managing sounds
+- `/eventmode enable {{duration}}` - Lifts sound cooldowns, daily play quotas & queue limits, and lets up to 10 sounds overlap, for a while (ie: `3h`). Requires moderator
+- `/eventmode disable` - Ends event mode early. Requires moderator
+- `/eventmode status` - Shows when event mode ends. Requires moderator
+- `/packs`
+  - `/packs browse` - Lists the starter packs of the packs index. Requires Manage Sounds permission
+  - `/packs publish {{tag}}` - Publishes sounds with a tag as a pack, replying with its share code. Requires Manage Sounds permission
+  - `/packs install {{code}} [dry_run]` - Adds the sounds of a published pack, or a starter pack of the index, to the library, after confirming what it adds. Requires Manage Sounds permission
+- `/access`
+  - `/access post [text]` - Posts a button members press to get or give up the soundboard user role. Requires Manage Roles permission
+- `/guilddata`
+  - `/guilddata export` - Exports everything the bot stores about this server as json. Requires Manage Server permission
+  - `/guilddata delete` - Deletes everything the bot stores about this server, once confirmed. Requires Manage Server permission
+- `/apikey`
+  - `/apikey create {{name}} [scopes]` - Creates an HTTP API key acting as you in this server, shown once. `scopes` limits what it can do: `upload`, `events`. Requires Manage Sounds permission
+  - `/apikey list` - Lists API keys of this server. Requires Manage Sounds permission
+  - `/apikey revoke {{name}}` - Revokes an API key. Requires Manage Sounds permission
+  - `/apikey quota {{name}} {{requests}}` - Limits requests an API key can make a day. `0` removes the quota. Requires Manage Sounds permission
+  - `/apikey stats [days]` - Requests made with this server's API keys. Requires Manage Sounds permission
+- `/permissions`
+  - `/permissions show [user]` - Displays your (or a member's) soundboard permissions and remaining plays
+  - `/permissions ban {{user}} [reason]` - Bans member from the soundboard. Moderators only
+  - `/permissions unban {{user}}` - Unbans member from the soundboard. Moderators only
+## Prefix Commands
+- `{prefix}join` - Have bot join the voice channel you're in. Says which permission it lacks (Connect or Speak), or that the channel is full, instead of failing to join
+- `/move {{channel}}` - Moves the bot to another voice channel, keeping the queue & what's playing
+- `{prefix}leave` - Have bot leave the voice channel
+- `/register [scope] [clear]` - [`dev use`] Register slash commands `global`ly or in `this-guild` (the default in guilds), or clear them with `clear`. Also runs as `{prefix}register`, before any slash commands are registered
+- `{prefix}registration {{mode}}` - [`dev use`] Switch slash commands to `global` or per `guild` registration, removing commands of the other mode
+- `{prefix}scan [dry_run]` - [`dev use`] Scan local audio directory and sync sound tracks with database
+- `{prefix}metrics` - [`dev use`] Display per-command call counts, errors, and latency, and HTTP API requests of today
+- `{prefix}telemetry` - [`dev use`] Display whether anonymous telemetry is on, and the report it sends
+- `{prefix}reload` - [`dev use`] Reload config from environment & dotenv file
+- `{prefix}features list` - [`dev use`] Display bot wide feature flags
+- `{prefix}features set {{flag}} {{enabled}}` - [`dev use`] Turn a bot wide feature (`sound_requests_enabled`, `url_imports_enabled`, `audio_editing_enabled`, `voice_commands_enabled`, `web_api_enabled`) on or off
+- `{prefix}jobs list` - [`dev use`] Display scheduled, running, and recently finished background jobs
+- `{prefix}database maintenance [vacuum]` - [`dev use`] Check integrity of the database, and vacuum & analyze it
+"
+    );
+
+    poise_check_msg(ctx.reply(text).await);
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "import",
+    custom_data = "LongRunning",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn import_metadata(
+    ctx: PoiseContext<'_>,
+    #[description = "CSV or JSON file mapping audio file names to names, tags, emojis, and volumes"]
+    file: serenity::all::Attachment,
+    #[description = "Report changes without writing to database"]
+    #[flag]
+    dry_run: bool,
+    #[description = "Also change protected sounds. Server admins only"]
+    #[flag]
+    force: bool,
+) -> PoiseResult {
+    log::info!("Importing metadata file {}", file.filename);
+    if force && !ctx.permissions().await?.is_admin {
+        return Err(AuthError::NotAdmin.into());
+    }
+
+    let format = metadata::MetadataFormat::from_file_name(&file.filename)?;
+    if file.size as u64 > metadata::MAX_METADATA_FILE_SIZE {
+        return Err(errors::AudioError::FileTooLarge {
+            size: file.size as u64,
+            max_size: metadata::MAX_METADATA_FILE_SIZE,
+        }
+        .into());
+    }
+
+    let operation = ctx.data().operations.start();
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .content(format!("Importing metadata from `{}`...", file.filename))
+                .components(vec![operation.cancel_action_row()]),
+        )
+        .await?;
+
+    let imported = async {
+        let content = tokio::select! {
+            content = file.download() => content?,
+            _ = operation.token.cancelled() => return Err(OperationError::Cancelled.into()),
+        };
+
+        let db_pool = ctx.data().db_pool.clone();
+        let cancel = operation.token.clone();
+        tokio::task::spawn_blocking(move || {
+            metadata::import_metadata(&db_pool, &content, format, dry_run, force, &cancel)
+        })
+        .await?
+    }
+    .await;
+    drop(operation);
+
+    let (report, failed_writes) = match imported {
+        Ok(imported) => imported,
+        Err(err) => {
+            let cancelled = err.is::<OperationError>();
+            let content = match cancelled {
+                true => "Metadata import cancelled - no changes were written",
+                false => "Metadata import failed",
+            };
+            reply
+                .edit(
+                    ctx,
+                    CreateReply::default().content(content).components(vec![]),
+                )
+                .await
+                .log_err_msg("Failed to edit metadata import reply")
+                .ok();
+
+            return match cancelled {
+                true => Ok(()),
+                false => Err(err),
+            };
+        }
+    };
+
+    let mut embed = report.summary_embed(failed_writes);
+    if dry_run {
+        embed = embed.title("Metadata Import Summary (dry run)");
+    }
+    let embed = branding::Branding::of(ctx.data(), ctx.guild_id()).apply(embed);
+    reply
+        .edit(
+            ctx,
+            CreateReply::default()
+                .content("Metadata import complete")
+                .embed(embed.clone())
+                .components(vec![]),
+        )
+        .await?;
+
+    if !dry_run {
+        undo::record_edit(
+            &ctx.data().db_pool,
+            undo::LibraryOpKind::BulkEdit,
+            ctx.guild_id(),
+            ctx.author().id,
+            report.previous.clone(),
+        );
+
+        helpers::send_audit_message(
+            ctx.http(),
+            ctx.data(),
+            ctx.guild_id(),
+            CreateMessage::new()
+                .content(format!("Metadata imported by <@{}>", ctx.author().id))
+                .embed(embed),
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "merge",
+    custom_data = "LongRunning",
+    check = "auth::check_manage_sounds",
+    check = "features::check_audio_editing"
+)]
+pub async fn merge_sounds(
+    ctx: PoiseContext<'_>,
+    #[description = "Sound played first"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    a: String,
+    #[description = "Sound played second"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    b: String,
+    #[description = "Name of the new sound. Defaults to `a + b`"]
+    #[min_length = 3]
+    #[max_length = 80]
+    name: Option<String>,
+    #[description = "Milliseconds the sounds overlap, fading one into the other"]
+    #[min = 1]
+    #[max = 5000]
+    crossfade_ms: Option<u64>,
+) -> PoiseResult {
+    let (first, second) = {
+        let table = ctx.data().audio_table();
+        let find_row = |name: &String| {
+            table
+                .find_audio_row(db::UniqueAudioTableCol::Name(name.clone()))
+                .ok_or_else(|| errors::AudioError::AudioTrackNotFound {
+                    track: name.clone(),
+                })
+        };
+        (find_row(&a)?, find_row(&b)?)
+    };
+
+    let name = name.unwrap_or_else(|| {
+        format!("{} + {}", first.name, second.name)
+            .chars()
+            .take(vars::BTN_LABEL_MAX_LEN)
+            .collect()
+    });
+    let name = collision::resolve_name(ctx.data(), ctx.guild_id(), &name)?;
+    log::info!("Merging sounds `{a}` and `{b}` into `{name}`");
+
+    let mut tags = first.tags.to_vec();
+    for tag in second.tags.iter() {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+
+    let temp_audio_file = audio::merge_audio_files_temp(
+        &ctx.data().config.get().ffmpeg_path,
+        &first.audio_file,
+        &second.audio_file,
+        crossfade_ms.map(std::time::Duration::from_millis),
+    )
+    .await?;
+
+    ingest::Ingest::new(ctx.data())
+        .run(
+            ingest::IngestSource::TempFile(temp_audio_file),
+            ingest::IngestTarget::New {
+                name: &name,
+                tags: Tags::from(tags),
+                author: ctx.author(),
+            },
+        )
+        .await?;
+
+    poise_check_msg(
+        ctx.reply(format!(
+            "Merged `{}` and `{}` into new sound `{name}`",
+            first.name, second.name
+        ))
+        .await,
+    );
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "replace",
+    custom_data = "LongRunning",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn replace_sound(
+    ctx: PoiseContext<'_>,
+    #[rename = "track"]
+    #[description = "Sound to replace audio of"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+    #[description = "New mp3 file"] file: Option<serenity::all::Attachment>,
+    #[description = "Link to new mp3 file"] url: Option<String>,
+) -> PoiseResult {
+    let source = match (file, url) {
+        (Some(file), None) => ingest::IngestSource::Attachment(file.url),
+        (None, Some(url)) => {
+            features::check(ctx.data(), FeatureFlag::UrlImports)?;
+            ingest::IngestSource::Url(url)
+        }
+        _ => return Err("Provide either a new file or a link to one, not both".into()),
+    };
+
+    let row = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()))
+        .ok_or(errors::AudioError::AudioTrackNotFound {
+            track: audio_track_name,
+        })?;
+    log::info!("Replacing audio of sound `{}`", row.name);
+
+    ingest::Ingest::new(ctx.data())
+        .run(source, ingest::IngestTarget::Replace(&row))
+        .await?;
+
+    poise_check_msg(
+        ctx.reply(format!("Replaced audio of sound `{}`", row.name))
+            .await,
+    );
+
+    helpers::send_audit_message(
+        ctx.http(),
+        ctx.data(),
+        ctx.guild_id(),
+        CreateMessage::new().content(format!(
+            "Audio of sound `{}` replaced by <@{}>",
+            row.name,
+            ctx.author().id
+        )),
+    )
+    .await;
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "trim",
+    check = "auth::check_manage_sounds",
+    check = "features::check_audio_editing"
+)]
+pub async fn trim_sound(
+    ctx: PoiseContext<'_>,
+    #[rename = "track"]
+    #[description = "Sound to trim"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+    #[description = "Millisecond to start sound from. Defaults to 0"] start_ms: Option<u64>,
+    #[description = "Millisecond to end sound at. Defaults to end of sound"] end_ms: Option<u64>,
+) -> PoiseResult {
+    let start_ms = start_ms.unwrap_or(0);
+    if start_ms == 0 && end_ms.is_none() {
+        return Err("Provide a start and/or end to trim the sound to".into());
+    }
+    if end_ms.is_some_and(|end_ms| end_ms <= start_ms) {
+        return Err("End of trim must come after its start".into());
+    }
+
+    // preview is only for the member trimming
+    ctx.defer_ephemeral().await?;
+
+    let row = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()))
+        .ok_or(errors::AudioError::AudioTrackNotFound {
+            track: audio_track_name,
+        })?;
+    let spec = trim::TrimSpec::new(
+        row.id,
+        start_ms,
+        end_ms,
+        &audio::hash_file(row.audio_file.as_path())?,
+    );
+    log::info!("Previewing trim of `{}` to {}", row.name, spec.range_text());
+
+    let ffmpeg_path = ctx.data().config.get().ffmpeg_path.clone();
+    let trimmed =
+        audio::trim_audio_file_temp(&ffmpeg_path, &row.audio_file, spec.start(), spec.end())
+            .await?;
+    let previews = async {
+        let before_duration = audio::probe_audio_track(row.audio_file.as_path())?.duration;
+        let after_duration = audio::probe_audio_track(&trimmed)?.duration;
+        let previews = trim_previews(
+            &ffmpeg_path,
+            row.audio_file.as_path(),
+            &trimmed,
+            before_duration + after_duration,
+        )
+        .await?;
+        Ok::<_, PoiseError>((before_duration, after_duration, previews))
+    }
+    .await;
+    std::fs::remove_file(&trimmed).ok();
+    let (before_duration, after_duration, previews) = previews?;
+
+    let mut content = format!(
+        "Trim `{}` to {}? Before: {:.2}s, After: {:.2}s. Listen to both, then confirm",
+        row.name,
+        spec.range_text(),
+        before_duration.as_secs_f64(),
+        after_duration.as_secs_f64()
+    );
+    if let Some(bitrate) = previews.compressed_kbps {
+        content.push_str(&format!(
+            "\n-# Previews compressed to {bitrate}kbps to fit discord's upload limit"
+        ));
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .attachment(CreateAttachment::bytes(previews.before, "before.mp3"))
+            .attachment(CreateAttachment::bytes(previews.after, "after.mp3"))
+            .components(vec![trim::confirm_action_row(spec)])
+            .ephemeral(true),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Audio of sound before & after a trim
+struct TrimPreviews {
+    before: Vec<u8>,
+    after: Vec<u8>,
+    /// Bitrate both were re-encoded at, when they were too large to upload together
+    compressed_kbps: Option<u32>,
+}
+
+async fn trim_previews(
+    ffmpeg_path: &std::path::Path,
+    before: &std::path::Path,
+    after: &std::path::Path,
+    duration: std::time::Duration,
+) -> Result<TrimPreviews, PoiseError> {
+    let size = std::fs::metadata(before)?.len() + std::fs::metadata(after)?.len();
+    if size <= vars::ATTACHMENT_MAX_SIZE {
+        return Ok(TrimPreviews {
+            before: std::fs::read(before)?,
+            after: std::fs::read(after)?,
+            compressed_kbps: None,
+        });
+    }
+
+    let bitrate =
+        attachments::preview_bitrate_kbps(duration, vars::ATTACHMENT_MAX_SIZE).ok_or(format!(
+            "Previews are too large to upload ({}), even compressed",
+            errors::format_file_size(size)
+        ))?;
+    log::info!("Trim previews are too large to upload, compressing them to {bitrate}kbps");
+
+    let mut previews = vec![];
+    for path in [before, after] {
+        let compressed = audio::compress_audio_file_temp(ffmpeg_path, path, bitrate).await?;
+        let preview = std::fs::read(&compressed);
+        std::fs::remove_file(&compressed).ok();
+        previews.push(preview?);
+    }
+
+    let after = previews.pop().unwrap_or_default();
+    let before = previews.pop().unwrap_or_default();
+    Ok(TrimPreviews {
+        before,
+        after,
+        compressed_kbps: Some(bitrate),
+    })
+}
+
+#[poise::command(slash_command, guild_only, rename = "stale")]
+pub async fn stale_sounds(
+    ctx: PoiseContext<'_>,
+    #[description = "Sounds not played in this many days. Defaults to 30"]
+    #[min = 1]
+    #[max = 3650]
+    days: Option<u32>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let days = days.unwrap_or(30);
+    let since = chrono::Utc::now() - chrono::Duration::days(days as i64);
+
+    let stale =
+        ctx.data()
+            .audio_table()
+            .stale_rows(guild_id.get(), since, vars::ACTION_ROWS_LIMIT)?;
+
+    if stale.is_empty() {
+        ctx.send(
+            CreateReply::default()
+                .content(format!("Every sound was played in the last {days} days"))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let lines: Vec<_> = stale
+        .iter()
+        .map(|(row, last_played_at)| {
+            let last_played = last_played_at.map_or("never played".to_string(), |played_at| {
+                format!("last played <t:{}:R>", played_at.timestamp())
+            });
+            format!("`{}` - {last_played}", row.name)
+        })
+        .collect();
+    let embed = serenity::all::CreateEmbed::new()
+        .title(format!("Sounds not played in {days} days"))
+        .description(helpers::embed_field_list(&lines));
+    let embed = branding::Branding::of(ctx.data(), Some(guild_id)).apply(embed);
+
+    // one sound per action row, 5 action rows per message
+    for (index, page) in stale.chunks(5).enumerate() {
+        let mut reply = CreateReply::default().ephemeral(true).components(
+            page.iter()
+                .map(|(row, _)| helpers::make_play_delete_action_row(row))
+                .collect(),
+        );
+        if index == 0 {
+            reply = reply.embed(embed.clone());
+        }
+        ctx.send(reply).await?;
+    }
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "reconcile",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn reconcile_sound(
+    ctx: PoiseContext<'_>,
+    #[description = "Only list what's out of sync, without buttons to fix it"]
+    #[flag]
+    dry_run: bool,
+) -> PoiseResult {
+    let reconciliation = reconcile::Reconciliation::load(ctx.data())?;
+    let branding = branding::Branding::of(ctx.data(), ctx.guild_id());
+
+    let reply = match dry_run {
+        true => CreateReply::default()
+            .embed(reconciliation.embed(&branding).title("Reconcile (dry run)")),
+        false => CreateReply::default()
+            .embed(reconciliation.embed(&branding))
+            .components(reconciliation.action_rows()),
+    };
+    ctx.send(reply.ephemeral(true)).await?;
+
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "info")]
+pub async fn sound_info(
+    ctx: PoiseContext<'_>,
+    #[rename = "track"]
+    #[description = "Sound to show details & play stats of"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let row = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()));
+
+    let reply = match row {
+        Some(row) => CreateReply::default()
+            .embed(helpers::sound_info_embed(ctx.data(), guild_id, &row)?)
+            .components(vec![helpers::sound_info_action_row(row.id)]),
+        None => CreateReply::default()
+            .content(format!("No sound named `{audio_track_name}`"))
+            .ephemeral(true),
+    };
+
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "favorite")]
+pub async fn favorite_sound(
+    ctx: PoiseContext<'_>,
+    #[rename = "track"]
+    #[description = "Track to add to, or remove from, your favorites"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+) -> PoiseResult {
+    let row = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()))
+        .ok_or(format!("Unable to locate audio track '{audio_track_name}'"))
+        .log_err()?;
+
+    let is_favorite = ctx
+        .data()
+        .user_prefs_table()
+        .toggle_favorite(ctx.author().id.get(), row.id)?;
+
+    let text = match is_favorite {
+        true => format!("Added `{}` to your favorites", row.name),
+        false => format!("Removed `{}` from your favorites", row.name),
+    };
+    ctx.send(CreateReply::default().content(text).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "request",
+    check = "features::check_sound_requests"
+)]
+pub async fn request_sound(
+    ctx: PoiseContext<'_>,
+    #[description = "Link to an audio file, or description of the sound you want"]
+    #[max_length = 1000]
+    request: String,
+    #[description = "Name for the sound"]
+    #[max_length = 80]
+    name: Option<String>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    ctx.permissions().await?.check_banned()?;
+
+    let table = ctx.data().sound_requests_table();
+    let pending = table.count_pending(guild_id.get(), ctx.author().id.get())?;
+    if pending >= requests::MAX_PENDING_REQUESTS {
+        ctx.send(
+            CreateReply::default()
+                .content(format!(
+                    "You already have {pending} requests waiting on moderators. Try again once they're reviewed"
+                ))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let request_id = table.insert_row(&db::SoundRequestRowInsert {
+        guild_id: guild_id.get(),
+        channel_id: ctx.channel_id().get(),
+        user_id: ctx.author().id.get(),
+        name: name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty()),
+        request: request.trim().to_string(),
+        created_at: chrono::Utc::now(),
+    })?;
+    let row = table
+        .find_row(request_id)?
+        .ok_or("Failed reading back sound request")?;
+
+    if let Err(err) = requests::notify_moderators(ctx.http(), ctx.data(), &row).await {
+        // moderators never saw it, don't leave it pending
+        table.delete_row(request_id)?;
+        return Err(err);
+    }
+
+    poise_check_msg(
+        helpers::guild_reply(
+            ctx,
+            format!("Sound request #{request_id} sent to moderators for review"),
+        )
+        .await,
+    );
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "search")]
+pub async fn search_sounds(
+    ctx: PoiseContext<'_>,
+    #[description = "Name or tag of sounds to search for"]
+    #[min_length = 3] // Trigram search needs at least 3 chars to match
+    #[max_length = 80] // Query is stored in page button custom ids (max 100 chars)
+    query: String,
+) -> PoiseResult {
+    let query = query.trim();
+    let rows = ctx
+        .data()
+        .audio_table()
+        .search_rows(query, search::SEARCH_RESULTS_LIMIT)?;
+
+    let reply = match rows.len() {
+        0 => CreateReply::default().content(format!("No sounds found matching `{query}`")),
+        len if len <= search::SEARCH_TEXT_RESULTS_LIMIT => {
+            let names: Vec<_> = rows.iter().map(|row| format!("- `{}`", row.name)).collect();
+            CreateReply::default().content(format!(
+                "Found {len} sounds matching `{query}`\n{}",
+                names.join("\n")
+            ))
+        }
+        _ => {
+            let (content, components) = search::board_page(&rows, query, 0);
+            CreateReply::default()
+                .content(content)
+                .components(components)
+        }
+    };
+
+    ctx.send(reply.ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Sounds popular with members who play what you play
+#[poise::command(slash_command, guild_only)]
+pub async fn suggest(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let data = ctx.data();
+
+    let plays = data.play_history_table().user_sound_plays(guild_id.get())?;
+    let audio_table = data.audio_table();
+    let rows: Vec<_> = suggest::suggest(&plays, ctx.author().id.get())
+        .into_iter()
+        .filter_map(|audio_id| audio_table.find_audio_row(db::UniqueAudioTableCol::Id(audio_id)))
+        .take(suggest::SUGGESTIONS_LIMIT)
+        .collect();
+    let (content, components) = suggest::board(&rows);
+
+    ctx.send(
+        CreateReply::default()
+            .content(content)
+            .components(components)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    check = "auth::check_manage_sounds",
+    subcommands("browse_packs", "publish_pack", "install_pack")
+)]
+pub async fn packs(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/packs command shouldn't be invoked directly. It should just house sub commands");
+    Ok(())
+}
+
+/// List starter packs of the packs index
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "browse",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn browse_packs(ctx: PoiseContext<'_>) -> PoiseResult {
+    let Some(index_url) = ctx.data().config.get().packs_index_url.clone() else {
+        poise_check_msg(
+            helpers::guild_reply(
+                ctx,
+                "No packs index is set, with `DISCORD_BOT_PACKS_INDEX_URL`",
+            )
+            .await,
+        );
+        return Ok(());
+    };
+
+    let text = match packs::fetch_index(&index_url).await {
+        Ok(index) if index.packs.is_empty() => "The packs index has no packs".to_string(),
+        Ok(index) => {
+            let lines: Vec<_> = index
+                .packs
+                .iter()
+                .take(packs::MAX_BROWSED_PACKS)
+                .map(|pack| {
+                    let description = pack
+                        .description
+                        .as_deref()
+                        .map(|description| format!(" - {description}"))
+                        .unwrap_or_default();
+                    format!(
+                        "- **{}** `{}` ({} sounds){description}",
+                        pack.name,
+                        pack.id,
+                        pack.sounds.len()
+                    )
+                })
+                .collect();
+            format!(
+                "{}\nInstall one with `/packs install {{id}}`",
+                lines.join("\n")
+            )
+        }
+        Err(err) => format!("Failed fetching packs index: {err}"),
+    };
+
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+/// Publish sounds with a tag as a pack other servers can install with its share code
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "publish",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn publish_pack(
+    ctx: PoiseContext<'_>,
+    #[description = "Tag of the sounds to publish"]
+    #[max_length = 100]
+    tag: String,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let tag = Tags::clean_tag(&tag);
+
+    let text = match packs::publish(ctx.data(), guild_id, &tag) {
+        Ok(manifest) => format!(
+            "Published {} sounds tagged `{tag}`. Install them in another server with `/packs install {}`",
+            manifest.sounds.len(),
+            manifest.code
+        ),
+        Err(err) => format!("Failed publishing pack: {err}"),
+    };
+
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+/// Add the sounds of a published pack to the library, after confirming what it adds
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "install",
+    check = "auth::check_manage_sounds"
+)]
+pub async fn install_pack(
+    ctx: PoiseContext<'_>,
+    #[description = "Share code of the pack, or id of a pack of the packs index"]
+    #[max_length = 32]
+    code: String,
+    #[description = "Only show what the pack would add, without the install button"]
+    #[flag]
+    dry_run: bool,
+) -> PoiseResult {
+    // finding the pack may fetch the packs index
+    ctx.defer_ephemeral().await?;
+
+    let pack = match packs::find(ctx.data(), &code).await {
+        Ok(pack) => pack,
+        Err(err) => {
+            poise_check_msg(
+                helpers::guild_reply(ctx, format!("Failed installing pack: {err}")).await,
+            );
+            return Ok(());
+        }
+    };
+    let report = packs::install(ctx.data(), ctx.author(), &pack, true).await;
+    let preview = format!("Pack `{}`: {}", pack.name, report.preview_text());
+
+    if dry_run || report.installed.is_empty() {
+        poise_check_msg(helpers::guild_reply(ctx, preview).await);
+        return Ok(());
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!("{preview}. Install it?"))
+            .components(vec![packs::confirm_action_row(
+                &code,
+                report.installed.len(),
+            )])
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
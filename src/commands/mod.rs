@@ -0,0 +1,45 @@
+use crate::common::UserData;
+
+#[cfg(feature = "admin-commands")]
+pub mod admin;
+#[cfg(feature = "board-commands")]
+pub mod boards;
+#[cfg(feature = "library-commands")]
+pub mod library;
+#[cfg(feature = "playback-commands")]
+pub mod playback;
+#[cfg(feature = "settings-commands")]
+pub mod settings;
+
+pub type GenericError = Box<dyn std::error::Error + Send + Sync>;
+pub type PoiseError = GenericError;
+pub type PoiseContext<'a> = poise::Context<'a, UserData, PoiseError>;
+pub type PoiseResult = Result<(), PoiseError>;
+pub type PoiseAppContext<'a> = poise::ApplicationContext<'a, UserData, PoiseError>;
+pub type PoiseCommand = poise::Command<UserData, PoiseError>;
+
+/// Every built-in command, module by module. Modules left out of the build with their cargo
+/// feature are left out here too
+pub fn all() -> Vec<PoiseCommand> {
+    #[allow(unused_mut)]
+    let mut commands = Vec::new();
+    #[cfg(feature = "playback-commands")]
+    commands.extend(playback::commands());
+    #[cfg(feature = "library-commands")]
+    commands.extend(library::commands());
+    #[cfg(feature = "board-commands")]
+    commands.extend(boards::commands());
+    #[cfg(feature = "settings-commands")]
+    commands.extend(settings::commands());
+    #[cfg(feature = "admin-commands")]
+    commands.extend(admin::commands());
+    commands
+}
+
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum Date {
+    #[name = "Recent"]
+    Date,
+    #[name = "Latest"]
+    DateReverse,
+}
@@ -0,0 +1,669 @@
+use poise::CreateReply;
+use serenity::async_trait;
+use songbird::{Event, EventContext, EventHandler as VoiceEventHandler, TrackEvent};
+
+use crate::{
+    audio::TrackHandleHelper,
+    auth::{self, AuthContext},
+    branding,
+    common::LogResult,
+    db, errors,
+    helpers::{self, poise_check_msg, SongbirdHelper},
+    playback::{EnqueueOutcome, QueuedTrack, StatusMessage},
+};
+
+use super::{PoiseCommand, PoiseContext, PoiseResult};
+
+/// Top level commands of this module, see [`super::all`]
+pub fn commands() -> Vec<PoiseCommand> {
+    vec![
+        join(),
+        leave(),
+        move_voice(),
+        play(),
+        replay(),
+        pause(),
+        resume(),
+        now_playing(),
+        seek(),
+        queue(),
+        ambience(),
+    ]
+}
+
+#[poise::command(prefix_command, guild_only)]
+pub async fn deafen(_ctx: PoiseContext<'_>) -> PoiseResult {
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only)]
+pub async fn join(ctx: PoiseContext<'_>) -> PoiseResult {
+    log::info!("Bot joining voice channel...");
+    let (guild_id, connect_to) = helpers::get_author_voice_channel(&ctx)?;
+
+    if let Err(err) = helpers::check_bot_voice_join(&ctx, connect_to) {
+        log::info!("Bot can't join Voice Channel ID: {connect_to} - {err}");
+        poise_check_msg(ctx.say(err.to_string()).await);
+        return Ok(());
+    }
+
+    log::info!("Bot will join Guild ID: {guild_id}, Voice Channel: {connect_to}");
+    let manager = helpers::poise_songbird_get(&ctx).await;
+
+    match manager.join(guild_id, connect_to).await {
+        Ok(handler_lock) => {
+            // Attach an event handler to see notifications of all track errors
+            let mut handler = handler_lock.lock().await;
+            handler.add_global_event(TrackEvent::Error.into(), TrackErrorNotifier);
+            crate::listen::listen_for_speech(ctx.data(), &mut handler, guild_id);
+            #[cfg(feature = "voice-commands")]
+            crate::voice_commands::listen(
+                ctx.data(),
+                ctx.serenity_context().cache.clone(),
+                &mut handler,
+                guild_id,
+                ctx.channel_id(),
+            )
+            .log_err_msg("Failed listening for voice commands")
+            .ok();
+            log::info!("Bot joined Guild ID: {guild_id}, Voice Channel ID: {connect_to}");
+            ctx.data().playback.schedule_idle_leave(guild_id);
+        }
+        Err(err) => {
+            log::error!(
+                "Bot failed to join Guild ID: {guild_id}, Voice Channel ID: {connect_to} - {}",
+                err.to_string()
+            );
+            poise_check_msg(ctx.say(format!("Failed joining <#{connect_to}>")).await);
+            return Ok(());
+        }
+    }
+
+    if let Ok(settings) = ctx.data().settings_table().get_settings().log_err() {
+        if let Some(ref join_audio) = settings.join_audio {
+            log::info!("Detected join audio: {join_audio}. Attempting to play.");
+            match ctx
+                .data()
+                .audio_table()
+                .find_audio_row(db::UniqueAudioTableCol::Name(join_audio.clone()))
+            {
+                Some(row) => {
+                    log::debug!("bot join audio playing: {}", row.name);
+                    manager
+                        .play_audio(guild_id, connect_to, &row.audio_file, Some(ctx.author().id))
+                        .await
+                        .log_err()
+                        .ok();
+                }
+                None => log::error!("Couldn't locate join audio"),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[poise::command(prefix_command, guild_only)]
+pub async fn leave(ctx: PoiseContext<'_>) -> PoiseResult {
+    let manager = helpers::poise_songbird_get(&ctx).await;
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("command::leave - Failed to get guild_id")?;
+
+    let handler = manager.get(guild_id);
+    let channel_id = ctx.channel_id();
+
+    match handler {
+        Some(_handler) => {
+            // if leave audio set, play exit audio track
+            if let Ok(settings) = ctx.data().settings_table().get_settings().log_err() {
+                if let Some(ref leave_audio) = settings.leave_audio {
+                    log::info!("Detected leave audio: {leave_audio}. Attempting to play.");
+                    match ctx
+                        .data()
+                        .audio_table()
+                        .find_audio_row(db::UniqueAudioTableCol::Name(leave_audio.clone()))
+                    {
+                        Some(row) => {
+                            log::debug!("bot leave audio playing: {}", row.name);
+                            manager
+                                .play_audio_to_end(
+                                    guild_id,
+                                    channel_id,
+                                    &row.audio_file,
+                                    Some(ctx.author().id),
+                                )
+                                .await
+                                .log_err()
+                                .ok();
+                        }
+                        None => log::error!("Couldn't locate leave audio"),
+                    }
+                }
+            }
+
+            ctx.data().playback.stop(guild_id);
+
+            match manager.remove(guild_id).await {
+                Ok(_) => poise_check_msg(ctx.say("Left voice channel").await),
+                Err(e) => poise_check_msg(ctx.say(format!("Failed {:?}", e)).await),
+            }
+        }
+        None => poise_check_msg(ctx.reply("Not in a voice channel").await),
+    }
+
+    Ok(())
+}
+
+/// Move the bot to another voice channel, keeping the queue & what's playing
+#[poise::command(slash_command, guild_only, rename = "move", check = "auth::check_play")]
+pub async fn move_voice(
+    ctx: PoiseContext<'_>,
+    #[description = "Voice channel to move to"]
+    #[channel_types("Voice", "Stage")]
+    channel: serenity::all::GuildChannel,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    if let Err(err) = helpers::check_bot_voice_join(&ctx, channel.id) {
+        log::info!("Bot can't move to Voice Channel ID: {} - {err}", channel.id);
+        poise_check_msg(helpers::guild_reply(ctx, err.to_string()).await);
+        return Ok(());
+    }
+
+    let content = match ctx.data().playback.move_to(guild_id, channel.id).await {
+        Ok(()) => format!("Moved to <#{}>", channel.id),
+        Err(err) => match err.downcast_ref::<errors::AudioError>() {
+            Some(errors::AudioError::NotInVoiceChannel) => {
+                "Not in a voice channel. Use `join` first".to_string()
+            }
+            _ => {
+                log::error!("Failed moving to Voice Channel ID: {} - {err}", channel.id);
+                format!("Failed moving to <#{}>", channel.id)
+            }
+        },
+    };
+
+    poise_check_msg(helpers::guild_reply(ctx, content).await);
+    Ok(())
+}
+
+#[poise::command(slash_command, prefix_command, guild_only, check = "auth::check_play")]
+pub async fn play(
+    ctx: PoiseContext<'_>,
+    #[rename = "track"]
+    #[description = "Track to play"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+    #[description = "Play over sounds already playing instead of queueing. Defaults to server setting"]
+    overlap: Option<bool>,
+) -> PoiseResult {
+    log::info!("Playing audio track {audio_track_name}...");
+
+    let table = ctx.data().audio_table();
+    let row = table.find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()));
+    match row {
+        Some(row) => play_row(ctx, row, overlap).await?,
+        None => poise_check_msg(
+            ctx.reply(format!("Audio Track '{audio_track_name}' not found"))
+                .await,
+        ),
+    }
+
+    Ok(())
+}
+
+/// Play sound of row on behalf of the command's author, replying with whether it started or was
+/// queued. Sounds restricted to others, on cooldown, or past the channel's hourly cap, are refused
+/// unless the author's a moderator
+async fn play_row(
+    ctx: PoiseContext<'_>,
+    row: db::AudioTableRow,
+    overlap: Option<bool>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let channel_id = ctx.channel_id();
+
+    let permissions = ctx.permissions().await?;
+    if !permissions.is_moderator {
+        if let Err(err) = auth::check_can_play(
+            &ctx.data().db_pool,
+            guild_id,
+            channel_id,
+            &row,
+            Some(ctx.author().id),
+            &permissions.member_roles,
+        ) {
+            poise_check_msg(helpers::guild_reply(ctx, err.to_string()).await);
+            return Ok(());
+        }
+    }
+
+    let name = row.name.clone();
+    let reply = helpers::guild_reply(ctx, format!("Playing track `{name}`")).await?;
+    let mut track = QueuedTrack::from_row(&row, channel_id).requested_by(ctx.author().id);
+
+    let prefs = ctx
+        .data()
+        .user_prefs_table()
+        .get_prefs(ctx.author().id.get())
+        .log_err()?;
+    if let Some(volume) = prefs.volume {
+        track = track.volume(volume as f32 / 100.0);
+    }
+    if let Some(overlap) = overlap {
+        track = track.overlap(overlap);
+    }
+    if permissions.priority_queue {
+        track = track.priority(true);
+    }
+    // admins aren't held to the guild's queue limits
+    if permissions.is_admin {
+        track = track.bypass_limits(true);
+    }
+
+    // ephemeral replies can only be edited through the interaction, not as channel messages
+    if !crate::settings::ephemeral_replies(&ctx.data().settings_table(), guild_id) {
+        let message = reply.message().await?;
+        ctx.data()
+            .track_bot_messages(guild_id, message.channel_id, &[message.id]);
+        track = track.status_message(StatusMessage {
+            channel_id: message.channel_id,
+            message_id: message.id,
+        });
+    }
+
+    let outcome = ctx.data().playback.enqueue(guild_id, track).await;
+    if outcome.is_ok() {
+        ctx.data()
+            .record_play(guild_id, channel_id, Some(ctx.author().id), &row);
+    }
+
+    match outcome {
+        Ok(EnqueueOutcome::Started | EnqueueOutcome::Overlapping) => {}
+        Ok(EnqueueOutcome::Queued { position }) => {
+            reply
+                .edit(
+                    ctx,
+                    CreateReply::default()
+                        .content(format!("Queued track `{name}` (position {position})")),
+                )
+                .await
+                .log_err()
+                .ok();
+        }
+        Err(err) => {
+            reply
+                .edit(
+                    ctx,
+                    CreateReply::default().content(format!("Failed playing `{name}` - {err}")),
+                )
+                .await
+                .log_err()
+                .ok();
+        }
+    }
+
+    Ok(())
+}
+
+/// Play the sound played last in the server again
+#[poise::command(slash_command, guild_only, check = "auth::check_play")]
+pub async fn replay(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let audio_id = match helpers::last_played_audio_id(ctx.data(), guild_id) {
+        Ok(audio_id) => audio_id,
+        Err(err) => {
+            poise_check_msg(helpers::guild_reply(ctx, err.to_string()).await);
+            return Ok(());
+        }
+    };
+
+    match ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Id(audio_id))
+    {
+        Some(row) => {
+            log::info!("Replaying audio track {}...", row.name);
+            play_row(ctx, row, None).await?;
+        }
+        None => poise_check_msg(
+            helpers::guild_reply(ctx, "The sound played last was deleted".to_string()).await,
+        ),
+    }
+
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, check = "auth::check_play")]
+pub async fn pause(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let name = ctx.data().playback.pause(guild_id)?;
+
+    poise_check_msg(helpers::guild_reply(ctx, format!("Paused `{name}`")).await);
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, check = "auth::check_play")]
+pub async fn resume(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let name = ctx.data().playback.resume(guild_id)?;
+
+    poise_check_msg(helpers::guild_reply(ctx, format!("Resumed `{name}`")).await);
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, check = "auth::check_play")]
+pub async fn seek(
+    ctx: PoiseContext<'_>,
+    #[description = "Time to jump to, as seconds, m:ss, or h:mm:ss"] timestamp: String,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let position = helpers::parse_timestamp(&timestamp)
+        .ok_or("Timestamp must be seconds, m:ss, or h:mm:ss. ie: 90, 1:30")?;
+
+    let name = ctx.data().playback.seek(guild_id, position).await?;
+
+    poise_check_msg(
+        helpers::guild_reply(
+            ctx,
+            format!(
+                "Jumped to {} in `{name}`",
+                helpers::format_timestamp(position)
+            ),
+        )
+        .await,
+    );
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("list_queue", "move_queued", "remove_queued")
+)]
+pub async fn queue(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/queue command shouldn't be invoked directly. It should just house sub commands");
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "list")]
+pub async fn list_queue(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let playback = &ctx.data().playback;
+
+    let now_playing = match playback.now_playing(guild_id).await {
+        Some(now_playing) => match now_playing.requested_by {
+            Some(user_id) => format!("`{}` (requested by <@{user_id}>)", now_playing.name),
+            None => format!("`{}`", now_playing.name),
+        },
+        None => "-".into(),
+    };
+    let queued: Vec<_> = playback
+        .queued_track_names(guild_id)
+        .iter()
+        .enumerate()
+        .map(|(index, name)| format!("{}. `{name}`", index + 1))
+        .collect();
+
+    let embed = serenity::all::CreateEmbed::new()
+        .title("Queue")
+        .field("Now Playing", now_playing, false)
+        .field(
+            format!("Up Next ({})", queued.len()),
+            helpers::embed_field_list(&queued),
+            false,
+        );
+
+    let embed = branding::Branding::of(ctx.data(), Some(guild_id)).apply(embed);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "move", check = "auth::check_play")]
+pub async fn move_queued(
+    ctx: PoiseContext<'_>,
+    #[description = "Queue position of track to move"]
+    #[min = 1]
+    from: usize,
+    #[description = "Queue position to move track to"]
+    #[min = 1]
+    to: usize,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let name = ctx.data().playback.move_queued(guild_id, from, to)?;
+
+    poise_check_msg(
+        helpers::guild_reply(ctx, format!("Moved `{name}` to queue position {to}")).await,
+    );
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "remove",
+    check = "auth::check_play"
+)]
+pub async fn remove_queued(
+    ctx: PoiseContext<'_>,
+    #[description = "Queue position of track to remove"]
+    #[min = 1]
+    position: usize,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let name = ctx.data().playback.remove_queued(guild_id, position)?;
+
+    poise_check_msg(helpers::guild_reply(ctx, format!("Removed `{name}` from queue")).await);
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("play_ambience", "ambience_volume", "stop_ambience", "list_ambience")
+)]
+pub async fn ambience(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!(
+        "/ambience command shouldn't be invoked directly. It should just house sub commands"
+    );
+    Ok(())
+}
+
+/// Loop a sound as a named ambience layer, under whatever else plays
+#[poise::command(slash_command, guild_only, rename = "play", check = "auth::check_play")]
+pub async fn play_ambience(
+    ctx: PoiseContext<'_>,
+    #[rename = "track"]
+    #[description = "Sound to loop"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    audio_track_name: String,
+    #[description = "Layer name, ie: rain. Defaults to the sound's name. Replaces the sound of a playing layer"]
+    #[max_length = 32]
+    layer: Option<String>,
+    #[description = "Volume percentage of the layer, 0-200. Defaults to 100"]
+    #[max = 200]
+    volume: Option<u32>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let channel_id = ctx.channel_id();
+    let row = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(audio_track_name.clone()))
+        .ok_or(errors::AudioError::AudioTrackNotFound {
+            track: audio_track_name,
+        })?;
+
+    let permissions = ctx.permissions().await?;
+    if !permissions.is_moderator {
+        if let Err(err) = auth::check_can_play(
+            &ctx.data().db_pool,
+            guild_id,
+            channel_id,
+            &row,
+            Some(ctx.author().id),
+            &permissions.member_roles,
+        ) {
+            poise_check_msg(helpers::guild_reply(ctx, err.to_string()).await);
+            return Ok(());
+        }
+    }
+
+    let layer = layer
+        .map(|layer| layer.trim().to_string())
+        .filter(|layer| !layer.is_empty())
+        .unwrap_or_else(|| row.name.clone());
+    let volume = volume.unwrap_or(100);
+    let track = QueuedTrack::from_row(&row, channel_id).requested_by(ctx.author().id);
+    ctx.data()
+        .playback
+        .start_layer(guild_id, layer.clone(), track, volume as f32 / 100.0)
+        .await?;
+    ctx.data()
+        .record_play(guild_id, channel_id, Some(ctx.author().id), &row);
+
+    poise_check_msg(
+        helpers::guild_reply(
+            ctx,
+            format!(
+                "Looping `{}` as ambience layer `{layer}` at {volume}%",
+                row.name
+            ),
+        )
+        .await,
+    );
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "volume",
+    check = "auth::check_play"
+)]
+pub async fn ambience_volume(
+    ctx: PoiseContext<'_>,
+    #[description = "Ambience layer"]
+    #[autocomplete = "helpers::autocomplete_ambience_layer"]
+    layer: String,
+    #[description = "Volume percentage of the layer, 0-200"]
+    #[max = 200]
+    volume: u32,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    ctx.data()
+        .playback
+        .set_layer_volume(guild_id, &layer, volume as f32 / 100.0)
+        .await?;
+
+    poise_check_msg(
+        helpers::guild_reply(
+            ctx,
+            format!("Ambience layer `{layer}` volume set to {volume}%"),
+        )
+        .await,
+    );
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "stop", check = "auth::check_play")]
+pub async fn stop_ambience(
+    ctx: PoiseContext<'_>,
+    #[description = "Ambience layer to stop. Stops every layer when left out"]
+    #[autocomplete = "helpers::autocomplete_ambience_layer"]
+    layer: Option<String>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let stopped = ctx
+        .data()
+        .playback
+        .stop_layers(guild_id, layer.as_deref())?;
+
+    let content = match stopped.len() {
+        0 => "No ambience layers are playing".to_string(),
+        _ => format!(
+            "Stopped ambience layers: {}",
+            stopped
+                .iter()
+                .map(|layer| format!("`{layer}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    poise_check_msg(helpers::guild_reply(ctx, content).await);
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "list")]
+pub async fn list_ambience(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let layers: Vec<_> = ctx
+        .data()
+        .playback
+        .layers(guild_id)
+        .iter()
+        .map(|layer| {
+            format!(
+                "`{}` - `{}` at {:.0}%",
+                layer.name,
+                layer.sound,
+                layer.volume * 100.0
+            )
+        })
+        .collect();
+
+    let embed = serenity::all::CreateEmbed::new().title("Ambience").field(
+        format!(
+            "Layers ({}/{})",
+            layers.len(),
+            crate::playback::MAX_AMBIENCE_LAYERS
+        ),
+        helpers::embed_field_list(&layers),
+        false,
+    );
+
+    let embed = branding::Branding::of(ctx.data(), Some(guild_id)).apply(embed);
+    ctx.send(CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "nowplaying")]
+pub async fn now_playing(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let reply = match ctx.data().playback.now_playing(guild_id).await {
+        Some(now_playing) => CreateReply::default()
+            .embed(helpers::now_playing_embed(
+                ctx.data(),
+                guild_id,
+                &now_playing,
+            ))
+            .components(vec![helpers::transport_action_row(now_playing.paused)]),
+        None => CreateReply::default().content(errors::AudioError::NothingPlaying.to_string()),
+    };
+
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+struct TrackErrorNotifier;
+
+#[async_trait]
+impl VoiceEventHandler for TrackErrorNotifier {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(track_list) = ctx {
+            for (state, handle) in *track_list {
+                log::error!(
+                    "Track {:?} requested by {:?} encountered an error: {:?}",
+                    handle.uuid(),
+                    handle.requested_by(),
+                    state.playing
+                );
+            }
+        }
+
+        None
+    }
+}
@@ -0,0 +1,753 @@
+use poise::CreateReply;
+use serenity::all::CreateMessage;
+
+use crate::{
+    auth::{self, AuthContext},
+    birthdays,
+    common::LogResult,
+    db::{self, BoardLayout},
+    errors::{self, AuthError},
+    event_sounds::{self, EventSound, SoundEvent},
+    helpers::{self, poise_check_msg},
+    settings::{self, GuildSettingKey, GuildSettings},
+    settings_file, vars,
+};
+
+use super::{PoiseCommand, PoiseContext, PoiseError, PoiseResult};
+
+/// Top level commands of this module, see [`super::all`]
+pub fn commands() -> Vec<PoiseCommand> {
+    vec![
+        guild_settings(),
+        permissions(),
+        prefs(),
+        birthday(),
+        eventsounds(),
+    ]
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "settings",
+    subcommands(
+        "get_setting",
+        "set_setting",
+        "reset_setting",
+        "list_settings",
+        "channel_volume",
+        "export_settings",
+        "import_settings"
+    )
+)]
+pub async fn guild_settings(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!(
+        "/settings command shouldn't be invoked directly. It should just house sub commands"
+    );
+    Ok(())
+}
+
+#[poise::command(slash_command, prefix_command, guild_only, rename = "get")]
+pub async fn get_setting(
+    ctx: PoiseContext<'_>,
+    #[description = "Setting name"] key: GuildSettingKey,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let value = crate::settings::get_guild_setting(&ctx.data().settings_table(), guild_id, key);
+
+    poise_check_msg(
+        helpers::guild_reply(ctx, settings::format_setting(key, value.as_deref())).await,
+    );
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "set",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn set_setting(
+    ctx: PoiseContext<'_>,
+    #[description = "Setting name"] key: GuildSettingKey,
+    #[description = "Setting value"]
+    #[rest]
+    value: String,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    match key.parse(&value) {
+        Ok(value) => {
+            ctx.data()
+                .settings_table()
+                .set_guild_setting(guild_id.get(), key.key(), &value)
+                .log_err()?;
+            poise_check_msg(
+                helpers::guild_reply(ctx, format!("Setting `{key}` set to `{value}`")).await,
+            );
+        }
+        Err(err) => poise_check_msg(
+            helpers::guild_reply(ctx, format!("Invalid value for `{key}`. {err}")).await,
+        ),
+    }
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "reset",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn reset_setting(
+    ctx: PoiseContext<'_>,
+    #[description = "Setting name"] key: GuildSettingKey,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    ctx.data()
+        .settings_table()
+        .delete_guild_setting(guild_id.get(), key.key())
+        .log_err()?;
+
+    let value = key.default_value().unwrap_or("not set".into());
+    poise_check_msg(helpers::guild_reply(ctx, format!("Setting `{key}` reset to `{value}`")).await);
+    Ok(())
+}
+
+#[poise::command(slash_command, prefix_command, guild_only, rename = "list")]
+pub async fn list_settings(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let table = ctx.data().settings_table();
+    let guild_settings = GuildSettings::load(&table, guild_id);
+
+    let mut text: String = GuildSettingKey::ALL
+        .iter()
+        .map(|key| {
+            let value = guild_settings.value(*key);
+            format!("{}\n", settings::format_setting(*key, value.as_deref()))
+        })
+        .collect();
+
+    for (channel_id, volume) in table.list_channel_volumes(guild_id.get()).log_err()? {
+        text.push_str(&format!("`volume` of <#{channel_id}>: `{volume}`\n"));
+    }
+
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+/// Set or clear volume of a voice channel, overriding the guild's volume setting
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "channel-volume",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn channel_volume(
+    ctx: PoiseContext<'_>,
+    #[description = "Voice channel"]
+    #[channel_types("Voice", "Stage")]
+    channel: serenity::all::GuildChannel,
+    #[description = "Volume percentage, 0-200. Leave empty to use the server's volume"]
+    #[max = 200]
+    volume: Option<u32>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let table = ctx.data().settings_table();
+
+    let content = match volume {
+        Some(volume) => {
+            table
+                .set_channel_volume(guild_id.get(), channel.id.get(), volume)
+                .log_err()?;
+            format!("Volume of <#{}> set to `{volume}`", channel.id)
+        }
+        None => {
+            table
+                .delete_channel_volume(guild_id.get(), channel.id.get())
+                .log_err()?;
+            format!("<#{}> uses the server's volume", channel.id)
+        }
+    };
+
+    poise_check_msg(helpers::guild_reply(ctx, content).await);
+    Ok(())
+}
+
+/// Names of this server's roles & channels, to match settings by name in another server
+fn guild_names(ctx: PoiseContext<'_>) -> Result<settings_file::GuildNames, PoiseError> {
+    let guild = ctx.guild().ok_or("Guild not found in cache")?;
+    Ok(settings_file::GuildNames {
+        roles: guild
+            .roles
+            .iter()
+            .map(|(id, role)| (id.get(), role.name.clone()))
+            .collect(),
+        channels: guild
+            .channels
+            .iter()
+            .map(|(id, channel)| (id.get(), channel.name.clone()))
+            .collect(),
+    })
+}
+
+/// Export this server's settings, channel volumes, sound restrictions & board themes
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "export",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn export_settings(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let names = guild_names(ctx)?;
+
+    let db_pool = ctx.data().db_pool.clone();
+    let exported =
+        tokio::task::spawn_blocking(move || settings_file::export(&db_pool, guild_id, &names))
+            .await??;
+
+    helpers::send_export_files(
+        ctx,
+        "Settings of this server. `/settings import` it here or in another server",
+        vec![exported],
+    )
+    .await
+}
+
+/// Replace this server's settings with the ones of a `/settings export` file
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "import",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn import_settings(
+    ctx: PoiseContext<'_>,
+    #[description = "JSON file from /settings export"] file: serenity::all::Attachment,
+    #[description = "Report changes without writing to database"]
+    #[flag]
+    dry_run: bool,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    log::info!(
+        "Importing settings file {}. guild_id: {guild_id}",
+        file.filename
+    );
+    if file.size as u64 > settings_file::MAX_SETTINGS_FILE_SIZE {
+        return Err(errors::AudioError::FileTooLarge {
+            size: file.size as u64,
+            max_size: settings_file::MAX_SETTINGS_FILE_SIZE,
+        }
+        .into());
+    }
+
+    ctx.defer_ephemeral().await?;
+    let names = guild_names(ctx)?;
+    let content = file.download().await?;
+
+    let db_pool = ctx.data().db_pool.clone();
+    let report = tokio::task::spawn_blocking(move || {
+        let file = settings_file::parse(&content)?;
+        let report = settings_file::ImportReport::read(&db_pool, &file, &names)?;
+        if !dry_run {
+            report.apply(&db_pool, guild_id)?;
+        }
+        Ok::<_, PoiseError>(report)
+    })
+    .await??;
+
+    ctx.send(
+        CreateReply::default()
+            .content(report.summary(dry_run))
+            .ephemeral(true),
+    )
+    .await?;
+
+    if !dry_run {
+        helpers::send_audit_message(
+            ctx.http(),
+            ctx.data(),
+            Some(guild_id),
+            CreateMessage::new().content(format!(
+                "Settings imported from `{}` by <@{}>",
+                file.filename,
+                ctx.author().id
+            )),
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    subcommands("show_permissions", "ban_user", "unban_user")
+)]
+pub async fn permissions(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!(
+        "/permissions command shouldn't be invoked directly. It should just house sub commands"
+    );
+    Ok(())
+}
+
+#[poise::command(slash_command, prefix_command, guild_only, rename = "show")]
+pub async fn show_permissions(
+    ctx: PoiseContext<'_>,
+    #[description = "Member to show permissions of. Defaults to you"] user: Option<
+        serenity::all::User,
+    >,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let permissions = match user {
+        Some(user) if user.id != ctx.author().id => {
+            ctx.permissions().await?.check_moderator()?;
+            let member = guild_id.member(ctx, user.id).await?;
+            ctx.member_permissions(&member)?
+        }
+        _ => ctx.permissions().await?,
+    };
+
+    let allowed = |result: Result<(), AuthError>| match result {
+        Ok(_) => "yes".to_string(),
+        Err(err) => format!("no - {err}"),
+    };
+    let plays_remaining = permissions
+        .plays_remaining
+        .map_or("unlimited".to_string(), |plays| plays.to_string());
+
+    let text = format!(
+        "Play sounds: {}\nManage sounds: {}\nModerator: {}\nPlays remaining today: {plays_remaining}",
+        allowed(permissions.check_play()),
+        allowed(permissions.check_manage_sounds()),
+        allowed(permissions.check_moderator()),
+    );
+
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "ban",
+    check = "auth::check_moderator"
+)]
+pub async fn ban_user(
+    ctx: PoiseContext<'_>,
+    #[description = "Member to ban from the soundboard"] user: serenity::all::User,
+    #[description = "Reason for ban"]
+    #[rest]
+    reason: Option<String>,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    ctx.data().banned_users_table().ban_user(
+        guild_id.get(),
+        user.id.get(),
+        ctx.author().id.get(),
+        reason.as_deref(),
+    )?;
+    ctx.data().authorizer.invalidate(guild_id, user.id);
+
+    let text = format!("<@{}> banned from the soundboard", user.id);
+    poise_check_msg(helpers::guild_reply(ctx, text.clone()).await);
+    helpers::send_audit_message(
+        ctx.http(),
+        ctx.data(),
+        Some(guild_id),
+        CreateMessage::new().content(format!(
+            "{text} by <@{}>. Reason: {}",
+            ctx.author().id,
+            reason.as_deref().unwrap_or("none")
+        )),
+    )
+    .await;
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    rename = "unban",
+    check = "auth::check_moderator"
+)]
+pub async fn unban_user(
+    ctx: PoiseContext<'_>,
+    #[description = "Member to unban from the soundboard"] user: serenity::all::User,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let was_banned = ctx
+        .data()
+        .banned_users_table()
+        .unban_user(guild_id.get(), user.id.get())?;
+    ctx.data().authorizer.invalidate(guild_id, user.id);
+
+    if !was_banned {
+        poise_check_msg(helpers::guild_reply(ctx, format!("<@{}> isn't banned", user.id)).await);
+        return Ok(());
+    }
+
+    let text = format!("<@{}> unbanned from the soundboard", user.id);
+    poise_check_msg(helpers::guild_reply(ctx, text.clone()).await);
+    helpers::send_audit_message(
+        ctx.http(),
+        ctx.data(),
+        Some(guild_id),
+        CreateMessage::new().content(format!("{text} by <@{}>", ctx.author().id)),
+    )
+    .await;
+
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("show_prefs", "set_prefs", "reset_prefs")
+)]
+pub async fn prefs(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!("/prefs command shouldn't be invoked directly. It should just house sub commands");
+    Ok(())
+}
+
+fn format_prefs(prefs: &db::UserPrefs) -> String {
+    format!(
+        "Board: `{}`\nButtons per message: `{}`\nVolume: `{}`\nCaptions: `{}`",
+        prefs.board,
+        prefs.page_size.unwrap_or(vars::ACTION_ROWS_LIMIT as u32),
+        prefs
+            .volume
+            .map_or("guild volume".to_string(), |volume| format!("{volume}%")),
+        match prefs.captions {
+            true => "on",
+            false => "off",
+        },
+    )
+}
+
+#[poise::command(slash_command, prefix_command, rename = "show")]
+pub async fn show_prefs(ctx: PoiseContext<'_>) -> PoiseResult {
+    let prefs = ctx
+        .data()
+        .user_prefs_table()
+        .get_prefs(ctx.author().id.get())?;
+
+    ctx.send(
+        CreateReply::default()
+            .content(format_prefs(&prefs))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, prefix_command, rename = "set")]
+pub async fn set_prefs(
+    ctx: PoiseContext<'_>,
+    #[description = "Show all sounds, or only your favorites, in /sounds display"] board: Option<
+        BoardLayout,
+    >,
+    #[description = "Buttons per soundboard message"]
+    #[min = 5]
+    #[max = 25]
+    page_size: Option<u32>,
+    #[description = "Your playback volume percentage, overrides guild volume"]
+    #[max = 200]
+    volume: Option<u32>,
+    #[description = "Direct message you the name of each sound played in your voice channel"]
+    captions: Option<bool>,
+) -> PoiseResult {
+    let table = ctx.data().user_prefs_table();
+    let mut prefs = table.get_prefs(ctx.author().id.get())?;
+
+    if let Some(board) = board {
+        prefs.board = board;
+    }
+    if let Some(page_size) = page_size {
+        prefs.page_size = Some(page_size.clamp(5, vars::ACTION_ROWS_LIMIT as u32));
+    }
+    if let Some(volume) = volume {
+        prefs.volume = Some(volume.min(200));
+    }
+    if let Some(captions) = captions {
+        prefs.captions = captions;
+    }
+    table.save_prefs(&prefs)?;
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!("Saved preferences\n{}", format_prefs(&prefs)))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(slash_command, prefix_command, rename = "reset")]
+pub async fn reset_prefs(ctx: PoiseContext<'_>) -> PoiseResult {
+    let prefs = db::UserPrefs {
+        user_id: ctx.author().id.get(),
+        ..Default::default()
+    };
+    ctx.data().user_prefs_table().save_prefs(&prefs)?;
+
+    ctx.send(
+        CreateReply::default()
+            .content(format!("Reset preferences\n{}", format_prefs(&prefs)))
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("set_birthday", "remove_birthday", "list_birthdays")
+)]
+pub async fn birthday(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!(
+        "/birthday command shouldn't be invoked directly. It should just house sub commands"
+    );
+    Ok(())
+}
+
+/// Greet a member in voice with a sound every year on their birthday or anniversary
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "set",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn set_birthday(
+    ctx: PoiseContext<'_>,
+    #[description = "Member to celebrate"] user: serenity::all::User,
+    #[description = "Day to celebrate, as MM-DD or YYYY-MM-DD (ie: 10-16)"] date: String,
+    #[description = "Sound to play"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    sound: String,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let Some(day) = birthdays::parse_day(&date) else {
+        poise_check_msg(
+            helpers::guild_reply(ctx, "Date must be a month & day, as `MM-DD` (ie: `10-16`)").await,
+        );
+        return Ok(());
+    };
+    let Some(row) = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(sound.clone()))
+    else {
+        poise_check_msg(helpers::guild_reply(ctx, format!("Sound `{sound}` not found")).await);
+        return Ok(());
+    };
+
+    ctx.data().birthdays_table().upsert_row(
+        guild_id.get(),
+        user.id.get(),
+        &day,
+        row.id,
+        ctx.channel_id().get(),
+    )?;
+
+    poise_check_msg(
+        helpers::guild_reply(
+            ctx,
+            format!(
+                "<@{}> is celebrated every {day} with `{}`, once they're in voice. Greetings are posted in this channel",
+                user.id, row.name
+            ),
+        )
+        .await,
+    );
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "remove",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn remove_birthday(
+    ctx: PoiseContext<'_>,
+    #[description = "Member to stop celebrating"] user: serenity::all::User,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+
+    let text = match ctx
+        .data()
+        .birthdays_table()
+        .delete_row(guild_id.get(), user.id.get())?
+    {
+        true => format!("Removed birthday of <@{}>", user.id),
+        false => format!("<@{}> has no birthday set", user.id),
+    };
+
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+#[poise::command(slash_command, guild_only, rename = "list")]
+pub async fn list_birthdays(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let rows = ctx.data().birthdays_table().rows(guild_id.get())?;
+    let audio_table = ctx.data().audio_table();
+
+    let text = match rows.is_empty() {
+        true => "No birthdays set. Add one with `/birthday set`".to_string(),
+        false => rows
+            .iter()
+            .map(|row| {
+                let sound = audio_table
+                    .find_audio_row(db::UniqueAudioTableCol::Id(row.audio_id))
+                    .map_or("deleted sound".to_string(), |sound| {
+                        format!("`{}`", sound.name)
+                    });
+                format!("{} <@{}> {sound}\n", row.day, row.user_id)
+            })
+            .collect(),
+    };
+
+    poise_check_msg(helpers::guild_reply(ctx, text).await);
+    Ok(())
+}
+
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("set_event_sound", "clear_event_sound", "list_event_sounds")
+)]
+pub async fn eventsounds(_ctx: PoiseContext<'_>) -> PoiseResult {
+    log::warn!(
+        "/eventsounds command shouldn't be invoked directly. It should just house sub commands"
+    );
+    Ok(())
+}
+
+/// Play a sound when members join, boost the server, or a poll ends
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "set",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn set_event_sound(
+    ctx: PoiseContext<'_>,
+    #[description = "Event to play the sound for"] event: SoundEvent,
+    #[description = "Sound to play"]
+    #[autocomplete = "helpers::autocomplete_audio_track_name"]
+    track: String,
+    #[description = "Post a text cue when the bot isn't in voice. Defaults to false"] cue: Option<
+        bool,
+    >,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let Some(row) = ctx
+        .data()
+        .audio_table()
+        .find_audio_row(db::UniqueAudioTableCol::Name(track.clone()))
+    else {
+        poise_check_msg(helpers::guild_reply(ctx, format!("Sound `{track}` not found")).await);
+        return Ok(());
+    };
+
+    let cue = cue.unwrap_or(false);
+    event_sounds::set(
+        &ctx.data().settings_table(),
+        guild_id,
+        event,
+        Some(EventSound {
+            audio_id: row.id,
+            cue,
+        }),
+    )?;
+
+    let cue_text = match cue {
+        true => ", or posts a text cue when not in voice",
+        false => "",
+    };
+    poise_check_msg(
+        helpers::guild_reply(
+            ctx,
+            format!("`{event}` events play `{}`{cue_text}", row.name),
+        )
+        .await,
+    );
+    Ok(())
+}
+
+/// Stop playing a sound for an event
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "clear",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn clear_event_sound(
+    ctx: PoiseContext<'_>,
+    #[description = "Event to stop playing a sound for"] event: SoundEvent,
+) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    event_sounds::set(&ctx.data().settings_table(), guild_id, event, None)?;
+
+    poise_check_msg(
+        helpers::guild_reply(ctx, format!("`{event}` events no longer play a sound")).await,
+    );
+    Ok(())
+}
+
+/// Display the sounds played for events
+#[poise::command(
+    slash_command,
+    guild_only,
+    rename = "list",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn list_event_sounds(ctx: PoiseContext<'_>) -> PoiseResult {
+    let guild_id = ctx.guild_id().ok_or("No guild id found")?;
+    let settings_table = ctx.data().settings_table();
+    let audio_table = ctx.data().audio_table();
+
+    let lines: Vec<_> = SoundEvent::ALL
+        .iter()
+        .map(|event| {
+            let sound = event_sounds::get(&settings_table, guild_id, *event);
+            let text = match sound {
+                Some(sound) => {
+                    let name = audio_table
+                        .find_audio_row(db::UniqueAudioTableCol::Id(sound.audio_id))
+                        .map_or("deleted sound".to_string(), |row| format!("`{}`", row.name));
+                    match sound.cue {
+                        true => format!("{name}, with a text cue"),
+                        false => name,
+                    }
+                }
+                None => "-".to_string(),
+            };
+            format!("- `{event}`: {text}")
+        })
+        .collect();
+
+    poise_check_msg(helpers::guild_reply(ctx, lines.join("\n")).await);
+    Ok(())
+}
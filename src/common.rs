@@ -1,18 +1,64 @@
 use std::path;
+use std::sync::Arc;
 
-use crate::audio::{AudioDir, AudioFile};
-use crate::commands::PoiseError;
-use crate::config::Config;
-use crate::db::{AudioTable, DbConnection, SettingsTable};
+use serenity::all::{ChannelId, GuildId, MessageId, UserId};
+
+use crate::audio::AudioDir;
+use crate::auth::Authorizer;
+use crate::collision::PendingImports;
+use crate::config::SharedConfig;
+use crate::db::{
+    ApiKeysTable, ApiUsageTable, AudioTable, AudioTableRow, BannedSoundsTable, BannedUsersTable,
+    BirthdaysTable, BoardOrderTable, BoardThemesTable, BotMessagesTable, DbConnection,
+    FeatureFlagsTable, JobsTable, PlayHistoryRowInsert, PlayHistoryTable, PostedBoardsTable,
+    SettingsTable, SoundRequestsTable, SoundRestrictionsTable, UserPrefsTable,
+};
+use crate::extensions::Extensions;
+use crate::metrics::MetricsRegistry;
+use crate::operations::Operations;
+use crate::playback::Playback;
+use crate::registration::CommandRegistrar;
 
 pub struct UserData {
-    pub config: Config,
+    pub config: Arc<SharedConfig>,
     pub db_pool: r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>,
+    pub metrics: MetricsRegistry,
+    pub playback: Arc<Playback>,
+    pub authorizer: Authorizer,
+    pub registrar: CommandRegistrar,
+    pub pending_imports: PendingImports,
+    pub operations: Operations,
+    pub extensions: Extensions,
 }
 
 impl UserData {
+    /// Data with its own caches & registries, sharing config, database & playback
+    pub fn new(
+        config: Arc<SharedConfig>,
+        db_pool: r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>,
+        playback: Arc<Playback>,
+    ) -> Self {
+        Self {
+            config,
+            db_pool,
+            metrics: MetricsRegistry::new(),
+            playback,
+            authorizer: Authorizer::new(),
+            registrar: CommandRegistrar::new(),
+            pending_imports: PendingImports::default(),
+            operations: Operations::default(),
+            extensions: Extensions::default(),
+        }
+    }
+
+    /// Hand events to extensions, see [`Extensions::dispatch`]
+    pub fn with_extensions(mut self, extensions: Extensions) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
     pub fn read_audio_dir(&self) -> AudioDir {
-        read_audio_dir(&self.config.audio_dir)
+        read_audio_dir(&self.config.get().audio_dir)
     }
 
     pub fn db_connection(&self) -> DbConnection {
@@ -29,52 +75,110 @@ impl UserData {
         SettingsTable::new(self.db_connection())
     }
 
-    /// Attempts to move file to audio dir. Will attempt copy if move fails
-    /// Moves can fail if target file and destination audio directory are on separate partitions of file systems
-    pub fn move_file_to_audio_dir(
-        &self,
-        path: impl AsRef<path::Path>,
-    ) -> Result<AudioFile, PoiseError> {
-        let target_file = path.as_ref();
-        let audio_dir = &self.config.audio_dir;
-
-        log::info!(
-            "Move file: {} to audio dir: {}",
-            target_file.to_string_lossy(),
-            audio_dir.to_string_lossy()
-        );
-
-        if !target_file.exists() {
-            return Err("Target file doesn't exist".into()).log_err();
-        }
+    pub fn play_history_table(&self) -> PlayHistoryTable {
+        PlayHistoryTable::new(self.db_connection())
+    }
+
+    pub fn banned_sounds_table(&self) -> BannedSoundsTable {
+        BannedSoundsTable::new(self.db_connection())
+    }
+
+    pub fn banned_users_table(&self) -> BannedUsersTable {
+        BannedUsersTable::new(self.db_connection())
+    }
+
+    pub fn user_prefs_table(&self) -> UserPrefsTable {
+        UserPrefsTable::new(self.db_connection())
+    }
+
+    pub fn sound_requests_table(&self) -> SoundRequestsTable {
+        SoundRequestsTable::new(self.db_connection())
+    }
+
+    pub fn feature_flags_table(&self) -> FeatureFlagsTable {
+        FeatureFlagsTable::new(self.db_connection())
+    }
+
+    pub fn jobs_table(&self) -> JobsTable {
+        JobsTable::new(self.db_connection())
+    }
 
-        if !target_file.is_file() {
-            return Err("Target file is not a file.".into()).log_err();
+    pub fn board_order_table(&self) -> BoardOrderTable {
+        BoardOrderTable::new(self.db_connection())
+    }
+
+    pub fn board_themes_table(&self) -> BoardThemesTable {
+        BoardThemesTable::new(self.db_connection())
+    }
+
+    pub fn birthdays_table(&self) -> BirthdaysTable {
+        BirthdaysTable::new(self.db_connection())
+    }
+
+    pub fn sound_restrictions_table(&self) -> SoundRestrictionsTable {
+        SoundRestrictionsTable::new(self.db_connection())
+    }
+
+    pub fn api_keys_table(&self) -> ApiKeysTable {
+        ApiKeysTable::new(self.db_connection())
+    }
+
+    pub fn api_usage_table(&self) -> ApiUsageTable {
+        ApiUsageTable::new(self.db_connection())
+    }
+
+    pub fn bot_messages_table(&self) -> BotMessagesTable {
+        BotMessagesTable::new(self.db_connection())
+    }
+
+    pub fn posted_boards_table(&self) -> PostedBoardsTable {
+        PostedBoardsTable::new(self.db_connection())
+    }
+
+    /// Track messages the bot sent to channel, so they're removed by message retention & `/cleanup`
+    pub fn track_bot_messages(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        message_ids: &[MessageId],
+    ) {
+        let table = self.bot_messages_table();
+        for message_id in message_ids {
+            table
+                .insert_message(
+                    guild_id.get(),
+                    channel_id.get(),
+                    message_id.get(),
+                    chrono::Utc::now(),
+                )
+                .log_err_msg("Failed tracking bot message")
+                .ok();
         }
+    }
 
-        let target_file_name = target_file
-            .file_name()
-            .ok_or("Failed to get target path file name")
-            .log_err()?;
-        let dest_file = audio_dir.join(target_file_name);
+    /// Record track played on behalf of user to play history. Counts toward user's daily play quota,
+    /// and the hourly cap of the channel it was played from
+    pub fn record_play(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        user_id: Option<UserId>,
+        audio_row: &AudioTableRow,
+    ) {
+        self.play_history_table()
+            .insert_row(&PlayHistoryRowInsert {
+                guild_id: guild_id.get(),
+                user_id: user_id.map(|id| id.get()),
+                channel_id: Some(channel_id.get()),
+                audio_id: Some(audio_row.id),
+                audio_name: audio_row.name.clone(),
+                played_at: chrono::Utc::now(),
+            })
+            .log_err_msg("Failed recording play history")
+            .ok();
 
-        match std::fs::rename(target_file, &dest_file) {
-            Ok(_) => Ok(AudioFile::new(dest_file)),
-            Err(err) => {
-                log::error!(
-                    "Failed to move target file to audio dir - {err}. Attempting copy instead."
-                );
-                std::fs::copy(target_file, &dest_file)
-                    .log_err_msg("Failed to copy target file to audio dir")?;
-
-                log::info!(
-                    "Copied target file: {} to destination: {}",
-                    target_file.to_string_lossy(),
-                    dest_file.to_string_lossy()
-                );
-
-                Ok(AudioFile::new(dest_file))
-            }
+        if let Some(user_id) = user_id {
+            self.authorizer.invalidate(guild_id, user_id);
         }
     }
 }
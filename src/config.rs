@@ -1,10 +1,19 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 use std::{env, path, str::FromStr};
 
 use serde::{Deserialize, Deserializer};
 
+use crate::naming::NameCharset;
+use crate::registration::CommandRegistration;
+use crate::vars;
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Config {
+    /// Not needed for command line commands or `--dry-run`. Checked before the bot connects
+    #[serde(default)]
     pub application_id: u64,
+    #[serde(default)]
     pub token: String,
     #[serde(default = "default_audio_dir")]
     pub audio_dir: path::PathBuf,
@@ -14,9 +23,90 @@ pub struct Config {
     pub sqlite_db_file: path::PathBuf,
     #[serde(
         default = "default_max_audio_file_duration",
-        deserialize_with = "de_max_audio_file_duration"
+        deserialize_with = "de_duration_millis"
     )]
     pub max_audio_file_duration: std::time::Duration,
+    #[serde(default = "default_max_audio_file_size")]
+    pub max_audio_file_size: u64,
+    #[serde(default = "default_scan_concurrency")]
+    pub scan_concurrency: usize,
+    #[serde(default)]
+    pub audit_channel_id: Option<u64>,
+    #[serde(default)]
+    pub record_command_stats: bool,
+    #[serde(default)]
+    pub sentry_dsn: Option<String>,
+    #[serde(default = "default_ffmpeg_path")]
+    pub ffmpeg_path: path::PathBuf,
+    #[serde(default)]
+    pub command_registration: CommandRegistration,
+    /// Most database connections open at once
+    #[serde(default = "default_db_pool_size")]
+    pub db_pool_size: u32,
+    /// How long to wait for a free database connection, before giving up
+    #[serde(
+        default = "default_db_connection_timeout",
+        deserialize_with = "de_duration_millis"
+    )]
+    pub db_connection_timeout: std::time::Duration,
+    /// How long a connection waits on another connection's lock, before failing with `database is locked`
+    #[serde(
+        default = "default_db_busy_timeout",
+        deserialize_with = "de_duration_millis"
+    )]
+    pub db_busy_timeout: std::time::Duration,
+    /// Whisper model listened to voice commands with. Voice commands are off without one
+    #[serde(default)]
+    pub whisper_model_path: Option<path::PathBuf>,
+    /// Said before the sound to play, ie: "hey soundboard, play airhorn"
+    #[serde(default = "default_wake_phrase")]
+    #[cfg_attr(not(feature = "voice-commands"), allow(dead_code))]
+    pub wake_phrase: String,
+    /// Directory sound packs are published to & installed from. Shared between bot deployments,
+    /// ie: a network share, so packs published in one can be installed in another
+    #[serde(default)]
+    pub packs_dir: Option<path::PathBuf>,
+    /// Url of a json index of starter packs, browsed & installed with `/packs`
+    #[serde(default)]
+    pub packs_index_url: Option<String>,
+    /// Url POSTed metadata of audio before it goes live, answering whether to allow it
+    #[serde(default)]
+    pub moderation_webhook_url: Option<String>,
+    /// Program run with metadata of audio on stdin before it goes live, printing whether to allow it
+    #[serde(default)]
+    pub moderation_command: Option<path::PathBuf>,
+    /// Transcribe new sounds with the whisper model, so they can be searched by what's said in them
+    #[serde(default)]
+    pub transcribe_sounds: bool,
+    /// Fewest characters in names of new sounds
+    #[serde(default = "default_sound_name_min_length")]
+    pub sound_name_min_length: usize,
+    /// Most characters in names of new sounds. Can't be more than fits on a button
+    #[serde(default = "default_sound_name_max_length")]
+    pub sound_name_max_length: usize,
+    /// Characters names of new sounds can use
+    #[serde(default)]
+    pub sound_name_charset: NameCharset,
+    /// Make names of new sounds lowercase slugs, ie: `Air Horn!` → `air-horn`
+    #[serde(default)]
+    pub sound_name_slugs: bool,
+    /// Address the HTTP API listens on, ie: `127.0.0.1:8080`. The API is off without one
+    #[serde(default)]
+    pub api_address: Option<std::net::SocketAddr>,
+    /// Requests per minute an HTTP API key, or a client address sending unknown keys, can make.
+    /// 0 removes the limit
+    #[serde(default = "default_api_rate_limit")]
+    pub api_rate_limit: u32,
+    /// Send anonymous usage counters to `telemetry_url` once a day. Off unless turned on
+    #[serde(default)]
+    pub telemetry: bool,
+    /// Endpoint telemetry reports are POSTed to, as json
+    #[serde(default)]
+    pub telemetry_url: Option<String>,
+    /// Megabytes of memory the most played sounds are kept in, so they start playing without
+    /// reading their file. 0 turns prefetching off
+    #[serde(default = "default_prefetch_memory_mb")]
+    pub prefetch_memory_mb: u32,
 }
 
 impl Config {
@@ -34,20 +124,99 @@ impl Config {
             .try_deserialize()
             .unwrap_or_else(|err| panic!("Failed deserializing config - {err}"));
 
-        cfg.validate();
+        cfg.validate().unwrap_or_else(|err| panic!("{err}"));
 
         cfg
     }
 
-    pub fn validate(&self) {
+    /// Load config again from environment & dotenv file. Values in the dotenv file take precedence,
+    /// since the process environment can't change while running. Fields that need a restart
+    /// to take effect keep their current values, and are returned as warnings.
+    pub fn reload(&self) -> Result<(Self, Vec<String>), String> {
+        let env_file = env::var("DISCORD_BOT_DOTENV_FILE");
+        let env_file = env_file.unwrap_or("./.env".into());
+
+        let mut vars: HashMap<String, String> = env::vars().collect();
+        // dotenv has no overriding loader, iterating the file is the only way to read changed values
+        #[allow(deprecated)]
+        if let Ok(iter) = dotenv::from_filename_iter(&env_file) {
+            for item in iter {
+                let (key, value) =
+                    item.map_err(|err| format!("Failed reading dotenv file {env_file} - {err}"))?;
+                vars.insert(key, value);
+            }
+        }
+
+        let mut cfg: Config = config::Config::builder()
+            .add_source(config::Environment::with_prefix("discord_bot").source(Some(vars)))
+            .build()
+            .map_err(|err| format!("Missing/Incorrect environment variables - {err}"))?
+            .try_deserialize()
+            .map_err(|err| format!("Failed deserializing config - {err}"))?;
+
+        cfg.validate()?;
+
+        let mut warnings = vec![];
+        let mut warn_changed = |name: &str, changed: bool| {
+            if changed {
+                warnings.push(format!(
+                    "{name} changed. Restart required for it to take effect."
+                ));
+            }
+        };
+        warn_changed("token", cfg.token != self.token);
+        warn_changed("application_id", cfg.application_id != self.application_id);
+        warn_changed("command_prefix", cfg.command_prefix != self.command_prefix);
+        warn_changed("sqlite_db_file", cfg.sqlite_db_file != self.sqlite_db_file);
+        warn_changed("sentry_dsn", cfg.sentry_dsn != self.sentry_dsn);
+        warn_changed("db_pool_size", cfg.db_pool_size != self.db_pool_size);
+        warn_changed(
+            "db_connection_timeout",
+            cfg.db_connection_timeout != self.db_connection_timeout,
+        );
+        warn_changed(
+            "db_busy_timeout",
+            cfg.db_busy_timeout != self.db_busy_timeout,
+        );
+        warn_changed(
+            "command_registration",
+            cfg.command_registration != self.command_registration,
+        );
+        warn_changed(
+            "whisper_model_path",
+            cfg.whisper_model_path != self.whisper_model_path,
+        );
+        warn_changed("api_address", cfg.api_address != self.api_address);
+
+        cfg.token = self.token.clone();
+        cfg.application_id = self.application_id;
+        cfg.command_prefix = self.command_prefix.clone();
+        cfg.sqlite_db_file = self.sqlite_db_file.clone();
+        cfg.sentry_dsn = self.sentry_dsn.clone();
+        cfg.db_pool_size = self.db_pool_size;
+        cfg.db_connection_timeout = self.db_connection_timeout;
+        cfg.db_busy_timeout = self.db_busy_timeout;
+        cfg.command_registration = self.command_registration;
+        cfg.whisper_model_path = self.whisper_model_path.clone();
+        cfg.api_address = self.api_address;
+
+        Ok((cfg, warnings))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
         let mut errs: Vec<String> = vec![];
 
         self.validate_audio_dir().map_err(|err| errs.push(err)).ok();
+        self.validate_sound_name_lengths()
+            .map_err(|err| errs.push(err))
+            .ok();
 
         if errs.len() > 0 {
             let err_msg: String = errs.iter().map(|err| format!("{err}\n")).collect();
-            panic!("{}", err_msg);
+            return Err(err_msg);
         }
+
+        Ok(())
     }
 
     fn validate_audio_dir(&self) -> Result<(), String> {
@@ -67,6 +236,18 @@ impl Config {
 
         Ok(())
     }
+
+    fn validate_sound_name_lengths(&self) -> Result<(), String> {
+        let (min, max) = (self.sound_name_min_length, self.sound_name_max_length);
+        if min == 0 || min > max || max > vars::BTN_LABEL_MAX_LEN {
+            return Err(format!(
+                "Sound name lengths must be between 1 and {} characters, with the min length no more than the max length - min: {min}, max: {max}",
+                vars::BTN_LABEL_MAX_LEN
+            ));
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for Config {
@@ -78,7 +259,60 @@ impl Default for Config {
             command_prefix: default_command_prefix(),
             sqlite_db_file: default_sqlite_db_file(),
             max_audio_file_duration: default_max_audio_file_duration(),
+            max_audio_file_size: default_max_audio_file_size(),
+            scan_concurrency: default_scan_concurrency(),
+            audit_channel_id: None,
+            record_command_stats: false,
+            sentry_dsn: None,
+            ffmpeg_path: default_ffmpeg_path(),
+            command_registration: CommandRegistration::default(),
+            db_pool_size: default_db_pool_size(),
+            db_connection_timeout: default_db_connection_timeout(),
+            db_busy_timeout: default_db_busy_timeout(),
+            whisper_model_path: None,
+            wake_phrase: default_wake_phrase(),
+            packs_dir: None,
+            packs_index_url: None,
+            moderation_webhook_url: None,
+            moderation_command: None,
+            transcribe_sounds: false,
+            sound_name_min_length: default_sound_name_min_length(),
+            sound_name_max_length: default_sound_name_max_length(),
+            sound_name_charset: NameCharset::default(),
+            sound_name_slugs: false,
+            api_address: None,
+            api_rate_limit: default_api_rate_limit(),
+            telemetry: false,
+            telemetry_url: None,
+            prefetch_memory_mb: default_prefetch_memory_mb(),
+        }
+    }
+}
+
+/// Config shared between commands, event handlers and the reload signal handler.
+/// Swapped out as a whole on reload, so readers always see a consistent config.
+pub struct SharedConfig(RwLock<Arc<Config>>);
+
+impl SharedConfig {
+    pub fn new(config: Config) -> Arc<Self> {
+        Arc::new(Self(RwLock::new(Arc::new(config))))
+    }
+
+    pub fn get(&self) -> Arc<Config> {
+        self.0.read().unwrap().clone()
+    }
+
+    /// Reload config. Returns warnings about fields that couldn't be reloaded
+    pub fn reload(&self) -> Result<Vec<String>, String> {
+        let (config, warnings) = self.get().reload()?;
+        *self.0.write().unwrap() = Arc::new(config);
+
+        log::info!("Reloaded config");
+        for warning in &warnings {
+            log::warn!("Config reload - {warning}");
         }
+
+        Ok(warnings)
     }
 }
 
@@ -98,7 +332,53 @@ pub fn default_max_audio_file_duration() -> std::time::Duration {
     std::time::Duration::from_secs(7)
 }
 
-pub fn de_max_audio_file_duration<'de, D>(deserializer: D) -> Result<std::time::Duration, D::Error>
+/// 10 MB
+pub fn default_max_audio_file_size() -> u64 {
+    10 * 1024 * 1024
+}
+
+fn default_ffmpeg_path() -> path::PathBuf {
+    path::PathBuf::from_str("ffmpeg").unwrap()
+}
+
+fn default_wake_phrase() -> String {
+    "hey soundboard".into()
+}
+
+fn default_sound_name_min_length() -> usize {
+    1
+}
+
+fn default_sound_name_max_length() -> usize {
+    vars::BTN_LABEL_MAX_LEN
+}
+
+fn default_db_pool_size() -> u32 {
+    10
+}
+
+fn default_db_connection_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(30)
+}
+
+fn default_api_rate_limit() -> u32 {
+    60
+}
+
+fn default_prefetch_memory_mb() -> u32 {
+    64
+}
+
+fn default_db_busy_timeout() -> std::time::Duration {
+    std::time::Duration::from_secs(5)
+}
+
+fn default_scan_concurrency() -> usize {
+    std::thread::available_parallelism().map_or(4, |n| n.get())
+}
+
+/// Duration from a number of milliseconds
+pub fn de_duration_millis<'de, D>(deserializer: D) -> Result<std::time::Duration, D::Error>
 where
     D: Deserializer<'de>,
 {
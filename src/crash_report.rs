@@ -0,0 +1,79 @@
+use serenity::all::{GuildId, UserId};
+
+use crate::commands::{PoiseContext, PoiseError};
+use crate::config::Config;
+
+/// Where an error happened. Attached to crash reports as tags
+#[derive(Debug, Clone)]
+pub struct ErrorContext {
+    pub command: String,
+    pub guild_id: Option<GuildId>,
+    pub user_id: UserId,
+}
+
+impl ErrorContext {
+    pub fn from_poise(ctx: PoiseContext<'_>) -> Self {
+        Self {
+            command: ctx.command().qualified_name.clone(),
+            guild_id: ctx.guild_id(),
+            user_id: ctx.author().id,
+        }
+    }
+
+    fn configure_scope(&self, scope: &mut sentry::Scope) {
+        scope.set_tag("command", &self.command);
+        scope.set_tag(
+            "guild_id",
+            self.guild_id
+                .map_or("none".to_string(), |id| id.to_string()),
+        );
+        scope.set_user(Some(sentry::User {
+            id: Some(self.user_id.to_string()),
+            ..Default::default()
+        }));
+    }
+}
+
+/// Initialize sentry crash reporting, if a DSN is configured. Installs a panic hook
+/// reporting panics from any thread. Reporting stops when returned guard is dropped.
+pub fn init(config: &Config) -> Option<sentry::ClientInitGuard> {
+    let dsn = config.sentry_dsn.as_ref()?;
+
+    log::info!("Initializing sentry crash reporting");
+    let guard = sentry::init((
+        dsn.as_str(),
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            ..Default::default()
+        },
+    ));
+
+    if !guard.is_enabled() {
+        log::warn!("Sentry crash reporting disabled. Check DISCORD_BOT_SENTRY_DSN");
+    }
+
+    Some(guard)
+}
+
+/// Report error returned from a command or event handler. No-op when sentry isn't initialized
+pub fn report_error(err: &PoiseError, context: &ErrorContext) {
+    sentry::with_scope(
+        |scope| context.configure_scope(scope),
+        || sentry::capture_error(err.as_ref()),
+    );
+}
+
+/// Report panic caught by the framework while running a command. Panic hook already
+/// reports the panic with its backtrace, this adds the command it happened in.
+pub fn report_panic(payload: Option<&str>, context: &ErrorContext) {
+    let message = format!("Command '{}' panicked", context.command);
+    let message = match payload {
+        Some(payload) => format!("{message} - {payload}"),
+        None => message,
+    };
+
+    sentry::with_scope(
+        |scope| context.configure_scope(scope),
+        || sentry::capture_message(&message, sentry::Level::Fatal),
+    );
+}
@@ -0,0 +1,363 @@
+use rusqlite::OptionalExtension;
+
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{add_column_if_missing, DbConnection, Table};
+
+/// What an API key may do. Keys get every scope unless created with fewer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiScope {
+    /// Add sounds, in one request or as a resumable upload
+    Upload,
+    /// Follow what's playing, ie: for a stream overlay
+    Events,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 2] = [ApiScope::Upload, ApiScope::Events];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Upload => "upload",
+            Self::Events => "events",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.as_str() == value)
+    }
+
+    /// Space separated scopes, ie: `upload events`
+    pub fn join(scopes: &[ApiScope]) -> String {
+        scopes
+            .iter()
+            .map(|scope| scope.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Parse space separated scopes. Returns the first unknown one as error
+    pub fn parse(value: &str) -> Result<Vec<ApiScope>, String> {
+        let mut scopes = vec![];
+        for part in value.split_whitespace() {
+            let scope = Self::from_str(part).ok_or_else(|| part.to_string())?;
+            if !scopes.contains(&scope) {
+                scopes.push(scope);
+            }
+        }
+        Ok(scopes)
+    }
+}
+
+impl std::fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Key scripts & the web dashboard authenticate to the HTTP API with, acting as the member who
+/// created it in its guild. Only a hash of the key is stored, the key is shown once
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiKeyRow {
+    pub id: i64,
+    pub guild_id: u64,
+    pub user_id: u64,
+    pub name: String,
+    /// hex encoded sha256 of key
+    pub key_hash: String,
+    pub scopes: Vec<ApiScope>,
+    /// Most requests the key can make a day (UTC). None is unlimited
+    pub daily_quota: Option<u64>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for ApiKeyRow {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            guild_id: row.get("guild_id")?,
+            user_id: row.get("user_id")?,
+            name: row.get("name")?,
+            key_hash: row.get("key_hash")?,
+            // scopes dropped by a later version are ignored
+            scopes: row
+                .get::<_, String>("scopes")?
+                .split_whitespace()
+                .filter_map(ApiScope::from_str)
+                .collect(),
+            daily_quota: row.get("daily_quota")?,
+            created_at: row.get("created_at")?,
+            last_used_at: row.get("last_used_at")?,
+        })
+    }
+}
+
+pub struct ApiKeysTable {
+    conn: DbConnection,
+}
+
+impl ApiKeysTable {
+    pub const TABLE_NAME: &'static str = "api_keys";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    /// Returns whether key was added. Key names are unique per guild
+    pub fn insert_row(
+        &self,
+        guild_id: u64,
+        user_id: u64,
+        name: &str,
+        key_hash: &str,
+        scopes: &[ApiScope],
+    ) -> Result<bool, PoiseError> {
+        log::info!("Adding api key `{name}` of user {user_id}. guild_id: {guild_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (guild_id, user_id, name, key_hash, scopes, created_at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT (guild_id, name) DO NOTHING
+            "
+        );
+
+        let inserted = self
+            .conn
+            .execute(
+                sql.as_str(),
+                (
+                    guild_id,
+                    user_id,
+                    name,
+                    key_hash,
+                    ApiScope::join(scopes),
+                    chrono::Utc::now(),
+                ),
+            )
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(inserted > 0)
+    }
+
+    /// Returns whether key was deleted
+    pub fn delete_row(&self, guild_id: u64, name: &str) -> Result<bool, PoiseError> {
+        log::info!("Revoking api key `{name}`. guild_id: {guild_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE guild_id = ?1 AND name = ?2");
+
+        let deleted = self
+            .conn
+            .execute(sql.as_str(), (guild_id, name))
+            .log_err_msg(format!("Failed deleting row from {table_name}"))?;
+
+        Ok(deleted > 0)
+    }
+
+    pub fn find_by_hash(&self, key_hash: &str) -> Result<Option<ApiKeyRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT * FROM {table_name} WHERE key_hash = ?1");
+
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), [key_hash], |row| ApiKeyRow::try_from(row))
+            .optional()
+            .log_err_msg(format!("Failed finding row of {table_name}"))?)
+    }
+
+    /// Number of keys of every guild
+    pub fn count_rows(&self) -> Result<u64, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT COUNT(*) FROM {table_name}");
+
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), (), |row| row.get(0))
+            .log_err_msg(format!("Failed counting rows of {table_name}"))?)
+    }
+
+    /// Keys of guild, by name
+    pub fn rows(&self, guild_id: u64) -> Result<Vec<ApiKeyRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT * FROM {table_name} WHERE guild_id = ?1 ORDER BY name");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map([guild_id], |row| ApiKeyRow::try_from(row))
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+
+    /// Returns whether key was found. None removes the quota
+    pub fn set_daily_quota(
+        &self,
+        guild_id: u64,
+        name: &str,
+        daily_quota: Option<u64>,
+    ) -> Result<bool, PoiseError> {
+        log::info!(
+            "Setting daily quota of api key `{name}` to {daily_quota:?}. guild_id: {guild_id}"
+        );
+
+        let table_name = Self::TABLE_NAME;
+        let sql =
+            format!("UPDATE {table_name} SET daily_quota = ?3 WHERE guild_id = ?1 AND name = ?2");
+
+        let updated = self
+            .conn
+            .execute(sql.as_str(), (guild_id, name, daily_quota))
+            .log_err_msg(format!("Failed updating row of {table_name}"))?;
+
+        Ok(updated > 0)
+    }
+
+    pub fn set_last_used(&self, id: i64) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("UPDATE {table_name} SET last_used_at = ?2 WHERE id = ?1");
+
+        self.conn
+            .execute(sql.as_str(), (id, chrono::Utc::now()))
+            .log_err_msg(format!("Failed updating row of {table_name}"))?;
+
+        Ok(())
+    }
+}
+
+impl Table for ApiKeysTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                guild_id INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                name VARCHAR(32) NOT NULL,
+                key_hash VARCHAR(64) NOT NULL UNIQUE,
+                scopes VARCHAR(200) NOT NULL DEFAULT 'upload',
+                daily_quota INTEGER,
+                created_at VARCHAR(40) NOT NULL,
+                last_used_at VARCHAR(40),
+                UNIQUE (guild_id, name)
+            );
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+
+        // keys created before scopes existed could do everything
+        add_column_if_missing(
+            &self.conn,
+            table_name,
+            "scopes",
+            "VARCHAR(200) NOT NULL DEFAULT 'upload'",
+        );
+        add_column_if_missing(&self.conn, table_name, "daily_quota", "INTEGER");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_api_keys_table() -> ApiKeysTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        ApiKeysTable::new(db_pool.get().unwrap())
+    }
+
+    #[test]
+    fn api_keys_test() {
+        let table = get_api_keys_table();
+        table.create_table();
+        table.create_table();
+
+        assert!(table
+            .insert_row(1, 2, "dashboard", "aa", &ApiScope::ALL)
+            .unwrap());
+        assert!(!table
+            .insert_row(1, 3, "dashboard", "bb", &ApiScope::ALL)
+            .unwrap());
+        assert!(table.insert_row(1, 3, "script", "cc", &[]).unwrap());
+        assert!(table
+            .insert_row(4, 2, "dashboard", "dd", &ApiScope::ALL)
+            .unwrap());
+
+        let row = table.find_by_hash("cc").unwrap().unwrap();
+        assert_eq!((row.guild_id, row.user_id), (1, 3));
+        assert_eq!(row.last_used_at, None);
+        assert_eq!(row.daily_quota, None);
+        assert_eq!(row.scopes, vec![]);
+        let dashboard = table.find_by_hash("aa").unwrap().unwrap();
+        assert_eq!(dashboard.scopes, vec![ApiScope::Upload, ApiScope::Events]);
+        table.set_last_used(row.id).unwrap();
+        assert!(table
+            .find_by_hash("cc")
+            .unwrap()
+            .unwrap()
+            .last_used_at
+            .is_some());
+        assert_eq!(table.find_by_hash("bb").unwrap(), None);
+
+        let names: Vec<_> = table
+            .rows(1)
+            .unwrap()
+            .into_iter()
+            .map(|row| row.name)
+            .collect();
+        assert_eq!(names, vec!["dashboard", "script"]);
+
+        assert!(table.set_daily_quota(1, "script", Some(100)).unwrap());
+        assert_eq!(
+            table.find_by_hash("cc").unwrap().unwrap().daily_quota,
+            Some(100)
+        );
+        assert!(!table.set_daily_quota(4, "script", Some(100)).unwrap());
+
+        assert!(table.delete_row(1, "script").unwrap());
+        assert!(!table.delete_row(1, "script").unwrap());
+        assert_eq!(table.find_by_hash("cc").unwrap(), None);
+        assert_eq!(table.rows(4).unwrap().len(), 1);
+        assert_eq!(table.count_rows().unwrap(), 2);
+    }
+
+    #[test]
+    fn api_scope_test() {
+        assert_eq!(
+            ApiScope::parse(" upload  upload "),
+            Ok(vec![ApiScope::Upload])
+        );
+        assert_eq!(ApiScope::parse(""), Ok(vec![]));
+        assert_eq!(ApiScope::parse("upload admin"), Err("admin".to_string()));
+        assert_eq!(
+            ApiScope::parse("events upload"),
+            Ok(vec![ApiScope::Events, ApiScope::Upload])
+        );
+        assert_eq!(ApiScope::join(&ApiScope::ALL), "upload events");
+    }
+}
@@ -0,0 +1,257 @@
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{ApiKeysTable, DbConnection, Table};
+
+/// How an HTTP API request went, as counted in the day's usage of its key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApiRequestOutcome {
+    Success,
+    /// Answered with an error, ie: an invalid sound
+    Failure,
+    /// Refused by the rate limit or the key's daily quota, before being handled
+    Rejected,
+}
+
+/// Requests of an API key over some days. Usage of revoked keys is kept, without a name
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiUsage {
+    pub key_id: i64,
+    pub key_name: Option<String>,
+    pub guild_id: u64,
+    /// Handled requests, including failures. Counted against the daily quota
+    pub requests: u64,
+    pub failures: u64,
+    pub rejected: u64,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for ApiUsage {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            key_id: row.get("key_id")?,
+            key_name: row.get("key_name")?,
+            guild_id: row.get("guild_id")?,
+            requests: row.get("requests")?,
+            failures: row.get("failures")?,
+            rejected: row.get("rejected")?,
+        })
+    }
+}
+
+/// Day of time, as stored in the `day` column
+pub fn usage_day(at: chrono::DateTime<chrono::Utc>) -> chrono::DateTime<chrono::Utc> {
+    at.date_naive().and_time(chrono::NaiveTime::MIN).and_utc()
+}
+
+/// Daily request counts of HTTP API keys
+pub struct ApiUsageTable {
+    conn: DbConnection,
+}
+
+impl ApiUsageTable {
+    pub const TABLE_NAME: &'static str = "api_usage";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    /// Count request of key, made at given time
+    pub fn record(
+        &self,
+        key_id: i64,
+        guild_id: u64,
+        at: chrono::DateTime<chrono::Utc>,
+        outcome: ApiRequestOutcome,
+    ) -> Result<(), PoiseError> {
+        let (requests, failures, rejected) = match outcome {
+            ApiRequestOutcome::Success => (1, 0, 0),
+            ApiRequestOutcome::Failure => (1, 1, 0),
+            ApiRequestOutcome::Rejected => (0, 0, 1),
+        };
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (key_id, guild_id, day, requests, failures, rejected)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT (key_id, day) DO UPDATE SET
+                requests = requests + excluded.requests,
+                failures = failures + excluded.failures,
+                rejected = rejected + excluded.rejected
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (
+                    key_id,
+                    guild_id,
+                    usage_day(at),
+                    requests,
+                    failures,
+                    rejected,
+                ),
+            )
+            .log_err_msg(format!("Failed recording usage into {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Handled requests of key on the day of given time
+    pub fn requests_on(
+        &self,
+        key_id: i64,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "SELECT COALESCE(SUM(requests), 0) FROM {table_name} WHERE key_id = ?1 AND day = ?2"
+        );
+
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), (key_id, usage_day(at)), |row| row.get(0))
+            .log_err_msg(format!("Failed counting requests of {table_name}"))?)
+    }
+
+    /// Usage of each key since the day of given time, busiest key first. Keys of every guild
+    /// when guild_id isn't given
+    pub fn usage_since(
+        &self,
+        guild_id: Option<u64>,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<ApiUsage>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let keys_table_name = ApiKeysTable::TABLE_NAME;
+        let sql = format!(
+            "
+            SELECT
+                usage.key_id,
+                keys.name AS key_name,
+                usage.guild_id,
+                SUM(usage.requests) AS requests,
+                SUM(usage.failures) AS failures,
+                SUM(usage.rejected) AS rejected
+            FROM {table_name} usage
+            LEFT JOIN {keys_table_name} keys ON keys.id = usage.key_id
+            WHERE (?1 IS NULL OR usage.guild_id = ?1) AND usage.day >= ?2
+            GROUP BY usage.key_id
+            ORDER BY requests + rejected DESC, usage.key_id
+            "
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map((guild_id, usage_day(since)), |row| ApiUsage::try_from(row))
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+}
+
+impl Table for ApiUsageTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                key_id INTEGER NOT NULL,
+                guild_id INTEGER NOT NULL,
+                day VARCHAR(40) NOT NULL,
+                requests INTEGER NOT NULL DEFAULT 0,
+                failures INTEGER NOT NULL DEFAULT 0,
+                rejected INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY (key_id, day)
+            );
+
+            CREATE INDEX IF NOT EXISTS {table_name}_guild_day_idx
+                ON {table_name} (guild_id, day);
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::db::ApiScope;
+
+    #[test]
+    fn api_usage_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let keys = ApiKeysTable::new(db_pool.get().unwrap());
+        keys.create_table();
+        let table = ApiUsageTable::new(db_pool.get().unwrap());
+        table.create_table();
+        table.create_table();
+
+        keys.insert_row(1, 2, "dashboard", "aa", &ApiScope::ALL)
+            .unwrap();
+        let key_id = keys.find_by_hash("aa").unwrap().unwrap().id;
+
+        let day = chrono::Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap();
+        let next_day = day + chrono::Duration::days(1);
+        table
+            .record(key_id, 1, day, ApiRequestOutcome::Success)
+            .unwrap();
+        table
+            .record(key_id, 1, day, ApiRequestOutcome::Failure)
+            .unwrap();
+        table
+            .record(key_id, 1, day, ApiRequestOutcome::Rejected)
+            .unwrap();
+        table
+            .record(key_id, 1, next_day, ApiRequestOutcome::Success)
+            .unwrap();
+        // revoked key of another guild
+        table
+            .record(99, 3, day, ApiRequestOutcome::Success)
+            .unwrap();
+
+        assert_eq!(table.requests_on(key_id, day).unwrap(), 2);
+        assert_eq!(table.requests_on(key_id, next_day).unwrap(), 1);
+        assert_eq!(table.requests_on(99, next_day).unwrap(), 0);
+
+        let usage = table.usage_since(Some(1), day).unwrap();
+        assert_eq!(
+            usage,
+            vec![ApiUsage {
+                key_id,
+                key_name: Some("dashboard".into()),
+                guild_id: 1,
+                requests: 3,
+                failures: 1,
+                rejected: 1,
+            }]
+        );
+        assert_eq!(table.usage_since(Some(1), next_day).unwrap()[0].requests, 1);
+
+        let usage = table.usage_since(None, day).unwrap();
+        assert_eq!(usage.len(), 2);
+        assert_eq!((usage[1].key_id, usage[1].key_name.clone()), (99, None));
+    }
+}
@@ -1,11 +1,14 @@
 use std::ops::Deref;
 
 use regex::Regex;
-use rusqlite::{params, types::FromSql, ToSql};
+use rusqlite::{params, types::FromSql, OptionalExtension, ToSql};
 
 use crate::{audio, commands::PoiseError, common::LogResult};
 
-use super::{DbConnection, Table};
+use super::{add_column_if_missing, DbConnection, PlayHistoryTable, Table};
+
+/// Audio row with when it was last played, if ever
+pub type StaleAudioRow = (AudioTableRow, Option<chrono::DateTime<chrono::Utc>>);
 
 pub struct AudioTableRow {
     pub id: i64,
@@ -16,6 +19,17 @@ pub struct AudioTableRow {
     pub author_id: Option<u64>,
     pub author_name: Option<String>,
     pub author_global_name: Option<String>,
+    pub file_hash: Option<String>,
+    pub file_size: Option<u64>,
+    pub file_modified_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Emoji shown on the sound's button. Unicode emoji, or custom emoji as `<:name:id>`
+    pub emoji: Option<String>,
+    /// Volume percentage of the sound relative to others, ie: to quiet down a loud sound
+    pub volume: Option<u32>,
+    /// Protected sounds can't be deleted, renamed, or bulk edited without an admin override
+    pub protected: bool,
+    /// Seconds after a play before the sound can be played again in the guild. None when it has no cooldown
+    pub cooldown: Option<u32>,
 }
 
 pub struct Tags(Vec<String>);
@@ -120,10 +134,73 @@ impl TryFrom<&rusqlite::Row<'_>> for AudioTableRow {
             author_global_name: row
                 .get("author_global_name")
                 .log_err_msg("From row.author_global_name fail")?,
+            file_hash: row
+                .get("file_hash")
+                .log_err_msg("From row.file_hash fail")?,
+            file_size: row
+                .get("file_size")
+                .log_err_msg("From row.file_size fail")?,
+            file_modified_at: row
+                .get("file_modified_at")
+                .log_err_msg("From row.file_modified_at fail")?,
+            emoji: row.get("emoji").log_err_msg("From row.emoji fail")?,
+            volume: row.get("volume").log_err_msg("From row.volume fail")?,
+            protected: row
+                .get("protected")
+                .log_err_msg("From row.protected fail")?,
+            cooldown: row.get("cooldown").log_err_msg("From row.cooldown fail")?,
         })
     }
 }
 
+impl AudioTableRow {
+    /// Whether stored file info matches the file on disk. Rows without a stored hash are never up to date.
+    pub fn file_info_matches(&self, info: &audio::AudioFileStat) -> bool {
+        self.file_hash.is_some()
+            && self.file_size == Some(info.size)
+            && self.file_modified_at == Some(info.modified_at)
+    }
+}
+
+#[cfg(test)]
+impl AudioTableRow {
+    /// Row for tests, named `Sound {id}` with audio at `/tmp/{id}.mp3`
+    pub fn test_row(id: i64) -> Self {
+        Self {
+            id,
+            name: format!("Sound {id}"),
+            tags: Tags::new(),
+            audio_file: audio::AudioFile::new(std::path::PathBuf::from(format!("/tmp/{id}.mp3"))),
+            created_at: chrono::Utc::now(),
+            author_id: None,
+            author_name: None,
+            author_global_name: None,
+            file_hash: None,
+            file_size: None,
+            file_modified_at: None,
+            emoji: None,
+            volume: None,
+            protected: false,
+            cooldown: None,
+        }
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_audio_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.audio_file = audio::AudioFile::new(path.into());
+        self
+    }
+
+    pub fn with_file_hash(mut self, hash: &str) -> Self {
+        self.file_hash = Some(hash.into());
+        self
+    }
+}
+
 pub struct AudioTableRowInsert {
     pub name: String,
     pub tags: Tags,
@@ -132,6 +209,9 @@ pub struct AudioTableRowInsert {
     pub author_id: Option<u64>,
     pub author_name: Option<String>,
     pub author_global_name: Option<String>,
+    pub file_hash: Option<String>,
+    pub file_size: Option<u64>,
+    pub file_modified_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 impl AsRef<AudioTableRowInsert> for AudioTableRowInsert {
@@ -181,6 +261,8 @@ pub struct AudioTable {
 impl AudioTable {
     pub const TABLE_NAME: &'static str = "audio";
     pub const FTS5_TABLE_NAME: &'static str = "fts5_audio";
+    pub const TRANSCRIPTS_TABLE_NAME: &'static str = "audio_transcripts";
+    pub const TRANSCRIPTS_FTS5_TABLE_NAME: &'static str = "fts5_audio_transcripts";
 
     pub fn new(connection: DbConnection) -> Self {
         Self { conn: connection }
@@ -239,6 +321,93 @@ impl AudioTable {
         }
     }
 
+    /// Audio rows whose name, tags, or transcript contain query, best matches first. Name & tag
+    /// matches come before transcript matches.
+    /// **note**: Query must be at least 3 chars to match anything (trigram tokenizer)
+    pub fn search_rows(
+        &self,
+        query: impl AsRef<str>,
+        limit: u64,
+    ) -> Result<Vec<AudioTableRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let fts5_table_name = Self::FTS5_TABLE_NAME;
+        let transcripts_fts5_table_name = Self::TRANSCRIPTS_FTS5_TABLE_NAME;
+        let sql = format!(
+            "
+            SELECT {table_name}.* FROM (
+                SELECT rowid AS id, 0 AS source, rank FROM {fts5_table_name}
+                WHERE {fts5_table_name} MATCH ?1
+                UNION ALL
+                SELECT rowid AS id, 1 AS source, rank FROM {transcripts_fts5_table_name}
+                WHERE {transcripts_fts5_table_name} MATCH ?1
+            ) AS matches
+            JOIN {table_name} ON {table_name}.id = matches.id
+            GROUP BY {table_name}.id
+            ORDER BY MIN(matches.source), MIN(matches.rank)
+            LIMIT {limit}"
+        );
+
+        // search as a single phrase, so fts5 query syntax in user input isn't interpreted
+        let phrase = format!("\"{}\"", query.as_ref().replace('"', "\"\""));
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt
+            .query_map(params![phrase], |row| AudioTableRow::try_from(row))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg("Failed searching audio rows")?;
+
+        Ok(rows)
+    }
+
+    /// Audio rows tagged with tag, newest first
+    pub fn tag_rows(&self, tag: &str, limit: u64) -> Result<Vec<AudioTableRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        // tags are stored space separated, pad them so only whole tags match
+        let sql = format!(
+            "
+            SELECT * FROM {table_name}
+            WHERE ' ' || tags || ' ' LIKE '% ' || ?1 || ' %'
+            ORDER BY created_at DESC
+            LIMIT ?2"
+        );
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt
+            .query_map(params![tag, limit], |row| AudioTableRow::try_from(row))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg("Failed selecting tagged audio rows")?;
+
+        Ok(rows)
+    }
+
+    /// Most recently added audio rows, newest first
+    pub fn recent_rows(&self, limit: u64) -> Result<Vec<AudioTableRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT * FROM {table_name} ORDER BY created_at DESC, id DESC LIMIT ?1");
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt
+            .query_map(params![limit], |row| AudioTableRow::try_from(row))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg("Failed selecting recent audio rows")?;
+
+        Ok(rows)
+    }
+
+    /// Random selection of audio rows
+    pub fn random_rows(&self, limit: u64) -> Result<Vec<AudioTableRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT * FROM {table_name} ORDER BY RANDOM() LIMIT ?1");
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt
+            .query_map(params![limit], |row| AudioTableRow::try_from(row))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg("Failed selecting random audio rows")?;
+
+        Ok(rows)
+    }
+
     pub fn find_audio_row(&self, col: impl AsRef<UniqueAudioTableCol>) -> Option<AudioTableRow> {
         let col = col.as_ref();
         let col_value = col.value();
@@ -270,9 +439,10 @@ impl AudioTable {
         let sql = format!(
             "
             INSERT INTO {table_name}
-                (name, tags, audio_file, created_at, author_id, author_name, author_global_name)
+                (name, tags, audio_file, created_at, author_id, author_name, author_global_name,
+                 file_hash, file_size, file_modified_at)
             VALUES
-                (?1, ?2, ?3, ?4, ?5, ?6, ?7)"
+                (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"
         );
 
         self.connection()
@@ -286,6 +456,9 @@ impl AudioTable {
                     &audio_row.author_id,
                     &audio_row.author_name,
                     &audio_row.author_global_name,
+                    &audio_row.file_hash,
+                    &audio_row.file_size,
+                    &audio_row.file_modified_at,
                 ),
             )
             .map_err(|err| {
@@ -325,23 +498,329 @@ impl AudioTable {
         Ok(())
     }
 
-    pub fn delete_audio_row(&self, col: impl AsRef<UniqueAudioTableCol>) -> Result<(), PoiseError> {
-        let column = col.as_ref();
-        match self.find_audio_row(&col) {
-            None => log::info!("Can't delete non-existent audio track. {column:?}"),
-            Some(row) => {
-                row.audio_file.delete();
-                let table_name = Self::TABLE_NAME;
-                let row_id = row.id;
-                let sql = format!("DELETE FROM {table_name} WHERE id = {row_id}");
+    /// Audio rows not played in guild since given time, or never played, with when they were last played.
+    /// Never played rows come first, then least recently played
+    /// Number of sounds on the soundboard
+    pub fn count_rows(&self) -> Result<u64, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT COUNT(*) FROM {table_name}");
 
-                self.conn
-                    .execute(sql.as_str(), ())
-                    .log_err_msg("Failed to delete audio row")?;
-            }
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), (), |row| row.get(0))
+            .log_err_msg(format!("Failed counting rows of {table_name}"))?)
+    }
+
+    pub fn stale_rows(
+        &self,
+        guild_id: u64,
+        since: chrono::DateTime<chrono::Utc>,
+        limit: u64,
+    ) -> Result<Vec<StaleAudioRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let history_table_name = PlayHistoryTable::TABLE_NAME;
+        let rollup_table_name = PlayHistoryTable::ROLLUP_TABLE_NAME;
+        let sql = format!(
+            "
+            SELECT {table_name}.*, MAX(history.played_at) AS last_played_at
+            FROM {table_name}
+            LEFT JOIN (
+                SELECT audio_id, played_at FROM {history_table_name} WHERE guild_id = ?1
+                UNION ALL
+                SELECT audio_id, day AS played_at FROM {rollup_table_name} WHERE guild_id = ?1
+            ) AS history
+                ON history.audio_id = {table_name}.id
+            GROUP BY {table_name}.id
+            HAVING last_played_at IS NULL OR last_played_at < ?2
+            ORDER BY last_played_at IS NOT NULL, last_played_at, {table_name}.id
+            LIMIT ?3
+        "
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg("Failed preparing stale audio rows sql")?;
+
+        let rows = stmt
+            .query_map(params![guild_id, since, limit], |row| {
+                Ok((AudioTableRow::try_from(row)?, row.get("last_played_at")?))
+            })
+            .log_err_msg("Failed selecting stale audio rows")?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg("Failed reading stale audio rows")?;
+
+        Ok(rows)
+    }
+
+    /// Unprotected audio rows matching every filter given, by name. `prefix` matches the start of
+    /// names, and `unplayed_since` sounds added before then that haven't been played in guild since
+    pub fn filter_rows(
+        &self,
+        guild_id: u64,
+        tag: Option<&str>,
+        prefix: Option<&str>,
+        unplayed_since: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<Vec<AudioTableRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let history_table_name = PlayHistoryTable::TABLE_NAME;
+        let rollup_table_name = PlayHistoryTable::ROLLUP_TABLE_NAME;
+        let sql = format!(
+            "
+            SELECT * FROM {table_name}
+            WHERE protected = 0
+                AND (?2 IS NULL OR ' ' || tags || ' ' LIKE '% ' || ?2 || ' %')
+                AND (?3 IS NULL OR substr(name, 1, length(?3)) = ?3)
+                AND (?4 IS NULL OR (
+                    created_at < ?4
+                    AND id NOT IN (
+                        SELECT audio_id FROM {history_table_name}
+                        WHERE guild_id = ?1 AND played_at >= ?4 AND audio_id IS NOT NULL
+                        UNION
+                        SELECT audio_id FROM {rollup_table_name}
+                        WHERE guild_id = ?1 AND day >= date(?4) AND audio_id IS NOT NULL
+                    )
+                ))
+            ORDER BY name
+        "
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg("Failed preparing filtered audio rows sql")?;
+
+        let rows = stmt
+            .query_map(params![guild_id, tag, prefix, unplayed_since], |row| {
+                AudioTableRow::try_from(row)
+            })
+            .log_err_msg("Failed selecting filtered audio rows")?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg("Failed reading filtered audio rows")?;
+
+        Ok(rows)
+    }
+
+    /// Store display metadata of audio row, as given by a metadata import
+    pub fn update_metadata(
+        &self,
+        row_id: i64,
+        name: &str,
+        tags: &Tags,
+        emoji: Option<&str>,
+        volume: Option<u32>,
+    ) -> Result<(), String> {
+        log::debug!("Updating metadata for audio row id: {row_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            UPDATE {table_name}
+            SET
+                name = ?,
+                tags = ?,
+                emoji = ?,
+                volume = ?
+            WHERE
+                id = ?;
+        "
+        );
+
+        self.conn
+            .execute(sql.as_str(), params![name, tags, emoji, volume, &row_id])
+            .log_err_msg("Failed updating audio track metadata")
+            .map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+
+    /// Mark audio row as protected, or unprotected
+    pub fn set_protected(&self, row_id: i64, protected: bool) -> Result<(), PoiseError> {
+        log::debug!("Setting protected: {protected} for audio row id: {row_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("UPDATE {table_name} SET protected = ?1 WHERE id = ?2");
+
+        self.conn
+            .execute(sql.as_str(), params![protected, row_id])
+            .log_err_msg("Failed updating audio track protected")?;
+
+        Ok(())
+    }
+
+    /// Set seconds between plays of sound in a guild. None removes its cooldown
+    pub fn set_cooldown(&self, row_id: i64, cooldown: Option<u32>) -> Result<(), PoiseError> {
+        log::debug!("Setting cooldown: {cooldown:?} for audio row id: {row_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("UPDATE {table_name} SET cooldown = ?1 WHERE id = ?2");
+
+        self.conn
+            .execute(sql.as_str(), params![cooldown, row_id])
+            .log_err_msg("Failed updating audio track cooldown")?;
+
+        Ok(())
+    }
+
+    /// Store audio row's file path, hash, size, and modified time, as seen on disk
+    pub fn update_file_info(
+        &self,
+        row_id: i64,
+        audio_file: &audio::AudioFile,
+        info: &audio::AudioFileInfo,
+    ) -> Result<(), String> {
+        log::debug!("Updating file info for audio row id: {row_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            UPDATE {table_name}
+            SET
+                audio_file = ?,
+                file_hash = ?,
+                file_size = ?,
+                file_modified_at = ?
+            WHERE
+                id = ?;
+        "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                params![
+                    audio_file,
+                    &info.hash,
+                    &info.stat.size,
+                    &info.stat.modified_at,
+                    &row_id
+                ],
+            )
+            .log_err_msg("Failed updating audio track file info")
+            .map_err(|err| err.to_string())?;
+
+        Ok(())
+    }
+
+    /// Insert a deleted audio row back, with all its columns. Row keeps its id unless another
+    /// row has taken it since. Returns id of the restored row
+    pub fn restore_row(&self, audio_row: &AudioTableRow) -> Result<i64, PoiseError> {
+        log::info!("Restoring audio row. Name: {}", audio_row.name);
+
+        let table_name = Self::TABLE_NAME;
+        let id = self
+            .find_audio_row(UniqueAudioTableCol::Id(audio_row.id))
+            .is_none()
+            .then_some(audio_row.id);
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (id, name, tags, audio_file, created_at, author_id, author_name, author_global_name,
+                 file_hash, file_size, file_modified_at, emoji, volume, protected, cooldown)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)"
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                params![
+                    id,
+                    &audio_row.name,
+                    &audio_row.tags,
+                    &audio_row.audio_file,
+                    &audio_row.created_at,
+                    &audio_row.author_id,
+                    &audio_row.author_name,
+                    &audio_row.author_global_name,
+                    &audio_row.file_hash,
+                    &audio_row.file_size,
+                    &audio_row.file_modified_at,
+                    &audio_row.emoji,
+                    &audio_row.volume,
+                    &audio_row.protected,
+                    &audio_row.cooldown,
+                ],
+            )
+            .log_err_msg("Failed to restore audio row")?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Delete audio row & its transcript only. Audio file is left untouched
+    pub fn delete_row(&self, row_id: i64) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let transcripts_table_name = Self::TRANSCRIPTS_TABLE_NAME;
+        let sql = format!(
+            "
+            DELETE FROM {transcripts_table_name} WHERE audio_id = {row_id};
+            DELETE FROM {table_name} WHERE id = {row_id};"
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed to delete audio row")?;
+
+        Ok(())
+    }
+
+    /// Delete audio rows & their transcripts together, or none of them. Audio files are left
+    /// untouched
+    pub fn delete_rows(&self, row_ids: &[i64]) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let transcripts_table_name = Self::TRANSCRIPTS_TABLE_NAME;
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .log_err_msg(format!("Failed starting delete of {table_name} rows"))?;
+
+        for row_id in row_ids {
+            tx.execute(
+                &format!("DELETE FROM {transcripts_table_name} WHERE audio_id = ?1"),
+                [row_id],
+            )
+            .log_err_msg("Failed to delete audio transcript")?;
+            tx.execute(&format!("DELETE FROM {table_name} WHERE id = ?1"), [row_id])
+                .log_err_msg("Failed to delete audio row")?;
         }
+
+        tx.commit()
+            .log_err_msg(format!("Failed committing delete of {table_name} rows"))?;
         Ok(())
     }
+
+    /// Store text spoken in audio row's sound, replacing any earlier transcript
+    pub fn set_transcript(&self, row_id: i64, transcript: &str) -> Result<(), PoiseError> {
+        log::debug!("Setting transcript of audio row id: {row_id}");
+        let transcripts_table_name = Self::TRANSCRIPTS_TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {transcripts_table_name} (audio_id, transcript, created_at)
+            VALUES (?1, ?2, ?3)
+            ON CONFLICT (audio_id) DO UPDATE SET
+                transcript = excluded.transcript,
+                created_at = excluded.created_at"
+        );
+
+        self.conn
+            .execute(sql.as_str(), (row_id, transcript, chrono::Utc::now()))
+            .log_err_msg("Failed to set audio transcript")?;
+
+        Ok(())
+    }
+
+    pub fn transcript(&self, row_id: i64) -> Result<Option<String>, PoiseError> {
+        let transcripts_table_name = Self::TRANSCRIPTS_TABLE_NAME;
+        let sql = format!("SELECT transcript FROM {transcripts_table_name} WHERE audio_id = ?1");
+
+        let transcript = self
+            .conn
+            .query_row(sql.as_str(), [row_id], |row| row.get(0))
+            .optional()
+            .log_err_msg("Failed to find audio transcript")?;
+
+        Ok(transcript)
+    }
 }
 
 impl Table for AudioTable {
@@ -366,7 +845,14 @@ impl Table for AudioTable {
                     created_at VARCHAR(25) NOT NULL,
                     author_id INTEGER,
                     author_name VARCHAR(256),
-                    author_global_name VARCHAR(256)
+                    author_global_name VARCHAR(256),
+                    file_hash VARCHAR(64),
+                    file_size INTEGER,
+                    file_modified_at VARCHAR(40),
+                    emoji VARCHAR(64),
+                    volume INTEGER,
+                    protected INTEGER NOT NULL DEFAULT 0,
+                    cooldown INTEGER
                 );
 
                 CREATE VIRTUAL TABLE IF NOT EXISTS {fts5_table_name} USING FTS5(
@@ -398,6 +884,60 @@ impl Table for AudioTable {
             .log_err_msg(format!("Failed creating table:{table_name}"))
             .unwrap();
 
+        // columns added after the initial release
+        add_column_if_missing(&self.conn, table_name, "file_hash", "VARCHAR(64)");
+        add_column_if_missing(&self.conn, table_name, "file_size", "INTEGER");
+        add_column_if_missing(&self.conn, table_name, "file_modified_at", "VARCHAR(40)");
+        add_column_if_missing(&self.conn, table_name, "emoji", "VARCHAR(64)");
+        add_column_if_missing(&self.conn, table_name, "volume", "INTEGER");
+        add_column_if_missing(
+            &self.conn,
+            table_name,
+            "protected",
+            "INTEGER NOT NULL DEFAULT 0",
+        );
+        add_column_if_missing(&self.conn, table_name, "cooldown", "INTEGER");
+
+        let transcripts_table_name = Self::TRANSCRIPTS_TABLE_NAME;
+        let transcripts_fts5_table_name = Self::TRANSCRIPTS_FTS5_TABLE_NAME;
+        let sql = format!(
+            "
+            BEGIN;
+                CREATE TABLE IF NOT EXISTS {transcripts_table_name} (
+                    audio_id INTEGER PRIMARY KEY,
+                    transcript TEXT NOT NULL,
+                    created_at VARCHAR(40) NOT NULL
+                );
+
+                CREATE VIRTUAL TABLE IF NOT EXISTS {transcripts_fts5_table_name} USING FTS5(
+                    transcript, content={transcripts_table_name}, content_rowid=audio_id, tokenize='trigram remove_diacritics 1'
+                );
+
+                CREATE TRIGGER IF NOT EXISTS {transcripts_table_name}_insert AFTER INSERT ON {transcripts_table_name} BEGIN
+                    INSERT INTO {transcripts_fts5_table_name}(rowid, transcript)
+                        VALUES (new.audio_id, new.transcript);
+                END;
+
+                CREATE TRIGGER IF NOT EXISTS {transcripts_table_name}_delete AFTER DELETE ON {transcripts_table_name} BEGIN
+                    INSERT INTO {transcripts_fts5_table_name}({transcripts_fts5_table_name}, rowid, transcript)
+                        VALUES('delete', old.audio_id, old.transcript);
+                END;
+
+                CREATE TRIGGER IF NOT EXISTS {transcripts_table_name}_update AFTER UPDATE ON {transcripts_table_name} BEGIN
+                    INSERT INTO {transcripts_fts5_table_name}({transcripts_fts5_table_name}, rowid, transcript)
+                        VALUES('delete', old.audio_id, old.transcript);
+
+                    INSERT INTO {transcripts_fts5_table_name}(rowid, transcript)
+                        VALUES (new.audio_id, new.transcript);
+                END;
+            COMMIT;"
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg(format!("Failed creating table:{transcripts_table_name}"))
+            .unwrap();
+
         log::info!("Created tables {table_name}, {fts5_table_name}!");
     }
 }
@@ -422,6 +962,7 @@ impl AudioTableOrderBy {
 
 #[cfg(test)]
 mod tests {
+    use crate::db::PlayHistoryRowInsert;
     use crate::helpers::{self, uuid_v4_str};
     use audio::AudioFile;
     use r2d2_sqlite::SqliteConnectionManager;
@@ -449,6 +990,9 @@ mod tests {
             created_at: chrono::Utc::now(),
             author_id: None,
             author_name: None,
+            file_hash: None,
+            file_size: None,
+            file_modified_at: None,
         }
     }
 
@@ -464,9 +1008,11 @@ mod tests {
         let table = get_audio_table();
 
         table.create_table();
+        assert_eq!(table.count_rows().unwrap(), 0);
         table
             .insert_audio_row(make_audio_table_row_insert())
             .unwrap();
+        assert_eq!(table.count_rows().unwrap(), 1);
     }
 
     #[test]
@@ -482,6 +1028,23 @@ mod tests {
         assert_eq!(row.name, row_insert.name);
     }
 
+    #[test]
+    fn table_tag_rows_test() {
+        let table = get_audio_table();
+        table.create_table();
+
+        for tags in ["halloween spooky", "spookyish", "Spooky"] {
+            let mut row_insert = make_audio_table_row_insert();
+            row_insert.tags = tags.into();
+            table.insert_audio_row(&row_insert).unwrap();
+        }
+
+        let rows = table.tag_rows("spooky", 10).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| row.tags.join(" ") != "spookyish"));
+        assert_eq!(table.tag_rows("spooky", 1).unwrap().len(), 1);
+    }
+
     #[test]
     fn table_update_row_test() {
         let table = get_audio_table();
@@ -508,6 +1071,298 @@ mod tests {
         assert_eq!(updated_row.name, new_name);
     }
 
+    #[test]
+    fn table_update_file_info_test() {
+        let table = get_audio_table();
+        table.create_table();
+
+        let row_insert = make_audio_table_row_insert();
+        table.insert_audio_row(&row_insert).unwrap();
+
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Name(row_insert.name.clone()))
+            .unwrap();
+
+        let audio_file = AudioFile::new(std::path::PathBuf::from("/tmp/moved.mp3"));
+        let info = audio::AudioFileInfo {
+            stat: audio::AudioFileStat {
+                size: 42,
+                modified_at: chrono::Utc::now(),
+            },
+            hash: "abc".into(),
+        };
+        table.update_file_info(row.id, &audio_file, &info).unwrap();
+
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Id(row.id))
+            .unwrap();
+        assert_eq!(row.audio_file, audio_file);
+        assert!(row.file_info_matches(&info.stat));
+    }
+
+    #[test]
+    fn table_update_metadata_test() {
+        let table = get_audio_table();
+        table.create_table();
+
+        let row_insert = make_audio_table_row_insert();
+        table.insert_audio_row(&row_insert).unwrap();
+
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Name(row_insert.name.clone()))
+            .unwrap();
+        assert_eq!(row.emoji, None);
+
+        table
+            .update_metadata(
+                row.id,
+                "Air Horn",
+                &"loud meme".into(),
+                Some("📯"),
+                Some(50),
+            )
+            .unwrap();
+
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Id(row.id))
+            .unwrap();
+        assert_eq!(row.name, "Air Horn");
+        assert_eq!(row.tags.to_string(), "loud meme");
+        assert_eq!(row.emoji.as_deref(), Some("📯"));
+        assert_eq!(row.volume, Some(50));
+    }
+
+    #[test]
+    fn table_restore_row_test() {
+        let table = get_audio_table();
+        table.create_table();
+
+        let row_insert = make_audio_table_row_insert();
+        table.insert_audio_row(&row_insert).unwrap();
+        let mut row = table
+            .find_audio_row(UniqueAudioTableCol::Name(row_insert.name.clone()))
+            .unwrap();
+        table
+            .update_metadata(row.id, &row.name, &row.tags, Some("📯"), Some(50))
+            .unwrap();
+        row.emoji = Some("📯".into());
+        row.volume = Some(50);
+
+        table.delete_row(row.id).unwrap();
+        assert_eq!(table.restore_row(&row).unwrap(), row.id);
+        let restored = table
+            .find_audio_row(UniqueAudioTableCol::Id(row.id))
+            .unwrap();
+        assert_eq!(restored.name, row.name);
+        assert_eq!(restored.emoji, row.emoji);
+        assert_eq!(restored.volume, row.volume);
+    }
+
+    #[test]
+    fn table_set_protected_test() {
+        let table = get_audio_table();
+        table.create_table();
+
+        let row_insert = make_audio_table_row_insert();
+        table.insert_audio_row(&row_insert).unwrap();
+
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Name(row_insert.name.clone()))
+            .unwrap();
+        assert!(!row.protected);
+
+        table.set_protected(row.id, true).unwrap();
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Id(row.id))
+            .unwrap();
+        assert!(row.protected);
+    }
+
+    #[test]
+    fn table_set_cooldown_test() {
+        let table = get_audio_table();
+        table.create_table();
+
+        let row_insert = make_audio_table_row_insert();
+        table.insert_audio_row(&row_insert).unwrap();
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Name(row_insert.name.clone()))
+            .unwrap();
+        assert_eq!(row.cooldown, None);
+
+        table.set_cooldown(row.id, Some(300)).unwrap();
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Id(row.id))
+            .unwrap();
+        assert_eq!(row.cooldown, Some(300));
+
+        table.set_cooldown(row.id, None).unwrap();
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Id(row.id))
+            .unwrap();
+        assert_eq!(row.cooldown, None);
+    }
+
+    #[test]
+    fn table_stale_rows_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let table = AudioTable::new(db_pool.get().unwrap());
+        table.create_table();
+        let history = PlayHistoryTable::new(db_pool.get().unwrap());
+        history.create_table();
+
+        for _ in 0..3 {
+            table
+                .insert_audio_row(make_audio_table_row_insert())
+                .unwrap();
+        }
+
+        let now = chrono::Utc::now();
+        let play = |audio_id: i64, guild_id: u64, played_at: chrono::DateTime<chrono::Utc>| {
+            history
+                .insert_row(&PlayHistoryRowInsert {
+                    guild_id,
+                    user_id: None,
+                    channel_id: None,
+                    audio_id: Some(audio_id),
+                    audio_name: "x".into(),
+                    played_at,
+                })
+                .unwrap();
+        };
+        play(1, 1, now);
+        play(2, 1, now - chrono::Duration::days(40));
+        play(3, 2, now);
+
+        let stale = table
+            .stale_rows(1, now - chrono::Duration::days(30), 10)
+            .unwrap();
+        let ids: Vec<_> = stale.iter().map(|(row, _)| row.id).collect();
+        assert_eq!(ids, vec![3, 2]);
+        assert_eq!(stale[0].1, None);
+        assert!(stale[1].1.is_some());
+
+        // rolled up plays still count as plays
+        history.roll_up(1, now + chrono::Duration::days(1)).unwrap();
+        let rolled_up = table
+            .stale_rows(1, now - chrono::Duration::days(30), 10)
+            .unwrap();
+        let ids: Vec<_> = rolled_up.iter().map(|(row, _)| row.id).collect();
+        assert_eq!(ids, vec![3, 2]);
+    }
+
+    #[test]
+    fn table_filter_rows_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let table = AudioTable::new(db_pool.get().unwrap());
+        table.create_table();
+        let history = PlayHistoryTable::new(db_pool.get().unwrap());
+        history.create_table();
+
+        let now = chrono::Utc::now();
+        for (name, tags) in [
+            ("old-airhorn", "meme loud"),
+            ("old-kazoo", "music"),
+            ("new-airhorn", "meme"),
+        ] {
+            let mut row_insert = make_audio_table_row_insert();
+            row_insert.name = name.into();
+            row_insert.tags = tags.into();
+            row_insert.created_at = now - chrono::Duration::days(60);
+            table.insert_audio_row(row_insert).unwrap();
+        }
+        history
+            .insert_row(&PlayHistoryRowInsert {
+                guild_id: 1,
+                user_id: None,
+                channel_id: None,
+                audio_id: Some(1),
+                audio_name: "old-airhorn".into(),
+                played_at: now,
+            })
+            .unwrap();
+
+        let names = |rows: Vec<AudioTableRow>| -> Vec<String> {
+            rows.into_iter().map(|row| row.name).collect()
+        };
+        let since = Some(now - chrono::Duration::days(30));
+        assert_eq!(
+            names(table.filter_rows(1, Some("meme"), None, None).unwrap()),
+            vec!["new-airhorn", "old-airhorn"]
+        );
+        assert_eq!(
+            names(
+                table
+                    .filter_rows(1, Some("meme"), Some("old-"), None)
+                    .unwrap()
+            ),
+            vec!["old-airhorn"]
+        );
+        assert_eq!(
+            names(table.filter_rows(1, None, Some("old-"), since).unwrap()),
+            vec!["old-kazoo"]
+        );
+        // plays of other guilds don't count
+        assert_eq!(table.filter_rows(2, None, None, since).unwrap().len(), 3);
+
+        table.set_protected(2, true).unwrap();
+        assert!(table
+            .filter_rows(1, None, Some("old-kazoo"), None)
+            .unwrap()
+            .is_empty());
+
+        table.delete_rows(&[1, 3]).unwrap();
+        assert_eq!(table.count_rows().unwrap(), 1);
+    }
+
+    #[test]
+    fn table_recent_rows_test() {
+        let table = get_audio_table();
+        table.create_table();
+
+        let now = chrono::Utc::now();
+        for days_ago in [3, 1, 2] {
+            let mut row_insert = make_audio_table_row_insert();
+            row_insert.created_at = now - chrono::Duration::days(days_ago);
+            table.insert_audio_row(&row_insert).unwrap();
+        }
+
+        let ids: Vec<_> = table
+            .recent_rows(2)
+            .unwrap()
+            .iter()
+            .map(|row| row.id)
+            .collect();
+        assert_eq!(ids, vec![2, 3]);
+    }
+
+    #[test]
+    fn table_migrate_test() {
+        let table = get_audio_table();
+
+        // table as created by initial release
+        table
+            .connection()
+            .execute_batch(
+                "CREATE TABLE audio (
+                    id INTEGER PRIMARY KEY,
+                    name VARCHAR(80) NOT NULL UNIQUE,
+                    tags VARCHAR(2048),
+                    audio_file VARCHAR(500) NOT NULL UNIQUE,
+                    created_at VARCHAR(25) NOT NULL,
+                    author_id INTEGER,
+                    author_name VARCHAR(256),
+                    author_global_name VARCHAR(256)
+                );",
+            )
+            .unwrap();
+
+        table.create_table();
+        table
+            .insert_audio_row(make_audio_table_row_insert())
+            .unwrap();
+    }
+
     #[test]
     fn table_autocomplete_track_names_test() {
         let table = get_audio_table();
@@ -546,6 +1401,58 @@ mod tests {
         assert_eq!("Beez's Biz", results[0]);
     }
 
+    #[test]
+    fn table_search_rows_test() {
+        let table = get_audio_table();
+        table.create_table();
+
+        let mut row_insert = make_audio_table_row_insert();
+        row_insert.name = "Air Horn".into();
+        row_insert.tags = Tags::from("loud mlg");
+        table.insert_audio_row(row_insert).unwrap();
+
+        let mut row_insert = make_audio_table_row_insert();
+        row_insert.name = "Sad \"Horn\"".into();
+        row_insert.tags = Tags::from("trombone");
+        table.insert_audio_row(row_insert).unwrap();
+
+        assert_eq!(2, table.search_rows("horn", 10).unwrap().len());
+        assert_eq!(1, table.search_rows("horn", 1).unwrap().len());
+        assert_eq!("Air Horn", table.search_rows("mlg", 10).unwrap()[0].name);
+        assert_eq!(1, table.search_rows("\"Horn\"", 10).unwrap().len());
+        assert!(table.search_rows("horn OR", 10).unwrap().is_empty());
+
+        // transcript matches come after name & tag matches
+        let sad_horn = table.search_rows("trombone", 1).unwrap().remove(0);
+        table
+            .set_transcript(sad_horn.id, "Absolutely not, horn")
+            .unwrap();
+        assert_eq!(
+            "Sad \"Horn\"",
+            table.search_rows("absolutely", 10).unwrap()[0].name
+        );
+        let names: Vec<_> = table
+            .search_rows("horn", 10)
+            .unwrap()
+            .into_iter()
+            .map(|row| row.name)
+            .collect();
+        assert_eq!(names.len(), 2);
+
+        table
+            .set_transcript(sad_horn.id, "Definitely, yes")
+            .unwrap();
+        assert!(table.search_rows("absolutely", 10).unwrap().is_empty());
+        assert_eq!(
+            table.transcript(sad_horn.id).unwrap().as_deref(),
+            Some("Definitely, yes")
+        );
+
+        table.delete_row(sad_horn.id).unwrap();
+        assert!(table.search_rows("definitely", 10).unwrap().is_empty());
+        assert_eq!(table.transcript(sad_horn.id).unwrap(), None);
+    }
+
     #[test]
     fn tags_test() {
         let tags = Tags::from("tag-1, tag_2, tag3, !#$%^&tag4&*(()\ttag5");
@@ -0,0 +1,181 @@
+use crate::{commands::PoiseError, common::LogResult, fingerprint::Fingerprint};
+
+use super::{DbConnection, Table};
+
+/// Audio of a sound deleted with `ban`, which uploads are checked against
+#[derive(Debug, Clone, PartialEq)]
+pub struct BannedSoundRow {
+    pub id: i64,
+    /// Name of the sound when it was banned
+    pub name: String,
+    pub file_hash: Option<String>,
+    /// None when the audio couldn't be decoded, leaving only its hash to match
+    pub fingerprint: Option<Fingerprint>,
+    pub banned_by: u64,
+}
+
+impl BannedSoundRow {
+    /// Whether audio with hash & fingerprint is the banned sound's audio
+    pub fn matches(&self, file_hash: &str, fingerprint: Option<&Fingerprint>) -> bool {
+        self.file_hash.as_deref() == Some(file_hash)
+            || self
+                .fingerprint
+                .as_ref()
+                .zip(fingerprint)
+                .is_some_and(|(banned, fingerprint)| banned.matches(fingerprint))
+    }
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for BannedSoundRow {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            name: row.get("name")?,
+            file_hash: row.get("file_hash")?,
+            fingerprint: row
+                .get::<_, Option<Vec<u8>>>("fingerprint")?
+                .map(|bytes| Fingerprint::from_bytes(&bytes)),
+            banned_by: row.get("banned_by")?,
+        })
+    }
+}
+
+pub struct BannedSoundsTable {
+    conn: DbConnection,
+}
+
+impl BannedSoundsTable {
+    pub const TABLE_NAME: &'static str = "banned_sounds";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    pub fn insert_row(
+        &self,
+        name: &str,
+        file_hash: Option<&str>,
+        fingerprint: Option<&Fingerprint>,
+        banned_by: u64,
+    ) -> Result<i64, PoiseError> {
+        log::info!("Banning sound `{name}`. banned_by: {banned_by}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (name, file_hash, fingerprint, banned_by, created_at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5)
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (
+                    name,
+                    file_hash,
+                    fingerprint.map(|fingerprint| fingerprint.to_bytes()),
+                    banned_by,
+                    chrono::Utc::now(),
+                ),
+            )
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn delete_row(&self, id: i64) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE id = ?1");
+
+        self.conn
+            .execute(sql.as_str(), [id])
+            .log_err_msg(format!("Failed deleting row of {table_name}"))?;
+
+        Ok(())
+    }
+
+    pub fn rows(&self) -> Result<Vec<BannedSoundRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT * FROM {table_name} ORDER BY id");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map((), |row| BannedSoundRow::try_from(row))
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+}
+
+impl Table for BannedSoundsTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                file_hash VARCHAR(64),
+                fingerprint BLOB,
+                banned_by INTEGER NOT NULL,
+                created_at VARCHAR(40) NOT NULL
+            );
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    #[test]
+    fn banned_sounds_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let table = BannedSoundsTable::new(db_pool.get().unwrap());
+        table.create_table();
+        table.create_table();
+
+        let fingerprint = Fingerprint::from_bytes(&[1, 0, 2, 0]);
+        table
+            .insert_row("airhorn", Some("abc"), Some(&fingerprint), 1)
+            .unwrap();
+        let trombone_id = table.insert_row("trombone", None, None, 2).unwrap();
+
+        let rows = table.rows().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].fingerprint.as_ref(), Some(&fingerprint));
+        assert_eq!(rows[0].file_hash.as_deref(), Some("abc"));
+        assert_eq!(rows[1].fingerprint, None);
+
+        assert!(rows[0].matches("abc", None));
+        assert!(!rows[1].matches("abc", Some(&fingerprint)));
+
+        table.delete_row(trombone_id).unwrap();
+        assert_eq!(table.rows().unwrap().len(), 1);
+    }
+}
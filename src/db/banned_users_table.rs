@@ -0,0 +1,138 @@
+use rusqlite::OptionalExtension;
+
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{DbConnection, Table};
+
+pub struct BannedUsersTable {
+    conn: DbConnection,
+}
+
+impl BannedUsersTable {
+    pub const TABLE_NAME: &'static str = "banned_users";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    pub fn ban_user(
+        &self,
+        guild_id: u64,
+        user_id: u64,
+        banned_by: u64,
+        reason: Option<&str>,
+    ) -> Result<(), PoiseError> {
+        log::info!("Banning user from soundboard. guild_id: {guild_id}, user_id: {user_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (guild_id, user_id, banned_by, reason, created_at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT (guild_id, user_id) DO UPDATE SET
+                banned_by = excluded.banned_by,
+                reason = excluded.reason
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (guild_id, user_id, banned_by, reason, chrono::Utc::now()),
+            )
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Returns whether user was banned
+    pub fn unban_user(&self, guild_id: u64, user_id: u64) -> Result<bool, PoiseError> {
+        log::info!("Unbanning user from soundboard. guild_id: {guild_id}, user_id: {user_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE guild_id = ?1 AND user_id = ?2");
+
+        let deleted = self
+            .conn
+            .execute(sql.as_str(), (guild_id, user_id))
+            .log_err_msg(format!("Failed deleting row from {table_name}"))?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Ban reason, when user is banned. Reason is empty when none was given
+    pub fn find_ban(&self, guild_id: u64, user_id: u64) -> Result<Option<String>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT reason FROM {table_name} WHERE guild_id = ?1 AND user_id = ?2");
+
+        let reason: Option<Option<String>> = self
+            .conn
+            .query_row(sql.as_str(), (guild_id, user_id), |row| row.get(0))
+            .optional()
+            .log_err_msg(format!("Failed finding row in {table_name}"))?;
+
+        Ok(reason.map(|reason| reason.unwrap_or_default()))
+    }
+}
+
+impl Table for BannedUsersTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                guild_id INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                banned_by INTEGER NOT NULL,
+                reason TEXT,
+                created_at VARCHAR(40) NOT NULL,
+                PRIMARY KEY (guild_id, user_id)
+            );
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_banned_users_table() -> BannedUsersTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        BannedUsersTable::new(db_pool.get().unwrap())
+    }
+
+    #[test]
+    fn ban_unban_test() {
+        let table = get_banned_users_table();
+        table.create_table();
+        table.create_table();
+
+        assert_eq!(table.find_ban(1, 2).unwrap(), None);
+
+        table.ban_user(1, 2, 3, None).unwrap();
+        table.ban_user(1, 2, 3, Some("spam")).unwrap();
+        assert_eq!(table.find_ban(1, 2).unwrap(), Some("spam".into()));
+        assert_eq!(table.find_ban(2, 2).unwrap(), None);
+
+        assert!(table.unban_user(1, 2).unwrap());
+        assert!(!table.unban_user(1, 2).unwrap());
+        assert_eq!(table.find_ban(1, 2).unwrap(), None);
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{DbConnection, Table};
+
+/// Member's birthday or anniversary in a guild, celebrated every year with a sound
+#[derive(Debug, Clone, PartialEq)]
+pub struct BirthdayRow {
+    pub id: i64,
+    pub guild_id: u64,
+    pub user_id: u64,
+    /// Day celebrated, as `MM-DD`
+    pub day: String,
+    pub audio_id: i64,
+    /// Text channel greetings are posted in
+    pub channel_id: u64,
+    /// Year birthday was last celebrated, so it's only celebrated once a year
+    pub celebrated_year: Option<i32>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for BirthdayRow {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            guild_id: row.get("guild_id")?,
+            user_id: row.get("user_id")?,
+            day: row.get("day")?,
+            audio_id: row.get("audio_id")?,
+            channel_id: row.get("channel_id")?,
+            celebrated_year: row.get("celebrated_year")?,
+        })
+    }
+}
+
+pub struct BirthdaysTable {
+    conn: DbConnection,
+}
+
+impl BirthdaysTable {
+    pub const TABLE_NAME: &'static str = "birthdays";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    /// Set member's birthday in guild, replacing the one already set. Replaced birthdays can be
+    /// celebrated again this year
+    pub fn upsert_row(
+        &self,
+        guild_id: u64,
+        user_id: u64,
+        day: &str,
+        audio_id: i64,
+        channel_id: u64,
+    ) -> Result<(), PoiseError> {
+        log::info!("Setting birthday of user {user_id} to {day}. guild_id: {guild_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (guild_id, user_id, day, audio_id, channel_id, celebrated_year, created_at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, NULL, ?6)
+            ON CONFLICT (guild_id, user_id) DO UPDATE SET
+                day = excluded.day,
+                audio_id = excluded.audio_id,
+                channel_id = excluded.channel_id,
+                celebrated_year = NULL
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (
+                    guild_id,
+                    user_id,
+                    day,
+                    audio_id,
+                    channel_id,
+                    chrono::Utc::now(),
+                ),
+            )
+            .log_err_msg(format!("Failed upserting row into {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Returns whether member had a birthday set
+    pub fn delete_row(&self, guild_id: u64, user_id: u64) -> Result<bool, PoiseError> {
+        log::info!("Removing birthday of user {user_id}. guild_id: {guild_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE guild_id = ?1 AND user_id = ?2");
+
+        let deleted = self
+            .conn
+            .execute(sql.as_str(), (guild_id, user_id))
+            .log_err_msg(format!("Failed deleting row from {table_name}"))?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Birthdays of guild, in calendar order
+    pub fn rows(&self, guild_id: u64) -> Result<Vec<BirthdayRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT * FROM {table_name} WHERE guild_id = ?1 ORDER BY day, id");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map([guild_id], |row| BirthdayRow::try_from(row))
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+
+    pub fn guild_ids(&self) -> Result<Vec<u64>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT DISTINCT guild_id FROM {table_name}");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let guild_ids = stmt
+            .query_map((), |row| row.get(0))
+            .log_err_msg(format!("Failed selecting guild ids of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading guild ids of {table_name}"))?;
+
+        Ok(guild_ids)
+    }
+
+    pub fn set_celebrated(&self, id: i64, year: i32) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("UPDATE {table_name} SET celebrated_year = ?2 WHERE id = ?1");
+
+        self.conn
+            .execute(sql.as_str(), (id, year))
+            .log_err_msg(format!("Failed updating celebrated year of {table_name}"))?;
+
+        Ok(())
+    }
+}
+
+impl Table for BirthdaysTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                guild_id INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                day VARCHAR(5) NOT NULL,
+                audio_id INTEGER NOT NULL,
+                channel_id INTEGER NOT NULL,
+                celebrated_year INTEGER,
+                created_at VARCHAR(40) NOT NULL,
+                UNIQUE (guild_id, user_id)
+            );
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_birthdays_table() -> BirthdaysTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        BirthdaysTable::new(db_pool.get().unwrap())
+    }
+
+    #[test]
+    fn birthdays_test() {
+        let table = get_birthdays_table();
+        table.create_table();
+        table.create_table();
+
+        table.upsert_row(1, 2, "10-16", 3, 4).unwrap();
+        table.upsert_row(1, 5, "01-02", 3, 4).unwrap();
+        table.upsert_row(6, 2, "07-04", 3, 4).unwrap();
+        assert_eq!(table.guild_ids().unwrap().len(), 2);
+
+        let rows = table.rows(1).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].user_id, 5);
+
+        // setting a birthday again replaces it, and it can be celebrated again
+        table.set_celebrated(rows[1].id, 2026).unwrap();
+        assert_eq!(table.rows(1).unwrap()[1].celebrated_year, Some(2026));
+        table.upsert_row(1, 2, "10-17", 7, 4).unwrap();
+        let rows = table.rows(1).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (
+                rows[1].day.as_str(),
+                rows[1].audio_id,
+                rows[1].celebrated_year
+            ),
+            ("10-17", 7, None)
+        );
+
+        assert!(table.delete_row(1, 2).unwrap());
+        assert!(!table.delete_row(1, 2).unwrap());
+        assert_eq!(table.rows(6).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{AudioTable, BoardLayout, DbConnection, Table, UserPrefsTable};
+
+/// Manual order of sounds on boards, set by `/board reorder`. The full board's order is shared by
+/// everyone, favorites boards are ordered by their user
+pub struct BoardOrderTable {
+    conn: DbConnection,
+}
+
+impl BoardOrderTable {
+    pub const TABLE_NAME: &'static str = "board_order";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    /// User id board's order is saved under. 0 for the shared full board
+    fn owner_id(board: BoardLayout, user_id: u64) -> u64 {
+        match board {
+            BoardLayout::Full => 0,
+            BoardLayout::Favorites => user_id,
+        }
+    }
+
+    /// Left join of audio table onto sort orders of user's board
+    pub fn join_clause(board: BoardLayout, user_id: u64) -> String {
+        let table_name = Self::TABLE_NAME;
+        let audio_table_name = AudioTable::TABLE_NAME;
+        let owner_id = Self::owner_id(board, user_id);
+
+        format!(
+            "LEFT JOIN {table_name}
+                ON {table_name}.audio_id = {audio_table_name}.id
+                AND {table_name}.board = '{board}'
+                AND {table_name}.user_id = {owner_id}"
+        )
+    }
+
+    /// Order by clause putting sounds with a sort order first, in sort order
+    pub fn order_by_clause() -> String {
+        let table_name = Self::TABLE_NAME;
+        format!("{table_name}.sort_order IS NULL, {table_name}.sort_order")
+    }
+
+    /// Ids of sounds on user's board, in board order. Sounds never reordered follow the ordered
+    /// sounds, oldest first
+    pub fn board_audio_ids(
+        &self,
+        board: BoardLayout,
+        user_id: u64,
+    ) -> Result<Vec<i64>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let audio_table_name = AudioTable::TABLE_NAME;
+        let join_clause = Self::join_clause(board, user_id);
+        let order_by_clause = Self::order_by_clause();
+        let where_clause = match board {
+            BoardLayout::Full => "".to_string(),
+            BoardLayout::Favorites => format!(
+                "WHERE {audio_table_name}.id IN (SELECT audio_id FROM {} WHERE user_id = {user_id})",
+                UserPrefsTable::FAVORITES_TABLE_NAME
+            ),
+        };
+        let sql = format!(
+            "
+            SELECT {audio_table_name}.id FROM {audio_table_name}
+            {join_clause}
+            {where_clause}
+            ORDER BY {order_by_clause}, {audio_table_name}.id
+            "
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let ids = stmt
+            .query_map((), |row| row.get(0))
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<i64>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(ids)
+    }
+
+    /// Replace order of user's board with the given sound ids, first to last
+    pub fn set_order(
+        &self,
+        board: BoardLayout,
+        user_id: u64,
+        audio_ids: &[i64],
+    ) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let owner_id = Self::owner_id(board, user_id);
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .log_err_msg(format!("Failed starting update of {table_name}"))?;
+
+        let delete_sql = format!("DELETE FROM {table_name} WHERE board = ?1 AND user_id = ?2");
+        tx.execute(delete_sql.as_str(), (board.as_str(), owner_id))
+            .log_err_msg(format!("Failed deleting rows of {table_name}"))?;
+
+        let insert_sql = format!(
+            "INSERT INTO {table_name} (board, user_id, audio_id, sort_order) VALUES (?1, ?2, ?3, ?4)"
+        );
+        {
+            let mut stmt = tx
+                .prepare(insert_sql.as_str())
+                .log_err_msg(format!("Failed preparing insert into {table_name}"))?;
+            for (sort_order, audio_id) in audio_ids.iter().enumerate() {
+                stmt.execute((board.as_str(), owner_id, audio_id, sort_order))
+                    .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+            }
+        }
+
+        tx.commit()
+            .log_err_msg(format!("Failed committing update of {table_name}"))?;
+        Ok(())
+    }
+}
+
+impl Table for BoardOrderTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                board VARCHAR(20) NOT NULL,
+                user_id INTEGER NOT NULL,
+                audio_id INTEGER NOT NULL,
+                sort_order INTEGER NOT NULL,
+                PRIMARY KEY (board, user_id, audio_id)
+            );
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::audio::AudioFile;
+    use crate::db::{AudioTableRowInsert, Tags, UniqueAudioTableCol};
+
+    #[test]
+    fn board_order_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let audio_table = || AudioTable::new(db_pool.get().unwrap());
+        let prefs_table = || UserPrefsTable::new(db_pool.get().unwrap());
+        let table = || BoardOrderTable::new(db_pool.get().unwrap());
+        audio_table().create_table();
+        prefs_table().create_table();
+        table().create_table();
+        table().create_table();
+
+        let ids: Vec<i64> = (0..4)
+            .map(|i| {
+                audio_table()
+                    .insert_audio_row(AudioTableRowInsert {
+                        name: format!("sound {i}"),
+                        tags: Tags::new(),
+                        audio_file: AudioFile::new(format!("/tmp/board_{i}.mp3").into()),
+                        created_at: chrono::Utc::now(),
+                        author_id: None,
+                        author_name: None,
+                        author_global_name: None,
+                        file_hash: None,
+                        file_size: None,
+                        file_modified_at: None,
+                    })
+                    .unwrap();
+                audio_table()
+                    .find_audio_row(UniqueAudioTableCol::Name(format!("sound {i}")))
+                    .unwrap()
+                    .id
+            })
+            .collect();
+
+        // unordered sounds follow ordered sounds
+        let board_ids = |board, user_id| table().board_audio_ids(board, user_id).unwrap();
+        assert_eq!(board_ids(BoardLayout::Full, 1), ids);
+        table()
+            .set_order(BoardLayout::Full, 1, &[ids[2], ids[0]])
+            .unwrap();
+        let full_order = vec![ids[2], ids[0], ids[1], ids[3]];
+        assert_eq!(board_ids(BoardLayout::Full, 1), full_order);
+        // full board order is shared
+        assert_eq!(board_ids(BoardLayout::Full, 2), full_order);
+
+        // favorites boards only have user's favorites, in their own order
+        prefs_table().toggle_favorite(1, ids[0]).unwrap();
+        prefs_table().toggle_favorite(1, ids[3]).unwrap();
+        assert_eq!(board_ids(BoardLayout::Favorites, 1), vec![ids[0], ids[3]]);
+        table()
+            .set_order(BoardLayout::Favorites, 1, &[ids[3], ids[0]])
+            .unwrap();
+        assert_eq!(board_ids(BoardLayout::Favorites, 1), vec![ids[3], ids[0]]);
+        assert_eq!(board_ids(BoardLayout::Full, 1), full_order);
+        assert!(board_ids(BoardLayout::Favorites, 2).is_empty());
+    }
+}
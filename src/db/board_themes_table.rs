@@ -0,0 +1,235 @@
+use rusqlite::OptionalExtension;
+
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{DbConnection, Table};
+
+/// Tag featured on a guild's board every year between two days, ie: `spooky` from `10-01` to `10-31`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoardThemeRow {
+    pub id: i64,
+    pub guild_id: u64,
+    pub tag: String,
+    /// First day theme is featured, as `MM-DD`
+    pub start_day: String,
+    /// Last day theme is featured, as `MM-DD`. Before start day when theme spans new year
+    pub end_day: String,
+    /// Whether theme is currently featured. Set by the theme switching job
+    pub active: bool,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for BoardThemeRow {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            guild_id: row.get("guild_id")?,
+            tag: row.get("tag")?,
+            start_day: row.get("start_day")?,
+            end_day: row.get("end_day")?,
+            active: row.get("active")?,
+        })
+    }
+}
+
+pub struct BoardThemesTable {
+    conn: DbConnection,
+}
+
+impl BoardThemesTable {
+    pub const TABLE_NAME: &'static str = "board_themes";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    /// Returns id of inserted theme
+    pub fn insert_row(
+        &self,
+        guild_id: u64,
+        tag: &str,
+        start_day: &str,
+        end_day: &str,
+    ) -> Result<i64, PoiseError> {
+        log::info!("Adding board theme `{tag}` {start_day} to {end_day}. guild_id: {guild_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (guild_id, tag, start_day, end_day, active, created_at)
+            VALUES
+                (?1, ?2, ?3, ?4, 0, ?5)
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (guild_id, tag, start_day, end_day, chrono::Utc::now()),
+            )
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Returns whether theme was deleted
+    pub fn delete_row(&self, guild_id: u64, id: i64) -> Result<bool, PoiseError> {
+        log::info!("Removing board theme {id}. guild_id: {guild_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE guild_id = ?1 AND id = ?2");
+
+        let deleted = self
+            .conn
+            .execute(sql.as_str(), (guild_id, id))
+            .log_err_msg(format!("Failed deleting row from {table_name}"))?;
+
+        Ok(deleted > 0)
+    }
+
+    /// Themes of guild, oldest first
+    pub fn rows(&self, guild_id: u64) -> Result<Vec<BoardThemeRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT * FROM {table_name} WHERE guild_id = ?1 ORDER BY id");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map([guild_id], |row| BoardThemeRow::try_from(row))
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+
+    pub fn guild_ids(&self) -> Result<Vec<u64>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT DISTINCT guild_id FROM {table_name}");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let guild_ids = stmt
+            .query_map((), |row| row.get(0))
+            .log_err_msg(format!("Failed selecting guild ids of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading guild ids of {table_name}"))?;
+
+        Ok(guild_ids)
+    }
+
+    /// Tag of guild's currently featured theme
+    pub fn active_tag(&self, guild_id: u64) -> Result<Option<String>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql =
+            format!("SELECT tag FROM {table_name} WHERE guild_id = ?1 AND active = 1 LIMIT 1");
+
+        let tag = self
+            .conn
+            .query_row(sql.as_str(), [guild_id], |row| row.get(0))
+            .optional()
+            .log_err_msg(format!("Failed finding active row in {table_name}"))?;
+
+        Ok(tag)
+    }
+
+    /// Make theme the only featured theme of guild. None features no theme
+    pub fn set_active(&self, guild_id: u64, id: Option<i64>) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("UPDATE {table_name} SET active = (id IS ?2) WHERE guild_id = ?1");
+
+        self.conn
+            .execute(sql.as_str(), (guild_id, id))
+            .log_err_msg(format!("Failed updating active row of {table_name}"))?;
+
+        Ok(())
+    }
+}
+
+impl Table for BoardThemesTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                guild_id INTEGER NOT NULL,
+                tag TEXT NOT NULL,
+                start_day VARCHAR(5) NOT NULL,
+                end_day VARCHAR(5) NOT NULL,
+                active INTEGER NOT NULL DEFAULT 0,
+                created_at VARCHAR(40) NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS {table_name}_guild_id_idx ON {table_name} (guild_id);
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_board_themes_table() -> BoardThemesTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        BoardThemesTable::new(db_pool.get().unwrap())
+    }
+
+    #[test]
+    fn board_themes_test() {
+        let table = get_board_themes_table();
+        table.create_table();
+        table.create_table();
+
+        let spooky = table.insert_row(1, "spooky", "10-01", "10-31").unwrap();
+        let winter = table.insert_row(1, "winter", "12-01", "01-06").unwrap();
+        table.insert_row(2, "summer", "06-21", "09-22").unwrap();
+        assert_eq!(table.guild_ids().unwrap().len(), 2);
+        assert_eq!(table.active_tag(1).unwrap(), None);
+
+        table.set_active(1, Some(spooky)).unwrap();
+        assert_eq!(table.active_tag(1).unwrap(), Some("spooky".into()));
+        table.set_active(1, Some(winter)).unwrap();
+        assert_eq!(table.active_tag(1).unwrap(), Some("winter".into()));
+        assert_eq!(
+            table
+                .rows(1)
+                .unwrap()
+                .iter()
+                .filter(|row| row.active)
+                .count(),
+            1
+        );
+
+        table.set_active(1, None).unwrap();
+        assert_eq!(table.active_tag(1).unwrap(), None);
+
+        // themes can only be deleted from their own guild
+        assert!(!table.delete_row(2, spooky).unwrap());
+        assert!(table.delete_row(1, spooky).unwrap());
+        assert_eq!(table.rows(1).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{DbConnection, Table};
+
+/// Message the bot sent to a channel, ie: a soundboard or play confirmation
+#[derive(Debug, Clone, PartialEq)]
+pub struct BotMessageRow {
+    pub id: i64,
+    pub guild_id: u64,
+    pub channel_id: u64,
+    pub message_id: u64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for BotMessageRow {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            guild_id: row.get("guild_id")?,
+            channel_id: row.get("channel_id")?,
+            message_id: row.get("message_id")?,
+            created_at: row.get("created_at")?,
+        })
+    }
+}
+
+/// Messages sent by the bot, so old ones can be cleaned up
+pub struct BotMessagesTable {
+    conn: DbConnection,
+}
+
+impl BotMessagesTable {
+    pub const TABLE_NAME: &'static str = "bot_messages";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    pub fn insert_message(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        message_id: u64,
+        created_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT OR IGNORE INTO {table_name}
+                (guild_id, channel_id, message_id, created_at)
+            VALUES
+                (?1, ?2, ?3, ?4)
+            "
+        );
+
+        self.conn
+            .execute(sql.as_str(), (guild_id, channel_id, message_id, created_at))
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Stop tracking message, once it's deleted
+    pub fn delete_message(&self, message_id: u64) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE message_id = ?1");
+
+        self.conn
+            .execute(sql.as_str(), [message_id])
+            .log_err_msg(format!("Failed deleting row from {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Guilds with messages tracked
+    pub fn guild_ids(&self) -> Result<Vec<u64>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT DISTINCT guild_id FROM {table_name}");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let guild_ids = stmt
+            .query_map((), |row| row.get(0))
+            .log_err_msg(format!("Failed selecting guilds of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading guilds of {table_name}"))?;
+
+        Ok(guild_ids)
+    }
+
+    /// Messages of guild sent before `before`, or beyond the newest `keep` messages of their channel.
+    /// Only messages of channel when channel_id is given. Oldest first
+    pub fn expired_rows(
+        &self,
+        guild_id: u64,
+        channel_id: Option<u64>,
+        before: Option<chrono::DateTime<chrono::Utc>>,
+        keep: Option<u32>,
+    ) -> Result<Vec<BotMessageRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            SELECT * FROM (
+                SELECT *, ROW_NUMBER() OVER (
+                    PARTITION BY channel_id ORDER BY created_at DESC, id DESC
+                ) AS newest_rank
+                FROM {table_name}
+                WHERE guild_id = ?1 AND (?2 IS NULL OR channel_id = ?2)
+            )
+            WHERE (?3 IS NOT NULL AND created_at < ?3) OR (?4 IS NOT NULL AND newest_rank > ?4)
+            ORDER BY created_at, id
+            "
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map((guild_id, channel_id, before, keep), |row| {
+                BotMessageRow::try_from(row)
+            })
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+}
+
+impl Table for BotMessagesTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY,
+                guild_id INTEGER NOT NULL,
+                channel_id INTEGER NOT NULL,
+                message_id INTEGER NOT NULL UNIQUE,
+                created_at VARCHAR(40) NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS {table_name}_guild_channel_idx
+                ON {table_name} (guild_id, channel_id, created_at);
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_bot_messages_table() -> BotMessagesTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        BotMessagesTable::new(db_pool.get().unwrap())
+    }
+
+    #[test]
+    fn expired_rows_test() {
+        let table = get_bot_messages_table();
+        table.create_table();
+
+        let now = chrono::Utc::now();
+        let hours_ago = |hours: i64| now - chrono::Duration::hours(hours);
+        table.insert_message(1, 10, 100, hours_ago(30)).unwrap();
+        table.insert_message(1, 10, 101, hours_ago(5)).unwrap();
+        table.insert_message(1, 10, 102, hours_ago(1)).unwrap();
+        table.insert_message(1, 11, 110, hours_ago(2)).unwrap();
+        table.insert_message(2, 20, 200, hours_ago(50)).unwrap();
+        table.insert_message(2, 20, 200, hours_ago(50)).unwrap();
+
+        let message_ids = |rows: Vec<BotMessageRow>| -> Vec<u64> {
+            rows.iter().map(|row| row.message_id).collect()
+        };
+
+        let expired = table.expired_rows(1, None, Some(hours_ago(24)), None);
+        assert_eq!(message_ids(expired.unwrap()), vec![100]);
+
+        let expired = table.expired_rows(1, None, None, Some(1));
+        assert_eq!(message_ids(expired.unwrap()), vec![100, 101]);
+
+        let expired = table.expired_rows(1, Some(11), Some(hours_ago(1)), Some(1));
+        assert_eq!(message_ids(expired.unwrap()), vec![110]);
+
+        assert!(table.expired_rows(1, None, None, None).unwrap().is_empty());
+        assert_eq!(table.guild_ids().unwrap().len(), 2);
+
+        table.delete_message(100).unwrap();
+        let expired = table.expired_rows(1, None, Some(hours_ago(24)), None);
+        assert!(expired.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{DbConnection, Table};
+
+pub struct CommandStatsRowInsert {
+    pub command: String,
+    pub guild_id: Option<u64>,
+    pub user_id: u64,
+    pub duration_ms: u64,
+    pub success: bool,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+pub struct CommandStatsTable {
+    conn: DbConnection,
+}
+
+impl CommandStatsTable {
+    pub const TABLE_NAME: &'static str = "command_stats";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    pub fn insert_row(&self, row: &CommandStatsRowInsert) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (command, guild_id, user_id, duration_ms, success, created_at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6)
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (
+                    &row.command,
+                    &row.guild_id,
+                    &row.user_id,
+                    &row.duration_ms,
+                    &row.success,
+                    &row.created_at,
+                ),
+            )
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(())
+    }
+}
+
+impl Table for CommandStatsTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY,
+                command VARCHAR(100) NOT NULL,
+                guild_id INTEGER,
+                user_id INTEGER NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                success BOOLEAN NOT NULL,
+                created_at VARCHAR(40) NOT NULL
+            );
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_command_stats_table() -> CommandStatsTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        CommandStatsTable::new(db_pool.get().unwrap())
+    }
+
+    #[test]
+    fn insert_row_test() {
+        let table = get_command_stats_table();
+        table.create_table();
+        table.create_table();
+
+        table
+            .insert_row(&CommandStatsRowInsert {
+                command: "sounds display".into(),
+                guild_id: Some(1),
+                user_id: 2,
+                duration_ms: 150,
+                success: true,
+                created_at: chrono::Utc::now(),
+            })
+            .unwrap();
+
+        let count: i64 = table
+            .connection()
+            .query_row("SELECT COUNT(*) FROM command_stats", (), |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+}
@@ -0,0 +1,113 @@
+use rusqlite::OptionalExtension;
+
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{DbConnection, Table};
+
+/// Bot wide feature flags set by bot owners. Features without a row use their default
+pub struct FeatureFlagsTable {
+    conn: DbConnection,
+}
+
+impl FeatureFlagsTable {
+    const TABLE_NAME: &'static str = "feature_flags";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    /// Whether feature is enabled. None when flag was never set
+    pub fn get_flag(&self, name: &str) -> Result<Option<bool>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT enabled FROM {table_name} WHERE name = ?1");
+
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), [name], |row| row.get(0))
+            .optional()
+            .log_err_msg(format!("Failed getting feature flag {name}"))?)
+    }
+
+    pub fn set_flag(&self, name: &str, enabled: bool, updated_by: u64) -> Result<(), PoiseError> {
+        log::info!("Saving feature flag. name: {name}, enabled: {enabled}, by: {updated_by}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (name, enabled, updated_by, updated_at)
+            VALUES
+                (?1, ?2, ?3, ?4)
+            ON CONFLICT (name) DO UPDATE SET
+                enabled = excluded.enabled,
+                updated_by = excluded.updated_by,
+                updated_at = excluded.updated_at
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (name, enabled, updated_by, chrono::Utc::now()),
+            )
+            .log_err_msg(format!("Failed saving feature flag {name}"))?;
+
+        Ok(())
+    }
+}
+
+impl Table for FeatureFlagsTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                name VARCHAR(40) PRIMARY KEY,
+                enabled BOOLEAN NOT NULL,
+                updated_by INTEGER NOT NULL,
+                updated_at VARCHAR(40) NOT NULL
+            );
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_feature_flags_table() -> FeatureFlagsTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        FeatureFlagsTable::new(db_pool.get().unwrap())
+    }
+
+    #[test]
+    fn set_flag_test() {
+        let table = get_feature_flags_table();
+        table.create_table();
+        table.create_table();
+
+        assert_eq!(table.get_flag("url_imports_enabled").unwrap(), None);
+
+        table.set_flag("url_imports_enabled", false, 1).unwrap();
+        assert_eq!(table.get_flag("url_imports_enabled").unwrap(), Some(false));
+
+        table.set_flag("url_imports_enabled", true, 2).unwrap();
+        assert_eq!(table.get_flag("url_imports_enabled").unwrap(), Some(true));
+        assert_eq!(table.get_flag("audio_editing_enabled").unwrap(), None);
+    }
+}
@@ -0,0 +1,401 @@
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{DbConnection, Table};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobStatus {
+    /// Waiting for its run time
+    Pending,
+    Running,
+    /// One off job that succeeded
+    Done,
+    /// One off job that errored
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Done => "done",
+            Self::Failed => "failed",
+        }
+    }
+
+    fn from_str(value: &str) -> Self {
+        match value {
+            "running" => Self::Running,
+            "done" => Self::Done,
+            "failed" => Self::Failed,
+            _ => Self::Pending,
+        }
+    }
+}
+
+pub struct JobRowInsert {
+    /// Kind of work, ie: `message_retention`
+    pub kind: String,
+    /// Guild job does work for. None for bot wide jobs
+    pub guild_id: Option<u64>,
+    /// Job specific arguments, as json
+    pub payload: Option<String>,
+    pub run_at: chrono::DateTime<chrono::Utc>,
+    /// Seconds between runs of a recurring job. None for one off jobs
+    pub interval_secs: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobRow {
+    pub id: i64,
+    pub kind: String,
+    pub guild_id: Option<u64>,
+    pub payload: Option<String>,
+    pub status: JobStatus,
+    pub run_at: chrono::DateTime<chrono::Utc>,
+    pub interval_secs: Option<i64>,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub last_run_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for JobRow {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        let status: String = row.get("status")?;
+
+        Ok(Self {
+            id: row.get("id")?,
+            kind: row.get("kind")?,
+            guild_id: row.get("guild_id")?,
+            payload: row.get("payload")?,
+            status: JobStatus::from_str(&status),
+            run_at: row.get("run_at")?,
+            interval_secs: row.get("interval_secs")?,
+            attempts: row.get("attempts")?,
+            last_error: row.get("last_error")?,
+            last_run_at: row.get("last_run_at")?,
+        })
+    }
+}
+
+/// Background jobs, kept in the database so scheduled work survives restarts
+pub struct JobsTable {
+    conn: DbConnection,
+}
+
+impl JobsTable {
+    pub const TABLE_NAME: &'static str = "jobs";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    /// Returns id of inserted job
+    pub fn insert_row(&self, row: &JobRowInsert) -> Result<i64, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (kind, guild_id, payload, status, run_at, interval_secs, attempts, created_at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6, 0, ?7)
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (
+                    &row.kind,
+                    &row.guild_id,
+                    &row.payload,
+                    JobStatus::Pending.as_str(),
+                    &row.run_at,
+                    &row.interval_secs,
+                    chrono::Utc::now(),
+                ),
+            )
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Insert job, unless one of its kind & guild is already pending or running. Returns whether it was inserted
+    pub fn ensure_scheduled(&self, row: &JobRowInsert) -> Result<bool, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            SELECT COUNT(*) FROM {table_name}
+            WHERE kind = ?1 AND guild_id IS ?2 AND status IN (?3, ?4)
+            "
+        );
+
+        let scheduled: u32 = self
+            .conn
+            .query_row(
+                sql.as_str(),
+                (
+                    &row.kind,
+                    &row.guild_id,
+                    JobStatus::Pending.as_str(),
+                    JobStatus::Running.as_str(),
+                ),
+                |row| row.get(0),
+            )
+            .log_err_msg(format!("Failed counting rows in {table_name}"))?;
+
+        match scheduled {
+            0 => self.insert_row(row).map(|_| true),
+            _ => Ok(false),
+        }
+    }
+
+    /// Pending jobs whose run time has come, longest waiting first
+    pub fn due_rows(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Vec<JobRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "SELECT * FROM {table_name} WHERE status = ?1 AND run_at <= ?2 ORDER BY run_at, id"
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map((JobStatus::Pending.as_str(), now), |row| {
+                JobRow::try_from(row)
+            })
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+
+    /// Scheduled & running jobs first, then the most recently run ones
+    pub fn rows(&self, limit: u32) -> Result<Vec<JobRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            SELECT * FROM {table_name}
+            ORDER BY status IN (?1, ?2) DESC, run_at, last_run_at DESC
+            LIMIT ?3
+            "
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map(
+                (
+                    JobStatus::Running.as_str(),
+                    JobStatus::Pending.as_str(),
+                    limit,
+                ),
+                |row| JobRow::try_from(row),
+            )
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+
+    /// Mark pending job as running. Returns false when it isn't pending anymore
+    pub fn claim(&self, id: i64) -> Result<bool, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            UPDATE {table_name}
+            SET status = ?1, attempts = attempts + 1, last_run_at = ?2
+            WHERE id = ?3 AND status = ?4
+            "
+        );
+
+        let updated = self
+            .conn
+            .execute(
+                sql.as_str(),
+                (
+                    JobStatus::Running.as_str(),
+                    chrono::Utc::now(),
+                    id,
+                    JobStatus::Pending.as_str(),
+                ),
+            )
+            .log_err_msg(format!("Failed updating row of {table_name}"))?;
+
+        Ok(updated > 0)
+    }
+
+    /// Record outcome of a run. Recurring jobs are scheduled again at next_run_at, one off jobs end
+    pub fn finish(
+        &self,
+        id: i64,
+        error: Option<&str>,
+        next_run_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<(), PoiseError> {
+        let status = match (next_run_at, error) {
+            (Some(_), _) => JobStatus::Pending,
+            (None, None) => JobStatus::Done,
+            (None, Some(_)) => JobStatus::Failed,
+        };
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            UPDATE {table_name}
+            SET status = ?1, last_error = ?2, run_at = COALESCE(?3, run_at)
+            WHERE id = ?4
+            "
+        );
+
+        self.conn
+            .execute(sql.as_str(), (status.as_str(), error, next_run_at, id))
+            .log_err_msg(format!("Failed updating row of {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Jobs left running by a restart are run again. Returns number of jobs requeued
+    pub fn requeue_running(&self) -> Result<usize, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("UPDATE {table_name} SET status = ?1 WHERE status = ?2");
+
+        Ok(self
+            .conn
+            .execute(
+                sql.as_str(),
+                (JobStatus::Pending.as_str(), JobStatus::Running.as_str()),
+            )
+            .log_err_msg(format!("Failed updating rows of {table_name}"))?)
+    }
+
+    /// Remove one off jobs that ended before cutoff
+    pub fn delete_finished(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE status IN (?1, ?2) AND last_run_at < ?3");
+
+        Ok(self
+            .conn
+            .execute(
+                sql.as_str(),
+                (JobStatus::Done.as_str(), JobStatus::Failed.as_str(), before),
+            )
+            .log_err_msg(format!("Failed deleting rows from {table_name}"))?)
+    }
+}
+
+impl Table for JobsTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind VARCHAR(64) NOT NULL,
+                guild_id INTEGER,
+                payload TEXT,
+                status VARCHAR(20) NOT NULL,
+                run_at VARCHAR(40) NOT NULL,
+                interval_secs INTEGER,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT,
+                last_run_at VARCHAR(40),
+                created_at VARCHAR(40) NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS {table_name}_status_run_at
+                ON {table_name} (status, run_at);
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_jobs_table() -> JobsTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        JobsTable::new(db_pool.get().unwrap())
+    }
+
+    #[test]
+    fn job_lifecycle_test() {
+        let table = get_jobs_table();
+        table.create_table();
+
+        let now = chrono::Utc::now();
+        let recurring = JobRowInsert {
+            kind: "message_retention".into(),
+            guild_id: None,
+            payload: None,
+            run_at: now - chrono::Duration::minutes(1),
+            interval_secs: Some(3600),
+        };
+        assert!(table.ensure_scheduled(&recurring).unwrap());
+        assert!(!table.ensure_scheduled(&recurring).unwrap());
+
+        let one_off = table
+            .insert_row(&JobRowInsert {
+                kind: "backup".into(),
+                guild_id: Some(1),
+                payload: Some("{}".into()),
+                run_at: now + chrono::Duration::hours(1),
+                interval_secs: None,
+            })
+            .unwrap();
+
+        let due = table.due_rows(now).unwrap();
+        assert_eq!(due.len(), 1);
+        let job = &due[0];
+        assert!(table.claim(job.id).unwrap());
+        assert!(!table.claim(job.id).unwrap());
+
+        // restart while running
+        assert_eq!(table.requeue_running().unwrap(), 1);
+        assert!(table.claim(job.id).unwrap());
+
+        let next_run_at = now + chrono::Duration::hours(1);
+        table.finish(job.id, None, Some(next_run_at)).unwrap();
+        assert!(table.due_rows(now).unwrap().is_empty());
+
+        let later = now + chrono::Duration::hours(2);
+        assert_eq!(table.due_rows(later).unwrap().len(), 2);
+        assert!(table.claim(one_off).unwrap());
+        table.finish(one_off, Some("boom"), None).unwrap();
+
+        let rows = table.rows(10).unwrap();
+        assert_eq!(rows[0].status, JobStatus::Pending);
+        assert_eq!(rows[0].attempts, 2);
+        assert_eq!(rows[1].status, JobStatus::Failed);
+        assert_eq!(rows[1].last_error.as_deref(), Some("boom"));
+
+        assert_eq!(table.delete_finished(later).unwrap(), 1);
+        assert_eq!(table.rows(10).unwrap().len(), 1);
+    }
+}
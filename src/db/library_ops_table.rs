@@ -0,0 +1,225 @@
+use rusqlite::OptionalExtension;
+
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{DbConnection, Table};
+
+pub struct LibraryOpRowInsert {
+    /// Kind of operation, ie: `delete`
+    pub kind: String,
+    /// Guild operation was made in
+    pub guild_id: Option<u64>,
+    pub user_id: u64,
+    /// Library state before the operation, as json
+    pub changes: String,
+}
+
+/// Library mutation that can be undone
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryOpRow {
+    pub id: i64,
+    pub kind: String,
+    pub guild_id: Option<u64>,
+    pub user_id: u64,
+    pub changes: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub undone_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for LibraryOpRow {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            kind: row.get("kind")?,
+            guild_id: row.get("guild_id")?,
+            user_id: row.get("user_id")?,
+            changes: row.get("changes")?,
+            created_at: row.get("created_at")?,
+            undone_at: row.get("undone_at")?,
+        })
+    }
+}
+
+/// Log of library mutations, newest undone first by `/undo`
+pub struct LibraryOpsTable {
+    conn: DbConnection,
+}
+
+impl LibraryOpsTable {
+    pub const TABLE_NAME: &'static str = "library_ops";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    /// Returns id of inserted operation
+    pub fn insert_row(&self, row: &LibraryOpRowInsert) -> Result<i64, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (kind, guild_id, user_id, changes, created_at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5)
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (
+                    &row.kind,
+                    &row.guild_id,
+                    &row.user_id,
+                    &row.changes,
+                    chrono::Utc::now(),
+                ),
+            )
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Newest operation that hasn't been undone, if made since given time
+    pub fn latest_undoable(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<LibraryOpRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            SELECT * FROM {table_name}
+            WHERE undone_at IS NULL
+            ORDER BY id DESC
+            LIMIT 1
+            "
+        );
+
+        let row = self
+            .conn
+            .query_row(sql.as_str(), (), |row| LibraryOpRow::try_from(row))
+            .optional()
+            .log_err_msg(format!("Failed getting row of {table_name}"))?;
+
+        Ok(row.filter(|row| row.created_at >= since))
+    }
+
+    pub fn set_undone(&self, id: i64) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("UPDATE {table_name} SET undone_at = ?1 WHERE id = ?2");
+
+        self.conn
+            .execute(sql.as_str(), (chrono::Utc::now(), id))
+            .log_err_msg(format!("Failed updating row of {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Replace what operation changed, ie: once part of it was undone
+    pub fn set_changes(&self, id: i64, changes: &str) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("UPDATE {table_name} SET changes = ?1 WHERE id = ?2");
+
+        self.conn
+            .execute(sql.as_str(), (changes, id))
+            .log_err_msg(format!("Failed updating row of {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Delete operations made before given time. Returns the deleted operations
+    pub fn delete_before(
+        &self,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<LibraryOpRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE created_at < ?1 RETURNING *");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing delete of {table_name}"))?;
+
+        let rows = stmt
+            .query_map([before], |row| LibraryOpRow::try_from(row))
+            .log_err_msg(format!("Failed deleting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading deleted rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+}
+
+impl Table for LibraryOpsTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind VARCHAR(20) NOT NULL,
+                guild_id INTEGER,
+                user_id INTEGER NOT NULL,
+                changes TEXT NOT NULL,
+                created_at VARCHAR(40) NOT NULL,
+                undone_at VARCHAR(40)
+            );
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    #[test]
+    fn library_ops_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let table = LibraryOpsTable::new(db_pool.get().unwrap());
+        table.create_table();
+        table.create_table();
+
+        let insert = |kind: &str| LibraryOpRowInsert {
+            kind: kind.into(),
+            guild_id: Some(1),
+            user_id: 2,
+            changes: "{}".into(),
+        };
+        let hour_ago = chrono::Utc::now() - chrono::Duration::hours(1);
+        assert_eq!(table.latest_undoable(hour_ago).unwrap(), None);
+
+        let delete_id = table.insert_row(&insert("delete")).unwrap();
+        let rename_id = table.insert_row(&insert("rename")).unwrap();
+        let latest = table.latest_undoable(hour_ago).unwrap().unwrap();
+        assert_eq!((latest.id, latest.kind.as_str()), (rename_id, "rename"));
+
+        // undone operations are skipped, ops older than since aren't undoable
+        table.set_undone(rename_id).unwrap();
+        assert_eq!(
+            table.latest_undoable(hour_ago).unwrap().unwrap().id,
+            delete_id
+        );
+        let later = chrono::Utc::now() + chrono::Duration::seconds(1);
+        assert_eq!(table.latest_undoable(later).unwrap(), None);
+
+        let deleted = table.delete_before(later).unwrap();
+        assert_eq!(deleted.len(), 2);
+        assert_eq!(table.latest_undoable(hour_ago).unwrap(), None);
+    }
+}
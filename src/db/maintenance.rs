@@ -0,0 +1,143 @@
+use std::time::{Duration, Instant};
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::errors;
+
+use super::DbConnection;
+
+/// Outcome of a maintenance run
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaintenanceReport {
+    /// Problems found by `PRAGMA integrity_check`. Empty when the database is fine
+    pub integrity_errors: Vec<String>,
+    /// Database & write ahead log size, in bytes
+    pub size_before: u64,
+    pub size_after: u64,
+    pub vacuumed: bool,
+    pub duration: Duration,
+}
+
+impl std::fmt::Display for MaintenanceReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let integrity = match self.integrity_errors.len() {
+            0 => "ok".to_string(),
+            count => format!("{count} problems found"),
+        };
+        let action = match self.vacuumed {
+            true => "Vacuumed & analyzed",
+            false => "Analyzed",
+        };
+
+        write!(
+            f,
+            "{action} database in {:.1}s. Integrity: {integrity}. Size: {} → {}",
+            self.duration.as_secs_f64(),
+            errors::format_file_size(self.size_before),
+            errors::format_file_size(self.size_after)
+        )
+    }
+}
+
+/// Check integrity of the database, refresh query planner statistics, and when vacuum is set,
+/// rebuild the database file so space freed by deleted rows is returned to the file system.
+/// Vacuuming locks the database while it runs
+pub fn run(conn: &DbConnection, vacuum: bool) -> Result<MaintenanceReport, PoiseError> {
+    let started = Instant::now();
+    let size_before = database_size(conn);
+
+    let integrity_errors = integrity_check(conn)?;
+    if !integrity_errors.is_empty() {
+        log::error!("Database integrity check failed - {integrity_errors:?}");
+    }
+
+    // vacuuming a corrupt database can make things worse
+    let vacuumed = vacuum && integrity_errors.is_empty();
+    if vacuumed {
+        conn.execute_batch("VACUUM;")
+            .log_err_msg("Failed vacuuming database")?;
+    }
+
+    conn.execute_batch("ANALYZE; PRAGMA optimize;")
+        .log_err_msg("Failed analyzing database")?;
+
+    // vacuum writes through the write ahead log, which keeps its size until checkpointed
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+        .log_err_msg("Failed checkpointing database")?;
+
+    Ok(MaintenanceReport {
+        integrity_errors,
+        size_before,
+        size_after: database_size(conn),
+        vacuumed,
+        duration: started.elapsed(),
+    })
+}
+
+fn integrity_check(conn: &DbConnection) -> Result<Vec<String>, PoiseError> {
+    let mut stmt = conn
+        .prepare("PRAGMA integrity_check")
+        .log_err_msg("Failed preparing integrity check")?;
+
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .log_err_msg("Failed running integrity check")?
+        .collect::<Result<Vec<_>, _>>()
+        .log_err_msg("Failed reading integrity check")?;
+
+    Ok(rows.into_iter().filter(|row| row != "ok").collect())
+}
+
+/// Size of database file and its write ahead log. 0 for in memory databases
+fn database_size(conn: &DbConnection) -> u64 {
+    let Some(path) = conn.path().filter(|path| !path.is_empty()) else {
+        return 0;
+    };
+
+    [path.to_string(), format!("{path}-wal")]
+        .iter()
+        .filter_map(|file| std::fs::metadata(file).ok())
+        .map(|meta| meta.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn maintenance_test() {
+        let dir = std::env::temp_dir().join(crate::helpers::uuid_v4_str());
+        std::fs::create_dir(&dir).unwrap();
+        let config = Config {
+            sqlite_db_file: dir.join("bot.db3"),
+            ..Config::default()
+        };
+        let db_pool = crate::db::create_pool(&config).unwrap();
+        let conn = db_pool.get().unwrap();
+
+        conn.execute_batch(
+            "
+            CREATE TABLE blobs (data BLOB);
+            WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 200)
+            INSERT INTO blobs SELECT zeroblob(4096) FROM n;
+            DELETE FROM blobs;
+            ",
+        )
+        .unwrap();
+
+        let analyzed = run(&conn, false).unwrap();
+        assert!(analyzed.integrity_errors.is_empty());
+        assert!(!analyzed.vacuumed);
+
+        let vacuumed = run(&conn, true).unwrap();
+        assert!(vacuumed.vacuumed);
+        assert!(vacuumed.size_after < vacuumed.size_before);
+        assert!(vacuumed.to_string().starts_with("Vacuumed & analyzed"));
+
+        drop(conn);
+        drop(db_pool);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
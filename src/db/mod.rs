@@ -1,14 +1,131 @@
+pub mod api_keys_table;
+pub mod api_usage_table;
 pub mod audio_table;
+pub mod banned_sounds_table;
+pub mod banned_users_table;
+pub mod birthdays_table;
+pub mod board_order_table;
+pub mod board_themes_table;
+pub mod bot_messages_table;
+pub mod command_stats_table;
+pub mod feature_flags_table;
+pub mod jobs_table;
+pub mod library_ops_table;
+pub mod maintenance;
 pub mod paginators;
+pub mod play_history_table;
+pub mod posted_boards_table;
 pub mod settings_table;
+pub mod sound_requests_table;
+pub mod sound_restrictions_table;
+pub mod user_prefs_table;
 
+pub use api_keys_table::{ApiKeyRow, ApiKeysTable, ApiScope};
+pub use api_usage_table::{usage_day, ApiRequestOutcome, ApiUsageTable};
 pub use audio_table::{AudioTable, AudioTableRow, AudioTableRowInsert, Tags, UniqueAudioTableCol};
+pub use banned_sounds_table::BannedSoundsTable;
+pub use banned_users_table::BannedUsersTable;
+pub use birthdays_table::{BirthdayRow, BirthdaysTable};
+pub use board_order_table::BoardOrderTable;
+pub use board_themes_table::{BoardThemeRow, BoardThemesTable};
+pub use bot_messages_table::BotMessagesTable;
+pub use command_stats_table::{CommandStatsRowInsert, CommandStatsTable};
+pub use feature_flags_table::FeatureFlagsTable;
+pub use jobs_table::{JobRow, JobRowInsert, JobsTable};
+pub use library_ops_table::{LibraryOpRowInsert, LibraryOpsTable};
 pub use paginators::AudioTablePaginator;
+pub use play_history_table::{PlayHistoryRow, PlayHistoryRowInsert, PlayHistoryTable};
+pub use posted_boards_table::{PostedBoardRow, PostedBoardsTable};
 pub use settings_table::SettingsTable;
+pub use sound_requests_table::{
+    SoundRequestRow, SoundRequestRowInsert, SoundRequestStatus, SoundRequestsTable,
+};
+pub use sound_restrictions_table::{SoundRestrictionRow, SoundRestrictionsTable};
+pub use user_prefs_table::{BoardLayout, UserPrefs, UserPrefsTable};
 
-pub type DbConnection = r2d2::PooledConnection<r2d2_sqlite::SqliteConnectionManager>;
+use r2d2_sqlite::SqliteConnectionManager;
+
+use crate::common::LogResult;
+use crate::config::Config;
+
+pub type DbConnection = r2d2::PooledConnection<SqliteConnectionManager>;
+pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
+
+/// Connection pool of the database file. Connections journal to a write ahead log, so reads don't
+/// block on writes, and wait for locks held by other connections instead of failing right away
+pub fn create_pool(config: &Config) -> Result<DbPool, r2d2::Error> {
+    let busy_timeout = config.db_busy_timeout;
+    let manager = SqliteConnectionManager::file(&config.sqlite_db_file).with_init(move |conn| {
+        conn.busy_timeout(busy_timeout)?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        conn.pragma_update(None, "foreign_keys", true)
+    });
+
+    r2d2::Pool::builder()
+        .max_size(config.db_pool_size)
+        .connection_timeout(config.db_connection_timeout)
+        .build(manager)
+}
 
 pub trait Table {
     fn connection(&self) -> &DbConnection;
     fn create_table(&self);
 }
+
+/// Adds column to an existing table if it's missing. Used to migrate tables created by older versions,
+/// since `CREATE TABLE IF NOT EXISTS` won't alter a table that already exists.
+pub fn add_column_if_missing(
+    conn: &DbConnection,
+    table_name: &str,
+    column_name: &str,
+    column_def: &str,
+) {
+    let sql = format!("SELECT COUNT(*) FROM pragma_table_info('{table_name}') WHERE name = ?");
+    let exists: i64 = conn
+        .query_row(sql.as_str(), [column_name], |row| row.get(0))
+        .log_err_msg(format!(
+            "Failed checking for column {table_name}.{column_name}"
+        ))
+        .unwrap_or(0);
+
+    if exists == 0 {
+        log::info!("Adding missing column {table_name}.{column_name}");
+        let sql = format!("ALTER TABLE {table_name} ADD COLUMN {column_name} {column_def}");
+        conn.execute(sql.as_str(), ())
+            .log_err_msg(format!("Failed adding column {table_name}.{column_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn create_pool_test() {
+        let dir = std::env::temp_dir().join(crate::helpers::uuid_v4_str());
+        std::fs::create_dir(&dir).unwrap();
+        let config = Config {
+            sqlite_db_file: dir.join("bot.db3"),
+            db_pool_size: 2,
+            ..Config::default()
+        };
+
+        let db_pool = create_pool(&config).unwrap();
+        assert_eq!(db_pool.max_size(), 2);
+
+        let conn = db_pool.get().unwrap();
+        let journal_mode: String = conn
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        let foreign_keys: bool = conn
+            .pragma_query_value(None, "foreign_keys", |row| row.get(0))
+            .unwrap();
+        assert!(foreign_keys);
+
+        drop(conn);
+        drop(db_pool);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
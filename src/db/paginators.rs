@@ -1,6 +1,6 @@
 use super::{
     audio_table::{AudioTableOrderBy, AudioTableRow},
-    AudioTable, DbConnection,
+    AudioTable, BoardLayout, BoardOrderTable, DbConnection, UserPrefsTable,
 };
 
 #[derive(Debug)]
@@ -9,6 +9,8 @@ pub struct AudioTablePaginator {
     order_by: AudioTableOrderBy,
     page_limit: u64,
     offset: u64,
+    favorites_of: Option<u64>,
+    board_order: Option<(BoardLayout, u64)>,
 }
 
 impl AudioTablePaginator {
@@ -22,10 +24,27 @@ impl AudioTablePaginator {
         let order_by = self.order_by.col_name();
         let page_limit = self.page_limit;
         let offset = self.offset;
+        let where_clause = match self.favorites_of {
+            Some(user_id) => format!(
+                "WHERE {table_name}.id IN (SELECT audio_id FROM {} WHERE user_id = {user_id})",
+                UserPrefsTable::FAVORITES_TABLE_NAME
+            ),
+            None => "".into(),
+        };
+        // manually ordered sounds go first
+        let (join_clause, board_order_by) = match self.board_order {
+            Some((board, user_id)) => (
+                BoardOrderTable::join_clause(board, user_id),
+                format!("{}, ", BoardOrderTable::order_by_clause()),
+            ),
+            None => ("".into(), "".into()),
+        };
 
         let sql = format!(
-            "SELECT * FROM {table_name}
-            ORDER BY {order_by}
+            "SELECT {table_name}.* FROM {table_name}
+            {join_clause}
+            {where_clause}
+            ORDER BY {board_order_by}{table_name}.{order_by}
             LIMIT {page_limit}
             OFFSET {offset};"
         );
@@ -56,6 +75,8 @@ pub struct AudioTablePaginatorBuilder {
     conn: DbConnection,
     order_by: AudioTableOrderBy,
     page_limit: u64,
+    favorites_of: Option<u64>,
+    board_order: Option<(BoardLayout, u64)>,
 }
 
 impl AudioTablePaginatorBuilder {
@@ -64,6 +85,8 @@ impl AudioTablePaginatorBuilder {
             conn: conn,
             order_by: AudioTableOrderBy::Id,
             page_limit: 500,
+            favorites_of: None,
+            board_order: None,
         }
     }
 
@@ -78,12 +101,26 @@ impl AudioTablePaginatorBuilder {
         self
     }
 
+    /// Only paginate sounds user marked as favorite
+    pub fn favorites_of(mut self, user_id: u64) -> Self {
+        self.favorites_of = Some(user_id);
+        self
+    }
+
+    /// Paginate sounds in the order of user's board, as set by `/board reorder`
+    pub fn board_order(mut self, board: BoardLayout, user_id: u64) -> Self {
+        self.board_order = Some((board, user_id));
+        self
+    }
+
     pub fn build(self) -> AudioTablePaginator {
         AudioTablePaginator {
             conn: self.conn,
             order_by: self.order_by,
             page_limit: self.page_limit,
             offset: 0,
+            favorites_of: self.favorites_of,
+            board_order: self.board_order,
         }
     }
 }
@@ -134,6 +171,9 @@ mod tests {
             created_at: chrono::Utc::now(),
             author_id: None,
             author_name: None,
+            file_hash: None,
+            file_size: None,
+            file_modified_at: None,
         }
     }
 
@@ -163,4 +203,57 @@ mod tests {
         let page = paginator.next();
         assert!(page.is_none());
     }
+
+    #[test]
+    fn audio_table_favorites_pagination_test() {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        let table = AudioTable::new(db_pool.get().unwrap());
+        table.create_table();
+        let prefs_table = UserPrefsTable::new(db_pool.get().unwrap());
+        prefs_table.create_table();
+
+        for _ in 0..3 {
+            table
+                .insert_audio_row(make_audio_table_row_insert())
+                .unwrap();
+        }
+        prefs_table.toggle_favorite(7, 2).unwrap();
+
+        let mut paginator = AudioTablePaginator::builder(db_pool.get().unwrap())
+            .favorites_of(7)
+            .build();
+
+        let page = paginator.next().unwrap().unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].id, 2);
+        assert!(paginator.next().is_none());
+    }
+
+    #[test]
+    fn audio_table_board_order_pagination_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let table = AudioTable::new(db_pool.get().unwrap());
+        table.create_table();
+        let order_table = BoardOrderTable::new(db_pool.get().unwrap());
+        order_table.create_table();
+
+        for _ in 0..3 {
+            table
+                .insert_audio_row(make_audio_table_row_insert())
+                .unwrap();
+        }
+        order_table
+            .set_order(BoardLayout::Full, 7, &[3, 1])
+            .unwrap();
+
+        let page = AudioTablePaginator::builder(db_pool.get().unwrap())
+            .board_order(BoardLayout::Full, 7)
+            .build()
+            .next()
+            .unwrap()
+            .unwrap();
+        let ids: Vec<_> = page.iter().map(|row| row.id).collect();
+        assert_eq!(ids, vec![3, 1, 2]);
+    }
 }
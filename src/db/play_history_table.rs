@@ -0,0 +1,757 @@
+use std::collections::HashMap;
+
+use rusqlite::OptionalExtension;
+
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{add_column_if_missing, DbConnection, Table};
+
+pub struct PlayHistoryRowInsert {
+    pub guild_id: u64,
+    /// None when track wasn't played on behalf of a user (ie: join/leave audio)
+    pub user_id: Option<u64>,
+    /// Text or voice channel sound was played from. None for plays recorded before channels were
+    /// tracked
+    pub channel_id: Option<u64>,
+    pub audio_id: Option<i64>,
+    pub audio_name: String,
+    pub played_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayHistoryRow {
+    pub id: i64,
+    pub guild_id: u64,
+    pub user_id: Option<u64>,
+    pub audio_id: Option<i64>,
+    pub audio_name: String,
+    pub played_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for PlayHistoryRow {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            guild_id: row.get("guild_id")?,
+            user_id: row.get("user_id")?,
+            audio_id: row.get("audio_id")?,
+            audio_name: row.get("audio_name")?,
+            played_at: row.get("played_at")?,
+        })
+    }
+}
+
+/// Plays of a sound in a guild
+#[derive(Debug, Default, PartialEq)]
+pub struct SoundPlayStats {
+    pub total: u64,
+    /// Plays since the time stats were requested for
+    pub recent: u64,
+    /// User ids & play counts of users who played the sound most, most plays first
+    pub top_players: Vec<(u64, u64)>,
+}
+
+pub struct PlayHistoryTable {
+    conn: DbConnection,
+}
+
+impl PlayHistoryTable {
+    pub const TABLE_NAME: &'static str = "play_history";
+    /// Daily play counts per sound, of plays older than the guild's play history retention
+    pub const ROLLUP_TABLE_NAME: &'static str = "play_history_daily";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    pub fn insert_row(&self, row: &PlayHistoryRowInsert) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (guild_id, user_id, channel_id, audio_id, audio_name, played_at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6)
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (
+                    &row.guild_id,
+                    &row.user_id,
+                    &row.channel_id,
+                    &row.audio_id,
+                    &row.audio_name,
+                    &row.played_at,
+                ),
+            )
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Number of tracks user played in guild since given time
+    pub fn count_user_plays_since(
+        &self,
+        guild_id: u64,
+        user_id: u64,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u32, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "SELECT COUNT(*) FROM {table_name} WHERE guild_id = ?1 AND user_id = ?2 AND played_at >= ?3"
+        );
+
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), (guild_id, user_id, since), |row| row.get(0))
+            .log_err_msg(format!("Failed counting plays in {table_name}"))?)
+    }
+
+    /// Number of tracks played in every guild since given time
+    pub fn count_plays_since(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<u64, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT COUNT(*) FROM {table_name} WHERE played_at >= ?1");
+
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), [since], |row| row.get(0))
+            .log_err_msg(format!("Failed counting plays in {table_name}"))?)
+    }
+
+    /// Ids of the sounds played most in every guild since given time, most played first
+    pub fn most_played_since(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+        limit: u32,
+    ) -> Result<Vec<i64>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            SELECT audio_id FROM {table_name}
+            WHERE played_at >= ?1 AND audio_id IS NOT NULL
+            GROUP BY audio_id
+            ORDER BY COUNT(*) DESC, audio_id
+            LIMIT ?2
+            "
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing most played of {table_name}"))?;
+
+        let ids = stmt
+            .query_map((since, limit), |row| row.get(0))
+            .log_err_msg(format!("Failed selecting most played of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading most played of {table_name}"))?;
+
+        Ok(ids)
+    }
+
+    /// When sound was last played in guild, if ever. Rolled up plays aren't included
+    pub fn last_played_at(
+        &self,
+        guild_id: u64,
+        audio_id: i64,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "SELECT MAX(played_at) FROM {table_name} WHERE guild_id = ?1 AND audio_id = ?2"
+        );
+
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), (guild_id, audio_id), |row| row.get(0))
+            .log_err_msg(format!("Failed getting last play of {table_name}"))?)
+    }
+
+    /// Id of the sound played last in guild, if any. Plays of since deleted sounds are included
+    pub fn last_played_audio_id(&self, guild_id: u64) -> Result<Option<i64>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            SELECT audio_id FROM {table_name}
+            WHERE guild_id = ?1 AND audio_id IS NOT NULL
+            ORDER BY played_at DESC, id DESC
+            LIMIT 1
+            "
+        );
+
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), [guild_id], |row| row.get(0))
+            .optional()
+            .log_err_msg(format!("Failed getting last play of {table_name}"))?)
+    }
+
+    /// Total & recent plays of sound in guild, and the users who played it most. Top players only
+    /// count plays that weren't rolled up yet
+    pub fn sound_stats(
+        &self,
+        guild_id: u64,
+        audio_id: i64,
+        since: chrono::DateTime<chrono::Utc>,
+        top_players: u32,
+    ) -> Result<SoundPlayStats, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "SELECT COUNT(*), COALESCE(SUM(played_at >= ?3), 0) FROM {table_name} WHERE guild_id = ?1 AND audio_id = ?2"
+        );
+
+        let (total, recent): (u64, u64) = self
+            .conn
+            .query_row(sql.as_str(), (guild_id, audio_id, since), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .log_err_msg(format!("Failed counting sound plays in {table_name}"))?;
+
+        let rollup_table_name = Self::ROLLUP_TABLE_NAME;
+        let sql = format!(
+            "SELECT COALESCE(SUM(plays), 0), COALESCE(SUM(CASE WHEN day >= ?3 THEN plays ELSE 0 END), 0) FROM {rollup_table_name} WHERE guild_id = ?1 AND audio_id = ?2"
+        );
+
+        let (rolled_up_total, rolled_up_recent): (u64, u64) = self
+            .conn
+            .query_row(sql.as_str(), (guild_id, audio_id, since), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .log_err_msg(format!(
+                "Failed counting sound plays in {rollup_table_name}"
+            ))?;
+
+        let sql = format!(
+            "
+            SELECT user_id, COUNT(*) AS plays FROM {table_name}
+            WHERE guild_id = ?1 AND audio_id = ?2 AND user_id IS NOT NULL
+            GROUP BY user_id
+            ORDER BY plays DESC, user_id
+            LIMIT ?3
+            "
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing top players of {table_name}"))?;
+
+        let top_players = stmt
+            .query_map((guild_id, audio_id, top_players), |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .log_err_msg(format!("Failed selecting top players of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading top players of {table_name}"))?;
+
+        Ok(SoundPlayStats {
+            total: total + rolled_up_total,
+            recent: recent + rolled_up_recent,
+            top_players,
+        })
+    }
+
+    /// Play counts of each sound by each user in guild, as `(user_id, audio_id, plays)`. Rolled
+    /// up plays aren't included, since who played them is lost
+    pub fn user_sound_plays(&self, guild_id: u64) -> Result<Vec<(u64, i64, u64)>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            SELECT user_id, audio_id, COUNT(*) FROM {table_name}
+            WHERE guild_id = ?1 AND user_id IS NOT NULL AND audio_id IS NOT NULL
+            GROUP BY user_id, audio_id
+            "
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing user plays of {table_name}"))?;
+
+        let plays = stmt
+            .query_map([guild_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .log_err_msg(format!("Failed selecting user plays of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading user plays of {table_name}"))?;
+
+        Ok(plays)
+    }
+
+    /// Times of plays in guild since given time, oldest first. Rolled up plays aren't included
+    pub fn played_at_since(
+        &self,
+        guild_id: u64,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<chrono::DateTime<chrono::Utc>>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "SELECT played_at FROM {table_name} WHERE guild_id = ?1 AND played_at >= ?2 ORDER BY played_at"
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let played_at = stmt
+            .query_map((guild_id, since), |row| row.get(0))
+            .log_err_msg(format!("Failed selecting play times of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading play times of {table_name}"))?;
+
+        Ok(played_at)
+    }
+
+    /// Times of plays from channel since given time, oldest first
+    pub fn channel_played_at_since(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<chrono::DateTime<chrono::Utc>>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "SELECT played_at FROM {table_name} WHERE guild_id = ?1 AND channel_id = ?2 AND played_at >= ?3 ORDER BY played_at"
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let played_at = stmt
+            .query_map((guild_id, channel_id, since), |row| row.get(0))
+            .log_err_msg(format!(
+                "Failed selecting channel play times of {table_name}"
+            ))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading channel play times of {table_name}"))?;
+
+        Ok(played_at)
+    }
+
+    /// Guilds with plays that weren't rolled up
+    pub fn guild_ids(&self) -> Result<Vec<u64>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT DISTINCT guild_id FROM {table_name}");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let guild_ids = stmt
+            .query_map((), |row| row.get(0))
+            .log_err_msg(format!("Failed selecting guild ids of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading guild ids of {table_name}"))?;
+
+        Ok(guild_ids)
+    }
+
+    /// Fold guild's plays before given time into daily play counts per sound, and delete them.
+    /// Sounds keep their play counts, but who played them and when in the day is lost.
+    /// Returns number of plays rolled up
+    pub fn roll_up(
+        &self,
+        guild_id: u64,
+        before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<usize, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let rollup_table_name = Self::ROLLUP_TABLE_NAME;
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .log_err_msg(format!("Failed starting roll up of {table_name}"))?;
+
+        let sql = format!(
+            "SELECT audio_id, audio_name, played_at FROM {table_name} WHERE guild_id = ?1 AND played_at < ?2"
+        );
+        let mut plays: HashMap<(chrono::NaiveDate, Option<i64>, String), u64> = HashMap::new();
+        {
+            let mut stmt = tx
+                .prepare(sql.as_str())
+                .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+            let rows = stmt
+                .query_map((guild_id, before), |row| {
+                    Ok((
+                        row.get::<_, Option<i64>>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, chrono::DateTime<chrono::Utc>>(2)?,
+                    ))
+                })
+                .log_err_msg(format!("Failed selecting rows of {table_name}"))?;
+
+            for row in rows {
+                let (audio_id, audio_name, played_at) =
+                    row.log_err_msg(format!("Failed reading row of {table_name}"))?;
+                *plays
+                    .entry((played_at.date_naive(), audio_id, audio_name))
+                    .or_default() += 1;
+            }
+        }
+
+        let update_sql = format!(
+            "
+            UPDATE {rollup_table_name} SET plays = plays + ?1
+            WHERE guild_id = ?2 AND day = ?3 AND audio_id IS ?4 AND audio_name = ?5
+            "
+        );
+        let insert_sql = format!(
+            "
+            INSERT INTO {rollup_table_name}
+                (plays, guild_id, day, audio_id, audio_name)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5)
+            "
+        );
+        for ((day, audio_id, audio_name), count) in plays {
+            let params = (
+                count,
+                guild_id,
+                day.and_time(chrono::NaiveTime::MIN).and_utc(),
+                audio_id,
+                audio_name,
+            );
+            let updated = tx
+                .execute(update_sql.as_str(), params.clone())
+                .log_err_msg(format!("Failed updating row of {rollup_table_name}"))?;
+            if updated == 0 {
+                tx.execute(insert_sql.as_str(), params)
+                    .log_err_msg(format!("Failed inserting row into {rollup_table_name}"))?;
+            }
+        }
+
+        let sql = format!("DELETE FROM {table_name} WHERE guild_id = ?1 AND played_at < ?2");
+        let deleted = tx
+            .execute(sql.as_str(), (guild_id, before))
+            .log_err_msg(format!("Failed deleting rows from {table_name}"))?;
+
+        tx.commit()
+            .log_err_msg(format!("Failed committing roll up of {table_name}"))?;
+
+        Ok(deleted)
+    }
+
+    /// All plays, oldest first. Only plays of guild when guild_id is given
+    pub fn rows(&self, guild_id: Option<u64>) -> Result<Vec<PlayHistoryRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "SELECT * FROM {table_name} WHERE ?1 IS NULL OR guild_id = ?1 ORDER BY played_at, id"
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map([guild_id], |row| PlayHistoryRow::try_from(row))
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+}
+
+impl Table for PlayHistoryTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        let rollup_table_name = Self::ROLLUP_TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY,
+                guild_id INTEGER NOT NULL,
+                user_id INTEGER,
+                channel_id INTEGER,
+                audio_id INTEGER,
+                audio_name VARCHAR(80) NOT NULL,
+                played_at VARCHAR(40) NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS {table_name}_guild_user_idx
+                ON {table_name} (guild_id, user_id, played_at);
+
+            CREATE INDEX IF NOT EXISTS {table_name}_guild_audio_idx
+                ON {table_name} (guild_id, audio_id);
+
+            CREATE TABLE IF NOT EXISTS {rollup_table_name} (
+                id INTEGER PRIMARY KEY,
+                guild_id INTEGER NOT NULL,
+                day VARCHAR(40) NOT NULL,
+                audio_id INTEGER,
+                audio_name VARCHAR(80) NOT NULL,
+                plays INTEGER NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS {rollup_table_name}_guild_audio_idx
+                ON {rollup_table_name} (guild_id, audio_id, day);
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+
+        // columns added after the initial release
+        add_column_if_missing(&self.conn, table_name, "channel_id", "INTEGER");
+
+        let sql = format!(
+            "CREATE INDEX IF NOT EXISTS {table_name}_guild_channel_idx
+                ON {table_name} (guild_id, channel_id, played_at);"
+        );
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg(format!("Failed creating index of {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_play_history_table() -> PlayHistoryTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        PlayHistoryTable::new(db_pool.get().unwrap())
+    }
+
+    fn make_row(
+        user_id: Option<u64>,
+        played_at: chrono::DateTime<chrono::Utc>,
+    ) -> PlayHistoryRowInsert {
+        PlayHistoryRowInsert {
+            guild_id: 1,
+            user_id,
+            channel_id: Some(9),
+            audio_id: Some(1),
+            audio_name: "airhorn".into(),
+            played_at,
+        }
+    }
+
+    #[test]
+    fn count_user_plays_since_test() {
+        let table = get_play_history_table();
+        table.create_table();
+        table.create_table();
+
+        let now = chrono::Utc::now();
+        table.insert_row(&make_row(Some(2), now)).unwrap();
+        table.insert_row(&make_row(Some(2), now)).unwrap();
+        table
+            .insert_row(&make_row(Some(2), now - chrono::Duration::days(2)))
+            .unwrap();
+        table.insert_row(&make_row(None, now)).unwrap();
+
+        let since = now - chrono::Duration::days(1);
+        assert_eq!(table.count_user_plays_since(1, 2, since).unwrap(), 2);
+        assert_eq!(table.count_user_plays_since(1, 3, since).unwrap(), 0);
+        assert_eq!(table.count_plays_since(since).unwrap(), 3);
+    }
+
+    #[test]
+    fn last_played_at_test() {
+        let table = get_play_history_table();
+        table.create_table();
+        assert_eq!(table.last_played_at(1, 1).unwrap(), None);
+
+        let now = chrono::Utc::now();
+        table
+            .insert_row(&make_row(Some(2), now - chrono::Duration::hours(1)))
+            .unwrap();
+        table.insert_row(&make_row(Some(2), now)).unwrap();
+
+        assert_eq!(table.last_played_at(1, 1).unwrap(), Some(now));
+        assert_eq!(table.last_played_at(2, 1).unwrap(), None);
+    }
+
+    #[test]
+    fn last_played_audio_id_test() {
+        let table = get_play_history_table();
+        table.create_table();
+        assert_eq!(table.last_played_audio_id(1).unwrap(), None);
+
+        let now = chrono::Utc::now();
+        table.insert_row(&make_row(Some(2), now)).unwrap();
+        table
+            .insert_row(&PlayHistoryRowInsert {
+                audio_id: Some(5),
+                ..make_row(None, now - chrono::Duration::hours(1))
+            })
+            .unwrap();
+        table
+            .insert_row(&PlayHistoryRowInsert {
+                audio_id: None,
+                ..make_row(None, now)
+            })
+            .unwrap();
+
+        assert_eq!(table.last_played_audio_id(1).unwrap(), Some(1));
+        assert_eq!(table.last_played_audio_id(2).unwrap(), None);
+
+        let since = now - chrono::Duration::days(1);
+        assert_eq!(table.most_played_since(since, 10).unwrap(), vec![1, 5]);
+        assert_eq!(table.most_played_since(since, 1).unwrap(), vec![1]);
+        assert!(table
+            .most_played_since(now + chrono::Duration::seconds(1), 10)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn rows_test() {
+        let table = get_play_history_table();
+        table.create_table();
+
+        let now = chrono::Utc::now();
+        table.insert_row(&make_row(Some(2), now)).unwrap();
+        table
+            .insert_row(&make_row(None, now - chrono::Duration::days(2)))
+            .unwrap();
+        table
+            .insert_row(&PlayHistoryRowInsert {
+                guild_id: 5,
+                ..make_row(Some(2), now)
+            })
+            .unwrap();
+
+        let rows = table.rows(Some(1)).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].user_id, None);
+        assert_eq!(rows[1].played_at, now);
+        assert_eq!(table.rows(None).unwrap().len(), 3);
+
+        let since = now - chrono::Duration::days(1);
+        assert_eq!(table.played_at_since(1, since).unwrap(), vec![now]);
+        assert!(table.played_at_since(7, since).unwrap().is_empty());
+        assert_eq!(
+            table.channel_played_at_since(1, 9, since).unwrap(),
+            vec![now]
+        );
+        assert!(table
+            .channel_played_at_since(1, 8, since)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn sound_stats_test() {
+        let table = get_play_history_table();
+        table.create_table();
+
+        let now = chrono::Utc::now();
+        let old = now - chrono::Duration::days(10);
+        for (user_id, played_at) in [
+            (Some(2), now),
+            (Some(2), old),
+            (Some(3), now),
+            (Some(4), old),
+            (Some(4), old),
+            (Some(4), old),
+            (None, now),
+        ] {
+            table.insert_row(&make_row(user_id, played_at)).unwrap();
+        }
+        table
+            .insert_row(&PlayHistoryRowInsert {
+                audio_id: Some(2),
+                ..make_row(Some(3), now)
+            })
+            .unwrap();
+
+        let stats = table
+            .sound_stats(1, 1, now - chrono::Duration::days(7), 2)
+            .unwrap();
+        assert_eq!(
+            stats,
+            SoundPlayStats {
+                total: 7,
+                recent: 3,
+                top_players: vec![(4, 3), (2, 2)],
+            }
+        );
+        assert_eq!(
+            table.sound_stats(1, 3, now, 3).unwrap(),
+            SoundPlayStats::default()
+        );
+
+        let mut plays = table.user_sound_plays(1).unwrap();
+        plays.sort();
+        assert_eq!(plays, vec![(2, 1, 2), (3, 1, 1), (3, 2, 1), (4, 1, 3)]);
+        assert!(table.user_sound_plays(2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn roll_up_test() {
+        let table = get_play_history_table();
+        table.create_table();
+
+        let now = chrono::Utc::now();
+        let old = now - chrono::Duration::days(40);
+        for (user_id, played_at) in [
+            (Some(2), now),
+            (Some(2), old),
+            (Some(3), old),
+            (None, old - chrono::Duration::days(1)),
+        ] {
+            table.insert_row(&make_row(user_id, played_at)).unwrap();
+        }
+        table
+            .insert_row(&PlayHistoryRowInsert {
+                guild_id: 5,
+                ..make_row(Some(3), old)
+            })
+            .unwrap();
+        assert_eq!(table.guild_ids().unwrap().len(), 2);
+
+        let since = now - chrono::Duration::days(7);
+        let before = table.sound_stats(1, 1, since, 3).unwrap();
+        let cutoff = now - chrono::Duration::days(30);
+        assert_eq!(table.roll_up(1, cutoff).unwrap(), 3);
+        assert_eq!(table.roll_up(1, cutoff).unwrap(), 0);
+
+        let after = table.sound_stats(1, 1, since, 3).unwrap();
+        assert_eq!(after.total, before.total);
+        assert_eq!(after.recent, before.recent);
+        assert_eq!(after.top_players, vec![(2, 1)]);
+        assert_eq!(table.rows(Some(1)).unwrap().len(), 1);
+        assert_eq!(table.rows(Some(5)).unwrap().len(), 1);
+
+        // later roll ups add to existing days
+        table.insert_row(&make_row(Some(4), old)).unwrap();
+        assert_eq!(table.roll_up(1, cutoff).unwrap(), 1);
+        assert_eq!(table.sound_stats(1, 1, since, 3).unwrap().total, 5);
+
+        let days: u32 = table
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM play_history_daily WHERE guild_id = 1",
+                (),
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(days, 2);
+    }
+}
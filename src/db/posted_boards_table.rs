@@ -0,0 +1,237 @@
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{DbConnection, Table};
+
+/// Soundboard posted to a channel to stay, checked & refreshed when the bot starts
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostedBoardRow {
+    pub id: i64,
+    pub guild_id: u64,
+    pub channel_id: u64,
+    /// Member who posted the board. Its layout follows their preferences
+    pub user_id: u64,
+    /// Messages of the board, in order
+    pub message_ids: Vec<u64>,
+    /// Restricted sounds left off the board, since the member couldn't play them
+    pub hidden_ids: Vec<i64>,
+    pub posted_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for PostedBoardRow {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            guild_id: row.get("guild_id")?,
+            channel_id: row.get("channel_id")?,
+            user_id: row.get("user_id")?,
+            message_ids: split_ids(&row.get::<_, String>("message_ids")?),
+            hidden_ids: split_ids(&row.get::<_, String>("hidden_ids")?),
+            posted_at: row.get("posted_at")?,
+        })
+    }
+}
+
+fn join_ids<T: ToString>(ids: &[T]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn split_ids<T: std::str::FromStr>(ids: &str) -> Vec<T> {
+    ids.split(',').filter_map(|id| id.parse().ok()).collect()
+}
+
+/// Soundboards posted without a time to live, so they can be brought back after a restart
+pub struct PostedBoardsTable {
+    conn: DbConnection,
+}
+
+impl PostedBoardsTable {
+    pub const TABLE_NAME: &'static str = "posted_boards";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    pub fn insert_board(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        user_id: u64,
+        message_ids: &[u64],
+        hidden_ids: &[i64],
+        posted_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (guild_id, channel_id, user_id, message_ids, hidden_ids, posted_at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6)
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (
+                    guild_id,
+                    channel_id,
+                    user_id,
+                    join_ids(message_ids),
+                    join_ids(hidden_ids),
+                    posted_at,
+                ),
+            )
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Messages of board, once it's posted again
+    pub fn update_message_ids(
+        &self,
+        id: i64,
+        message_ids: &[u64],
+        posted_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("UPDATE {table_name} SET message_ids = ?2, posted_at = ?3 WHERE id = ?1");
+
+        self.conn
+            .execute(sql.as_str(), (id, join_ids(message_ids), posted_at))
+            .log_err_msg(format!("Failed updating row of {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Stop tracking board, once its messages are gone
+    pub fn delete_board(&self, id: i64) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE id = ?1");
+
+        self.conn
+            .execute(sql.as_str(), [id])
+            .log_err_msg(format!("Failed deleting row from {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Stop tracking boards with message, once it's deleted. Returns number of boards forgotten
+    pub fn delete_boards_with_message(&self, message_id: u64) -> Result<usize, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "DELETE FROM {table_name} WHERE ',' || message_ids || ',' LIKE '%,' || ?1 || ',%'"
+        );
+
+        let deleted = self
+            .conn
+            .execute(sql.as_str(), [message_id])
+            .log_err_msg(format!("Failed deleting rows from {table_name}"))?;
+
+        Ok(deleted)
+    }
+
+    /// Every posted board, oldest first
+    pub fn rows(&self) -> Result<Vec<PostedBoardRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT * FROM {table_name} ORDER BY id");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map([], |row| PostedBoardRow::try_from(row))
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+}
+
+impl Table for PostedBoardsTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                guild_id INTEGER NOT NULL,
+                channel_id INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                message_ids TEXT NOT NULL,
+                hidden_ids TEXT NOT NULL DEFAULT '',
+                posted_at VARCHAR(40) NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS {table_name}_guild_idx
+                ON {table_name} (guild_id);
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    #[test]
+    fn posted_boards_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let table = PostedBoardsTable::new(db_pool.get().unwrap());
+        table.create_table();
+        table.create_table();
+
+        let now = chrono::Utc::now();
+        table.insert_board(1, 2, 3, &[10, 11], &[], now).unwrap();
+        table.insert_board(1, 4, 3, &[12], &[7, 8], now).unwrap();
+
+        let rows = table.rows().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0],
+            PostedBoardRow {
+                id: rows[0].id,
+                guild_id: 1,
+                channel_id: 2,
+                user_id: 3,
+                message_ids: vec![10, 11],
+                hidden_ids: vec![],
+                posted_at: now,
+            }
+        );
+        assert_eq!(rows[1].hidden_ids, vec![7, 8]);
+
+        table.update_message_ids(rows[0].id, &[20], now).unwrap();
+        table.delete_board(rows[1].id).unwrap();
+        let rows = table.rows().unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].message_ids, vec![20]);
+
+        table.insert_board(1, 2, 3, &[120, 121], &[], now).unwrap();
+        assert_eq!(table.delete_boards_with_message(2).unwrap(), 0);
+        assert_eq!(table.delete_boards_with_message(12).unwrap(), 0);
+        assert_eq!(table.delete_boards_with_message(121).unwrap(), 1);
+        assert_eq!(table.rows().unwrap().len(), 1);
+    }
+}
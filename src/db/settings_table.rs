@@ -27,6 +27,8 @@ pub struct SettingsTable {
 
 impl SettingsTable {
     const TABLE_NAME: &'static str = "settings";
+    pub const GUILD_TABLE_NAME: &'static str = "guild_settings";
+    pub const CHANNEL_VOLUMES_TABLE_NAME: &'static str = "channel_volumes";
 
     pub fn new(connection: DbConnection) -> Self {
         Self { conn: connection }
@@ -101,6 +103,149 @@ impl SettingsTable {
 
         Ok(())
     }
+
+    pub fn get_guild_setting(
+        &self,
+        guild_id: u64,
+        key: &str,
+    ) -> Result<Option<String>, PoiseError> {
+        let table_name = Self::GUILD_TABLE_NAME;
+        let sql = format!("SELECT value FROM {table_name} WHERE guild_id = ?1 AND key = ?2");
+
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), (guild_id, key), |row| row.get(0))
+            .optional()
+            .log_err_msg(format!("Failed getting guild setting {key}"))?)
+    }
+
+    pub fn set_guild_setting(
+        &self,
+        guild_id: u64,
+        key: &str,
+        value: &str,
+    ) -> Result<(), PoiseError> {
+        log::info!("Saving guild setting. guild_id: {guild_id}, key: {key}, value: {value}");
+
+        let table_name = Self::GUILD_TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (guild_id, key, value)
+            VALUES
+                (?1, ?2, ?3)
+            ON CONFLICT (guild_id, key) DO UPDATE SET value = excluded.value
+            "
+        );
+
+        self.conn
+            .execute(sql.as_str(), (guild_id, key, value))
+            .log_err_msg(format!("Failed saving guild setting {key}"))?;
+
+        Ok(())
+    }
+
+    pub fn delete_guild_setting(&self, guild_id: u64, key: &str) -> Result<(), PoiseError> {
+        log::info!("Deleting guild setting. guild_id: {guild_id}, key: {key}");
+
+        let table_name = Self::GUILD_TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE guild_id = ?1 AND key = ?2");
+
+        self.conn
+            .execute(sql.as_str(), (guild_id, key))
+            .log_err_msg(format!("Failed deleting guild setting {key}"))?;
+
+        Ok(())
+    }
+
+    /// All settings stored for guild, as (key, value) pairs sorted by key
+    #[allow(unused)]
+    pub fn list_guild_settings(&self, guild_id: u64) -> Result<Vec<(String, String)>, PoiseError> {
+        let table_name = Self::GUILD_TABLE_NAME;
+        let sql = format!("SELECT key, value FROM {table_name} WHERE guild_id = ?1 ORDER BY key");
+
+        let mut stmt = self.conn.prepare(sql.as_str()).log_err()?;
+        let settings = stmt
+            .query_map([guild_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .log_err()?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed listing guild settings of {table_name}"))?;
+
+        Ok(settings)
+    }
+
+    /// Volume percentage of voice channel, overriding guild's volume setting
+    pub fn get_channel_volume(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+    ) -> Result<Option<u32>, PoiseError> {
+        let table_name = Self::CHANNEL_VOLUMES_TABLE_NAME;
+        let sql =
+            format!("SELECT volume FROM {table_name} WHERE guild_id = ?1 AND channel_id = ?2");
+
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), (guild_id, channel_id), |row| row.get(0))
+            .optional()
+            .log_err_msg(format!("Failed getting volume of channel {channel_id}"))?)
+    }
+
+    pub fn set_channel_volume(
+        &self,
+        guild_id: u64,
+        channel_id: u64,
+        volume: u32,
+    ) -> Result<(), PoiseError> {
+        log::info!("Saving channel volume. guild_id: {guild_id}, channel_id: {channel_id}, volume: {volume}");
+
+        let table_name = Self::CHANNEL_VOLUMES_TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (guild_id, channel_id, volume)
+            VALUES
+                (?1, ?2, ?3)
+            ON CONFLICT (guild_id, channel_id) DO UPDATE SET volume = excluded.volume
+            "
+        );
+
+        self.conn
+            .execute(sql.as_str(), (guild_id, channel_id, volume))
+            .log_err_msg(format!("Failed saving volume of channel {channel_id}"))?;
+
+        Ok(())
+    }
+
+    pub fn delete_channel_volume(&self, guild_id: u64, channel_id: u64) -> Result<(), PoiseError> {
+        log::info!("Deleting channel volume. guild_id: {guild_id}, channel_id: {channel_id}");
+
+        let table_name = Self::CHANNEL_VOLUMES_TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE guild_id = ?1 AND channel_id = ?2");
+
+        self.conn
+            .execute(sql.as_str(), (guild_id, channel_id))
+            .log_err_msg(format!("Failed deleting volume of channel {channel_id}"))?;
+
+        Ok(())
+    }
+
+    /// Channel ids & volume percentages of guild's voice channels with their own volume
+    pub fn list_channel_volumes(&self, guild_id: u64) -> Result<Vec<(u64, u32)>, PoiseError> {
+        let table_name = Self::CHANNEL_VOLUMES_TABLE_NAME;
+        let sql = format!(
+            "SELECT channel_id, volume FROM {table_name} WHERE guild_id = ?1 ORDER BY channel_id"
+        );
+
+        let mut stmt = self.conn.prepare(sql.as_str()).log_err()?;
+        let volumes = stmt
+            .query_map([guild_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .log_err()?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed listing channel volumes of {table_name}"))?;
+
+        Ok(volumes)
+    }
 }
 
 impl Table for SettingsTable {
@@ -111,6 +256,8 @@ impl Table for SettingsTable {
     fn create_table(&self) {
         let table_name = Self::TABLE_NAME;
         log::info!("Creating table: {table_name}");
+        let guild_table_name = Self::GUILD_TABLE_NAME;
+        let channel_volumes_table_name = Self::CHANNEL_VOLUMES_TABLE_NAME;
         let sql = format!(
             "
             CREATE TABLE IF NOT EXISTS {table_name} (
@@ -118,6 +265,20 @@ impl Table for SettingsTable {
                 join_audio VARCHAR(80),
                 leave_audio VARCHAR(80)
             );
+
+            CREATE TABLE IF NOT EXISTS {guild_table_name} (
+                guild_id INTEGER NOT NULL,
+                key VARCHAR(40) NOT NULL,
+                value TEXT NOT NULL,
+                PRIMARY KEY (guild_id, key)
+            );
+
+            CREATE TABLE IF NOT EXISTS {channel_volumes_table_name} (
+                guild_id INTEGER NOT NULL,
+                channel_id INTEGER NOT NULL,
+                volume INTEGER NOT NULL,
+                PRIMARY KEY (guild_id, channel_id)
+            );
         "
         );
 
@@ -178,4 +339,59 @@ mod tests {
         assert_eq!(settings.join_audio, join_audio);
         assert_eq!(settings.leave_audio, leave_audio);
     }
+
+    #[test]
+    fn guild_settings_test() {
+        let table = get_settings_table();
+        table.create_table();
+
+        assert_eq!(table.get_guild_setting(1, "volume").unwrap(), None);
+
+        table.set_guild_setting(1, "volume", "50").unwrap();
+        table.set_guild_setting(1, "volume", "80").unwrap();
+        table.set_guild_setting(1, "locale", "en-US").unwrap();
+        table.set_guild_setting(2, "volume", "100").unwrap();
+
+        assert_eq!(
+            table.get_guild_setting(1, "volume").unwrap(),
+            Some("80".into())
+        );
+        assert_eq!(
+            table.list_guild_settings(1).unwrap(),
+            vec![
+                ("locale".to_string(), "en-US".to_string()),
+                ("volume".to_string(), "80".to_string())
+            ]
+        );
+
+        table.delete_guild_setting(1, "volume").unwrap();
+        assert_eq!(table.get_guild_setting(1, "volume").unwrap(), None);
+        assert_eq!(
+            table.get_guild_setting(2, "volume").unwrap(),
+            Some("100".into())
+        );
+    }
+
+    #[test]
+    fn channel_volumes_test() {
+        let table = get_settings_table();
+        table.create_table();
+
+        assert_eq!(table.get_channel_volume(1, 10).unwrap(), None);
+
+        table.set_channel_volume(1, 10, 50).unwrap();
+        table.set_channel_volume(1, 10, 40).unwrap();
+        table.set_channel_volume(1, 11, 150).unwrap();
+        table.set_channel_volume(2, 10, 100).unwrap();
+
+        assert_eq!(table.get_channel_volume(1, 10).unwrap(), Some(40));
+        assert_eq!(
+            table.list_channel_volumes(1).unwrap(),
+            vec![(10, 40), (11, 150)]
+        );
+
+        table.delete_channel_volume(1, 10).unwrap();
+        assert_eq!(table.get_channel_volume(1, 10).unwrap(), None);
+        assert_eq!(table.get_channel_volume(2, 10).unwrap(), Some(100));
+    }
 }
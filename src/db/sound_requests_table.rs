@@ -0,0 +1,287 @@
+use rusqlite::OptionalExtension;
+
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{DbConnection, Table};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SoundRequestStatus {
+    Pending,
+    Approved,
+    Denied,
+}
+
+impl SoundRequestStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Approved => "approved",
+            Self::Denied => "denied",
+        }
+    }
+
+    fn from_str(value: &str) -> Self {
+        match value {
+            "approved" => Self::Approved,
+            "denied" => Self::Denied,
+            _ => Self::Pending,
+        }
+    }
+}
+
+pub struct SoundRequestRowInsert {
+    pub guild_id: u64,
+    /// Channel request was made in. Requester is notified there once it's resolved
+    pub channel_id: u64,
+    pub user_id: u64,
+    /// Name for the sound once imported
+    pub name: Option<String>,
+    /// Url of audio file, or description of the sound wanted
+    pub request: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundRequestRow {
+    pub id: i64,
+    pub guild_id: u64,
+    pub channel_id: u64,
+    pub user_id: u64,
+    pub name: Option<String>,
+    pub request: String,
+    pub status: SoundRequestStatus,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub resolved_by: Option<u64>,
+}
+
+impl SoundRequestRow {
+    /// Request is a link to an audio file, which can be imported as is
+    pub fn url(&self) -> Option<&str> {
+        let request = self.request.trim();
+        match request.starts_with("https://") || request.starts_with("http://") {
+            true => Some(request),
+            false => None,
+        }
+    }
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for SoundRequestRow {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        let status: String = row.get("status")?;
+
+        Ok(Self {
+            id: row.get("id")?,
+            guild_id: row.get("guild_id")?,
+            channel_id: row.get("channel_id")?,
+            user_id: row.get("user_id")?,
+            name: row.get("name")?,
+            request: row.get("request")?,
+            status: SoundRequestStatus::from_str(&status),
+            created_at: row.get("created_at")?,
+            resolved_by: row.get("resolved_by")?,
+        })
+    }
+}
+
+/// Sounds requested by members, waiting on a moderator to approve or deny them
+pub struct SoundRequestsTable {
+    conn: DbConnection,
+}
+
+impl SoundRequestsTable {
+    pub const TABLE_NAME: &'static str = "sound_requests";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    /// Returns id of inserted request
+    pub fn insert_row(&self, row: &SoundRequestRowInsert) -> Result<i64, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (guild_id, channel_id, user_id, name, request, status, created_at)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (
+                    &row.guild_id,
+                    &row.channel_id,
+                    &row.user_id,
+                    &row.name,
+                    &row.request,
+                    SoundRequestStatus::Pending.as_str(),
+                    &row.created_at,
+                ),
+            )
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn find_row(&self, id: i64) -> Result<Option<SoundRequestRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT * FROM {table_name} WHERE id = ?1");
+
+        Ok(self
+            .conn
+            .query_row(sql.as_str(), [id], |row| SoundRequestRow::try_from(row))
+            .optional()
+            .log_err_msg(format!("Failed finding row in {table_name}"))?)
+    }
+
+    pub fn delete_row(&self, id: i64) -> Result<(), PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE id = ?1");
+
+        self.conn
+            .execute(sql.as_str(), [id])
+            .log_err_msg(format!("Failed deleting row from {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Number of requests user has waiting on moderators in guild
+    pub fn count_pending(&self, guild_id: u64, user_id: u64) -> Result<u32, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "SELECT COUNT(*) FROM {table_name} WHERE guild_id = ?1 AND user_id = ?2 AND status = ?3"
+        );
+
+        Ok(self
+            .conn
+            .query_row(
+                sql.as_str(),
+                (guild_id, user_id, SoundRequestStatus::Pending.as_str()),
+                |row| row.get(0),
+            )
+            .log_err_msg(format!("Failed counting rows in {table_name}"))?)
+    }
+
+    /// Approve or deny a pending request. Returns false when request was already resolved,
+    /// ie: two moderators pressed a button at the same time
+    pub fn resolve(
+        &self,
+        id: i64,
+        status: SoundRequestStatus,
+        resolved_by: u64,
+    ) -> Result<bool, PoiseError> {
+        log::info!("Resolving sound request {id} - {}", status.as_str());
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            UPDATE {table_name}
+            SET status = ?1, resolved_by = ?2, resolved_at = ?3
+            WHERE id = ?4 AND status = ?5
+            "
+        );
+
+        let updated = self
+            .conn
+            .execute(
+                sql.as_str(),
+                (
+                    status.as_str(),
+                    resolved_by,
+                    chrono::Utc::now(),
+                    id,
+                    SoundRequestStatus::Pending.as_str(),
+                ),
+            )
+            .log_err_msg(format!("Failed updating row of {table_name}"))?;
+
+        Ok(updated > 0)
+    }
+}
+
+impl Table for SoundRequestsTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                guild_id INTEGER NOT NULL,
+                channel_id INTEGER NOT NULL,
+                user_id INTEGER NOT NULL,
+                name VARCHAR(80),
+                request TEXT NOT NULL,
+                status VARCHAR(20) NOT NULL,
+                created_at VARCHAR(40) NOT NULL,
+                resolved_by INTEGER,
+                resolved_at VARCHAR(40)
+            );
+
+            CREATE INDEX IF NOT EXISTS {table_name}_guild_user_status
+                ON {table_name} (guild_id, user_id, status);
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_sound_requests_table() -> SoundRequestsTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        SoundRequestsTable::new(db_pool.get().unwrap())
+    }
+
+    #[test]
+    fn resolve_request_test() {
+        let table = get_sound_requests_table();
+        table.create_table();
+        table.create_table();
+
+        let id = table
+            .insert_row(&SoundRequestRowInsert {
+                guild_id: 1,
+                channel_id: 2,
+                user_id: 3,
+                name: Some("airhorn".into()),
+                request: "https://example.com/airhorn.mp3".into(),
+                created_at: chrono::Utc::now(),
+            })
+            .unwrap();
+
+        let row = table.find_row(id).unwrap().unwrap();
+        assert_eq!(row.status, SoundRequestStatus::Pending);
+        assert_eq!(row.url(), Some("https://example.com/airhorn.mp3"));
+        assert_eq!(table.count_pending(1, 3).unwrap(), 1);
+
+        assert!(table.resolve(id, SoundRequestStatus::Denied, 4).unwrap());
+        assert!(!table.resolve(id, SoundRequestStatus::Approved, 4).unwrap());
+
+        let row = table.find_row(id).unwrap().unwrap();
+        assert_eq!(row.status, SoundRequestStatus::Denied);
+        assert_eq!(row.resolved_by, Some(4));
+        assert_eq!(table.count_pending(1, 3).unwrap(), 0);
+
+        table.delete_row(id).unwrap();
+        assert_eq!(table.find_row(id).unwrap(), None);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{DbConnection, Table};
+
+/// Role or user a sound is restricted to. Restricted sounds can only be played by members
+/// matching one of their restrictions
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoundRestrictionRow {
+    pub id: i64,
+    pub guild_id: u64,
+    pub audio_id: i64,
+    pub role_id: Option<u64>,
+    pub user_id: Option<u64>,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for SoundRestrictionRow {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.get("id")?,
+            guild_id: row.get("guild_id")?,
+            audio_id: row.get("audio_id")?,
+            role_id: row.get("role_id")?,
+            user_id: row.get("user_id")?,
+        })
+    }
+}
+
+pub struct SoundRestrictionsTable {
+    conn: DbConnection,
+}
+
+impl SoundRestrictionsTable {
+    pub const TABLE_NAME: &'static str = "sound_restrictions";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    /// Restrict sound in guild to a role or user. Returns whether the restriction is new
+    pub fn insert_row(
+        &self,
+        guild_id: u64,
+        audio_id: i64,
+        role_id: Option<u64>,
+        user_id: Option<u64>,
+    ) -> Result<bool, PoiseError> {
+        log::info!(
+            "Restricting sound {audio_id} to role {role_id:?}, user {user_id:?}. guild_id: {guild_id}"
+        );
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name} (guild_id, audio_id, role_id, user_id, created_at)
+            SELECT ?1, ?2, ?3, ?4, ?5
+            WHERE NOT EXISTS (
+                SELECT 1 FROM {table_name}
+                WHERE guild_id = ?1 AND audio_id = ?2 AND role_id IS ?3 AND user_id IS ?4
+            )
+            "
+        );
+
+        let inserted = self
+            .conn
+            .execute(
+                sql.as_str(),
+                (guild_id, audio_id, role_id, user_id, chrono::Utc::now()),
+            )
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(inserted > 0)
+    }
+
+    /// Lift every restriction of sound in guild. Returns number of restrictions lifted
+    pub fn delete_rows(&self, guild_id: u64, audio_id: i64) -> Result<usize, PoiseError> {
+        log::info!("Lifting restrictions of sound {audio_id}. guild_id: {guild_id}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("DELETE FROM {table_name} WHERE guild_id = ?1 AND audio_id = ?2");
+
+        let deleted = self
+            .conn
+            .execute(sql.as_str(), (guild_id, audio_id))
+            .log_err_msg(format!("Failed deleting rows from {table_name}"))?;
+
+        Ok(deleted)
+    }
+
+    /// Restrictions of guild's sounds, grouped by sound
+    pub fn rows(&self, guild_id: u64) -> Result<Vec<SoundRestrictionRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT * FROM {table_name} WHERE guild_id = ?1 ORDER BY audio_id, id");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map([guild_id], |row| SoundRestrictionRow::try_from(row))
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+
+    /// Restrictions of a sound in guild. Empty when anyone can play it
+    pub fn sound_rows(
+        &self,
+        guild_id: u64,
+        audio_id: i64,
+    ) -> Result<Vec<SoundRestrictionRow>, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql =
+            format!("SELECT * FROM {table_name} WHERE guild_id = ?1 AND audio_id = ?2 ORDER BY id");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let rows = stmt
+            .query_map((guild_id, audio_id), |row| {
+                SoundRestrictionRow::try_from(row)
+            })
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(rows)
+    }
+}
+
+impl Table for SoundRestrictionsTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                guild_id INTEGER NOT NULL,
+                audio_id INTEGER NOT NULL,
+                role_id INTEGER,
+                user_id INTEGER,
+                created_at VARCHAR(40) NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS {table_name}_sound ON {table_name} (guild_id, audio_id);
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_sound_restrictions_table() -> SoundRestrictionsTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        SoundRestrictionsTable::new(db_pool.get().unwrap())
+    }
+
+    #[test]
+    fn sound_restrictions_test() {
+        let table = get_sound_restrictions_table();
+        table.create_table();
+        table.create_table();
+
+        assert!(table.insert_row(1, 2, Some(3), None).unwrap());
+        assert!(table.insert_row(1, 2, None, Some(4)).unwrap());
+        assert!(!table.insert_row(1, 2, Some(3), None).unwrap());
+        assert!(table.insert_row(1, 5, Some(3), None).unwrap());
+        assert!(table.insert_row(6, 2, Some(3), None).unwrap());
+
+        let rows = table.sound_rows(1, 2).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].role_id, rows[0].user_id), (Some(3), None));
+        assert_eq!((rows[1].role_id, rows[1].user_id), (None, Some(4)));
+        assert_eq!(table.rows(1).unwrap().len(), 3);
+
+        assert_eq!(table.delete_rows(1, 2).unwrap(), 2);
+        assert!(table.sound_rows(1, 2).unwrap().is_empty());
+        assert_eq!(table.rows(1).unwrap().len(), 1);
+        assert_eq!(table.rows(6).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,361 @@
+use rusqlite::OptionalExtension;
+
+use crate::{commands::PoiseError, common::LogResult};
+
+use super::{add_column_if_missing, DbConnection, Table};
+
+/// Which sounds `/sounds display` shows to the user
+#[derive(Debug, Clone, Copy, Default, PartialEq, poise::ChoiceParameter)]
+pub enum BoardLayout {
+    #[default]
+    #[name = "full"]
+    Full,
+    #[name = "favorites"]
+    Favorites,
+}
+
+impl BoardLayout {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Favorites => "favorites",
+        }
+    }
+
+    pub fn from_str(value: &str) -> Self {
+        match value {
+            "favorites" => Self::Favorites,
+            _ => Self::Full,
+        }
+    }
+}
+
+impl std::fmt::Display for BoardLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UserPrefs {
+    pub user_id: u64,
+    pub board: BoardLayout,
+    /// Buttons per soundboard message. None uses the default
+    pub page_size: Option<u32>,
+    /// Personal playback volume percentage. None uses the guild volume
+    pub volume: Option<u32>,
+    /// Direct message the name of each sound played in the user's voice channel
+    pub captions: bool,
+}
+
+impl TryFrom<&rusqlite::Row<'_>> for UserPrefs {
+    type Error = rusqlite::Error;
+
+    fn try_from(row: &rusqlite::Row<'_>) -> Result<Self, Self::Error> {
+        let board: String = row.get("board")?;
+
+        Ok(Self {
+            user_id: row.get("user_id")?,
+            board: BoardLayout::from_str(&board),
+            page_size: row.get("page_size")?,
+            volume: row.get("volume")?,
+            captions: row.get("captions")?,
+        })
+    }
+}
+
+/// Per-user preferences & favorite sounds. Preferences follow the user across guilds
+pub struct UserPrefsTable {
+    conn: DbConnection,
+}
+
+impl UserPrefsTable {
+    pub const TABLE_NAME: &'static str = "user_prefs";
+    pub const FAVORITES_TABLE_NAME: &'static str = "user_favorites";
+    pub const HOTBAR_TABLE_NAME: &'static str = "user_hotbar";
+
+    pub fn new(connection: DbConnection) -> Self {
+        Self { conn: connection }
+    }
+
+    /// User's preferences, or defaults when user hasn't saved any
+    pub fn get_prefs(&self, user_id: u64) -> Result<UserPrefs, PoiseError> {
+        let table_name = Self::TABLE_NAME;
+        let sql = format!("SELECT * FROM {table_name} WHERE user_id = ?1");
+
+        let prefs = self
+            .conn
+            .query_row(sql.as_str(), [user_id], |row| UserPrefs::try_from(row))
+            .optional()
+            .log_err_msg(format!("Failed getting row of {table_name}"))?;
+
+        Ok(prefs.unwrap_or(UserPrefs {
+            user_id,
+            ..Default::default()
+        }))
+    }
+
+    pub fn save_prefs(&self, prefs: &UserPrefs) -> Result<(), PoiseError> {
+        log::info!("Saving user prefs - {prefs:?}");
+
+        let table_name = Self::TABLE_NAME;
+        let sql = format!(
+            "
+            INSERT INTO {table_name}
+                (user_id, board, page_size, volume, captions)
+            VALUES
+                (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT (user_id) DO UPDATE SET
+                board = excluded.board,
+                page_size = excluded.page_size,
+                volume = excluded.volume,
+                captions = excluded.captions
+            "
+        );
+
+        self.conn
+            .execute(
+                sql.as_str(),
+                (
+                    prefs.user_id,
+                    prefs.board.as_str(),
+                    prefs.page_size,
+                    prefs.volume,
+                    prefs.captions,
+                ),
+            )
+            .log_err_msg(format!("Failed saving row of {table_name}"))?;
+
+        Ok(())
+    }
+
+    /// Add sound to user's favorites, or remove it if already a favorite.
+    /// Returns whether sound is now a favorite
+    pub fn toggle_favorite(&self, user_id: u64, audio_id: i64) -> Result<bool, PoiseError> {
+        let table_name = Self::FAVORITES_TABLE_NAME;
+
+        let sql = format!("DELETE FROM {table_name} WHERE user_id = ?1 AND audio_id = ?2");
+        let deleted = self
+            .conn
+            .execute(sql.as_str(), (user_id, audio_id))
+            .log_err_msg(format!("Failed deleting row from {table_name}"))?;
+
+        if deleted > 0 {
+            return Ok(false);
+        }
+
+        let sql = format!("INSERT INTO {table_name} (user_id, audio_id) VALUES (?1, ?2)");
+        self.conn
+            .execute(sql.as_str(), (user_id, audio_id))
+            .log_err_msg(format!("Failed inserting row into {table_name}"))?;
+
+        Ok(true)
+    }
+
+    /// Put sound in slot of user's hotbar, or empty the slot when audio_id is None
+    pub fn set_hotbar_slot(
+        &self,
+        user_id: u64,
+        slot: u32,
+        audio_id: Option<i64>,
+    ) -> Result<(), PoiseError> {
+        log::info!("Setting hotbar slot {slot} to {audio_id:?}. user_id: {user_id}");
+
+        let table_name = Self::HOTBAR_TABLE_NAME;
+        match audio_id {
+            Some(audio_id) => {
+                let sql = format!(
+                    "
+                    INSERT INTO {table_name} (user_id, slot, audio_id) VALUES (?1, ?2, ?3)
+                    ON CONFLICT (user_id, slot) DO UPDATE SET audio_id = excluded.audio_id
+                    "
+                );
+                self.conn
+                    .execute(sql.as_str(), (user_id, slot, audio_id))
+                    .log_err_msg(format!("Failed saving row of {table_name}"))?;
+            }
+            None => {
+                let sql = format!("DELETE FROM {table_name} WHERE user_id = ?1 AND slot = ?2");
+                self.conn
+                    .execute(sql.as_str(), (user_id, slot))
+                    .log_err_msg(format!("Failed deleting row from {table_name}"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Filled slots of user's hotbar, as `(slot, audio_id)`, in slot order
+    pub fn hotbar(&self, user_id: u64) -> Result<Vec<(u32, i64)>, PoiseError> {
+        let table_name = Self::HOTBAR_TABLE_NAME;
+        let sql =
+            format!("SELECT slot, audio_id FROM {table_name} WHERE user_id = ?1 ORDER BY slot");
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let slots = stmt
+            .query_map([user_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(slots)
+    }
+
+    /// Users of user_ids with captions on
+    pub fn captioned_users(&self, user_ids: &[u64]) -> Result<Vec<u64>, PoiseError> {
+        if user_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let table_name = Self::TABLE_NAME;
+        let placeholders = vec!["?"; user_ids.len()].join(", ");
+        let sql = format!(
+            "SELECT user_id FROM {table_name} WHERE captions = 1 AND user_id IN ({placeholders})"
+        );
+
+        let mut stmt = self
+            .conn
+            .prepare(sql.as_str())
+            .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+
+        let user_ids = stmt
+            .query_map(rusqlite::params_from_iter(user_ids), |row| row.get(0))
+            .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+            .collect::<Result<Vec<u64>, _>>()
+            .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+        Ok(user_ids)
+    }
+}
+
+impl Table for UserPrefsTable {
+    fn connection(&self) -> &DbConnection {
+        &self.conn
+    }
+
+    fn create_table(&self) {
+        let table_name = Self::TABLE_NAME;
+        let favorites_table_name = Self::FAVORITES_TABLE_NAME;
+        let hotbar_table_name = Self::HOTBAR_TABLE_NAME;
+        log::info!("Creating table: {table_name}");
+        let sql = format!(
+            "
+            CREATE TABLE IF NOT EXISTS {table_name} (
+                user_id INTEGER PRIMARY KEY,
+                board VARCHAR(20) NOT NULL,
+                page_size INTEGER,
+                volume INTEGER,
+                captions INTEGER NOT NULL DEFAULT 0
+            );
+
+            CREATE TABLE IF NOT EXISTS {favorites_table_name} (
+                user_id INTEGER NOT NULL,
+                audio_id INTEGER NOT NULL,
+                PRIMARY KEY (user_id, audio_id)
+            );
+
+            CREATE TABLE IF NOT EXISTS {hotbar_table_name} (
+                user_id INTEGER NOT NULL,
+                slot INTEGER NOT NULL,
+                audio_id INTEGER NOT NULL,
+                PRIMARY KEY (user_id, slot)
+            );
+        "
+        );
+
+        self.conn
+            .execute_batch(sql.as_str())
+            .log_err_msg("Failed create table")
+            .log_ok_msg(format!("Created table {table_name}"))
+            .unwrap();
+
+        // columns added after the initial release
+        add_column_if_missing(
+            &self.conn,
+            table_name,
+            "captions",
+            "INTEGER NOT NULL DEFAULT 0",
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    fn get_user_prefs_table() -> UserPrefsTable {
+        let db_manager = SqliteConnectionManager::memory();
+        let db_pool = r2d2::Pool::new(db_manager).unwrap();
+        UserPrefsTable::new(db_pool.get().unwrap())
+    }
+
+    #[test]
+    fn save_prefs_test() {
+        let table = get_user_prefs_table();
+        table.create_table();
+        table.create_table();
+
+        let mut prefs = table.get_prefs(1).unwrap();
+        assert_eq!(prefs.board, BoardLayout::Full);
+        assert_eq!(prefs.volume, None);
+
+        prefs.board = BoardLayout::Favorites;
+        prefs.volume = Some(50);
+        table.save_prefs(&prefs).unwrap();
+        prefs.page_size = Some(10);
+        table.save_prefs(&prefs).unwrap();
+
+        assert_eq!(table.get_prefs(1).unwrap(), prefs);
+    }
+
+    #[test]
+    fn captioned_users_test() {
+        let table = get_user_prefs_table();
+        table.create_table();
+
+        for (user_id, captions) in [(1, true), (2, false), (3, true)] {
+            let prefs = UserPrefs {
+                user_id,
+                captions,
+                ..Default::default()
+            };
+            table.save_prefs(&prefs).unwrap();
+        }
+
+        assert_eq!(table.captioned_users(&[1, 2, 4]).unwrap(), vec![1]);
+        assert!(table.captioned_users(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn toggle_favorite_test() {
+        let table = get_user_prefs_table();
+        table.create_table();
+
+        assert!(table.toggle_favorite(1, 5).unwrap());
+        assert!(!table.toggle_favorite(1, 5).unwrap());
+        assert!(table.toggle_favorite(1, 5).unwrap());
+    }
+
+    #[test]
+    fn hotbar_test() {
+        let table = get_user_prefs_table();
+        table.create_table();
+        assert!(table.hotbar(1).unwrap().is_empty());
+
+        table.set_hotbar_slot(1, 3, Some(7)).unwrap();
+        table.set_hotbar_slot(1, 1, Some(5)).unwrap();
+        table.set_hotbar_slot(2, 1, Some(9)).unwrap();
+        assert_eq!(table.hotbar(1).unwrap(), vec![(1, 5), (3, 7)]);
+
+        table.set_hotbar_slot(1, 3, Some(8)).unwrap();
+        table.set_hotbar_slot(1, 1, None).unwrap();
+        assert_eq!(table.hotbar(1).unwrap(), vec![(3, 8)]);
+        assert_eq!(table.hotbar(2).unwrap(), vec![(1, 9)]);
+    }
+}
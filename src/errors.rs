@@ -7,4 +7,194 @@ pub enum AudioError {
     AudioTrackNotFound { track: String },
     #[error("Bot not in voice channel.")]
     NotInVoiceChannel,
+    #[error("Nothing is playing.")]
+    NothingPlaying,
+    #[error("Nothing has been played here yet.")]
+    NothingToReplay,
+    #[error("Couldn't seek track - {reason}")]
+    SeekFailed { reason: String },
+    #[error("No track at queue position {position}. The queue has {len} tracks.")]
+    InvalidQueuePosition { position: usize, len: usize },
+    #[error("{max} ambience layers are already playing. Stop one first.")]
+    TooManyLayers { max: usize },
+    #[error("No ambience layer named `{layer}` is playing.")]
+    LayerNotFound { layer: String },
+    #[error("Audio file is too large ({}). The max file size is {}.", format_file_size(*size), format_file_size(*max_size))]
+    FileTooLarge { size: u64, max_size: u64 },
+    #[error("Audio matches banned sound `{name}`.")]
+    BannedSound { name: String },
+    #[error("`{name}` is on cooldown. It can be played again <t:{ready_at}:R>.")]
+    SoundCooldown { name: String, ready_at: i64 },
+    #[error("`{name}` is restricted to certain roles & members.")]
+    SoundRestricted { name: String },
+    #[error("This channel played its {cap} sounds for the hour. Sounds can be played here again <t:{ready_at}:R>.")]
+    ChannelCapReached { cap: u32, ready_at: i64 },
+    #[error(
+        "The queue is full, {max} sounds are already waiting. Try again once some have played."
+    )]
+    QueueFull { max: usize },
+    #[error("The queue is capped at {} of sounds, and `{name}` would go over it. Try again once some have played.", crate::helpers::format_timestamp(*max))]
+    QueueTooLong {
+        name: String,
+        max: std::time::Duration,
+    },
+}
+
+/// Reasons a member isn't allowed to use the soundboard. Displayed to the member as is
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AuthError {
+    #[error("You're banned from using the soundboard.{}", reason_suffix(.reason))]
+    Banned { reason: String },
+    #[error("You need the <@&{role_id}> role to play sounds.")]
+    MissingUserRole { role_id: u64 },
+    #[error("Only soundboard moderators can do that.")]
+    NotModerator,
+    #[error("You've played your {quota} sounds for today. Try again later.")]
+    QuotaExceeded { quota: u32 },
+    #[error("It's quiet hours. Sounds can be played again <t:{ends_at}:t>.")]
+    QuietHours { ends_at: i64 },
+    #[error("Sound `{name}` is protected. A server admin can override that with `force`.")]
+    SoundProtected { name: String },
+    #[error("Only server admins can override protected sounds.")]
+    NotAdmin,
+    #[error("Requires the Manage Server permission.")]
+    MissingManageGuild,
+}
+
+/// Why a long running operation stopped before finishing
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum OperationError {
+    #[error("Cancelled")]
+    Cancelled,
+}
+
+/// Feature turned off by a bot owner with `/features`. Displayed to the member as is
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum FeatureError {
+    #[error("{feature} is turned off right now. Try again later.")]
+    Disabled { feature: &'static str },
+}
+
+/// Upload stopped by the moderation hooks. Displayed to the member as is
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ModerationError {
+    #[error("Sound was rejected by moderation.{}", reason_suffix(.reason))]
+    Rejected { reason: String },
+    #[error("Sound couldn't be checked by moderation. Try again later.")]
+    Unavailable,
+}
+
+/// Why `/undo` couldn't reverse a library operation. Displayed to the member as is
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum UndoError {
+    #[error("Nothing to undo from the last {minutes} minutes.")]
+    NothingToUndo { minutes: i64 },
+    #[error("Can't undo - another sound is named `{name}` now. Rename it first.")]
+    NameTaken { name: String },
+    #[error("Can't undo - the audio of `{name}` is gone.")]
+    AudioMissing { name: String },
+    #[error("Brought back {restored} purged sounds, but not {}. `/undo` tries them again.", quoted_list(.failed))]
+    PartlyRestored {
+        restored: usize,
+        failed: Vec<String>,
+    },
+}
+
+/// Why a sound couldn't be moved by `/board reorder`. Displayed to the member as is
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum BoardOrderError {
+    #[error("`{name}` isn't on your {board} board.")]
+    NotOnBoard { name: String, board: String },
+}
+
+/// Sound name breaking the naming rules. Displayed to the member as is
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum NamingError {
+    #[error("Sound name can't be empty.")]
+    Empty,
+    #[error("Sound name `{name}` is too short. Names need at least {min} characters.")]
+    TooShort { name: String, min: usize },
+    #[error("Sound name is too long. Names can have at most {max} characters.")]
+    TooLong { max: usize },
+    #[error("Sound name can't contain `{character}`. Names can only use {allowed}.")]
+    InvalidCharacter {
+        character: String,
+        allowed: &'static str,
+    },
+    #[error("Sound name `{name}` doesn't follow the naming rules. Try `{suggestion}`.")]
+    NotNormalized { name: String, suggestion: String },
+}
+
+/// Why the bot can't join a voice channel. Displayed to the member as is
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum JoinError {
+    #[error("I lack {permissions} in <#{channel_id}>. Ask a server admin to grant it to me.")]
+    MissingPermissions {
+        permissions: String,
+        channel_id: u64,
+    },
+    #[error("<#{channel_id}> is full ({limit} members). Make room, raise its user limit, or grant me Move Members.")]
+    ChannelFull { channel_id: u64, limit: u32 },
+}
+
+/// Why an HTTP API upload was refused. Returned to the API caller as is
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum UploadError {
+    #[error("Upload not found. It may have expired.")]
+    NotFound,
+    #[error("Upload is at offset {offset}, not {sent}.")]
+    OffsetMismatch { offset: u64, sent: u64 },
+    #[error("Chunk goes past the upload size of {size} bytes.")]
+    PastEnd { size: u64 },
+    #[error("Another chunk of this upload is being written.")]
+    Busy,
+    #[error("{max} uploads are already in progress. Finish or cancel one first.")]
+    TooManyUploads { max: usize },
+    #[error("Missing `{field}`.")]
+    MissingField { field: &'static str },
+}
+
+fn reason_suffix(reason: &str) -> String {
+    match reason.is_empty() {
+        true => "".into(),
+        false => format!(" Reason: {reason}"),
+    }
+}
+
+/// Names as a comma separated list of inline code. ie: `airhorn`, `kazoo`
+fn quoted_list(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| format!("`{name}`"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Format byte count as human readable file size. ie: 1.5 MB
+pub fn format_file_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_file_size_test() {
+        assert_eq!("512 B", format_file_size(512));
+        assert_eq!("1.5 KB", format_file_size(1536));
+        assert_eq!("10.0 MB", format_file_size(10 * 1024 * 1024));
+    }
 }
@@ -0,0 +1,169 @@
+use chrono::{DateTime, Utc};
+use serenity::all::GuildId;
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::db::{JobsTable, SettingsTable};
+use crate::jobs::{self, JobKind};
+
+/// Guild setting holding when event mode ends. Not a `/settings` key, it's set by `/eventmode`
+pub const EVENT_MODE_KEY: &str = "event_mode_until";
+
+/// Longest event mode can be enabled for
+pub const MAX_EVENT_DURATION: chrono::Duration = chrono::Duration::days(7);
+
+/// Most tracks playing at once with overlap playback during event mode
+pub const EVENT_MAX_OVERLAPPING_TRACKS: usize = 10;
+
+/// Parse a duration of minutes, hours, or days, ie: `90m`, `2h`, `1d`. Plain numbers are minutes
+pub fn parse_duration(value: &str) -> Option<chrono::Duration> {
+    let value = value.trim().to_lowercase();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => value.split_at(index),
+        None => (value.as_str(), "m"),
+    };
+
+    let number = number.parse::<i64>().ok().filter(|number| *number > 0)?;
+    match unit.trim() {
+        "m" | "min" | "mins" | "minutes" => chrono::Duration::try_minutes(number),
+        "h" | "hr" | "hrs" | "hours" => chrono::Duration::try_hours(number),
+        "d" | "day" | "days" => chrono::Duration::try_days(number),
+        _ => None,
+    }
+}
+
+/// When guild's event mode ends, when it's on
+pub fn active_until(table: &SettingsTable, guild_id: GuildId) -> Option<DateTime<Utc>> {
+    stored_until(table, guild_id).filter(|until| *until > Utc::now())
+}
+
+/// Whether guild is in event mode, where cooldowns, play quotas & queue limits are lifted
+pub fn is_active(table: &SettingsTable, guild_id: GuildId) -> bool {
+    active_until(table, guild_id).is_some()
+}
+
+/// Whether event mode is on, from the stored [`EVENT_MODE_KEY`] setting of guild
+pub fn is_stored_active(guild_id: GuildId, value: &str) -> bool {
+    parse_until(guild_id, value).is_some_and(|until| until > Utc::now())
+}
+
+fn stored_until(table: &SettingsTable, guild_id: GuildId) -> Option<DateTime<Utc>> {
+    table
+        .get_guild_setting(guild_id.get(), EVENT_MODE_KEY)
+        .log_err()
+        .ok()
+        .flatten()
+        .and_then(|value| parse_until(guild_id, &value))
+}
+
+fn parse_until(guild_id: GuildId, value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .log_err_msg(format!("Invalid stored event mode end for {guild_id}"))
+        .ok()
+        .map(|until| until.with_timezone(&Utc))
+}
+
+/// Turn on guild's event mode until a time, replacing when any current event mode ends. A job
+/// turns it back off once it ends
+pub fn enable(
+    settings_table: &SettingsTable,
+    jobs_table: &JobsTable,
+    guild_id: GuildId,
+    until: DateTime<Utc>,
+) -> Result<(), PoiseError> {
+    settings_table.set_guild_setting(guild_id.get(), EVENT_MODE_KEY, &until.to_rfc3339())?;
+
+    let mut job = jobs::one_off(JobKind::EndEventMode, Some(guild_id));
+    job.run_at = until;
+    jobs_table.insert_row(&job)?;
+    Ok(())
+}
+
+/// Turn off guild's event mode. Returns whether it was on
+pub fn disable(table: &SettingsTable, guild_id: GuildId) -> Result<bool, PoiseError> {
+    let active = is_active(table, guild_id);
+    table.delete_guild_setting(guild_id.get(), EVENT_MODE_KEY)?;
+    Ok(active)
+}
+
+/// Turn off guild's event mode if it has ended. Event mode enabled again for longer since the
+/// job was scheduled is left on. Returns whether event mode was turned off
+pub fn end_if_over(table: &SettingsTable, guild_id: GuildId) -> Result<bool, PoiseError> {
+    match stored_until(table, guild_id) {
+        Some(until) if until <= Utc::now() => {
+            table.delete_guild_setting(guild_id.get(), EVENT_MODE_KEY)?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::db::Table;
+    use crate::settings::GuildSettings;
+
+    #[test]
+    fn parse_duration_test() {
+        assert_eq!(parse_duration("90"), Some(chrono::Duration::minutes(90)));
+        assert_eq!(parse_duration("2h"), Some(chrono::Duration::hours(2)));
+        assert_eq!(
+            parse_duration(" 3 Hours "),
+            Some(chrono::Duration::hours(3))
+        );
+        assert_eq!(parse_duration("1d"), Some(chrono::Duration::days(1)));
+        assert_eq!(parse_duration("0h"), None);
+        assert_eq!(parse_duration("2w"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn event_mode_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let settings_table = SettingsTable::new(db_pool.get().unwrap());
+        let jobs_table = JobsTable::new(db_pool.get().unwrap());
+        settings_table.create_table();
+        jobs_table.create_table();
+
+        let guild_id = GuildId::new(1);
+        assert!(!is_active(&settings_table, guild_id));
+        settings_table
+            .set_guild_setting(guild_id.get(), "max_queue_length", "5")
+            .unwrap();
+        assert_eq!(
+            GuildSettings::load(&settings_table, guild_id).max_queue_length(),
+            Some(5)
+        );
+
+        let until = Utc::now() + chrono::Duration::hours(2);
+        enable(&settings_table, &jobs_table, guild_id, until).unwrap();
+        assert_eq!(
+            GuildSettings::load(&settings_table, guild_id).max_queue_length(),
+            None
+        );
+        assert_eq!(
+            active_until(&settings_table, guild_id).map(|until| until.timestamp()),
+            Some(until.timestamp())
+        );
+        assert!(!is_active(&settings_table, GuildId::new(2)));
+        assert_eq!(jobs_table.rows(10).unwrap()[0].kind, "end_event_mode");
+
+        // event mode extended past the job isn't ended early
+        assert!(!end_if_over(&settings_table, guild_id).unwrap());
+        assert!(is_active(&settings_table, guild_id));
+
+        let ended = Utc::now() - chrono::Duration::minutes(1);
+        enable(&settings_table, &jobs_table, guild_id, ended).unwrap();
+        assert!(!is_active(&settings_table, guild_id));
+        assert!(end_if_over(&settings_table, guild_id).unwrap());
+        assert!(!end_if_over(&settings_table, guild_id).unwrap());
+
+        enable(&settings_table, &jobs_table, guild_id, until).unwrap();
+        assert!(disable(&settings_table, guild_id).unwrap());
+        assert!(!disable(&settings_table, guild_id).unwrap());
+    }
+}
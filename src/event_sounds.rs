@@ -0,0 +1,220 @@
+use serenity::all::{GuildId, Message, MessageType, UserId};
+use serenity::client::Context;
+
+use crate::commands::{PoiseError, PoiseResult};
+use crate::common::{LogResult, UserData};
+use crate::db::{SettingsTable, UniqueAudioTableCol};
+use crate::playback::QueuedTrack;
+use crate::quiet_hours;
+use crate::settings::GuildSettings;
+
+/// Kind of the system message discord posts once a poll ends. Newer than serenity's message kinds
+const POLL_RESULT_MESSAGE_KIND: u8 = 46;
+
+/// Discord events guilds can play a sound for, set with `/eventsounds`
+#[derive(Debug, Clone, Copy, PartialEq, poise::ChoiceParameter)]
+pub enum SoundEvent {
+    #[name = "member_join"]
+    MemberJoin,
+    #[name = "boost"]
+    Boost,
+    #[name = "poll_end"]
+    PollEnd,
+}
+
+impl SoundEvent {
+    pub const ALL: [SoundEvent; 3] = [Self::MemberJoin, Self::Boost, Self::PollEnd];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::MemberJoin => "member_join",
+            Self::Boost => "boost",
+            Self::PollEnd => "poll_end",
+        }
+    }
+
+    /// Event a system message announces, if any. Events are read from system messages, so they
+    /// come through without the privileged server members intent
+    pub fn from_message_kind(kind: MessageType) -> Option<Self> {
+        match kind {
+            MessageType::MemberJoin => Some(Self::MemberJoin),
+            MessageType::NitroBoost
+            | MessageType::NitroTier1
+            | MessageType::NitroTier2
+            | MessageType::NitroTier3 => Some(Self::Boost),
+            MessageType::Unknown(POLL_RESULT_MESSAGE_KIND) => Some(Self::PollEnd),
+            _ => None,
+        }
+    }
+
+    /// Text posted in place of the sound, for event of user
+    fn cue_text(&self, user_id: UserId, sound: &str) -> String {
+        match self {
+            Self::MemberJoin => format!("👋 <@{user_id}> joined the server! 🔊 `{sound}`"),
+            Self::Boost => format!("🎉 <@{user_id}> boosted the server! 🔊 `{sound}`"),
+            Self::PollEnd => format!("📊 A poll ended! 🔊 `{sound}`"),
+        }
+    }
+}
+
+impl std::fmt::Display for SoundEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.key())
+    }
+}
+
+/// Sound a guild plays for an event
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventSound {
+    pub audio_id: i64,
+    /// Post a text cue when the sound can't play, ie: the bot isn't in voice
+    pub cue: bool,
+}
+
+/// Guild settings holding event sounds. Not `/settings` keys, they're set by `/eventsounds`
+fn sound_key(event: SoundEvent) -> String {
+    format!("event_sound_{}", event.key())
+}
+
+fn cue_key(event: SoundEvent) -> String {
+    format!("event_cue_{}", event.key())
+}
+
+/// Sound guild plays for event, if it set one
+pub fn get(table: &SettingsTable, guild_id: GuildId, event: SoundEvent) -> Option<EventSound> {
+    let get = |key: String| {
+        table
+            .get_guild_setting(guild_id.get(), &key)
+            .log_err()
+            .ok()
+            .flatten()
+    };
+
+    Some(EventSound {
+        audio_id: get(sound_key(event))?.parse().ok()?,
+        cue: get(cue_key(event)).is_some_and(|value| value == "true"),
+    })
+}
+
+/// Set or clear sound guild plays for event
+pub fn set(
+    table: &SettingsTable,
+    guild_id: GuildId,
+    event: SoundEvent,
+    sound: Option<EventSound>,
+) -> Result<(), PoiseError> {
+    match sound {
+        Some(sound) => {
+            table.set_guild_setting(
+                guild_id.get(),
+                &sound_key(event),
+                &sound.audio_id.to_string(),
+            )?;
+            table.set_guild_setting(guild_id.get(), &cue_key(event), &sound.cue.to_string())?;
+        }
+        None => {
+            table.delete_guild_setting(guild_id.get(), &sound_key(event))?;
+            table.delete_guild_setting(guild_id.get(), &cue_key(event))?;
+        }
+    }
+    Ok(())
+}
+
+/// Play the sound guild set for the event a system message announces. When the bot isn't in voice,
+/// or quiet hours refuse sounds, a text cue is posted in the message's channel instead, if the
+/// guild wants one
+pub async fn handle_message(ctx: &Context, data: &UserData, message: &Message) -> PoiseResult {
+    let (Some(guild_id), Some(event)) = (
+        message.guild_id,
+        SoundEvent::from_message_kind(message.kind),
+    ) else {
+        return Ok(());
+    };
+
+    let settings_table = data.settings_table();
+    let Some(sound) = get(&settings_table, guild_id, event) else {
+        return Ok(());
+    };
+    let Some(row) = data
+        .audio_table()
+        .find_audio_row(UniqueAudioTableCol::Id(sound.audio_id))
+    else {
+        log::warn!("Sound of {event} event no longer exists, guild_id: {guild_id}");
+        return Ok(());
+    };
+
+    let playable = data.playback.voice_channel(guild_id).await.is_some()
+        && quiet_hours::refused_until(&GuildSettings::load(&settings_table, guild_id)).is_none();
+    if playable {
+        log::info!(
+            "Playing `{}` for {event} event, guild_id: {guild_id}",
+            row.name
+        );
+        let outcome = data
+            .playback
+            .enqueue(guild_id, QueuedTrack::from_row(&row, message.channel_id))
+            .await
+            .log_err_msg(format!("Failed playing sound of {event} event"));
+        if outcome.is_ok() {
+            data.record_play(guild_id, message.channel_id, None, &row);
+            return Ok(());
+        }
+    }
+
+    if sound.cue {
+        let sent = message
+            .channel_id
+            .say(&ctx.http, event.cue_text(message.author.id, &row.name))
+            .await?;
+        data.track_bot_messages(guild_id, sent.channel_id, &[sent.id]);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::db::Table;
+
+    #[test]
+    fn from_message_kind_test() {
+        assert_eq!(
+            SoundEvent::from_message_kind(MessageType::MemberJoin),
+            Some(SoundEvent::MemberJoin)
+        );
+        assert_eq!(
+            SoundEvent::from_message_kind(MessageType::NitroTier2),
+            Some(SoundEvent::Boost)
+        );
+        assert_eq!(
+            SoundEvent::from_message_kind(MessageType::Unknown(POLL_RESULT_MESSAGE_KIND)),
+            Some(SoundEvent::PollEnd)
+        );
+        assert_eq!(SoundEvent::from_message_kind(MessageType::Regular), None);
+    }
+
+    #[test]
+    fn event_sound_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let table = SettingsTable::new(db_pool.get().unwrap());
+        table.create_table();
+
+        let guild_id = GuildId::new(1);
+        assert_eq!(get(&table, guild_id, SoundEvent::Boost), None);
+
+        let sound = EventSound {
+            audio_id: 3,
+            cue: true,
+        };
+        set(&table, guild_id, SoundEvent::Boost, Some(sound.clone())).unwrap();
+        assert_eq!(get(&table, guild_id, SoundEvent::Boost), Some(sound));
+        assert_eq!(get(&table, guild_id, SoundEvent::MemberJoin), None);
+        assert_eq!(get(&table, GuildId::new(2), SoundEvent::Boost), None);
+
+        set(&table, guild_id, SoundEvent::Boost, None).unwrap();
+        assert_eq!(get(&table, guild_id, SoundEvent::Boost), None);
+    }
+}
@@ -0,0 +1,188 @@
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::db::{AudioTablePaginator, AudioTableRow, PlayHistoryRow, PlayHistoryTable};
+use crate::operations;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, poise::ChoiceParameter)]
+pub enum ExportFormat {
+    #[default]
+    #[name = "csv"]
+    Csv,
+    #[name = "json"]
+    Json,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown export format '{name}'. Use csv or json")),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct PlayHistoryExportRow {
+    id: i64,
+    guild_id: u64,
+    user_id: Option<u64>,
+    audio_id: Option<i64>,
+    audio_name: String,
+    played_at: String,
+}
+
+impl From<PlayHistoryRow> for PlayHistoryExportRow {
+    fn from(row: PlayHistoryRow) -> Self {
+        Self {
+            id: row.id,
+            guild_id: row.guild_id,
+            user_id: row.user_id,
+            audio_id: row.audio_id,
+            audio_name: row.audio_name,
+            played_at: row.played_at.to_rfc3339(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct LibraryExportRow {
+    id: i64,
+    name: String,
+    tags: String,
+    file: String,
+    created_at: String,
+    author_id: Option<u64>,
+    author_name: Option<String>,
+    file_size: Option<u64>,
+    emoji: Option<String>,
+    volume: Option<u32>,
+}
+
+impl From<AudioTableRow> for LibraryExportRow {
+    fn from(row: AudioTableRow) -> Self {
+        Self {
+            id: row.id,
+            tags: row.tags.to_string(),
+            file: row.audio_file.file_name_lossy(),
+            name: row.name,
+            created_at: row.created_at.to_rfc3339(),
+            author_id: row.author_id,
+            author_name: row.author_name,
+            file_size: row.file_size,
+            emoji: row.emoji,
+            volume: row.volume,
+        }
+    }
+}
+
+pub struct ExportFile {
+    pub file_name: String,
+    pub content: Vec<u8>,
+}
+
+fn serialize_rows<T: Serialize>(rows: &[T], format: ExportFormat) -> Result<Vec<u8>, PoiseError> {
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            for row in rows {
+                writer
+                    .serialize(row)
+                    .log_err_msg("Failed writing CSV row")?;
+            }
+            Ok(writer.into_inner()?)
+        }
+        ExportFormat::Json => Ok(serde_json::to_vec_pretty(rows)?),
+    }
+}
+
+/// Dump play history & library metadata, for analyzing usage in spreadsheets.
+/// Play history is limited to guild when guild_id is given
+pub fn export_stats(
+    db_pool: &r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>,
+    guild_id: Option<u64>,
+    format: ExportFormat,
+    cancel: &CancellationToken,
+) -> Result<Vec<ExportFile>, PoiseError> {
+    log::info!("Exporting stats. guild_id: {guild_id:?}, format: {format:?}");
+
+    let plays: Vec<PlayHistoryExportRow> = PlayHistoryTable::new(db_pool.get()?)
+        .rows(guild_id)?
+        .into_iter()
+        .map(PlayHistoryExportRow::from)
+        .collect();
+
+    let mut library: Vec<LibraryExportRow> = vec![];
+    for page in AudioTablePaginator::builder(db_pool.get()?).build() {
+        operations::check(cancel)?;
+        library.extend(page.log_err()?.into_iter().map(LibraryExportRow::from));
+    }
+
+    operations::check(cancel)?;
+    let extension = format.extension();
+    Ok(vec![
+        ExportFile {
+            file_name: format!("play_history.{extension}"),
+            content: serialize_rows(&plays, format)?,
+        },
+        ExportFile {
+            file_name: format!("library.{extension}"),
+            content: serialize_rows(&library, format)?,
+        },
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::db::{PlayHistoryRowInsert, Table};
+
+    #[test]
+    fn export_stats_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        crate::db::AudioTable::new(db_pool.get().unwrap()).create_table();
+        let history = PlayHistoryTable::new(db_pool.get().unwrap());
+        history.create_table();
+        history
+            .insert_row(&PlayHistoryRowInsert {
+                guild_id: 1,
+                user_id: None,
+                channel_id: None,
+                audio_id: Some(3),
+                audio_name: "airhorn".into(),
+                played_at: chrono::Utc::now(),
+            })
+            .unwrap();
+
+        let cancel = CancellationToken::new();
+        let files = export_stats(&db_pool, Some(1), ExportFormat::Csv, &cancel).unwrap();
+        assert_eq!(files[0].file_name, "play_history.csv");
+        let csv = String::from_utf8(files[0].content.clone()).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("id,guild_id,user_id,audio_id,audio_name,played_at")
+        );
+        assert!(lines.next().unwrap().starts_with("1,1,,3,airhorn,"));
+
+        let files = export_stats(&db_pool, Some(2), ExportFormat::Json, &cancel).unwrap();
+        assert_eq!(files[1].file_name, "library.json");
+        let plays: serde_json::Value = serde_json::from_slice(&files[0].content).unwrap();
+        assert_eq!(plays, serde_json::json!([]));
+
+        cancel.cancel();
+        assert!(export_stats(&db_pool, Some(1), ExportFormat::Csv, &cancel).is_err());
+    }
+}
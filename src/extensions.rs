@@ -0,0 +1,133 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use serenity::all::FullEvent;
+use serenity::async_trait;
+use serenity::client::Context;
+
+use crate::commands::{PoiseCommand, PoiseResult};
+use crate::common::{LogResult, UserData};
+
+/// Extra commands & event handling compiled into the bot, so forks can add guild specific
+/// behaviour without patching the built-in command list
+#[async_trait]
+pub trait Extension: Send + Sync {
+    /// Name used in logs
+    fn name(&self) -> &'static str;
+
+    /// Commands added next to the built-in ones. Registered, checked & logged like any other
+    fn commands(&self) -> Vec<PoiseCommand> {
+        Vec::new()
+    }
+
+    /// Called for every gateway event the bot handled without error
+    async fn on_event(&self, _ctx: &Context, _event: &FullEvent, _data: &UserData) -> PoiseResult {
+        Ok(())
+    }
+}
+
+/// Extensions built into this binary. Forks add theirs here, ideally each behind its own cargo
+/// feature, ie: `#[cfg(feature = "my-guild")] Arc::new(my_guild::MyGuild::default())`
+fn registered() -> Vec<Arc<dyn Extension>> {
+    vec![]
+}
+
+/// Loaded extensions, shared by the framework & event handler
+#[derive(Clone, Default)]
+pub struct Extensions {
+    extensions: Arc<Vec<Arc<dyn Extension>>>,
+}
+
+impl Extensions {
+    pub fn new(extensions: Vec<Arc<dyn Extension>>) -> Self {
+        Self {
+            extensions: Arc::new(extensions),
+        }
+    }
+
+    /// Extensions registered in this build
+    pub fn registered() -> Self {
+        let extensions = Self::new(registered());
+        if !extensions.extensions.is_empty() {
+            log::info!("Loaded extensions: {}", extensions.names().join(", "));
+        }
+        extensions
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        self.extensions.iter().map(|ext| ext.name()).collect()
+    }
+
+    /// Built-in commands followed by the commands of every extension. Extension commands named
+    /// like one already in the list are skipped, built-in commands always win
+    pub fn with_commands(&self, mut commands: Vec<PoiseCommand>) -> Vec<PoiseCommand> {
+        let mut names: HashSet<String> = commands.iter().map(|cmd| cmd.name.clone()).collect();
+
+        for extension in self.extensions.iter() {
+            for command in extension.commands() {
+                if !names.insert(command.name.clone()) {
+                    log::warn!(
+                        "Skipping command {} of extension {}, a command with that name already exists",
+                        command.name,
+                        extension.name()
+                    );
+                    continue;
+                }
+                commands.push(command);
+            }
+        }
+
+        commands
+    }
+
+    /// Pass event to every extension. A failing extension is logged & doesn't stop the others
+    pub async fn dispatch(&self, ctx: &Context, event: &FullEvent, data: &UserData) {
+        for extension in self.extensions.iter() {
+            extension
+                .on_event(ctx, event, data)
+                .await
+                .log_err_msg(format!(
+                    "Extension {} failed handling {} event",
+                    extension.name(),
+                    event.snake_case_name()
+                ))
+                .ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands;
+
+    struct Greeter;
+
+    #[async_trait]
+    impl Extension for Greeter {
+        fn name(&self) -> &'static str {
+            "greeter"
+        }
+
+        fn commands(&self) -> Vec<PoiseCommand> {
+            let mut hello = commands::admin::echo();
+            hello.name = "hello".into();
+            // clashes with the built-in command
+            vec![hello, commands::admin::version()]
+        }
+    }
+
+    #[test]
+    fn extension_commands_test() {
+        let extensions = Extensions::new(vec![Arc::new(Greeter)]);
+        assert_eq!(extensions.names(), vec!["greeter"]);
+
+        let commands =
+            extensions.with_commands(vec![commands::admin::echo(), commands::admin::version()]);
+        let names: Vec<&str> = commands.iter().map(|cmd| cmd.name.as_str()).collect();
+        assert_eq!(names, vec!["echo", "version", "hello"]);
+
+        let commands = Extensions::default().with_commands(vec![commands::admin::echo()]);
+        assert_eq!(commands.len(), 1);
+    }
+}
@@ -0,0 +1,109 @@
+use crate::commands::{PoiseContext, PoiseError};
+use crate::common::{LogResult, UserData};
+use crate::db::FeatureFlagsTable;
+use crate::errors::FeatureError;
+
+/// Bot wide features bot owners can turn off at runtime with `/features`, ie: while one misbehaves.
+/// Features are enabled unless turned off
+#[derive(Debug, Clone, Copy, PartialEq, poise::ChoiceParameter)]
+pub enum FeatureFlag {
+    #[name = "sound_requests_enabled"]
+    SoundRequests,
+    #[name = "url_imports_enabled"]
+    UrlImports,
+    #[name = "audio_editing_enabled"]
+    AudioEditing,
+    #[name = "voice_commands_enabled"]
+    VoiceCommands,
+    #[name = "web_api_enabled"]
+    WebApi,
+}
+
+impl FeatureFlag {
+    pub const ALL: [FeatureFlag; 5] = [
+        Self::SoundRequests,
+        Self::UrlImports,
+        Self::AudioEditing,
+        Self::VoiceCommands,
+        Self::WebApi,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::SoundRequests => "sound_requests_enabled",
+            Self::UrlImports => "url_imports_enabled",
+            Self::AudioEditing => "audio_editing_enabled",
+            Self::VoiceCommands => "voice_commands_enabled",
+            Self::WebApi => "web_api_enabled",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::SoundRequests => "Members can request sounds with /request",
+            Self::UrlImports => {
+                "Sounds can be downloaded from links, ie: adding & approving sounds"
+            }
+            Self::AudioEditing => "Sounds can be merged & trimmed with ffmpeg",
+            Self::VoiceCommands => {
+                "Sounds can be played by voice, in guilds with voice commands on"
+            }
+            Self::WebApi => {
+                "Scripts & the web dashboard can use the HTTP API, when it's configured"
+            }
+        }
+    }
+
+    /// Feature name shown to members when it's turned off
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::SoundRequests => "Requesting sounds",
+            Self::UrlImports => "Adding sounds from links",
+            Self::AudioEditing => "Merging & trimming sounds",
+            Self::VoiceCommands => "Voice commands",
+            Self::WebApi => "The HTTP API",
+        }
+    }
+}
+
+impl std::fmt::Display for FeatureFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.key())
+    }
+}
+
+/// Whether feature is turned on. Features stay on when their flag can't be read
+pub fn enabled(data: &UserData, flag: FeatureFlag) -> bool {
+    table_enabled(&data.feature_flags_table(), flag)
+}
+
+/// Whether feature is turned on, for background tasks without user data
+pub fn table_enabled(table: &FeatureFlagsTable, flag: FeatureFlag) -> bool {
+    table
+        .get_flag(flag.key())
+        .log_err()
+        .ok()
+        .flatten()
+        .unwrap_or(true)
+}
+
+pub fn check(data: &UserData, flag: FeatureFlag) -> Result<(), FeatureError> {
+    match enabled(data, flag) {
+        true => Ok(()),
+        false => Err(FeatureError::Disabled {
+            feature: flag.label(),
+        }),
+    }
+}
+
+/// Command check. Sound requests are turned on
+pub async fn check_sound_requests(ctx: PoiseContext<'_>) -> Result<bool, PoiseError> {
+    check(ctx.data(), FeatureFlag::SoundRequests)?;
+    Ok(true)
+}
+
+/// Command check. Merging & trimming sounds is turned on
+pub async fn check_audio_editing(ctx: PoiseContext<'_>) -> Result<bool, PoiseError> {
+    check(ctx.data(), FeatureFlag::AudioEditing)?;
+    Ok(true)
+}
@@ -0,0 +1,193 @@
+use std::f32::consts::PI;
+use std::path;
+
+use crate::audio;
+use crate::commands::PoiseError;
+
+/// Seconds of audio in each fingerprinted frame
+const FRAME_SECS: f32 = 0.1;
+
+/// Frequencies energy is measured at, in Hz. Log spaced over the range most sounds carry
+/// their character in. Neighbouring bands give 16 bits per frame
+const BANDS: [f32; 17] = [
+    300.0, 346.0, 399.0, 461.0, 532.0, 614.0, 709.0, 818.0, 944.0, 1090.0, 1258.0, 1452.0, 1676.0,
+    1935.0, 2233.0, 2578.0, 3000.0,
+];
+
+/// Frames quieter than this share of the loudest frame are silence, trimmed off both ends
+const SILENCE_RATIO: f32 = 0.01;
+
+/// Share of differing bits under which two fingerprints are the same audio
+const MAX_BIT_ERROR_RATE: f32 = 0.25;
+
+/// Fewest frames fingerprints must overlap by to match, so blips don't match everything
+const MIN_OVERLAP_FRAMES: usize = 5;
+
+/// Acoustic fingerprint of audio, robust to re-encoding, volume changes & trimming, unlike a
+/// file hash. Each frame is 16 bits of whether energy differences between neighbouring
+/// frequency bands rose or fell since the previous frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fingerprint(Vec<u16>);
+
+impl Fingerprint {
+    /// Fingerprint of mono samples
+    pub fn from_samples(samples: &[f32], sample_rate: u32) -> Self {
+        let frame_len = ((sample_rate as f32 * FRAME_SECS) as usize).max(1);
+        let hop = (frame_len / 2).max(1);
+        let window: Vec<f32> = (0..frame_len)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / frame_len as f32).cos())
+            .collect();
+
+        let mut energies: Vec<[f32; BANDS.len()]> = vec![];
+        let mut start = 0;
+        while start + frame_len <= samples.len() {
+            let frame: Vec<f32> = samples[start..start + frame_len]
+                .iter()
+                .zip(&window)
+                .map(|(sample, weight)| sample * weight)
+                .collect();
+            energies.push(BANDS.map(|freq| goertzel_power(&frame, freq, sample_rate)));
+            start += hop;
+        }
+
+        let loudness: Vec<f32> = energies.iter().map(|bands| bands.iter().sum()).collect();
+        let loudest = loudness.iter().cloned().fold(0.0, f32::max);
+        let audible = |loudness: &f32| *loudness > loudest * SILENCE_RATIO;
+        let first = loudness.iter().position(audible).unwrap_or(0);
+        let last = loudness.iter().rposition(audible).unwrap_or(0);
+        let energies = match energies.is_empty() {
+            true => &energies[..],
+            false => &energies[first..=last],
+        };
+
+        let frames = energies
+            .windows(2)
+            .map(|pair| {
+                let (prev, cur) = (&pair[0], &pair[1]);
+                (0..BANDS.len() - 1).fold(0u16, |bits, band| {
+                    let diff = (cur[band] - cur[band + 1]) - (prev[band] - prev[band + 1]);
+                    match diff > 0.0 {
+                        true => bits | (1 << band),
+                        false => bits,
+                    }
+                })
+            })
+            .collect();
+        Self(frames)
+    }
+
+    /// Whether fingerprints are of the same audio, at any offset of one in the other
+    pub fn matches(&self, other: &Fingerprint) -> bool {
+        let (a, b) = (&self.0, &other.0);
+        let shorter = a.len().min(b.len());
+        let min_overlap = MIN_OVERLAP_FRAMES.max(shorter * 8 / 10);
+        if shorter < min_overlap {
+            return false;
+        }
+
+        // offset of b's first frame in a
+        let offsets = -(b.len() as isize - min_overlap as isize)..=(a.len() - min_overlap) as isize;
+        offsets.into_iter().any(|offset| {
+            let pairs: Vec<(u16, u16)> = a
+                .iter()
+                .enumerate()
+                .filter_map(|(i, frame)| {
+                    let j = i as isize - offset;
+                    (j >= 0)
+                        .then(|| b.get(j as usize))
+                        .flatten()
+                        .map(|other| (*frame, *other))
+                })
+                .collect();
+            if pairs.len() < min_overlap {
+                return false;
+            }
+
+            let differing: u32 = pairs.iter().map(|(a, b)| (a ^ b).count_ones()).sum();
+            let bits = pairs.len() * (BANDS.len() - 1);
+            (differing as f32 / bits as f32) < MAX_BIT_ERROR_RATE
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0
+            .iter()
+            .flat_map(|frame| frame.to_le_bytes())
+            .collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        Self(
+            bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect(),
+        )
+    }
+}
+
+/// Fingerprint of mp3 file
+pub fn fingerprint_file(path: impl AsRef<path::Path>) -> Result<Fingerprint, PoiseError> {
+    let (samples, sample_rate) = audio::decode_audio_mono(path)?;
+    Ok(Fingerprint::from_samples(&samples, sample_rate))
+}
+
+/// Power of frame at frequency
+fn goertzel_power(frame: &[f32], freq: f32, sample_rate: u32) -> f32 {
+    let coeff = 2.0 * (2.0 * PI * freq / sample_rate as f32).cos();
+    let (mut prev, mut prev2) = (0.0f32, 0.0f32);
+    for sample in frame {
+        let next = sample + coeff * prev - prev2;
+        prev2 = prev;
+        prev = next;
+    }
+    prev2 * prev2 + prev * prev - coeff * prev * prev2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 16_000;
+
+    /// Tones changing every 150ms, picked by seed
+    fn melody(seed: u32, secs: f32) -> Vec<f32> {
+        let mut state = seed;
+        let mut freq = 440.0;
+        (0..(SAMPLE_RATE as f32 * secs) as usize)
+            .map(|i| {
+                if i % (SAMPLE_RATE as usize * 15 / 100) == 0 {
+                    state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    freq = 300.0 + (state >> 16) as f32 % 2700.0;
+                }
+                (2.0 * PI * freq * i as f32 / SAMPLE_RATE as f32).sin()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fingerprint_test() {
+        let airhorn = melody(1, 3.0);
+        let fingerprint = Fingerprint::from_samples(&airhorn, SAMPLE_RATE);
+        assert!(fingerprint.matches(&fingerprint));
+
+        // quieter, and padded with silence. Paddings & trims aren't whole frames
+        let mut quieter = vec![0.0; SAMPLE_RATE as usize / 2 + 300];
+        quieter.extend(airhorn.iter().map(|sample| sample * 0.3));
+        assert!(Fingerprint::from_samples(&quieter, SAMPLE_RATE).matches(&fingerprint));
+
+        // trimmed
+        let trimmed =
+            &airhorn[SAMPLE_RATE as usize / 4 + 300..airhorn.len() - SAMPLE_RATE as usize / 4];
+        assert!(fingerprint.matches(&Fingerprint::from_samples(trimmed, SAMPLE_RATE)));
+
+        let trombone = Fingerprint::from_samples(&melody(2, 3.0), SAMPLE_RATE);
+        assert!(!fingerprint.matches(&trombone));
+        assert!(!fingerprint.matches(&Fingerprint::from_samples(&[], SAMPLE_RATE)));
+
+        assert_eq!(
+            Fingerprint::from_bytes(&fingerprint.to_bytes()),
+            fingerprint
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use rusqlite::types::ValueRef;
+use serde_json::{Map, Value};
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton, CreateMessage,
+    EditInteractionResponse, GuildId,
+};
+use serenity::client::Context;
+
+use crate::commands::{PoiseError, PoiseResult};
+use crate::common::{LogResult, UserData};
+use crate::db::{
+    ApiKeysTable, ApiUsageTable, BannedUsersTable, BirthdaysTable, BoardThemesTable,
+    BotMessagesTable, CommandStatsTable, DbConnection, DbPool, JobsTable, LibraryOpsTable,
+    PlayHistoryTable, PostedBoardsTable, SettingsTable, SoundRequestsTable, SoundRestrictionsTable,
+};
+use crate::export::ExportFile;
+use crate::helpers::{self, ButtonCustomId};
+use crate::retry;
+
+/// Tables holding data of a single guild, in their `guild_id` column. The sound library and
+/// user preferences are shared by every guild, so they aren't guild data
+const GUILD_TABLES: [&str; 16] = [
+    SettingsTable::GUILD_TABLE_NAME,
+    SettingsTable::CHANNEL_VOLUMES_TABLE_NAME,
+    BannedUsersTable::TABLE_NAME,
+    BotMessagesTable::TABLE_NAME,
+    PlayHistoryTable::TABLE_NAME,
+    PlayHistoryTable::ROLLUP_TABLE_NAME,
+    SoundRequestsTable::TABLE_NAME,
+    CommandStatsTable::TABLE_NAME,
+    JobsTable::TABLE_NAME,
+    BoardThemesTable::TABLE_NAME,
+    BirthdaysTable::TABLE_NAME,
+    SoundRestrictionsTable::TABLE_NAME,
+    ApiKeysTable::TABLE_NAME,
+    ApiUsageTable::TABLE_NAME,
+    PostedBoardsTable::TABLE_NAME,
+    LibraryOpsTable::TABLE_NAME,
+];
+
+/// Everything stored about guild, as a json file of rows by table name
+pub fn export(db_pool: &DbPool, guild_id: GuildId) -> Result<ExportFile, PoiseError> {
+    log::info!("Exporting guild data. guild_id: {guild_id}");
+
+    let conn = db_pool.get()?;
+    let mut tables = Map::new();
+    for table_name in GUILD_TABLES {
+        tables.insert(
+            table_name.into(),
+            Value::Array(table_rows(&conn, table_name, guild_id)?),
+        );
+    }
+
+    let export = serde_json::json!({
+        "guild_id": guild_id.get(),
+        "exported_at": chrono::Utc::now().to_rfc3339(),
+        "tables": tables,
+    });
+
+    Ok(ExportFile {
+        file_name: format!("guild_data_{guild_id}.json"),
+        content: serde_json::to_vec_pretty(&export)?,
+    })
+}
+
+fn table_rows(
+    conn: &DbConnection,
+    table_name: &str,
+    guild_id: GuildId,
+) -> Result<Vec<Value>, PoiseError> {
+    let sql = format!("SELECT * FROM {table_name} WHERE guild_id = ?1");
+    let mut stmt = conn
+        .prepare(sql.as_str())
+        .log_err_msg(format!("Failed preparing select of {table_name}"))?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+
+    let rows = stmt
+        .query_map([guild_id.get()], |row| {
+            let mut object = Map::new();
+            for (index, column) in columns.iter().enumerate() {
+                let value = match row.get_ref(index)? {
+                    ValueRef::Null => Value::Null,
+                    ValueRef::Integer(value) => value.into(),
+                    ValueRef::Real(value) => value.into(),
+                    ValueRef::Text(value) => String::from_utf8_lossy(value).into(),
+                    ValueRef::Blob(value) => value.to_vec().into(),
+                };
+                object.insert(column.clone(), value);
+            }
+            Ok(Value::Object(object))
+        })
+        .log_err_msg(format!("Failed selecting rows of {table_name}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .log_err_msg(format!("Failed reading rows of {table_name}"))?;
+
+    Ok(rows)
+}
+
+/// Delete everything stored about guild. Returns number of rows deleted per table
+pub fn delete(
+    db_pool: &DbPool,
+    guild_id: GuildId,
+) -> Result<Vec<(&'static str, usize)>, PoiseError> {
+    log::warn!("Deleting guild data. guild_id: {guild_id}");
+
+    let conn = db_pool.get()?;
+    let tx = conn
+        .unchecked_transaction()
+        .log_err_msg("Failed starting guild data delete")?;
+
+    let mut deleted = vec![];
+    for table_name in GUILD_TABLES {
+        let sql = format!("DELETE FROM {table_name} WHERE guild_id = ?1");
+        let count = tx
+            .execute(sql.as_str(), [guild_id.get()])
+            .log_err_msg(format!("Failed deleting rows from {table_name}"))?;
+        deleted.push((table_name, count));
+    }
+
+    tx.commit()
+        .log_err_msg("Failed committing guild data delete")?;
+
+    Ok(deleted)
+}
+
+/// Confirm & cancel buttons of a guild data delete
+pub fn confirm_delete_action_row(guild_id: GuildId) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(ButtonCustomId::ConfirmGuildDataDelete(guild_id))
+            .label("Delete everything")
+            .style(ButtonStyle::Danger),
+        CreateButton::new(ButtonCustomId::CancelGuildDataDelete).label("Cancel"),
+    ])
+}
+
+/// Delete guild's data from the confirm button of `/guilddata delete`. The interaction must
+/// already be acknowledged
+pub async fn confirm_delete(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+    guild_id: GuildId,
+) -> PoiseResult {
+    if component.guild_id != Some(guild_id) {
+        return Err("Guild data can only be deleted from its own guild".into()).log_err();
+    }
+
+    // audit channel setting is deleted along with everything else
+    let audit_channel_id = helpers::audit_channel_id(data, Some(guild_id));
+    let deleted = delete(&data.db_pool, guild_id)?;
+    let rows: usize = deleted.iter().map(|(_, count)| count).sum();
+    let tables: Vec<_> = deleted
+        .iter()
+        .filter(|(_, count)| *count > 0)
+        .map(|(table_name, count)| format!("`{table_name}`: {count}"))
+        .collect();
+
+    let text = format!("Deleted {rows} rows of this server's data");
+    let content = match tables.is_empty() {
+        true => text.clone(),
+        false => format!("{text} ({})", tables.join(", ")),
+    };
+    component
+        .edit_response(
+            ctx,
+            EditInteractionResponse::new()
+                .content(content)
+                .components(vec![]),
+        )
+        .await
+        .log_err_msg("Failed replying to guild data delete")
+        .ok();
+
+    if let Some(channel_id) = audit_channel_id {
+        let message = CreateMessage::new().content(format!("{text} by <@{}>", component.user.id));
+        helpers::check_msg(
+            retry::discord_retry("send audit message", || {
+                channel_id.send_message(ctx, message.clone())
+            })
+            .await,
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::db::{PlayHistoryRowInsert, Table};
+
+    #[test]
+    fn export_delete_guild_data_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let settings = SettingsTable::new(db_pool.get().unwrap());
+        settings.create_table();
+        BannedUsersTable::new(db_pool.get().unwrap()).create_table();
+        BotMessagesTable::new(db_pool.get().unwrap()).create_table();
+        SoundRequestsTable::new(db_pool.get().unwrap()).create_table();
+        CommandStatsTable::new(db_pool.get().unwrap()).create_table();
+        JobsTable::new(db_pool.get().unwrap()).create_table();
+        BoardThemesTable::new(db_pool.get().unwrap()).create_table();
+        BirthdaysTable::new(db_pool.get().unwrap()).create_table();
+        SoundRestrictionsTable::new(db_pool.get().unwrap()).create_table();
+        ApiKeysTable::new(db_pool.get().unwrap()).create_table();
+        ApiUsageTable::new(db_pool.get().unwrap()).create_table();
+        PostedBoardsTable::new(db_pool.get().unwrap()).create_table();
+        LibraryOpsTable::new(db_pool.get().unwrap()).create_table();
+        let history = PlayHistoryTable::new(db_pool.get().unwrap());
+        history.create_table();
+
+        for guild_id in [1, 2] {
+            settings
+                .set_guild_setting(guild_id, "volume", "50")
+                .unwrap();
+            history
+                .insert_row(&PlayHistoryRowInsert {
+                    guild_id,
+                    user_id: Some(3),
+                    channel_id: None,
+                    audio_id: Some(4),
+                    audio_name: "airhorn".into(),
+                    played_at: chrono::Utc::now(),
+                })
+                .unwrap();
+        }
+
+        let file = export(&db_pool, GuildId::new(1)).unwrap();
+        assert_eq!(file.file_name, "guild_data_1.json");
+        let export: Value = serde_json::from_slice(&file.content).unwrap();
+        assert_eq!(export["guild_id"], 1);
+        assert_eq!(
+            export["tables"]["guild_settings"],
+            serde_json::json!([{"guild_id": 1, "key": "volume", "value": "50"}])
+        );
+        assert_eq!(export["tables"]["play_history"][0]["audio_name"], "airhorn");
+        assert_eq!(export["tables"]["banned_users"], serde_json::json!([]));
+
+        let deleted = delete(&db_pool, GuildId::new(1)).unwrap();
+        assert!(deleted.contains(&("guild_settings", 1)));
+        assert!(deleted.contains(&("play_history", 1)));
+        assert!(history.rows(Some(1)).unwrap().is_empty());
+        assert_eq!(history.rows(Some(2)).unwrap().len(), 1);
+        assert_eq!(
+            settings.get_guild_setting(2, "volume").unwrap(),
+            Some("50".into())
+        );
+    }
+}
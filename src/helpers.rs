@@ -1,7 +1,12 @@
+use std::collections::HashSet;
 use std::num::ParseIntError;
 use std::sync::Arc;
 
-use serenity::all::{ChannelId, CreateActionRow, CreateButton, GuildId};
+use serenity::all::{
+    ButtonStyle, CacheHttp, ChannelId, ComponentInteraction, CreateActionRow, CreateButton,
+    CreateEmbed, CreateInteractionResponseFollowup, CreateMessage, EditInteractionResponse,
+    EditMessage, GuildId, Http, MessageId, Permissions, ReactionType, UserId,
+};
 use serenity::async_trait;
 use serenity::{all::Message, client::Context, Result as SerenityResult};
 use songbird::tracks::TrackHandle;
@@ -9,11 +14,22 @@ use songbird::{Songbird, SongbirdKey};
 
 use crate::audio;
 use crate::audio::TrackHandleHelper;
+use crate::auth;
+use crate::board_order::BoardMove;
+use crate::branding::Branding;
+use crate::collision::NameCollision;
 use crate::commands::{PoiseContext, PoiseError, PoiseResult};
-use crate::common::LogResult;
-use crate::db::AudioTableRow;
-use crate::errors::AudioError;
-use crate::vars;
+use crate::common::{LogResult, UserData};
+use crate::db::{AudioTablePaginator, AudioTableRow, BoardLayout, UniqueAudioTableCol, UserPrefs};
+use crate::errors::{AudioError, JoinError};
+use crate::playback::{EnqueueOutcome, NowPlaying, QueuedTrack};
+use crate::purge::PurgeSpec;
+use crate::settings::PlayFeedback;
+use crate::trim::TrimSpec;
+use crate::{retry, search, settings, themes, vars};
+
+/// How long ephemeral & reaction play feedback stays up
+const PLAY_FEEDBACK_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
 
 pub async fn songbird_get(ctx: &Context) -> Arc<songbird::Songbird> {
     songbird::get(ctx)
@@ -29,6 +45,150 @@ pub async fn poise_songbird_get(ctx: &PoiseContext<'_>) -> Arc<songbird::Songbir
         .clone()
 }
 
+/// Queue sound of a play button on behalf of user. Shared by button presses & the `--dry-run` console.
+/// Sounds on cooldown, past the channel's hourly cap, or restricted to others aren't played, unless
+/// user is a moderator. Sounds past the guild's queue limits aren't queued, unless user is an admin
+pub async fn play_sound_button(
+    data: &UserData,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    user_id: Option<UserId>,
+    audio_track_id: i64,
+    permissions: &auth::SoundboardPermissions,
+) -> Result<ButtonPlay, PoiseError> {
+    let audio_row = data
+        .audio_table()
+        .find_audio_row(UniqueAudioTableCol::Id(audio_track_id))
+        .ok_or("Unable to locate audio track for button custom id")
+        .log_err()?;
+
+    log::info!(
+        "Found audio track. Name: {}, File: {}",
+        audio_row.name,
+        audio_row.audio_file.to_string_lossy()
+    );
+
+    if !permissions.is_moderator {
+        if let Err(err) = auth::check_can_play(
+            &data.db_pool,
+            guild_id,
+            channel_id,
+            &audio_row,
+            user_id,
+            &permissions.member_roles,
+        ) {
+            return Ok(ButtonPlay {
+                name: audio_row.name,
+                outcome: Err(err),
+            });
+        }
+    }
+
+    let mut track = QueuedTrack::from_row(&audio_row, channel_id)
+        .priority(permissions.priority_queue)
+        .bypass_limits(permissions.is_admin);
+    if let Some(user_id) = user_id {
+        track = track.requested_by(user_id);
+    }
+    let outcome = data.playback.enqueue(guild_id, track).await;
+    if outcome.is_ok() {
+        data.record_play(guild_id, channel_id, user_id, &audio_row);
+    }
+
+    Ok(ButtonPlay {
+        name: audio_row.name,
+        outcome,
+    })
+}
+
+/// Id of the sound played last in guild, for replaying it
+pub fn last_played_audio_id(data: &UserData, guild_id: GuildId) -> Result<i64, PoiseError> {
+    data.play_history_table()
+        .last_played_audio_id(guild_id.get())?
+        .ok_or(AudioError::NothingToReplay.into())
+}
+
+/// Sound queued by a play button, and whether it played
+pub struct ButtonPlay {
+    pub name: String,
+    pub outcome: Result<EnqueueOutcome, AudioError>,
+}
+
+impl ButtonPlay {
+    /// Feedback for whoever pressed the button, ie: `▶ airhorn`
+    pub fn feedback_text(&self) -> String {
+        match &self.outcome {
+            Ok(EnqueueOutcome::Started | EnqueueOutcome::Overlapping) => {
+                format!("▶ `{}`", self.name)
+            }
+            Ok(EnqueueOutcome::Queued { position }) => {
+                format!("⏳ `{}` queued (position {position})", self.name)
+            }
+            Err(err) => format!("❌ Failed playing `{}` - {err}", self.name),
+        }
+    }
+
+    fn feedback_emoji(&self) -> char {
+        match &self.outcome {
+            Ok(EnqueueOutcome::Started | EnqueueOutcome::Overlapping) => '▶',
+            Ok(EnqueueOutcome::Queued { .. }) => '⏳',
+            Err(_) => '❌',
+        }
+    }
+}
+
+/// Acknowledge play button press the guild's way, then take the feedback down after a few seconds.
+/// Ephemeral feedback edits the press' deferred response
+pub async fn send_play_feedback(
+    http: Arc<Http>,
+    component: &ComponentInteraction,
+    feedback: PlayFeedback,
+    played: &ButtonPlay,
+) {
+    match feedback {
+        // refusals are shown anyway, nothing playing isn't feedback enough
+        PlayFeedback::None if played.outcome.is_err() => {
+            let followup = CreateInteractionResponseFollowup::new()
+                .content(played.feedback_text())
+                .ephemeral(true);
+            component
+                .create_followup(&http, followup)
+                .await
+                .log_err_msg("Failed sending play refusal")
+                .ok();
+        }
+        PlayFeedback::None => {}
+        PlayFeedback::Ephemeral => {
+            let response = EditInteractionResponse::new().content(played.feedback_text());
+            if component
+                .edit_response(&http, response)
+                .await
+                .log_err_msg("Failed sending play feedback")
+                .is_ok()
+            {
+                let component = component.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(PLAY_FEEDBACK_DURATION).await;
+                    component.delete_response(&http).await.ok();
+                });
+            }
+        }
+        PlayFeedback::Reaction => {
+            let reaction = component
+                .message
+                .react(&http, played.feedback_emoji())
+                .await
+                .log_err_msg("Failed reacting with play feedback");
+            if let Ok(reaction) = reaction {
+                tokio::spawn(async move {
+                    tokio::time::sleep(PLAY_FEEDBACK_DURATION).await;
+                    reaction.delete(&http).await.ok();
+                });
+            }
+        }
+    }
+}
+
 /// check if message successfully sent, or log to error
 pub fn check_msg(result: SerenityResult<Message>) {
     if let Err(err) = result {
@@ -42,9 +202,139 @@ pub fn poise_check_msg(result: Result<poise::ReplyHandle, serenity::Error>) {
     }
 }
 
+/// Audit channel of guild. Guild's audit channel setting takes precedence over config
+pub fn audit_channel_id(data: &UserData, guild_id: Option<GuildId>) -> Option<ChannelId> {
+    guild_id
+        .and_then(|guild_id| settings::audit_channel(&data.settings_table(), guild_id))
+        .or(data.config.get().audit_channel_id.map(ChannelId::new))
+}
+
+/// send message to audit channel, if one is configured
+pub async fn send_audit_message(
+    cache_http: impl CacheHttp,
+    data: &UserData,
+    guild_id: Option<GuildId>,
+    message: CreateMessage,
+) {
+    let channel_id = audit_channel_id(data, guild_id);
+
+    if let Some(channel_id) = channel_id {
+        log::debug!("Sending audit message to channel id: {channel_id}");
+        check_msg(
+            retry::discord_retry("send audit message", || {
+                channel_id.send_message(&cache_http, message.clone())
+            })
+            .await,
+        );
+    }
+}
+
+/// Reply to command, only visible to the author when guild has ephemeral replies enabled
+pub async fn guild_reply<'a>(
+    ctx: PoiseContext<'a>,
+    content: impl Into<String>,
+) -> Result<poise::ReplyHandle<'a>, serenity::Error> {
+    let ephemeral = ctx.guild_id().is_some_and(|guild_id| {
+        settings::ephemeral_replies(&ctx.data().settings_table(), guild_id)
+    });
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(ephemeral),
+    )
+    .await
+}
+
+/// Reply with exported files, over as many messages as discord's upload limit needs
+pub async fn send_export_files(
+    ctx: PoiseContext<'_>,
+    content: &str,
+    files: Vec<crate::export::ExportFile>,
+) -> PoiseResult {
+    let messages = crate::attachments::split_into_messages(files, vars::ATTACHMENT_MAX_SIZE);
+    let content = match messages.len() {
+        1 => content.to_string(),
+        count => format!(
+            "{content}. Too large for one upload, so it's sent as {count} messages. Files split into parts join back together in order"
+        ),
+    };
+
+    for (index, files) in messages.into_iter().enumerate() {
+        let reply = match index {
+            0 => poise::CreateReply::default().content(&content),
+            _ => poise::CreateReply::default(),
+        };
+        let reply = files
+            .into_iter()
+            .fold(reply.ephemeral(true), |reply, file| {
+                reply.attachment(serenity::all::CreateAttachment::bytes(
+                    file.content,
+                    file.file_name,
+                ))
+            });
+        ctx.send(reply).await?;
+    }
+
+    Ok(())
+}
+
+/// Marks commands that can take longer than discord's 3 second interaction response window.
+/// Their responses are deferred before the command runs, and the first reply edits the deferred response.
+///
+/// `#[poise::command(slash_command, custom_data = "LongRunning")]`
+pub struct LongRunning;
+
+pub fn is_long_running(command: &poise::Command<UserData, PoiseError>) -> bool {
+    command.custom_data.downcast_ref::<LongRunning>().is_some()
+}
+
 #[derive(Debug)]
 pub enum ButtonCustomId {
     PlayAudio(i64),
+    DeleteAudio(i64),
+    ApproveRequest(i64),
+    DenyRequest(i64),
+    /// Page of a search results board, and the search query
+    SearchPage(usize, String),
+    /// Opens search modal
+    SearchModal,
+    /// Re-rolls sounds of a `/sounds random-button` board, and the number of sounds on it
+    ShuffleBoard(u32),
+    /// Confirms trim of a sound previewed by `/sounds trim`
+    ConfirmTrim(TrimSpec),
+    CancelTrim,
+    /// Uploader's choice for a pending import named like an existing sound
+    ResolveCollision(i64, NameCollision),
+    PausePlayback,
+    ResumePlayback,
+    /// Plays the sound played last in guild again, from now playing buttons
+    ReplayLast,
+    /// Shows setup settings of guild, from its welcome message
+    OnboardingSetup(GuildId),
+    /// Posts soundboard in channel, like `/sounds display`
+    DisplaySounds,
+    /// Refreshes play stats of a `/sounds info` embed
+    RefreshSoundInfo(i64),
+    /// Cancels a long running operation, ie: a scan
+    CancelOperation(i64),
+    /// Confirms `/guilddata delete` of guild
+    ConfirmGuildDataDelete(GuildId),
+    CancelGuildDataDelete,
+    /// Confirms `/sounds purge` of the sounds previewed
+    ConfirmPurge(PurgeSpec),
+    CancelPurge,
+    /// Confirms `/packs install` of the pack with code previewed
+    ConfirmPackInstall(String),
+    CancelPackInstall,
+    /// Moves a sound on the board of whoever presses it, from `/board reorder`
+    MoveBoardSound(i64, BoardLayout, BoardMove),
+    /// Gives or takes the soundboard user role of whoever presses it, from `/access post`
+    ToggleAccess,
+    /// Removes sounds whose audio file is missing, from `/sounds reconcile`
+    ReconcileRemoveRows,
+    /// Imports audio files without a sound, from `/sounds reconcile`
+    ReconcileImportFiles,
     Unknown(String),
 }
 
@@ -53,13 +343,86 @@ impl TryFrom<String> for ButtonCustomId {
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
         let parts: Vec<_> = value.split("::").collect();
+        let parse_id = || -> Result<i64, String> {
+            parts
+                .get(1)
+                .ok_or("Missing id".to_string())
+                .and_then(|id| id.parse().map_err(|e: ParseIntError| e.to_string()))
+                .log_err_op(|e| format!("Parse error on button custom id '{value}' - {e}"))
+        };
+
         match parts[0] {
-            "play" => {
-                let id: i64 = parts[1]
+            "play" => Ok(ButtonCustomId::PlayAudio(parse_id()?)),
+            "delete" => Ok(ButtonCustomId::DeleteAudio(parse_id()?)),
+            "request_approve" => Ok(ButtonCustomId::ApproveRequest(parse_id()?)),
+            "request_deny" => Ok(ButtonCustomId::DenyRequest(parse_id()?)),
+            "search_modal" => Ok(ButtonCustomId::SearchModal),
+            "shuffle" => Ok(ButtonCustomId::ShuffleBoard(
+                u32::try_from(parse_id()?)
+                    .map_err(|e| format!("Invalid count in button custom id '{value}' - {e}"))?,
+            )),
+            "trim" => Ok(ButtonCustomId::ConfirmTrim(
+                TrimSpec::from_parts(&parts[1..])
+                    .log_err_op(|e| format!("Parse error on button custom id '{value}' - {e}"))?,
+            )),
+            "trim_cancel" => Ok(ButtonCustomId::CancelTrim),
+            "purge" => Ok(ButtonCustomId::ConfirmPurge(
+                PurgeSpec::from_parts(&parts[1..])
+                    .log_err_op(|e| format!("Parse error on button custom id '{value}' - {e}"))?,
+            )),
+            "purge_cancel" => Ok(ButtonCustomId::CancelPurge),
+            "pack_install" if parts.len() > 1 => {
+                Ok(ButtonCustomId::ConfirmPackInstall(parts[1..].join("::")))
+            }
+            "pack_install_cancel" => Ok(ButtonCustomId::CancelPackInstall),
+            "import_collision" => Ok(ButtonCustomId::ResolveCollision(
+                parse_id()?,
+                parts
+                    .get(2)
+                    .and_then(|choice| NameCollision::parse(choice))
+                    .ok_or(format!("Invalid choice in button custom id '{value}'"))?,
+            )),
+            "pause" => Ok(ButtonCustomId::PausePlayback),
+            "resume" => Ok(ButtonCustomId::ResumePlayback),
+            "replay" => Ok(ButtonCustomId::ReplayLast),
+            "onboarding_setup" => Ok(ButtonCustomId::OnboardingSetup(
+                u64::try_from(parse_id()?)
+                    .ok()
+                    .filter(|id| *id > 0)
+                    .map(GuildId::new)
+                    .ok_or(format!("Invalid guild id in button custom id '{value}'"))?,
+            )),
+            "guilddata_delete" => Ok(ButtonCustomId::ConfirmGuildDataDelete(
+                u64::try_from(parse_id()?)
+                    .ok()
+                    .filter(|id| *id > 0)
+                    .map(GuildId::new)
+                    .ok_or(format!("Invalid guild id in button custom id '{value}'"))?,
+            )),
+            "guilddata_cancel" => Ok(ButtonCustomId::CancelGuildDataDelete),
+            "access_toggle" => Ok(ButtonCustomId::ToggleAccess),
+            "reconcile_remove" => Ok(ButtonCustomId::ReconcileRemoveRows),
+            "reconcile_import" => Ok(ButtonCustomId::ReconcileImportFiles),
+            "display_sounds" => Ok(ButtonCustomId::DisplaySounds),
+            "sound_info" => Ok(ButtonCustomId::RefreshSoundInfo(parse_id()?)),
+            "board_move" => Ok(ButtonCustomId::MoveBoardSound(
+                parse_id()?,
+                parts
+                    .get(2)
+                    .map(|board| BoardLayout::from_str(board))
+                    .ok_or(format!("Missing board in button custom id '{value}'"))?,
+                parts
+                    .get(3)
+                    .and_then(|board_move| BoardMove::parse(board_move))
+                    .ok_or(format!("Invalid move in button custom id '{value}'"))?,
+            )),
+            "operation_cancel" => Ok(ButtonCustomId::CancelOperation(parse_id()?)),
+            "search" if parts.len() > 2 => {
+                let page = parts[1]
                     .parse()
                     .map_err(|e: ParseIntError| e.to_string())
                     .log_err_op(|e| format!("Parse error on button custom id '{value}' - {e}"))?;
-                Ok(ButtonCustomId::PlayAudio(id))
+                Ok(ButtonCustomId::SearchPage(page, parts[2..].join("::")))
             }
             _ => Ok(ButtonCustomId::Unknown(value)),
         }
@@ -70,6 +433,38 @@ impl From<ButtonCustomId> for String {
     fn from(value: ButtonCustomId) -> Self {
         match value {
             ButtonCustomId::PlayAudio(val) => format!("play::{val}"),
+            ButtonCustomId::DeleteAudio(val) => format!("delete::{val}"),
+            ButtonCustomId::ApproveRequest(val) => format!("request_approve::{val}"),
+            ButtonCustomId::DenyRequest(val) => format!("request_deny::{val}"),
+            ButtonCustomId::SearchPage(page, query) => format!("search::{page}::{query}"),
+            ButtonCustomId::SearchModal => "search_modal".to_string(),
+            ButtonCustomId::ShuffleBoard(count) => format!("shuffle::{count}"),
+            ButtonCustomId::ConfirmTrim(spec) => format!("trim::{}", spec.to_parts()),
+            ButtonCustomId::CancelTrim => "trim_cancel".to_string(),
+            ButtonCustomId::ConfirmPurge(spec) => format!("purge::{}", spec.to_parts()),
+            ButtonCustomId::CancelPurge => "purge_cancel".to_string(),
+            ButtonCustomId::ConfirmPackInstall(code) => format!("pack_install::{code}"),
+            ButtonCustomId::CancelPackInstall => "pack_install_cancel".to_string(),
+            ButtonCustomId::ResolveCollision(id, choice) => {
+                format!("import_collision::{id}::{}", choice.as_str())
+            }
+            ButtonCustomId::PausePlayback => "pause".to_string(),
+            ButtonCustomId::ResumePlayback => "resume".to_string(),
+            ButtonCustomId::ReplayLast => "replay".to_string(),
+            ButtonCustomId::OnboardingSetup(guild_id) => format!("onboarding_setup::{guild_id}"),
+            ButtonCustomId::DisplaySounds => "display_sounds".to_string(),
+            ButtonCustomId::RefreshSoundInfo(val) => format!("sound_info::{val}"),
+            ButtonCustomId::MoveBoardSound(val, board, board_move) => {
+                format!("board_move::{val}::{board}::{}", board_move.as_str())
+            }
+            ButtonCustomId::CancelOperation(val) => format!("operation_cancel::{val}"),
+            ButtonCustomId::ConfirmGuildDataDelete(guild_id) => {
+                format!("guilddata_delete::{guild_id}")
+            }
+            ButtonCustomId::CancelGuildDataDelete => "guilddata_cancel".to_string(),
+            ButtonCustomId::ToggleAccess => "access_toggle".to_string(),
+            ButtonCustomId::ReconcileRemoveRows => "reconcile_remove".to_string(),
+            ButtonCustomId::ReconcileImportFiles => "reconcile_import".to_string(),
             ButtonCustomId::Unknown(val) => format!("{val}"),
         }
     }
@@ -91,15 +486,64 @@ impl ButtonLabel for &str {
     }
 }
 
+/// Join items as a newline separated embed field value, within the embed field value length limit
+pub fn embed_field_list(items: &[String]) -> String {
+    if items.is_empty() {
+        return "-".into();
+    }
+
+    let mut value = String::new();
+    for (index, item) in items.iter().enumerate() {
+        let remaining = items.len() - index;
+        let more = format!("...and {remaining} more");
+
+        if value.len() + item.len() + 1 + more.len() > vars::EMBED_FIELD_VALUE_MAX_LEN {
+            value.push_str(&more);
+            break;
+        }
+
+        value.push_str(item);
+        value.push('\n');
+    }
+
+    value
+}
+
+/// Label cut short to the button label limit, ie: `a very long na...`. Counted in characters, so
+/// names with multibyte characters aren't cut mid character
 pub fn truncate_button_label(label: impl AsRef<str>) -> String {
     let label = label.as_ref();
-    if label.len() > vars::BTN_LABEL_MAX_LEN {
-        format!("{}...", label[0..(vars::BTN_LABEL_MAX_LEN - 3)].to_string())
+    if label.chars().count() > vars::BTN_LABEL_MAX_LEN {
+        let kept: String = label.chars().take(vars::BTN_LABEL_MAX_LEN - 3).collect();
+        format!("{kept}...")
     } else {
         label.to_string()
     }
 }
 
+/// Labels of the sound buttons on a board. Names too long for a button are cut short, and numbered
+/// when cut short to the label of another button on the board, ie: `a very long na... (2)`
+#[derive(Debug, Default)]
+pub struct BoardLabels(HashSet<String>);
+
+impl BoardLabels {
+    pub fn label(&mut self, name: &str) -> String {
+        let mut label = truncate_button_label(name);
+        let mut number = 2;
+        while !self.0.insert(label.clone()) {
+            let suffix = format!("... ({number})");
+            let kept: String = name
+                .chars()
+                .take(vars::BTN_LABEL_MAX_LEN - suffix.chars().count())
+                .collect();
+            label = format!("{kept}{suffix}");
+            number += 1;
+        }
+
+        label
+    }
+}
+
 /// Get voice channel the author of command is currently in.
 /// Returns tuple (guild_id, channel_id)
 pub fn get_author_voice_channel(ctx: &PoiseContext) -> Result<(GuildId, ChannelId), PoiseError> {
@@ -121,14 +565,81 @@ pub fn get_author_voice_channel(ctx: &PoiseContext) -> Result<(GuildId, ChannelI
     }
 }
 
+/// Bot has the permissions it needs in voice channel, and there's room for it. `members` is how
+/// many others are in the channel
+pub fn check_voice_join(
+    channel_id: ChannelId,
+    permissions: Permissions,
+    user_limit: Option<u32>,
+    members: usize,
+) -> Result<(), JoinError> {
+    let missing = (Permissions::CONNECT | Permissions::SPEAK) - permissions;
+    if !missing.is_empty() && !permissions.administrator() {
+        return Err(JoinError::MissingPermissions {
+            permissions: missing.get_permission_names().join(" & "),
+            channel_id: channel_id.get(),
+        });
+    }
+
+    // members with Move Members can join full channels
+    match user_limit.filter(|limit| *limit > 0) {
+        Some(limit) if members >= limit as usize && !permissions.move_members() => {
+            Err(JoinError::ChannelFull {
+                channel_id: channel_id.get(),
+                limit,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// [check_voice_join] with the bot's permissions & the channel's members from cache. Passes when
+/// they aren't cached, leaving songbird to fail
+pub fn check_bot_voice_join(ctx: &PoiseContext, channel_id: ChannelId) -> Result<(), JoinError> {
+    let bot_id = ctx.serenity_context().cache.current_user().id;
+    let Some(guild) = ctx.guild() else {
+        return Ok(());
+    };
+    let (Some(channel), Some(member)) =
+        (guild.channels.get(&channel_id), guild.members.get(&bot_id))
+    else {
+        return Ok(());
+    };
+
+    let members = guild
+        .voice_states
+        .values()
+        .filter(|voice_state| {
+            voice_state.channel_id == Some(channel_id) && voice_state.user_id != bot_id
+        })
+        .count();
+    check_voice_join(
+        channel_id,
+        guild.user_permissions_in(channel, member),
+        channel.user_limit,
+        members,
+    )
+}
+
 #[async_trait]
 pub trait SongbirdHelper {
-    /// Begins play audio track and returns handle to track
+    /// Begins play audio track and returns handle to track. Track is attributed to the member
+    /// who asked for it, see [`TrackHandleHelper::requested_by`]
     async fn play_audio(
         &self,
         guild_id: GuildId,
         channel_id: ChannelId,
         audio_track: &audio::AudioFile,
+        requested_by: Option<UserId>,
+    ) -> Result<TrackHandle, AudioError>;
+
+    /// Begins playing audio track from its file's bytes, already read into memory
+    async fn play_audio_bytes(
+        &self,
+        guild_id: GuildId,
+        audio_track: &audio::AudioFile,
+        bytes: Arc<[u8]>,
+        requested_by: Option<UserId>,
     ) -> Result<TrackHandle, AudioError>;
 
     /// Plays audio track all the way to the end, then returns audio track
@@ -137,6 +648,7 @@ pub trait SongbirdHelper {
         guild_id: GuildId,
         channel_id: ChannelId,
         audio_track: &audio::AudioFile,
+        requested_by: Option<UserId>,
     ) -> Result<TrackHandle, AudioError>;
 
     async fn leave_voice_channel(&self, guild_id: GuildId) -> PoiseResult;
@@ -164,6 +676,7 @@ impl SongbirdHelper for Songbird {
         guild_id: GuildId,
         _channel_id: ChannelId,
         audio_track: &audio::AudioFile,
+        requested_by: Option<UserId>,
     ) -> Result<TrackHandle, AudioError> {
         log::debug!("Starting to play_audio_track - {audio_track:?}");
 
@@ -173,8 +686,36 @@ impl SongbirdHelper for Songbird {
             Some(handler_lock) => {
                 let mut handler = handler_lock.lock().await;
 
-                let track_handle = handler.play_input(audio_input.into());
-                log::info!("Playing track {audio_track:?}");
+                let track_handle =
+                    audio::play_track(&mut handler, audio_input.into(), requested_by);
+                log::info!(
+                    "Playing track {audio_track:?}{}",
+                    requested_by_log(requested_by)
+                );
+                Ok(track_handle)
+            }
+            None => Err(AudioError::NotInVoiceChannel),
+        }
+    }
+
+    async fn play_audio_bytes(
+        &self,
+        guild_id: GuildId,
+        audio_track: &audio::AudioFile,
+        bytes: Arc<[u8]>,
+        requested_by: Option<UserId>,
+    ) -> Result<TrackHandle, AudioError> {
+        log::debug!("Starting to play_audio_track from memory - {audio_track:?}");
+
+        match self.get(guild_id) {
+            Some(handler_lock) => {
+                let mut handler = handler_lock.lock().await;
+
+                let track_handle = audio::play_track(&mut handler, bytes.into(), requested_by);
+                log::info!(
+                    "Playing track {audio_track:?} from memory{}",
+                    requested_by_log(requested_by)
+                );
                 Ok(track_handle)
             }
             None => Err(AudioError::NotInVoiceChannel),
@@ -186,6 +727,7 @@ impl SongbirdHelper for Songbird {
         guild_id: GuildId,
         _channel_id: ChannelId,
         audio_track: &audio::AudioFile,
+        requested_by: Option<UserId>,
     ) -> Result<TrackHandle, AudioError> {
         log::debug!("Starting to play_audio_track - {audio_track:?}");
 
@@ -195,8 +737,12 @@ impl SongbirdHelper for Songbird {
             Some(handler_lock) => {
                 let mut handler = handler_lock.lock().await;
 
-                let track_handle = handler.play_input(audio_input.into());
-                log::info!("Playing track {audio_track:?}");
+                let track_handle =
+                    audio::play_track(&mut handler, audio_input.into(), requested_by);
+                log::info!(
+                    "Playing track {audio_track:?}{}",
+                    requested_by_log(requested_by)
+                );
 
                 track_handle.wait_for_end().await;
                 Ok(track_handle)
@@ -206,6 +752,14 @@ impl SongbirdHelper for Songbird {
     }
 }
 
+/// Who a played track is attributed to in logs, ie: ` requested by 1234`
+fn requested_by_log(requested_by: Option<UserId>) -> String {
+    requested_by
+        .map(|user_id| format!(" requested by {user_id}"))
+        .unwrap_or_default()
+}
+
+#[allow(unused)]
 #[async_trait]
 pub trait PoiseContextHelper<'a> {
     async fn songbird(&self) -> Arc<songbird::Songbird>;
@@ -221,18 +775,341 @@ impl<'a> PoiseContextHelper<'a> for PoiseContext<'a> {
     }
 }
 
-pub fn make_action_row(audio_rows: &[AudioTableRow]) -> CreateActionRow {
+pub fn make_play_button(track: &AudioTableRow, label: String) -> CreateButton {
+    let button = CreateButton::new(ButtonCustomId::PlayAudio(track.id)).label(label);
+
+    match track
+        .emoji
+        .as_deref()
+        .and_then(|emoji| ReactionType::try_from(emoji).ok())
+    {
+        Some(emoji) => button.emoji(emoji),
+        None => button,
+    }
+}
+
+/// Message of a soundboard, sent to a channel or edited in place
+#[derive(Debug, Clone)]
+pub struct BoardMessage {
+    pub content: Option<String>,
+    pub components: Vec<CreateActionRow>,
+}
+
+impl BoardMessage {
+    fn new(content: Option<String>, components: Vec<CreateActionRow>) -> Self {
+        Self {
+            content,
+            components,
+        }
+    }
+
+    pub fn create(&self) -> CreateMessage {
+        let builder = CreateMessage::new().components(self.components.clone());
+        match &self.content {
+            Some(content) => builder.content(content),
+            None => builder,
+        }
+    }
+
+    /// Replaces the whole message, clearing content it no longer has
+    pub fn edit(&self) -> EditMessage {
+        EditMessage::new()
+            .content(self.content.clone().unwrap_or_default())
+            .components(self.components.clone())
+    }
+}
+
+/// Board of sound buttons, over as many messages as it takes, with a search button on the last
+/// one. Sounds are limited to the user's favorites with the favorites board layout.
+/// The full board starts with sounds of the guild's featured theme, when one is featured.
+/// `hidden` sounds are left out, ie: sounds restricted to others than the member asking.
+/// Returns None if there are no sounds to show
+pub fn soundboard_messages(
+    data: &UserData,
+    guild_id: GuildId,
+    prefs: &UserPrefs,
+    hidden: &HashSet<i64>,
+) -> Result<Option<Vec<BoardMessage>>, PoiseError> {
+    let page_size = prefs
+        .page_size
+        .map_or(vars::ACTION_ROWS_LIMIT, |size| size as u64);
+
+    let mut paginator_builder = AudioTablePaginator::builder(data.db_connection())
+        .page_limit(page_size)
+        .board_order(prefs.board, prefs.user_id);
+    if prefs.board == BoardLayout::Favorites {
+        paginator_builder = paginator_builder.favorites_of(prefs.user_id);
+    }
+
+    let search_row = || CreateActionRow::Buttons(vec![search::search_button()]);
+
+    let mut displayed = false;
+    let mut messages = vec![];
+    let mut search_added = false;
+    let mut labels = BoardLabels::default();
+
+    // full board leads with sounds of the guild's featured theme
+    if prefs.board == BoardLayout::Full {
+        let featured = data.board_themes_table().active_tag(guild_id.get())?;
+        let featured_rows = match &featured {
+            Some(tag) => data
+                .audio_table()
+                .tag_rows(tag, themes::FEATURED_SOUNDS_LIMIT)?
+                .into_iter()
+                .filter(|row| !hidden.contains(&row.id))
+                .collect(),
+            None => vec![],
+        };
+        if let (Some(tag), false) = (featured, featured_rows.is_empty()) {
+            let (content, btn_grid) = themes::featured_board(&tag, &featured_rows, &mut labels);
+            messages.push(BoardMessage::new(Some(content), btn_grid));
+        }
+    }
+
+    let mut pages = paginator_builder.build().peekable();
+    while let Some(audio_rows) = pages.next() {
+        let audio_rows: Vec<_> = audio_rows
+            .log_err()?
+            .into_iter()
+            .filter(|row| !hidden.contains(&row.id))
+            .collect();
+        if audio_rows.is_empty() {
+            continue;
+        }
+        displayed = true;
+
+        // ActionRows: Have a 5x5 grid limit
+        // (https://discordjs.guide/message-components/action-rows.html#action-rows)
+        let mut btn_grid: Vec<_> = audio_rows
+            .chunks(5)
+            .map(|rows| make_action_row(rows, &mut labels))
+            .collect();
+
+        // search button goes on last message of board, if it has room
+        if pages.peek().is_none() && btn_grid.len() < 5 {
+            btn_grid.push(search_row());
+            search_added = true;
+        }
+        messages.push(BoardMessage::new(None, btn_grid));
+    }
+
+    if displayed && !search_added {
+        messages.push(BoardMessage::new(None, vec![search_row()]));
+    }
+
+    Ok(displayed.then_some(messages))
+}
+
+/// Send messages of board to channel, in order. Returns ids of the messages sent, messages that
+/// failed sending are logged & left out
+pub async fn send_board_messages(
+    http: &Http,
+    channel_id: ChannelId,
+    messages: &[BoardMessage],
+) -> Vec<MessageId> {
+    let mut message_ids = vec![];
+    for message in messages {
+        let builder = message.create();
+        let sent = retry::discord_retry("send soundboard message", || {
+            channel_id.send_message(http, builder.clone())
+        })
+        .await
+        .log_err_msg("Error sending soundboard message");
+        message_ids.extend(sent.ok().map(|message| message.id));
+    }
+    message_ids
+}
+
+/// Send board of sound buttons to channel, see [`soundboard_messages`].
+/// Returns ids of the messages sent, or None if there were no sounds to send
+pub async fn send_soundboard(
+    http: &Http,
+    data: &UserData,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    prefs: &UserPrefs,
+    hidden: &HashSet<i64>,
+) -> Result<Option<Vec<MessageId>>, PoiseError> {
+    match soundboard_messages(data, guild_id, prefs, hidden)? {
+        Some(messages) => Ok(Some(send_board_messages(http, channel_id, &messages).await)),
+        None => Ok(None),
+    }
+}
+
+/// Delete messages from channel once `ttl` has passed, ie: temporary soundboards
+pub fn delete_messages_after(
+    http: Arc<Http>,
+    channel_id: ChannelId,
+    message_ids: Vec<MessageId>,
+    ttl: std::time::Duration,
+) {
+    tokio::spawn(async move {
+        tokio::time::sleep(ttl).await;
+
+        log::info!(
+            "Deleting {} expired messages, channel_id: {channel_id}",
+            message_ids.len()
+        );
+        for message_id in message_ids {
+            retry::discord_retry("delete expired message", || {
+                channel_id.delete_message(&http, message_id)
+            })
+            .await
+            .log_err_msg("Failed deleting expired message")
+            .ok();
+        }
+    });
+}
+
+/// Row of play buttons, labeled uniquely among the other buttons of the board
+pub fn make_action_row(audio_rows: &[AudioTableRow], labels: &mut BoardLabels) -> CreateActionRow {
     let buttons: Vec<_> = audio_rows
         .iter()
-        .map(|track| {
-            CreateButton::new(ButtonCustomId::PlayAudio(track.id))
-                .label(track.name.to_button_label())
-        })
+        .map(|row| make_play_button(row, labels.label(&row.name)))
         .collect();
 
     CreateActionRow::Buttons(buttons)
 }
 
+/// Row with a play button for sound, followed by a delete button
+pub fn make_play_delete_action_row(audio_row: &AudioTableRow) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        make_play_button(audio_row, audio_row.name.to_button_label()),
+        CreateButton::new(ButtonCustomId::DeleteAudio(audio_row.id))
+            .label("Delete")
+            .style(ButtonStyle::Danger),
+    ])
+}
+
+/// Time as `m:ss`. ie: `1:05`
+pub fn format_timestamp(time: std::time::Duration) -> String {
+    let secs = time.as_secs();
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/// Parse timestamp as seconds, `m:ss`, or `h:mm:ss`. Seconds may have a fraction, ie: `1:05.5`
+pub fn parse_timestamp(value: &str) -> Option<std::time::Duration> {
+    let parts: Vec<_> = value.trim().split(':').collect();
+    let (secs, larger) = parts.split_last()?;
+    if larger.len() > 2 {
+        return None;
+    }
+
+    let secs: f64 = secs
+        .parse()
+        .ok()
+        .filter(|secs: &f64| secs.is_finite() && *secs >= 0.0)?;
+    let mut total = secs;
+    for (part, scale) in larger.iter().rev().zip([60.0, 3600.0]) {
+        total += part.parse::<u32>().ok()? as f64 * scale;
+    }
+
+    Some(std::time::Duration::from_secs_f64(total))
+}
+
+pub fn now_playing_embed(
+    data: &UserData,
+    guild_id: GuildId,
+    now_playing: &NowPlaying,
+) -> CreateEmbed {
+    let status = match now_playing.paused {
+        true => "Paused",
+        false => "Playing",
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("{status} `{}`", now_playing.name))
+        .field("Position", format_timestamp(now_playing.position), true)
+        .field("Queued", now_playing.queued.to_string(), true);
+    if let Some(user_id) = now_playing.requested_by {
+        embed = embed.field("Requested by", format!("<@{user_id}>"), true);
+    }
+    if now_playing.overlapping > 0 {
+        embed = embed.field("Overlapping", now_playing.overlapping.to_string(), true);
+    }
+
+    Branding::of(data, Some(guild_id)).apply(embed)
+}
+
+/// Details of sound, and how often it's been played in guild
+pub fn sound_info_embed(
+    data: &UserData,
+    guild_id: GuildId,
+    row: &AudioTableRow,
+) -> Result<CreateEmbed, PoiseError> {
+    let now = chrono::Utc::now();
+    let stats = data.play_history_table().sound_stats(
+        guild_id.get(),
+        row.id,
+        now - chrono::Duration::days(7),
+        3,
+    )?;
+
+    let added = match row.author_id {
+        Some(author_id) => format!("<t:{}:R> by <@{author_id}>", row.created_at.timestamp()),
+        None => format!("<t:{}:R>", row.created_at.timestamp()),
+    };
+    let top_players: Vec<_> = stats
+        .top_players
+        .iter()
+        .enumerate()
+        .map(|(index, (user_id, plays))| format!("{}. <@{user_id}> - {plays}", index + 1))
+        .collect();
+
+    let mut embed = CreateEmbed::new()
+        .title(match &row.emoji {
+            Some(emoji) => format!("{emoji} {}", row.name),
+            None => row.name.clone(),
+        })
+        .field("Added", added, true)
+        .field("Volume", format!("{}%", row.volume.unwrap_or(100)), true);
+    if !row.tags.is_empty() {
+        embed = embed.field("Tags", row.tags.to_string(), true);
+    }
+    if row.protected {
+        embed = embed.field("Protected", "🔒 Yes", true);
+    }
+    if let Some(cooldown) = row.cooldown {
+        embed = embed.field("Cooldown", format!("⏱ {cooldown}s"), true);
+    }
+    if let Some(transcript) = data.audio_table().transcript(row.id)? {
+        embed = embed.field("Transcript", format!("\"{transcript}\""), false);
+    }
+
+    let embed = embed
+        .field("Plays", stats.total.to_string(), true)
+        .field("Last 7 Days", stats.recent.to_string(), true)
+        .field("Top Players", embed_field_list(&top_players), false)
+        .timestamp(now);
+    Ok(Branding::of(data, Some(guild_id)).apply(embed))
+}
+
+/// Refresh button of a sound's info embed
+pub fn sound_info_action_row(audio_id: i64) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![CreateButton::new(ButtonCustomId::RefreshSoundInfo(
+        audio_id,
+    ))
+    .label("Refresh")
+    .emoji(ReactionType::Unicode("🔄".into()))])
+}
+
+/// Pause, resume, & replay buttons for what's playing
+pub fn transport_action_row(paused: bool) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(ButtonCustomId::PausePlayback)
+            .label("Pause")
+            .emoji(ReactionType::Unicode("⏸️".into()))
+            .disabled(paused),
+        CreateButton::new(ButtonCustomId::ResumePlayback)
+            .label("Resume")
+            .emoji(ReactionType::Unicode("▶️".into()))
+            .disabled(!paused),
+        CreateButton::new(ButtonCustomId::ReplayLast)
+            .label("Replay")
+            .emoji(ReactionType::Unicode("🔁".into())),
+    ])
+}
+
 pub async fn autocomplete_audio_track_name<'a>(
     ctx: PoiseContext<'_>,
     partial: &'a str,
@@ -253,6 +1130,25 @@ pub async fn autocomplete_opt_audio_track_name<'a>(
     futures::stream::iter(track_names)
 }
 
+/// Names of ambience layers playing in guild, starting with `partial`
+pub async fn autocomplete_ambience_layer<'a>(
+    ctx: PoiseContext<'_>,
+    partial: &'a str,
+) -> impl futures::stream::Stream<Item = String> + 'a {
+    let layers = match ctx.guild_id() {
+        Some(guild_id) => ctx.data().playback.layers(guild_id),
+        None => vec![],
+    };
+    let partial = partial.to_lowercase();
+
+    futures::stream::iter(
+        layers
+            .into_iter()
+            .map(|layer| layer.name)
+            .filter(move |name| name.to_lowercase().starts_with(&partial)),
+    )
+}
+
 pub fn uuid_v4_str() -> String {
     // Create uuid audio file in /tmp directory
     let uuid = uuid::Uuid::new_v4();
@@ -275,10 +1171,252 @@ pub fn title_case(s: impl AsRef<str>) -> String {
         .join(" ")
 }
 
+/// Labels of the buttons of action rows, by row. For checking boards in tests
+#[cfg(test)]
+pub fn button_labels(components: &[CreateActionRow]) -> Vec<Vec<String>> {
+    components
+        .iter()
+        .map(|row| {
+            let row = serde_json::to_value(row).unwrap();
+            row["components"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|button| button["label"].as_str().unwrap_or_default().to_string())
+                .collect()
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn check_voice_join_test() {
+        let channel_id = ChannelId::new(1);
+        let voice = Permissions::CONNECT | Permissions::SPEAK;
+
+        assert_eq!(check_voice_join(channel_id, voice, None, 20), Ok(()));
+        assert_eq!(check_voice_join(channel_id, voice, Some(0), 20), Ok(()));
+        assert_eq!(check_voice_join(channel_id, voice, Some(5), 4), Ok(()));
+        assert_eq!(
+            check_voice_join(channel_id, Permissions::CONNECT, None, 0),
+            Err(JoinError::MissingPermissions {
+                permissions: "Speak".into(),
+                channel_id: 1
+            })
+        );
+        assert_eq!(
+            check_voice_join(channel_id, Permissions::ADMINISTRATOR, None, 0),
+            Ok(())
+        );
+        assert_eq!(
+            check_voice_join(channel_id, voice, Some(5), 5),
+            Err(JoinError::ChannelFull {
+                channel_id: 1,
+                limit: 5
+            })
+        );
+        assert_eq!(
+            check_voice_join(channel_id, voice | Permissions::MOVE_MEMBERS, Some(5), 5),
+            Ok(())
+        );
+        assert_eq!(
+            JoinError::MissingPermissions {
+                permissions: "Connect & Speak".into(),
+                channel_id: 1
+            }
+            .to_string(),
+            "I lack Connect & Speak in <#1>. Ask a server admin to grant it to me."
+        );
+    }
+
+    #[test]
+    fn play_feedback_text_test() {
+        let played = |outcome| ButtonPlay {
+            name: "airhorn".into(),
+            outcome,
+        };
+
+        assert_eq!(
+            played(Ok(EnqueueOutcome::Started)).feedback_text(),
+            "▶ `airhorn`"
+        );
+        assert_eq!(
+            played(Ok(EnqueueOutcome::Queued { position: 2 })).feedback_text(),
+            "⏳ `airhorn` queued (position 2)"
+        );
+        assert_eq!(
+            played(Err(AudioError::NotInVoiceChannel)).feedback_emoji(),
+            '❌'
+        );
+    }
+
+    #[test]
+    fn board_labels_test() {
+        assert_eq!(truncate_button_label("airhorn"), "airhorn");
+        let long_name = "é".repeat(100);
+        assert_eq!(
+            truncate_button_label(&long_name),
+            format!("{}...", "é".repeat(77))
+        );
+
+        let mut labels = BoardLabels::default();
+        assert_eq!(labels.label("airhorn"), "airhorn");
+        assert_eq!(labels.label(&long_name), format!("{}...", "é".repeat(77)));
+        let numbered = labels.label(&format!("{long_name}!"));
+        assert_eq!(numbered, format!("{}... (2)", "é".repeat(73)));
+        assert_eq!(numbered.chars().count(), vars::BTN_LABEL_MAX_LEN);
+        assert!(labels.label(&format!("{long_name}?")).ends_with("... (3)"));
+    }
+
+    #[test]
+    fn embed_field_list_test() {
+        assert_eq!("-", embed_field_list(&[]));
+        assert_eq!("a\nb\n", embed_field_list(&["a".into(), "b".into()]));
+
+        let items: Vec<String> = (0..100).map(|i| format!("{i:0>20}")).collect();
+        let value = embed_field_list(&items);
+        assert!(value.len() <= vars::EMBED_FIELD_VALUE_MAX_LEN);
+        assert!(value.ends_with("more"));
+    }
+
+    #[test]
+    fn button_custom_id_test() {
+        let custom_id: String = ButtonCustomId::ApproveRequest(7).into();
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::ApproveRequest(7))
+        ));
+        assert!(matches!(
+            ButtonCustomId::try_from("play::3".to_string()),
+            Ok(ButtonCustomId::PlayAudio(3))
+        ));
+        assert!(ButtonCustomId::try_from("request_deny::x".to_string()).is_err());
+        assert!(ButtonCustomId::try_from("play".to_string()).is_err());
+        assert!(matches!(
+            ButtonCustomId::try_from(String::from(ButtonCustomId::ReplayLast)),
+            Ok(ButtonCustomId::ReplayLast)
+        ));
+
+        let custom_id: String = ButtonCustomId::SearchPage(2, "a::b".into()).into();
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::SearchPage(2, query)) if query == "a::b"
+        ));
+
+        let custom_id: String = ButtonCustomId::ShuffleBoard(12).into();
+        assert_eq!(custom_id, "shuffle::12");
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::ShuffleBoard(12))
+        ));
+        assert!(ButtonCustomId::try_from("shuffle::-1".to_string()).is_err());
+
+        let custom_id: String =
+            ButtonCustomId::MoveBoardSound(3, BoardLayout::Favorites, BoardMove::Down).into();
+        assert_eq!(custom_id, "board_move::3::favorites::down");
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::MoveBoardSound(
+                3,
+                BoardLayout::Favorites,
+                BoardMove::Down
+            ))
+        ));
+
+        let custom_id: String = ButtonCustomId::OnboardingSetup(GuildId::new(42)).into();
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::OnboardingSetup(guild_id)) if guild_id.get() == 42
+        ));
+        assert!(ButtonCustomId::try_from("onboarding_setup::0".to_string()).is_err());
+
+        let custom_id: String = ButtonCustomId::ResolveCollision(5, NameCollision::Suffix).into();
+        assert_eq!(custom_id, "import_collision::5::suffix");
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::ResolveCollision(5, NameCollision::Suffix))
+        ));
+        assert!(ButtonCustomId::try_from("import_collision::5::rename".to_string()).is_err());
+
+        let custom_id: String = ButtonCustomId::RefreshSoundInfo(9).into();
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::RefreshSoundInfo(9))
+        ));
+
+        let custom_id: String = ButtonCustomId::ConfirmPackInstall("a1b2c3".to_string()).into();
+        assert_eq!(custom_id, "pack_install::a1b2c3");
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::ConfirmPackInstall(code)) if code == "a1b2c3"
+        ));
+        assert!(matches!(
+            ButtonCustomId::try_from("pack_install_cancel".to_string()),
+            Ok(ButtonCustomId::CancelPackInstall)
+        ));
+
+        let custom_id: String = ButtonCustomId::CancelOperation(3).into();
+        assert_eq!(custom_id, "operation_cancel::3");
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::CancelOperation(3))
+        ));
+
+        let custom_id: String = ButtonCustomId::ReconcileImportFiles.into();
+        assert_eq!(custom_id, "reconcile_import");
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::ReconcileImportFiles)
+        ));
+
+        let custom_id: String = ButtonCustomId::ConfirmGuildDataDelete(GuildId::new(8)).into();
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::ConfirmGuildDataDelete(guild_id)) if guild_id.get() == 8
+        ));
+        assert!(ButtonCustomId::try_from("guilddata_delete::x".to_string()).is_err());
+
+        let custom_id: String = ButtonCustomId::ToggleAccess.into();
+        assert!(matches!(
+            ButtonCustomId::try_from(custom_id),
+            Ok(ButtonCustomId::ToggleAccess)
+        ));
+    }
+
+    #[test]
+    fn is_long_running_test() {
+        assert!(is_long_running(&crate::commands::library::scan()));
+        assert!(!is_long_running(&crate::commands::admin::echo()));
+    }
+
+    #[test]
+    fn format_timestamp_test() {
+        assert_eq!("0:00", format_timestamp(std::time::Duration::ZERO));
+        assert_eq!(
+            "1:05",
+            format_timestamp(std::time::Duration::from_millis(65_900))
+        );
+        assert_eq!(
+            "61:00",
+            format_timestamp(std::time::Duration::from_secs(3660))
+        );
+    }
+
+    #[test]
+    fn parse_timestamp_test() {
+        let secs = std::time::Duration::from_secs_f64;
+        assert_eq!(Some(secs(90.0)), parse_timestamp("90"));
+        assert_eq!(Some(secs(65.5)), parse_timestamp("1:05.5"));
+        assert_eq!(Some(secs(3723.0)), parse_timestamp(" 1:02:03 "));
+        assert_eq!(None, parse_timestamp("1:2:3:4"));
+        assert_eq!(None, parse_timestamp("-5"));
+        assert_eq!(None, parse_timestamp("1:xx"));
+        assert_eq!(None, parse_timestamp(""));
+    }
+
     #[test]
     fn title_case_test() {
         assert_eq!("This Is A Title", title_case("this is a title"));
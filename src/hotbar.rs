@@ -0,0 +1,65 @@
+use serenity::all::CreateActionRow;
+
+use crate::db::AudioTableRow;
+use crate::helpers;
+
+/// Slots of a hotbar, shown as 3 rows of 3 buttons
+pub const HOTBAR_SLOTS: u32 = 9;
+
+const SLOTS_PER_ROW: u32 = 3;
+
+/// Message content and play buttons of user's hotbar, from its filled `(slot, sound)` slots.
+/// Buttons keep the row of their slot, ie: slot 5 is always in the middle row
+pub fn board(slots: &[(u32, AudioTableRow)]) -> (String, Vec<CreateActionRow>) {
+    if slots.is_empty() {
+        return (
+            "Your hotbar is empty. Add sounds to it with `/hotbar set`".into(),
+            vec![],
+        );
+    }
+
+    let components = (0..HOTBAR_SLOTS / SLOTS_PER_ROW)
+        .filter_map(|row| {
+            let buttons: Vec<_> = slots
+                .iter()
+                .filter(|(slot, _)| (slot - 1) / SLOTS_PER_ROW == row)
+                .map(|(slot, sound)| {
+                    helpers::make_play_button(
+                        sound,
+                        helpers::truncate_button_label(format!("{slot}. {}", sound.name)),
+                    )
+                })
+                .collect();
+            (!buttons.is_empty()).then_some(CreateActionRow::Buttons(buttons))
+        })
+        .collect();
+
+    ("🎛️ Your hotbar".into(), components)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hotbar_board_test() {
+        let slots: Vec<_> = [1, 3, 7, 9]
+            .into_iter()
+            .map(|slot| (slot, AudioTableRow::test_row(slot as i64)))
+            .collect();
+
+        // middle row is empty
+        let (content, components) = board(&slots);
+        assert_eq!(content, "🎛️ Your hotbar");
+        assert_eq!(
+            helpers::button_labels(&components),
+            vec![
+                vec!["1. Sound 1", "3. Sound 3"],
+                vec!["7. Sound 7", "9. Sound 9"]
+            ]
+        );
+
+        let (_, components) = board(&[]);
+        assert!(components.is_empty());
+    }
+}
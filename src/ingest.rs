@@ -0,0 +1,425 @@
+use std::path;
+
+use serenity::all::User;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+use crate::audio::{self, AudioFile, AudioFileInfo, AudioFileValidator, AudioTrackInfo};
+use crate::commands::PoiseError;
+use crate::common::{LogResult, UserData};
+use crate::db::{AudioTableRow, AudioTableRowInsert, Tags, UniqueAudioTableCol};
+use crate::errors::{AudioError, OperationError};
+use crate::features::{self, FeatureFlag};
+use crate::fingerprint;
+use crate::moderation::{self, ModerationRequest};
+use crate::naming;
+use crate::operations;
+use crate::transcribe;
+
+/// Step of the audio ingestion pipeline, in the order they run
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub enum IngestStage {
+    /// Downloading audio from url to temp dir
+    #[default]
+    Download,
+    /// Checking file exists, and is within the size limit
+    Validate,
+    Hash,
+    /// Converting audio to MP3. MP3 is the only format accepted, so files pass through as is
+    Transcode,
+    /// Leveling loudness of audio. Not done yet, so files pass through as is
+    Normalize,
+    /// Checking audio codec and duration
+    Probe,
+    /// Matching audio against banned sounds. Skipped when no sounds are banned
+    Fingerprint,
+    /// Asking the moderation hooks whether audio can go live. Skipped without hooks
+    Moderate,
+    /// Moving file to audio dir
+    Store,
+    /// Writing sound to database
+    Index,
+}
+
+impl std::fmt::Display for IngestStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let label = match self {
+            Self::Download => "downloading",
+            Self::Validate => "validating",
+            Self::Hash => "hashing",
+            Self::Transcode => "transcoding",
+            Self::Normalize => "normalizing",
+            Self::Probe => "probing",
+            Self::Fingerprint => "fingerprinting",
+            Self::Moderate => "moderating",
+            Self::Store => "storing",
+            Self::Index => "indexing",
+        };
+        write!(f, "{label}")
+    }
+}
+
+/// Where audio of a sound comes from
+pub enum IngestSource {
+    /// Link to an MP3 file. Only when url imports are enabled
+    Url(String),
+    /// Url of an MP3 file attached to a discord message
+    Attachment(String),
+    /// File in temp dir, ie: output of ffmpeg. The file is moved or removed by ingestion
+    TempFile(path::PathBuf),
+}
+
+/// What audio is ingested as
+pub enum IngestTarget<'a> {
+    /// New sound
+    New {
+        name: &'a str,
+        tags: Tags,
+        author: &'a User,
+    },
+    /// Swap audio of existing sound. The sound keeps its id, so its tags, play history, favorites,
+    /// and existing board buttons are untouched
+    Replace(&'a AudioTableRow),
+}
+
+/// Audio file that passed validation, ready to be stored
+struct PreparedAudio {
+    path: path::PathBuf,
+    hash: String,
+    track: AudioTrackInfo,
+}
+
+/// Optional listener of the stage ingestion is at, and token cancelling ingestion between stages
+#[derive(Default)]
+struct Progress {
+    sender: Option<watch::Sender<IngestStage>>,
+    cancel: CancellationToken,
+}
+
+impl Progress {
+    fn stage(&self, stage: IngestStage) -> Result<(), OperationError> {
+        operations::check(&self.cancel)?;
+        log::debug!("Ingestion stage: {stage}");
+        if let Some(sender) = &self.sender {
+            sender.send_replace(stage);
+        }
+        Ok(())
+    }
+}
+
+/// Single path audio takes into the soundboard, whether it's uploaded, imported, merged, or trimmed.
+/// Runs download → validate → hash → transcode → normalize → probe → fingerprint → moderate → store → index
+pub struct Ingest<'a> {
+    data: &'a UserData,
+    progress: Progress,
+}
+
+impl<'a> Ingest<'a> {
+    pub fn new(data: &'a UserData) -> Self {
+        Self {
+            data,
+            progress: Progress::default(),
+        }
+    }
+
+    /// Send each stage to progress as it starts
+    pub fn progress(mut self, progress: watch::Sender<IngestStage>) -> Self {
+        self.progress.sender = Some(progress);
+        self
+    }
+
+    /// Stop ingestion at the next stage once cancelled. Cancelled downloads stop right away
+    pub fn cancel(mut self, cancel: CancellationToken) -> Self {
+        self.progress.cancel = cancel;
+        self
+    }
+
+    pub async fn run(
+        &self,
+        source: IngestSource,
+        target: IngestTarget<'_>,
+    ) -> Result<AudioFile, PoiseError> {
+        if let IngestTarget::New { name, .. } = &target {
+            naming::check(&self.data.config.get(), name)?;
+            self.check_name_free(name)?;
+        }
+
+        let temp_audio_file = match &source {
+            IngestSource::Url(url) | IngestSource::Attachment(url) => {
+                log::info!("Ingesting audio url - {url}");
+                if matches!(source, IngestSource::Url(_)) {
+                    features::check(self.data, FeatureFlag::UrlImports)?;
+                }
+
+                self.progress.stage(IngestStage::Download)?;
+                let max_size = self.data.config.get().max_audio_file_size;
+                audio::download_audio_url_temp(url, max_size, &self.progress.cancel).await?
+            }
+            IngestSource::TempFile(path) => path.clone(),
+        };
+
+        let result = self.ingest_file(&temp_audio_file, target).await;
+        if result.is_err() && temp_audio_file.exists() {
+            std::fs::remove_file(&temp_audio_file)
+                .log_err_msg("Failed removing temp audio file")
+                .ok();
+        }
+
+        result
+    }
+
+    async fn ingest_file(
+        &self,
+        temp_audio_file: &path::Path,
+        target: IngestTarget<'_>,
+    ) -> Result<AudioFile, PoiseError> {
+        if let IngestTarget::New { name, .. } = &target {
+            // name may have been taken while downloading
+            self.check_name_free(name)?;
+        }
+
+        let config = self.data.config.get();
+        let validator = AudioFileValidator::default()
+            .max_audio_duration(config.max_audio_file_duration)
+            .max_file_size(config.max_audio_file_size)
+            .reject_uuid_files(false);
+        let prepared = prepare(&validator, temp_audio_file, &self.progress)?;
+        self.check_not_banned(&prepared)?;
+
+        if moderation::enabled(&config) {
+            self.progress.stage(IngestStage::Moderate)?;
+            let (action, name, tags, author) = match &target {
+                IngestTarget::New { name, tags, author } => {
+                    ("new", *name, tags.as_slice(), Some(*author))
+                }
+                IngestTarget::Replace(row) => {
+                    ("replace", row.name.as_str(), row.tags.as_slice(), None)
+                }
+            };
+            let request = ModerationRequest {
+                action,
+                name,
+                tags,
+                author_id: author.map(|author| author.id.get()),
+                author_name: author.map(|author| author.name.as_str()),
+                file_hash: &prepared.hash,
+                file_size: std::fs::metadata(&prepared.path)?.len(),
+                duration_ms: prepared.track.duration.as_millis() as u64,
+                audio_path: Some(&prepared.path),
+            };
+            moderation::check(&config, &request).await?;
+        }
+
+        log::info!(
+            "Storing audio track. Duration: {:.2}s",
+            prepared.track.duration.as_secs_f64()
+        );
+        self.progress.stage(IngestStage::Store)?;
+        let audio_file = store(&config.audio_dir, &prepared.path)?;
+
+        self.progress.stage(IngestStage::Index)?;
+        let info = AudioFileInfo {
+            stat: audio_file.stat().log_err()?,
+            hash: prepared.hash,
+        };
+        let table = self.data.audio_table();
+        let row_id = match target {
+            IngestTarget::New { name, tags, author } => {
+                table
+                    .insert_audio_row(AudioTableRowInsert {
+                        name: name.into(),
+                        audio_file: audio_file.clone(),
+                        author_global_name: author.global_name.clone(),
+                        author_id: Some(author.id.into()),
+                        author_name: Some(author.name.clone()),
+                        tags,
+                        created_at: chrono::Utc::now(),
+                        file_hash: Some(info.hash),
+                        file_size: Some(info.stat.size),
+                        file_modified_at: Some(info.stat.modified_at),
+                    })
+                    .log_err()?;
+                table
+                    .find_audio_row(UniqueAudioTableCol::Name(name.into()))
+                    .map(|row| row.id)
+            }
+            IngestTarget::Replace(row) => {
+                log::info!(
+                    "Replacing audio file of sound. Name: {}, File: {}",
+                    row.name,
+                    row.audio_file.to_string_lossy()
+                );
+                table.update_file_info(row.id, &audio_file, &info)?;
+
+                if audio_file.as_path() != row.audio_file.as_path() {
+                    row.audio_file.delete();
+                }
+                Some(row.id)
+            }
+        };
+
+        if let Some(row_id) = row_id {
+            transcribe::queue(self.data, row_id, audio_file.as_path_buf());
+        }
+
+        Ok(audio_file)
+    }
+
+    /// Reject audio of a sound deleted with `ban`, even re-encoded, trimmed, or renamed
+    fn check_not_banned(&self, prepared: &PreparedAudio) -> Result<(), PoiseError> {
+        let banned = self.data.banned_sounds_table().rows()?;
+        if banned.is_empty() {
+            return Ok(());
+        }
+
+        self.progress.stage(IngestStage::Fingerprint)?;
+        let fingerprint = fingerprint::fingerprint_file(&prepared.path)
+            .log_err_msg("Failed fingerprinting audio")
+            .ok();
+        match banned
+            .iter()
+            .find(|row| row.matches(&prepared.hash, fingerprint.as_ref()))
+        {
+            Some(row) => Err(AudioError::BannedSound {
+                name: row.name.clone(),
+            }
+            .into())
+            .log_err(),
+            None => Ok(()),
+        }
+    }
+
+    fn check_name_free(&self, name: &str) -> Result<(), PoiseError> {
+        match self
+            .data
+            .audio_table()
+            .find_audio_row(UniqueAudioTableCol::Name(name.into()))
+        {
+            Some(_) => Err(format!("Can't add sound `{name}`. It already exists").into()).log_err(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Validate, hash, transcode, normalize, and probe audio file, before it's stored
+fn prepare(
+    validator: &AudioFileValidator,
+    path: &path::Path,
+    progress: &Progress,
+) -> Result<PreparedAudio, PoiseError> {
+    progress.stage(IngestStage::Validate)?;
+    validator.check_file(path)?;
+
+    progress.stage(IngestStage::Hash)?;
+    let hash = audio::hash_file(path)?;
+
+    progress.stage(IngestStage::Transcode)?;
+    progress.stage(IngestStage::Normalize)?;
+
+    progress.stage(IngestStage::Probe)?;
+    let track = validator.check_track(path)?;
+
+    Ok(PreparedAudio {
+        path: path.to_path_buf(),
+        hash,
+        track,
+    })
+}
+
+/// Validate, hash, and probe audio file already in audio dir, ie: one found by a scan
+pub fn inspect(
+    validator: &AudioFileValidator,
+    audio_file: &AudioFile,
+) -> Result<AudioFileInfo, PoiseError> {
+    validator.check_file(audio_file.as_path())?;
+    let info = audio_file.info()?;
+    validator.check_track(audio_file.as_path())?;
+
+    Ok(info)
+}
+
+/// Attempts to move file to audio dir. Will attempt copy if move fails
+/// Moves can fail if target file and destination audio directory are on separate partitions of file systems
+fn store(audio_dir: &path::Path, target_file: &path::Path) -> Result<AudioFile, PoiseError> {
+    log::info!(
+        "Move file: {} to audio dir: {}",
+        target_file.to_string_lossy(),
+        audio_dir.to_string_lossy()
+    );
+
+    if !target_file.exists() {
+        return Err("Target file doesn't exist".into()).log_err();
+    }
+
+    if !target_file.is_file() {
+        return Err("Target file is not a file.".into()).log_err();
+    }
+
+    let target_file_name = target_file
+        .file_name()
+        .ok_or("Failed to get target path file name")
+        .log_err()?;
+    let dest_file = audio_dir.join(target_file_name);
+
+    match std::fs::rename(target_file, &dest_file) {
+        Ok(_) => Ok(AudioFile::new(dest_file)),
+        Err(err) => {
+            log::error!(
+                "Failed to move target file to audio dir - {err}. Attempting copy instead."
+            );
+            std::fs::copy(target_file, &dest_file)
+                .log_err_msg("Failed to copy target file to audio dir")?;
+            std::fs::remove_file(target_file)
+                .log_err_msg("Failed to remove target file after copy")
+                .ok();
+
+            log::info!(
+                "Copied target file: {} to destination: {}",
+                target_file.to_string_lossy(),
+                dest_file.to_string_lossy()
+            );
+
+            Ok(AudioFile::new(dest_file))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prepare_invalid_audio_test() {
+        let dir = std::env::temp_dir().join(crate::helpers::uuid_v4_str());
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("not-audio.mp3");
+        std::fs::write(&path, b"not audio").unwrap();
+
+        let (progress_tx, progress_rx) = watch::channel(IngestStage::default());
+        let progress = Progress {
+            sender: Some(progress_tx),
+            cancel: CancellationToken::new(),
+        };
+
+        let too_small = AudioFileValidator::new().max_file_size(4);
+        assert!(prepare(&too_small, &path, &progress).is_err());
+        assert_eq!(*progress_rx.borrow(), IngestStage::Validate);
+
+        assert!(prepare(&AudioFileValidator::new(), &path, &progress).is_err());
+        assert_eq!(*progress_rx.borrow(), IngestStage::Probe);
+        assert!(IngestStage::Hash < IngestStage::Probe);
+
+        progress.cancel.cancel();
+        let cancelled = prepare(&AudioFileValidator::new(), &path, &progress);
+        assert!(cancelled.is_err_and(|err| err.is::<OperationError>()));
+        assert_eq!(*progress_rx.borrow(), IngestStage::Probe);
+
+        let audio_dir = dir.join("sounds");
+        std::fs::create_dir(&audio_dir).unwrap();
+        let audio_file = store(&audio_dir, &path).unwrap();
+        assert_eq!(audio_file.as_path(), audio_dir.join("not-audio.mp3"));
+        assert!(!path.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,455 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use serenity::all::{Cache, ChannelId, GuildId, Http};
+use tokio::sync::Semaphore;
+
+use crate::birthdays;
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::config::SharedConfig;
+use crate::db::{
+    maintenance, DbPool, JobRow, JobRowInsert, JobsTable, PlayHistoryTable, SettingsTable, Table,
+};
+use crate::event_mode;
+use crate::playback::Playback;
+use crate::prefetch::{self, SoundCache};
+use crate::retention::{self, RetentionPolicy};
+use crate::settings;
+use crate::telemetry::{self, TelemetryReport};
+use crate::themes;
+use crate::undo;
+
+/// How often the jobs table is checked for due jobs
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Jobs run at once, across all guilds
+const MAX_CONCURRENT_JOBS: usize = 4;
+
+/// Runs of a failing one off job before it's marked failed
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Finished one off jobs are kept this many days, so they show in `/jobs list`
+const FINISHED_JOB_DAYS: i64 = 7;
+
+/// Kinds of background work. Stored by name in the jobs table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JobKind {
+    /// Schedules a message retention cleanup of each guild with message retention set
+    MessageRetention,
+    /// Deletes a guild's tracked bot messages not kept by its retention policy
+    GuildMessageRetention,
+    /// Removes old finished one off jobs, and library operations too old to undo
+    PruneJobs,
+    /// Checks integrity of the database, and vacuums & analyzes it
+    DatabaseMaintenance,
+    /// Rolls up plays older than each guild's play history retention into daily play counts
+    PlayHistoryRollup,
+    /// Features the theme of the day on each guild's board
+    BoardThemes,
+    /// Turns off a guild's event mode once it ends
+    EndEventMode,
+    /// Greets members in voice on their birthday, playing their birthday sound
+    Birthdays,
+    /// Sends the daily telemetry report, when telemetry is turned on
+    Telemetry,
+    /// Reads the most played sounds into memory, so they start playing right away
+    PrefetchSounds,
+}
+
+impl JobKind {
+    /// Jobs scheduled when the bot starts, that reschedule themselves after each run
+    pub const RECURRING: [JobKind; 8] = [
+        JobKind::MessageRetention,
+        JobKind::PruneJobs,
+        JobKind::DatabaseMaintenance,
+        JobKind::PlayHistoryRollup,
+        JobKind::BoardThemes,
+        JobKind::Birthdays,
+        JobKind::Telemetry,
+        JobKind::PrefetchSounds,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::MessageRetention => "message_retention",
+            Self::GuildMessageRetention => "guild_message_retention",
+            Self::PruneJobs => "prune_jobs",
+            Self::DatabaseMaintenance => "database_maintenance",
+            Self::PlayHistoryRollup => "play_history_rollup",
+            Self::BoardThemes => "board_themes",
+            Self::EndEventMode => "end_event_mode",
+            Self::Birthdays => "birthdays",
+            Self::Telemetry => "telemetry",
+            Self::PrefetchSounds => "prefetch_sounds",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "message_retention" => Some(Self::MessageRetention),
+            "guild_message_retention" => Some(Self::GuildMessageRetention),
+            "prune_jobs" => Some(Self::PruneJobs),
+            "database_maintenance" => Some(Self::DatabaseMaintenance),
+            "play_history_rollup" => Some(Self::PlayHistoryRollup),
+            "board_themes" => Some(Self::BoardThemes),
+            "end_event_mode" => Some(Self::EndEventMode),
+            "birthdays" => Some(Self::Birthdays),
+            "telemetry" => Some(Self::Telemetry),
+            "prefetch_sounds" => Some(Self::PrefetchSounds),
+            _ => None,
+        }
+    }
+
+    /// Time between runs of recurring jobs. None for one off jobs
+    pub fn interval(&self) -> Option<Duration> {
+        match self {
+            Self::MessageRetention => Some(retention::RETENTION_INTERVAL),
+            Self::GuildMessageRetention => None,
+            Self::PruneJobs => Some(Duration::from_secs(24 * 60 * 60)),
+            Self::DatabaseMaintenance => Some(Duration::from_secs(7 * 24 * 60 * 60)),
+            Self::PlayHistoryRollup => Some(Duration::from_secs(24 * 60 * 60)),
+            // themes switch at midnight of each guild's timezone
+            Self::BoardThemes => Some(Duration::from_secs(60 * 60)),
+            Self::EndEventMode => None,
+            // birthdays start at midnight of each guild's timezone, and members join voice
+            // throughout the day
+            Self::Birthdays => Some(Duration::from_secs(10 * 60)),
+            Self::Telemetry => Some(Duration::from_secs(24 * 60 * 60)),
+            Self::PrefetchSounds => Some(Duration::from_secs(30 * 60)),
+        }
+    }
+}
+
+impl std::fmt::Display for JobKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// One off job of kind, run as soon as a slot is free
+pub fn one_off(kind: JobKind, guild_id: Option<GuildId>) -> JobRowInsert {
+    JobRowInsert {
+        kind: kind.as_str().into(),
+        guild_id: guild_id.map(|id| id.get()),
+        payload: None,
+        run_at: chrono::Utc::now(),
+        interval_secs: None,
+    }
+}
+
+/// Runs due jobs of the jobs table in the background, a few at a time. Jobs live in the database,
+/// so scheduled work isn't lost on restart
+#[derive(Clone)]
+struct Scheduler {
+    http: Arc<Http>,
+    cache: Arc<Cache>,
+    db_pool: DbPool,
+    config: Arc<SharedConfig>,
+    playback: Arc<Playback>,
+    sound_cache: Arc<SoundCache>,
+    slots: Arc<Semaphore>,
+}
+
+/// Start scheduler. Jobs left running by the last shutdown are run again, and recurring jobs
+/// are scheduled if they aren't already
+pub fn spawn_scheduler(
+    http: Arc<Http>,
+    cache: Arc<Cache>,
+    db_pool: DbPool,
+    config: Arc<SharedConfig>,
+    playback: Arc<Playback>,
+    sound_cache: Arc<SoundCache>,
+) {
+    let scheduler = Scheduler {
+        http,
+        cache,
+        db_pool,
+        config,
+        playback,
+        sound_cache,
+        slots: Arc::new(Semaphore::new(MAX_CONCURRENT_JOBS)),
+    };
+
+    tokio::spawn(async move {
+        scheduler
+            .init()
+            .log_err_msg("Failed initializing job scheduler")
+            .ok();
+
+        let start = tokio::time::Instant::now() + POLL_INTERVAL;
+        let mut interval = tokio::time::interval_at(start, POLL_INTERVAL);
+
+        loop {
+            interval.tick().await;
+            scheduler.run_due_jobs().await;
+        }
+    });
+}
+
+impl Scheduler {
+    fn jobs_table(&self) -> Result<JobsTable, PoiseError> {
+        Ok(JobsTable::new(self.db_pool.get()?))
+    }
+
+    fn init(&self) -> Result<(), PoiseError> {
+        // scheduler starts before the ready event creates the other tables
+        let table = self.jobs_table()?;
+        table.create_table();
+
+        let requeued = table.requeue_running()?;
+        if requeued > 0 {
+            log::info!("Requeued {requeued} jobs interrupted by restart");
+        }
+
+        for kind in JobKind::RECURRING {
+            let Some(interval) = kind.interval() else {
+                continue;
+            };
+
+            let row = JobRowInsert {
+                kind: kind.as_str().into(),
+                guild_id: None,
+                payload: None,
+                run_at: chrono::Utc::now() + chrono::Duration::from_std(interval)?,
+                interval_secs: Some(interval.as_secs() as i64),
+            };
+            if table.ensure_scheduled(&row)? {
+                log::info!("Scheduled recurring job {kind}");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn run_due_jobs(&self) {
+        let due = self
+            .jobs_table()
+            .and_then(|table| table.due_rows(chrono::Utc::now()))
+            .log_err_msg("Failed reading due jobs")
+            .unwrap_or_default();
+
+        for job in due {
+            // waits for a slot, so jobs past the limit stay pending until the next poll claims them
+            let Ok(slot) = self.slots.clone().acquire_owned().await else {
+                return;
+            };
+
+            let claimed = self
+                .jobs_table()
+                .and_then(|table| table.claim(job.id))
+                .log_err_msg("Failed claiming job")
+                .unwrap_or(false);
+            if !claimed {
+                continue;
+            }
+
+            let scheduler = self.clone();
+            tokio::spawn(async move {
+                scheduler.run_job(job).await;
+                drop(slot);
+            });
+        }
+    }
+
+    async fn run_job(&self, job: JobRow) {
+        log::info!(
+            "Running job {} {}. guild_id: {:?}",
+            job.id,
+            job.kind,
+            job.guild_id
+        );
+
+        let kind = JobKind::parse(&job.kind);
+        let result = match kind {
+            Some(kind) => self.run(kind, &job).await,
+            None => Err(format!("Unknown job kind {}", job.kind).into()),
+        };
+
+        let error = match &result {
+            Ok(()) => None,
+            Err(err) => {
+                log::error!("Job {} {} failed - {err}", job.id, job.kind);
+                Some(err.to_string())
+            }
+        };
+
+        let now = chrono::Utc::now();
+        let next_run_at = match (kind.and_then(|kind| kind.interval()), &error) {
+            (Some(interval), _) => chrono::Duration::from_std(interval)
+                .ok()
+                .map(|interval| now + interval),
+            // retry failed one off jobs, backing off a minute more each attempt. Claiming the
+            // job counted this run, so attempts is one behind
+            (None, Some(_)) if kind.is_some() && job.attempts + 1 < MAX_ATTEMPTS => {
+                Some(now + chrono::Duration::minutes(job.attempts as i64 + 1))
+            }
+            (None, _) => None,
+        };
+
+        self.jobs_table()
+            .and_then(|table| table.finish(job.id, error.as_deref(), next_run_at))
+            .log_err_msg(format!("Failed finishing job {}", job.id))
+            .ok();
+    }
+
+    async fn run(&self, kind: JobKind, job: &JobRow) -> Result<(), PoiseError> {
+        match kind {
+            JobKind::MessageRetention => {
+                let table = self.jobs_table()?;
+                for (guild_id, _) in retention::read_guild_policies(&self.db_pool)? {
+                    table.ensure_scheduled(&one_off(
+                        JobKind::GuildMessageRetention,
+                        Some(guild_id),
+                    ))?;
+                }
+                Ok(())
+            }
+            JobKind::GuildMessageRetention => {
+                let guild_id = GuildId::new(job.guild_id.ok_or("Job requires a guild")?);
+                let policy =
+                    RetentionPolicy::guild(&SettingsTable::new(self.db_pool.get()?), guild_id);
+                let deleted =
+                    retention::cleanup(&self.http, &self.db_pool, guild_id, None, policy, false)
+                        .await?;
+                if deleted > 0 {
+                    log::info!("Cleaned up {deleted} old bot messages. guild_id: {guild_id}");
+                }
+                Ok(())
+            }
+            JobKind::PruneJobs => {
+                let before = chrono::Utc::now() - chrono::Duration::days(FINISHED_JOB_DAYS);
+                let deleted = self.jobs_table()?.delete_finished(before)?;
+                log::info!("Pruned {deleted} finished jobs");
+
+                let pruned = undo::prune(&self.db_pool)?;
+                log::info!("Pruned {pruned} library operations too old to undo");
+                Ok(())
+            }
+            JobKind::DatabaseMaintenance => {
+                let db_pool = self.db_pool.clone();
+                let report =
+                    tokio::task::spawn_blocking(move || maintenance::run(&db_pool.get()?, true))
+                        .await??;
+                log::info!("{report}");
+
+                match report.integrity_errors.is_empty() {
+                    true => Ok(()),
+                    false => Err(format!(
+                        "Database integrity check failed - {}",
+                        report.integrity_errors.join(", ")
+                    )
+                    .into()),
+                }
+            }
+            JobKind::PlayHistoryRollup => {
+                let db_pool = self.db_pool.clone();
+                let rolled_up =
+                    tokio::task::spawn_blocking(move || roll_up_play_history(&db_pool)).await??;
+                log::info!("Rolled up {rolled_up} plays into daily play counts");
+                Ok(())
+            }
+            JobKind::BoardThemes => {
+                themes::switch_themes(&self.db_pool)?;
+                Ok(())
+            }
+            JobKind::Birthdays => {
+                birthdays::celebrate(&self.http, &self.cache, &self.db_pool, &self.playback)
+                    .await?;
+                Ok(())
+            }
+            JobKind::Telemetry => {
+                let config = self.config.get();
+                if telemetry::endpoint(&config).is_none() {
+                    return Ok(());
+                }
+                let report = TelemetryReport::collect(&self.db_pool, self.cache.guild_count())?;
+                telemetry::send(&config, &report).await
+            }
+            JobKind::PrefetchSounds => {
+                let budget = self.config.get().prefetch_memory_mb as usize * 1024 * 1024;
+                let cached =
+                    prefetch::prefetch_hot_sounds(&self.sound_cache, &self.db_pool, budget).await?;
+                log::debug!("Prefetched sounds: {cached}");
+                Ok(())
+            }
+            JobKind::EndEventMode => {
+                let guild_id = GuildId::new(job.guild_id.ok_or("Job requires a guild")?);
+                let settings_table = SettingsTable::new(self.db_pool.get()?);
+                if !event_mode::end_if_over(&settings_table, guild_id)? {
+                    return Ok(());
+                }
+                log::info!("Event mode ended. guild_id: {guild_id}");
+
+                let channel_id = settings::audit_channel(&settings_table, guild_id).or(self
+                    .config
+                    .get()
+                    .audit_channel_id
+                    .map(ChannelId::new));
+                if let Some(channel_id) = channel_id {
+                    channel_id
+                        .say(
+                            &self.http,
+                            "Event mode ended. Cooldowns, play quotas, and queue limits are back",
+                        )
+                        .await
+                        .log_err_msg("Failed sending event mode audit message")
+                        .ok();
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Roll up plays of guilds with play history retention set. Plays are rolled up by whole days,
+/// so a day's plays end up in one daily play count. Returns number of plays rolled up
+fn roll_up_play_history(db_pool: &DbPool) -> Result<usize, PoiseError> {
+    let settings_table = SettingsTable::new(db_pool.get()?);
+    let history_table = PlayHistoryTable::new(db_pool.get()?);
+
+    let today = chrono::Utc::now()
+        .date_naive()
+        .and_time(chrono::NaiveTime::MIN)
+        .and_utc();
+    let mut rolled_up = 0;
+    for guild_id in history_table.guild_ids()? {
+        if let Some(retention) =
+            settings::play_history_retention(&settings_table, GuildId::new(guild_id))
+        {
+            rolled_up += history_table.roll_up(guild_id, today - retention)?;
+        }
+    }
+
+    Ok(rolled_up)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_kind_test() {
+        for kind in [
+            JobKind::MessageRetention,
+            JobKind::GuildMessageRetention,
+            JobKind::PruneJobs,
+            JobKind::DatabaseMaintenance,
+            JobKind::PlayHistoryRollup,
+            JobKind::BoardThemes,
+            JobKind::EndEventMode,
+            JobKind::Birthdays,
+            JobKind::Telemetry,
+            JobKind::PrefetchSounds,
+        ] {
+            assert_eq!(JobKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(JobKind::parse("unknown"), None);
+
+        assert!(JobKind::RECURRING
+            .iter()
+            .all(|kind| kind.interval().is_some()));
+        assert_eq!(JobKind::GuildMessageRetention.interval(), None);
+        assert_eq!(JobKind::EndEventMode.interval(), None);
+    }
+}
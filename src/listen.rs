@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serenity::all::GuildId;
+use serenity::async_trait;
+use songbird::{Call, CoreEvent, Event, EventContext, EventHandler as VoiceEventHandler};
+
+use crate::common::UserData;
+use crate::playback::Playback;
+use crate::settings;
+
+/// Sample rate of audio handed to speech recognition
+pub const SAMPLE_RATE: usize = 16_000;
+
+/// Voice is received as 48kHz stereo, every third frame is kept
+const DECIMATION: usize = 48_000 / SAMPLE_RATE;
+
+/// Silent 20ms voice ticks ending an utterance
+const SILENT_TICKS: u32 = 25;
+
+/// Utterances shorter than this are coughs & clicks, not commands
+const MIN_UTTERANCE_SAMPLES: usize = SAMPLE_RATE * 3 / 10;
+
+/// 20ms voice ticks of talking, with pauses shorter than an utterance break, that count as
+/// sustained speech rather than a quick laugh or "nice"
+const SUSTAINED_SPEECH_TICKS: u32 = 50;
+
+/// Longest utterance kept. "hey soundboard, play" and a sound name fit in a few seconds
+const MAX_UTTERANCE_SAMPLES: usize = SAMPLE_RATE * 6;
+
+/// Downmix interleaved 48kHz stereo voice to 16kHz mono samples between -1 and 1
+pub fn downmix(stereo: &[i16]) -> Vec<f32> {
+    stereo
+        .chunks_exact(2)
+        .step_by(DECIMATION)
+        .map(|frame| (frame[0] as f32 + frame[1] as f32) / 2.0 / i16::MAX as f32)
+        .collect()
+}
+
+#[derive(Default)]
+struct SpeakerBuffer {
+    samples: Vec<f32>,
+    silent_ticks: u32,
+}
+
+/// Splits received voice into utterances, per speaker. An utterance ends once its speaker has
+/// been silent for half a second
+#[derive(Default)]
+pub struct Utterances {
+    speakers: HashMap<u32, SpeakerBuffer>,
+}
+
+impl Utterances {
+    /// Add 20ms of speaker's voice, as 48kHz stereo
+    pub fn push(&mut self, ssrc: u32, stereo: &[i16]) {
+        let speaker = self.speakers.entry(ssrc).or_default();
+        speaker.silent_ticks = 0;
+
+        let room = MAX_UTTERANCE_SAMPLES.saturating_sub(speaker.samples.len());
+        let samples = downmix(stereo);
+        speaker.samples.extend(samples.into_iter().take(room));
+    }
+
+    /// Speaker was silent for a tick. Returns their finished utterance, as 16kHz mono
+    pub fn silence(&mut self, ssrc: u32) -> Option<Vec<f32>> {
+        let speaker = self.speakers.get_mut(&ssrc)?;
+        speaker.silent_ticks += 1;
+        if speaker.silent_ticks < SILENT_TICKS {
+            return None;
+        }
+
+        let speaker = self.speakers.remove(&ssrc)?;
+        match speaker.samples.len() >= MIN_UTTERANCE_SAMPLES {
+            true => Some(speaker.samples),
+            false => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SpeechEvent {
+    /// Members have been talking for a second
+    Started,
+    /// Members have been silent long enough after talking
+    Ended,
+}
+
+/// Tells when members of a voice channel start & stop talking, from whether anyone spoke each tick
+pub struct SpeechDetector {
+    /// Silent ticks ending speech
+    end_ticks: u32,
+    speaking_ticks: u32,
+    silent_ticks: u32,
+    talking: bool,
+}
+
+impl SpeechDetector {
+    pub fn new(end_after: std::time::Duration) -> Self {
+        Self {
+            end_ticks: (end_after.as_millis() / 20).max(1) as u32,
+            speaking_ticks: 0,
+            silent_ticks: 0,
+            talking: false,
+        }
+    }
+
+    /// Record a 20ms voice tick. Returns whether speech started or ended with it
+    pub fn tick(&mut self, speaking: bool) -> Option<SpeechEvent> {
+        if speaking {
+            self.silent_ticks = 0;
+            self.speaking_ticks += 1;
+            if !self.talking && self.speaking_ticks >= SUSTAINED_SPEECH_TICKS {
+                self.talking = true;
+                return Some(SpeechEvent::Started);
+            }
+            return None;
+        }
+
+        self.silent_ticks += 1;
+        if self.silent_ticks >= SILENT_TICKS {
+            self.speaking_ticks = 0;
+        }
+        if self.talking && self.silent_ticks >= self.end_ticks {
+            self.talking = false;
+            return Some(SpeechEvent::Ended);
+        }
+        None
+    }
+}
+
+/// Pause ambience while members talk in call, when guild has speech pause on
+pub fn listen_for_speech(data: &UserData, call: &mut Call, guild_id: GuildId) {
+    let Some(end_after) = settings::speech_pause(&data.settings_table(), guild_id) else {
+        return;
+    };
+
+    let listener = SpeechPauseListener {
+        playback: data.playback.clone(),
+        guild_id,
+        detector: Mutex::new(SpeechDetector::new(end_after)),
+    };
+    call.add_global_event(CoreEvent::VoiceTick.into(), listener);
+    log::info!("Pausing ambience on speech. guild_id: {guild_id}");
+}
+
+struct SpeechPauseListener {
+    playback: Arc<Playback>,
+    guild_id: GuildId,
+    detector: Mutex<SpeechDetector>,
+}
+
+#[async_trait]
+impl VoiceEventHandler for SpeechPauseListener {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::VoiceTick(tick) = ctx {
+            // discord only sends voice while members talk, so any packet is speech
+            let speaking = tick.speaking.values().any(|voice| voice.packet.is_some());
+            let event = self.detector.lock().unwrap().tick(speaking);
+            match event {
+                Some(SpeechEvent::Started) => self.playback.speech_started(self.guild_id),
+                Some(SpeechEvent::Ended) => self.playback.speech_ended(self.guild_id),
+                None => {}
+            }
+        }
+
+        None
+    }
+}
+
+/// Lowercase words of text, without punctuation
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Sound name of a transcribed voice command, ie: "Hey, soundboard! Play airhorn." → `airhorn`.
+/// None when the wake phrase wasn't said
+pub fn parse_command(transcript: &str, wake_phrase: &str) -> Option<String> {
+    let wake = words(wake_phrase);
+    let said = words(transcript);
+    if wake.is_empty() {
+        return None;
+    }
+
+    let start = said
+        .windows(wake.len())
+        .position(|window| window == wake.as_slice())?;
+    let mut rest = &said[start + wake.len()..];
+    if rest.first().is_some_and(|word| word == "play") {
+        rest = &rest[1..];
+    }
+
+    match rest.is_empty() {
+        true => None,
+        false => Some(rest.join(" ")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_command_test() {
+        let wake = "hey soundboard";
+        assert_eq!(
+            parse_command("Hey, soundboard! Play airhorn.", wake),
+            Some("airhorn".into())
+        );
+        assert_eq!(
+            parse_command(" um hey soundboard sad trombone", wake),
+            Some("sad trombone".into())
+        );
+        assert_eq!(parse_command("hey soundboard, play", wake), None);
+        assert_eq!(parse_command("play airhorn", wake), None);
+        assert_eq!(parse_command("hey sound board play airhorn", wake), None);
+    }
+
+    #[test]
+    fn speech_detector_test() {
+        let mut detector = SpeechDetector::new(std::time::Duration::from_secs(2));
+
+        // short bursts of speech aren't sustained
+        for _ in 0..SUSTAINED_SPEECH_TICKS - 1 {
+            assert_eq!(detector.tick(true), None);
+        }
+        for _ in 0..SILENT_TICKS {
+            assert_eq!(detector.tick(false), None);
+        }
+
+        // speech with short pauses is
+        for _ in 0..SUSTAINED_SPEECH_TICKS - 10 {
+            assert_eq!(detector.tick(true), None);
+        }
+        assert_eq!(detector.tick(false), None);
+        for _ in 1..10 {
+            assert_eq!(detector.tick(true), None);
+        }
+        assert_eq!(detector.tick(true), Some(SpeechEvent::Started));
+
+        for _ in 1..100 {
+            assert_eq!(detector.tick(false), None);
+        }
+        assert_eq!(detector.tick(false), Some(SpeechEvent::Ended));
+    }
+
+    #[test]
+    fn utterances_test() {
+        let mut utterances = Utterances::default();
+        // 20ms of 48kHz stereo
+        let tick = vec![i16::MAX; 960 * 2];
+
+        for _ in 0..50 {
+            utterances.push(1, &tick);
+        }
+        utterances.push(2, &tick);
+
+        for _ in 1..SILENT_TICKS {
+            assert_eq!(utterances.silence(1), None);
+        }
+        let utterance = utterances.silence(1).unwrap();
+        assert_eq!(utterance.len(), 50 * 320);
+        assert_eq!(utterance[0], 1.0);
+
+        // too short to be a command
+        for _ in 0..SILENT_TICKS {
+            assert_eq!(utterances.silence(2), None);
+        }
+        assert_eq!(utterances.silence(3), None);
+    }
+}
@@ -1,16 +1,33 @@
 //#![allow(warnings)]
+// builds leaving out command modules also leave the code only those commands use
+#![cfg_attr(
+    not(all(
+        feature = "playback-commands",
+        feature = "library-commands",
+        feature = "board-commands",
+        feature = "settings-commands",
+        feature = "admin-commands"
+    )),
+    allow(dead_code, unused_imports)
+)]
 use commands::PoiseResult;
 use common::LogResult;
-use db::{AudioTable, SettingsTable, Table};
+use db::{
+    ApiKeysTable, ApiUsageTable, AudioTable, BannedSoundsTable, BannedUsersTable, BirthdaysTable,
+    BoardLayout, BoardOrderTable, BoardThemesTable, BotMessagesTable, CommandStatsTable,
+    FeatureFlagsTable, LibraryOpsTable, PlayHistoryTable, PostedBoardsTable, SettingsTable,
+    SoundRequestsTable, SoundRestrictionsTable, Table, UserPrefsTable,
+};
 use env_logger;
 use log;
-use r2d2_sqlite::SqliteConnectionManager;
 use reqwest::Client as HttpClient;
 use serenity::all::{
     ApplicationId, ComponentInteraction, ComponentInteractionDataKind, CreateInteractionResponse,
-    FullEvent, Interaction, VoiceState,
+    CreateInteractionResponseMessage, CreateMessage, EditInteractionResponse, FullEvent,
+    Interaction, ModalInteraction, VoiceState,
 };
 use serenity::client::Context;
+use std::sync::Arc;
 
 use serenity::{
     client::Client,
@@ -19,20 +36,94 @@ use serenity::{
 
 use songbird::SerenityInit;
 
+mod access;
+mod activity;
+mod api;
+mod attachments;
 mod audio;
+mod auth;
+mod birthdays;
+mod board_order;
+mod branding;
+mod cli;
+mod collision;
 mod commands;
 mod common;
 mod config;
+mod crash_report;
 mod db;
 mod errors;
+mod event_mode;
+mod event_sounds;
+mod export;
+mod extensions;
+mod features;
+mod fingerprint;
+mod guild_data;
 mod helpers;
+mod hotbar;
+mod ingest;
+mod jobs;
+// utterances & voice command parsing are only used by voice commands
+#[cfg_attr(not(feature = "voice-commands"), allow(dead_code))]
+mod listen;
+mod metadata;
+mod metrics;
+mod moderation;
+mod naming;
+mod onboarding;
+mod operations;
+mod packs;
+mod playback;
+mod posted_boards;
+mod prefetch;
+mod purge;
+mod quiet_hours;
+mod rate_limit;
+mod recent;
+mod reconcile;
+mod registration;
+mod requests;
+mod retention;
+mod retry;
+mod scan;
+mod search;
+mod selfcheck;
+mod settings;
+mod settings_file;
+mod shuffle;
+mod simulate;
+mod suggest;
+mod telemetry;
+mod themes;
+// resampling & transcript cleanup are only used when built with voice commands
+#[cfg_attr(not(feature = "voice-commands"), allow(dead_code))]
+mod transcribe;
+mod trim;
+mod undo;
+mod uploads;
 mod vars;
+mod version;
+mod voice;
+#[cfg(feature = "voice-commands")]
+mod voice_commands;
+#[cfg(feature = "voice-commands")]
+mod whisper;
 
 use crate::commands::PoiseError;
 use crate::common::UserData;
-use crate::config::Config;
+use crate::config::{Config, SharedConfig};
+use crate::crash_report::ErrorContext;
+use crate::errors::{AudioError, AuthError, FeatureError};
+use crate::extensions::Extensions;
 use crate::helpers::ButtonCustomId;
-use crate::helpers::SongbirdHelper;
+use crate::metrics::CommandTimer;
+use crate::playback::Playback;
+use crate::prefetch::SoundCache;
+use crate::rate_limit::RateLimiter;
+use crate::settings::PlayFeedback;
+use crate::uploads::Uploads;
+use crate::voice::{HttpChat, SongbirdVoice};
 
 type FrameworkContext<'a> = poise::FrameworkContext<'a, UserData, PoiseError>;
 
@@ -43,13 +134,39 @@ async fn main() -> anyhow::Result<()> {
     let config = Config::new();
     env_logger::init();
 
+    let _sentry_guard = crash_report::init(&config);
+
     // framework configuration
     let token = config.token.clone();
     let cmd_prefix = config.command_prefix.clone();
     let application_id = config.application_id;
-    let sqlite_db_file = config.sqlite_db_file.clone();
-    let db_manager = SqliteConnectionManager::file(sqlite_db_file);
-    let db_pool = r2d2::Pool::new(db_manager).expect("Failed to create sqlite connection pool");
+    let db_pool = db::create_pool(&config).expect("Failed to create sqlite connection pool");
+    let config = SharedConfig::new(config);
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "--dry-run") {
+        return simulate::run(config, db_pool).await;
+    }
+    if let Some(result) = cli::run(&args, &db_pool) {
+        return result;
+    }
+
+    if token.is_empty() || application_id == 0 {
+        anyhow::bail!("DISCORD_BOT_TOKEN & DISCORD_BOT_APPLICATION_ID must be set to run the bot");
+    }
+
+    let checks = selfcheck::run(&config.get(), &db_pool).await;
+    let report = selfcheck::render(&checks);
+    if selfcheck::has_fatal(&checks) {
+        log::error!("Startup checks failed\n{report}");
+        anyhow::bail!("Startup checks failed, see FAIL checks above\n{report}");
+    }
+    log::info!("Startup checks\n{report}");
+
+    #[cfg(unix)]
+    tokio::spawn(reload_config_on_sighup(config.clone()));
+
+    let extensions = Extensions::registered();
 
     log::info!("Setting up framework...");
     let framework: poise::Framework<UserData, PoiseError> =
@@ -59,27 +176,56 @@ async fn main() -> anyhow::Result<()> {
                     prefix: Some(cmd_prefix),
                     ..Default::default()
                 },
-                commands: vec![
-                    commands::echo(),
-                    commands::join(),
-                    commands::leave(),
-                    commands::sounds(),
-                    commands::play(),
-                    commands::scan(),
-                    commands::register(),
-                ],
+                commands: extensions.with_commands(commands::all()),
+                pre_command: |ctx| Box::pin(pre_command(ctx)),
+                post_command: |ctx| Box::pin(metrics::record_poise_command(ctx, true)),
+                on_error: |error| Box::pin(on_error(error)),
                 event_handler: |ctx, event, framework, data| {
                     Box::pin(event_handler(ctx, event, framework, data))
                 },
                 ..Default::default()
             })
-            .setup(|_ctx, _ready, _framework| {
+            .setup(|ctx, _ready, _framework| {
                 Box::pin(async move {
                     //poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                    Ok(UserData {
-                        config: config,
-                        db_pool: db_pool,
-                    })
+                    let sound_cache = Arc::new(SoundCache::default());
+                    let playback = Playback::new(
+                        Arc::new(SongbirdVoice::new(
+                            helpers::songbird_get(ctx).await,
+                            sound_cache.clone(),
+                        )),
+                        Arc::new(HttpChat::new(
+                            ctx.http.clone(),
+                            ctx.cache.clone(),
+                            ctx.shard.clone(),
+                        )),
+                        db_pool.clone(),
+                    );
+                    jobs::spawn_scheduler(
+                        ctx.http.clone(),
+                        ctx.cache.clone(),
+                        db_pool.clone(),
+                        config.clone(),
+                        playback.clone(),
+                        sound_cache,
+                    );
+                    if let Some(address) = config.get().api_address {
+                        api::spawn(
+                            address,
+                            api::ApiState {
+                                data: UserData::new(
+                                    config.clone(),
+                                    db_pool.clone(),
+                                    playback.clone(),
+                                ),
+                                http: ctx.http.clone(),
+                                cache: ctx.cache.clone(),
+                                uploads: Uploads::default(),
+                                limiter: RateLimiter::default(),
+                            },
+                        );
+                    }
+                    Ok(UserData::new(config, db_pool, playback).with_extensions(extensions))
                 })
             })
             .build();
@@ -114,12 +260,98 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// Reload config whenever process receives SIGHUP
+#[cfg(unix)]
+async fn reload_config_on_sighup(config: std::sync::Arc<SharedConfig>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(err) => {
+            log::error!("Failed listening for SIGHUP. Config reload disabled - {err}");
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        log::info!("Received SIGHUP, reloading config");
+        config
+            .reload()
+            .map_err(|err| log::error!("Failed reloading config - {err}"))
+            .ok();
+    }
+}
+
 pub struct HttpKey;
 
 impl TypeMapKey for HttpKey {
     type Value = HttpClient;
 }
 
+async fn pre_command(ctx: commands::PoiseContext<'_>) {
+    ctx.set_invocation_data(CommandTimer::start()).await;
+
+    // acknowledge slow commands right away, before discord's 3 second response window closes
+    if helpers::is_long_running(&ctx.command()) {
+        log::debug!("Deferring response for long running command");
+        ctx.defer()
+            .await
+            .log_err_msg("Failed deferring response")
+            .ok();
+    }
+}
+
+async fn on_error(error: poise::FrameworkError<'_, UserData, PoiseError>) {
+    if let Some(ctx) = error.ctx() {
+        metrics::record_poise_command(ctx, false).await;
+    }
+
+    match &error {
+        poise::FrameworkError::Command { error, ctx, .. } => {
+            crash_report::report_error(error, &ErrorContext::from_poise(*ctx));
+        }
+        poise::FrameworkError::CommandPanic { payload, ctx, .. } => {
+            crash_report::report_panic(payload.as_deref(), &ErrorContext::from_poise(*ctx));
+        }
+        poise::FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+            ..
+        } => {
+            // permission denials & disabled features are expected, tell member why instead of treating it as an error
+            let denial = match (
+                error.downcast_ref::<AuthError>(),
+                error.downcast_ref::<FeatureError>(),
+            ) {
+                (Some(auth_error), _) => Some(auth_error.to_string()),
+                (_, Some(feature_error)) => Some(feature_error.to_string()),
+                _ => None,
+            };
+            if let Some(denial) = denial {
+                log::info!(
+                    "Command '{}' denied for user {} - {denial}",
+                    ctx.command().qualified_name,
+                    ctx.author().id
+                );
+                ctx.send(
+                    poise::CreateReply::default()
+                        .content(denial)
+                        .ephemeral(true),
+                )
+                .await
+                .log_err()
+                .ok();
+                return;
+            }
+        }
+        _ => {}
+    }
+
+    if let Err(err) = poise::builtins::on_error(error).await {
+        log::error!("Error while handling error - {err}");
+    }
+}
+
 async fn event_handler(
     ctx: &Context,
     event: &FullEvent,
@@ -130,22 +362,42 @@ async fn event_handler(
         FullEvent::Ready { data_about_bot } => {
             handle_ready(ctx, data_about_bot, framework, data).await?;
         }
+        // commands of guilds the bot was already in are registered on ready
+        FullEvent::GuildCreate {
+            guild,
+            is_new: Some(true),
+        } => {
+            data.registrar
+                .register_guild(&ctx.http, &framework.options().commands, guild.id)
+                .await
+                .log_err_msg(format!("Failed registering commands in guild {}", guild.id))
+                .ok();
+            onboarding::welcome(ctx, guild)
+                .await
+                .log_err_msg(format!("Failed welcoming guild {}", guild.id))
+                .ok();
+        }
         FullEvent::InteractionCreate { interaction } => {
             handle_interaction_create(ctx, interaction, framework, data).await?;
         }
         FullEvent::VoiceStateUpdate { old, new } => {
             handle_voice_state_update(ctx, old, new, framework, data).await?
         }
+        FullEvent::Message { new_message } => {
+            event_sounds::handle_message(ctx, data, new_message).await?;
+        }
         _ => {}
     }
 
+    data.extensions.dispatch(ctx, event, data).await;
+
     Ok(())
 }
 
 async fn handle_ready(
-    _ctx: &Context,
+    ctx: &Context,
     ready: &serenity::model::gateway::Ready,
-    _framework: FrameworkContext<'_>,
+    framework: FrameworkContext<'_>,
     data: &UserData,
 ) -> PoiseResult {
     log::info!(
@@ -165,6 +417,38 @@ async fn handle_ready(
 
     AudioTable::new(data.db_connection()).create_table();
     SettingsTable::new(data.db_connection()).create_table();
+    CommandStatsTable::new(data.db_connection()).create_table();
+    PlayHistoryTable::new(data.db_connection()).create_table();
+    BannedUsersTable::new(data.db_connection()).create_table();
+    BannedSoundsTable::new(data.db_connection()).create_table();
+    LibraryOpsTable::new(data.db_connection()).create_table();
+    UserPrefsTable::new(data.db_connection()).create_table();
+    SoundRequestsTable::new(data.db_connection()).create_table();
+    FeatureFlagsTable::new(data.db_connection()).create_table();
+    BotMessagesTable::new(data.db_connection()).create_table();
+    BoardThemesTable::new(data.db_connection()).create_table();
+    BoardOrderTable::new(data.db_connection()).create_table();
+    BirthdaysTable::new(data.db_connection()).create_table();
+    SoundRestrictionsTable::new(data.db_connection()).create_table();
+    ApiKeysTable::new(data.db_connection()).create_table();
+    ApiUsageTable::new(data.db_connection()).create_table();
+    PostedBoardsTable::new(data.db_connection()).create_table();
+
+    reconcile::log_startup_check(data);
+
+    let guild_ids: Vec<_> = ready.guilds.iter().map(|guild| guild.id).collect();
+    data.registrar
+        .register_once(
+            &ctx.http,
+            &framework.options().commands,
+            &guild_ids,
+            data.config.get().command_registration,
+        )
+        .await
+        .log_err_msg("Failed registering commands")
+        .ok();
+
+    posted_boards::warm_up_once(&ctx.http, data).await;
 
     Ok(())
 }
@@ -174,7 +458,7 @@ async fn handle_voice_state_update(
     old: &Option<VoiceState>,
     new: &VoiceState,
     _framework: FrameworkContext<'_>,
-    _data: &UserData,
+    data: &UserData,
 ) -> PoiseResult {
     // if member left voice channel
     if new.channel_id.is_none() {
@@ -191,8 +475,7 @@ async fn handle_voice_state_update(
                     // if bot only member in voice channel
                     if members.len() == 1 && members[0].user.id == ctx.cache.current_user().id {
                         log::info!("No one in voice channel. Bot is leaving. guild_id: {guild_id}, channel_id: {channel_id}");
-                        let manager = helpers::songbird_get(&ctx).await;
-                        manager.leave_voice_channel(guild_id).await?;
+                        data.playback.leave(guild_id).await?;
                     }
                 }
                 None => {}
@@ -214,6 +497,9 @@ async fn handle_interaction_create(
         Interaction::Component(component) => {
             handle_component_interaction(ctx, interaction, component, framework, data).await?;
         }
+        Interaction::Modal(modal) if modal.data.custom_id == search::SEARCH_MODAL_ID => {
+            handle_search_modal(ctx, modal, data).await?;
+        }
         _ => {}
     }
 
@@ -230,7 +516,28 @@ async fn handle_component_interaction(
     log::info!("component interaction event");
     match component.data.kind {
         ComponentInteractionDataKind::Button => {
-            handle_btn_interaction(ctx, interaction, component, framework, data).await?
+            let timer = std::time::Instant::now();
+            let result = handle_btn_interaction(ctx, interaction, component, framework, data).await;
+            metrics::record(
+                data,
+                "button",
+                component.guild_id,
+                component.user.id,
+                timer.elapsed(),
+                result.is_ok(),
+            );
+
+            if let Err(err) = &result {
+                crash_report::report_error(
+                    err,
+                    &ErrorContext {
+                        command: "button".into(),
+                        guild_id: component.guild_id,
+                        user_id: component.user.id,
+                    },
+                );
+            }
+            result?
         }
         _ => {}
     }
@@ -247,15 +554,115 @@ async fn handle_btn_interaction(
 ) -> PoiseResult {
     log::debug!("Interaction Component Button pressed");
     let custom_id = &component.data.custom_id;
+    let button = ButtonCustomId::try_from(custom_id.clone())?;
 
-    component
-        .create_response(&ctx.http, CreateInteractionResponse::Acknowledge)
-        .await
-        .log_err_msg("Failed to create response for btn interaction")
-        .ok();
+    let permissions = match (&component.member, component.guild_id) {
+        (Some(member), Some(guild_id)) => {
+            let manage_guild = member
+                .permissions
+                .is_some_and(|permissions| permissions.manage_guild());
+            Some(
+                data.authorizer
+                    .permissions(data, guild_id, member, manage_guild),
+            )
+        }
+        _ => None,
+    };
 
-    match ButtonCustomId::try_from(custom_id.clone())? {
-        ButtonCustomId::PlayAudio(audio_track_id) => {
+    // sound & transport buttons require play permission, delete & trim buttons require managing sounds,
+    // and request review buttons require a moderator
+    let denied = permissions.as_ref().and_then(|permissions| match button {
+        ButtonCustomId::PlayAudio(_)
+        | ButtonCustomId::ReplayLast
+        | ButtonCustomId::PausePlayback
+        | ButtonCustomId::ResumePlayback => permissions.check_play().err(),
+        ButtonCustomId::DeleteAudio(_)
+        | ButtonCustomId::ConfirmTrim(_)
+        | ButtonCustomId::ConfirmPurge(_)
+        | ButtonCustomId::ConfirmPackInstall(_)
+        | ButtonCustomId::ResolveCollision(..)
+        | ButtonCustomId::CancelOperation(_)
+        | ButtonCustomId::ReconcileRemoveRows
+        | ButtonCustomId::ReconcileImportFiles => permissions.check_manage_sounds().err(),
+        ButtonCustomId::ApproveRequest(_) | ButtonCustomId::DenyRequest(_) => {
+            permissions.check_moderator().err()
+        }
+        ButtonCustomId::ConfirmGuildDataDelete(_) => permissions.check_manage_guild().err(),
+        // the full board's order is shared, favorites boards are ordered by their user
+        ButtonCustomId::MoveBoardSound(_, BoardLayout::Full, _) => {
+            permissions.check_manage_sounds().err()
+        }
+        ButtonCustomId::SearchPage(..)
+        | ButtonCustomId::SearchModal
+        | ButtonCustomId::ShuffleBoard(_)
+        | ButtonCustomId::CancelTrim
+        | ButtonCustomId::CancelPurge
+        | ButtonCustomId::CancelPackInstall
+        | ButtonCustomId::CancelGuildDataDelete
+        | ButtonCustomId::ToggleAccess
+        | ButtonCustomId::OnboardingSetup(_)
+        | ButtonCustomId::DisplaySounds
+        | ButtonCustomId::RefreshSoundInfo(_)
+        | ButtonCustomId::MoveBoardSound(_, BoardLayout::Favorites, _)
+        | ButtonCustomId::Unknown(_) => None,
+    });
+
+    let play_feedback = match (&button, component.guild_id) {
+        (ButtonCustomId::PlayAudio(_) | ButtonCustomId::ReplayLast, Some(guild_id)) => {
+            settings::play_feedback(&data.settings_table(), guild_id)
+        }
+        _ => PlayFeedback::None,
+    };
+
+    let response = match (&denied, &button) {
+        (Some(err), _) => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(err.to_string())
+                .ephemeral(true),
+        ),
+        // reviewing a request can take a while (ie: downloading the sound), answer when done
+        (
+            None,
+            ButtonCustomId::ApproveRequest(_)
+            | ButtonCustomId::DenyRequest(_)
+            | ButtonCustomId::DeleteAudio(_)
+            | ButtonCustomId::DisplaySounds
+            | ButtonCustomId::ToggleAccess,
+        ) => CreateInteractionResponse::Defer(
+            CreateInteractionResponseMessage::new().ephemeral(true),
+        ),
+        (None, ButtonCustomId::PlayAudio(_) | ButtonCustomId::ReplayLast)
+            if play_feedback == PlayFeedback::Ephemeral =>
+        {
+            CreateInteractionResponse::Defer(
+                CreateInteractionResponseMessage::new().ephemeral(true),
+            )
+        }
+        (None, ButtonCustomId::SearchModal) => {
+            CreateInteractionResponse::Modal(search::search_modal())
+        }
+        (None, ButtonCustomId::OnboardingSetup(guild_id)) => CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(onboarding::setup_message(data, *guild_id))
+                .ephemeral(true),
+        ),
+        (None, _) => CreateInteractionResponse::Acknowledge,
+    };
+
+    retry::discord_retry("btn interaction acknowledge", || {
+        component.create_response(&ctx.http, response.clone())
+    })
+    .await
+    .log_err_msg("Failed to create response for btn interaction")
+    .ok();
+
+    if let Some(err) = denied {
+        log::info!("Button press denied for user {} - {err}", component.user.id);
+        return Ok(());
+    }
+
+    match button {
+        ButtonCustomId::PlayAudio(_) | ButtonCustomId::ReplayLast => {
             log::info!("Play Audio Button Pressed - '{custom_id}'");
 
             let channel_id = component.channel_id;
@@ -263,31 +670,249 @@ async fn handle_btn_interaction(
                 .guild_id
                 .ok_or("ComponentInteraction.guild_id is None")
                 .log_err()?;
+            let audio_track_id = match button {
+                ButtonCustomId::PlayAudio(audio_track_id) => audio_track_id,
+                _ => helpers::last_played_audio_id(data, guild_id)?,
+            };
 
-            let table = data.audio_table();
+            let played = helpers::play_sound_button(
+                data,
+                guild_id,
+                channel_id,
+                Some(component.user.id),
+                audio_track_id,
+                &permissions.clone().unwrap_or_default(),
+            )
+            .await?;
+            helpers::send_play_feedback(ctx.http.clone(), component, play_feedback, &played).await;
+        }
+        ButtonCustomId::SearchPage(page, query) => {
+            log::info!("Search Page Button Pressed - '{custom_id}'");
 
-            match table.find_audio_row(db::UniqueAudioTableCol::Id(audio_track_id)) {
-                Some(audio_row) => {
-                    log::info!(
-                        "Found audio track. Name: {}, File: {}",
-                        audio_row.name,
-                        audio_row.audio_file.to_string_lossy()
-                    );
+            let rows = data
+                .audio_table()
+                .search_rows(&query, search::SEARCH_RESULTS_LIMIT)?;
+            let (content, components) = search::board_page(&rows, &query, page);
+            component
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .content(content)
+                        .components(components),
+                )
+                .await
+                .log_err_msg("Failed updating search board")
+                .ok();
+        }
+        ButtonCustomId::SearchModal => {
+            log::info!("Search Modal Button Pressed - '{custom_id}'");
+        }
+        ButtonCustomId::ShuffleBoard(count) => {
+            log::info!("Shuffle Button Pressed - '{custom_id}'");
 
-                    let manager = helpers::songbird_get(&ctx).await;
-                    manager
-                        .play_audio(guild_id, channel_id, &audio_row.audio_file)
-                        .await
-                        .ok();
-                }
-                None => {
-                    return Err(format!(
-                        "Unable to locate audio track for button custom id"
+            let count = count.clamp(1, shuffle::MAX_SHUFFLE_COUNT);
+            let rows = data.audio_table().random_rows(count as u64)?;
+            let (content, components) = shuffle::board(&rows, count);
+            component
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .content(content)
+                        .components(components),
+                )
+                .await
+                .log_err_msg("Failed updating shuffle board")
+                .ok();
+        }
+        ButtonCustomId::MoveBoardSound(audio_id, board, board_move) => {
+            log::info!("Board Move Button Pressed - '{custom_id}'");
+            board_order::move_button(ctx, component, data, audio_id, board, board_move).await?;
+        }
+        ButtonCustomId::ConfirmTrim(spec) => {
+            log::info!("Confirm Trim Button Pressed - '{custom_id}'");
+            trim::confirm_trim(ctx, component, data, spec).await?;
+        }
+        ButtonCustomId::ResolveCollision(id, choice) => {
+            log::info!("Name Collision Button Pressed - '{custom_id}'");
+            collision::resolve_pending(ctx, component, data, id, choice).await?;
+        }
+        ButtonCustomId::PausePlayback | ButtonCustomId::ResumePlayback => {
+            log::info!("Transport Button Pressed - '{custom_id}'");
+            handle_transport_btn(ctx, component, data, button).await?;
+        }
+        ButtonCustomId::ReconcileRemoveRows => {
+            log::info!("Reconcile Remove Button Pressed - '{custom_id}'");
+            reconcile::remove_rows(ctx, component, data).await?;
+        }
+        ButtonCustomId::ReconcileImportFiles => {
+            log::info!("Reconcile Import Button Pressed - '{custom_id}'");
+            reconcile::import_files(ctx, component, data).await?;
+        }
+        ButtonCustomId::CancelOperation(id) => {
+            log::info!("Cancel Operation Button Pressed - '{custom_id}'");
+            // operation edits its own reply once it stops
+            if !data.operations.cancel(id) {
+                log::info!("Operation {id} already finished");
+            }
+        }
+        ButtonCustomId::CancelTrim => {
+            log::info!("Cancel Trim Button Pressed - '{custom_id}'");
+            component
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .content("Trim cancelled")
+                        .components(vec![]),
+                )
+                .await
+                .log_err_msg("Failed replying to trim cancel")
+                .ok();
+        }
+        ButtonCustomId::ConfirmPurge(spec) => {
+            log::info!("Confirm Purge Button Pressed - '{custom_id}'");
+            purge::confirm_purge(ctx, component, data, spec).await?;
+        }
+        ButtonCustomId::CancelPurge => {
+            log::info!("Cancel Purge Button Pressed - '{custom_id}'");
+            component
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .content("Nothing deleted")
+                        .components(vec![]),
+                )
+                .await
+                .log_err_msg("Failed replying to purge cancel")
+                .ok();
+        }
+        ButtonCustomId::ConfirmPackInstall(code) => {
+            log::info!("Confirm Pack Install Button Pressed - '{custom_id}'");
+            packs::confirm_install(ctx, component, data, code).await?;
+        }
+        ButtonCustomId::CancelPackInstall => {
+            log::info!("Cancel Pack Install Button Pressed - '{custom_id}'");
+            component
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .content("Nothing installed")
+                        .components(vec![]),
+                )
+                .await
+                .log_err_msg("Failed replying to pack install cancel")
+                .ok();
+        }
+        ButtonCustomId::ConfirmGuildDataDelete(guild_id) => {
+            log::info!("Confirm Guild Data Delete Button Pressed - '{custom_id}'");
+            guild_data::confirm_delete(ctx, component, data, guild_id).await?;
+        }
+        ButtonCustomId::CancelGuildDataDelete => {
+            log::info!("Cancel Guild Data Delete Button Pressed - '{custom_id}'");
+            component
+                .edit_response(
+                    ctx,
+                    EditInteractionResponse::new()
+                        .content("Nothing deleted")
+                        .components(vec![]),
+                )
+                .await
+                .log_err_msg("Failed replying to guild data delete cancel")
+                .ok();
+        }
+        ButtonCustomId::ToggleAccess => {
+            log::info!("Access Button Pressed - '{custom_id}'");
+            let guild_id = component
+                .guild_id
+                .ok_or("ComponentInteraction.guild_id is None")
+                .log_err()?;
+            access::toggle(ctx, component, data, guild_id).await?;
+        }
+        ButtonCustomId::DeleteAudio(audio_track_id) => {
+            log::info!("Delete Audio Button Pressed - '{custom_id}'");
+            handle_delete_audio_btn(ctx, component, data, audio_track_id).await?;
+        }
+        ButtonCustomId::ApproveRequest(request_id) => {
+            requests::review_request(ctx, component, data, request_id, true).await?;
+        }
+        ButtonCustomId::DenyRequest(request_id) => {
+            requests::review_request(ctx, component, data, request_id, false).await?;
+        }
+        ButtonCustomId::OnboardingSetup(guild_id) => {
+            log::info!("Onboarding Setup Button Pressed for guild {guild_id} - '{custom_id}'");
+        }
+        ButtonCustomId::DisplaySounds => {
+            log::info!("Display Sounds Button Pressed - '{custom_id}'");
+
+            let prefs = data
+                .user_prefs_table()
+                .get_prefs(component.user.id.get())
+                .log_err()?;
+            let content = match component.guild_id {
+                Some(guild_id) => {
+                    let hidden = auth::hidden_sound_ids(
+                        &data.sound_restrictions_table(),
+                        guild_id,
+                        component.user.id,
+                        &permissions.clone().unwrap_or_default(),
+                    )?;
+                    let displayed = helpers::send_soundboard(
+                        &ctx.http,
+                        data,
+                        guild_id,
+                        component.channel_id,
+                        &prefs,
+                        &hidden,
                     )
-                    .into())
-                    .log_err();
+                    .await?;
+                    match displayed {
+                        Some(message_ids) => {
+                            data.track_bot_messages(guild_id, component.channel_id, &message_ids);
+                            posted_boards::record(
+                                data,
+                                guild_id,
+                                component.channel_id,
+                                component.user.id,
+                                &message_ids,
+                                &hidden,
+                            );
+                            format!("Displayed sounds ({} board)", prefs.board)
+                        }
+                        None => "No sounds to display yet".into(),
+                    }
                 }
-            }
+                None => "The soundboard can only be shown in a server".into(),
+            };
+
+            component
+                .edit_response(ctx, EditInteractionResponse::new().content(content))
+                .await
+                .log_err_msg("Failed replying to display sounds button")
+                .ok();
+        }
+        ButtonCustomId::RefreshSoundInfo(audio_id) => {
+            log::info!("Refresh Sound Info Button Pressed - '{custom_id}'");
+
+            let guild_id = component
+                .guild_id
+                .ok_or("ComponentInteraction.guild_id is None")
+                .log_err()?;
+            let row = data
+                .audio_table()
+                .find_audio_row(db::UniqueAudioTableCol::Id(audio_id));
+            let response = match row {
+                Some(row) => EditInteractionResponse::new()
+                    .embed(helpers::sound_info_embed(data, guild_id, &row)?),
+                None => EditInteractionResponse::new()
+                    .content("Sound was deleted")
+                    .embeds(vec![])
+                    .components(vec![]),
+            };
+
+            component
+                .edit_response(ctx, response)
+                .await
+                .log_err_msg("Failed refreshing sound info")
+                .ok();
         }
         ButtonCustomId::Unknown(value) => {
             return Err(format!(
@@ -300,3 +925,123 @@ async fn handle_btn_interaction(
 
     Ok(())
 }
+
+/// Delete sound from a delete button. The interaction must already be deferred
+async fn handle_delete_audio_btn(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+    audio_track_id: i64,
+) -> PoiseResult {
+    let table = data.audio_table();
+    let content = match table.find_audio_row(db::UniqueAudioTableCol::Id(audio_track_id)) {
+        // buttons can't carry an admin override, protected sounds are only removed by command
+        Some(audio_row) if audio_row.protected => AuthError::SoundProtected {
+            name: audio_row.name,
+        }
+        .to_string(),
+        Some(audio_row) => {
+            undo::delete_sound(
+                &data.db_pool,
+                &data.config.get().audio_dir,
+                &audio_row,
+                component.guild_id,
+                component.user.id,
+                None,
+            )?;
+            helpers::send_audit_message(
+                ctx,
+                data,
+                component.guild_id,
+                CreateMessage::new().content(format!(
+                    "Sound `{}` deleted by <@{}>",
+                    audio_row.name, component.user.id
+                )),
+            )
+            .await;
+            format!("Deleted sound `{}`", audio_row.name)
+        }
+        None => "Sound was already deleted".to_string(),
+    };
+
+    component
+        .edit_response(ctx, EditInteractionResponse::new().content(content))
+        .await
+        .log_err_msg("Failed replying to delete button")
+        .ok();
+
+    Ok(())
+}
+
+/// Reply to a submitted soundboard search modal with a board of matching sounds
+async fn handle_search_modal(
+    ctx: &Context,
+    modal: &ModalInteraction,
+    data: &UserData,
+) -> PoiseResult {
+    let query = search::modal_query(modal).unwrap_or_default();
+    log::info!("Search modal submitted. query: '{query}'");
+
+    let rows = data
+        .audio_table()
+        .search_rows(&query, search::SEARCH_RESULTS_LIMIT)?;
+    let message = match rows.is_empty() {
+        true => CreateInteractionResponseMessage::new()
+            .content(format!("No sounds found matching `{query}`")),
+        false => {
+            let (content, components) = search::board_page(&rows, &query, 0);
+            CreateInteractionResponseMessage::new()
+                .content(content)
+                .components(components)
+        }
+    };
+
+    retry::discord_retry("search modal response", || {
+        modal.create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(message.clone().ephemeral(true)),
+        )
+    })
+    .await
+    .log_err_msg("Failed to respond to search modal")?;
+
+    Ok(())
+}
+
+/// Pause or resume playback from now playing buttons, then refresh the now playing message.
+/// The interaction must already be acknowledged
+async fn handle_transport_btn(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+    button: ButtonCustomId,
+) -> PoiseResult {
+    let guild_id = component
+        .guild_id
+        .ok_or("ComponentInteraction.guild_id is None")
+        .log_err()?;
+
+    let result = match button {
+        ButtonCustomId::ResumePlayback => data.playback.resume(guild_id),
+        _ => data.playback.pause(guild_id),
+    };
+    result.log_err_msg("Failed pausing/resuming playback").ok();
+
+    let response = match data.playback.now_playing(guild_id).await {
+        Some(now_playing) => EditInteractionResponse::new()
+            .embed(helpers::now_playing_embed(data, guild_id, &now_playing))
+            .components(vec![helpers::transport_action_row(now_playing.paused)]),
+        None => EditInteractionResponse::new()
+            .content(AudioError::NothingPlaying.to_string())
+            .embeds(vec![])
+            .components(vec![]),
+    };
+
+    component
+        .edit_response(ctx, response)
+        .await
+        .log_err_msg("Failed updating now playing message")
+        .ok();
+
+    Ok(())
+}
@@ -0,0 +1,394 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer};
+use serenity::all::{CreateEmbed, ReactionType};
+use tokio_util::sync::CancellationToken;
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::db::{AudioTable, AudioTablePaginator, AudioTableRow, Tags};
+use crate::{helpers, operations, undo, vars};
+
+/// Largest metadata file accepted for import
+pub const MAX_METADATA_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Metadata of one audio file. Fields left empty keep their current value
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct MetadataEntry {
+    /// Audio file name, with or without extension
+    pub file: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Space or comma separated. JSON files may also give a list
+    #[serde(default, deserialize_with = "de_tags")]
+    pub tags: Option<String>,
+    #[serde(default)]
+    pub emoji: Option<String>,
+    /// Volume percentage, 0-200
+    #[serde(default)]
+    pub volume: Option<u32>,
+}
+
+fn de_tags<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum TagsValue {
+        List(Vec<String>),
+        Text(String),
+    }
+
+    Ok(
+        Option::<TagsValue>::deserialize(deserializer)?.map(|value| match value {
+            TagsValue::List(tags) => tags.join(" "),
+            TagsValue::Text(tags) => tags,
+        }),
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetadataFormat {
+    Csv,
+    Json,
+}
+
+impl MetadataFormat {
+    pub fn from_file_name(file_name: &str) -> Result<Self, String> {
+        let file_name = file_name.to_lowercase();
+        if file_name.ends_with(".csv") {
+            Ok(Self::Csv)
+        } else if file_name.ends_with(".json") {
+            Ok(Self::Json)
+        } else {
+            Err("Metadata file must be a .csv or .json file".into())
+        }
+    }
+}
+
+/// Read metadata entries. CSV files need a header row with a `file` column, and optionally
+/// `name`, `tags`, `emoji`, and `volume` columns. JSON files are a list of objects with the same keys.
+pub fn parse_metadata(
+    content: &[u8],
+    format: MetadataFormat,
+) -> Result<Vec<MetadataEntry>, String> {
+    match format {
+        MetadataFormat::Csv => csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(content)
+            .deserialize()
+            .enumerate()
+            .map(|(index, entry)| {
+                // + 2 for header row & one based line numbers
+                entry.map_err(|err| format!("Invalid CSV row on line {} - {err}", index + 2))
+            })
+            .collect(),
+        MetadataFormat::Json => {
+            serde_json::from_slice(content).map_err(|err| format!("Invalid JSON - {err}"))
+        }
+    }
+}
+
+/// Import metadata file into the library. Nothing is written when `dry_run` is set.
+/// Returns import report, and number of failed writes
+pub fn import_metadata(
+    db_pool: &r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>,
+    content: &[u8],
+    format: MetadataFormat,
+    dry_run: bool,
+    force: bool,
+    cancel: &CancellationToken,
+) -> Result<(MetadataImportReport, usize), PoiseError> {
+    let entries = parse_metadata(content, format)?;
+    log::info!(
+        "Importing metadata of {} audio files. dry_run: {dry_run}",
+        entries.len()
+    );
+
+    let mut rows = vec![];
+    for page in AudioTablePaginator::builder(db_pool.get()?).build() {
+        operations::check(cancel)?;
+        rows.extend(page.log_err()?);
+    }
+
+    let report = MetadataImportReport::plan(entries, &rows, force);
+    operations::check(cancel)?;
+    let failed_writes = match dry_run {
+        true => 0,
+        false => report.apply(&AudioTable::new(db_pool.get()?)),
+    };
+
+    Ok((report, failed_writes))
+}
+
+/// Changes to one audio row
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetadataUpdate {
+    pub row_id: i64,
+    pub file: String,
+    pub name: String,
+    pub tags: String,
+    pub emoji: Option<String>,
+    pub volume: Option<u32>,
+}
+
+#[derive(Debug, Default)]
+pub struct MetadataImportReport {
+    pub updates: Vec<MetadataUpdate>,
+    pub unchanged: usize,
+    /// Entries whose file isn't in the library. Run a scan first to add them
+    pub unmatched: Vec<String>,
+    pub invalid: Vec<(String, String)>,
+    /// Metadata of updated sounds before the import, to undo it with
+    pub previous: Vec<undo::SoundMetadata>,
+}
+
+impl MetadataImportReport {
+    /// Work out changes entries make to the library rows, without writing anything. Protected sounds
+    /// are only changed when forced
+    pub fn plan(entries: Vec<MetadataEntry>, rows: &[AudioTableRow], force: bool) -> Self {
+        let mut report = Self::default();
+        let mut names: HashMap<String, i64> =
+            rows.iter().map(|row| (row.name.clone(), row.id)).collect();
+
+        for entry in entries {
+            let file = entry.file.trim().to_string();
+            let row = rows.iter().find(|row| {
+                row.audio_file.file_name_lossy() == file || row.audio_file.file_stem() == file
+            });
+            let Some(row) = row else {
+                report.unmatched.push(file);
+                continue;
+            };
+
+            match Self::plan_entry(entry, row, &names) {
+                Ok(Some(_)) if row.protected && !force => {
+                    report.invalid.push((file, "Sound is protected".into()))
+                }
+                Ok(Some(update)) => {
+                    names.retain(|_, id| *id != row.id);
+                    names.insert(update.name.clone(), row.id);
+                    report.updates.push(update);
+                    report.previous.push(undo::SoundMetadata::from(row));
+                }
+                Ok(None) => report.unchanged += 1,
+                Err(reason) => report.invalid.push((file, reason)),
+            }
+        }
+
+        report
+    }
+
+    fn plan_entry(
+        entry: MetadataEntry,
+        row: &AudioTableRow,
+        names: &HashMap<String, i64>,
+    ) -> Result<Option<MetadataUpdate>, String> {
+        let not_empty = |value: Option<String>| {
+            value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+
+        let name = not_empty(entry.name).unwrap_or(row.name.clone());
+        if name.chars().count() > vars::BTN_LABEL_MAX_LEN {
+            return Err(format!(
+                "Name is longer than {} characters",
+                vars::BTN_LABEL_MAX_LEN
+            ));
+        }
+        if let Some(id) = names.get(&name).filter(|id| **id != row.id) {
+            return Err(format!("Name `{name}` is already used by sound id {id}"));
+        }
+
+        let tags = match not_empty(entry.tags) {
+            Some(tags) => Tags::from(tags.replace(',', " ")).to_string(),
+            None => row.tags.to_string(),
+        };
+
+        let emoji = match not_empty(entry.emoji) {
+            Some(emoji) => {
+                if emoji.len() > 64 || emoji.contains(char::is_whitespace) {
+                    return Err(format!("Invalid emoji `{emoji}`"));
+                }
+                ReactionType::try_from(emoji.as_str())
+                    .map_err(|_| format!("Invalid emoji `{emoji}`"))?;
+                Some(emoji)
+            }
+            None => row.emoji.clone(),
+        };
+
+        let volume = match entry.volume {
+            Some(volume) if volume > 200 => {
+                return Err("Volume must be a percentage between 0 and 200".into())
+            }
+            Some(volume) => Some(volume),
+            None => row.volume,
+        };
+
+        let unchanged = name == row.name
+            && tags == row.tags.to_string()
+            && emoji == row.emoji
+            && volume == row.volume;
+        if unchanged {
+            return Ok(None);
+        }
+
+        Ok(Some(MetadataUpdate {
+            row_id: row.id,
+            file: row.audio_file.file_name_lossy(),
+            name,
+            tags,
+            emoji,
+            volume,
+        }))
+    }
+
+    /// Write updates to database. Returns number of failed writes
+    pub fn apply(&self, table: &AudioTable) -> usize {
+        self.updates
+            .iter()
+            .filter(|update| {
+                table
+                    .update_metadata(
+                        update.row_id,
+                        &update.name,
+                        &Tags::from(update.tags.as_str()),
+                        update.emoji.as_deref(),
+                        update.volume,
+                    )
+                    .log_err()
+                    .is_err()
+            })
+            .count()
+    }
+
+    fn update_lines(&self) -> Vec<String> {
+        self.updates
+            .iter()
+            .map(|update| format!("`{}` → {}", update.file, update.name))
+            .collect()
+    }
+
+    fn invalid_lines(&self) -> Vec<String> {
+        self.invalid
+            .iter()
+            .map(|(file, reason)| format!("`{file}`: {reason}"))
+            .collect()
+    }
+
+    pub fn summary_embed(&self, failed_writes: usize) -> CreateEmbed {
+        let mut embed = CreateEmbed::new()
+            .title("Metadata Import Summary")
+            .description(format!("Unchanged: {}", self.unchanged))
+            .field(
+                format!("Updated ({})", self.updates.len()),
+                helpers::embed_field_list(&self.update_lines()),
+                false,
+            )
+            .field(
+                format!("Not in library ({})", self.unmatched.len()),
+                helpers::embed_field_list(&self.unmatched),
+                false,
+            )
+            .field(
+                format!("Invalid ({})", self.invalid.len()),
+                helpers::embed_field_list(&self.invalid_lines()),
+                false,
+            );
+
+        if failed_writes > 0 {
+            embed = embed.field("Failed Writes", failed_writes.to_string(), false);
+        }
+
+        embed
+    }
+}
+
+impl std::fmt::Display for MetadataImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Updated: {}", self.updates.len())?;
+        for line in self.update_lines() {
+            writeln!(f, "  {line}")?;
+        }
+        writeln!(f, "Unchanged: {}", self.unchanged)?;
+        writeln!(f, "Not in library: {}", self.unmatched.len())?;
+        for file in &self.unmatched {
+            writeln!(f, "  {file}")?;
+        }
+        writeln!(f, "Invalid: {}", self.invalid.len())?;
+        for line in self.invalid_lines() {
+            writeln!(f, "  {line}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_metadata_test() {
+        let csv = "file,name,tags,emoji,volume\nairhorn.mp3, Air Horn ,\"loud,meme\",📯,50\nbruh.mp3,,,,\n";
+        let entries = parse_metadata(csv.as_bytes(), MetadataFormat::Csv).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name.as_deref(), Some("Air Horn"));
+        assert_eq!(entries[0].volume, Some(50));
+        assert_eq!(entries[1].name, None);
+
+        let json = r#"[{"file": "airhorn", "tags": ["loud", "meme"]}, {"file": "bruh.mp3", "volume": 80}]"#;
+        let entries = parse_metadata(json.as_bytes(), MetadataFormat::Json).unwrap();
+        assert_eq!(entries[0].tags.as_deref(), Some("loud meme"));
+        assert_eq!(entries[1].volume, Some(80));
+
+        assert!(parse_metadata(b"file,volume\na.mp3,loud\n", MetadataFormat::Csv).is_err());
+        assert!(MetadataFormat::from_file_name("sounds.txt").is_err());
+    }
+
+    #[test]
+    fn plan_import_test() {
+        let rows = vec![
+            AudioTableRow::test_row(1).with_audio_file("/tmp/airhorn.mp3"),
+            AudioTableRow::test_row(2).with_audio_file("/tmp/bruh.mp3"),
+        ];
+        let entry = |file: &str, name: Option<&str>, volume: Option<u32>| MetadataEntry {
+            file: file.into(),
+            name: name.map(|name| name.into()),
+            volume,
+            ..Default::default()
+        };
+
+        let report = MetadataImportReport::plan(
+            vec![
+                entry("airhorn", Some("Air Horn"), Some(50)),
+                entry("bruh.mp3", Some("Air Horn"), None),
+                entry("bruh.mp3", None, Some(300)),
+                entry("bruh.mp3", Some("Sound 2"), None),
+                entry("missing.mp3", Some("Missing"), None),
+            ],
+            &rows,
+            false,
+        );
+
+        assert_eq!(report.updates.len(), 1);
+        assert_eq!(report.updates[0].row_id, 1);
+        assert_eq!(report.updates[0].volume, Some(50));
+        assert_eq!(report.unchanged, 1);
+        assert_eq!(report.unmatched, vec!["missing.mp3".to_string()]);
+        assert_eq!(report.invalid.len(), 2);
+
+        let protected_rows = vec![AudioTableRow {
+            protected: true,
+            ..AudioTableRow::test_row(1).with_audio_file("/tmp/airhorn.mp3")
+        }];
+        let entries = || vec![entry("airhorn", Some("Air Horn"), None)];
+        let report = MetadataImportReport::plan(entries(), &protected_rows, false);
+        assert!(report.updates.is_empty());
+        assert_eq!(report.invalid[0].1, "Sound is protected");
+        let report = MetadataImportReport::plan(entries(), &protected_rows, true);
+        assert_eq!(report.updates.len(), 1);
+    }
+}
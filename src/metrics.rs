@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serenity::all::{GuildId, UserId};
+
+use crate::commands::PoiseContext;
+use crate::common::{LogResult, UserData};
+use crate::db::{CommandStatsRowInsert, CommandStatsTable};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandMetrics {
+    pub calls: u64,
+    pub failures: u64,
+    pub total_duration: Duration,
+    pub max_duration: Duration,
+}
+
+impl CommandMetrics {
+    pub fn avg_duration(&self) -> Duration {
+        match self.calls {
+            0 => Duration::ZERO,
+            calls => self.total_duration / calls as u32,
+        }
+    }
+}
+
+/// In-memory command latency & outcome counters, since bot start
+#[derive(Debug)]
+pub struct MetricsRegistry {
+    started_at: Instant,
+    commands: Mutex<HashMap<String, CommandMetrics>>,
+}
+
+impl Default for MetricsRegistry {
+    fn default() -> Self {
+        Self {
+            started_at: Instant::now(),
+            commands: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Time since bot started
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    pub fn record_command(&self, name: &str, duration: Duration, success: bool) {
+        let mut commands = self.commands.lock().unwrap();
+        let metrics = commands.entry(name.to_string()).or_default();
+
+        metrics.calls += 1;
+        metrics.total_duration += duration;
+        metrics.max_duration = metrics.max_duration.max(duration);
+        if !success {
+            metrics.failures += 1;
+        }
+    }
+
+    /// Snapshot of command metrics, sorted by command name
+    pub fn command_metrics(&self) -> Vec<(String, CommandMetrics)> {
+        let commands = self.commands.lock().unwrap();
+        let mut metrics: Vec<_> = commands
+            .iter()
+            .map(|(name, metrics)| (name.clone(), metrics.clone()))
+            .collect();
+
+        metrics.sort_by(|a, b| a.0.cmp(&b.0));
+        metrics
+    }
+}
+
+/// Command start time. Stored as poise invocation data by the pre command hook
+pub struct CommandTimer(Instant);
+
+impl CommandTimer {
+    pub fn start() -> Self {
+        Self(Instant::now())
+    }
+}
+
+/// Record command outcome to metrics registry, and to stats table when enabled
+pub fn record(
+    data: &UserData,
+    name: &str,
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+    duration: Duration,
+    success: bool,
+) {
+    log::debug!(
+        "Command '{name}' took {}ms, success: {success}",
+        duration.as_millis()
+    );
+    data.metrics.record_command(name, duration, success);
+
+    if data.config.get().record_command_stats {
+        CommandStatsTable::new(data.db_connection())
+            .insert_row(&CommandStatsRowInsert {
+                command: name.to_string(),
+                guild_id: guild_id.map(|id| id.get()),
+                user_id: user_id.get(),
+                duration_ms: duration.as_millis() as u64,
+                success,
+                created_at: chrono::Utc::now(),
+            })
+            .log_err_msg("Failed recording command stats")
+            .ok();
+    }
+}
+
+/// Record outcome of a poise command, timed from the pre command hook
+pub async fn record_poise_command(ctx: PoiseContext<'_>, success: bool) {
+    let duration = ctx
+        .invocation_data::<CommandTimer>()
+        .await
+        .map_or(Duration::ZERO, |timer| timer.0.elapsed());
+
+    record(
+        ctx.data(),
+        &ctx.command().qualified_name,
+        ctx.guild_id(),
+        ctx.author().id,
+        duration,
+        success,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_command_test() {
+        let registry = MetricsRegistry::new();
+        registry.record_command("play", Duration::from_millis(10), true);
+        registry.record_command("play", Duration::from_millis(30), false);
+        registry.record_command("echo", Duration::from_millis(5), true);
+
+        let metrics = registry.command_metrics();
+        assert_eq!(metrics.len(), 2);
+        assert_eq!(metrics[0].0, "echo");
+
+        let (_, play) = &metrics[1];
+        assert_eq!(play.calls, 2);
+        assert_eq!(play.failures, 1);
+        assert_eq!(play.avg_duration(), Duration::from_millis(20));
+        assert_eq!(play.max_duration, Duration::from_millis(30));
+    }
+}
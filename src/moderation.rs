@@ -0,0 +1,212 @@
+use std::path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::config::Config;
+use crate::errors::ModerationError;
+use crate::version;
+
+/// Longest a moderation hook can take to answer, before the upload is rejected
+const HOOK_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Audio about to go live, sent to the moderation hooks as json
+#[derive(Debug, Serialize)]
+pub struct ModerationRequest<'a> {
+    /// `new` for new sounds, `replace` for new audio of an existing sound
+    pub action: &'static str,
+    pub name: &'a str,
+    pub tags: &'a [String],
+    pub author_id: Option<u64>,
+    pub author_name: Option<&'a str>,
+    /// Sha256 of the audio file
+    pub file_hash: &'a str,
+    pub file_size: u64,
+    pub duration_ms: u64,
+    /// Local path of the audio file. Only sent to the moderation command, which runs on the same
+    /// machine
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_path: Option<&'a path::Path>,
+}
+
+/// Answer of a moderation hook
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct ModerationVerdict {
+    pub allow: bool,
+    /// Shown to the uploader when rejected
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl ModerationVerdict {
+    fn check(self) -> Result<(), ModerationError> {
+        match self.allow {
+            true => Ok(()),
+            false => Err(ModerationError::Rejected {
+                reason: self.reason.unwrap_or_default(),
+            }),
+        }
+    }
+}
+
+/// Whether moderation hooks are set up
+pub fn enabled(config: &Config) -> bool {
+    config.moderation_webhook_url.is_some() || config.moderation_command.is_some()
+}
+
+/// Ask the moderation webhook & command whether audio can go live. Hooks that fail or time out
+/// reject the audio, so nothing slips through while a moderation service is down
+pub async fn check(config: &Config, request: &ModerationRequest<'_>) -> Result<(), PoiseError> {
+    if let Some(url) = &config.moderation_webhook_url {
+        let verdict = ask_webhook(url, request)
+            .await
+            .log_err_msg("Moderation webhook failed")
+            .map_err(|_| ModerationError::Unavailable)?;
+        log_verdict("webhook", request, &verdict);
+        verdict.check()?;
+    }
+
+    if let Some(command) = &config.moderation_command {
+        let verdict = ask_command(command, request)
+            .await
+            .log_err_msg("Moderation command failed")
+            .map_err(|_| ModerationError::Unavailable)?;
+        log_verdict("command", request, &verdict);
+        verdict.check()?;
+    }
+
+    Ok(())
+}
+
+fn log_verdict(hook: &str, request: &ModerationRequest<'_>, verdict: &ModerationVerdict) {
+    if !verdict.allow {
+        log::warn!(
+            "Moderation {hook} rejected `{}` - {}. author_id: {:?}",
+            request.name,
+            verdict.reason.as_deref().unwrap_or("no reason"),
+            request.author_id
+        );
+    }
+}
+
+/// POST request to url, which answers with a verdict
+async fn ask_webhook(
+    url: &str,
+    request: &ModerationRequest<'_>,
+) -> Result<ModerationVerdict, PoiseError> {
+    let request = ModerationRequest {
+        audio_path: None,
+        ..*request
+    };
+
+    let body = reqwest::Client::new()
+        .post(url)
+        .header(
+            reqwest::header::USER_AGENT,
+            format!("soundboard-bot/{}", version::VERSION),
+        )
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&request)?)
+        .timeout(HOOK_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())?
+        .text()
+        .await?;
+
+    Ok(serde_json::from_str(&body)?)
+}
+
+/// Run command with request on stdin, which prints a verdict to stdout
+async fn ask_command(
+    command: &path::Path,
+    request: &ModerationRequest<'_>,
+) -> Result<ModerationVerdict, PoiseError> {
+    let mut child = tokio::process::Command::new(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()?;
+
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or("No stdin for moderation command")?;
+    stdin.write_all(&serde_json::to_vec(request)?).await?;
+    drop(stdin);
+
+    let output = tokio::time::timeout(HOOK_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| "Moderation command timed out")??;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("Moderation command failed - {}", stderr.trim()).into());
+    }
+
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> ModerationRequest<'static> {
+        ModerationRequest {
+            action: "new",
+            name: "airhorn",
+            tags: &[],
+            author_id: Some(1),
+            author_name: Some("bob"),
+            file_hash: "abc",
+            file_size: 3,
+            duration_ms: 1500,
+            audio_path: None,
+        }
+    }
+
+    #[test]
+    fn moderation_verdict_test() {
+        let verdict: ModerationVerdict = serde_json::from_str(r#"{"allow": true}"#).unwrap();
+        assert!(verdict.check().is_ok());
+
+        let verdict: ModerationVerdict =
+            serde_json::from_str(r#"{"allow": false, "reason": "Too loud"}"#).unwrap();
+        assert_eq!(
+            verdict.check().unwrap_err().to_string(),
+            "Sound was rejected by moderation. Reason: Too loud"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn moderation_command_test() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(crate::helpers::uuid_v4_str());
+        std::fs::create_dir(&dir).unwrap();
+        let script = dir.join("moderate.sh");
+        // rejects sounds named airhorn
+        std::fs::write(
+            &script,
+            "#!/bin/sh\nif grep -q '\"name\":\"airhorn\"'; then echo '{\"allow\": false}'; else echo '{\"allow\": true}'; fi\n",
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let verdict = ask_command(&script, &request()).await.unwrap();
+        assert!(!verdict.allow);
+
+        let allowed = ModerationRequest {
+            name: "trombone",
+            ..request()
+        };
+        assert!(ask_command(&script, &allowed).await.unwrap().allow);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
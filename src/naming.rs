@@ -0,0 +1,184 @@
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::errors::NamingError;
+
+/// Punctuation outside ascii allowed in unicode names, ie: curly quotes & dashes
+const UNICODE_PUNCTUATION: &str = "‘’‚“”„–—…¡¿«»‹›·";
+
+/// Characters sound names can use, other than spaces
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NameCharset {
+    /// Letters & numbers of any language, and punctuation. No emoji, symbols, or control characters
+    #[default]
+    Unicode,
+    /// Printable ascii
+    Ascii,
+}
+
+impl NameCharset {
+    fn allows(&self, character: char) -> bool {
+        match self {
+            Self::Unicode => {
+                character.is_alphanumeric()
+                    || character.is_ascii_punctuation()
+                    || UNICODE_PUNCTUATION.contains(character)
+            }
+            Self::Ascii => character.is_ascii_graphic(),
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Self::Unicode => "letters, numbers, spaces, and punctuation",
+            Self::Ascii => "ascii letters, numbers, spaces, and punctuation",
+        }
+    }
+}
+
+/// Name following the configured naming rules. Runs of whitespace, ie: newlines, become a single
+/// space, and names are made lowercase slugs when slugs are on, ie: `Air Horn!` → `air-horn`
+pub fn normalize(config: &Config, name: &str) -> Result<String, NamingError> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    let name = match config.sound_name_slugs {
+        true => slugify(&name),
+        false => name,
+    };
+
+    if name.is_empty() {
+        return Err(NamingError::Empty);
+    }
+
+    let charset = config.sound_name_charset;
+    if let Some(character) = name.chars().find(|c| *c != ' ' && !charset.allows(*c)) {
+        return Err(NamingError::InvalidCharacter {
+            character: character.escape_debug().to_string(),
+            allowed: charset.describe(),
+        });
+    }
+
+    let length = name.chars().count();
+    if length < config.sound_name_min_length {
+        return Err(NamingError::TooShort {
+            name,
+            min: config.sound_name_min_length,
+        });
+    }
+    if length > config.sound_name_max_length {
+        return Err(NamingError::TooLong {
+            max: config.sound_name_max_length,
+        });
+    }
+
+    Ok(name)
+}
+
+/// Check name already follows the naming rules
+pub fn check(config: &Config, name: &str) -> Result<(), NamingError> {
+    let normalized = normalize(config, name)?;
+    match normalized == name {
+        true => Ok(()),
+        false => Err(NamingError::NotNormalized {
+            name: name.into(),
+            suggestion: normalized,
+        }),
+    }
+}
+
+/// Name with a number added to tell it apart from a taken name, ie: `airhorn (2)`, or
+/// `airhorn-2` with slugs. Name is cut short to keep within the max length
+pub fn numbered(config: &Config, name: &str, number: u32) -> String {
+    let suffix = match config.sound_name_slugs {
+        true => format!("-{number}"),
+        false => format!(" ({number})"),
+    };
+
+    let max_length = config
+        .sound_name_max_length
+        .saturating_sub(suffix.chars().count());
+    let base: String = name.chars().take(max_length).collect();
+    format!("{}{suffix}", base.trim_end_matches([' ', '-']))
+}
+
+fn slugify(name: &str) -> String {
+    let slug: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| match c.is_alphanumeric() {
+            true => c,
+            false => '-',
+        })
+        .collect();
+
+    slug.split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_test() {
+        let mut config = Config::default();
+        assert_eq!(
+            normalize(&config, "  Air\nHorn!  ").unwrap(),
+            "Air Horn!".to_string()
+        );
+        assert_eq!(normalize(&config, "¿Qué pasa?").unwrap(), "¿Qué pasa?");
+        assert_eq!(
+            normalize(&config, "airhorn 📯").unwrap_err(),
+            NamingError::InvalidCharacter {
+                character: "📯".into(),
+                allowed: NameCharset::Unicode.describe(),
+            }
+        );
+        assert_eq!(normalize(&config, " \n").unwrap_err(), NamingError::Empty);
+        assert_eq!(
+            normalize(&config, &"a".repeat(81)).unwrap_err(),
+            NamingError::TooLong { max: 80 }
+        );
+
+        config.sound_name_slugs = true;
+        config.sound_name_min_length = 3;
+        assert_eq!(normalize(&config, "Air Horn!").unwrap(), "air-horn");
+        assert_eq!(
+            normalize(&config, "--Bruh -- moment--").unwrap(),
+            "bruh-moment"
+        );
+        assert_eq!(
+            normalize(&config, "O!").unwrap_err(),
+            NamingError::TooShort {
+                name: "o".into(),
+                min: 3
+            }
+        );
+
+        config.sound_name_charset = NameCharset::Ascii;
+        assert!(normalize(&config, "Qué").is_err());
+
+        assert!(check(&config, "air-horn").is_ok());
+        assert_eq!(
+            check(&config, "Air Horn").unwrap_err(),
+            NamingError::NotNormalized {
+                name: "Air Horn".into(),
+                suggestion: "air-horn".into()
+            }
+        );
+    }
+
+    #[test]
+    fn numbered_test() {
+        let mut config = Config::default();
+        assert_eq!(numbered(&config, "airhorn", 2), "airhorn (2)");
+        assert_eq!(numbered(&config, &"a".repeat(80), 12).chars().count(), 80);
+
+        config.sound_name_slugs = true;
+        config.sound_name_max_length = 8;
+        assert_eq!(numbered(&config, "air-horn", 3), "air-ho-3");
+        assert_eq!(numbered(&config, "air-horn-blast", 3), "air-ho-3");
+    }
+}
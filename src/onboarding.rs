@@ -0,0 +1,145 @@
+use serenity::all::{
+    audit_log, ButtonStyle, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter,
+    CreateMessage, Guild, GuildId, MemberAction, UserId,
+};
+use serenity::client::Context;
+
+use crate::commands::PoiseError;
+use crate::common::{LogResult, UserData};
+use crate::helpers::ButtonCustomId;
+use crate::retry;
+use crate::settings::{self, GuildSettingKey, GuildSettings};
+
+/// Settings worth looking at right after inviting the bot, in the order they're listed
+const SETUP_SETTINGS: [GuildSettingKey; 5] = [
+    GuildSettingKey::ModeratorRole,
+    GuildSettingKey::UserRole,
+    GuildSettingKey::AuditChannel,
+    GuildSettingKey::RequestChannel,
+    GuildSettingKey::Volume,
+];
+
+pub fn welcome_embed(guild_name: &str) -> CreateEmbed {
+    CreateEmbed::new()
+        .title("Thanks for adding Soundboard Bot!")
+        .description(format!(
+            "Sounds are played in voice channels of **{guild_name}** by pressing their buttons on a soundboard."
+        ))
+        .field(
+            "1. Setup",
+            "Pick a moderator role to manage sounds, and optionally a role required to play them, \
+            with `/settings set`. Press **Setup** to see the current settings",
+            false,
+        )
+        .field(
+            "2. Add Sounds",
+            "Add sounds with `/sounds add`, or let members request them with `/request`",
+            false,
+        )
+        .field(
+            "3. Play",
+            "Join a voice channel, then press **Show Soundboard** or run `/sounds display`",
+            false,
+        )
+        .footer(CreateEmbedFooter::new("/sounds help lists every command"))
+}
+
+/// Buttons of welcome message. Only the setup button works outside of the guild, ie: in DMs
+pub fn welcome_action_row(guild_id: GuildId, in_guild: bool) -> CreateActionRow {
+    let mut buttons = vec![CreateButton::new(ButtonCustomId::OnboardingSetup(guild_id))
+        .label("Setup")
+        .style(ButtonStyle::Primary)];
+    if in_guild {
+        buttons.push(CreateButton::new(ButtonCustomId::DisplaySounds).label("Show Soundboard"));
+    }
+
+    CreateActionRow::Buttons(buttons)
+}
+
+/// Current values of the settings to set up first
+pub fn setup_message(data: &UserData, guild_id: GuildId) -> String {
+    let guild_settings = GuildSettings::load(&data.settings_table(), guild_id);
+    let settings: String = SETUP_SETTINGS
+        .iter()
+        .map(|key| {
+            let value = guild_settings.value(*key);
+            format!("{}\n", settings::format_setting(*key, value.as_deref()))
+        })
+        .collect();
+
+    format!(
+        "{settings}\nChange them with `/settings set {{key}} {{value}}` (requires Manage Server). \
+        `/settings list` shows every setting"
+    )
+}
+
+/// Post welcome message in guild's system channel, or DM whoever invited the bot when there's
+/// no system channel (or posting there fails)
+pub async fn welcome(ctx: &Context, guild: &Guild) -> Result<(), PoiseError> {
+    log::info!(
+        "Joined guild {} ({}), sending welcome",
+        guild.name,
+        guild.id
+    );
+
+    let embed = welcome_embed(&guild.name);
+
+    if let Some(channel_id) = guild.system_channel_id {
+        let message = CreateMessage::new()
+            .embed(embed.clone())
+            .components(vec![welcome_action_row(guild.id, true)]);
+        let sent = retry::discord_retry("send welcome message", || {
+            channel_id.send_message(&ctx.http, message.clone())
+        })
+        .await
+        .log_err_msg(format!(
+            "Failed posting welcome in system channel of {}",
+            guild.id
+        ));
+
+        if sent.is_ok() {
+            return Ok(());
+        }
+    }
+
+    let inviter = find_inviter(ctx, guild.id).await.ok_or(format!(
+        "No channel or inviter to welcome guild {}",
+        guild.id
+    ))?;
+
+    let message = CreateMessage::new()
+        .embed(embed)
+        .components(vec![welcome_action_row(guild.id, false)]);
+    inviter
+        .direct_message(&ctx.http, message)
+        .await
+        .log_err_msg(format!("Failed sending welcome DM to {inviter}"))?;
+
+    Ok(())
+}
+
+/// Member who added the bot to guild, from the guild's audit log. Needs the View Audit Log permission
+async fn find_inviter(ctx: &Context, guild_id: GuildId) -> Option<UserId> {
+    let bot_id = ctx.cache.current_user().id;
+
+    let logs = guild_id
+        .audit_logs(
+            &ctx.http,
+            Some(audit_log::Action::Member(MemberAction::BotAdd)),
+            None,
+            None,
+            Some(10),
+        )
+        .await
+        .log_err_msg(format!("Failed reading audit log of {guild_id}"))
+        .ok()?;
+
+    logs.entries
+        .iter()
+        .find(|entry| {
+            entry
+                .target_id
+                .is_some_and(|target| target.get() == bot_id.get())
+        })
+        .map(|entry| entry.user_id)
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+
+use serenity::all::{ButtonStyle, CreateActionRow, CreateButton};
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::OperationError;
+use crate::helpers::ButtonCustomId;
+
+/// Long running operations (scans, imports, exports) that can be cancelled from their reply's
+/// Cancel button, by the id carried in the button
+#[derive(Default)]
+pub struct Operations {
+    next_id: AtomicI64,
+    tokens: Mutex<HashMap<i64, CancellationToken>>,
+}
+
+impl Operations {
+    /// Register a new operation. It stops being cancellable once dropped
+    pub fn start(&self) -> Operation<'_> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let token = CancellationToken::new();
+        self.tokens.lock().unwrap().insert(id, token.clone());
+
+        Operation {
+            id,
+            token,
+            operations: self,
+        }
+    }
+
+    /// Cancel operation. Returns false when it already finished
+    pub fn cancel(&self, id: i64) -> bool {
+        match self.tokens.lock().unwrap().get(&id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Running operation, whose token is threaded through the work it does
+pub struct Operation<'a> {
+    pub id: i64,
+    pub token: CancellationToken,
+    operations: &'a Operations,
+}
+
+impl Operation<'_> {
+    /// Cancel button of operation's reply
+    pub fn cancel_action_row(&self) -> CreateActionRow {
+        cancel_action_row(self.id)
+    }
+}
+
+impl Drop for Operation<'_> {
+    fn drop(&mut self) {
+        self.operations.tokens.lock().unwrap().remove(&self.id);
+    }
+}
+
+pub fn cancel_action_row(id: i64) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![CreateButton::new(ButtonCustomId::CancelOperation(id))
+        .label("Cancel")
+        .style(ButtonStyle::Danger)])
+}
+
+/// Errors once token is cancelled. Called between steps of an operation
+pub fn check(token: &CancellationToken) -> Result<(), OperationError> {
+    match token.is_cancelled() {
+        true => Err(OperationError::Cancelled),
+        false => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_operation_test() {
+        let operations = Operations::default();
+        let operation = operations.start();
+        let other = operations.start();
+        assert_ne!(operation.id, other.id);
+
+        assert!(check(&operation.token).is_ok());
+        assert!(operations.cancel(operation.id));
+        assert_eq!(check(&operation.token), Err(OperationError::Cancelled));
+        assert!(check(&other.token).is_ok());
+
+        let id = other.id;
+        drop(other);
+        assert!(!operations.cancel(id));
+    }
+}
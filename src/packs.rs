@@ -0,0 +1,486 @@
+use std::path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton, CreateMessage,
+    EditInteractionResponse, GuildId, User,
+};
+use serenity::client::Context;
+use tokio_util::sync::CancellationToken;
+
+use crate::audio;
+use crate::commands::{PoiseError, PoiseResult};
+use crate::common::{LogResult, UserData};
+use crate::db::{AudioTableRow, Tags, UniqueAudioTableCol};
+use crate::helpers::{self, ButtonCustomId};
+use crate::ingest::{Ingest, IngestSource, IngestTarget};
+use crate::naming;
+use crate::version;
+
+/// Most sounds published in one pack
+pub const MAX_PACK_SOUNDS: u64 = 200;
+
+/// Most index packs listed by `/packs browse`, keeping the reply within a message
+pub const MAX_BROWSED_PACKS: usize = 15;
+
+/// File describing a pack, in the pack's directory
+const MANIFEST_FILE: &str = "pack.json";
+
+/// Tagged sounds published by a guild, so other deployments sharing the packs dir can install them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackManifest {
+    /// Share code, also the name of the pack's directory
+    pub code: String,
+    pub name: String,
+    pub guild_id: u64,
+    pub published_at: chrono::DateTime<chrono::Utc>,
+    pub sounds: Vec<PackSound>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackSound {
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub emoji: Option<String>,
+    /// Volume percentage, 0-200
+    #[serde(default)]
+    pub volume: Option<u32>,
+    /// Audio file name, in the pack's directory. Empty for sounds of the packs index
+    #[serde(default)]
+    pub file: String,
+    /// Download url of the audio, for sounds of the packs index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+/// Starter packs new deployments can install, fetched from the packs index url
+#[derive(Debug, Deserialize)]
+pub struct PackIndex {
+    pub packs: Vec<IndexedPack>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndexedPack {
+    /// Installed by, ie: `/packs install memes`
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub sounds: Vec<PackSound>,
+}
+
+impl PackIndex {
+    /// Pack with id, ignoring case
+    pub fn find(&self, id: &str) -> Option<&IndexedPack> {
+        let id = id.trim();
+        self.packs
+            .iter()
+            .find(|pack| pack.id.eq_ignore_ascii_case(id))
+    }
+}
+
+/// Sounds of a pack install, by what happened to them
+#[derive(Debug, Default)]
+pub struct PackInstallReport {
+    pub installed: Vec<String>,
+    /// Sounds named like a sound already in the library
+    pub skipped: Vec<String>,
+    pub failed: Vec<(String, String)>,
+}
+
+impl PackInstallReport {
+    /// What an install would do, from a dry run. ie: "Would install 3 sounds, skipping 1 already
+    /// in the library"
+    pub fn preview_text(&self) -> String {
+        let mut text = format!(
+            "Would install {} sounds, skipping {} already in the library",
+            self.installed.len(),
+            self.skipped.len()
+        );
+        if !self.failed.is_empty() {
+            let failed: Vec<_> = self
+                .failed
+                .iter()
+                .map(|(name, err)| format!("`{name}` ({err})"))
+                .collect();
+            text.push_str(&format!(". Can't install: {}", failed.join(", ")));
+        }
+        text
+    }
+}
+
+impl std::fmt::Display for PackInstallReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Installed {} sounds, skipped {} already in the library",
+            self.installed.len(),
+            self.skipped.len()
+        )?;
+        if !self.failed.is_empty() {
+            let failed: Vec<_> = self
+                .failed
+                .iter()
+                .map(|(name, err)| format!("`{name}` ({err})"))
+                .collect();
+            write!(f, ". Failed: {}", failed.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+const NO_PACKS_ERR: &str =
+    "Sound packs need a packs directory or index, set with `DISCORD_BOT_PACKS_DIR` or `DISCORD_BOT_PACKS_INDEX_URL`";
+
+/// New share code, ie: `3F9A1C2B`
+fn share_code() -> String {
+    helpers::uuid_v4_str()[..8].to_uppercase()
+}
+
+/// Directory of pack with code. Codes are checked, so they can't point outside the packs dir
+fn pack_dir(packs_dir: &path::Path, code: &str) -> Result<path::PathBuf, PoiseError> {
+    let code = code.trim().to_uppercase();
+    if code.is_empty() || !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("`{code}` isn't a pack share code").into());
+    }
+    Ok(packs_dir.join(code))
+}
+
+/// Copy audio files of rows into a new pack directory, along with its manifest
+pub fn write_pack(
+    packs_dir: &path::Path,
+    name: &str,
+    guild_id: GuildId,
+    rows: &[AudioTableRow],
+) -> Result<PackManifest, PoiseError> {
+    let code = share_code();
+    let dir = pack_dir(packs_dir, &code)?;
+    std::fs::create_dir_all(&dir).log_err_msg("Failed creating pack directory")?;
+
+    let mut sounds = vec![];
+    for row in rows {
+        let file = row.audio_file.file_name_lossy();
+        std::fs::copy(row.audio_file.as_path_buf(), dir.join(&file))
+            .log_err_msg(format!("Failed copying `{}` into pack", row.name))?;
+        sounds.push(PackSound {
+            name: row.name.clone(),
+            tags: row.tags.to_vec(),
+            emoji: row.emoji.clone(),
+            volume: row.volume,
+            file,
+            url: None,
+        });
+    }
+
+    let manifest = PackManifest {
+        code,
+        name: name.into(),
+        guild_id: guild_id.get(),
+        published_at: chrono::Utc::now(),
+        sounds,
+    };
+    std::fs::write(
+        dir.join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&manifest)?,
+    )
+    .log_err_msg("Failed writing pack manifest")?;
+
+    log::info!(
+        "Published pack {} `{name}` of {} sounds. guild_id: {guild_id}",
+        manifest.code,
+        manifest.sounds.len()
+    );
+    Ok(manifest)
+}
+
+pub fn read_pack(packs_dir: &path::Path, code: &str) -> Result<PackManifest, PoiseError> {
+    let path = pack_dir(packs_dir, code)?.join(MANIFEST_FILE);
+    let content = std::fs::read(&path).map_err(|_| format!("No pack found with code `{code}`"))?;
+    Ok(serde_json::from_slice(&content)?)
+}
+
+/// Packs listed by the index at url
+pub async fn fetch_index(url: &str) -> Result<PackIndex, PoiseError> {
+    let body = reqwest::Client::new()
+        .get(url)
+        .header(
+            reqwest::header::USER_AGENT,
+            format!("soundboard-bot/{}", version::VERSION),
+        )
+        .timeout(Duration::from_secs(30))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .log_err_msg("Failed fetching packs index")?
+        .text()
+        .await?;
+
+    Ok(serde_json::from_str(&body).log_err_msg("Failed parsing packs index")?)
+}
+
+/// Publish sounds tagged with tag as a pack. Needs a packs dir
+pub fn publish(data: &UserData, guild_id: GuildId, tag: &str) -> Result<PackManifest, PoiseError> {
+    let packs_dir = data.config.get().packs_dir.clone().ok_or(
+        "Publishing sound packs needs a packs directory, set with `DISCORD_BOT_PACKS_DIR`",
+    )?;
+
+    let rows = data.audio_table().tag_rows(tag, MAX_PACK_SOUNDS)?;
+    if rows.is_empty() {
+        return Err(format!("No sounds are tagged `{tag}`").into());
+    }
+
+    write_pack(&packs_dir, tag, guild_id, &rows)
+}
+
+/// Pack found by its share code or index id, and where its audio comes from
+pub struct FoundPack {
+    pub name: String,
+    /// Directory of the pack's audio files. None for packs of the index, downloaded from urls
+    dir: Option<path::PathBuf>,
+    pub sounds: Vec<PackSound>,
+}
+
+/// Find pack with code, in the packs dir or the packs index
+pub async fn find(data: &UserData, code: &str) -> Result<FoundPack, PoiseError> {
+    let config = data.config.get();
+    if config.packs_dir.is_none() && config.packs_index_url.is_none() {
+        return Err(NO_PACKS_ERR.into());
+    }
+
+    if let Some(packs_dir) = &config.packs_dir {
+        match read_pack(packs_dir, code) {
+            Ok(manifest) => {
+                return Ok(FoundPack {
+                    name: manifest.name,
+                    dir: Some(pack_dir(packs_dir, code)?),
+                    sounds: manifest.sounds,
+                });
+            }
+            Err(err) if config.packs_index_url.is_none() => return Err(err),
+            Err(_) => {}
+        }
+    }
+
+    if let Some(index_url) = &config.packs_index_url {
+        let index = fetch_index(index_url).await?;
+        if let Some(pack) = index.find(code) {
+            return Ok(FoundPack {
+                name: pack.name.clone(),
+                dir: None,
+                sounds: pack.sounds.clone(),
+            });
+        }
+    }
+
+    Err(format!("No pack found with code `{}`", code.trim()).into())
+}
+
+/// Add sounds of pack to the library. Sounds run through ingestion, so the library's size &
+/// duration limits apply to them. Nothing is added when `dry_run` is set, sounds that would be
+/// are reported as installed
+pub async fn install(
+    data: &UserData,
+    author: &User,
+    pack: &FoundPack,
+    dry_run: bool,
+) -> PackInstallReport {
+    log::info!(
+        "Installing pack `{}` of {} sounds. dry_run: {dry_run}",
+        pack.name,
+        pack.sounds.len()
+    );
+
+    let mut report = PackInstallReport::default();
+    for sound in pack.sounds.iter() {
+        let name = match naming::normalize(&data.config.get(), &sound.name) {
+            Ok(name) => name,
+            Err(err) => {
+                report.failed.push((sound.name.clone(), err.to_string()));
+                continue;
+            }
+        };
+        if data
+            .audio_table()
+            .find_audio_row(UniqueAudioTableCol::Name(name.clone()))
+            .is_some()
+        {
+            report.skipped.push(name);
+            continue;
+        }
+        if dry_run {
+            report.installed.push(name);
+            continue;
+        }
+
+        match install_sound(data, author, pack.dir.as_deref(), &name, sound).await {
+            Ok(()) => report.installed.push(name),
+            Err(err) => report.failed.push((name, err.to_string())),
+        }
+    }
+    report
+}
+
+/// Confirm & cancel buttons of a pack install preview
+pub fn confirm_action_row(code: &str, count: usize) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(ButtonCustomId::ConfirmPackInstall(code.trim().to_string()))
+            .label(format!("Install {count} sounds"))
+            .style(ButtonStyle::Primary),
+        CreateButton::new(ButtonCustomId::CancelPackInstall).label("Cancel"),
+    ])
+}
+
+/// Install pack from the confirm button of its preview. The interaction must already be
+/// acknowledged
+pub async fn confirm_install(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+    code: String,
+) -> PoiseResult {
+    let reply = |content: String| async move {
+        component
+            .edit_response(
+                ctx,
+                EditInteractionResponse::new()
+                    .content(content)
+                    .components(vec![]),
+            )
+            .await
+            .log_err_msg("Failed replying to pack install confirmation")
+            .ok();
+    };
+
+    reply(format!("Installing pack `{code}`...")).await;
+    let text = match find(data, &code).await {
+        Ok(pack) => {
+            let report = install(data, &component.user, &pack, false).await;
+            format!("Pack `{}`: {report}", pack.name)
+        }
+        Err(err) => format!("Failed installing pack: {err}"),
+    };
+    reply(text.clone()).await;
+
+    helpers::send_audit_message(
+        ctx,
+        data,
+        component.guild_id,
+        CreateMessage::new().content(format!("{text}, installed by <@{}>", component.user.id)),
+    )
+    .await;
+
+    Ok(())
+}
+
+async fn install_sound(
+    data: &UserData,
+    author: &User,
+    dir: Option<&path::Path>,
+    name: &str,
+    sound: &PackSound,
+) -> Result<(), PoiseError> {
+    let temp_file = match (dir, &sound.url) {
+        (Some(dir), _) => {
+            // file names come from the manifest, keep them inside the pack dir
+            let file_name = path::Path::new(&sound.file)
+                .file_name()
+                .ok_or("Invalid file name")?;
+            let temp_file = std::env::temp_dir().join(format!("{}.mp3", helpers::uuid_v4_str()));
+            std::fs::copy(dir.join(file_name), &temp_file).map_err(|err| err.to_string())?;
+            temp_file
+        }
+        // index is picked by the bot's operator, so its downloads don't need url imports on
+        (None, Some(url)) => {
+            let max_size = data.config.get().max_audio_file_size;
+            audio::download_audio_url_temp(url, max_size, &CancellationToken::new()).await?
+        }
+        (None, None) => return Err("No download url".into()),
+    };
+
+    Ingest::new(data)
+        .run(
+            IngestSource::TempFile(temp_file),
+            IngestTarget::New {
+                name,
+                tags: Tags::from(sound.tags.clone()),
+                author,
+            },
+        )
+        .await?;
+
+    if sound.emoji.is_some() || sound.volume.is_some() {
+        let table = data.audio_table();
+        if let Some(row) = table.find_audio_row(UniqueAudioTableCol::Name(name.into())) {
+            table.update_metadata(
+                row.id,
+                &row.name,
+                &row.tags,
+                sound.emoji.as_deref(),
+                sound.volume.map(|volume| volume.min(200)),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_read_pack_test() {
+        let dir = std::env::temp_dir().join(helpers::uuid_v4_str());
+        std::fs::create_dir(&dir).unwrap();
+        let audio_path = dir.join("airhorn.mp3");
+        std::fs::write(&audio_path, b"mp3").unwrap();
+
+        let row = AudioTableRow {
+            tags: Tags::from("meme loud"),
+            emoji: Some("📯".into()),
+            volume: Some(80),
+            ..AudioTableRow::test_row(1)
+                .with_name("airhorn")
+                .with_audio_file(audio_path)
+        };
+
+        let packs_dir = dir.join("packs");
+        let manifest = write_pack(&packs_dir, "meme", GuildId::new(1), &[row]).unwrap();
+        assert_eq!(manifest.code.len(), 8);
+        assert_eq!(manifest.sounds[0].tags, vec!["meme", "loud"]);
+        assert!(packs_dir.join(&manifest.code).join("airhorn.mp3").exists());
+
+        // codes are case insensitive
+        let read = read_pack(&packs_dir, &manifest.code.to_lowercase()).unwrap();
+        assert_eq!(read, manifest);
+
+        assert!(read_pack(&packs_dir, "../packs").is_err());
+        assert!(read_pack(&packs_dir, "AAAAAAAA").is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn pack_index_test() {
+        let index: PackIndex = serde_json::from_str(
+            r#"{"packs": [{
+                "id": "memes",
+                "name": "Meme starter pack",
+                "sounds": [{"name": "airhorn", "tags": ["meme"], "url": "https://example.com/airhorn.mp3"}]
+            }]}"#,
+        )
+        .unwrap();
+
+        let pack = index.find(" MEMES").unwrap();
+        assert_eq!(pack.description, None);
+        assert_eq!(pack.sounds[0].file, "");
+        assert_eq!(
+            pack.sounds[0].url.as_deref(),
+            Some("https://example.com/airhorn.mp3")
+        );
+        assert!(index.find("games").is_none());
+    }
+}
@@ -0,0 +1,2104 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serenity::all::{ChannelId, GuildId, MessageId, UserId};
+use tokio::sync::broadcast;
+
+use crate::audio::AudioFile;
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::db::{AudioTableRow, SettingsTable, UserPrefsTable};
+use crate::errors::AudioError;
+use crate::quiet_hours;
+use crate::settings::{self, GuildSettings};
+use crate::voice::{ChatBackend, TrackControl, TrackEndCallback, VoiceBackend};
+
+/// Sounds tagged with this are ambience, paused while members talk in guilds with speech pause on
+pub const AMBIENCE_TAG: &str = "ambience";
+
+/// Most ambience layers looping at once in a guild
+pub const MAX_AMBIENCE_LAYERS: usize = 4;
+
+/// Volume changes a crossfade is made of
+const CROSSFADE_STEPS: u32 = 20;
+
+/// Playback events kept for subscribers that fall behind
+const EVENT_CAPACITY: usize = 256;
+
+/// Discord message reporting a track's status. Edited as the track starts, finishes, or fails
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatusMessage {
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+}
+
+#[derive(Debug, Clone)]
+pub struct QueuedTrack {
+    pub name: String,
+    pub audio_file: AudioFile,
+    pub channel_id: ChannelId,
+    pub status_message: Option<StatusMessage>,
+    /// Volume overriding guild's volume setting, where 1.0 is unchanged volume
+    pub volume: Option<f32>,
+    /// Volume of the sound relative to others. Scales the playback volume
+    pub sound_volume: f32,
+    /// Play over tracks already playing instead of queueing. Defaults to guild's overlap setting
+    pub overlap: Option<bool>,
+    /// Queued in the priority lane, ahead of regular tracks
+    pub priority: bool,
+    /// Member who played the track, shown in the guild's now playing message
+    pub requested_by: Option<UserId>,
+    /// Background sound, paused while members talk
+    pub ambience: bool,
+    /// Play time, when known. Counted against the guild's max queue duration
+    pub duration: Option<std::time::Duration>,
+    /// Queued past the guild's queue limits, ie: played by an admin
+    pub bypass_limits: bool,
+}
+
+impl QueuedTrack {
+    pub fn new(name: String, audio_file: AudioFile, channel_id: ChannelId) -> Self {
+        Self {
+            name,
+            audio_file,
+            channel_id,
+            status_message: None,
+            volume: None,
+            sound_volume: 1.0,
+            overlap: None,
+            priority: false,
+            requested_by: None,
+            ambience: false,
+            duration: None,
+            bypass_limits: false,
+        }
+    }
+
+    /// Track for sound of soundboard, played at the sound's volume
+    pub fn from_row(row: &AudioTableRow, channel_id: ChannelId) -> Self {
+        let mut track = Self::new(row.name.clone(), row.audio_file.clone(), channel_id);
+        track.ambience = row
+            .tags
+            .iter()
+            .any(|tag| tag.eq_ignore_ascii_case(AMBIENCE_TAG));
+        match row.volume {
+            Some(percent) => track.sound_volume(percent as f32 / 100.0),
+            None => track,
+        }
+    }
+
+    pub fn status_message(mut self, status_message: StatusMessage) -> Self {
+        self.status_message = Some(status_message);
+        self
+    }
+
+    pub fn volume(mut self, volume: f32) -> Self {
+        self.volume = Some(volume);
+        self
+    }
+
+    pub fn sound_volume(mut self, sound_volume: f32) -> Self {
+        self.sound_volume = sound_volume;
+        self
+    }
+
+    pub fn overlap(mut self, overlap: bool) -> Self {
+        self.overlap = Some(overlap);
+        self
+    }
+
+    pub fn priority(mut self, priority: bool) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn requested_by(mut self, user_id: UserId) -> Self {
+        self.requested_by = Some(user_id);
+        self
+    }
+
+    pub fn bypass_limits(mut self, bypass_limits: bool) -> Self {
+        self.bypass_limits = bypass_limits;
+        self
+    }
+}
+
+/// Limits on tracks waiting in a guild's queue, from the guild's settings
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct QueueLimits {
+    /// Most tracks waiting
+    pub max_length: Option<usize>,
+    /// Most play time waiting, summed over the queued tracks
+    pub max_duration: Option<std::time::Duration>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum EnqueueOutcome {
+    /// Nothing was playing, track started right away
+    Started,
+    /// Track waiting behind the current track. One based position in queue
+    Queued { position: usize },
+    /// Track started over tracks already playing
+    Overlapping,
+}
+
+struct PlayingTrack {
+    track: QueuedTrack,
+    handle: Arc<dyn TrackControl>,
+}
+
+/// Named looping track playing alongside the queue, ie: rain under a tavern
+struct AmbienceLayer {
+    name: String,
+    /// Volume of the layer relative to the guild's volume, where 1.0 is unchanged volume
+    volume: f32,
+    playing: PlayingTrack,
+}
+
+impl AmbienceLayer {
+    /// Layer's track, with the layer's volume folded into the sound's volume
+    fn scaled_track(&self) -> QueuedTrack {
+        let mut track = self.playing.track.clone();
+        track.sound_volume *= self.volume;
+        track
+    }
+}
+
+/// Change of what's playing in a guild, sent to subscribers of `Playback::subscribe`. Serialized
+/// for the HTTP API's event stream, tagged by `type`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlaybackEvent {
+    PlayStart {
+        guild_id: GuildId,
+        name: String,
+        requested_by: Option<UserId>,
+        /// Playing over the current track
+        overlapping: bool,
+    },
+    PlayEnd {
+        guild_id: GuildId,
+        name: String,
+        requested_by: Option<UserId>,
+        failed: bool,
+    },
+    /// Names of queued tracks, in play order
+    QueueUpdated {
+        guild_id: GuildId,
+        queue: Vec<String>,
+    },
+}
+
+impl PlaybackEvent {
+    pub fn guild_id(&self) -> GuildId {
+        match self {
+            Self::PlayStart { guild_id, .. }
+            | Self::PlayEnd { guild_id, .. }
+            | Self::QueueUpdated { guild_id, .. } => *guild_id,
+        }
+    }
+
+    /// Name of the event, ie: `play_start`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::PlayStart { .. } => "play_start",
+            Self::PlayEnd { .. } => "play_end",
+            Self::QueueUpdated { .. } => "queue_updated",
+        }
+    }
+}
+
+/// Snapshot of an ambience layer
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayerInfo {
+    pub name: String,
+    /// Name of the sound looping in the layer
+    pub sound: String,
+    pub volume: f32,
+}
+
+/// Snapshot of what's playing in a guild
+#[derive(Debug, Clone)]
+pub struct NowPlaying {
+    pub name: String,
+    /// Member who asked for the current track
+    pub requested_by: Option<UserId>,
+    pub paused: bool,
+    /// Time into current track
+    pub position: std::time::Duration,
+    /// Tracks playing over the current track
+    pub overlapping: usize,
+    pub queued: usize,
+}
+
+#[derive(Default)]
+struct GuildPlayback {
+    current: Option<PlayingTrack>,
+    /// Tracks playing over the current track, in overlap mode
+    overlapping: Vec<PlayingTrack>,
+    /// Overlapping tracks being started. Counted against the overlap cap before they play
+    overlap_starting: usize,
+    /// Set while the next track is being started, so concurrent enqueues wait in line
+    starting: bool,
+    /// Set while playback is paused. New tracks are queued rather than overlapped until resumed
+    paused: bool,
+    /// Set while playback is paused by members talking over ambience, rather than by a member
+    speech_paused: bool,
+    queue: VecDeque<QueuedTrack>,
+    /// Ambience layers looping alongside the queue, in the order they were started
+    layers: Vec<AmbienceLayer>,
+    /// Set while ambience layers are paused by members talking
+    layers_speech_paused: bool,
+    /// Number of tracks started. Idle timers compare it to tell if anything played while they slept
+    started_count: u64,
+    /// When the guild's latest track started. The bot's activity shows the latest track of all guilds
+    started_at: Option<std::time::Instant>,
+    /// Number of now playing message updates. Updates sent in the background skip themselves
+    /// once a newer one was made
+    now_playing_version: u64,
+}
+
+impl GuildPlayback {
+    fn is_idle(&self) -> bool {
+        self.current.is_none()
+            && !self.starting
+            && self.overlapping.is_empty()
+            && self.overlap_starting == 0
+            && self.layers.is_empty()
+    }
+
+    /// Whether a track is playing (or starting) that the queue waits on
+    fn current_busy(&self) -> bool {
+        self.current.is_some() || self.starting
+    }
+
+    /// Whether starting ambience layer would be one too many. Restarting a playing layer isn't
+    fn layers_full(&self, layer: &str) -> bool {
+        self.layers
+            .iter()
+            .filter(|other| other.name != layer)
+            .count()
+            >= MAX_AMBIENCE_LAYERS
+    }
+
+    /// Tracks playing, or about to
+    fn active_count(&self) -> usize {
+        self.current_busy() as usize + self.overlapping.len() + self.overlap_starting
+    }
+
+    /// Refuse track if queueing it would go over limits, unless it bypasses them. Tracks of
+    /// unknown duration count as no time
+    fn check_queue_limits(
+        &self,
+        track: &QueuedTrack,
+        limits: QueueLimits,
+    ) -> Result<(), AudioError> {
+        if track.bypass_limits {
+            return Ok(());
+        }
+
+        if let Some(max) = limits.max_length.filter(|max| self.queue.len() >= *max) {
+            return Err(AudioError::QueueFull { max });
+        }
+
+        if let Some(max) = limits.max_duration {
+            let queued: std::time::Duration =
+                self.queue.iter().filter_map(|queued| queued.duration).sum();
+            if queued + track.duration.unwrap_or_default() > max {
+                return Err(AudioError::QueueTooLong {
+                    name: track.name.clone(),
+                    max,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Push track to back of its lane. Priority tracks are kept at the front of the queue,
+    /// behind earlier priority tracks. Returns one based queue position
+    fn push(&mut self, track: QueuedTrack) -> usize {
+        if !track.priority {
+            self.queue.push_back(track);
+            return self.queue.len();
+        }
+
+        let index = self
+            .queue
+            .iter()
+            .rposition(|queued| queued.priority)
+            .map_or(0, |last| last + 1);
+        self.queue.insert(index, track);
+        index + 1
+    }
+
+    /// Zero based index of one based queue position
+    fn queue_index(&self, position: usize) -> Result<usize, AudioError> {
+        match position {
+            1.. if position <= self.queue.len() => Ok(position - 1),
+            _ => Err(AudioError::InvalidQueuePosition {
+                position,
+                len: self.queue.len(),
+            }),
+        }
+    }
+
+    /// Name of latest track still playing in guild
+    fn latest_track_name(&self) -> Option<&str> {
+        self.overlapping
+            .last()
+            .or(self.current.as_ref())
+            .map(|playing| playing.track.name.as_str())
+    }
+
+    /// Content of guild's now playing message
+    fn now_playing_text(&self) -> String {
+        let Some(playing) = &self.current else {
+            return "⏹️ Nothing playing".to_string();
+        };
+
+        let status = match self.paused {
+            true => "⏸️ Paused",
+            false => "🎶 Now playing",
+        };
+        let requested_by = playing
+            .track
+            .requested_by
+            .map(|user_id| format!(" (requested by <@{user_id}>)"))
+            .unwrap_or_default();
+
+        format!("{status}: `{}`{requested_by}", playing.track.name)
+    }
+
+    /// Move queued track between one based positions. Returns moved track
+    fn move_queued(&mut self, from: usize, to: usize) -> Result<&QueuedTrack, AudioError> {
+        let from = self.queue_index(from)?;
+        let to = self.queue_index(to)?;
+
+        let track = self.queue.remove(from).expect("queue index checked");
+        self.queue.insert(to, track);
+        Ok(&self.queue[to])
+    }
+}
+
+/// Per-guild playback state. Owns the track queue, handles of the currently playing track and
+/// tracks overlapping it, and advances the queue from track end events of the voice backend.
+pub struct Playback {
+    voice: Arc<dyn VoiceBackend>,
+    chat: Arc<dyn ChatBackend>,
+    db_pool: r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>,
+    guilds: Mutex<HashMap<GuildId, GuildPlayback>>,
+    /// Now playing message of each guild. Held while the message is sent or edited, so updates
+    /// don't race each other into sending two messages
+    now_playing_messages: tokio::sync::Mutex<HashMap<GuildId, StatusMessage>>,
+    /// Activity last shown on the bot, so it's only set when it changes
+    activity: Mutex<Option<String>>,
+    events: broadcast::Sender<PlaybackEvent>,
+}
+
+impl Playback {
+    pub fn new(
+        voice: Arc<dyn VoiceBackend>,
+        chat: Arc<dyn ChatBackend>,
+        db_pool: r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            voice,
+            chat,
+            db_pool,
+            guilds: Mutex::new(HashMap::new()),
+            now_playing_messages: tokio::sync::Mutex::new(HashMap::new()),
+            activity: Mutex::new(None),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        })
+    }
+
+    /// Receive playback events of every guild, from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<PlaybackEvent> {
+        self.events.subscribe()
+    }
+
+    fn send_event(&self, event: PlaybackEvent) {
+        // fails when nobody's subscribed
+        self.events.send(event).ok();
+    }
+
+    fn send_queue_updated(&self, guild_id: GuildId) {
+        self.send_event(PlaybackEvent::QueueUpdated {
+            guild_id,
+            queue: self.queued_track_names(guild_id),
+        });
+    }
+
+    fn send_play_end(&self, guild_id: GuildId, track: &QueuedTrack, failed: bool) {
+        self.send_event(PlaybackEvent::PlayEnd {
+            guild_id,
+            name: track.name.clone(),
+            requested_by: track.requested_by,
+            failed,
+        });
+    }
+
+    fn settings_table(&self) -> Option<SettingsTable> {
+        self.db_pool
+            .get()
+            .log_err_msg("Failed getting db connection")
+            .ok()
+            .map(SettingsTable::new)
+    }
+
+    /// Play track now if nothing is playing in guild, otherwise add it to the guild's queue.
+    /// Overlapping tracks play over whatever is playing, until the guild's cap is reached
+    pub async fn enqueue(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        mut track: QueuedTrack,
+    ) -> Result<EnqueueOutcome, AudioError> {
+        let settings = self
+            .settings_table()
+            .map(|table| GuildSettings::load(&table, guild_id));
+        let max_overlapping = match track.overlap {
+            Some(false) => None,
+            overlap => settings.as_ref().and_then(|settings| {
+                let enabled = overlap.unwrap_or(settings.overlap_playback);
+                enabled.then(|| settings.max_overlapping_tracks())
+            }),
+        };
+        let limits = settings
+            .as_ref()
+            .map(|settings| QueueLimits {
+                max_length: settings.max_queue_length(),
+                max_duration: settings.max_queue_duration(),
+            })
+            .unwrap_or_default();
+        if limits.max_duration.is_some() && track.duration.is_none() && !track.bypass_limits {
+            track.duration = probe_duration(track.audio_file.clone()).await;
+        }
+
+        let overlap_now = {
+            let mut guilds = self.guilds.lock().unwrap();
+            let guild = guilds.entry(guild_id).or_default();
+
+            let can_overlap =
+                !guild.paused && max_overlapping.is_some_and(|max| guild.active_count() < max);
+            if guild.current_busy() && can_overlap {
+                guild.overlap_starting += 1;
+                true
+            } else if guild.current_busy() {
+                if let Err(err) = guild.check_queue_limits(&track, limits) {
+                    log::info!("Refused queueing track for guild_id: {guild_id} - {err}");
+                    return Err(err);
+                }
+                let position = guild.push(track);
+                drop(guilds);
+                log::info!("Queued track for guild_id: {guild_id}, position: {position}");
+                self.send_queue_updated(guild_id);
+                return Ok(EnqueueOutcome::Queued { position });
+            } else {
+                guild.starting = true;
+                false
+            }
+        };
+
+        if overlap_now {
+            return self.start_overlapping(guild_id, track).await;
+        }
+
+        match self.start_track(guild_id, track, false, false).await {
+            Ok(_) => {
+                self.guilds
+                    .lock()
+                    .unwrap()
+                    .entry(guild_id)
+                    .or_default()
+                    .starting = false;
+                self.update_now_playing(guild_id);
+                Ok(EnqueueOutcome::Started)
+            }
+            Err(err) => {
+                // tracks may have queued up behind the failed one
+                self.play_next(guild_id).await;
+                Err(err)
+            }
+        }
+    }
+
+    async fn start_overlapping(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        track: QueuedTrack,
+    ) -> Result<EnqueueOutcome, AudioError> {
+        let result = self.start_track(guild_id, track, true, false).await;
+
+        let idle = {
+            let mut guilds = self.guilds.lock().unwrap();
+            let guild = guilds.entry(guild_id).or_default();
+            guild.overlap_starting -= 1;
+            guild.is_idle()
+        };
+        if idle {
+            self.schedule_idle_leave(guild_id);
+        }
+
+        result.map(|_| EnqueueOutcome::Overlapping)
+    }
+
+    /// Tracks started across all guilds since bot start
+    pub fn tracks_started(&self) -> u64 {
+        self.guilds
+            .lock()
+            .unwrap()
+            .values()
+            .map(|guild| guild.started_count)
+            .sum()
+    }
+
+    /// Guilds a track is playing in
+    pub fn playing_guilds(&self) -> usize {
+        self.guilds
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|guild| !guild.is_idle())
+            .count()
+    }
+
+    /// Voice channel the bot is in, in guild
+    pub async fn voice_channel(&self, guild_id: GuildId) -> Option<ChannelId> {
+        self.voice.current_channel(guild_id).await
+    }
+
+    /// Name of currently playing track
+    #[allow(unused)]
+    pub fn current_track_name(&self, guild_id: GuildId) -> Option<String> {
+        self.guilds
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .and_then(|guild| guild.current.as_ref())
+            .map(|playing| playing.track.name.clone())
+    }
+
+    /// Current track, time into it, and number of tracks waiting behind it
+    pub async fn now_playing(&self, guild_id: GuildId) -> Option<NowPlaying> {
+        let (name, requested_by, handle, paused, overlapping, queued) = {
+            let guilds = self.guilds.lock().unwrap();
+            let guild = guilds.get(&guild_id)?;
+            let playing = guild
+                .current
+                .as_ref()
+                .or_else(|| guild.overlapping.first())?;
+            (
+                playing.track.name.clone(),
+                playing.track.requested_by,
+                Arc::clone(&playing.handle),
+                guild.paused,
+                guild.overlapping.len(),
+                guild.queue.len(),
+            )
+        };
+
+        let position = handle
+            .position()
+            .await
+            .log_err_msg("Failed getting track position")
+            .unwrap_or_default();
+
+        Some(NowPlaying {
+            name,
+            requested_by,
+            paused,
+            position,
+            overlapping,
+            queued,
+        })
+    }
+
+    /// Names of queued tracks, in play order
+    pub fn queued_track_names(&self, guild_id: GuildId) -> Vec<String> {
+        self.guilds
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .map(|guild| guild.queue.iter().map(|track| track.name.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// Move queued track from one based position to another. Returns name of moved track
+    pub fn move_queued(
+        &self,
+        guild_id: GuildId,
+        from: usize,
+        to: usize,
+    ) -> Result<String, AudioError> {
+        let name = {
+            let mut guilds = self.guilds.lock().unwrap();
+            let guild = guilds.entry(guild_id).or_default();
+            guild.move_queued(from, to)?.name.clone()
+        };
+
+        log::info!("Moved queued track `{name}` from {from} to {to}, guild_id: {guild_id}");
+        self.send_queue_updated(guild_id);
+        Ok(name)
+    }
+
+    /// Drop track at one based queue position. Returns name of removed track
+    pub fn remove_queued(&self, guild_id: GuildId, position: usize) -> Result<String, AudioError> {
+        let track = {
+            let mut guilds = self.guilds.lock().unwrap();
+            let guild = guilds.entry(guild_id).or_default();
+            let index = guild.queue_index(position)?;
+            guild.queue.remove(index).expect("queue index checked")
+        };
+
+        log::info!(
+            "Removed queued track `{}`, guild_id: {guild_id}",
+            track.name
+        );
+        self.update_status(&track, format!("Removed `{}` from queue", track.name));
+        self.send_queue_updated(guild_id);
+        Ok(track.name)
+    }
+
+    /// Jump to position in current track. Returns name of current track
+    pub async fn seek(
+        &self,
+        guild_id: GuildId,
+        position: std::time::Duration,
+    ) -> Result<String, AudioError> {
+        let (name, handle) = self
+            .guilds
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .and_then(|guild| guild.current.as_ref())
+            .map(|playing| (playing.track.name.clone(), Arc::clone(&playing.handle)))
+            .ok_or(AudioError::NothingPlaying)?;
+
+        log::info!("Seeking track `{name}` to {position:?}, guild_id: {guild_id}");
+        handle
+            .seek(position)
+            .await
+            .map_err(|err| AudioError::SeekFailed {
+                reason: err.to_string(),
+            })
+            .log_err()?;
+
+        Ok(name)
+    }
+
+    /// Pause playing tracks. Queue holds until playback is resumed. Returns name of current track
+    pub fn pause(self: &Arc<Self>, guild_id: GuildId) -> Result<String, AudioError> {
+        self.set_paused(guild_id, true)
+    }
+
+    /// Resume paused tracks. Returns name of current track
+    pub fn resume(self: &Arc<Self>, guild_id: GuildId) -> Result<String, AudioError> {
+        self.set_paused(guild_id, false)
+    }
+
+    fn set_paused(self: &Arc<Self>, guild_id: GuildId, paused: bool) -> Result<String, AudioError> {
+        let mut guilds = self.guilds.lock().unwrap();
+        let guild = guilds
+            .get_mut(&guild_id)
+            .ok_or(AudioError::NothingPlaying)?;
+
+        let mut playing = guild.current.iter().chain(&guild.overlapping).peekable();
+        let name = playing
+            .peek()
+            .map(|playing| playing.track.name.clone())
+            .ok_or(AudioError::NothingPlaying)?;
+
+        for playing in playing {
+            let result = match paused {
+                true => playing.handle.pause(),
+                false => playing.handle.resume(),
+            };
+            result.log_err_msg("Failed pausing/resuming track").ok();
+        }
+
+        log::info!("Playback paused: {paused}, guild_id: {guild_id}");
+        guild.paused = paused;
+        guild.speech_paused = false;
+        drop(guilds);
+
+        self.update_now_playing(guild_id);
+        Ok(name)
+    }
+
+    /// Members started talking in guild's voice channel. Pauses playback when ambience is playing
+    pub fn speech_started(self: &Arc<Self>, guild_id: GuildId) {
+        self.set_layers_speech_paused(guild_id, true);
+
+        let ambience_playing = self
+            .guilds
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .is_some_and(|guild| {
+                !guild.paused
+                    && guild
+                        .current
+                        .as_ref()
+                        .is_some_and(|playing| playing.track.ambience)
+            });
+        if !ambience_playing || self.set_paused(guild_id, true).is_err() {
+            return;
+        }
+
+        if let Some(guild) = self.guilds.lock().unwrap().get_mut(&guild_id) {
+            guild.speech_paused = true;
+        }
+    }
+
+    /// Members stopped talking in guild's voice channel. Resumes playback paused by speech, but not
+    /// playback a member paused
+    pub fn speech_ended(self: &Arc<Self>, guild_id: GuildId) {
+        self.set_layers_speech_paused(guild_id, false);
+
+        let speech_paused = self
+            .guilds
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .is_some_and(|guild| guild.speech_paused);
+        if speech_paused {
+            self.set_paused(guild_id, false).ok();
+        }
+    }
+
+    /// Pause or resume guild's ambience layers for members talking
+    fn set_layers_speech_paused(&self, guild_id: GuildId, paused: bool) {
+        let mut guilds = self.guilds.lock().unwrap();
+        let Some(guild) = guilds.get_mut(&guild_id) else {
+            return;
+        };
+        if guild.layers.is_empty() || guild.layers_speech_paused == paused {
+            return;
+        }
+
+        for layer in &guild.layers {
+            let result = match paused {
+                true => layer.playing.handle.pause(),
+                false => layer.playing.handle.resume(),
+            };
+            result
+                .log_err_msg("Failed pausing/resuming ambience layer")
+                .ok();
+        }
+        guild.layers_speech_paused = paused;
+    }
+
+    /// Loop track as a named ambience layer, alongside the queue & other layers, at the layer's
+    /// own volume. Replaces the track of a layer that's already playing
+    pub async fn start_layer(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        layer: String,
+        track: QueuedTrack,
+        volume: f32,
+    ) -> Result<(), AudioError> {
+        let layers_full = self
+            .guilds
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .is_some_and(|guild| guild.layers_full(&layer));
+        if layers_full {
+            return Err(AudioError::TooManyLayers {
+                max: MAX_AMBIENCE_LAYERS,
+            });
+        }
+
+        let handle = self
+            .voice
+            .play(
+                guild_id,
+                track.channel_id,
+                &track.audio_file,
+                track.requested_by,
+                self.track_end_callback(guild_id),
+            )
+            .await
+            .log_err_msg(format!("Failed playing ambience layer {layer}"))?;
+        handle
+            .enable_loop()
+            .log_err_msg("Failed looping ambience layer")
+            .ok();
+
+        let layer = AmbienceLayer {
+            name: layer,
+            volume,
+            playing: PlayingTrack { track, handle },
+        };
+        let voice_channel_id = self.voice.current_channel(guild_id).await;
+        self.apply_volume(
+            guild_id,
+            voice_channel_id,
+            &layer.scaled_track(),
+            layer.playing.handle.as_ref(),
+        );
+
+        let replaced = {
+            let mut guilds = self.guilds.lock().unwrap();
+            let guild = guilds.entry(guild_id).or_default();
+            // another layer may have started while this one did
+            if guild.layers_full(&layer.name) {
+                drop(guilds);
+                layer.playing.handle.stop().log_err().ok();
+                return Err(AudioError::TooManyLayers {
+                    max: MAX_AMBIENCE_LAYERS,
+                });
+            }
+
+            let replaced = guild
+                .layers
+                .iter()
+                .position(|other| other.name == layer.name)
+                .map(|index| guild.layers.remove(index));
+            log::info!(
+                "Looping `{}` as ambience layer `{}`, guild_id: {guild_id}",
+                layer.playing.track.name,
+                layer.name
+            );
+            guild.layers.push(layer);
+            replaced
+        };
+
+        if let Some(replaced) = replaced {
+            replaced.playing.handle.stop().log_err().ok();
+        }
+        Ok(())
+    }
+
+    /// Change volume of guild's ambience layer, where 1.0 is the guild's volume
+    pub async fn set_layer_volume(
+        &self,
+        guild_id: GuildId,
+        layer: &str,
+        volume: f32,
+    ) -> Result<(), AudioError> {
+        let voice_channel_id = self.voice.current_channel(guild_id).await;
+
+        let mut guilds = self.guilds.lock().unwrap();
+        let layer = guilds
+            .get_mut(&guild_id)
+            .and_then(|guild| guild.layers.iter_mut().find(|other| other.name == layer))
+            .ok_or_else(|| AudioError::LayerNotFound {
+                layer: layer.to_string(),
+            })?;
+
+        layer.volume = volume;
+        self.apply_volume(
+            guild_id,
+            voice_channel_id,
+            &layer.scaled_track(),
+            layer.playing.handle.as_ref(),
+        );
+        Ok(())
+    }
+
+    /// Stop guild's ambience layers named `layer`, or all of them when None. Returns names of
+    /// stopped layers
+    pub fn stop_layers(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        layer: Option<&str>,
+    ) -> Result<Vec<String>, AudioError> {
+        let (stopped, idle) = {
+            let mut guilds = self.guilds.lock().unwrap();
+            let guild = guilds.entry(guild_id).or_default();
+            let (stopped, kept) = std::mem::take(&mut guild.layers)
+                .into_iter()
+                .partition::<Vec<_>, _>(|other| layer.is_none_or(|layer| other.name == layer));
+            guild.layers = kept;
+            if guild.layers.is_empty() {
+                guild.layers_speech_paused = false;
+            }
+            (stopped, guild.is_idle())
+        };
+
+        if let (Some(layer), true) = (layer, stopped.is_empty()) {
+            return Err(AudioError::LayerNotFound {
+                layer: layer.to_string(),
+            });
+        }
+
+        for layer in &stopped {
+            log::info!(
+                "Stopping ambience layer `{}`, guild_id: {guild_id}",
+                layer.name
+            );
+            layer
+                .playing
+                .handle
+                .stop()
+                .log_err_msg("Failed stopping ambience layer")
+                .ok();
+        }
+        if idle && !stopped.is_empty() {
+            self.schedule_idle_leave(guild_id);
+        }
+
+        Ok(stopped.into_iter().map(|layer| layer.name).collect())
+    }
+
+    /// Ambience layers playing in guild, in the order they were started
+    pub fn layers(&self, guild_id: GuildId) -> Vec<LayerInfo> {
+        self.guilds
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .map(|guild| {
+                guild
+                    .layers
+                    .iter()
+                    .map(|layer| LayerInfo {
+                        name: layer.name.clone(),
+                        sound: layer.playing.track.name.clone(),
+                        volume: layer.volume,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Stop current track & ambience layers, and drop queued tracks. Used when bot leaves voice
+    /// channel
+    pub fn stop(self: &Arc<Self>, guild_id: GuildId) {
+        let (current, overlapping, queue, layers) =
+            match self.guilds.lock().unwrap().get_mut(&guild_id) {
+                Some(guild) => {
+                    guild.paused = false;
+                    guild.speech_paused = false;
+                    guild.layers_speech_paused = false;
+                    (
+                        guild.current.take(),
+                        std::mem::take(&mut guild.overlapping),
+                        std::mem::take(&mut guild.queue),
+                        std::mem::take(&mut guild.layers),
+                    )
+                }
+                None => return,
+            };
+
+        log::info!(
+            "Stopping playback for guild_id: {guild_id}, dropping {} queued tracks",
+            queue.len()
+        );
+
+        for playing in current.into_iter().chain(overlapping) {
+            playing
+                .handle
+                .stop()
+                .log_err_msg("Failed stopping track")
+                .ok();
+            self.update_status(&playing.track, format!("Stopped `{}`", playing.track.name));
+            self.send_play_end(guild_id, &playing.track, false);
+        }
+
+        let queued = !queue.is_empty();
+        for track in queue {
+            self.update_status(&track, format!("Skipped `{}`", track.name));
+        }
+        if queued {
+            self.send_queue_updated(guild_id);
+        }
+
+        for layer in layers {
+            layer
+                .playing
+                .handle
+                .stop()
+                .log_err_msg("Failed stopping ambience layer")
+                .ok();
+        }
+
+        self.update_now_playing(guild_id);
+        self.update_activity();
+    }
+
+    /// Start playing track. Tracks fading in start muted. Returns handle of the track
+    async fn start_track(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        track: QueuedTrack,
+        overlapping: bool,
+        fade_in: bool,
+    ) -> Result<Arc<dyn TrackControl>, AudioError> {
+        let handle = self
+            .voice
+            .play(
+                guild_id,
+                track.channel_id,
+                &track.audio_file,
+                track.requested_by,
+                self.track_end_callback(guild_id),
+            )
+            .await
+            .log_err_msg(format!("Failed playing track {}", track.name))?;
+
+        let voice_channel_id = self.voice.current_channel(guild_id).await;
+        match fade_in {
+            true => handle
+                .set_volume(0.0)
+                .log_err_msg("Failed muting track")
+                .ok(),
+            false => {
+                self.apply_volume(guild_id, voice_channel_id, &track, handle.as_ref());
+                None
+            }
+        };
+
+        if let Some(voice_channel_id) = voice_channel_id {
+            self.send_captions(guild_id, voice_channel_id, &track);
+        }
+
+        if !overlapping {
+            self.schedule_crossfade(guild_id, Arc::clone(&handle), track.audio_file.clone());
+        }
+
+        self.send_event(PlaybackEvent::PlayStart {
+            guild_id,
+            name: track.name.clone(),
+            requested_by: track.requested_by,
+            overlapping,
+        });
+
+        {
+            let mut guilds = self.guilds.lock().unwrap();
+            let guild = guilds.entry(guild_id).or_default();
+            guild.started_count += 1;
+            guild.started_at = Some(std::time::Instant::now());
+            let playing = PlayingTrack {
+                track,
+                handle: Arc::clone(&handle),
+            };
+            match overlapping {
+                true => guild.overlapping.push(playing),
+                false => guild.current = Some(playing),
+            }
+        }
+
+        self.update_activity();
+        Ok(handle)
+    }
+
+    /// Crossfade into the next queued track once current track gets within the guild's crossfade
+    /// of its end. Does nothing without a crossfade, or for tracks too short to fade
+    fn schedule_crossfade(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        handle: Arc<dyn TrackControl>,
+        audio_file: AudioFile,
+    ) {
+        let Some(fade) = self
+            .settings_table()
+            .and_then(|table| settings::crossfade(&table, guild_id))
+        else {
+            return;
+        };
+
+        let playback = Arc::clone(self);
+        tokio::spawn(async move {
+            let Some(duration) = probe_duration(audio_file).await else {
+                return;
+            };
+            // short sounds would be more fade than sound
+            if duration < fade * 2 {
+                return;
+            }
+
+            // pausing holds the position, so wait again until the fade is due
+            loop {
+                if !playback.is_current(guild_id, handle.uuid()) {
+                    return;
+                }
+                let Ok(position) = handle.position().await else {
+                    return;
+                };
+                match crossfade_wait(duration, position, fade) {
+                    Some(wait) => tokio::time::sleep(wait).await,
+                    None => break,
+                }
+            }
+
+            playback.crossfade_next(guild_id, handle.uuid(), fade).await;
+        });
+    }
+
+    fn is_current(&self, guild_id: GuildId, track_uuid: uuid::Uuid) -> bool {
+        self.guilds
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .and_then(|guild| guild.current.as_ref())
+            .is_some_and(|playing| playing.handle.uuid() == track_uuid)
+    }
+
+    /// Start the next queued track over the current one, fading it in as the current one fades
+    /// out. The faded out track is stopped. Does nothing once the track isn't current, playback is
+    /// paused, or nothing is queued
+    async fn crossfade_next(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        track_uuid: uuid::Uuid,
+        fade: std::time::Duration,
+    ) {
+        let (outgoing, next) = {
+            let mut guilds = self.guilds.lock().unwrap();
+            let Some(guild) = guilds.get_mut(&guild_id) else {
+                return;
+            };
+            let is_current = guild
+                .current
+                .as_ref()
+                .is_some_and(|playing| playing.handle.uuid() == track_uuid);
+            if !is_current || guild.paused || guild.starting {
+                return;
+            }
+            let Some(next) = guild.queue.pop_front() else {
+                return;
+            };
+
+            // fades out over the next track, then ends like any overlapping track
+            let outgoing = guild.current.take().expect("current track checked");
+            let handles = (outgoing.track.clone(), Arc::clone(&outgoing.handle));
+            guild.overlapping.push(outgoing);
+            guild.starting = true;
+            (handles, next)
+        };
+        let (outgoing_track, outgoing) = outgoing;
+
+        log::info!(
+            "Crossfading `{}` into `{}` over {fade:?}, guild_id: {guild_id}",
+            outgoing_track.name,
+            next.name
+        );
+        self.send_queue_updated(guild_id);
+        let name = next.name.clone();
+        let incoming_track = next.clone();
+        let incoming = match self.start_track(guild_id, next, false, true).await {
+            Ok(handle) => handle,
+            Err(_) => {
+                self.update_status(&incoming_track, format!("Failed playing `{name}`"));
+                self.play_next(guild_id).await;
+                return;
+            }
+        };
+
+        if let Some(guild) = self.guilds.lock().unwrap().get_mut(&guild_id) {
+            guild.starting = false;
+        }
+        self.update_status(&incoming_track, format!("Playing `{name}`"));
+        self.update_now_playing(guild_id);
+
+        let voice_channel_id = self.voice.current_channel(guild_id).await;
+        let incoming_volume = self
+            .track_volume(guild_id, voice_channel_id, &incoming_track)
+            .unwrap_or(1.0);
+        let outgoing_volume = self
+            .track_volume(guild_id, voice_channel_id, &outgoing_track)
+            .unwrap_or(1.0);
+        for step in 1..=CROSSFADE_STEPS {
+            tokio::time::sleep(fade / CROSSFADE_STEPS).await;
+            let progress = step as f32 / CROSSFADE_STEPS as f32;
+            incoming.set_volume(incoming_volume * progress).ok();
+            outgoing.set_volume(outgoing_volume * (1.0 - progress)).ok();
+        }
+
+        outgoing
+            .stop()
+            .log_err_msg("Failed stopping faded out track")
+            .ok();
+    }
+
+    /// Set volume of playing track
+    fn apply_volume(
+        &self,
+        guild_id: GuildId,
+        voice_channel_id: Option<ChannelId>,
+        track: &QueuedTrack,
+        handle: &dyn TrackControl,
+    ) {
+        if let Some(volume) = self.track_volume(guild_id, voice_channel_id, track) {
+            handle
+                .set_volume(volume)
+                .log_err_msg("Failed setting track volume")
+                .ok();
+        }
+    }
+
+    /// Volume track plays at. Track's own volume, then voice channel's volume, then guild's
+    /// volume, scaled by the sound's volume. None when it's left unchanged
+    fn track_volume(
+        &self,
+        guild_id: GuildId,
+        voice_channel_id: Option<ChannelId>,
+        track: &QueuedTrack,
+    ) -> Option<f32> {
+        let volume = self.settings_table().map_or(track.volume, |table| {
+            let settings = GuildSettings::load(&table, guild_id);
+            let volume = track
+                .volume
+                .or_else(|| {
+                    voice_channel_id.and_then(|channel_id| {
+                        settings::channel_volume(&table, guild_id, channel_id)
+                    })
+                })
+                .unwrap_or_else(|| settings.volume_scale());
+            Some(quiet_hours::cap_volume(&settings, volume))
+        });
+        volume.map(|volume| volume * track.sound_volume)
+    }
+
+    fn track_end_callback(self: &Arc<Self>, guild_id: GuildId) -> TrackEndCallback {
+        let playback = Arc::clone(self);
+        Arc::new(move |track_uuid, failed| {
+            let playback = Arc::clone(&playback);
+            Box::pin(async move { playback.track_ended(guild_id, track_uuid, failed).await })
+        })
+    }
+
+    /// Clean up ended track, then start next queued track
+    async fn track_ended(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        track_uuid: uuid::Uuid,
+        failed: bool,
+    ) {
+        let finished = {
+            let mut guilds = self.guilds.lock().unwrap();
+            let Some(guild) = guilds.get_mut(&guild_id) else {
+                return;
+            };
+
+            // ambience layers loop, so they only end when they fail
+            let layer = guild
+                .layers
+                .iter()
+                .position(|layer| layer.playing.handle.uuid() == track_uuid);
+            if let Some(index) = layer {
+                let layer = guild.layers.remove(index);
+                let idle = guild.is_idle();
+                drop(guilds);
+
+                log::warn!(
+                    "Ambience layer `{}` ended, failed: {failed}, guild_id: {guild_id}",
+                    layer.name
+                );
+                if idle {
+                    self.schedule_idle_leave(guild_id);
+                }
+                return;
+            }
+
+            // overlapping tracks end on their own, the queue only advances with the current track
+            let overlapping = guild
+                .overlapping
+                .iter()
+                .position(|playing| playing.handle.uuid() == track_uuid);
+            if let Some(index) = overlapping {
+                let playing = guild.overlapping.remove(index);
+                let idle = guild.is_idle();
+                drop(guilds);
+
+                self.update_status(&playing.track, format!("Finished `{}`", playing.track.name));
+                self.send_play_end(guild_id, &playing.track, failed);
+                self.update_activity();
+                if idle {
+                    self.schedule_idle_leave(guild_id);
+                }
+                return;
+            }
+
+            // end & error events can both fire for a track, only handle the first
+            let is_current = guild
+                .current
+                .as_ref()
+                .is_some_and(|playing| playing.handle.uuid() == track_uuid);
+            if !is_current {
+                return;
+            }
+
+            guild.starting = true;
+            guild.current.take()
+        };
+
+        if let Some(playing) = finished {
+            let status = match failed {
+                true => "Failed playing",
+                false => "Finished",
+            };
+            self.update_status(&playing.track, format!("{status} `{}`", playing.track.name));
+            self.send_play_end(guild_id, &playing.track, failed);
+        }
+
+        self.play_next(guild_id).await;
+    }
+
+    /// Start queued tracks until one plays, or the queue is empty
+    async fn play_next(self: &Arc<Self>, guild_id: GuildId) {
+        let mut popped = false;
+        loop {
+            let next = self
+                .guilds
+                .lock()
+                .unwrap()
+                .get_mut(&guild_id)
+                .and_then(|guild| guild.queue.pop_front());
+
+            let Some(track) = next else {
+                break;
+            };
+            popped = true;
+
+            let name = track.name.clone();
+            let status_track = track.clone();
+            match self.start_track(guild_id, track, false, false).await {
+                Ok(_) => {
+                    self.update_status(&status_track, format!("Playing `{name}`"));
+                    break;
+                }
+                Err(_) => self.update_status(&status_track, format!("Failed playing `{name}`")),
+            }
+        }
+
+        let idle = match self.guilds.lock().unwrap().get_mut(&guild_id) {
+            Some(guild) => {
+                guild.starting = false;
+                guild.is_idle()
+            }
+            None => false,
+        };
+
+        if popped {
+            self.send_queue_updated(guild_id);
+        }
+        self.update_now_playing(guild_id);
+        self.update_activity();
+        if idle {
+            self.schedule_idle_leave(guild_id);
+        }
+    }
+
+    /// Leave voice channel once guild's idle timeout passes without anything being played
+    pub fn schedule_idle_leave(self: &Arc<Self>, guild_id: GuildId) {
+        let Some(timeout) = self
+            .settings_table()
+            .and_then(|table| settings::idle_timeout(&table, guild_id))
+        else {
+            return;
+        };
+
+        let started_count = self.started_count(guild_id);
+        let playback = Arc::clone(self);
+
+        log::debug!(
+            "Leaving voice in {}s if idle. guild_id: {guild_id}",
+            timeout.as_secs()
+        );
+        tokio::spawn(async move {
+            tokio::time::sleep(timeout).await;
+
+            let still_idle =
+                playback.started_count(guild_id) == started_count && playback.is_idle(guild_id);
+
+            if still_idle && playback.voice.in_call(guild_id) {
+                log::info!("Idle timeout reached. Bot is leaving. guild_id: {guild_id}");
+                playback.leave(guild_id).await.log_err().ok();
+            }
+        });
+    }
+
+    /// Move bot to another voice channel of guild, keeping the queue & what's playing. Playing
+    /// tracks take on the new channel's volume
+    pub async fn move_to(
+        self: &Arc<Self>,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+    ) -> Result<(), PoiseError> {
+        if !self.voice.in_call(guild_id) {
+            return Err(AudioError::NotInVoiceChannel.into());
+        }
+
+        log::info!("Moving to voice channel {channel_id}, guild_id: {guild_id}");
+        self.voice.move_to(guild_id, channel_id).await?;
+
+        let playing: Vec<_> = match self.guilds.lock().unwrap().get(&guild_id) {
+            Some(guild) => guild
+                .current
+                .iter()
+                .chain(&guild.overlapping)
+                .map(|playing| (playing.track.clone(), Arc::clone(&playing.handle)))
+                .chain(
+                    guild
+                        .layers
+                        .iter()
+                        .map(|layer| (layer.scaled_track(), Arc::clone(&layer.playing.handle))),
+                )
+                .collect(),
+            None => vec![],
+        };
+        for (track, handle) in playing {
+            self.apply_volume(guild_id, Some(channel_id), &track, handle.as_ref());
+        }
+
+        Ok(())
+    }
+
+    /// Stop playback, then leave guild's voice channel
+    pub async fn leave(self: &Arc<Self>, guild_id: GuildId) -> Result<(), PoiseError> {
+        self.stop(guild_id);
+        self.voice.leave(guild_id).await
+    }
+
+    fn is_idle(&self, guild_id: GuildId) -> bool {
+        match self.guilds.lock().unwrap().get(&guild_id) {
+            Some(guild) => guild.is_idle(),
+            None => true,
+        }
+    }
+
+    fn started_count(&self, guild_id: GuildId) -> u64 {
+        self.guilds
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .map_or(0, |guild| guild.started_count)
+    }
+
+    /// Show guild's current track in its now playing channel, when one is set. The message is
+    /// sent once, then edited. A new one is sent if it was deleted, or the channel changed
+    fn update_now_playing(self: &Arc<Self>, guild_id: GuildId) {
+        let Some(channel_id) = self
+            .settings_table()
+            .and_then(|table| settings::now_playing_channel(&table, guild_id))
+        else {
+            return;
+        };
+
+        let (version, content) = {
+            let mut guilds = self.guilds.lock().unwrap();
+            let guild = guilds.entry(guild_id).or_default();
+            guild.now_playing_version += 1;
+            (guild.now_playing_version, guild.now_playing_text())
+        };
+
+        let playback = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut messages = playback.now_playing_messages.lock().await;
+            if playback.now_playing_version(guild_id) != version {
+                return;
+            }
+
+            let message = messages
+                .get(&guild_id)
+                .filter(|message| message.channel_id == channel_id);
+            if let Some(message) = message {
+                let edited = playback
+                    .chat
+                    .edit_message(channel_id, message.message_id, content.clone())
+                    .await
+                    .log_err_msg("Failed editing now playing message");
+                if edited.is_ok() {
+                    return;
+                }
+            }
+
+            let sent = playback
+                .chat
+                .send_message(channel_id, content)
+                .await
+                .log_err_msg("Failed sending now playing message");
+            if let Ok(message_id) = sent {
+                messages.insert(
+                    guild_id,
+                    StatusMessage {
+                        channel_id,
+                        message_id,
+                    },
+                );
+            }
+        });
+    }
+
+    /// Show latest track playing in any guild as the bot's activity, ie: `Playing airhorn in
+    /// 3 servers`. Cleared once nothing is playing
+    fn update_activity(&self) {
+        let activity = {
+            let guilds = self.guilds.lock().unwrap();
+            let playing: Vec<_> = guilds
+                .values()
+                .filter_map(|guild| Some((guild.started_at, guild.latest_track_name()?)))
+                .collect();
+
+            playing
+                .iter()
+                .max_by_key(|(started_at, _)| *started_at)
+                .map(|(_, name)| match playing.len() {
+                    1 => name.to_string(),
+                    servers => format!("{name} in {servers} servers"),
+                })
+        };
+
+        let mut shown = self.activity.lock().unwrap();
+        if *shown != activity {
+            self.chat.set_activity(activity.clone());
+            *shown = activity;
+        }
+    }
+
+    /// Direct message track's name to members of the voice channel with captions on, so members
+    /// who can't hear it can follow along. Ephemeral messages only reach whoever ran a command,
+    /// so captions go out as direct messages
+    fn send_captions(&self, guild_id: GuildId, channel_id: ChannelId, track: &QueuedTrack) {
+        let members: Vec<u64> = self
+            .chat
+            .voice_channel_members(guild_id, channel_id)
+            .iter()
+            .map(|user_id| user_id.get())
+            .collect();
+        if members.is_empty() {
+            return;
+        }
+
+        let captioned = self
+            .db_pool
+            .get()
+            .map_err(PoiseError::from)
+            .and_then(|conn| UserPrefsTable::new(conn).captioned_users(&members))
+            .log_err_msg("Failed getting users with captions on")
+            .unwrap_or_default();
+        if captioned.is_empty() {
+            return;
+        }
+
+        let requested_by = track
+            .requested_by
+            .map(|user_id| format!(" (requested by <@{user_id}>)"))
+            .unwrap_or_default();
+        let content = format!("🔊 `{}` in <#{channel_id}>{requested_by}", track.name);
+
+        let chat = Arc::clone(&self.chat);
+        tokio::spawn(async move {
+            for user_id in captioned {
+                chat.send_direct_message(UserId::new(user_id), content.clone())
+                    .await
+                    .log_err_msg(format!("Failed sending caption to user {user_id}"))
+                    .ok();
+            }
+        });
+    }
+
+    fn now_playing_version(&self, guild_id: GuildId) -> u64 {
+        self.guilds
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .map_or(0, |guild| guild.now_playing_version)
+    }
+
+    fn update_status(&self, track: &QueuedTrack, content: String) {
+        let Some(status) = track.status_message else {
+            return;
+        };
+
+        let chat = Arc::clone(&self.chat);
+        tokio::spawn(async move {
+            chat.edit_message(status.channel_id, status.message_id, content)
+                .await
+                .log_err_msg("Failed editing track status message")
+                .ok();
+        });
+    }
+}
+
+/// Play time of audio file, None if it couldn't be read
+async fn probe_duration(audio_file: AudioFile) -> Option<std::time::Duration> {
+    tokio::task::spawn_blocking(move || crate::audio::probe_audio_track(audio_file.as_path()))
+        .await
+        .ok()?
+        .ok()
+        .map(|info| info.duration)
+}
+
+/// How long until a track `position` into its `duration` should start fading out. None once it
+/// should
+fn crossfade_wait(
+    duration: std::time::Duration,
+    position: std::time::Duration,
+    fade: std::time::Duration,
+) -> Option<std::time::Duration> {
+    let fade_at = duration.saturating_sub(fade);
+    (position < fade_at).then(|| fade_at - position)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path;
+    use std::time::Duration;
+
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::db::Table;
+    use crate::simulate::{SimulatedChat, SimulatedVoice};
+
+    const GUILD_ID: GuildId = GuildId::new(1);
+
+    fn simulated_playback() -> (Arc<Playback>, Arc<SimulatedVoice>, Arc<SimulatedChat>) {
+        let voice = Arc::new(SimulatedVoice::new(None));
+        let chat = Arc::new(SimulatedChat::default());
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let playback = Playback::new(voice.clone(), chat.clone(), db_pool);
+        (playback, voice, chat)
+    }
+
+    fn make_track(name: &str) -> QueuedTrack {
+        QueuedTrack::new(
+            name.into(),
+            AudioFile::new(path::PathBuf::from(format!("/tmp/{name}.mp3"))),
+            ChannelId::new(1),
+        )
+    }
+
+    #[test]
+    fn guild_playback_push_test() {
+        let mut guild = GuildPlayback::default();
+        assert!(guild.is_idle());
+
+        assert_eq!(guild.push(make_track("first")), 1);
+        assert_eq!(guild.push(make_track("second")), 2);
+        assert_eq!(guild.queue.pop_front().unwrap().name, "first");
+
+        guild.starting = true;
+        assert!(!guild.is_idle());
+    }
+
+    #[test]
+    fn guild_playback_queue_limits_test() {
+        let mut guild = GuildPlayback::default();
+        let minute = |name: &str| {
+            let mut track = make_track(name);
+            track.duration = Some(Duration::from_secs(60));
+            track
+        };
+        assert!(guild
+            .check_queue_limits(&minute("a"), QueueLimits::default())
+            .is_ok());
+
+        guild.push(minute("a"));
+        guild.push(minute("b"));
+        let limits = QueueLimits {
+            max_length: Some(2),
+            max_duration: None,
+        };
+        assert!(matches!(
+            guild.check_queue_limits(&minute("c"), limits),
+            Err(AudioError::QueueFull { max: 2 })
+        ));
+        let admin_track = minute("c").bypass_limits(true);
+        assert!(guild.check_queue_limits(&admin_track, limits).is_ok());
+
+        let limits = QueueLimits {
+            max_length: None,
+            max_duration: Some(Duration::from_secs(150)),
+        };
+        assert!(matches!(
+            guild.check_queue_limits(&minute("c"), limits),
+            Err(AudioError::QueueTooLong { .. })
+        ));
+        // unknown durations count as no time
+        assert!(guild.check_queue_limits(&make_track("c"), limits).is_ok());
+    }
+
+    #[test]
+    fn guild_playback_push_priority_test() {
+        let mut guild = GuildPlayback::default();
+        guild.push(make_track("a"));
+        guild.push(make_track("b"));
+
+        assert_eq!(guild.push(make_track("mod 1").priority(true)), 1);
+        assert_eq!(guild.push(make_track("mod 2").priority(true)), 2);
+        assert_eq!(guild.push(make_track("c")), 5);
+
+        let names: Vec<_> = guild
+            .queue
+            .iter()
+            .map(|track| track.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["mod 1", "mod 2", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn guild_playback_move_queued_test() {
+        let mut guild = GuildPlayback::default();
+        for name in ["a", "b", "c"] {
+            guild.push(make_track(name));
+        }
+
+        assert_eq!(guild.move_queued(3, 1).unwrap().name, "c");
+        let names: Vec<_> = guild
+            .queue
+            .iter()
+            .map(|track| track.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["c", "a", "b"]);
+
+        assert!(guild.move_queued(0, 1).is_err());
+        assert!(guild.move_queued(1, 4).is_err());
+        assert!(guild.queue_index(3).is_ok());
+    }
+
+    #[test]
+    fn guild_playback_active_count_test() {
+        let mut guild = GuildPlayback::default();
+        assert_eq!(guild.active_count(), 0);
+
+        guild.starting = true;
+        guild.overlap_starting = 2;
+        assert_eq!(guild.active_count(), 3);
+
+        guild.starting = false;
+        assert_eq!(guild.active_count(), 2);
+        assert!(!guild.is_idle());
+    }
+
+    #[tokio::test]
+    async fn playback_queue_advances_test() {
+        let (playback, voice, chat) = simulated_playback();
+        let status = StatusMessage {
+            channel_id: ChannelId::new(1),
+            message_id: MessageId::new(2),
+        };
+
+        let outcome = playback.enqueue(GUILD_ID, make_track("a")).await.unwrap();
+        assert_eq!(outcome, EnqueueOutcome::Started);
+        let outcome = playback
+            .enqueue(GUILD_ID, make_track("b").status_message(status))
+            .await
+            .unwrap();
+        assert_eq!(outcome, EnqueueOutcome::Queued { position: 1 });
+        assert_eq!(playback.queued_track_names(GUILD_ID), vec!["b"]);
+
+        let playing = voice.playing(GUILD_ID);
+        assert_eq!(playing.len(), 1);
+        playing[0].finish(false).await;
+
+        assert_eq!(playback.now_playing(GUILD_ID).await.unwrap().name, "b");
+        assert!(playback.queued_track_names(GUILD_ID).is_empty());
+        assert_eq!(voice.tracks().len(), 2);
+
+        // status messages are edited in the background
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(
+            chat.edits(),
+            vec![(status.channel_id, status.message_id, "Playing `b`".into())]
+        );
+
+        playback.leave(GUILD_ID).await.unwrap();
+        assert!(voice.playing(GUILD_ID).is_empty());
+        assert!(!voice.in_call(GUILD_ID));
+        assert!(playback.now_playing(GUILD_ID).await.is_none());
+
+        // bot's activity follows the current track, and clears once nothing plays
+        assert_eq!(
+            chat.activities(),
+            vec![Some("a".into()), Some("b".into()), None]
+        );
+    }
+
+    #[tokio::test]
+    async fn playback_events_test() {
+        let (playback, voice, _chat) = simulated_playback();
+        let mut events = playback.subscribe();
+
+        let track = make_track("a").requested_by(UserId::new(5));
+        playback.enqueue(GUILD_ID, track).await.unwrap();
+        playback.enqueue(GUILD_ID, make_track("b")).await.unwrap();
+        // requester is handed to the voice backend, and shown as now playing
+        assert_eq!(
+            voice.playing(GUILD_ID)[0].requested_by,
+            Some(UserId::new(5))
+        );
+        let now_playing = playback.now_playing(GUILD_ID).await.unwrap();
+        assert_eq!(now_playing.requested_by, Some(UserId::new(5)));
+        voice.playing(GUILD_ID)[0].finish(false).await;
+        playback.leave(GUILD_ID).await.unwrap();
+
+        let queue_updated = |queue: Vec<&str>| PlaybackEvent::QueueUpdated {
+            guild_id: GUILD_ID,
+            queue: queue.into_iter().map(String::from).collect(),
+        };
+        let play_start = |name: &str, requested_by| PlaybackEvent::PlayStart {
+            guild_id: GUILD_ID,
+            name: name.into(),
+            requested_by,
+            overlapping: false,
+        };
+        let play_end = |name: &str, requested_by| PlaybackEvent::PlayEnd {
+            guild_id: GUILD_ID,
+            name: name.into(),
+            requested_by,
+            failed: false,
+        };
+        let expected = vec![
+            play_start("a", Some(UserId::new(5))),
+            queue_updated(vec!["b"]),
+            play_end("a", Some(UserId::new(5))),
+            play_start("b", None),
+            queue_updated(vec![]),
+            play_end("b", None),
+        ];
+        for event in expected {
+            assert_eq!(events.try_recv().unwrap(), event);
+        }
+        assert!(events.try_recv().is_err());
+
+        let json = serde_json::to_value(play_end("a", Some(UserId::new(5)))).unwrap();
+        assert_eq!(json["type"], "play_end");
+        assert_eq!(json["name"], "a");
+        assert_eq!(json["requested_by"], "5");
+    }
+
+    #[tokio::test]
+    async fn playback_pause_volume_test() {
+        let (playback, voice, _chat) = simulated_playback();
+        assert!(playback.pause(GUILD_ID).is_err());
+
+        let track = make_track("a").volume(0.5).sound_volume(0.5);
+        playback.enqueue(GUILD_ID, track).await.unwrap();
+        let playing = voice.playing(GUILD_ID).remove(0);
+        assert_eq!(playing.volume(), 0.25);
+
+        assert_eq!(playback.pause(GUILD_ID).unwrap(), "a");
+        assert!(playing.is_paused());
+        assert!(playback.now_playing(GUILD_ID).await.unwrap().paused);
+
+        // paused playback queues tracks instead of overlapping them
+        let outcome = playback
+            .enqueue(GUILD_ID, make_track("b").overlap(true))
+            .await
+            .unwrap();
+        assert_eq!(outcome, EnqueueOutcome::Queued { position: 1 });
+
+        playback.resume(GUILD_ID).unwrap();
+        assert!(!playing.is_paused());
+    }
+
+    #[tokio::test]
+    async fn playback_move_test() {
+        let (playback, voice, _chat) = simulated_playback();
+        assert!(playback.move_to(GUILD_ID, ChannelId::new(7)).await.is_err());
+
+        playback.enqueue(GUILD_ID, make_track("a")).await.unwrap();
+        playback.enqueue(GUILD_ID, make_track("b")).await.unwrap();
+        playback.move_to(GUILD_ID, ChannelId::new(7)).await.unwrap();
+
+        // queue & current track are kept
+        assert_eq!(
+            voice.current_channel(GUILD_ID).await,
+            Some(ChannelId::new(7))
+        );
+        assert_eq!(playback.now_playing(GUILD_ID).await.unwrap().name, "a");
+        assert_eq!(playback.queued_track_names(GUILD_ID), vec!["b"]);
+        assert!(!voice.playing(GUILD_ID)[0].has_ended());
+    }
+
+    #[tokio::test]
+    async fn playback_ambience_layers_test() {
+        let (playback, voice, _chat) = simulated_playback();
+
+        playback
+            .start_layer(GUILD_ID, "rain".into(), make_track("rain"), 0.5)
+            .await
+            .unwrap();
+        playback
+            .start_layer(GUILD_ID, "tavern".into(), make_track("tavern"), 1.0)
+            .await
+            .unwrap();
+        let rain = voice.playing(GUILD_ID).remove(0);
+        assert!(rain.is_looping());
+        assert_eq!(rain.volume(), 0.5);
+
+        // layers loop, and play alongside the queue
+        rain.finish(false).await;
+        assert!(!rain.has_ended());
+        playback.enqueue(GUILD_ID, make_track("a")).await.unwrap();
+        assert_eq!(voice.playing(GUILD_ID).len(), 3);
+
+        playback
+            .set_layer_volume(GUILD_ID, "rain", 0.25)
+            .await
+            .unwrap();
+        assert_eq!(rain.volume(), 0.25);
+        assert!(playback
+            .set_layer_volume(GUILD_ID, "fire", 1.0)
+            .await
+            .is_err());
+
+        // a layer's track is replaced by starting it again
+        playback
+            .start_layer(GUILD_ID, "rain".into(), make_track("storm"), 1.0)
+            .await
+            .unwrap();
+        assert!(rain.has_ended());
+        let layers = playback.layers(GUILD_ID);
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[1].sound, "storm");
+
+        for name in ["fire", "wind"] {
+            playback
+                .start_layer(GUILD_ID, name.into(), make_track(name), 1.0)
+                .await
+                .unwrap();
+        }
+        let too_many = playback
+            .start_layer(GUILD_ID, "birds".into(), make_track("birds"), 1.0)
+            .await;
+        assert!(matches!(too_many, Err(AudioError::TooManyLayers { .. })));
+
+        assert_eq!(
+            playback.stop_layers(GUILD_ID, Some("tavern")).unwrap(),
+            vec!["tavern"]
+        );
+        assert!(playback.stop_layers(GUILD_ID, Some("tavern")).is_err());
+        assert_eq!(playback.stop_layers(GUILD_ID, None).unwrap().len(), 3);
+        assert!(playback.layers(GUILD_ID).is_empty());
+        assert_eq!(playback.now_playing(GUILD_ID).await.unwrap().name, "a");
+    }
+
+    #[test]
+    fn crossfade_wait_test() {
+        let fade = Duration::from_secs(2);
+        assert_eq!(
+            crossfade_wait(Duration::from_secs(10), Duration::from_secs(3), fade),
+            Some(Duration::from_secs(5))
+        );
+        assert_eq!(
+            crossfade_wait(Duration::from_secs(10), Duration::from_secs(8), fade),
+            None
+        );
+        assert_eq!(
+            crossfade_wait(Duration::from_secs(1), Duration::ZERO, fade),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn playback_crossfade_test() {
+        let (playback, voice, _chat) = simulated_playback();
+        playback.enqueue(GUILD_ID, make_track("a")).await.unwrap();
+        playback.enqueue(GUILD_ID, make_track("b")).await.unwrap();
+        let a = voice.playing(GUILD_ID).remove(0);
+
+        let fade = Duration::from_millis(40);
+        playback.crossfade_next(GUILD_ID, a.uuid(), fade).await;
+        assert!(a.has_ended());
+        assert_eq!(a.volume(), 0.0);
+        assert_eq!(playback.now_playing(GUILD_ID).await.unwrap().name, "b");
+        let b = voice.playing(GUILD_ID).pop().unwrap();
+        assert_eq!(b.volume(), 1.0);
+
+        // fading needs a current track, and something queued after it
+        playback.crossfade_next(GUILD_ID, a.uuid(), fade).await;
+        playback.crossfade_next(GUILD_ID, b.uuid(), fade).await;
+        assert!(!b.has_ended());
+        assert_eq!(playback.now_playing(GUILD_ID).await.unwrap().name, "b");
+    }
+
+    #[tokio::test]
+    async fn playback_speech_pause_test() {
+        let (playback, voice, _chat) = simulated_playback();
+        playback.enqueue(GUILD_ID, make_track("a")).await.unwrap();
+        let playing = voice.playing(GUILD_ID).remove(0);
+
+        // only ambience pauses for speech
+        playback.speech_started(GUILD_ID);
+        assert!(!playing.is_paused());
+        playing.finish(false).await;
+
+        let mut ambience = make_track("rain");
+        ambience.ambience = true;
+        playback.enqueue(GUILD_ID, ambience).await.unwrap();
+        let playing = voice.playing(GUILD_ID).remove(0);
+
+        playback.speech_started(GUILD_ID);
+        assert!(playing.is_paused());
+        playback.speech_ended(GUILD_ID);
+        assert!(!playing.is_paused());
+
+        // playback paused by a member stays paused once speech ends
+        playback.speech_started(GUILD_ID);
+        playback.pause(GUILD_ID).unwrap();
+        playback.speech_ended(GUILD_ID);
+        assert!(playing.is_paused());
+    }
+
+    #[tokio::test]
+    async fn now_playing_message_test() {
+        let voice = Arc::new(SimulatedVoice::new(None));
+        let chat = Arc::new(SimulatedChat::default());
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let settings = SettingsTable::new(db_pool.get().unwrap());
+        settings.create_table();
+        settings
+            .set_guild_setting(GUILD_ID.get(), "now_playing_channel", "7")
+            .unwrap();
+        let playback = Playback::new(voice.clone(), chat.clone(), db_pool);
+
+        let track = make_track("airhorn").requested_by(UserId::new(3));
+        playback.enqueue(GUILD_ID, track).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(
+            chat.sent(),
+            vec![(
+                ChannelId::new(7),
+                "🎶 Now playing: `airhorn` (requested by <@3>)".into()
+            )]
+        );
+
+        // later updates edit the same message
+        playback.pause(GUILD_ID).unwrap();
+        voice.playing(GUILD_ID)[0].finish(false).await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(chat.sent().len(), 1);
+        let edits: Vec<_> = chat
+            .edits()
+            .into_iter()
+            .map(|(_, message_id, content)| (message_id.get(), content))
+            .collect();
+        assert_eq!(edits.last(), Some(&(1, "⏹️ Nothing playing".to_string())));
+    }
+
+    #[tokio::test]
+    async fn captions_test() {
+        let voice = Arc::new(SimulatedVoice::new(None));
+        let chat = Arc::new(SimulatedChat::default());
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let prefs_table = UserPrefsTable::new(db_pool.get().unwrap());
+        prefs_table.create_table();
+        let mut prefs = prefs_table.get_prefs(3).unwrap();
+        prefs.captions = true;
+        prefs_table.save_prefs(&prefs).unwrap();
+        let playback = Playback::new(voice.clone(), chat.clone(), db_pool);
+
+        // only members of the voice channel with captions on get captions
+        chat.join_voice(ChannelId::new(1), UserId::new(3));
+        chat.join_voice(ChannelId::new(1), UserId::new(4));
+        chat.join_voice(ChannelId::new(2), UserId::new(5));
+
+        let track = make_track("airhorn").requested_by(UserId::new(4));
+        playback.enqueue(GUILD_ID, track).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(
+            chat.direct_messages(),
+            vec![(
+                UserId::new(3),
+                "🔊 `airhorn` in <#1> (requested by <@4>)".into()
+            )]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use serenity::all::{ChannelId, GuildId, Http, MessageId, UserId};
+
+use crate::commands::PoiseError;
+use crate::common::{LogResult, UserData};
+use crate::db::PostedBoardRow;
+use crate::helpers::{self, BoardMessage};
+use crate::{retention, retry};
+
+/// Set by the first warm up. Ready fires again when the gateway reconnects, boards are only
+/// checked once per process
+static WARMED_UP: AtomicBool = AtomicBool::new(false);
+
+/// What's done with a posted board when the bot starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BoardAction {
+    /// Every message is still there, with room for the board as it is now
+    Refresh,
+    /// Messages went missing or the board changed size, leftovers are replaced by a new board
+    Repost,
+    /// Nothing left of the board, or no sounds left to show. Leftovers are deleted
+    Forget,
+}
+
+impl BoardAction {
+    /// Action for board of `posted` messages, with `found` of them still in the channel.
+    /// `rebuilt` is how many messages the board takes now, None when there are no sounds
+    fn plan(posted: usize, found: usize, rebuilt: Option<usize>) -> Self {
+        match rebuilt {
+            _ if found == 0 => Self::Forget,
+            None => Self::Forget,
+            Some(rebuilt) if found == posted && rebuilt == posted => Self::Refresh,
+            Some(_) => Self::Repost,
+        }
+    }
+}
+
+/// Boards checked by a warm up, by what was done with them
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct WarmUp {
+    pub refreshed: usize,
+    pub reposted: usize,
+    pub forgotten: usize,
+    /// Boards that couldn't be checked, ie: missing access to the channel. Tried again next start
+    pub skipped: usize,
+}
+
+/// Remember board posted without a time to live, so it's refreshed when the bot starts
+pub fn record(
+    data: &UserData,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    user_id: UserId,
+    message_ids: &[MessageId],
+    hidden: &HashSet<i64>,
+) {
+    let message_ids: Vec<_> = message_ids.iter().map(|id| id.get()).collect();
+    let mut hidden: Vec<_> = hidden.iter().copied().collect();
+    hidden.sort_unstable();
+
+    data.posted_boards_table()
+        .insert_board(
+            guild_id.get(),
+            channel_id.get(),
+            user_id.get(),
+            &message_ids,
+            &hidden,
+            chrono::Utc::now(),
+        )
+        .log_err_msg("Failed recording posted soundboard")
+        .ok();
+}
+
+/// Check posted boards, the first time the bot is ready. See [`warm_up`]
+pub async fn warm_up_once(http: &Http, data: &UserData) {
+    if WARMED_UP.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    if let Ok(warm_up) = warm_up(http, data)
+        .await
+        .log_err_msg("Failed warming up posted soundboards")
+    {
+        log::info!(
+            "Warmed up posted soundboards. refreshed: {}, reposted: {}, forgotten: {}, skipped: {}",
+            warm_up.refreshed,
+            warm_up.reposted,
+            warm_up.forgotten,
+            warm_up.skipped
+        );
+    }
+}
+
+/// Fetch the messages of every posted board. Boards still whole are edited in place with the
+/// sounds as they are now, boards with messages missing are posted again, and boards without
+/// any messages left are forgotten
+pub async fn warm_up(http: &Http, data: &UserData) -> Result<WarmUp, PoiseError> {
+    let mut warm_up = WarmUp::default();
+
+    for board in data.posted_boards_table().rows()? {
+        let channel_id = ChannelId::new(board.channel_id);
+        let Some(found) = fetch_messages(http, &board).await else {
+            warm_up.skipped += 1;
+            continue;
+        };
+
+        let prefs = data.user_prefs_table().get_prefs(board.user_id)?;
+        let hidden: HashSet<_> = board.hidden_ids.iter().copied().collect();
+        let messages =
+            helpers::soundboard_messages(data, GuildId::new(board.guild_id), &prefs, &hidden)?;
+
+        let action = BoardAction::plan(
+            board.message_ids.len(),
+            found.len(),
+            messages.as_ref().map(|messages| messages.len()),
+        );
+        log::debug!(
+            "Posted soundboard {} has {}/{} messages left - {action:?}",
+            board.id,
+            found.len(),
+            board.message_ids.len()
+        );
+
+        match (action, messages) {
+            (BoardAction::Refresh, Some(messages)) => {
+                match refresh_messages(http, channel_id, &found, &messages).await {
+                    true => warm_up.refreshed += 1,
+                    false => warm_up.skipped += 1,
+                }
+            }
+            (BoardAction::Repost, Some(messages)) => {
+                delete_messages(http, data, channel_id, &found).await;
+                let message_ids = helpers::send_board_messages(http, channel_id, &messages).await;
+                data.track_bot_messages(GuildId::new(board.guild_id), channel_id, &message_ids);
+
+                let message_ids: Vec<_> = message_ids.iter().map(|id| id.get()).collect();
+                data.posted_boards_table().update_message_ids(
+                    board.id,
+                    &message_ids,
+                    chrono::Utc::now(),
+                )?;
+                warm_up.reposted += 1;
+            }
+            _ => {
+                delete_messages(http, data, channel_id, &found).await;
+                data.posted_boards_table().delete_board(board.id)?;
+                warm_up.forgotten += 1;
+            }
+        }
+    }
+
+    Ok(warm_up)
+}
+
+/// Messages of board still in its channel, in order. None if they couldn't be fetched
+async fn fetch_messages(http: &Http, board: &PostedBoardRow) -> Option<Vec<MessageId>> {
+    let channel_id = ChannelId::new(board.channel_id);
+
+    let mut found = vec![];
+    for message_id in board.message_ids.iter().copied().map(MessageId::new) {
+        match retry::discord_retry("fetch soundboard message", || {
+            channel_id.message(http, message_id)
+        })
+        .await
+        {
+            Ok(message) => found.push(message.id),
+            // deleted by a member, or by message retention
+            Err(err) if retention::is_unknown_message(&err) => {}
+            Err(err) => {
+                log::warn!("Failed fetching soundboard message {message_id} - {err}");
+                return None;
+            }
+        }
+    }
+
+    Some(found)
+}
+
+/// Edit board's messages in place. Returns whether every message was edited
+async fn refresh_messages(
+    http: &Http,
+    channel_id: ChannelId,
+    message_ids: &[MessageId],
+    messages: &[BoardMessage],
+) -> bool {
+    let mut refreshed = true;
+    for (message_id, message) in message_ids.iter().zip(messages) {
+        let builder = message.edit();
+        refreshed &= retry::discord_retry("refresh soundboard message", || {
+            channel_id.edit_message(http, *message_id, builder.clone())
+        })
+        .await
+        .log_err_msg("Failed refreshing soundboard message")
+        .is_ok();
+    }
+    refreshed
+}
+
+/// Delete what's left of a board, and stop tracking it for message retention
+async fn delete_messages(
+    http: &Http,
+    data: &UserData,
+    channel_id: ChannelId,
+    message_ids: &[MessageId],
+) {
+    for message_id in message_ids {
+        retry::discord_retry("delete soundboard message", || {
+            channel_id.delete_message(http, *message_id)
+        })
+        .await
+        .log_err_msg("Failed deleting soundboard message")
+        .ok();
+
+        data.bot_messages_table()
+            .delete_message(message_id.get())
+            .ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn board_action_test() {
+        assert_eq!(BoardAction::plan(3, 3, Some(3)), BoardAction::Refresh);
+        // board grew or shrank since it was posted
+        assert_eq!(BoardAction::plan(3, 3, Some(4)), BoardAction::Repost);
+        assert_eq!(BoardAction::plan(3, 3, Some(2)), BoardAction::Repost);
+        // some messages deleted
+        assert_eq!(BoardAction::plan(3, 1, Some(3)), BoardAction::Repost);
+        // every message deleted, or no sounds left
+        assert_eq!(BoardAction::plan(3, 0, Some(3)), BoardAction::Forget);
+        assert_eq!(BoardAction::plan(3, 3, None), BoardAction::Forget);
+    }
+}
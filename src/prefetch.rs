@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::db::{AudioTable, DbPool, PlayHistoryTable, UniqueAudioTableCol};
+
+/// Sounds are ranked by their plays over this many days
+const HOT_SOUND_DAYS: i64 = 7;
+
+/// Most sounds prefetched. The memory budget usually runs out first
+const MAX_HOT_SOUNDS: u32 = 100;
+
+struct CachedSound {
+    bytes: Arc<[u8]>,
+    /// Modified time of the file when it was read. Stale bytes are dropped instead of played
+    modified: SystemTime,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    sounds: HashMap<PathBuf, CachedSound>,
+    size: usize,
+    budget: usize,
+    /// Ticks on every use, orders sounds by how recently they were used
+    clock: u64,
+}
+
+impl CacheState {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn remove(&mut self, path: &Path) {
+        if let Some(sound) = self.sounds.remove(path) {
+            self.size -= sound.bytes.len();
+        }
+    }
+
+    /// Drop least recently used sounds until within budget
+    fn evict(&mut self) {
+        while self.size > self.budget {
+            let Some(path) = self
+                .sounds
+                .iter()
+                .min_by_key(|(_, sound)| sound.last_used)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            self.remove(&path);
+        }
+    }
+}
+
+/// Audio files of the most played sounds, held in memory so they start playing without waiting
+/// on the disk. Least recently used files are dropped once over the memory budget
+#[derive(Default)]
+pub struct SoundCache {
+    state: Mutex<CacheState>,
+}
+
+impl SoundCache {
+    /// Bytes of audio file, when cached & file wasn't modified since
+    pub fn get(&self, path: &Path, modified: SystemTime) -> Option<Arc<[u8]>> {
+        let mut state = self.state.lock().unwrap();
+        let last_used = state.tick();
+
+        match state.sounds.get_mut(path) {
+            Some(sound) if sound.modified == modified => {
+                sound.last_used = last_used;
+                Some(Arc::clone(&sound.bytes))
+            }
+            Some(_) => {
+                state.remove(path);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Cache audio file's bytes as the most recently used. Files larger than the whole budget
+    /// aren't cached
+    pub fn insert(&self, path: PathBuf, bytes: Arc<[u8]>, modified: SystemTime) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if bytes.len() > state.budget {
+            return false;
+        }
+
+        state.remove(&path);
+        let last_used = state.tick();
+        state.size += bytes.len();
+        state.sounds.insert(
+            path,
+            CachedSound {
+                bytes,
+                modified,
+                last_used,
+            },
+        );
+        state.evict();
+        true
+    }
+
+    /// Change how many bytes of audio can be held. A budget of 0 empties the cache
+    pub fn set_budget(&self, budget: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.budget = budget;
+        state.evict();
+    }
+
+    /// Cached sounds, and their size in bytes
+    pub fn usage(&self) -> (usize, usize) {
+        let state = self.state.lock().unwrap();
+        (state.sounds.len(), state.size)
+    }
+}
+
+/// Read the audio files of the most played sounds of the last week into cache, as many as fit
+/// its budget, most played first. Returns how many sounds are cached
+pub async fn prefetch_hot_sounds(
+    cache: &SoundCache,
+    db_pool: &DbPool,
+    budget: usize,
+) -> Result<usize, PoiseError> {
+    cache.set_budget(budget);
+    if budget == 0 {
+        return Ok(0);
+    }
+
+    let since = chrono::Utc::now() - chrono::Duration::days(HOT_SOUND_DAYS);
+    let hot_ids = PlayHistoryTable::new(db_pool.get()?).most_played_since(since, MAX_HOT_SOUNDS)?;
+    let audio_table = AudioTable::new(db_pool.get()?);
+
+    let mut hot_files = vec![];
+    let mut total_size = 0;
+    for audio_id in hot_ids {
+        let Some(row) = audio_table.find_audio_row(UniqueAudioTableCol::Id(audio_id)) else {
+            continue;
+        };
+        let path = row.audio_file.as_path_buf();
+        let Ok(metadata) = tokio::fs::metadata(&path).await else {
+            continue;
+        };
+        let Ok(modified) = metadata.modified() else {
+            continue;
+        };
+
+        // a big sound not fitting leaves room for smaller, less played ones
+        let size = metadata.len() as usize;
+        if total_size + size > budget {
+            continue;
+        }
+        total_size += size;
+        hot_files.push((path, modified));
+    }
+
+    // least played first, so the most played end up the most recently used
+    for (path, modified) in hot_files.iter().rev() {
+        if cache.get(path, *modified).is_some() {
+            continue;
+        }
+        if let Ok(bytes) = tokio::fs::read(path)
+            .await
+            .log_err_msg(format!("Failed prefetching {}", path.display()))
+        {
+            cache.insert(path.clone(), bytes.into(), *modified);
+        }
+    }
+
+    Ok(cache.usage().0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sound_cache_test() {
+        let cache = SoundCache::default();
+        let modified = SystemTime::UNIX_EPOCH;
+        let bytes = |len: usize| Arc::<[u8]>::from(vec![0; len]);
+
+        // nothing fits until there's a budget
+        assert!(!cache.insert("a".into(), bytes(4), modified));
+        cache.set_budget(10);
+
+        assert!(cache.insert("a".into(), bytes(4), modified));
+        assert!(cache.insert("b".into(), bytes(4), modified));
+        assert!(!cache.insert("huge".into(), bytes(11), modified));
+        assert_eq!(cache.usage(), (2, 8));
+
+        // a is used more recently than b, so b is dropped for c
+        assert!(cache.get(Path::new("a"), modified).is_some());
+        assert!(cache.insert("c".into(), bytes(4), modified));
+        assert_eq!(cache.usage(), (2, 8));
+        assert!(cache.get(Path::new("b"), modified).is_none());
+        assert!(cache.get(Path::new("a"), modified).is_some());
+
+        // modified files are dropped
+        let later = modified + std::time::Duration::from_secs(1);
+        assert!(cache.get(Path::new("c"), later).is_none());
+        assert_eq!(cache.usage(), (1, 4));
+
+        cache.set_budget(0);
+        assert_eq!(cache.usage(), (0, 0));
+    }
+}
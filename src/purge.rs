@@ -0,0 +1,232 @@
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton, CreateMessage,
+    EditInteractionResponse, GuildId,
+};
+use serenity::client::Context;
+use sha2::{Digest, Sha256};
+
+use crate::commands::{PoiseError, PoiseResult};
+use crate::common::{LogResult, UserData};
+use crate::db::AudioTableRow;
+use crate::helpers::{self, ButtonCustomId};
+use crate::undo;
+
+/// Names of sounds listed in a purge preview, before the rest are only counted
+pub const PREVIEW_NAMES_LIMIT: usize = 10;
+
+/// Sounds `/sounds purge` deletes, carried by the confirm button of its preview. Holds which
+/// sounds matched at preview time, so a stale preview can't delete sounds that weren't counted
+#[derive(Debug, Clone, PartialEq)]
+pub struct PurgeSpec {
+    pub tag: Option<String>,
+    /// Start of the names of sounds deleted
+    pub prefix: Option<String>,
+    /// Sounds not played in this many days
+    pub unplayed_days: Option<u32>,
+    pub count: usize,
+    /// [`hash_ids`] of the sounds matched at preview time
+    pub ids_hash: String,
+}
+
+/// Short hex hash of sounds' ids, in any order. Fits in a button custom id
+pub fn hash_ids(rows: &[AudioTableRow]) -> String {
+    let mut ids: Vec<_> = rows.iter().map(|row| row.id).collect();
+    ids.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    for id in ids {
+        hasher.update(id.to_le_bytes());
+    }
+    hasher.finalize()[..4]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+impl PurgeSpec {
+    pub fn has_filter(&self) -> bool {
+        self.tag.is_some() || self.prefix.is_some() || self.unplayed_days.is_some()
+    }
+
+    /// Unprotected sounds matching every filter of spec
+    pub fn rows(
+        &self,
+        data: &UserData,
+        guild_id: GuildId,
+    ) -> Result<Vec<AudioTableRow>, PoiseError> {
+        let unplayed_since = self
+            .unplayed_days
+            .map(|days| chrono::Utc::now() - chrono::Duration::days(days as i64));
+        data.audio_table().filter_rows(
+            guild_id.get(),
+            self.tag.as_deref(),
+            self.prefix.as_deref(),
+            unplayed_since,
+        )
+    }
+
+    /// Parse custom id parts following `purge::`. ie: `["12", "1a2b3c4d", "90", "meme", "old-"]`
+    pub fn from_parts(parts: &[&str]) -> Result<Self, String> {
+        let [count, ids_hash, unplayed_days, tag, prefix @ ..] = parts else {
+            return Err(format!("Expected 5 purge parts, found {}", parts.len()));
+        };
+        if prefix.is_empty() {
+            return Err(format!("Expected 5 purge parts, found {}", parts.len()));
+        }
+        let parse_err = |err: std::num::ParseIntError| err.to_string();
+        let non_empty = |value: String| (!value.is_empty()).then_some(value);
+
+        Ok(Self {
+            tag: non_empty(tag.to_string()),
+            // prefixes may hold the separator, so they're last
+            prefix: non_empty(prefix.join("::")),
+            unplayed_days: match *unplayed_days {
+                "" => None,
+                days => Some(days.parse().map_err(parse_err)?),
+            },
+            count: count.parse().map_err(parse_err)?,
+            ids_hash: ids_hash.to_string(),
+        })
+    }
+
+    pub fn to_parts(&self) -> String {
+        format!(
+            "{}::{}::{}::{}::{}",
+            self.count,
+            self.ids_hash,
+            self.unplayed_days
+                .map_or("".into(), |days| days.to_string()),
+            self.tag.as_deref().unwrap_or_default(),
+            self.prefix.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Human readable filters. ie: "tagged `meme`, not played in 90 days"
+    pub fn filter_text(&self) -> String {
+        let mut filters = vec![];
+        if let Some(tag) = &self.tag {
+            filters.push(format!("tagged `{tag}`"));
+        }
+        if let Some(prefix) = &self.prefix {
+            filters.push(format!("named `{prefix}...`"));
+        }
+        if let Some(days) = self.unplayed_days {
+            filters.push(format!("not played in {days} days"));
+        }
+        filters.join(", ")
+    }
+}
+
+/// Confirm & cancel buttons of a purge preview
+pub fn confirm_action_row(spec: PurgeSpec) -> CreateActionRow {
+    let label = format!("Delete {} sounds", spec.count);
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(ButtonCustomId::ConfirmPurge(spec))
+            .label(label)
+            .style(ButtonStyle::Danger),
+        CreateButton::new(ButtonCustomId::CancelPurge).label("Cancel"),
+    ])
+}
+
+/// Delete sounds from the confirm button of a purge preview, unless the sounds matching changed
+/// since. The interaction must already be acknowledged
+pub async fn confirm_purge(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+    spec: PurgeSpec,
+) -> PoiseResult {
+    let reply = |content: String| async move {
+        component
+            .edit_response(
+                ctx,
+                EditInteractionResponse::new()
+                    .content(content)
+                    .components(vec![]),
+            )
+            .await
+            .log_err_msg("Failed replying to purge confirmation")
+            .ok();
+    };
+
+    let guild_id = component
+        .guild_id
+        .ok_or("ComponentInteraction.guild_id is None")
+        .log_err()?;
+    let rows = spec.rows(data, guild_id)?;
+    if rows.len() != spec.count {
+        reply(format!(
+            "{} sounds match now, not {}. Run `/sounds purge` again",
+            rows.len(),
+            spec.count
+        ))
+        .await;
+        return Ok(());
+    }
+    if hash_ids(&rows) != spec.ids_hash {
+        reply("Different sounds match now. Run `/sounds purge` again".to_string()).await;
+        return Ok(());
+    }
+
+    undo::purge_sounds(
+        &data.db_pool,
+        &data.config.get().audio_dir,
+        &rows,
+        Some(guild_id),
+        component.user.id,
+    )?;
+    log::info!(
+        "Purged {} sounds {}. guild_id: {guild_id}",
+        rows.len(),
+        spec.filter_text()
+    );
+
+    let text = format!("Deleted {} sounds {}", rows.len(), spec.filter_text());
+    reply(format!("{text}. `/undo` brings them back")).await;
+
+    helpers::send_audit_message(
+        ctx,
+        data,
+        Some(guild_id),
+        CreateMessage::new().content(format!("{text} by <@{}>", component.user.id)),
+    )
+    .await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn purge_spec_parts_test() {
+        let spec = PurgeSpec {
+            tag: Some("meme".into()),
+            prefix: Some("old::".into()),
+            unplayed_days: Some(90),
+            count: 12,
+            ids_hash: "1a2b3c4d".into(),
+        };
+        assert_eq!(spec.to_parts(), "12::1a2b3c4d::90::meme::old::");
+
+        let custom_id = format!("purge::{}", spec.to_parts());
+        let parts: Vec<_> = custom_id.split("::").collect();
+        assert_eq!(PurgeSpec::from_parts(&parts[1..]), Ok(spec));
+
+        let spec = PurgeSpec {
+            tag: None,
+            prefix: None,
+            unplayed_days: Some(30),
+            count: 1,
+            ids_hash: "00ff00ff".into(),
+        };
+        assert_eq!(
+            PurgeSpec::from_parts(&["1", "00ff00ff", "30", "", ""]),
+            Ok(spec.clone())
+        );
+        assert_eq!(spec.filter_text(), "not played in 30 days");
+        assert!(PurgeSpec::from_parts(&["1", "00ff00ff", "30", ""]).is_err());
+        assert!(PurgeSpec::from_parts(&["x", "00ff00ff", "30", "", ""]).is_err());
+    }
+}
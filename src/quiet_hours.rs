@@ -0,0 +1,192 @@
+use chrono::{DateTime, Duration, FixedOffset, NaiveTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::settings::GuildSettings;
+
+/// Daily time range sounds are refused (or played quieter) in. Wraps past midnight when it
+/// ends before it starts, ie: 22:00-07:00
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Parse `HH:MM-HH:MM`
+    pub fn parse(value: &str) -> Option<Self> {
+        let (start, end) = value.split_once('-')?;
+        let parse_time = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").ok();
+
+        let quiet_hours = Self {
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        };
+        (quiet_hours.start != quiet_hours.end).then_some(quiet_hours)
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        match self.start < self.end {
+            true => self.start <= time && time < self.end,
+            false => time >= self.start || time < self.end,
+        }
+    }
+
+    /// When quiet hours containing `now` end, in the guild's timezone
+    pub fn ends_at(&self, now: DateTime<Utc>, timezone: FixedOffset) -> DateTime<Utc> {
+        let local = now.with_timezone(&timezone);
+        let local_time = local.time().with_nanosecond(0).unwrap_or(local.time());
+
+        let mut until = self.end - local_time;
+        if until <= Duration::zero() {
+            until += Duration::days(1);
+        }
+        now.with_nanosecond(0).unwrap_or(now) + until
+    }
+}
+
+impl std::fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+impl TryFrom<String> for QuietHours {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value).ok_or(format!("Invalid quiet hours {value}"))
+    }
+}
+
+impl From<QuietHours> for String {
+    fn from(quiet_hours: QuietHours) -> Self {
+        quiet_hours.to_string()
+    }
+}
+
+/// Parse UTC offset, ie: `UTC`, `+02:00`, `-5`, or `UTC+5:30`
+pub fn parse_utc_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim().to_uppercase();
+    let value = value
+        .strip_prefix("UTC")
+        .or(value.strip_prefix("GMT"))
+        .unwrap_or(&value);
+    if value.is_empty() || value == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let (sign, offset) = match value.split_at(1) {
+        ("+", offset) => (1, offset),
+        ("-", offset) => (-1, offset),
+        _ => return None,
+    };
+    let (hours, mins) = offset.split_once(':').unwrap_or((offset, "0"));
+    let (hours, mins) = (hours.parse::<i32>().ok()?, mins.parse::<i32>().ok()?);
+    if hours > 14 || mins >= 60 {
+        return None;
+    }
+
+    FixedOffset::east_opt(sign * (hours * 60 * 60 + mins * 60))
+}
+
+/// Offset as `+HH:MM`
+pub fn format_utc_offset(offset: FixedOffset) -> String {
+    offset.to_string()
+}
+
+/// Guild's quiet hours, if they're in effect at `now`
+pub fn active(settings: &GuildSettings, now: DateTime<Utc>) -> Option<QuietHours> {
+    let quiet_hours = settings.quiet_hours?;
+    let local = now.with_timezone(&settings.timezone);
+
+    quiet_hours.contains(local.time()).then_some(quiet_hours)
+}
+
+/// Volume capped to guild's quiet hours volume, while quiet hours are in effect
+pub fn cap_volume(settings: &GuildSettings, volume: f32) -> f32 {
+    match settings.quiet_hours_volume_scale() {
+        Some(cap) if active(settings, Utc::now()).is_some() => volume.min(cap),
+        _ => volume,
+    }
+}
+
+/// When quiet hours refusing playback end. None when sounds can be played right now,
+/// or are only played quieter
+pub fn refused_until(settings: &GuildSettings) -> Option<DateTime<Utc>> {
+    if settings.quiet_hours_volume.is_some() {
+        return None;
+    }
+
+    let now = Utc::now();
+    active(settings, now).map(|quiet_hours| quiet_hours.ends_at(now, settings.timezone))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn time(hour: u32, min: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, min, 0).unwrap()
+    }
+
+    #[test]
+    fn quiet_hours_contains_test() {
+        let overnight = QuietHours::parse("22:00-07:00").unwrap();
+        assert!(overnight.contains(time(23, 30)));
+        assert!(overnight.contains(time(3, 0)));
+        assert!(!overnight.contains(time(7, 0)));
+        assert!(!overnight.contains(time(12, 0)));
+        assert_eq!(overnight.to_string(), "22:00-07:00");
+
+        let daytime = QuietHours::parse(" 9:00 - 17:30").unwrap();
+        assert!(daytime.contains(time(9, 0)));
+        assert!(!daytime.contains(time(17, 30)));
+        assert!(!daytime.contains(time(20, 0)));
+        assert_eq!(daytime.to_string(), "09:00-17:30");
+
+        assert_eq!(QuietHours::parse("22:00-22:00"), None);
+        assert_eq!(QuietHours::parse("22:00"), None);
+        assert_eq!(QuietHours::parse("25:00-07:00"), None);
+    }
+
+    #[test]
+    fn quiet_hours_ends_at_test() {
+        let overnight = QuietHours::parse("22:00-07:00").unwrap();
+        let plus_two = FixedOffset::east_opt(2 * 60 * 60).unwrap();
+
+        // 23:30 local, ends 07:00 local the next day
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 21, 30, 0).unwrap();
+        assert_eq!(
+            overnight.ends_at(now, plus_two),
+            Utc.with_ymd_and_hms(2024, 1, 2, 5, 0, 0).unwrap()
+        );
+
+        // 03:00 local, ends 07:00 local the same day
+        let now = Utc.with_ymd_and_hms(2024, 1, 2, 1, 0, 0).unwrap();
+        assert_eq!(
+            overnight.ends_at(now, plus_two),
+            Utc.with_ymd_and_hms(2024, 1, 2, 5, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_utc_offset_test() {
+        let offset = |secs: i32| FixedOffset::east_opt(secs);
+
+        assert_eq!(parse_utc_offset("UTC"), offset(0));
+        assert_eq!(parse_utc_offset("+02:00"), offset(2 * 60 * 60));
+        assert_eq!(parse_utc_offset("utc-5"), offset(-5 * 60 * 60));
+        assert_eq!(parse_utc_offset("UTC+5:30"), offset(5 * 60 * 60 + 30 * 60));
+        assert_eq!(parse_utc_offset("+15"), None);
+        assert_eq!(parse_utc_offset("Europe/Berlin"), None);
+        assert_eq!(format_utc_offset(offset(-5 * 60 * 60).unwrap()), "-05:00");
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Buckets kept before idle ones are dropped, so callers that come & go don't grow the map forever
+const MAX_IDLE_BUCKETS: usize = 4096;
+
+/// Token bucket. Holds up to `capacity` tokens, refilled evenly over a minute, so a caller can
+/// burst up to its limit, then carries on at its per minute rate
+#[derive(Debug, Clone, PartialEq)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(capacity: f64, now: Instant) -> Self {
+        Self {
+            tokens: capacity,
+            updated_at: now,
+        }
+    }
+
+    fn refill(&mut self, capacity: f64, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * capacity / 60.0).min(capacity);
+        self.updated_at = now;
+    }
+
+    /// Take a token. Returns how long until one is available when empty
+    fn take(&mut self, capacity: f64, now: Instant) -> Result<(), Duration> {
+        self.refill(capacity, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+
+        let missing = 1.0 - self.tokens;
+        Err(Duration::from_secs_f64(missing * 60.0 / capacity))
+    }
+}
+
+/// Per caller request limits, ie: per API key
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<String, TokenBucket>>,
+}
+
+impl RateLimiter {
+    /// Count a request of caller against `per_minute` requests. Returns how long to wait before
+    /// retrying when over the limit. A limit of 0 lets every request through
+    pub fn check(&self, caller: &str, per_minute: u32, now: Instant) -> Result<(), Duration> {
+        if per_minute == 0 {
+            return Ok(());
+        }
+
+        let capacity = per_minute as f64;
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_IDLE_BUCKETS {
+            prune(&mut buckets, capacity, now);
+        }
+
+        buckets
+            .entry(caller.to_string())
+            .or_insert_with(|| TokenBucket::new(capacity, now))
+            .take(capacity, now)
+    }
+}
+
+/// Drop buckets that filled back up, since they'd be created full anyway
+fn prune(buckets: &mut HashMap<String, TokenBucket>, capacity: f64, now: Instant) {
+    buckets.retain(|_, bucket| {
+        bucket.refill(capacity, now);
+        bucket.tokens < capacity
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_limit_test() {
+        let limiter = RateLimiter::default();
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.check("a", 3, now), Ok(()));
+        }
+        assert_eq!(limiter.check("a", 3, now), Err(Duration::from_secs(20)));
+        // other callers have their own bucket
+        assert_eq!(limiter.check("b", 3, now), Ok(()));
+
+        // a token is back every 20 seconds
+        let later = now + Duration::from_secs(10);
+        assert_eq!(limiter.check("a", 3, later), Err(Duration::from_secs(10)));
+        let later = now + Duration::from_secs(20);
+        assert_eq!(limiter.check("a", 3, later), Ok(()));
+        assert!(limiter.check("a", 3, later).is_err());
+
+        assert_eq!(limiter.check("a", 0, later), Ok(()));
+    }
+
+    #[test]
+    fn rate_limit_prune_test() {
+        let limiter = RateLimiter::default();
+        let now = Instant::now();
+        limiter.check("a", 60, now).unwrap();
+        limiter.check("b", 60, now).unwrap();
+
+        let mut buckets = limiter.buckets.lock().unwrap();
+        prune(&mut buckets, 60.0, now + Duration::from_millis(500));
+        assert_eq!(buckets.len(), 2);
+        prune(&mut buckets, 60.0, now + Duration::from_secs(1));
+        assert!(buckets.is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+use serenity::all::CreateActionRow;
+
+use crate::db::AudioTableRow;
+use crate::helpers;
+use crate::vars;
+
+/// Sounds on a recent board, when no count is given
+pub const DEFAULT_RECENT_COUNT: u32 = 10;
+
+/// Most sounds on a recent board. 5 rows of 5, a full message of buttons
+pub const MAX_RECENT_COUNT: u32 = 25;
+
+/// Who added sound, as a mention when their id is known
+fn uploader(row: &AudioTableRow) -> String {
+    match (row.author_id, &row.author_global_name, &row.author_name) {
+        (Some(author_id), _, _) => format!("<@{author_id}>"),
+        (None, Some(name), _) | (None, None, Some(name)) => name.clone(),
+        (None, None, None) => "unknown".into(),
+    }
+}
+
+/// Message content and sound buttons of the newest sounds, listing who added each and when.
+/// Rows are expected newest first
+pub fn board(rows: &[AudioTableRow]) -> (String, Vec<CreateActionRow>) {
+    if rows.is_empty() {
+        return ("No sounds added yet".into(), vec![]);
+    }
+
+    let mut content = format!("🆕 {} newest sounds", rows.len());
+    for row in rows {
+        let line = format!(
+            "\n- `{}` by {} <t:{}:R>",
+            row.name,
+            uploader(row),
+            row.created_at.timestamp()
+        );
+        if content.chars().count() + line.chars().count() > vars::MESSAGE_CONTENT_MAX_LEN {
+            break;
+        }
+        content.push_str(&line);
+    }
+
+    let mut labels = helpers::BoardLabels::default();
+    let components = rows
+        .chunks(5)
+        .map(|rows| helpers::make_action_row(rows, &mut labels))
+        .collect();
+
+    (content, components)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_board_test() {
+        let mut rows: Vec<_> = (0..7).map(AudioTableRow::test_row).collect();
+        rows[0].author_id = Some(42);
+        rows[0].author_name = Some("bob".into());
+        rows[1].author_name = Some("bob".into());
+        rows[1].author_global_name = Some("Bob".into());
+
+        let (content, components) = board(&rows);
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines[0], "🆕 7 newest sounds");
+        assert!(lines[1].starts_with("- `Sound 0` by <@42> <t:"));
+        assert!(lines[2].starts_with("- `Sound 1` by Bob <t:"));
+        assert!(lines[3].starts_with("- `Sound 2` by unknown <t:"));
+        let labels = helpers::button_labels(&components);
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels[0][0], "Sound 0");
+        assert_eq!(labels[1], vec!["Sound 5", "Sound 6"]);
+
+        let (content, components) = board(&[]);
+        assert_eq!(content, "No sounds added yet");
+        assert!(components.is_empty());
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::HashSet;
+
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton, CreateEmbed, CreateMessage,
+    EditInteractionResponse,
+};
+use serenity::client::Context;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+use crate::audio::AudioFile;
+use crate::branding::Branding;
+use crate::commands::{PoiseError, PoiseResult};
+use crate::common::{LogResult, UserData};
+use crate::db::{self, AudioTableRow};
+use crate::helpers::{self, ButtonCustomId};
+use crate::{scan, settings};
+
+/// Sounds whose files are missing, and audio files no sound uses. Compared by path only, so
+/// nothing is read
+pub struct Reconciliation {
+    /// Rows whose audio file doesn't exist in the audio directory
+    pub dangling: Vec<AudioTableRow>,
+    /// Audio files in the audio directory without a row
+    pub orphaned: Vec<AudioFile>,
+}
+
+impl Reconciliation {
+    pub fn new(rows: Vec<AudioTableRow>, audio_files: impl IntoIterator<Item = AudioFile>) -> Self {
+        let audio_files: Vec<_> = audio_files.into_iter().collect();
+        let paths: HashSet<_> = audio_files
+            .iter()
+            .map(|audio_file| audio_file.as_path_buf())
+            .collect();
+        let row_paths: HashSet<_> = rows
+            .iter()
+            .map(|row| row.audio_file.as_path_buf())
+            .collect();
+
+        Self {
+            dangling: rows
+                .into_iter()
+                .filter(|row| !paths.contains(&row.audio_file.as_path_buf()))
+                .collect(),
+            orphaned: audio_files
+                .into_iter()
+                .filter(|audio_file| !row_paths.contains(&audio_file.as_path_buf()))
+                .collect(),
+        }
+    }
+
+    /// Compare every row against the audio directory
+    pub fn load(data: &UserData) -> Result<Self, PoiseError> {
+        let mut rows = vec![];
+        for page in db::AudioTablePaginator::builder(data.db_connection()).build() {
+            rows.extend(page?);
+        }
+
+        Ok(Self::new(rows, data.read_audio_dir()))
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.dangling.is_empty() && self.orphaned.is_empty()
+    }
+
+    pub fn embed(&self, branding: &Branding) -> CreateEmbed {
+        let dangling: Vec<_> = self
+            .dangling
+            .iter()
+            .map(|row| format!("{}: `{}`", row.name, row.audio_file.file_name_lossy()))
+            .collect();
+        let orphaned: Vec<_> = self
+            .orphaned
+            .iter()
+            .map(|audio_file| format!("`{}`", audio_file.file_name_lossy()))
+            .collect();
+
+        let embed = CreateEmbed::new()
+            .title("Reconcile")
+            .description(match self.is_clean() {
+                true => "Every sound has its audio file, and every audio file is a sound",
+                false => "Remove sounds whose audio file is missing, or import audio files as sounds. Imported files moved from a missing sound are matched back to it",
+            })
+            .field(
+                format!("Missing files ({})", dangling.len()),
+                helpers::embed_field_list(&dangling),
+                false,
+            )
+            .field(
+                format!("Files without a sound ({})", orphaned.len()),
+                helpers::embed_field_list(&orphaned),
+                false,
+            );
+        branding.apply(embed)
+    }
+
+    /// Fix buttons, for whichever of rows & files there are
+    pub fn action_rows(&self) -> Vec<CreateActionRow> {
+        let mut buttons = vec![];
+        if !self.dangling.is_empty() {
+            buttons.push(
+                CreateButton::new(ButtonCustomId::ReconcileRemoveRows)
+                    .label(format!("Remove {} missing", self.dangling.len()))
+                    .style(ButtonStyle::Danger),
+            );
+        }
+        if !self.orphaned.is_empty() {
+            buttons.push(
+                CreateButton::new(ButtonCustomId::ReconcileImportFiles)
+                    .label(format!("Import {} files", self.orphaned.len()))
+                    .style(ButtonStyle::Primary),
+            );
+        }
+
+        match buttons.is_empty() {
+            true => vec![],
+            false => vec![CreateActionRow::Buttons(buttons)],
+        }
+    }
+}
+
+/// Warn about missing & orphaned audio files once the bot starts
+pub fn log_startup_check(data: &UserData) {
+    let Ok(reconciliation) = Reconciliation::load(data).log_err_msg("Failed reconciling sounds")
+    else {
+        return;
+    };
+
+    if !reconciliation.is_clean() {
+        log::warn!(
+            "{} sounds are missing their audio file, and {} audio files aren't sounds. Fix them with `/sounds reconcile`",
+            reconciliation.dangling.len(),
+            reconciliation.orphaned.len()
+        );
+    }
+}
+
+async fn edit_reply(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+    content: String,
+    reconciliation: &Reconciliation,
+) {
+    component
+        .edit_response(
+            ctx,
+            EditInteractionResponse::new()
+                .content(content)
+                .embed(reconciliation.embed(&Branding::of(data, component.guild_id)))
+                .components(reconciliation.action_rows()),
+        )
+        .await
+        .log_err_msg("Failed updating reconcile reply")
+        .ok();
+}
+
+/// Delete rows whose audio file is still missing
+pub async fn remove_rows(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+) -> PoiseResult {
+    let reconciliation = Reconciliation::load(data)?;
+    let table = data.audio_table();
+
+    let removed: Vec<_> = reconciliation
+        .dangling
+        .iter()
+        .filter(|row| table.delete_row(row.id).log_err().is_ok())
+        .map(|row| format!("`{}`", row.name))
+        .collect();
+
+    let reconciliation = Reconciliation::load(data)?;
+    edit_reply(
+        ctx,
+        component,
+        data,
+        format!("Removed {} sounds missing their audio file", removed.len()),
+        &reconciliation,
+    )
+    .await;
+
+    if !removed.is_empty() {
+        helpers::send_audit_message(
+            ctx,
+            data,
+            component.guild_id,
+            CreateMessage::new().content(format!(
+                "Sounds missing their audio file removed by <@{}>: {}",
+                component.user.id,
+                removed.join(", ")
+            )),
+        )
+        .await;
+    }
+
+    Ok(())
+}
+
+/// Scan audio files without a row, adding them as sounds. Files matching the hash of a row
+/// whose file is missing are moved back to that row
+pub async fn import_files(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+) -> PoiseResult {
+    let reconciliation = Reconciliation::load(data)?;
+    let config = data.config.get();
+    let scanner = scan::AudioDirScanner::new()
+        .max_audio_duration(config.max_audio_file_duration)
+        .max_file_size(config.max_audio_file_size)
+        .concurrency(config.scan_concurrency);
+
+    let (progress_tx, _) = watch::channel(scan::ScanProgress::default());
+    let mut report = scanner
+        .scan(
+            reconciliation.orphaned,
+            reconciliation.dangling,
+            progress_tx,
+            &CancellationToken::new(),
+        )
+        .await?;
+    // missing rows are only removed by their own button
+    report.removed.clear();
+
+    let name_collision = component
+        .guild_id
+        .map(|guild_id| settings::name_collision(&data.settings_table(), guild_id))
+        .unwrap_or_default();
+    let failed_writes = report.apply(&data.audio_table(), &config, name_collision);
+    let branding = Branding::of(data, component.guild_id);
+    let summary = report
+        .summary_embed(failed_writes, &branding)
+        .title("Reconcile Import");
+
+    let reconciliation = Reconciliation::load(data)?;
+    component
+        .edit_response(
+            ctx,
+            EditInteractionResponse::new()
+                .content(format!(
+                    "Imported {} files, matched {} to missing sounds",
+                    report.new.len(),
+                    report.renamed.len()
+                ))
+                .embeds(vec![summary.clone(), reconciliation.embed(&branding)])
+                .components(reconciliation.action_rows()),
+        )
+        .await
+        .log_err_msg("Failed updating reconcile reply")
+        .ok();
+
+    helpers::send_audit_message(
+        ctx,
+        data,
+        component.guild_id,
+        CreateMessage::new()
+            .content(format!("Audio files imported by <@{}>", component.user.id))
+            .embed(summary),
+    )
+    .await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reconciliation_test() {
+        let rows = vec![
+            AudioTableRow::test_row(1).with_audio_file("/sounds/a.mp3"),
+            AudioTableRow::test_row(2).with_audio_file("/sounds/b.mp3"),
+        ];
+        let audio_files = ["a.mp3", "c.mp3"]
+            .map(|file_name| AudioFile::new(format!("/sounds/{file_name}").into()));
+
+        let reconciliation = Reconciliation::new(rows, audio_files);
+        assert_eq!(reconciliation.dangling.len(), 1);
+        assert_eq!(reconciliation.dangling[0].id, 2);
+        assert_eq!(
+            reconciliation.orphaned,
+            vec![AudioFile::new("/sounds/c.mp3".into())]
+        );
+        assert!(!reconciliation.is_clean());
+        assert_eq!(
+            helpers::button_labels(&reconciliation.action_rows()),
+            vec![vec!["Remove 1 missing", "Import 1 files"]]
+        );
+
+        let reconciliation = Reconciliation::new(vec![], []);
+        assert!(reconciliation.is_clean());
+        assert!(reconciliation.action_rows().is_empty());
+    }
+}
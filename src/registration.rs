@@ -0,0 +1,236 @@
+use std::sync::Mutex;
+
+use serde::Deserialize;
+use serenity::all::{Command, CreateCommand, CreateEmbed, CreateEmbedFooter, GuildId, Http};
+
+use crate::commands::PoiseError;
+use crate::common::UserData;
+use crate::retry;
+
+/// Where slash commands are registered with discord
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, poise::ChoiceParameter)]
+#[serde(rename_all = "lowercase")]
+pub enum CommandRegistration {
+    /// Commands are only registered with the `register` command
+    #[default]
+    #[name = "manual"]
+    Manual,
+    /// Registered once for every guild. Discord can take a while to show changes
+    #[name = "global"]
+    Global,
+    /// Registered in each guild separately. Changes show up right away, handy when developing
+    #[name = "guild"]
+    Guild,
+}
+
+impl std::fmt::Display for CommandRegistration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Manual => "manual",
+            Self::Global => "global",
+            Self::Guild => "guild",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Where the `register` command registers or clears slash commands
+#[derive(Debug, Clone, Copy, PartialEq, poise::ChoiceParameter)]
+pub enum RegisterScope {
+    /// Every guild. Discord can take a while to show changes
+    #[name = "global"]
+    Global,
+    /// Only the guild the command was run in. Changes show up right away
+    #[name = "this-guild"]
+    Guild,
+}
+
+impl std::fmt::Display for RegisterScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Global => write!(f, "globally"),
+            Self::Guild => write!(f, "in this guild"),
+        }
+    }
+}
+
+/// Names of commands registered as slash commands, ie: not prefix only commands
+pub fn slash_command_names(commands: &[poise::Command<UserData, PoiseError>]) -> Vec<String> {
+    commands
+        .iter()
+        .filter(|command| {
+            command.slash_action.is_some()
+                || command.context_menu_action.is_some()
+                || !command.subcommands.is_empty()
+        })
+        .map(|command| command.name.clone())
+        .collect()
+}
+
+/// Register slash commands in scope, replacing commands registered there, or clear them when
+/// `clear`. Guild scope needs the guild. Returns names of commands registered
+pub async fn register_scope(
+    http: &Http,
+    commands: &[poise::Command<UserData, PoiseError>],
+    scope: RegisterScope,
+    guild_id: Option<GuildId>,
+    clear: bool,
+) -> Result<Vec<String>, PoiseError> {
+    let (create_commands, names) = match clear {
+        true => (vec![], vec![]),
+        false => (
+            poise::builtins::create_application_commands(commands),
+            slash_command_names(commands),
+        ),
+    };
+
+    match scope {
+        RegisterScope::Global => set_global_commands(http, create_commands).await?,
+        RegisterScope::Guild => {
+            let guild_id = guild_id.ok_or("Registering in this guild must be run in a guild")?;
+            set_guild_commands(http, guild_id, create_commands).await?
+        }
+    }
+
+    log::info!("Registered {} commands {scope}", names.len());
+    Ok(names)
+}
+
+/// Embed summing up what the `register` command registered, or cleared
+pub fn register_embed(scope: RegisterScope, clear: bool, names: &[String]) -> CreateEmbed {
+    let embed = match clear {
+        true => CreateEmbed::new()
+            .title(format!("Cleared slash commands {scope}"))
+            .description("No slash commands are registered there now"),
+        false => CreateEmbed::new()
+            .title(format!("Registered {} slash commands {scope}", names.len()))
+            .description(
+                names
+                    .iter()
+                    .map(|name| format!("`/{name}`"))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+    };
+
+    match scope {
+        RegisterScope::Global => embed.footer(CreateEmbedFooter::new(
+            "Global changes can take a while to show up in discord",
+        )),
+        RegisterScope::Guild => embed,
+    }
+}
+
+/// Registers slash commands in the configured mode, and removes commands registered in the other mode,
+/// so commands don't show up twice. Guilds are registered one at a time, leaving serenity's
+/// ratelimiter room to space out requests, and transient failures are retried.
+#[derive(Default)]
+pub struct CommandRegistrar {
+    /// Mode commands are currently registered in. None until first registered
+    mode: Mutex<Option<CommandRegistration>>,
+}
+
+impl CommandRegistrar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn mode(&self) -> Option<CommandRegistration> {
+        *self.mode.lock().unwrap()
+    }
+
+    /// Register commands in `mode`, unless they already are. Called on every ready event,
+    /// which discord sends again after reconnecting
+    pub async fn register_once(
+        &self,
+        http: &Http,
+        commands: &[poise::Command<UserData, PoiseError>],
+        guild_ids: &[GuildId],
+        mode: CommandRegistration,
+    ) -> Result<String, PoiseError> {
+        match self.mode() {
+            Some(_) => Ok("Commands already registered".into()),
+            None => self.switch(http, commands, guild_ids, mode).await,
+        }
+    }
+
+    /// Register commands in `mode`, and remove commands of the other mode. Returns summary of changes
+    pub async fn switch(
+        &self,
+        http: &Http,
+        commands: &[poise::Command<UserData, PoiseError>],
+        guild_ids: &[GuildId],
+        mode: CommandRegistration,
+    ) -> Result<String, PoiseError> {
+        let create_commands = poise::builtins::create_application_commands(commands);
+
+        let summary = match mode {
+            CommandRegistration::Manual => "Manual registration, commands left as they are".into(),
+            CommandRegistration::Global => {
+                set_global_commands(http, create_commands.clone()).await?;
+                for guild_id in guild_ids {
+                    set_guild_commands(http, *guild_id, vec![]).await?;
+                }
+                format!(
+                    "Registered {} commands globally, removed guild commands from {} guilds",
+                    create_commands.len(),
+                    guild_ids.len()
+                )
+            }
+            CommandRegistration::Guild => {
+                for guild_id in guild_ids {
+                    set_guild_commands(http, *guild_id, create_commands.clone()).await?;
+                }
+                set_global_commands(http, vec![]).await?;
+                format!(
+                    "Registered {} commands in {} guilds, removed global commands",
+                    create_commands.len(),
+                    guild_ids.len()
+                )
+            }
+        };
+
+        log::info!("Command registration {mode} - {summary}");
+        *self.mode.lock().unwrap() = Some(mode);
+
+        Ok(summary)
+    }
+
+    /// Register commands in guild the bot just joined, when registering per guild
+    pub async fn register_guild(
+        &self,
+        http: &Http,
+        commands: &[poise::Command<UserData, PoiseError>],
+        guild_id: GuildId,
+    ) -> Result<(), PoiseError> {
+        if self.mode() != Some(CommandRegistration::Guild) {
+            return Ok(());
+        }
+
+        let create_commands = poise::builtins::create_application_commands(commands);
+        set_guild_commands(http, guild_id, create_commands).await?;
+        log::info!("Registered commands in new guild {guild_id}");
+
+        Ok(())
+    }
+}
+
+async fn set_global_commands(http: &Http, commands: Vec<CreateCommand>) -> Result<(), PoiseError> {
+    retry::discord_retry("set global commands", || {
+        Command::set_global_commands(http, commands.clone())
+    })
+    .await?;
+    Ok(())
+}
+
+async fn set_guild_commands(
+    http: &Http,
+    guild_id: GuildId,
+    commands: Vec<CreateCommand>,
+) -> Result<(), PoiseError> {
+    retry::discord_retry("set guild commands", || {
+        guild_id.set_commands(http, commands.clone())
+    })
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,227 @@
+use serenity::all::{
+    ButtonStyle, CacheHttp, ChannelId, ComponentInteraction, CreateActionRow, CreateButton,
+    CreateEmbed, CreateMessage, EditInteractionResponse, EditMessage, GuildId, UserId,
+};
+use serenity::client::Context;
+
+use crate::branding::Branding;
+use crate::commands::{PoiseError, PoiseResult};
+use crate::common::{LogResult, UserData};
+use crate::db::{SoundRequestRow, SoundRequestStatus, Tags};
+use crate::helpers::{self, ButtonCustomId};
+use crate::{collision, ingest, retry, settings, vars};
+
+/// Requests a member can have waiting on moderators at once
+pub const MAX_PENDING_REQUESTS: u32 = 5;
+
+/// Sound name derived from file name of url. ie: `https://x.com/air_horn.mp3?dl=1` -> `Air Horn`
+pub fn sound_name_from_url(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let file_name = path.trim_end_matches('/').rsplit('/').next()?;
+    let stem = match file_name.rsplit_once('.') {
+        Some((stem, _ext)) if !stem.is_empty() => stem,
+        _ => file_name,
+    };
+
+    let name = helpers::title_case(stem.replace(['_', '-', '+'], " "));
+    match name.is_empty() {
+        true => None,
+        false => Some(name.chars().take(vars::BTN_LABEL_MAX_LEN).collect()),
+    }
+}
+
+fn request_embed(data: &UserData, row: &SoundRequestRow) -> CreateEmbed {
+    let status = match row.status {
+        SoundRequestStatus::Pending => "Pending".to_string(),
+        SoundRequestStatus::Approved => format!("Approved by <@{}>", row.resolved_by.unwrap_or(0)),
+        SoundRequestStatus::Denied => format!("Denied by <@{}>", row.resolved_by.unwrap_or(0)),
+    };
+
+    let embed = CreateEmbed::new()
+        .title(format!("Sound Request #{}", row.id))
+        .field("Requested By", format!("<@{}>", row.user_id), true)
+        .field("Name", row.name.as_deref().unwrap_or("-"), true)
+        .field("Status", status, true)
+        .field("Request", row.request.clone(), false)
+        .timestamp(row.created_at);
+    Branding::load(&data.settings_table(), GuildId::new(row.guild_id)).apply(embed)
+}
+
+/// Post request to guild's request channel (or audit channel), with approve & deny buttons for moderators
+pub async fn notify_moderators(
+    cache_http: impl CacheHttp,
+    data: &UserData,
+    row: &SoundRequestRow,
+) -> PoiseResult {
+    let guild_id = GuildId::new(row.guild_id);
+    let channel_id = settings::request_channel(&data.settings_table(), guild_id)
+        .or(helpers::audit_channel_id(data, Some(guild_id)))
+        .ok_or("No request or audit channel is set up for moderators to review requests")?;
+
+    let buttons = CreateActionRow::Buttons(vec![
+        CreateButton::new(ButtonCustomId::ApproveRequest(row.id))
+            .label("Approve")
+            .style(ButtonStyle::Success),
+        CreateButton::new(ButtonCustomId::DenyRequest(row.id))
+            .label("Deny")
+            .style(ButtonStyle::Danger),
+    ]);
+    let message = CreateMessage::new()
+        .embed(request_embed(data, row))
+        .components(vec![buttons]);
+
+    retry::discord_retry("send sound request message", || {
+        channel_id.send_message(&cache_http, message.clone())
+    })
+    .await
+    .log_err_msg("Failed sending sound request to moderators")?;
+
+    Ok(())
+}
+
+/// Approve or deny a request from the buttons of its moderator message. Approving a request
+/// for an audio url imports it into the soundboard. The interaction must already be deferred.
+pub async fn review_request(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+    request_id: i64,
+    approve: bool,
+) -> PoiseResult {
+    log::info!(
+        "Reviewing sound request {request_id}. approve: {approve}, moderator: {}",
+        component.user.id
+    );
+
+    let reply = |content: String| async move {
+        component
+            .edit_response(ctx, EditInteractionResponse::new().content(content))
+            .await
+            .log_err_msg("Failed replying to sound request review")
+            .ok();
+    };
+
+    let table = data.sound_requests_table();
+    let row = match table.find_row(request_id)? {
+        Some(row) if row.status == SoundRequestStatus::Pending => row,
+        Some(_) => {
+            reply(format!("Request #{request_id} was already reviewed")).await;
+            return Ok(());
+        }
+        None => return Err(format!("Sound request {request_id} not found").into()).log_err(),
+    };
+
+    let mut imported_name = None;
+    if approve {
+        if let Some(url) = row.url() {
+            match import_request(ctx, data, &row, url).await {
+                Ok(name) => imported_name = Some(name),
+                Err(err) => {
+                    reply(format!(
+                        "Failed importing request #{request_id}, it's still pending - {err}"
+                    ))
+                    .await;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    let status = match approve {
+        true => SoundRequestStatus::Approved,
+        false => SoundRequestStatus::Denied,
+    };
+    if !table.resolve(request_id, status, component.user.id.get())? {
+        reply(format!("Request #{request_id} was already reviewed")).await;
+        return Ok(());
+    }
+
+    let row = table.find_row(request_id)?.unwrap_or(row);
+    component
+        .channel_id
+        .edit_message(
+            ctx,
+            component.message.id,
+            EditMessage::new()
+                .embed(request_embed(data, &row))
+                .components(vec![]),
+        )
+        .await
+        .log_err_msg("Failed updating sound request message")
+        .ok();
+
+    let outcome = match (approve, &imported_name) {
+        (true, Some(name)) => format!("was approved and added as `{name}`"),
+        (true, None) => "was approved. A moderator will add it soon".to_string(),
+        (false, _) => "was denied".to_string(),
+    };
+    let text = format!(
+        "<@{}>, your sound request #{request_id} {outcome}",
+        row.user_id
+    );
+    helpers::check_msg(
+        ChannelId::new(row.channel_id)
+            .send_message(ctx, CreateMessage::new().content(text))
+            .await,
+    );
+
+    helpers::send_audit_message(
+        ctx,
+        data,
+        Some(GuildId::new(row.guild_id)),
+        CreateMessage::new().content(format!(
+            "Sound request #{request_id} {} by <@{}>",
+            status.as_str(),
+            component.user.id
+        )),
+    )
+    .await;
+
+    reply(format!("Request #{request_id} {}", status.as_str())).await;
+    Ok(())
+}
+
+/// Import request's audio url, credited to the requester. Returns name of the new sound
+async fn import_request(
+    ctx: &Context,
+    data: &UserData,
+    row: &SoundRequestRow,
+    url: &str,
+) -> Result<String, PoiseError> {
+    let name = row
+        .name
+        .clone()
+        .or_else(|| sound_name_from_url(url))
+        .ok_or("Request has no name, and one can't be made from its url")?;
+    let name = collision::resolve_name(data, Some(GuildId::new(row.guild_id)), &name)?;
+
+    let requester = UserId::new(row.user_id).to_user(ctx).await?;
+    let target = ingest::IngestTarget::New {
+        name: &name,
+        tags: Tags::new(),
+        author: &requester,
+    };
+    ingest::Ingest::new(data)
+        .run(ingest::IngestSource::Url(url.into()), target)
+        .await?;
+
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sound_name_from_url_test() {
+        assert_eq!(
+            sound_name_from_url("https://x.com/sounds/air_horn.mp3?dl=1"),
+            Some("Air Horn".into())
+        );
+        assert_eq!(
+            sound_name_from_url("https://x.com/bruh-moment"),
+            Some("Bruh Moment".into())
+        );
+        assert_eq!(sound_name_from_url(""), None);
+    }
+}
@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use serenity::all::{ChannelId, GuildId, Http, MessageId};
+use serenity::http::HttpError;
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::db::{BotMessagesTable, DbPool, PostedBoardsTable, SettingsTable};
+use crate::retry;
+use crate::settings::GuildSettings;
+
+/// How often old bot messages are cleaned up in guilds with message retention set
+pub const RETENTION_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Which of the bot's tracked messages are kept
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RetentionPolicy {
+    /// Messages older than this are removed
+    pub max_age: Option<chrono::Duration>,
+    /// Newest messages kept per channel, older ones are removed
+    pub keep: Option<u32>,
+}
+
+impl RetentionPolicy {
+    /// Guild's message retention settings
+    pub fn guild(table: &SettingsTable, guild_id: GuildId) -> Self {
+        let settings = GuildSettings::load(table, guild_id);
+        Self {
+            max_age: settings.message_retention(),
+            keep: settings.message_retention_count(),
+        }
+    }
+
+    /// Whether messages are kept forever
+    pub fn keeps_all(&self) -> bool {
+        self.max_age.is_none() && self.keep.is_none()
+    }
+}
+
+/// Delete guild's tracked bot messages not kept by policy. Only messages in channel when channel_id
+/// is given. Posted boards losing a message are forgotten, so they aren't posted again on startup.
+/// Returns number of messages deleted, or that would be when `dry_run` is set
+pub async fn cleanup(
+    http: &Http,
+    db_pool: &DbPool,
+    guild_id: GuildId,
+    channel_id: Option<ChannelId>,
+    policy: RetentionPolicy,
+    dry_run: bool,
+) -> Result<usize, PoiseError> {
+    if policy.keeps_all() {
+        return Ok(0);
+    }
+
+    let before = policy.max_age.map(|max_age| chrono::Utc::now() - max_age);
+    let rows = BotMessagesTable::new(db_pool.get()?).expired_rows(
+        guild_id.get(),
+        channel_id.map(|id| id.get()),
+        before,
+        policy.keep,
+    )?;
+    if dry_run {
+        return Ok(rows.len());
+    }
+
+    let mut deleted = 0;
+    for row in rows {
+        let channel_id = ChannelId::new(row.channel_id);
+        let message_id = MessageId::new(row.message_id);
+
+        match retry::discord_retry("delete old bot message", || {
+            channel_id.delete_message(http, message_id)
+        })
+        .await
+        {
+            Ok(()) => deleted += 1,
+            // already gone, ie: deleted by a member or a temporary soundboard's ttl
+            Err(err) if is_unknown_message(&err) => {}
+            Err(err) => {
+                log::warn!("Failed deleting old bot message {message_id} - {err}");
+                continue;
+            }
+        }
+
+        BotMessagesTable::new(db_pool.get()?)
+            .delete_message(row.message_id)
+            .ok();
+        PostedBoardsTable::new(db_pool.get()?)
+            .delete_boards_with_message(row.message_id)
+            .log_err_msg("Failed forgetting posted soundboard of old bot message")
+            .ok();
+    }
+
+    Ok(deleted)
+}
+
+/// Whether request failed since the message, or its channel, no longer exists
+pub fn is_unknown_message(err: &serenity::Error) -> bool {
+    matches!(
+        err,
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(response))
+            if response.status_code.as_u16() == 404
+    )
+}
+
+/// Retention policies of guilds with tracked messages, that don't keep them forever
+fn guild_policies(
+    settings_table: &SettingsTable,
+    bot_messages_table: &BotMessagesTable,
+) -> Result<Vec<(GuildId, RetentionPolicy)>, PoiseError> {
+    Ok(bot_messages_table
+        .guild_ids()?
+        .into_iter()
+        .map(GuildId::new)
+        .map(|guild_id| (guild_id, RetentionPolicy::guild(settings_table, guild_id)))
+        .filter(|(_, policy)| !policy.keeps_all())
+        .collect())
+}
+
+pub fn read_guild_policies(
+    db_pool: &DbPool,
+) -> Result<Vec<(GuildId, RetentionPolicy)>, PoiseError> {
+    guild_policies(
+        &SettingsTable::new(db_pool.get()?),
+        &BotMessagesTable::new(db_pool.get()?),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::db::Table;
+    use crate::settings::GuildSettingKey;
+
+    #[test]
+    fn guild_policies_test() {
+        // each connection of an in memory pool is its own database, tables are created where they're read
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        let settings_table = SettingsTable::new(db_pool.get().unwrap());
+        settings_table.create_table();
+        let bot_messages_table = BotMessagesTable::new(db_pool.get().unwrap());
+        bot_messages_table.create_table();
+
+        let now = chrono::Utc::now();
+        bot_messages_table.insert_message(1, 10, 100, now).unwrap();
+        bot_messages_table.insert_message(2, 20, 200, now).unwrap();
+        settings_table
+            .set_guild_setting(1, GuildSettingKey::MessageRetention.key(), "12")
+            .unwrap();
+
+        let policies = guild_policies(&settings_table, &bot_messages_table).unwrap();
+        assert_eq!(
+            policies,
+            vec![(
+                GuildId::new(1),
+                RetentionPolicy {
+                    max_age: Some(chrono::Duration::hours(12)),
+                    keep: None,
+                }
+            )]
+        );
+    }
+}
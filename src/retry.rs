@@ -0,0 +1,126 @@
+use std::future::Future;
+use std::time::Duration;
+
+use serenity::http::HttpError;
+
+/// Retries discord http operations that failed with transient errors (429, 5xx, timeouts)
+/// using exponential backoff. Serenity's ratelimiter already waits on rate-limit headers
+/// before sending requests, this handles whatever still gets through.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(unused)]
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    #[allow(unused)]
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Delay before retrying, after `attempt` (zero based) failed
+    pub fn delay_for_attempt(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt);
+        self.base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+
+    pub async fn run<T, F, Fut>(&self, op_name: &str, mut op: F) -> serenity::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = serenity::Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match op().await {
+                Ok(val) => return Ok(val),
+                Err(err) if attempt + 1 < self.max_attempts && is_retryable(&err) => {
+                    let delay = self.delay_for_attempt(attempt);
+                    log::warn!(
+                        "Discord operation '{op_name}' failed (attempt {}/{}), retrying in {}ms - {err}",
+                        attempt + 1,
+                        self.max_attempts,
+                        delay.as_millis()
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Whether error is transient, and operation is worth retrying
+pub fn is_retryable(err: &serenity::Error) -> bool {
+    match err {
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(response)) => {
+            let status = response.status_code.as_u16();
+            status == 429 || (500..600).contains(&status)
+        }
+        serenity::Error::Http(HttpError::Request(err)) => err.is_timeout() || err.is_connect(),
+        _ => false,
+    }
+}
+
+/// Run discord http operation with default retry policy
+pub async fn discord_retry<T, F, Fut>(op_name: &str, op: F) -> serenity::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = serenity::Result<T>>,
+{
+    RetryPolicy::new().run(op_name, op).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_for_attempt_test() {
+        let policy = RetryPolicy::new().base_delay(Duration::from_millis(100));
+
+        assert_eq!(Duration::from_millis(100), policy.delay_for_attempt(0));
+        assert_eq!(Duration::from_millis(200), policy.delay_for_attempt(1));
+        assert_eq!(Duration::from_millis(800), policy.delay_for_attempt(3));
+        assert_eq!(Duration::from_secs(5), policy.delay_for_attempt(40));
+    }
+
+    #[tokio::test]
+    async fn non_retryable_error_test() {
+        let mut attempts = 0;
+        let result: serenity::Result<()> = RetryPolicy::new()
+            .run("test", || {
+                attempts += 1;
+                async { Err(serenity::Error::Other("not retryable")) }
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+}
@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::path;
+
+use futures::StreamExt;
+use serenity::all::CreateEmbed;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+
+use crate::audio::{AudioFile, AudioFileInfo, AudioFileValidator};
+use crate::branding::Branding;
+use crate::collision::{self, NameCollision};
+use crate::common::LogResult;
+use crate::config::Config;
+use crate::db::{AudioTable, AudioTableRow, AudioTableRowInsert, Tags, UniqueAudioTableCol};
+use crate::errors::OperationError;
+use crate::helpers;
+use crate::{ingest, naming, operations};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScanProgress {
+    pub scanned: usize,
+    pub total: usize,
+}
+
+impl std::fmt::Display for ScanProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "scanned {}/{}", self.scanned, self.total)
+    }
+}
+
+/// Result of scanning a single audio file
+pub enum ScanOutcome {
+    /// File not in database
+    New {
+        audio_file: AudioFile,
+        info: AudioFileInfo,
+    },
+    /// File in database, but its contents changed since last scan
+    Changed {
+        row_id: i64,
+        audio_file: AudioFile,
+        info: AudioFileInfo,
+    },
+    /// File failed validation (codec, duration, etc)
+    Invalid {
+        audio_file: AudioFile,
+        reason: String,
+    },
+}
+
+/// Audio row whose file went missing, and was found again under a new file name (matched by hash)
+pub struct ScanRename {
+    pub row: AudioTableRow,
+    pub audio_file: AudioFile,
+    pub info: AudioFileInfo,
+}
+
+#[derive(Default)]
+pub struct ScanReport {
+    pub new: Vec<(AudioFile, AudioFileInfo)>,
+    pub changed: Vec<(i64, AudioFile, AudioFileInfo)>,
+    pub renamed: Vec<ScanRename>,
+    /// Audio rows whose files no longer exist in the audio directory
+    pub removed: Vec<AudioTableRow>,
+    pub unchanged: usize,
+    pub invalid: Vec<(AudioFile, String)>,
+}
+
+impl ScanReport {
+    fn push(&mut self, outcome: ScanOutcome) {
+        match outcome {
+            ScanOutcome::New { audio_file, info } => self.new.push((audio_file, info)),
+            ScanOutcome::Changed {
+                row_id,
+                audio_file,
+                info,
+            } => self.changed.push((row_id, audio_file, info)),
+            ScanOutcome::Invalid { audio_file, reason } => self.invalid.push((audio_file, reason)),
+        }
+    }
+
+    /// Pair new files with missing rows of the same hash. Those are renames, not additions & removals
+    fn match_renames(&mut self, missing_rows: Vec<AudioTableRow>) {
+        for row in missing_rows {
+            let position = row
+                .file_hash
+                .as_ref()
+                .and_then(|hash| self.new.iter().position(|(_, info)| &info.hash == hash));
+
+            match position {
+                Some(index) => {
+                    let (audio_file, info) = self.new.remove(index);
+                    self.renamed.push(ScanRename {
+                        row,
+                        audio_file,
+                        info,
+                    });
+                }
+                None => self.removed.push(row),
+            }
+        }
+    }
+
+    /// Write scan results to database. New files are named by the naming rules, and named like an
+    /// existing sound are suffixed when `name_collision` is suffix, failing to be written otherwise.
+    /// Returns number of failed writes
+    pub fn apply(
+        &self,
+        table: &AudioTable,
+        config: &Config,
+        name_collision: NameCollision,
+    ) -> usize {
+        let mut failed = 0;
+
+        for (audio_file, info) in &self.new {
+            let Ok(mut name) = naming::normalize(config, &audio_file.audio_title())
+                .log_err_msg(format!("Invalid name for {}", audio_file.file_name_lossy()))
+            else {
+                failed += 1;
+                continue;
+            };
+            let name_taken = table
+                .find_audio_row(UniqueAudioTableCol::Name(name.clone()))
+                .is_some();
+            if name_taken && name_collision == NameCollision::Suffix {
+                name = collision::suffixed_name(table, config, &name);
+            }
+            let new_audio = AudioTableRowInsert {
+                name,
+                tags: Tags::new(),
+                audio_file: audio_file.clone(),
+                created_at: chrono::Utc::now(),
+                author_id: None,
+                author_name: None,
+                author_global_name: None,
+                file_hash: Some(info.hash.clone()),
+                file_size: Some(info.stat.size),
+                file_modified_at: Some(info.stat.modified_at),
+            };
+
+            if table.insert_audio_row(new_audio).log_err().is_err() {
+                failed += 1;
+            }
+        }
+
+        let changed = self
+            .changed
+            .iter()
+            .map(|(row_id, audio_file, info)| (*row_id, audio_file, info));
+        let renamed = self
+            .renamed
+            .iter()
+            .map(|rename| (rename.row.id, &rename.audio_file, &rename.info));
+
+        for (row_id, audio_file, info) in changed.chain(renamed) {
+            if table
+                .update_file_info(row_id, audio_file, info)
+                .log_err()
+                .is_err()
+            {
+                failed += 1;
+            }
+        }
+
+        for row in &self.removed {
+            if table.delete_row(row.id).log_err().is_err() {
+                failed += 1;
+            }
+        }
+
+        failed
+    }
+
+    /// Summary of scan, in the color & footer of branding
+    pub fn summary_embed(&self, failed_writes: usize, branding: &Branding) -> CreateEmbed {
+        let added: Vec<_> = self
+            .new
+            .iter()
+            .map(|(audio_file, _)| audio_file.audio_title())
+            .collect();
+        let removed: Vec<_> = self.removed.iter().map(|row| row.name.clone()).collect();
+        let renamed: Vec<_> = self
+            .renamed
+            .iter()
+            .map(|rename| {
+                format!(
+                    "{}: `{}` → `{}`",
+                    rename.row.name,
+                    rename.row.audio_file.file_name_lossy(),
+                    rename.audio_file.file_name_lossy()
+                )
+            })
+            .collect();
+        let invalid: Vec<_> = self
+            .invalid
+            .iter()
+            .map(|(audio_file, reason)| format!("`{}`: {reason}", audio_file.file_name_lossy()))
+            .collect();
+
+        let embed = CreateEmbed::new()
+            .title("Scan Summary")
+            .description(format!(
+                "Updated: {}, Unchanged: {}",
+                self.changed.len(),
+                self.unchanged
+            ))
+            .field(
+                format!("Added ({})", added.len()),
+                helpers::embed_field_list(&added),
+                false,
+            )
+            .field(
+                format!("Removed ({})", removed.len()),
+                helpers::embed_field_list(&removed),
+                false,
+            )
+            .field(
+                format!("Renamed ({})", renamed.len()),
+                helpers::embed_field_list(&renamed),
+                false,
+            )
+            .field(
+                format!("Skipped/Invalid ({})", invalid.len()),
+                helpers::embed_field_list(&invalid),
+                false,
+            );
+
+        let failed = (failed_writes > 0)
+            .then(|| format!("{failed_writes} database writes failed. Check logs for details."));
+        branding.apply_with_footer(embed, failed)
+    }
+}
+
+/// Scans audio directory for new, changed, renamed, and removed audio files.
+/// Files whose size and modified time match the database are skipped without being read.
+/// Remaining files are hashed & probed concurrently on blocking tasks.
+pub struct AudioDirScanner {
+    validator: AudioFileValidator,
+    concurrency: usize,
+}
+
+impl Default for AudioDirScanner {
+    fn default() -> Self {
+        Self {
+            validator: AudioFileValidator::new(),
+            concurrency: 4,
+        }
+    }
+}
+
+impl AudioDirScanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_audio_duration(mut self, max_duration: std::time::Duration) -> Self {
+        self.validator = self.validator.max_audio_duration(max_duration);
+        self
+    }
+
+    pub fn max_file_size(mut self, max_size: u64) -> Self {
+        self.validator = self.validator.max_file_size(max_size);
+        self
+    }
+
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Errors once cancelled. Files being scanned then finish in the background, remaining files are skipped
+    pub async fn scan(
+        &self,
+        audio_files: impl IntoIterator<Item = AudioFile>,
+        known_rows: Vec<AudioTableRow>,
+        progress: watch::Sender<ScanProgress>,
+        cancel: &CancellationToken,
+    ) -> Result<ScanReport, OperationError> {
+        let mut known_rows: HashMap<path::PathBuf, AudioTableRow> = known_rows
+            .into_iter()
+            .map(|row| (row.audio_file.as_path_buf(), row))
+            .collect();
+
+        let mut report = ScanReport::default();
+        let mut candidates: Vec<(AudioFile, Option<i64>)> = vec![];
+
+        for audio_file in audio_files {
+            match known_rows.remove(audio_file.as_path()) {
+                Some(row) => {
+                    let unchanged = audio_file
+                        .stat()
+                        .is_ok_and(|stat| row.file_info_matches(&stat));
+
+                    if unchanged {
+                        report.unchanged += 1;
+                    } else {
+                        candidates.push((audio_file, Some(row.id)));
+                    }
+                }
+                None => candidates.push((audio_file, None)),
+            }
+        }
+
+        let mut scan_progress = ScanProgress {
+            scanned: report.unchanged,
+            total: report.unchanged + candidates.len(),
+        };
+        progress.send_replace(scan_progress.clone());
+
+        log::info!(
+            "Scan skipping {} unchanged audio files, scanning {} audio files",
+            report.unchanged,
+            candidates.len()
+        );
+
+        let mut outcomes = futures::stream::iter(candidates)
+            .map(|(audio_file, row_id)| {
+                // files added via discord commands have uuid names, and are only valid if already in database
+                let validator = self.validator.clone().reject_uuid_files(row_id.is_none());
+                tokio::task::spawn_blocking(move || scan_audio_file(audio_file, row_id, validator))
+            })
+            .buffer_unordered(self.concurrency);
+
+        while let Some(outcome) = outcomes.next().await {
+            if let Ok(outcome) = outcome.log_err_msg("Scan task failed") {
+                report.push(outcome);
+            }
+
+            scan_progress.scanned += 1;
+            progress.send_replace(scan_progress.clone());
+            operations::check(cancel)?;
+        }
+
+        // rows whose files weren't found in the audio directory
+        report.match_renames(known_rows.into_values().collect());
+
+        Ok(report)
+    }
+}
+
+fn scan_audio_file(
+    audio_file: AudioFile,
+    row_id: Option<i64>,
+    validator: AudioFileValidator,
+) -> ScanOutcome {
+    match (ingest::inspect(&validator, &audio_file), row_id) {
+        (Ok(info), Some(row_id)) => ScanOutcome::Changed {
+            row_id,
+            audio_file,
+            info,
+        },
+        (Ok(info), None) => ScanOutcome::New { audio_file, info },
+        (Err(err), _) => ScanOutcome::Invalid {
+            audio_file,
+            reason: err.to_string(),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioFileStat;
+    use crate::common::read_audio_dir;
+
+    fn make_audio_file_info(hash: &str) -> AudioFileInfo {
+        AudioFileInfo {
+            stat: AudioFileStat {
+                size: 1,
+                modified_at: chrono::Utc::now(),
+            },
+            hash: hash.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn scan_invalid_audio_file_test() {
+        let dir = std::env::temp_dir().join(crate::helpers::uuid_v4_str());
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::write(dir.join("not-audio.mp3"), b"not audio").unwrap();
+        std::fs::write(dir.join("ignored.txt"), b"ignored").unwrap();
+
+        let (progress_tx, progress_rx) = watch::channel(ScanProgress::default());
+        let report = AudioDirScanner::new()
+            .concurrency(2)
+            .scan(
+                read_audio_dir(&dir),
+                vec![],
+                progress_tx,
+                &CancellationToken::new(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(report.new.len(), 0);
+        assert_eq!(report.unchanged, 0);
+        assert_eq!(report.invalid.len(), 1);
+        assert_eq!(
+            *progress_rx.borrow(),
+            ScanProgress {
+                scanned: 1,
+                total: 1
+            }
+        );
+
+        let (progress_tx, _) = watch::channel(ScanProgress::default());
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let cancelled = AudioDirScanner::new()
+            .scan(read_audio_dir(&dir), vec![], progress_tx, &cancel)
+            .await;
+        assert!(cancelled.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn match_renames_test() {
+        let mut report = ScanReport::default();
+        report.new.push((
+            AudioFile::new(path::PathBuf::from("/tmp/renamed.mp3")),
+            make_audio_file_info("aaa"),
+        ));
+        report.new.push((
+            AudioFile::new(path::PathBuf::from("/tmp/added.mp3")),
+            make_audio_file_info("bbb"),
+        ));
+
+        report.match_renames(vec![
+            AudioTableRow::test_row(1)
+                .with_audio_file("/tmp/original.mp3")
+                .with_file_hash("aaa"),
+            AudioTableRow::test_row(2)
+                .with_audio_file("/tmp/deleted.mp3")
+                .with_file_hash("ccc"),
+            AudioTableRow::test_row(3).with_audio_file("/tmp/legacy.mp3"),
+        ]);
+
+        assert_eq!(report.new.len(), 1);
+        assert_eq!(report.renamed.len(), 1);
+        assert_eq!(report.renamed[0].row.id, 1);
+        assert_eq!(report.removed.len(), 2);
+    }
+}
@@ -0,0 +1,153 @@
+use serenity::all::{
+    ActionRowComponent, CreateActionRow, CreateButton, CreateInputText, CreateModal,
+    InputTextStyle, ModalInteraction, ReactionType,
+};
+
+use crate::db::AudioTableRow;
+use crate::helpers::{self, ButtonCustomId};
+use crate::vars;
+
+/// Most sounds a search returns
+pub const SEARCH_RESULTS_LIMIT: u64 = 100;
+
+/// Searches with up to this many results are listed as text, more are shown as a button board
+pub const SEARCH_TEXT_RESULTS_LIMIT: usize = 5;
+
+/// Sound buttons per board page. 4 rows of 5, the last action row is for page navigation
+pub const BOARD_PAGE_SIZE: usize = 20;
+
+/// Custom id of search modal opened from soundboard search buttons
+pub const SEARCH_MODAL_ID: &str = "search_modal";
+
+/// Button on soundboard messages that opens search modal
+pub fn search_button() -> CreateButton {
+    CreateButton::new(ButtonCustomId::SearchModal)
+        .label("Search")
+        .emoji(ReactionType::Unicode("🔍".into()))
+}
+
+pub fn search_modal() -> CreateModal {
+    CreateModal::new(SEARCH_MODAL_ID, "Search Sounds").components(vec![CreateActionRow::InputText(
+        CreateInputText::new(InputTextStyle::Short, "Name or Tag", "query")
+            .min_length(3) // Trigram search needs at least 3 chars to match
+            .max_length(80), // Query is stored in page button custom ids (max 100 chars)
+    )])
+}
+
+/// Query entered in a submitted search modal
+pub fn modal_query(modal: &ModalInteraction) -> Option<String> {
+    modal
+        .data
+        .components
+        .iter()
+        .flat_map(|row| &row.components)
+        .find_map(|component| match component {
+            ActionRowComponent::InputText(input) if input.custom_id == "query" => {
+                input.value.clone()
+            }
+            _ => None,
+        })
+        .map(|query| query.trim().to_string())
+        .filter(|query| !query.is_empty())
+}
+
+/// Message content and components for a page of search results board
+pub fn board_page(
+    rows: &[AudioTableRow],
+    query: &str,
+    page: usize,
+) -> (String, Vec<CreateActionRow>) {
+    let pages = rows.len().div_ceil(BOARD_PAGE_SIZE).max(1);
+    let page = page.min(pages - 1);
+
+    let mut content = format!(
+        "Found {} sounds matching `{query}` - page {}/{pages}",
+        rows.len(),
+        page + 1
+    );
+
+    let page_rows = rows.chunks(BOARD_PAGE_SIZE).nth(page).unwrap_or_default();
+    let mut labels = helpers::BoardLabels::default();
+    let mut components: Vec<_> = page_rows
+        .chunks(5)
+        .map(|rows| helpers::make_action_row(rows, &mut labels))
+        .collect();
+
+    // full names of sounds too long for their buttons, as many as fit in the message
+    let mut shortened = page_rows
+        .iter()
+        .filter(|row| row.name.chars().count() > vars::BTN_LABEL_MAX_LEN)
+        .peekable();
+    if shortened.peek().is_some() {
+        content.push_str("\nShortened names:");
+    }
+    for row in shortened {
+        let line = format!("\n- `{}`", row.name);
+        if content.chars().count() + line.chars().count() > vars::MESSAGE_CONTENT_MAX_LEN {
+            break;
+        }
+        content.push_str(&line);
+    }
+
+    if pages > 1 {
+        components.push(CreateActionRow::Buttons(vec![
+            CreateButton::new(ButtonCustomId::SearchPage(
+                page.saturating_sub(1),
+                query.to_string(),
+            ))
+            .label("Previous")
+            .disabled(page == 0),
+            CreateButton::new(ButtonCustomId::SearchPage(page + 1, query.to_string()))
+                .label("Next")
+                .disabled(page + 1 == pages),
+        ]));
+    }
+
+    (content, components)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn board_page_test() {
+        let rows: Vec<_> = (0..23).map(AudioTableRow::test_row).collect();
+
+        let (content, components) = board_page(&rows, "sound", 0);
+        assert_eq!(content, "Found 23 sounds matching `sound` - page 1/2");
+        assert_eq!(components.len(), 5);
+
+        // sound buttons row + navigation row
+        let (content, components) = board_page(&rows, "sound", 1);
+        assert_eq!(content, "Found 23 sounds matching `sound` - page 2/2");
+        assert_eq!(
+            helpers::button_labels(&components),
+            vec![
+                vec!["Sound 20", "Sound 21", "Sound 22"],
+                vec!["Previous", "Next"]
+            ]
+        );
+
+        // out of range pages show last page
+        let (content, _) = board_page(&rows, "sound", 9);
+        assert!(content.ends_with("page 2/2"));
+
+        // single page has no navigation row
+        let (_, components) = board_page(&rows[0..10], "sound", 0);
+        let labels = helpers::button_labels(&components);
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels[1][4], "Sound 9");
+
+        // names cut short on buttons are listed in full
+        let long_row = AudioTableRow::test_row(30).with_name("a".repeat(90));
+        let (content, _) = board_page(&[long_row], "aaa", 0);
+        assert_eq!(
+            content,
+            format!(
+                "Found 1 sounds matching `aaa` - page 1/1\nShortened names:\n- `{}`",
+                "a".repeat(90)
+            )
+        );
+    }
+}
@@ -0,0 +1,291 @@
+use std::path::{Path, PathBuf};
+
+use serenity::all::Http;
+
+use crate::config::Config;
+use crate::db::DbPool;
+use crate::helpers;
+
+/// How bad a failed check is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CheckStatus {
+    Ok,
+    /// Bot runs, but some features won't work
+    Warning,
+    /// Bot would fail later on, so it isn't started
+    Fatal,
+}
+
+impl CheckStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warning => "WARN",
+            Self::Fatal => "FAIL",
+        }
+    }
+}
+
+/// Outcome of a startup check
+#[derive(Debug, Clone, PartialEq)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Run checks of the environment the bot needs before it connects to discord. Fatal problems
+/// are ones the bot would otherwise only hit later, ie: when the first sound is added
+pub async fn run(config: &Config, db_pool: &DbPool) -> Vec<Check> {
+    let mut checks = vec![
+        check_audio_dir(&config.audio_dir),
+        check_database(db_pool),
+        check_executable("ffmpeg", &config.ffmpeg_path, CheckStatus::Warning),
+    ];
+
+    if let Some(command) = &config.moderation_command {
+        checks.push(check_executable(
+            "moderation command",
+            command,
+            CheckStatus::Fatal,
+        ));
+    }
+    if let Some(model_path) = &config.whisper_model_path {
+        checks.push(check_file("whisper model", model_path));
+    }
+    if let Some(packs_dir) = &config.packs_dir {
+        checks.push(check_dir("packs dir", packs_dir));
+    }
+    checks.push(check_token(&config.token, config.application_id).await);
+
+    checks
+}
+
+/// Whether any check failed fatally
+pub fn has_fatal(checks: &[Check]) -> bool {
+    checks
+        .iter()
+        .any(|check| check.status == CheckStatus::Fatal)
+}
+
+/// Checks as a table, one row per check
+pub fn render(checks: &[Check]) -> String {
+    let width = checks
+        .iter()
+        .map(|check| check.name.len())
+        .max()
+        .unwrap_or(0);
+
+    checks
+        .iter()
+        .map(|check| {
+            format!(
+                "[{:>4}] {:<width$}  {}",
+                check.status.as_str(),
+                check.name,
+                check.detail
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Audio dir exists and sounds can be written to it
+fn check_audio_dir(path: &Path) -> Check {
+    let name = "audio dir";
+    if !path.is_dir() {
+        return Check::new(
+            name,
+            CheckStatus::Fatal,
+            format!("{} doesn't exist, or isn't a directory", path.display()),
+        );
+    }
+
+    let probe = path.join(format!(".selfcheck-{}", helpers::uuid_v4_str()));
+    match std::fs::write(&probe, b"").and_then(|_| std::fs::remove_file(&probe)) {
+        Ok(()) => Check::new(name, CheckStatus::Ok, path.display().to_string()),
+        Err(err) => Check::new(
+            name,
+            CheckStatus::Fatal,
+            format!("{} isn't writable - {err}", path.display()),
+        ),
+    }
+}
+
+/// Database can be connected to and written
+fn check_database(db_pool: &DbPool) -> Check {
+    let name = "database";
+    let conn = match db_pool.get() {
+        Ok(conn) => conn,
+        Err(err) => return Check::new(name, CheckStatus::Fatal, format!("Can't connect - {err}")),
+    };
+
+    // an immediate transaction takes the write lock, which fails on a read only database
+    match conn.execute_batch("BEGIN IMMEDIATE; ROLLBACK;") {
+        Ok(())
+            if !conn
+                .is_readonly(rusqlite::DatabaseName::Main)
+                .unwrap_or(true) =>
+        {
+            Check::new(name, CheckStatus::Ok, "writable")
+        }
+        Ok(()) => Check::new(name, CheckStatus::Fatal, "Database is read only"),
+        Err(err) => Check::new(name, CheckStatus::Fatal, format!("Can't write - {err}")),
+    }
+}
+
+/// Path of program, looked up on the PATH when it's a bare name, ie: `ffmpeg`
+fn find_program(path: &Path) -> Option<PathBuf> {
+    if path.components().count() > 1 {
+        return path.is_file().then(|| path.to_path_buf());
+    }
+
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(path))
+            .find(|path| path.is_file())
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+/// Program can be run, ie: it's on the PATH and executable. Programs aren't run, since some
+/// act on their input
+fn check_executable(name: &'static str, path: &Path, missing: CheckStatus) -> Check {
+    match find_program(path) {
+        Some(found) if is_executable(&found) => {
+            Check::new(name, CheckStatus::Ok, found.display().to_string())
+        }
+        Some(found) => Check::new(
+            name,
+            missing,
+            format!("{} isn't executable", found.display()),
+        ),
+        None => Check::new(name, missing, format!("{} not found", path.display())),
+    }
+}
+
+fn check_file(name: &'static str, path: &Path) -> Check {
+    match path.is_file() {
+        true => Check::new(name, CheckStatus::Ok, path.display().to_string()),
+        false => Check::new(
+            name,
+            CheckStatus::Warning,
+            format!("{} doesn't exist", path.display()),
+        ),
+    }
+}
+
+fn check_dir(name: &'static str, path: &Path) -> Check {
+    match path.is_dir() {
+        true => Check::new(name, CheckStatus::Ok, path.display().to_string()),
+        false => Check::new(
+            name,
+            CheckStatus::Warning,
+            format!("{} doesn't exist, or isn't a directory", path.display()),
+        ),
+    }
+}
+
+/// Token is valid, and belongs to the configured application. Discord being unreachable is only
+/// a warning, since the bot retries connecting
+async fn check_token(token: &str, application_id: u64) -> Check {
+    let name = "token";
+    match Http::new(token).get_current_application_info().await {
+        Ok(info) if info.id.get() == application_id => Check::new(
+            name,
+            CheckStatus::Ok,
+            format!("application {application_id}"),
+        ),
+        Ok(info) => Check::new(
+            name,
+            CheckStatus::Fatal,
+            format!(
+                "Token is for application {}, but DISCORD_BOT_APPLICATION_ID is {application_id}",
+                info.id
+            ),
+        ),
+        Err(serenity::Error::Http(serenity::all::HttpError::UnsuccessfulRequest(response)))
+            if response.status_code.as_u16() == 401 =>
+        {
+            Check::new(name, CheckStatus::Fatal, "Token was rejected by discord")
+        }
+        Err(err) => Check::new(
+            name,
+            CheckStatus::Warning,
+            format!("Couldn't reach discord to check token - {err}"),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+
+    #[test]
+    fn selfcheck_test() {
+        let dir = std::env::temp_dir().join(helpers::uuid_v4_str());
+        std::fs::create_dir(&dir).unwrap();
+
+        // probe file is cleaned up
+        assert_eq!(check_audio_dir(&dir).status, CheckStatus::Ok);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        assert_eq!(
+            check_audio_dir(&dir.join("missing")).status,
+            CheckStatus::Fatal
+        );
+
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        assert_eq!(check_database(&db_pool).status, CheckStatus::Ok);
+
+        let missing = check_executable("ffmpeg", &dir.join("no-ffmpeg"), CheckStatus::Warning);
+        assert_eq!(missing.status, CheckStatus::Warning);
+        let script = dir.join("moderate.sh");
+        std::fs::write(&script, "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        assert_eq!(
+            check_executable("moderation command", &script, CheckStatus::Fatal).status,
+            CheckStatus::Fatal
+        );
+        assert_eq!(
+            check_executable("sh", Path::new("sh"), CheckStatus::Fatal).status,
+            CheckStatus::Ok
+        );
+        assert_eq!(check_file("model", &dir).status, CheckStatus::Warning);
+        assert_eq!(check_file("model", &script).status, CheckStatus::Ok);
+        assert_eq!(check_dir("packs", &dir).status, CheckStatus::Ok);
+
+        let checks = vec![
+            Check::new("audio dir", CheckStatus::Ok, "/sounds"),
+            Check::new("ffmpeg", CheckStatus::Warning, "Can't run ffmpeg"),
+        ];
+        assert!(!has_fatal(&checks));
+        assert_eq!(
+            render(&checks),
+            "[  ok] audio dir  /sounds\n[WARN] ffmpeg     Can't run ffmpeg"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
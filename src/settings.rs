@@ -0,0 +1,837 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{ChannelId, GuildId, RoleId};
+
+use crate::collision::NameCollision;
+use crate::common::LogResult;
+use crate::db::SettingsTable;
+use crate::event_mode;
+use crate::helpers;
+use crate::quiet_hours::{self, QuietHours};
+
+/// Per-guild settings, stored as key/value pairs in the guild settings table
+#[derive(Debug, Clone, Copy, PartialEq, poise::ChoiceParameter)]
+pub enum GuildSettingKey {
+    #[name = "volume"]
+    Volume,
+    #[name = "idle_timeout"]
+    IdleTimeout,
+    #[name = "ephemeral_replies"]
+    EphemeralReplies,
+    #[name = "audit_channel"]
+    AuditChannel,
+    #[name = "locale"]
+    Locale,
+    #[name = "user_role"]
+    UserRole,
+    #[name = "moderator_role"]
+    ModeratorRole,
+    #[name = "daily_play_quota"]
+    DailyPlayQuota,
+    #[name = "channel_hourly_cap"]
+    ChannelHourlyCap,
+    #[name = "request_channel"]
+    RequestChannel,
+    #[name = "overlap_playback"]
+    OverlapPlayback,
+    #[name = "max_overlapping_tracks"]
+    MaxOverlappingTracks,
+    #[name = "max_queue_length"]
+    MaxQueueLength,
+    #[name = "max_queue_duration"]
+    MaxQueueDuration,
+    #[name = "priority_role"]
+    PriorityRole,
+    #[name = "timezone"]
+    Timezone,
+    #[name = "quiet_hours"]
+    QuietHours,
+    #[name = "quiet_hours_volume"]
+    QuietHoursVolume,
+    #[name = "message_retention"]
+    MessageRetention,
+    #[name = "message_retention_count"]
+    MessageRetentionCount,
+    #[name = "play_history_retention"]
+    PlayHistoryRetention,
+    #[name = "play_feedback"]
+    PlayFeedback,
+    #[name = "name_collision"]
+    NameCollision,
+    #[name = "now_playing_channel"]
+    NowPlayingChannel,
+    #[name = "voice_commands"]
+    VoiceCommands,
+    #[name = "speech_pause"]
+    SpeechPause,
+    #[name = "crossfade"]
+    Crossfade,
+}
+
+impl GuildSettingKey {
+    pub const ALL: [GuildSettingKey; 27] = [
+        Self::Volume,
+        Self::IdleTimeout,
+        Self::EphemeralReplies,
+        Self::AuditChannel,
+        Self::Locale,
+        Self::UserRole,
+        Self::ModeratorRole,
+        Self::DailyPlayQuota,
+        Self::ChannelHourlyCap,
+        Self::RequestChannel,
+        Self::OverlapPlayback,
+        Self::MaxOverlappingTracks,
+        Self::MaxQueueLength,
+        Self::MaxQueueDuration,
+        Self::PriorityRole,
+        Self::Timezone,
+        Self::QuietHours,
+        Self::QuietHoursVolume,
+        Self::MessageRetention,
+        Self::MessageRetentionCount,
+        Self::PlayHistoryRetention,
+        Self::PlayFeedback,
+        Self::NameCollision,
+        Self::NowPlayingChannel,
+        Self::VoiceCommands,
+        Self::SpeechPause,
+        Self::Crossfade,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Volume => "volume",
+            Self::IdleTimeout => "idle_timeout",
+            Self::EphemeralReplies => "ephemeral_replies",
+            Self::AuditChannel => "audit_channel",
+            Self::Locale => "locale",
+            Self::UserRole => "user_role",
+            Self::ModeratorRole => "moderator_role",
+            Self::DailyPlayQuota => "daily_play_quota",
+            Self::ChannelHourlyCap => "channel_hourly_cap",
+            Self::RequestChannel => "request_channel",
+            Self::OverlapPlayback => "overlap_playback",
+            Self::MaxOverlappingTracks => "max_overlapping_tracks",
+            Self::MaxQueueLength => "max_queue_length",
+            Self::MaxQueueDuration => "max_queue_duration",
+            Self::PriorityRole => "priority_role",
+            Self::Timezone => "timezone",
+            Self::QuietHours => "quiet_hours",
+            Self::QuietHoursVolume => "quiet_hours_volume",
+            Self::MessageRetention => "message_retention",
+            Self::MessageRetentionCount => "message_retention_count",
+            Self::PlayHistoryRetention => "play_history_retention",
+            Self::PlayFeedback => "play_feedback",
+            Self::NameCollision => "name_collision",
+            Self::NowPlayingChannel => "now_playing_channel",
+            Self::VoiceCommands => "voice_commands",
+            Self::SpeechPause => "speech_pause",
+            Self::Crossfade => "crossfade",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|setting| setting.key() == key)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::Volume => "Playback volume percentage, 0-200",
+            Self::IdleTimeout => "Seconds without playback before bot leaves voice. 0 disables",
+            Self::EphemeralReplies => "Only show command replies to whoever ran the command",
+            Self::AuditChannel => "Channel for audit messages, overrides bot wide audit channel",
+            Self::Locale => "Guild locale, ie: en-US",
+            Self::UserRole => "Role required to play sounds. Anyone can play when not set",
+            Self::ModeratorRole => {
+                "Role allowed to manage sounds & bans. Anyone can manage sounds when not set"
+            }
+            Self::DailyPlayQuota => "Sounds a member can play per 24 hours. 0 is unlimited",
+            Self::ChannelHourlyCap => {
+                "Sounds everyone can play together per channel per hour. Moderators are exempt. 0 is unlimited"
+            }
+            Self::RequestChannel => {
+                "Channel moderators review sound requests in. Defaults to the audit channel"
+            }
+            Self::OverlapPlayback => "Play sounds over each other instead of queueing them",
+            Self::MaxOverlappingTracks => {
+                "Most sounds playing at once with overlap playback. Extra sounds are queued"
+            }
+            Self::MaxQueueLength => {
+                "Most sounds waiting in the queue, more are refused. Admins are exempt, and event mode lifts it. 0 is unlimited"
+            }
+            Self::MaxQueueDuration => {
+                "Most seconds of sounds waiting in the queue, more are refused. Admins are exempt, and event mode lifts it. 0 is unlimited"
+            }
+            Self::PriorityRole => "Role whose sounds are queued ahead of everyone else's",
+            Self::Timezone => "UTC offset quiet hours are in, ie: +02:00",
+            Self::QuietHours => {
+                "Daily time range sounds can't be played in, ie: 22:00-07:00. Moderators are exempt"
+            }
+            Self::QuietHoursVolume => {
+                "Volume percentage sounds are capped to in quiet hours, instead of being refused"
+            }
+            Self::MessageRetention => {
+                "Hours soundboards & play messages are kept before they're cleaned up. 0 keeps them"
+            }
+            Self::MessageRetentionCount => {
+                "Most soundboard & play messages kept per channel, older ones are cleaned up. 0 is unlimited"
+            }
+            Self::PlayHistoryRetention => {
+                "Days individual plays are kept before they're rolled up into daily play counts. 0 keeps them"
+            }
+            Self::PlayFeedback => {
+                "How sound button presses are acknowledged: none, ephemeral (a short lived message) or reaction"
+            }
+            Self::NameCollision => {
+                "What happens when a new sound is named like an existing one: reject, suffix or overwrite (asks first)"
+            }
+            Self::NowPlayingChannel => {
+                "Channel with a single message showing what's playing, edited as sounds start & end"
+            }
+            Self::SpeechPause => {
+                "Pause ambience sounds while members talk, resuming after this many seconds of silence. 0 disables"
+            }
+            Self::Crossfade => {
+                "Milliseconds queued sounds fade into each other over, instead of playing back to back. 0 disables"
+            }
+            Self::VoiceCommands => {
+                "Listen in voice for \"hey soundboard, play <sound>\". Experimental, needs a whisper model"
+            }
+        }
+    }
+
+    /// Value used when setting isn't stored for guild, see [`GuildSettings::default`]
+    pub fn default_value(&self) -> Option<String> {
+        GuildSettings::default().value(*self)
+    }
+
+    /// Validate value entered by user, and normalize it to its stored form
+    pub fn parse(&self, value: &str) -> Result<String, String> {
+        let value = value.trim();
+
+        match self {
+            Self::Volume | Self::QuietHoursVolume => {
+                match value.trim_end_matches('%').parse::<u32>() {
+                    Ok(volume) if volume <= 200 => Ok(volume.to_string()),
+                    _ => Err("Volume must be a percentage between 0 and 200".into()),
+                }
+            }
+            Self::Timezone => quiet_hours::parse_utc_offset(value)
+                .map(quiet_hours::format_utc_offset)
+                .ok_or("Timezone must be a UTC offset, ie: +02:00 or UTC-5".into()),
+            Self::QuietHours => QuietHours::parse(value)
+                .map(|quiet_hours| quiet_hours.to_string())
+                .ok_or("Quiet hours must be a time range, ie: 22:00-07:00".into()),
+            Self::IdleTimeout => match value.parse::<u64>() {
+                Ok(secs) if secs <= 24 * 60 * 60 => Ok(secs.to_string()),
+                _ => Err("Idle timeout must be a number of seconds, at most 86400".into()),
+            },
+            Self::EphemeralReplies => {
+                parse_bool(value).ok_or("Ephemeral replies must be true or false".into())
+            }
+            Self::OverlapPlayback => {
+                parse_bool(value).ok_or("Overlap playback must be true or false".into())
+            }
+            Self::SpeechPause => match value.parse::<u64>() {
+                Ok(secs) if secs <= 300 => Ok(secs.to_string()),
+                _ => Err("Speech pause must be a number of seconds, at most 300".into()),
+            },
+            Self::Crossfade => match value.trim_end_matches("ms").parse::<u64>() {
+                Ok(millis) if millis <= 10_000 => Ok(millis.to_string()),
+                _ => Err("Crossfade must be a number of milliseconds, at most 10000".into()),
+            },
+            Self::VoiceCommands => {
+                parse_bool(value).ok_or("Voice commands must be true or false".into())
+            }
+            Self::MaxOverlappingTracks => match value.parse::<u32>() {
+                Ok(max) if (2..=10).contains(&max) => Ok(max.to_string()),
+                _ => Err("Max overlapping tracks must be a number between 2 and 10".into()),
+            },
+            Self::MaxQueueLength => value
+                .parse::<u32>()
+                .map(|max| max.to_string())
+                .map_err(|_| "Max queue length must be a number. 0 is unlimited".into()),
+            Self::MaxQueueDuration => match helpers::parse_timestamp(value) {
+                Some(max) if max.as_secs() <= 24 * 60 * 60 => Ok(max.as_secs().to_string()),
+                _ => Err(
+                    "Max queue duration must be seconds or m:ss, at most 24 hours. 0 is unlimited"
+                        .into(),
+                ),
+            },
+            Self::AuditChannel | Self::RequestChannel | Self::NowPlayingChannel => value
+                .trim_start_matches("<#")
+                .trim_end_matches('>')
+                .parse::<std::num::NonZeroU64>()
+                .map(|id| id.to_string())
+                .map_err(|_| "Channel must be a channel mention or id".into()),
+            Self::UserRole | Self::ModeratorRole | Self::PriorityRole => value
+                .trim_start_matches("<@&")
+                .trim_end_matches('>')
+                .parse::<std::num::NonZeroU64>()
+                .map(|id| id.to_string())
+                .map_err(|_| "Role must be a role mention or id".into()),
+            Self::MessageRetention => match value.parse::<u32>() {
+                Ok(hours) if hours <= 24 * 365 => Ok(hours.to_string()),
+                _ => Err("Message retention must be a number of hours, at most 8760".into()),
+            },
+            Self::PlayHistoryRetention => match value.parse::<u32>() {
+                Ok(days) if days == 0 || (7..=3650).contains(&days) => Ok(days.to_string()),
+                _ => Err(
+                    "Play history retention must be a number of days, 7 to 3650. 0 keeps plays"
+                        .into(),
+                ),
+            },
+            Self::MessageRetentionCount => value
+                .parse::<u32>()
+                .map(|count| count.to_string())
+                .map_err(|_| "Message retention count must be a number. 0 is unlimited".into()),
+            Self::PlayFeedback => PlayFeedback::parse(value)
+                .map(|feedback| feedback.as_str().to_string())
+                .ok_or("Play feedback must be none, ephemeral or reaction".into()),
+            Self::NameCollision => NameCollision::parse(value)
+                .map(|policy| policy.as_str().to_string())
+                .ok_or("Name collision must be reject, suffix or overwrite".into()),
+            Self::DailyPlayQuota => value
+                .parse::<u32>()
+                .map(|quota| quota.to_string())
+                .map_err(|_| "Daily play quota must be a number. 0 is unlimited".into()),
+            Self::ChannelHourlyCap => value
+                .parse::<u32>()
+                .map(|cap| cap.to_string())
+                .map_err(|_| "Channel hourly cap must be a number. 0 is unlimited".into()),
+            Self::Locale => {
+                let re = regex::Regex::new(r"^[a-zA-Z]{2}(-[a-zA-Z]{2})?$").unwrap();
+                match re.is_match(value) {
+                    true => Ok(match value.split_once('-') {
+                        Some((lang, region)) => {
+                            format!("{}-{}", lang.to_lowercase(), region.to_uppercase())
+                        }
+                        None => value.to_lowercase(),
+                    }),
+                    false => Err("Locale must look like en or en-US".into()),
+                }
+            }
+        }
+    }
+}
+
+/// How sound button presses are acknowledged to whoever pressed them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayFeedback {
+    /// Nothing visible, the sound playing is feedback enough. Refused sounds are still explained
+    #[default]
+    None,
+    /// Short lived message only the presser sees, ie: `▶ airhorn`
+    Ephemeral,
+    /// Emoji reaction on the soundboard message
+    Reaction,
+}
+
+impl PlayFeedback {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Ephemeral => "ephemeral",
+            Self::Reaction => "reaction",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "none" | "off" => Some(Self::None),
+            "ephemeral" | "message" => Some(Self::Ephemeral),
+            "reaction" | "react" => Some(Self::Reaction),
+            _ => None,
+        }
+    }
+}
+
+/// Normalize yes/no style value to `true` or `false`
+fn parse_bool(value: &str) -> Option<String> {
+    match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" => Some("true".into()),
+        "false" | "no" | "off" => Some("false".into()),
+        _ => None,
+    }
+}
+
+impl std::fmt::Display for GuildSettingKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.key())
+    }
+}
+
+/// Typed settings of a guild, with their defaults. Stored values are validated & normalized by
+/// [`GuildSettingKey::parse`] before they're deserialized, so settings that aren't stored, or are
+/// no longer valid, fall back to their default instead of failing the whole guild's settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildSettings {
+    /// Percentage, 0-200
+    pub volume: u32,
+    /// Seconds, 0 disables
+    pub idle_timeout: u64,
+    pub ephemeral_replies: bool,
+    pub audit_channel: Option<ChannelId>,
+    pub locale: String,
+    pub user_role: Option<RoleId>,
+    pub moderator_role: Option<RoleId>,
+    /// 0 is unlimited
+    pub daily_play_quota: u32,
+    /// 0 is unlimited
+    pub channel_hourly_cap: u32,
+    pub request_channel: Option<ChannelId>,
+    pub overlap_playback: bool,
+    pub max_overlapping_tracks: usize,
+    /// 0 is unlimited
+    pub max_queue_length: u32,
+    /// Seconds, 0 is unlimited
+    pub max_queue_duration: u64,
+    pub priority_role: Option<RoleId>,
+    #[serde(with = "utc_offset")]
+    pub timezone: chrono::FixedOffset,
+    pub quiet_hours: Option<QuietHours>,
+    /// Percentage, None refuses sounds in quiet hours
+    pub quiet_hours_volume: Option<u32>,
+    /// Hours, 0 keeps messages
+    pub message_retention: u32,
+    /// 0 is unlimited
+    pub message_retention_count: u32,
+    /// Days, 0 keeps plays
+    pub play_history_retention: u32,
+    pub play_feedback: PlayFeedback,
+    pub name_collision: NameCollision,
+    pub now_playing_channel: Option<ChannelId>,
+    pub voice_commands: bool,
+    /// Seconds, 0 disables
+    pub speech_pause: u64,
+    /// Milliseconds, 0 disables
+    pub crossfade: u64,
+    /// Whether event mode is on, lifting play quotas & queue limits. Set by `/eventmode`, not a
+    /// setting of its own
+    #[serde(skip)]
+    pub event_mode: bool,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        Self {
+            volume: 100,
+            idle_timeout: 0,
+            ephemeral_replies: false,
+            audit_channel: None,
+            locale: "en-US".into(),
+            user_role: None,
+            moderator_role: None,
+            daily_play_quota: 0,
+            channel_hourly_cap: 0,
+            request_channel: None,
+            overlap_playback: false,
+            max_overlapping_tracks: 4,
+            max_queue_length: 0,
+            max_queue_duration: 0,
+            priority_role: None,
+            timezone: chrono::FixedOffset::east_opt(0).unwrap(),
+            quiet_hours: None,
+            quiet_hours_volume: None,
+            message_retention: 0,
+            message_retention_count: 0,
+            play_history_retention: 0,
+            play_feedback: PlayFeedback::default(),
+            name_collision: NameCollision::default(),
+            now_playing_channel: None,
+            voice_commands: false,
+            speech_pause: 0,
+            crossfade: 0,
+            event_mode: false,
+        }
+    }
+}
+
+impl GuildSettings {
+    /// Settings of guild, as stored in the guild settings table
+    pub fn load(table: &SettingsTable, guild_id: GuildId) -> Self {
+        let stored = table
+            .list_guild_settings(guild_id.get())
+            .log_err_msg(format!("Failed reading guild settings of {guild_id}"))
+            .unwrap_or_default();
+
+        Self::from_stored(guild_id, stored)
+    }
+
+    /// Settings from stored key/value pairs. Keys of other features sharing the table are ignored,
+    /// besides when event mode ends
+    pub fn from_stored(
+        guild_id: GuildId,
+        stored: impl IntoIterator<Item = (String, String)>,
+    ) -> Self {
+        let mut event_mode = false;
+        let values: serde_json::Map<String, serde_json::Value> = stored
+            .into_iter()
+            .filter_map(|(key, value)| {
+                if key == event_mode::EVENT_MODE_KEY {
+                    event_mode = event_mode::is_stored_active(guild_id, &value);
+                    return None;
+                }
+                let setting = GuildSettingKey::from_key(&key)?;
+                let value = setting
+                    .parse(&value)
+                    .log_err_msg(format!("Invalid stored guild setting {key} for {guild_id}"))
+                    .ok()?;
+                Some((key, stored_json(value)))
+            })
+            .collect();
+
+        let settings: Self = serde_json::from_value(serde_json::Value::Object(values))
+            .log_err_msg(format!("Failed reading guild settings of {guild_id}"))
+            .unwrap_or_default();
+        Self {
+            event_mode,
+            ..settings
+        }
+    }
+
+    /// Setting in its stored form, ie: `100` for volume. None when it's not set & has no default
+    pub fn value(&self, key: GuildSettingKey) -> Option<String> {
+        match serde_json::to_value(self).ok()?.get(key.key())? {
+            serde_json::Value::Null => None,
+            serde_json::Value::String(value) => Some(value.clone()),
+            value => Some(value.to_string()),
+        }
+    }
+
+    /// Playback volume, where 1.0 is unchanged volume
+    pub fn volume_scale(&self) -> f32 {
+        self.volume as f32 / 100.0
+    }
+
+    /// Plays allowed per member per 24 hours. None when unlimited, or lifted by event mode
+    pub fn daily_play_quota(&self) -> Option<u32> {
+        Some(self.daily_play_quota).filter(|quota| *quota > 0 && !self.event_mode)
+    }
+
+    /// Plays allowed per channel per hour, by everyone together. None when unlimited, or lifted
+    /// by event mode
+    pub fn channel_hourly_cap(&self) -> Option<u32> {
+        Some(self.channel_hourly_cap).filter(|cap| *cap > 0 && !self.event_mode)
+    }
+
+    /// Most tracks playing at once with overlap playback. Raised to the max during event mode
+    pub fn max_overlapping_tracks(&self) -> usize {
+        match self.event_mode {
+            true => event_mode::EVENT_MAX_OVERLAPPING_TRACKS,
+            false => self.max_overlapping_tracks,
+        }
+    }
+
+    /// Most tracks waiting in the queue. None when unlimited, or lifted by event mode
+    pub fn max_queue_length(&self) -> Option<usize> {
+        Some(self.max_queue_length)
+            .filter(|max| *max > 0 && !self.event_mode)
+            .map(|max| max as usize)
+    }
+
+    /// Most play time waiting in the queue, summed over queued tracks. None when unlimited, or
+    /// lifted by event mode
+    pub fn max_queue_duration(&self) -> Option<Duration> {
+        Some(self.max_queue_duration)
+            .filter(|secs| *secs > 0 && !self.event_mode)
+            .map(Duration::from_secs)
+    }
+
+    /// Volume sounds are capped to in quiet hours, where 1.0 is unchanged volume.
+    /// None when sounds are refused in quiet hours
+    pub fn quiet_hours_volume_scale(&self) -> Option<f32> {
+        self.quiet_hours_volume
+            .map(|percent| percent as f32 / 100.0)
+    }
+
+    /// How long bot messages are kept before they're cleaned up. None when they're kept forever
+    pub fn message_retention(&self) -> Option<chrono::Duration> {
+        Some(self.message_retention)
+            .filter(|hours| *hours > 0)
+            .map(|hours| chrono::Duration::hours(hours.into()))
+    }
+
+    /// Most bot messages kept per channel. None when unlimited
+    pub fn message_retention_count(&self) -> Option<u32> {
+        Some(self.message_retention_count).filter(|count| *count > 0)
+    }
+}
+
+/// Settings are stored as text. Numbers & booleans are read as such, anything else as a string
+fn stored_json(value: String) -> serde_json::Value {
+    match serde_json::from_str(&value) {
+        Ok(json @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => json,
+        _ => serde_json::Value::String(value),
+    }
+}
+
+/// Timezone in its stored form, ie: `+02:00`
+mod utc_offset {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    use crate::quiet_hours;
+
+    pub fn serialize<S: Serializer>(
+        offset: &chrono::FixedOffset,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&quiet_hours::format_utc_offset(*offset))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<chrono::FixedOffset, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        quiet_hours::parse_utc_offset(&value)
+            .ok_or_else(|| serde::de::Error::custom(format!("Invalid UTC offset {value}")))
+    }
+}
+
+/// Setting & its value, as shown in `/settings` replies
+pub fn format_setting(key: GuildSettingKey, value: Option<&str>) -> String {
+    format!(
+        "`{key}`: {} - {}",
+        value.map_or("`not set`".to_string(), |value| format!("`{value}`")),
+        key.description()
+    )
+}
+
+/// Stored value of setting, or its default when not set (or stored value is no longer valid)
+pub fn get_guild_setting(
+    table: &SettingsTable,
+    guild_id: GuildId,
+    key: GuildSettingKey,
+) -> Option<String> {
+    GuildSettings::load(table, guild_id).value(key)
+}
+
+/// Volume of voice channel overriding the guild's volume, where 1.0 is unchanged volume
+pub fn channel_volume(
+    table: &SettingsTable,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Option<f32> {
+    table
+        .get_channel_volume(guild_id.get(), channel_id.get())
+        .log_err()
+        .ok()
+        .flatten()
+        .map(|percent| percent as f32 / 100.0)
+}
+
+/// Time without playback before bot leaves voice channel. None when disabled
+pub fn idle_timeout(table: &SettingsTable, guild_id: GuildId) -> Option<Duration> {
+    Some(GuildSettings::load(table, guild_id).idle_timeout)
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+}
+
+pub fn speech_pause(table: &SettingsTable, guild_id: GuildId) -> Option<Duration> {
+    Some(GuildSettings::load(table, guild_id).speech_pause)
+        .filter(|secs| *secs > 0)
+        .map(Duration::from_secs)
+}
+
+/// How long consecutive queued tracks fade into each other. None when they play back to back
+pub fn crossfade(table: &SettingsTable, guild_id: GuildId) -> Option<Duration> {
+    Some(GuildSettings::load(table, guild_id).crossfade)
+        .filter(|millis| *millis > 0)
+        .map(Duration::from_millis)
+}
+
+pub fn ephemeral_replies(table: &SettingsTable, guild_id: GuildId) -> bool {
+    GuildSettings::load(table, guild_id).ephemeral_replies
+}
+
+pub fn audit_channel(table: &SettingsTable, guild_id: GuildId) -> Option<ChannelId> {
+    GuildSettings::load(table, guild_id).audit_channel
+}
+
+pub fn request_channel(table: &SettingsTable, guild_id: GuildId) -> Option<ChannelId> {
+    GuildSettings::load(table, guild_id).request_channel
+}
+
+pub fn now_playing_channel(table: &SettingsTable, guild_id: GuildId) -> Option<ChannelId> {
+    GuildSettings::load(table, guild_id).now_playing_channel
+}
+
+#[cfg_attr(not(feature = "voice-commands"), allow(dead_code))]
+pub fn voice_commands(table: &SettingsTable, guild_id: GuildId) -> bool {
+    GuildSettings::load(table, guild_id).voice_commands
+}
+
+pub fn user_role(table: &SettingsTable, guild_id: GuildId) -> Option<RoleId> {
+    GuildSettings::load(table, guild_id).user_role
+}
+
+/// Plays allowed per channel per hour, by everyone together. None when unlimited, or lifted by
+/// event mode
+pub fn channel_hourly_cap(table: &SettingsTable, guild_id: GuildId) -> Option<u32> {
+    GuildSettings::load(table, guild_id).channel_hourly_cap()
+}
+
+/// Guild's timezone, as a UTC offset
+pub fn timezone(table: &SettingsTable, guild_id: GuildId) -> chrono::FixedOffset {
+    GuildSettings::load(table, guild_id).timezone
+}
+
+/// How long individual plays are kept before they're rolled up into daily play counts.
+/// None when they're kept forever
+pub fn play_history_retention(
+    table: &SettingsTable,
+    guild_id: GuildId,
+) -> Option<chrono::Duration> {
+    Some(GuildSettings::load(table, guild_id).play_history_retention)
+        .filter(|days| *days > 0)
+        .map(|days| chrono::Duration::days(days.into()))
+}
+
+/// How guild's sound button presses are acknowledged
+pub fn play_feedback(table: &SettingsTable, guild_id: GuildId) -> PlayFeedback {
+    GuildSettings::load(table, guild_id).play_feedback
+}
+
+/// What happens when a sound added in guild is named like an existing sound
+pub fn name_collision(table: &SettingsTable, guild_id: GuildId) -> NameCollision {
+    GuildSettings::load(table, guild_id).name_collision
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_setting_test() {
+        assert_eq!(GuildSettingKey::Volume.parse("80%"), Ok("80".into()));
+        assert!(GuildSettingKey::Volume.parse("201").is_err());
+        assert!(GuildSettingKey::IdleTimeout.parse("-5").is_err());
+        assert_eq!(
+            GuildSettingKey::EphemeralReplies.parse("On"),
+            Ok("true".into())
+        );
+        assert_eq!(
+            GuildSettingKey::AuditChannel.parse("<#1234>"),
+            Ok("1234".into())
+        );
+        assert!(GuildSettingKey::AuditChannel.parse("general").is_err());
+        assert_eq!(GuildSettingKey::Locale.parse("EN-us"), Ok("en-US".into()));
+        assert!(GuildSettingKey::Locale.parse("english").is_err());
+        assert_eq!(GuildSettingKey::UserRole.parse("<@&42>"), Ok("42".into()));
+        assert_eq!(
+            GuildSettingKey::OverlapPlayback.parse("yes"),
+            Ok("true".into())
+        );
+        assert!(GuildSettingKey::MaxOverlappingTracks.parse("1").is_err());
+        assert_eq!(
+            GuildSettingKey::MaxQueueDuration.parse("10:00"),
+            Ok("600".into())
+        );
+        assert_eq!(
+            GuildSettingKey::MaxQueueDuration.parse("90"),
+            Ok("90".into())
+        );
+        assert!(GuildSettingKey::MaxQueueDuration.parse("25:00:00").is_err());
+        assert!(GuildSettingKey::MaxQueueLength.parse("-1").is_err());
+        assert_eq!(
+            GuildSettingKey::Timezone.parse("UTC+2"),
+            Ok("+02:00".into())
+        );
+        assert_eq!(
+            GuildSettingKey::QuietHours.parse("22:00 - 7:00"),
+            Ok("22:00-07:00".into())
+        );
+        assert!(GuildSettingKey::QuietHoursVolume.parse("300").is_err());
+        assert!(GuildSettingKey::MessageRetention.parse("9000").is_err());
+        assert_eq!(
+            GuildSettingKey::PlayFeedback.parse("React"),
+            Ok("reaction".into())
+        );
+        assert!(GuildSettingKey::PlayFeedback.parse("loud").is_err());
+    }
+
+    #[test]
+    fn guild_settings_test() {
+        let guild_id = GuildId::new(1);
+        let settings = GuildSettings::from_stored(
+            guild_id,
+            [
+                ("volume", "80"),
+                ("ephemeral_replies", "true"),
+                ("audit_channel", "1234"),
+                ("locale", "en"),
+                ("timezone", "+02:00"),
+                ("quiet_hours", "22:00-07:00"),
+                ("play_feedback", "reaction"),
+                // no longer valid, falls back to its default
+                ("max_overlapping_tracks", "40"),
+                // of another feature sharing the table
+                ("event_mode", "whatever"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string())),
+        );
+
+        assert_eq!(
+            settings,
+            GuildSettings {
+                volume: 80,
+                ephemeral_replies: true,
+                audit_channel: Some(ChannelId::new(1234)),
+                locale: "en".into(),
+                timezone: chrono::FixedOffset::east_opt(2 * 3600).unwrap(),
+                quiet_hours: QuietHours::parse("22:00-07:00"),
+                play_feedback: PlayFeedback::Reaction,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            settings.value(GuildSettingKey::AuditChannel),
+            Some("1234".into())
+        );
+        assert_eq!(
+            settings.value(GuildSettingKey::Timezone),
+            Some("+02:00".into())
+        );
+        assert_eq!(settings.value(GuildSettingKey::UserRole), None);
+
+        // round trips through its stored form
+        let stored = GuildSettingKey::ALL
+            .into_iter()
+            .filter_map(|key| Some((key.key().to_string(), settings.value(key)?)));
+        assert_eq!(GuildSettings::from_stored(guild_id, stored), settings);
+
+        // event mode lifts limits, until it ends
+        let until = chrono::Utc::now() + chrono::Duration::hours(1);
+        let stored = |until: chrono::DateTime<chrono::Utc>| {
+            [
+                ("max_queue_length", "5".to_string()),
+                (event_mode::EVENT_MODE_KEY, until.to_rfc3339()),
+            ]
+            .map(|(key, value)| (key.to_string(), value))
+        };
+        let settings = GuildSettings::from_stored(guild_id, stored(until));
+        assert!(settings.event_mode);
+        assert_eq!(settings.max_queue_length(), None);
+        assert_eq!(
+            settings.max_overlapping_tracks(),
+            event_mode::EVENT_MAX_OVERLAPPING_TRACKS
+        );
+        let settings =
+            GuildSettings::from_stored(guild_id, stored(until - chrono::Duration::hours(2)));
+        assert!(!settings.event_mode);
+        assert_eq!(settings.max_queue_length(), Some(5));
+    }
+
+    #[test]
+    fn default_value_is_valid_test() {
+        for key in GuildSettingKey::ALL {
+            if let Some(value) = key.default_value() {
+                assert_eq!(key.parse(&value), Ok(value.clone()));
+            }
+        }
+    }
+}
@@ -0,0 +1,521 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::{Deserialize, Serialize};
+use serenity::all::GuildId;
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::db::{
+    AudioTable, BoardThemesTable, DbPool, SettingsTable, SoundRestrictionsTable,
+    UniqueAudioTableCol,
+};
+use crate::event_mode;
+use crate::export::ExportFile;
+use crate::settings::GuildSettingKey;
+use crate::themes;
+
+/// Biggest settings file `/settings import` reads
+pub const MAX_SETTINGS_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Version of settings files written. Files from newer versions are refused
+const SETTINGS_FILE_VERSION: u32 = 1;
+
+/// Guild's configuration, as written by `/settings export` & read by `/settings import`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettingsFile {
+    pub version: u32,
+    pub guild_id: u64,
+    pub exported_at: String,
+    /// Stored guild settings by key, including the ones set by other commands, ie: event sounds
+    pub settings: BTreeMap<String, String>,
+    #[serde(default)]
+    pub channel_volumes: Vec<ChannelVolume>,
+    #[serde(default)]
+    pub sound_restrictions: Vec<SoundRestriction>,
+    #[serde(default)]
+    pub board_themes: Vec<BoardTheme>,
+    /// Names of roles referenced by id, to find them by name in another guild
+    #[serde(default)]
+    pub roles: BTreeMap<u64, String>,
+    /// Names of channels referenced by id, to find them by name in another guild
+    #[serde(default)]
+    pub channels: BTreeMap<u64, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelVolume {
+    pub channel_id: u64,
+    pub volume: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoundRestriction {
+    /// Name of sound, to find it when its id changed
+    pub sound: String,
+    pub audio_id: i64,
+    pub role_id: Option<u64>,
+    pub user_id: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoardTheme {
+    pub tag: String,
+    pub start_day: String,
+    pub end_day: String,
+}
+
+/// Names of a guild's roles & channels by id
+#[derive(Debug, Clone, Default)]
+pub struct GuildNames {
+    pub roles: HashMap<u64, String>,
+    pub channels: HashMap<u64, String>,
+}
+
+/// Find id in guild, or else an id named like it. Lowest id wins when names are shared
+fn resolve_id(guild: &HashMap<u64, String>, id: u64, name: Option<&String>) -> Option<u64> {
+    if guild.contains_key(&id) {
+        return Some(id);
+    }
+
+    let name = name?;
+    guild
+        .iter()
+        .filter(|(_, guild_name)| *guild_name == name)
+        .map(|(id, _)| *id)
+        .min()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Reference {
+    Role,
+    Channel,
+}
+
+/// What guild setting's value is the id of
+fn reference(key: GuildSettingKey) -> Option<Reference> {
+    match key {
+        GuildSettingKey::UserRole
+        | GuildSettingKey::ModeratorRole
+        | GuildSettingKey::PriorityRole => Some(Reference::Role),
+        GuildSettingKey::AuditChannel
+        | GuildSettingKey::RequestChannel
+        | GuildSettingKey::NowPlayingChannel => Some(Reference::Channel),
+        _ => None,
+    }
+}
+
+fn setting_key(key: &str) -> Option<GuildSettingKey> {
+    GuildSettingKey::ALL
+        .into_iter()
+        .find(|setting_key| setting_key.key() == key)
+}
+
+impl SettingsFile {
+    /// Configuration of guild. Runtime state, like when event mode ends, isn't included
+    pub fn collect(
+        db_pool: &DbPool,
+        guild_id: GuildId,
+        names: &GuildNames,
+    ) -> Result<Self, PoiseError> {
+        let settings_table = SettingsTable::new(db_pool.get()?);
+        let audio_table = AudioTable::new(db_pool.get()?);
+
+        let mut file = Self {
+            version: SETTINGS_FILE_VERSION,
+            guild_id: guild_id.get(),
+            exported_at: chrono::Utc::now().to_rfc3339(),
+            settings: BTreeMap::new(),
+            channel_volumes: vec![],
+            sound_restrictions: vec![],
+            board_themes: vec![],
+            roles: BTreeMap::new(),
+            channels: BTreeMap::new(),
+        };
+
+        for (key, value) in settings_table.list_guild_settings(guild_id.get())? {
+            if key == event_mode::EVENT_MODE_KEY {
+                continue;
+            }
+            match setting_key(&key).and_then(reference) {
+                Some(Reference::Role) => add_name(&mut file.roles, &names.roles, &value),
+                Some(Reference::Channel) => add_name(&mut file.channels, &names.channels, &value),
+                None => {}
+            }
+            file.settings.insert(key, value);
+        }
+
+        for (channel_id, volume) in settings_table.list_channel_volumes(guild_id.get())? {
+            add_name(&mut file.channels, &names.channels, &channel_id.to_string());
+            file.channel_volumes
+                .push(ChannelVolume { channel_id, volume });
+        }
+
+        let restrictions_table = SoundRestrictionsTable::new(db_pool.get()?);
+        for row in restrictions_table.rows(guild_id.get())? {
+            // restrictions outliving their sound have nothing to restrict
+            let Some(audio_row) = audio_table.find_audio_row(UniqueAudioTableCol::Id(row.audio_id))
+            else {
+                continue;
+            };
+            if let Some(role_id) = row.role_id {
+                add_name(&mut file.roles, &names.roles, &role_id.to_string());
+            }
+            file.sound_restrictions.push(SoundRestriction {
+                sound: audio_row.name,
+                audio_id: row.audio_id,
+                role_id: row.role_id,
+                user_id: row.user_id,
+            });
+        }
+
+        let themes_table = BoardThemesTable::new(db_pool.get()?);
+        file.board_themes = themes_table
+            .rows(guild_id.get())?
+            .into_iter()
+            .map(|row| BoardTheme {
+                tag: row.tag,
+                start_day: row.start_day,
+                end_day: row.end_day,
+            })
+            .collect();
+
+        Ok(file)
+    }
+}
+
+/// Add name of role or channel id found in guild
+fn add_name(found: &mut BTreeMap<u64, String>, guild: &HashMap<u64, String>, id: &str) {
+    if let Some((id, name)) = id.parse().ok().and_then(|id| Some((id, guild.get(&id)?))) {
+        found.insert(id, name.clone());
+    }
+}
+
+/// Export configuration of guild as a json file
+pub fn export(
+    db_pool: &DbPool,
+    guild_id: GuildId,
+    names: &GuildNames,
+) -> Result<ExportFile, PoiseError> {
+    log::info!("Exporting settings. guild_id: {guild_id}");
+    let file = SettingsFile::collect(db_pool, guild_id, names)?;
+
+    Ok(ExportFile {
+        file_name: format!("settings_{guild_id}.json"),
+        content: serde_json::to_vec_pretty(&file)?,
+    })
+}
+
+/// Configuration read from a settings file, after ids were resolved in the importing guild
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub settings: Vec<(String, String)>,
+    pub channel_volumes: Vec<(u64, u32)>,
+    /// Restrictions as (audio_id, role_id, user_id)
+    pub sound_restrictions: Vec<(i64, Option<u64>, Option<u64>)>,
+    pub board_themes: Vec<BoardTheme>,
+    /// Reasons parts of the file were left out
+    pub skipped: Vec<String>,
+}
+
+impl ImportReport {
+    /// Resolve & validate configuration of file for guild
+    pub fn read(
+        db_pool: &DbPool,
+        file: &SettingsFile,
+        names: &GuildNames,
+    ) -> Result<Self, PoiseError> {
+        if file.version > SETTINGS_FILE_VERSION {
+            return Err(format!(
+                "Settings file version {} is newer than this bot supports ({SETTINGS_FILE_VERSION})",
+                file.version
+            )
+            .into());
+        }
+
+        let mut report = Self::default();
+        let role = |id: u64| resolve_id(&names.roles, id, file.roles.get(&id));
+        let channel = |id: u64| resolve_id(&names.channels, id, file.channels.get(&id));
+
+        for (key, value) in &file.settings {
+            if key == event_mode::EVENT_MODE_KEY {
+                continue;
+            }
+            let Some(setting_key) = setting_key(key) else {
+                // set by other commands, ie: event sounds & branding
+                report.settings.push((key.clone(), value.clone()));
+                continue;
+            };
+
+            let value = match (reference(setting_key), value.parse::<u64>()) {
+                (Some(Reference::Role), Ok(id)) => match role(id) {
+                    Some(id) => id.to_string(),
+                    None => {
+                        report.skipped.push(format!("`{key}`: role not found"));
+                        continue;
+                    }
+                },
+                (Some(Reference::Channel), Ok(id)) => match channel(id) {
+                    Some(id) => id.to_string(),
+                    None => {
+                        report.skipped.push(format!("`{key}`: channel not found"));
+                        continue;
+                    }
+                },
+                _ => value.clone(),
+            };
+            match setting_key.parse(&value) {
+                Ok(value) => report.settings.push((key.clone(), value)),
+                Err(err) => report.skipped.push(format!("`{key}`: {err}")),
+            }
+        }
+
+        for ChannelVolume { channel_id, volume } in &file.channel_volumes {
+            match (channel(*channel_id), *volume <= 200) {
+                (Some(channel_id), true) => report.channel_volumes.push((channel_id, *volume)),
+                (None, _) => report
+                    .skipped
+                    .push(format!("Volume of channel {channel_id}: channel not found")),
+                (_, false) => report
+                    .skipped
+                    .push(format!("Volume of channel {channel_id}: volume above 200")),
+            }
+        }
+
+        let audio_table = AudioTable::new(db_pool.get()?);
+        for restriction in &file.sound_restrictions {
+            let sound = &restriction.sound;
+            let audio_row = audio_table
+                .find_audio_row(UniqueAudioTableCol::Id(restriction.audio_id))
+                .filter(|row| &row.name == sound)
+                .or_else(|| audio_table.find_audio_row(UniqueAudioTableCol::Name(sound.clone())));
+            let Some(audio_row) = audio_row else {
+                report
+                    .skipped
+                    .push(format!("Restriction of `{sound}`: sound not found"));
+                continue;
+            };
+
+            let role_id = match restriction.role_id.map(role) {
+                Some(None) => {
+                    report
+                        .skipped
+                        .push(format!("Restriction of `{sound}`: role not found"));
+                    continue;
+                }
+                role_id => role_id.flatten(),
+            };
+            if role_id.is_none() && restriction.user_id.is_none() {
+                continue;
+            }
+            report
+                .sound_restrictions
+                .push((audio_row.id, role_id, restriction.user_id));
+        }
+
+        for theme in &file.board_themes {
+            match (
+                themes::parse_day(&theme.start_day),
+                themes::parse_day(&theme.end_day),
+            ) {
+                (Some(start_day), Some(end_day)) => report.board_themes.push(BoardTheme {
+                    tag: theme.tag.clone(),
+                    start_day,
+                    end_day,
+                }),
+                _ => report
+                    .skipped
+                    .push(format!("Theme `{}`: days must be MM-DD", theme.tag)),
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Replace guild's configuration with the one read
+    pub fn apply(&self, db_pool: &DbPool, guild_id: GuildId) -> Result<(), PoiseError> {
+        log::info!("Importing settings. guild_id: {guild_id}");
+        let settings_table = SettingsTable::new(db_pool.get()?);
+        let restrictions_table = SoundRestrictionsTable::new(db_pool.get()?);
+        let themes_table = BoardThemesTable::new(db_pool.get()?);
+        let guild = guild_id.get();
+
+        for (key, _) in settings_table.list_guild_settings(guild)? {
+            if key != event_mode::EVENT_MODE_KEY {
+                settings_table.delete_guild_setting(guild, &key)?;
+            }
+        }
+        for (key, value) in &self.settings {
+            settings_table.set_guild_setting(guild, key, value)?;
+        }
+
+        for (channel_id, _) in settings_table.list_channel_volumes(guild)? {
+            settings_table.delete_channel_volume(guild, channel_id)?;
+        }
+        for (channel_id, volume) in &self.channel_volumes {
+            settings_table.set_channel_volume(guild, *channel_id, *volume)?;
+        }
+
+        let mut restricted: Vec<i64> = restrictions_table
+            .rows(guild)?
+            .into_iter()
+            .map(|row| row.audio_id)
+            .collect();
+        restricted.dedup();
+        for audio_id in restricted {
+            restrictions_table.delete_rows(guild, audio_id)?;
+        }
+        for (audio_id, role_id, user_id) in &self.sound_restrictions {
+            restrictions_table.insert_row(guild, *audio_id, *role_id, *user_id)?;
+        }
+
+        for row in themes_table.rows(guild)? {
+            themes_table.delete_row(guild, row.id)?;
+        }
+        for theme in &self.board_themes {
+            themes_table.insert_row(guild, &theme.tag, &theme.start_day, &theme.end_day)?;
+        }
+        themes::switch_guild_theme(&themes_table, &settings_table, guild_id).log_err()?;
+
+        Ok(())
+    }
+
+    pub fn summary(&self, dry_run: bool) -> String {
+        let mut text = format!(
+            "{} {} settings, {} channel volumes, {} sound restrictions, and {} board themes",
+            match dry_run {
+                true => "Would import",
+                false => "Imported",
+            },
+            self.settings.len(),
+            self.channel_volumes.len(),
+            self.sound_restrictions.len(),
+            self.board_themes.len(),
+        );
+        if !self.skipped.is_empty() {
+            text.push_str(&format!("\nSkipped {}:", self.skipped.len()));
+            for reason in &self.skipped {
+                text.push_str(&format!("\n- {reason}"));
+            }
+        }
+        text
+    }
+}
+
+/// Parse a settings file
+pub fn parse(content: &[u8]) -> Result<SettingsFile, PoiseError> {
+    serde_json::from_slice(content).map_err(|err| format!("Invalid settings file. {err}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::db::Table;
+
+    fn get_db_pool() -> DbPool {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        SettingsTable::new(db_pool.get().unwrap()).create_table();
+        AudioTable::new(db_pool.get().unwrap()).create_table();
+        SoundRestrictionsTable::new(db_pool.get().unwrap()).create_table();
+        BoardThemesTable::new(db_pool.get().unwrap()).create_table();
+        db_pool
+    }
+
+    #[test]
+    fn resolve_id_test() {
+        let guild = HashMap::from([(1, "mods".to_string()), (2, "djs".to_string())]);
+        let (mods, djs) = ("mods".to_string(), "djs".to_string());
+
+        assert_eq!(resolve_id(&guild, 1, None), Some(1));
+        assert_eq!(resolve_id(&guild, 10, Some(&djs)), Some(2));
+        assert_eq!(resolve_id(&guild, 10, None), None);
+        assert_eq!(resolve_id(&guild, 10, Some(&"admins".to_string())), None);
+        assert_eq!(resolve_id(&guild, 1, Some(&mods)), Some(1));
+    }
+
+    #[test]
+    fn settings_file_test() {
+        let db_pool = get_db_pool();
+        let settings_table = SettingsTable::new(db_pool.get().unwrap());
+        settings_table.set_guild_setting(1, "volume", "80").unwrap();
+        settings_table
+            .set_guild_setting(1, "moderator_role", "10")
+            .unwrap();
+        settings_table
+            .set_guild_setting(1, "audit_channel", "20")
+            .unwrap();
+        settings_table
+            .set_guild_setting(1, "embed_color", "#ff0000")
+            .unwrap();
+        settings_table
+            .set_guild_setting(1, event_mode::EVENT_MODE_KEY, "2026-01-01T00:00:00Z")
+            .unwrap();
+        settings_table.set_channel_volume(1, 21, 50).unwrap();
+        let themes_table = BoardThemesTable::new(db_pool.get().unwrap());
+        themes_table
+            .insert_row(1, "spooky", "10-01", "10-31")
+            .unwrap();
+        drop((settings_table, themes_table));
+
+        let names = GuildNames {
+            roles: HashMap::from([(10, "mods".to_string())]),
+            channels: HashMap::from([(20, "audit".to_string()), (21, "lounge".to_string())]),
+        };
+        let export = export(&db_pool, GuildId::new(1), &names).unwrap();
+        let file = parse(&export.content).unwrap();
+        assert!(!file.settings.contains_key(event_mode::EVENT_MODE_KEY));
+        assert_eq!(file.settings.len(), 4);
+        assert_eq!(file.roles.get(&10).map(String::as_str), Some("mods"));
+        assert_eq!(file.channels.len(), 2);
+
+        // sister guild, with the audit channel but a differently named lounge
+        let names = GuildNames {
+            roles: HashMap::from([(11, "mods".to_string())]),
+            channels: HashMap::from([(30, "audit".to_string()), (31, "hangout".to_string())]),
+        };
+        let report = ImportReport::read(&db_pool, &file, &names).unwrap();
+        assert_eq!(report.settings.len(), 4);
+        assert!(report
+            .settings
+            .contains(&("moderator_role".to_string(), "11".to_string())));
+        assert!(report
+            .settings
+            .contains(&("audit_channel".to_string(), "30".to_string())));
+        assert!(report.channel_volumes.is_empty());
+        assert_eq!(report.skipped.len(), 1);
+
+        let settings_table = SettingsTable::new(db_pool.get().unwrap());
+        settings_table
+            .set_guild_setting(2, "locale", "de-DE")
+            .unwrap();
+        drop(settings_table);
+        report.apply(&db_pool, GuildId::new(2)).unwrap();
+
+        let settings_table = SettingsTable::new(db_pool.get().unwrap());
+        let settings = settings_table.list_guild_settings(2).unwrap();
+        assert_eq!(settings.len(), 4);
+        assert!(!settings.iter().any(|(key, _)| key == "locale"));
+        drop(settings_table);
+        let themes = BoardThemesTable::new(db_pool.get().unwrap())
+            .rows(2)
+            .unwrap();
+        assert_eq!(themes.len(), 1);
+        assert_eq!(themes[0].tag, "spooky");
+    }
+
+    #[test]
+    fn settings_file_invalid_test() {
+        assert!(parse(b"not json").is_err());
+
+        let db_pool = get_db_pool();
+        let mut file = parse(br#"{"version": 1, "guild_id": 1, "exported_at": "", "settings": {"volume": "900", "timezone": "+02:00"}}"#)
+            .unwrap();
+        let report = ImportReport::read(&db_pool, &file, &GuildNames::default()).unwrap();
+        assert_eq!(report.settings, vec![("timezone".into(), "+02:00".into())]);
+        assert_eq!(report.skipped.len(), 1);
+
+        file.version = SETTINGS_FILE_VERSION + 1;
+        assert!(ImportReport::read(&db_pool, &file, &GuildNames::default()).is_err());
+    }
+}
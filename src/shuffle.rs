@@ -0,0 +1,54 @@
+use serenity::all::{CreateActionRow, CreateButton, ReactionType};
+
+use crate::db::AudioTableRow;
+use crate::helpers::{self, ButtonCustomId};
+
+/// Sounds on a shuffle board, when no count is given
+pub const DEFAULT_SHUFFLE_COUNT: u32 = 10;
+
+/// Most sounds on a shuffle board. 4 rows of 5, the last action row is for the shuffle button
+pub const MAX_SHUFFLE_COUNT: u32 = 20;
+
+/// Message content and components of a board of random sounds, with a 🔀 button re-rolling them
+pub fn board(rows: &[AudioTableRow], count: u32) -> (String, Vec<CreateActionRow>) {
+    let content = match rows.len() {
+        0 => "No sounds to shuffle".to_string(),
+        len => format!("🔀 {len} random sounds"),
+    };
+
+    let mut labels = helpers::BoardLabels::default();
+    let mut components: Vec<_> = rows
+        .chunks(5)
+        .map(|rows| helpers::make_action_row(rows, &mut labels))
+        .collect();
+    components.push(CreateActionRow::Buttons(vec![CreateButton::new(
+        ButtonCustomId::ShuffleBoard(count),
+    )
+    .label("Shuffle")
+    .emoji(ReactionType::Unicode("🔀".into()))]));
+
+    (content, components)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shuffle_board_test() {
+        let rows: Vec<_> = (0..12).map(AudioTableRow::test_row).collect();
+
+        // 3 rows of sound buttons + shuffle row
+        let (content, components) = board(&rows, 12);
+        assert_eq!(content, "🔀 12 random sounds");
+        let labels = helpers::button_labels(&components);
+        assert_eq!(labels.len(), 4);
+        assert_eq!(labels[2], vec!["Sound 10", "Sound 11"]);
+        assert_eq!(labels[3], vec!["Shuffle"]);
+
+        // empty board can still be shuffled once sounds are added
+        let (content, components) = board(&[], 12);
+        assert_eq!(content, "No sounds to shuffle");
+        assert_eq!(helpers::button_labels(&components), vec![vec!["Shuffle"]]);
+    }
+}
@@ -0,0 +1,540 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serenity::all::{ChannelId, GuildId, MessageId, UserId};
+use serenity::async_trait;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::audio::AudioFile;
+use crate::auth::SoundboardPermissions;
+use crate::commands::PoiseError;
+use crate::common::{LogResult, UserData};
+use crate::config::SharedConfig;
+use crate::db::{
+    AudioTable, PlayHistoryTable, SettingsTable, SoundRestrictionsTable, Table, UniqueAudioTableCol,
+};
+use crate::errors::AudioError;
+use crate::helpers::{self, ButtonCustomId};
+use crate::playback::{Playback, QueuedTrack};
+use crate::search;
+use crate::voice::{ChatBackend, TrackControl, TrackEndCallback, VoiceBackend};
+
+/// Guild & voice channel sounds play in, when running with `--dry-run`
+const GUILD_ID: GuildId = GuildId::new(1);
+const CHANNEL_ID: ChannelId = ChannelId::new(1);
+
+/// How long tracks play for with `--dry-run`. Audio files aren't decoded, every track plays this long
+const TRACK_LENGTH: Duration = Duration::from_secs(3);
+
+const USAGE: &str = "\
+Dry run - sounds play in a simulated voice channel. Commands:
+  play <name>          Play sound by name
+  search <query>       List sounds matching query, with the ids of their play buttons
+  press <custom_id>    Press button by custom id, ie: play::12, pause, resume, search::0::query
+  queue                Show what's playing & queued
+  pause | resume       Pause or resume playback
+  finish               End current track early, as if it played to the end
+  stop                 Stop playback & leave voice channel
+  quit";
+
+/// Voice backend that plays nothing. Tracks end after `track_length`, or when ended with
+/// [`SimulatedTrack::finish`] if there's no track length
+pub struct SimulatedVoice {
+    track_length: Option<Duration>,
+    tracks: Mutex<Vec<Arc<SimulatedTrack>>>,
+    /// Voice channel joined in each guild
+    calls: Mutex<HashMap<GuildId, ChannelId>>,
+    next_uuid: AtomicU64,
+}
+
+impl SimulatedVoice {
+    pub fn new(track_length: Option<Duration>) -> Self {
+        Self {
+            track_length,
+            tracks: Mutex::new(vec![]),
+            calls: Mutex::new(HashMap::new()),
+            next_uuid: AtomicU64::new(1),
+        }
+    }
+
+    /// Tracks played so far, oldest first
+    #[allow(unused)]
+    pub fn tracks(&self) -> Vec<Arc<SimulatedTrack>> {
+        self.tracks.lock().unwrap().clone()
+    }
+
+    /// Tracks of guild that haven't ended
+    pub fn playing(&self, guild_id: GuildId) -> Vec<Arc<SimulatedTrack>> {
+        self.tracks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|track| track.guild_id == guild_id && !track.has_ended())
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl VoiceBackend for SimulatedVoice {
+    /// Joins the voice channel if the bot isn't in one yet, unlike songbird
+    async fn play(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        audio_file: &AudioFile,
+        requested_by: Option<UserId>,
+        on_end: TrackEndCallback,
+    ) -> Result<Arc<dyn TrackControl>, AudioError> {
+        self.calls
+            .lock()
+            .unwrap()
+            .entry(guild_id)
+            .or_insert(channel_id);
+
+        let track = Arc::new(SimulatedTrack {
+            uuid: uuid::Uuid::from_u64_pair(0, self.next_uuid.fetch_add(1, Ordering::Relaxed)),
+            guild_id,
+            audio_file: audio_file.clone(),
+            requested_by,
+            on_end,
+            state: Mutex::new(TrackState {
+                volume: 1.0,
+                paused: false,
+                ended: false,
+                looping: false,
+                position: Duration::ZERO,
+                resumed_at: Some(Instant::now()),
+            }),
+        });
+        self.tracks.lock().unwrap().push(Arc::clone(&track));
+        log::info!(
+            "Simulated playing track {audio_file:?}, guild_id: {guild_id}, requested_by: {requested_by:?}"
+        );
+
+        if let Some(track_length) = self.track_length {
+            let track = Arc::clone(&track);
+            tokio::spawn(async move {
+                tokio::time::sleep(track_length).await;
+                track.finish(false).await;
+            });
+        }
+
+        Ok(track)
+    }
+
+    fn in_call(&self, guild_id: GuildId) -> bool {
+        self.calls.lock().unwrap().contains_key(&guild_id)
+    }
+
+    async fn current_channel(&self, guild_id: GuildId) -> Option<ChannelId> {
+        self.calls.lock().unwrap().get(&guild_id).copied()
+    }
+
+    async fn move_to(&self, guild_id: GuildId, channel_id: ChannelId) -> Result<(), PoiseError> {
+        log::info!("Simulated moving to voice channel {channel_id}, guild_id: {guild_id}");
+        self.calls.lock().unwrap().insert(guild_id, channel_id);
+        Ok(())
+    }
+
+    async fn leave(&self, guild_id: GuildId) -> Result<(), PoiseError> {
+        log::info!("Simulated leaving voice channel, guild_id: {guild_id}");
+        self.calls.lock().unwrap().remove(&guild_id);
+        Ok(())
+    }
+}
+
+struct TrackState {
+    volume: f32,
+    paused: bool,
+    ended: bool,
+    looping: bool,
+    /// Time played before last pause or seek
+    position: Duration,
+    /// When track last started playing. None while paused
+    resumed_at: Option<Instant>,
+}
+
+impl TrackState {
+    fn position(&self) -> Duration {
+        self.position + self.resumed_at.map_or(Duration::ZERO, |at| at.elapsed())
+    }
+}
+
+pub struct SimulatedTrack {
+    uuid: uuid::Uuid,
+    pub guild_id: GuildId,
+    #[allow(unused)]
+    pub audio_file: AudioFile,
+    #[allow(unused)]
+    pub requested_by: Option<UserId>,
+    on_end: TrackEndCallback,
+    state: Mutex<TrackState>,
+}
+
+#[allow(unused)]
+impl SimulatedTrack {
+    pub fn volume(&self) -> f32 {
+        self.state.lock().unwrap().volume
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused
+    }
+
+    pub fn has_ended(&self) -> bool {
+        self.state.lock().unwrap().ended
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.state.lock().unwrap().looping
+    }
+
+    /// End track as if it played to the end, or failed. Does nothing if track already ended.
+    /// Looping tracks start over instead, unless they failed
+    pub async fn finish(&self, failed: bool) {
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.looping && !failed {
+                state.position = Duration::ZERO;
+                state.resumed_at = (!state.paused).then(Instant::now);
+                return;
+            }
+        }
+
+        if self.mark_ended() {
+            (self.on_end)(self.uuid, failed).await;
+        }
+    }
+
+    /// Returns false if track had already ended
+    fn mark_ended(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        !std::mem::replace(&mut state.ended, true)
+    }
+}
+
+#[async_trait]
+impl TrackControl for SimulatedTrack {
+    fn uuid(&self) -> uuid::Uuid {
+        self.uuid
+    }
+
+    fn set_volume(&self, volume: f32) -> Result<(), PoiseError> {
+        self.state.lock().unwrap().volume = volume;
+        Ok(())
+    }
+
+    fn pause(&self) -> Result<(), PoiseError> {
+        let mut state = self.state.lock().unwrap();
+        state.position = state.position();
+        state.resumed_at = None;
+        state.paused = true;
+        Ok(())
+    }
+
+    fn resume(&self) -> Result<(), PoiseError> {
+        let mut state = self.state.lock().unwrap();
+        if state.paused {
+            state.resumed_at = Some(Instant::now());
+            state.paused = false;
+        }
+        Ok(())
+    }
+
+    /// Stopped tracks fire their end event, like songbird's
+    fn stop(&self) -> Result<(), PoiseError> {
+        if self.mark_ended() {
+            tokio::spawn((self.on_end)(self.uuid, false));
+        }
+        Ok(())
+    }
+
+    fn enable_loop(&self) -> Result<(), PoiseError> {
+        self.state.lock().unwrap().looping = true;
+        Ok(())
+    }
+
+    async fn position(&self) -> Result<Duration, PoiseError> {
+        Ok(self.state.lock().unwrap().position())
+    }
+
+    async fn seek(&self, position: Duration) -> Result<(), PoiseError> {
+        let mut state = self.state.lock().unwrap();
+        state.position = position;
+        if !state.paused {
+            state.resumed_at = Some(Instant::now());
+        }
+        Ok(())
+    }
+}
+
+/// Chat backend that records sent & edited messages instead of sending them
+#[derive(Default)]
+pub struct SimulatedChat {
+    edits: Mutex<Vec<(ChannelId, MessageId, String)>>,
+    sent: Mutex<Vec<(ChannelId, String)>>,
+    direct_messages: Mutex<Vec<(UserId, String)>>,
+    /// Users in each voice channel
+    voice_members: Mutex<HashMap<ChannelId, Vec<UserId>>>,
+    activities: Mutex<Vec<Option<String>>>,
+}
+
+impl SimulatedChat {
+    /// Content of edited messages, oldest first
+    #[allow(unused)]
+    pub fn edits(&self) -> Vec<(ChannelId, MessageId, String)> {
+        self.edits.lock().unwrap().clone()
+    }
+
+    /// Content of sent messages, oldest first. Message ids count up from 1 in this order
+    #[allow(unused)]
+    pub fn sent(&self) -> Vec<(ChannelId, String)> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// Content of direct messages, oldest first
+    #[allow(unused)]
+    pub fn direct_messages(&self) -> Vec<(UserId, String)> {
+        self.direct_messages.lock().unwrap().clone()
+    }
+
+    /// Put user in voice channel
+    #[allow(unused)]
+    pub fn join_voice(&self, channel_id: ChannelId, user_id: UserId) {
+        self.voice_members
+            .lock()
+            .unwrap()
+            .entry(channel_id)
+            .or_default()
+            .push(user_id);
+    }
+
+    /// Activities the bot was shown with, oldest first
+    #[allow(unused)]
+    pub fn activities(&self) -> Vec<Option<String>> {
+        self.activities.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl ChatBackend for SimulatedChat {
+    async fn edit_message(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        content: String,
+    ) -> Result<(), PoiseError> {
+        println!("[message {message_id}] {content}");
+        self.edits
+            .lock()
+            .unwrap()
+            .push((channel_id, message_id, content));
+        Ok(())
+    }
+
+    async fn send_message(
+        &self,
+        channel_id: ChannelId,
+        content: String,
+    ) -> Result<MessageId, PoiseError> {
+        let mut sent = self.sent.lock().unwrap();
+        sent.push((channel_id, content.clone()));
+        let message_id = MessageId::new(sent.len() as u64);
+        println!("[message {message_id}] {content}");
+        Ok(message_id)
+    }
+
+    async fn send_direct_message(
+        &self,
+        user_id: UserId,
+        content: String,
+    ) -> Result<(), PoiseError> {
+        println!("[direct message to {user_id}] {content}");
+        self.direct_messages
+            .lock()
+            .unwrap()
+            .push((user_id, content));
+        Ok(())
+    }
+
+    fn voice_channel_members(&self, _guild_id: GuildId, channel_id: ChannelId) -> Vec<UserId> {
+        self.voice_members
+            .lock()
+            .unwrap()
+            .get(&channel_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn set_activity(&self, name: Option<String>) {
+        match &name {
+            Some(name) => println!("[activity] Playing {name}"),
+            None => println!("[activity] cleared"),
+        }
+        self.activities.lock().unwrap().push(name);
+    }
+}
+
+/// Run soundboard against the database without connecting to Discord. Reads commands from stdin
+pub async fn run(
+    config: Arc<SharedConfig>,
+    db_pool: r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>,
+) -> anyhow::Result<()> {
+    AudioTable::new(db_pool.get()?).create_table();
+    SettingsTable::new(db_pool.get()?).create_table();
+    PlayHistoryTable::new(db_pool.get()?).create_table();
+    SoundRestrictionsTable::new(db_pool.get()?).create_table();
+
+    let voice = Arc::new(SimulatedVoice::new(Some(TRACK_LENGTH)));
+    let playback = Playback::new(
+        voice.clone(),
+        Arc::new(SimulatedChat::default()),
+        db_pool.clone(),
+    );
+    let data = UserData::new(config, db_pool, playback);
+
+    println!("{USAGE}");
+
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Some(line) = lines.next_line().await? {
+        let (command, arg) = line
+            .trim()
+            .split_once(' ')
+            .map_or((line.trim(), ""), |(command, arg)| (command, arg.trim()));
+
+        let result = match command {
+            "" => Ok(()),
+            "quit" | "exit" => break,
+            "help" => {
+                println!("{USAGE}");
+                Ok(())
+            }
+            "play" => play(&data, arg).await,
+            "search" => {
+                print_search(&data, arg, 0);
+                Ok(())
+            }
+            "press" => press(&data, arg).await,
+            "queue" => {
+                print_queue(&data).await;
+                Ok(())
+            }
+            "pause" => data.playback.pause(GUILD_ID).map(drop).map_err(Into::into),
+            "resume" => data.playback.resume(GUILD_ID).map(drop).map_err(Into::into),
+            "finish" => {
+                for track in voice.playing(GUILD_ID).into_iter().take(1) {
+                    track.finish(false).await;
+                }
+                Ok(())
+            }
+            "stop" => data.playback.leave(GUILD_ID).await,
+            _ => Err(format!("Unknown command '{command}'. Try `help`").into()),
+        };
+
+        if let Err(err) = result {
+            println!("Error - {err}");
+        }
+    }
+
+    data.playback.stop(GUILD_ID);
+    Ok(())
+}
+
+async fn play(data: &UserData, name: &str) -> Result<(), PoiseError> {
+    let row = data
+        .audio_table()
+        .find_audio_row(UniqueAudioTableCol::Name(name.to_string()))
+        .ok_or(format!("No sound named `{name}`"))?;
+
+    let outcome = data
+        .playback
+        .enqueue(GUILD_ID, QueuedTrack::from_row(&row, CHANNEL_ID))
+        .await
+        .log_err()?;
+    data.record_play(GUILD_ID, CHANNEL_ID, None, &row);
+
+    println!("{outcome:?} `{}`", row.name);
+    Ok(())
+}
+
+/// Route button custom id the way button interactions are, for the buttons that don't need Discord
+async fn press(data: &UserData, custom_id: &str) -> Result<(), PoiseError> {
+    let button = ButtonCustomId::try_from(custom_id.to_string())?;
+    match button {
+        ButtonCustomId::PlayAudio(_) | ButtonCustomId::ReplayLast => {
+            let audio_track_id = match button {
+                ButtonCustomId::PlayAudio(audio_track_id) => audio_track_id,
+                _ => helpers::last_played_audio_id(data, GUILD_ID)?,
+            };
+            let played = helpers::play_sound_button(
+                data,
+                GUILD_ID,
+                CHANNEL_ID,
+                None,
+                audio_track_id,
+                &SoundboardPermissions::default(),
+            )
+            .await?;
+            println!("{}", played.feedback_text());
+            Ok(())
+        }
+        ButtonCustomId::PausePlayback => Ok(data.playback.pause(GUILD_ID).map(drop)?),
+        ButtonCustomId::ResumePlayback => Ok(data.playback.resume(GUILD_ID).map(drop)?),
+        ButtonCustomId::SearchPage(page, query) => {
+            print_search(data, &query, page);
+            Ok(())
+        }
+        ButtonCustomId::Unknown(value) => Err(format!("Unrecognized button '{value}'").into()),
+        button => {
+            Err(format!("Button {button:?} needs Discord, it can't be pressed in a dry run").into())
+        }
+    }
+}
+
+fn print_search(data: &UserData, query: &str, page: usize) {
+    let rows = match data
+        .audio_table()
+        .search_rows(query, search::SEARCH_RESULTS_LIMIT)
+    {
+        Ok(rows) => rows,
+        Err(err) => {
+            println!("Error - {err}");
+            return;
+        }
+    };
+
+    let (content, _components) = search::board_page(&rows, query, page);
+    println!("{content}");
+    for row in rows
+        .chunks(search::BOARD_PAGE_SIZE)
+        .nth(page)
+        .unwrap_or_default()
+    {
+        println!("  play::{}  {}", row.id, row.name);
+    }
+}
+
+async fn print_queue(data: &UserData) {
+    match data.playback.now_playing(GUILD_ID).await {
+        Some(now_playing) => println!(
+            "Playing `{}`{} at {:.1}s, {} overlapping",
+            now_playing.name,
+            if now_playing.paused { " (paused)" } else { "" },
+            now_playing.position.as_secs_f32(),
+            now_playing.overlapping
+        ),
+        None => println!("{}", AudioError::NothingPlaying),
+    }
+
+    for (index, name) in data
+        .playback
+        .queued_track_names(GUILD_ID)
+        .iter()
+        .enumerate()
+    {
+        println!("  {}. {name}", index + 1);
+    }
+}
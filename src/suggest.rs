@@ -0,0 +1,102 @@
+use std::collections::{HashMap, HashSet};
+
+use serenity::all::CreateActionRow;
+
+use crate::db::AudioTableRow;
+use crate::helpers;
+
+/// Most sounds suggested at once. 2 rows of 5 buttons
+pub const SUGGESTIONS_LIMIT: usize = 10;
+
+/// Ids of sounds user hasn't played, best suggestions first, from `(user_id, audio_id, plays)`
+/// play counts of a guild.
+///
+/// Sounds are scored by the members who play what user plays: each member adds to the sounds
+/// they played by how many of user's sounds they share, damped by how many sounds they play
+/// overall, so members who play everything don't drown out the rest. Sounds played by more
+/// members come first among sounds scored the same, which is every sound when user hasn't
+/// played anything yet
+pub fn suggest(plays: &[(u64, i64, u64)], user_id: u64) -> Vec<i64> {
+    let mut sounds_by_user: HashMap<u64, HashSet<i64>> = HashMap::new();
+    for (player_id, audio_id, _) in plays {
+        sounds_by_user
+            .entry(*player_id)
+            .or_default()
+            .insert(*audio_id);
+    }
+    let played = sounds_by_user.remove(&user_id).unwrap_or_default();
+
+    let mut scores: HashMap<i64, f64> = HashMap::new();
+    let mut players: HashMap<i64, u64> = HashMap::new();
+    for sounds in sounds_by_user.values() {
+        let shared = sounds.intersection(&played).count();
+        let similarity = shared as f64 / (sounds.len() as f64).sqrt();
+
+        for audio_id in sounds.difference(&played) {
+            *scores.entry(*audio_id).or_default() += similarity;
+            *players.entry(*audio_id).or_default() += 1;
+        }
+    }
+
+    let mut suggestions: Vec<_> = scores.into_iter().collect();
+    suggestions.sort_by(|(a_id, a_score), (b_id, b_score)| {
+        b_score
+            .total_cmp(a_score)
+            .then(players[b_id].cmp(&players[a_id]))
+            .then(a_id.cmp(b_id))
+    });
+    suggestions
+        .into_iter()
+        .map(|(audio_id, _)| audio_id)
+        .collect()
+}
+
+/// Message content and sound buttons of sounds suggested to user
+pub fn board(rows: &[AudioTableRow]) -> (String, Vec<CreateActionRow>) {
+    if rows.is_empty() {
+        return (
+            "No suggestions yet. Once members play a few sounds, there'll be some to try".into(),
+            vec![],
+        );
+    }
+
+    let mut labels = helpers::BoardLabels::default();
+    let components = rows
+        .chunks(5)
+        .map(|rows| helpers::make_action_row(rows, &mut labels))
+        .collect();
+
+    (
+        format!("💡 {} sounds you might like", rows.len()),
+        components,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggest_test() {
+        let plays = [
+            // user 1 plays sounds 1 & 2
+            (1, 1, 5),
+            (1, 2, 1),
+            // user 2 shares both, and plays 3
+            (2, 1, 1),
+            (2, 2, 1),
+            (2, 3, 1),
+            // user 3 shares one, and plays 4
+            (3, 1, 1),
+            (3, 4, 9),
+            // users 4 & 5 share nothing, and play 5
+            (4, 5, 1),
+            (5, 5, 1),
+        ];
+
+        assert_eq!(suggest(&plays, 1), vec![3, 4, 5]);
+        // sounds played by more members first when user hasn't played anything
+        assert_eq!(suggest(&plays, 9), vec![1, 2, 5, 3, 4]);
+        assert!(suggest(&[], 1).is_empty());
+    }
+}
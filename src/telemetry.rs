@@ -0,0 +1,122 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::config::Config;
+use crate::db::{ApiKeysTable, AudioTable, DbPool, FeatureFlagsTable, PlayHistoryTable};
+use crate::features::{self, FeatureFlag};
+use crate::version;
+
+/// How long the telemetry endpoint has to answer
+const REPORT_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Aggregate counters of a deployment, sent daily when telemetry is turned on. Holds no ids,
+/// names, or anything else about guilds, members, or sounds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TelemetryReport {
+    pub version: &'static str,
+    pub guilds: usize,
+    pub sounds: u64,
+    pub plays_last_day: u64,
+    pub api_keys: u64,
+    /// Bot wide feature flags, by key
+    pub features: BTreeMap<&'static str, bool>,
+    /// Built with the `voice-commands` feature
+    pub voice_commands_build: bool,
+}
+
+impl TelemetryReport {
+    /// Counters of deployment right now. Guilds are counted from the cache
+    pub fn collect(db_pool: &DbPool, guilds: usize) -> Result<Self, PoiseError> {
+        let since = chrono::Utc::now() - chrono::Duration::days(1);
+        let flags = FeatureFlagsTable::new(db_pool.get()?);
+
+        Ok(Self {
+            version: version::VERSION,
+            guilds,
+            sounds: AudioTable::new(db_pool.get()?).count_rows()?,
+            plays_last_day: PlayHistoryTable::new(db_pool.get()?).count_plays_since(since)?,
+            api_keys: ApiKeysTable::new(db_pool.get()?).count_rows()?,
+            features: FeatureFlag::ALL
+                .into_iter()
+                .map(|flag| (flag.key(), features::table_enabled(&flags, flag)))
+                .collect(),
+            voice_commands_build: cfg!(feature = "voice-commands"),
+        })
+    }
+}
+
+/// Url reports are sent to. None unless telemetry is turned on & has an endpoint
+pub fn endpoint(config: &Config) -> Option<&str> {
+    match config.telemetry {
+        true => config.telemetry_url.as_deref(),
+        false => None,
+    }
+}
+
+/// POST report as json to the configured endpoint. Does nothing unless telemetry is turned on
+pub async fn send(config: &Config, report: &TelemetryReport) -> Result<(), PoiseError> {
+    let Some(url) = endpoint(config) else {
+        log::debug!("Telemetry is off, not sending report");
+        return Ok(());
+    };
+
+    log::info!("Sending telemetry report to {url}");
+    reqwest::Client::new()
+        .post(url)
+        .header(
+            reqwest::header::USER_AGENT,
+            format!("soundboard-bot/{}", version::VERSION),
+        )
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(report)?)
+        .timeout(REPORT_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .log_err_msg("Failed sending telemetry report")?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::db::Table;
+
+    #[test]
+    fn telemetry_endpoint_test() {
+        let mut config = Config::default();
+        assert_eq!(endpoint(&config), None);
+
+        config.telemetry_url = Some("https://example.com/report".into());
+        assert_eq!(endpoint(&config), None);
+
+        config.telemetry = true;
+        assert_eq!(endpoint(&config), Some("https://example.com/report"));
+    }
+
+    #[test]
+    fn telemetry_report_test() {
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        AudioTable::new(db_pool.get().unwrap()).create_table();
+        PlayHistoryTable::new(db_pool.get().unwrap()).create_table();
+        ApiKeysTable::new(db_pool.get().unwrap()).create_table();
+        let flags = FeatureFlagsTable::new(db_pool.get().unwrap());
+        flags.create_table();
+        flags.set_flag(FeatureFlag::WebApi.key(), false, 1).unwrap();
+
+        let report = TelemetryReport::collect(&db_pool, 3).unwrap();
+        assert_eq!((report.guilds, report.sounds, report.api_keys), (3, 0, 0));
+        assert!(!report.features["web_api_enabled"]);
+        assert!(report.features["url_imports_enabled"]);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["plays_last_day"], 0);
+    }
+}
@@ -0,0 +1,135 @@
+use serenity::all::{CreateActionRow, GuildId};
+
+use crate::commands::PoiseError;
+use crate::db::{AudioTableRow, BoardThemeRow, BoardThemesTable, DbPool, SettingsTable};
+use crate::helpers;
+use crate::settings;
+
+/// Most sounds of a featured theme shown on the board. 4 rows of 5, like a board message
+pub const FEATURED_SOUNDS_LIMIT: u64 = 20;
+
+/// Parse a day of the year, as `MM-DD`. Returns it zero padded, ie: `3-7` → `03-07`
+pub fn parse_day(value: &str) -> Option<String> {
+    // leap year, so 02-29 is a valid day
+    chrono::NaiveDate::parse_from_str(&format!("2000-{}", value.trim()), "%Y-%m-%d")
+        .ok()
+        .map(|date| date.format("%m-%d").to_string())
+}
+
+/// Whether theme is featured on day (`MM-DD`). Themes ending before they start span new year
+fn covers(theme: &BoardThemeRow, day: &str) -> bool {
+    let (start, end) = (theme.start_day.as_str(), theme.end_day.as_str());
+    match start <= end {
+        true => start <= day && day <= end,
+        false => start <= day || day <= end,
+    }
+}
+
+/// Theme to feature on date. When themes overlap, the most recently added one wins
+pub fn current_theme(themes: &[BoardThemeRow], date: chrono::NaiveDate) -> Option<&BoardThemeRow> {
+    let day = date.format("%m-%d").to_string();
+    themes.iter().rev().find(|theme| covers(theme, &day))
+}
+
+/// Feature guild's current theme, in the guild's timezone. Returns the newly featured tag when
+/// the featured theme changed, Some(None) when a theme stopped being featured
+pub fn switch_guild_theme(
+    themes_table: &BoardThemesTable,
+    settings_table: &SettingsTable,
+    guild_id: GuildId,
+) -> Result<Option<Option<String>>, PoiseError> {
+    let today = chrono::Utc::now()
+        .with_timezone(&settings::timezone(settings_table, guild_id))
+        .date_naive();
+    let themes = themes_table.rows(guild_id.get())?;
+
+    let active = themes.iter().find(|theme| theme.active);
+    let current = current_theme(&themes, today);
+    if active.map(|theme| theme.id) == current.map(|theme| theme.id) {
+        return Ok(None);
+    }
+
+    themes_table.set_active(guild_id.get(), current.map(|theme| theme.id))?;
+    Ok(Some(current.map(|theme| theme.tag.clone())))
+}
+
+/// Switch featured theme of every guild with themes. Returns number of guilds switched
+pub fn switch_themes(db_pool: &DbPool) -> Result<usize, PoiseError> {
+    let themes_table = BoardThemesTable::new(db_pool.get()?);
+    let settings_table = SettingsTable::new(db_pool.get()?);
+
+    let mut switched = 0;
+    for guild_id in themes_table.guild_ids()? {
+        let guild_id = GuildId::new(guild_id);
+        if let Some(tag) = switch_guild_theme(&themes_table, &settings_table, guild_id)? {
+            log::info!("Switched featured theme to {tag:?}. guild_id: {guild_id}");
+            switched += 1;
+        }
+    }
+
+    Ok(switched)
+}
+
+/// Message content and sound buttons of featured theme, shown at the top of the full board
+pub fn featured_board(
+    tag: &str,
+    rows: &[AudioTableRow],
+    labels: &mut helpers::BoardLabels,
+) -> (String, Vec<CreateActionRow>) {
+    let content = format!("✨ Featured: `{tag}`");
+    let components = rows
+        .chunks(5)
+        .map(|rows| helpers::make_action_row(rows, labels))
+        .collect();
+
+    (content, components)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_theme(id: i64, start_day: &str, end_day: &str) -> BoardThemeRow {
+        BoardThemeRow {
+            id,
+            guild_id: 1,
+            tag: format!("theme{id}"),
+            start_day: start_day.into(),
+            end_day: end_day.into(),
+            active: false,
+        }
+    }
+
+    fn date(value: &str) -> chrono::NaiveDate {
+        chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn parse_day_test() {
+        assert_eq!(parse_day("10-31"), Some("10-31".into()));
+        assert_eq!(parse_day("3-7"), Some("03-07".into()));
+        assert_eq!(parse_day("02-29"), Some("02-29".into()));
+        assert_eq!(parse_day("02-30"), None);
+        assert_eq!(parse_day("13-01"), None);
+        assert_eq!(parse_day("halloween"), None);
+    }
+
+    #[test]
+    fn current_theme_test() {
+        let themes = vec![
+            make_theme(1, "10-01", "10-31"),
+            make_theme(2, "12-01", "01-06"),
+            make_theme(3, "10-25", "11-01"),
+        ];
+
+        let current = |value| current_theme(&themes, date(value)).map(|theme| theme.id);
+        assert_eq!(current("2026-10-01"), Some(1));
+        // overlapping themes feature the newest
+        assert_eq!(current("2026-10-31"), Some(3));
+        assert_eq!(current("2026-11-02"), None);
+        // spans new year
+        assert_eq!(current("2026-12-25"), Some(2));
+        assert_eq!(current("2027-01-06"), Some(2));
+        assert_eq!(current("2027-01-07"), None);
+    }
+}
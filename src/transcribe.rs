@@ -0,0 +1,114 @@
+use std::path;
+
+use regex::Regex;
+
+use crate::common::UserData;
+use crate::listen::SAMPLE_RATE;
+
+/// Resample mono samples to the sample rate speech recognition takes, by linear interpolation
+pub fn resample(samples: &[f32], sample_rate: u32) -> Vec<f32> {
+    if sample_rate as usize == SAMPLE_RATE || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let step = sample_rate as f64 / SAMPLE_RATE as f64;
+    let len = (samples.len() as f64 / step) as usize;
+    (0..len)
+        .map(|i| {
+            let position = i as f64 * step;
+            let index = position as usize;
+            let next = samples.get(index + 1).unwrap_or(&samples[index]);
+            let frac = (position - index as f64) as f32;
+            samples[index] + (next - samples[index]) * frac
+        })
+        .collect()
+}
+
+/// Transcript without whisper's annotations of non speech, ie: `[BLANK_AUDIO]` or `(music)`.
+/// None when nothing was said
+pub fn clean_transcript(transcript: &str) -> Option<String> {
+    let annotations = Regex::new(r"\[[^\]]*\]|\([^)]*\)|\*[^*]*\*").unwrap();
+    let text = annotations.replace_all(transcript, " ");
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.chars().any(char::is_alphanumeric) {
+        true => Some(text),
+        false => None,
+    }
+}
+
+/// Transcribe audio of a sound in the background, when sound transcription is on
+pub fn queue(data: &UserData, row_id: i64, path: path::PathBuf) {
+    let config = data.config.get();
+    if !config.transcribe_sounds {
+        return;
+    }
+
+    #[cfg(feature = "voice-commands")]
+    {
+        let Some(model_path) = config.whisper_model_path.clone() else {
+            log::warn!("Sound transcription is on, but no whisper model is set");
+            return;
+        };
+
+        use crate::common::LogResult;
+        let db_pool = data.db_pool.clone();
+        tokio::task::spawn_blocking(move || {
+            transcribe_file(&model_path, &path)
+                .and_then(|transcript| match transcript {
+                    Some(transcript) => crate::db::AudioTable::new(db_pool.get()?)
+                        .set_transcript(row_id, &transcript),
+                    None => Ok(()),
+                })
+                .log_err_msg(format!("Failed transcribing audio row id: {row_id}"))
+                .ok();
+        });
+    }
+
+    #[cfg(not(feature = "voice-commands"))]
+    log::warn!(
+        "Sound transcription is on, but the bot wasn't built with the voice-commands feature. Not transcribing {} (audio row id: {row_id})",
+        path.to_string_lossy()
+    );
+}
+
+#[cfg(feature = "voice-commands")]
+fn transcribe_file(
+    model_path: &path::Path,
+    path: &path::Path,
+) -> Result<Option<String>, crate::commands::PoiseError> {
+    let transcriber =
+        crate::whisper::transcriber(model_path).ok_or("Whisper model isn't loaded")?;
+    let (samples, sample_rate) = crate::audio::decode_audio_mono(path)?;
+    let transcript = transcriber.transcribe(&resample(&samples, sample_rate))?;
+    Ok(clean_transcript(&transcript))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resample_test() {
+        let samples: Vec<f32> = (0..48).map(|i| i as f32).collect();
+        assert_eq!(
+            resample(&samples, 48_000),
+            vec![
+                0.0, 3.0, 6.0, 9.0, 12.0, 15.0, 18.0, 21.0, 24.0, 27.0, 30.0, 33.0, 36.0, 39.0,
+                42.0, 45.0
+            ]
+        );
+        assert_eq!(resample(&samples, 24_000)[1], 1.5);
+        assert_eq!(resample(&samples[..16], 16_000).len(), 16);
+        assert!(resample(&[], 44_100).is_empty());
+    }
+
+    #[test]
+    fn clean_transcript_test() {
+        assert_eq!(
+            clean_transcript(" Absolutely not! [laughs]"),
+            Some("Absolutely not!".into())
+        );
+        assert_eq!(clean_transcript("[BLANK_AUDIO]"), None);
+        assert_eq!(clean_transcript(" (music) *applause* ..."), None);
+    }
+}
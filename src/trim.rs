@@ -0,0 +1,202 @@
+use std::time::Duration;
+
+use serenity::all::{
+    ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton, CreateMessage,
+    EditInteractionResponse,
+};
+use serenity::client::Context;
+
+use crate::audio;
+use crate::commands::PoiseResult;
+use crate::common::{LogResult, UserData};
+use crate::db::UniqueAudioTableCol;
+use crate::features::{self, FeatureFlag};
+use crate::helpers::{self, ButtonCustomId};
+use crate::ingest;
+
+/// Chars of the audio file hash kept in confirm button custom ids
+const HASH_PREFIX_LEN: usize = 16;
+
+/// Proposed trim of a sound, carried by the confirm button of its preview.
+/// Holds hash of the audio file at preview time, so a stale preview can't cut an already trimmed sound
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrimSpec {
+    pub audio_id: i64,
+    pub start_ms: u64,
+    pub end_ms: Option<u64>,
+    pub file_hash: String,
+}
+
+impl TrimSpec {
+    pub fn new(audio_id: i64, start_ms: u64, end_ms: Option<u64>, file_hash: &str) -> Self {
+        Self {
+            audio_id,
+            start_ms,
+            end_ms,
+            file_hash: file_hash.chars().take(HASH_PREFIX_LEN).collect(),
+        }
+    }
+
+    pub fn start(&self) -> Duration {
+        Duration::from_millis(self.start_ms)
+    }
+
+    pub fn end(&self) -> Option<Duration> {
+        self.end_ms.map(Duration::from_millis)
+    }
+
+    /// Whether audio file with given hash is the one previewed
+    pub fn matches_hash(&self, file_hash: &str) -> bool {
+        file_hash.starts_with(&self.file_hash)
+    }
+
+    /// Parse custom id parts following `trim::`. ie: `["3", "0", "1500", "ab12"]`
+    pub fn from_parts(parts: &[&str]) -> Result<Self, String> {
+        let [audio_id, start_ms, end_ms, file_hash] = parts else {
+            return Err(format!("Expected 4 trim parts, found {}", parts.len()));
+        };
+        let parse_err = |err: std::num::ParseIntError| err.to_string();
+
+        Ok(Self {
+            audio_id: audio_id.parse().map_err(parse_err)?,
+            start_ms: start_ms.parse().map_err(parse_err)?,
+            end_ms: match *end_ms {
+                "" => None,
+                end_ms => Some(end_ms.parse().map_err(parse_err)?),
+            },
+            file_hash: file_hash.to_string(),
+        })
+    }
+
+    pub fn to_parts(&self) -> String {
+        format!(
+            "{}::{}::{}::{}",
+            self.audio_id,
+            self.start_ms,
+            self.end_ms.map_or("".into(), |end_ms| end_ms.to_string()),
+            self.file_hash
+        )
+    }
+
+    /// Human readable trim range. ie: `0.50s - 2.00s`
+    pub fn range_text(&self) -> String {
+        match self.end() {
+            Some(end) => format!(
+                "{:.2}s - {:.2}s",
+                self.start().as_secs_f64(),
+                end.as_secs_f64()
+            ),
+            None => format!("{:.2}s - end", self.start().as_secs_f64()),
+        }
+    }
+}
+
+/// Confirm & cancel buttons of a trim preview
+pub fn confirm_action_row(spec: TrimSpec) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(ButtonCustomId::ConfirmTrim(spec))
+            .label("Trim")
+            .style(ButtonStyle::Danger),
+        CreateButton::new(ButtonCustomId::CancelTrim).label("Cancel"),
+    ])
+}
+
+/// Trim sound from the confirm button of its preview. Overwrites the sound's audio file,
+/// unless it changed since the preview. The interaction must already be acknowledged.
+pub async fn confirm_trim(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    data: &UserData,
+    spec: TrimSpec,
+) -> PoiseResult {
+    let reply = |content: String| async move {
+        component
+            .edit_response(
+                ctx,
+                EditInteractionResponse::new()
+                    .content(content)
+                    .components(vec![]),
+            )
+            .await
+            .log_err_msg("Failed replying to trim confirmation")
+            .ok();
+    };
+
+    if let Err(err) = features::check(data, FeatureFlag::AudioEditing) {
+        reply(err.to_string()).await;
+        return Ok(());
+    }
+
+    let Some(row) = data
+        .audio_table()
+        .find_audio_row(UniqueAudioTableCol::Id(spec.audio_id))
+    else {
+        reply("Sound no longer exists".into()).await;
+        return Ok(());
+    };
+
+    let file_hash = audio::hash_file(row.audio_file.as_path())?;
+    if !spec.matches_hash(&file_hash) {
+        reply(format!(
+            "Audio of `{}` changed since this preview. Run `/sounds trim` again",
+            row.name
+        ))
+        .await;
+        return Ok(());
+    }
+
+    let ffmpeg_path = data.config.get().ffmpeg_path.clone();
+    let trimmed =
+        audio::trim_audio_file_temp(&ffmpeg_path, &row.audio_file, spec.start(), spec.end())
+            .await?;
+    let replaced = ingest::Ingest::new(data)
+        .run(
+            ingest::IngestSource::TempFile(trimmed),
+            ingest::IngestTarget::Replace(&row),
+        )
+        .await;
+    if let Err(err) = replaced {
+        reply(format!("Failed trimming `{}` - {err}", row.name)).await;
+        return Ok(());
+    }
+
+    reply(format!("Trimmed `{}` to {}", row.name, spec.range_text())).await;
+
+    helpers::send_audit_message(
+        ctx,
+        data,
+        component.guild_id,
+        CreateMessage::new().content(format!(
+            "Sound `{}` trimmed to {} by <@{}>",
+            row.name,
+            spec.range_text(),
+            component.user.id
+        )),
+    )
+    .await;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trim_spec_parts_test() {
+        let spec = TrimSpec::new(3, 250, Some(1500), "0123456789abcdef0123");
+        assert_eq!(spec.to_parts(), "3::250::1500::0123456789abcdef");
+        assert_eq!(
+            TrimSpec::from_parts(&["3", "250", "1500", "0123456789abcdef"]),
+            Ok(spec.clone())
+        );
+        assert!(spec.matches_hash("0123456789abcdef0123"));
+        assert!(!spec.matches_hash("f123456789abcdef0123"));
+
+        let spec = TrimSpec::from_parts(&["3", "250", "", "ab"]).unwrap();
+        assert_eq!(spec.end_ms, None);
+        assert_eq!(spec.range_text(), "0.25s - end");
+
+        assert!(TrimSpec::from_parts(&["3", "250"]).is_err());
+    }
+}
@@ -0,0 +1,666 @@
+use std::fs;
+use std::path;
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{GuildId, UserId};
+
+use crate::audio::AudioFile;
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::db::{
+    AudioTable, AudioTableRow, BannedSoundsTable, DbPool, LibraryOpRowInsert, LibraryOpsTable,
+    Tags, UniqueAudioTableCol,
+};
+use crate::errors::UndoError;
+use crate::helpers;
+
+/// Minutes after a library operation it can be undone. Audio of deleted sounds is kept this long
+pub const UNDO_WINDOW_MINUTES: i64 = 30;
+
+/// Directory of the audio dir deleted sounds' audio is kept in, until they can't be undone.
+/// Scans only read audio files at the top of the audio dir, so trashed audio isn't added back
+const TRASH_DIR: &str = ".trash";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LibraryOpKind {
+    Delete,
+    Rename,
+    Retag,
+    /// Metadata import changing many sounds at once
+    BulkEdit,
+    /// `/sounds purge` deleting many sounds at once
+    Purge,
+}
+
+impl LibraryOpKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Delete => "delete",
+            Self::Rename => "rename",
+            Self::Retag => "retag",
+            Self::BulkEdit => "bulk_edit",
+            Self::Purge => "purge",
+        }
+    }
+
+    /// Kind of an edit of a sound's name & tags. None when nothing changed
+    pub fn of_edit(before: &SoundMetadata, after: &AudioTableRow) -> Option<Self> {
+        match (
+            before.name != after.name,
+            before.tags != after.tags.to_string(),
+        ) {
+            (true, _) => Some(Self::Rename),
+            (false, true) => Some(Self::Retag),
+            (false, false) => None,
+        }
+    }
+
+    fn label(value: &str) -> &str {
+        match value {
+            "bulk_edit" => "bulk edit",
+            value => value,
+        }
+    }
+}
+
+/// Metadata of a sound before it was edited
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoundMetadata {
+    pub id: i64,
+    pub name: String,
+    pub tags: String,
+    pub emoji: Option<String>,
+    pub volume: Option<u32>,
+}
+
+impl From<&AudioTableRow> for SoundMetadata {
+    fn from(row: &AudioTableRow) -> Self {
+        Self {
+            id: row.id,
+            name: row.name.clone(),
+            tags: row.tags.to_string(),
+            emoji: row.emoji.clone(),
+            volume: row.volume,
+        }
+    }
+}
+
+/// Sound before it was deleted, with where its audio was moved to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeletedSound {
+    pub id: i64,
+    pub name: String,
+    pub tags: String,
+    pub audio_file: path::PathBuf,
+    /// Rfc 3339 timestamps
+    pub created_at: String,
+    pub file_modified_at: Option<String>,
+    pub author_id: Option<u64>,
+    pub author_name: Option<String>,
+    pub author_global_name: Option<String>,
+    pub file_hash: Option<String>,
+    pub file_size: Option<u64>,
+    pub emoji: Option<String>,
+    pub volume: Option<u32>,
+    pub protected: bool,
+    pub cooldown: Option<u32>,
+    pub transcript: Option<String>,
+    /// None when the audio file couldn't be moved, and was deleted instead
+    pub trash_file: Option<path::PathBuf>,
+    /// Ban added along with the delete, lifted when the delete is undone
+    pub banned_sound_id: Option<i64>,
+}
+
+impl DeletedSound {
+    fn of(
+        row: &AudioTableRow,
+        transcript: Option<String>,
+        trash_file: Option<path::PathBuf>,
+        banned_sound_id: Option<i64>,
+    ) -> Self {
+        Self {
+            id: row.id,
+            name: row.name.clone(),
+            tags: row.tags.to_string(),
+            audio_file: row.audio_file.as_path_buf(),
+            created_at: row.created_at.to_rfc3339(),
+            file_modified_at: row.file_modified_at.map(|value| value.to_rfc3339()),
+            author_id: row.author_id,
+            author_name: row.author_name.clone(),
+            author_global_name: row.author_global_name.clone(),
+            file_hash: row.file_hash.clone(),
+            file_size: row.file_size,
+            emoji: row.emoji.clone(),
+            volume: row.volume,
+            protected: row.protected,
+            cooldown: row.cooldown,
+            transcript,
+            trash_file,
+            banned_sound_id,
+        }
+    }
+
+    fn to_row(&self) -> Result<AudioTableRow, PoiseError> {
+        let parse =
+            |value: &str| chrono::DateTime::parse_from_rfc3339(value).map(|value| value.to_utc());
+
+        Ok(AudioTableRow {
+            id: self.id,
+            name: self.name.clone(),
+            tags: Tags::from(self.tags.as_str()),
+            audio_file: AudioFile::new(self.audio_file.clone()),
+            created_at: parse(&self.created_at)?,
+            author_id: self.author_id,
+            author_name: self.author_name.clone(),
+            author_global_name: self.author_global_name.clone(),
+            file_hash: self.file_hash.clone(),
+            file_size: self.file_size,
+            file_modified_at: self.file_modified_at.as_deref().map(parse).transpose()?,
+            emoji: self.emoji.clone(),
+            volume: self.volume,
+            protected: self.protected,
+            cooldown: self.cooldown,
+        })
+    }
+}
+
+/// Library state before an operation, which undoing it goes back to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LibraryChange {
+    Edited { sounds: Vec<SoundMetadata> },
+    Deleted { sound: Box<DeletedSound> },
+    Purged { sounds: Vec<DeletedSound> },
+}
+
+impl LibraryChange {
+    fn describe(&self) -> String {
+        match self {
+            Self::Edited { sounds } if sounds.len() == 1 => format!("`{}`", sounds[0].name),
+            Self::Edited { sounds } => format!("{} sounds", sounds.len()),
+            Self::Deleted { sound } => format!("`{}`", sound.name),
+            Self::Purged { sounds } if sounds.len() == 1 => format!("`{}`", sounds[0].name),
+            Self::Purged { sounds } => format!("{} sounds", sounds.len()),
+        }
+    }
+}
+
+/// Log operation, and forget operations too old to undo
+fn record(
+    db_pool: &DbPool,
+    kind: LibraryOpKind,
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+    change: &LibraryChange,
+) -> Result<(), PoiseError> {
+    LibraryOpsTable::new(db_pool.get()?).insert_row(&LibraryOpRowInsert {
+        kind: kind.as_str().into(),
+        guild_id: guild_id.map(|id| id.get()),
+        user_id: user_id.get(),
+        changes: serde_json::to_string(change)?,
+    })?;
+
+    prune(db_pool)
+        .log_err_msg("Failed pruning library operations")
+        .ok();
+    Ok(())
+}
+
+/// Log edit of sounds, given their metadata from before the edit. Failing to log doesn't fail
+/// the edit, it's only left out of `/undo`
+pub fn record_edit(
+    db_pool: &DbPool,
+    kind: LibraryOpKind,
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+    sounds: Vec<SoundMetadata>,
+) {
+    if sounds.is_empty() {
+        return;
+    }
+
+    record(
+        db_pool,
+        kind,
+        guild_id,
+        user_id,
+        &LibraryChange::Edited { sounds },
+    )
+    .log_err_msg(format!("Failed logging {} operation", kind.as_str()))
+    .ok();
+}
+
+/// Delete sound, keeping its audio in the trash until the delete can't be undone
+pub fn delete_sound(
+    db_pool: &DbPool,
+    audio_dir: &path::Path,
+    row: &AudioTableRow,
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+    banned_sound_id: Option<i64>,
+) -> Result<(), PoiseError> {
+    let table = AudioTable::new(db_pool.get()?);
+    let transcript = table.transcript(row.id).unwrap_or_default();
+
+    let trash_file = move_to_trash(audio_dir, &row.audio_file)
+        .log_err_msg(format!("Failed moving audio of `{}` to trash", row.name))
+        .ok();
+    if trash_file.is_none() {
+        row.audio_file.delete();
+    }
+    table.delete_row(row.id)?;
+
+    let sound = DeletedSound::of(row, transcript, trash_file, banned_sound_id);
+    record(
+        db_pool,
+        LibraryOpKind::Delete,
+        guild_id,
+        user_id,
+        &LibraryChange::Deleted {
+            sound: Box::new(sound),
+        },
+    )
+    .log_err_msg("Failed logging delete operation")
+    .ok();
+
+    Ok(())
+}
+
+/// Delete sounds together, or none of them when deleting their rows fails. Their audio is kept in
+/// the trash until the purge can't be undone, and `/undo` brings them all back
+pub fn purge_sounds(
+    db_pool: &DbPool,
+    audio_dir: &path::Path,
+    rows: &[AudioTableRow],
+    guild_id: Option<GuildId>,
+    user_id: UserId,
+) -> Result<(), PoiseError> {
+    let table = AudioTable::new(db_pool.get()?);
+
+    let mut sounds = vec![];
+    for row in rows {
+        let transcript = table.transcript(row.id).unwrap_or_default();
+        let trash_file = move_to_trash(audio_dir, &row.audio_file)
+            .log_err_msg(format!("Failed moving audio of `{}` to trash", row.name))
+            .ok();
+        sounds.push(DeletedSound::of(row, transcript, trash_file, None));
+    }
+
+    let ids: Vec<i64> = rows.iter().map(|row| row.id).collect();
+    if let Err(err) = table.delete_rows(&ids) {
+        for sound in sounds.iter() {
+            if let Some(trash_file) = &sound.trash_file {
+                fs::rename(trash_file, &sound.audio_file)
+                    .log_err_msg("Failed moving audio back from trash")
+                    .ok();
+            }
+        }
+        return Err(err);
+    }
+
+    // audio that couldn't be trashed can't be brought back, it's deleted once its row is gone
+    for sound in sounds.iter().filter(|sound| sound.trash_file.is_none()) {
+        AudioFile::new(sound.audio_file.clone()).delete();
+    }
+
+    record(
+        db_pool,
+        LibraryOpKind::Purge,
+        guild_id,
+        user_id,
+        &LibraryChange::Purged { sounds },
+    )
+    .log_err_msg("Failed logging purge operation")
+    .ok();
+
+    Ok(())
+}
+
+fn move_to_trash(
+    audio_dir: &path::Path,
+    audio_file: &AudioFile,
+) -> Result<path::PathBuf, PoiseError> {
+    let trash_dir = audio_dir.join(TRASH_DIR);
+    fs::create_dir_all(&trash_dir)?;
+
+    let trash_file = trash_dir.join(format!(
+        "{}-{}",
+        helpers::uuid_v4_str(),
+        audio_file.file_name_lossy()
+    ));
+    fs::rename(audio_file.as_path_buf(), &trash_file)?;
+    Ok(trash_file)
+}
+
+/// Reverse newest library operation not undone yet, if made within the undo window.
+/// Returns what was undone, ie: "rename of `airhorn`"
+pub fn undo_latest(db_pool: &DbPool) -> Result<String, PoiseError> {
+    let ops_table = LibraryOpsTable::new(db_pool.get()?);
+    let since = chrono::Utc::now() - chrono::Duration::minutes(UNDO_WINDOW_MINUTES);
+    let op = ops_table
+        .latest_undoable(since)?
+        .ok_or(UndoError::NothingToUndo {
+            minutes: UNDO_WINDOW_MINUTES,
+        })?;
+
+    log::info!("Undoing library operation {} ({})", op.id, op.kind);
+    let change: LibraryChange = serde_json::from_str(&op.changes)?;
+    match &change {
+        LibraryChange::Edited { sounds } => undo_edit(db_pool, sounds)?,
+        LibraryChange::Deleted { sound } => undo_delete(db_pool, sound)?,
+        LibraryChange::Purged { sounds } => undo_purge(db_pool, op.id, sounds)?,
+    }
+    ops_table.set_undone(op.id)?;
+
+    Ok(format!(
+        "{} of {}",
+        LibraryOpKind::label(&op.kind),
+        change.describe()
+    ))
+}
+
+fn undo_edit(db_pool: &DbPool, sounds: &[SoundMetadata]) -> Result<(), PoiseError> {
+    let table = AudioTable::new(db_pool.get()?);
+    let ids: Vec<i64> = sounds.iter().map(|sound| sound.id).collect();
+    for sound in sounds {
+        let taken = table
+            .find_audio_row(UniqueAudioTableCol::Name(sound.name.clone()))
+            .is_some_and(|row| !ids.contains(&row.id));
+        if taken {
+            return Err(UndoError::NameTaken {
+                name: sound.name.clone(),
+            }
+            .into());
+        }
+    }
+
+    // sounds may have swapped names, so names are freed up before any are given back
+    let rows: Vec<AudioTableRow> = sounds
+        .iter()
+        .filter_map(|sound| table.find_audio_row(UniqueAudioTableCol::Id(sound.id)))
+        .collect();
+    for row in rows.iter() {
+        let placeholder = format!("undo-{}-{}", row.id, helpers::uuid_v4_str());
+        table.update_metadata(
+            row.id,
+            &placeholder,
+            &row.tags,
+            row.emoji.as_deref(),
+            row.volume,
+        )?;
+    }
+
+    for sound in sounds
+        .iter()
+        .filter(|sound| rows.iter().any(|row| row.id == sound.id))
+    {
+        table.update_metadata(
+            sound.id,
+            &sound.name,
+            &Tags::from(sound.tags.as_str()),
+            sound.emoji.as_deref(),
+            sound.volume,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn undo_delete(db_pool: &DbPool, sound: &DeletedSound) -> Result<(), PoiseError> {
+    let table = AudioTable::new(db_pool.get()?);
+    if table
+        .find_audio_row(UniqueAudioTableCol::Name(sound.name.clone()))
+        .is_some()
+    {
+        return Err(UndoError::NameTaken {
+            name: sound.name.clone(),
+        }
+        .into());
+    }
+
+    let audio_missing = || UndoError::AudioMissing {
+        name: sound.name.clone(),
+    };
+    let trash_file = sound.trash_file.as_ref().ok_or_else(audio_missing)?;
+    if !trash_file.is_file() || sound.audio_file.exists() {
+        return Err(audio_missing().into());
+    }
+
+    fs::rename(trash_file, &sound.audio_file)?;
+    let row_id = table.restore_row(&sound.to_row()?).inspect_err(|_| {
+        fs::rename(&sound.audio_file, trash_file)
+            .log_err_msg("Failed moving audio back to trash")
+            .ok();
+    })?;
+
+    if let Some(transcript) = &sound.transcript {
+        table.set_transcript(row_id, transcript)?;
+    }
+    if let Some(banned_sound_id) = sound.banned_sound_id {
+        BannedSoundsTable::new(db_pool.get()?).delete_row(banned_sound_id)?;
+    }
+
+    Ok(())
+}
+
+/// Bring back purged sounds, unless another sound took the name of any. When only some come back,
+/// operation is left to undo with the sounds that didn't, so their audio stays in the trash
+fn undo_purge(db_pool: &DbPool, op_id: i64, sounds: &[DeletedSound]) -> Result<(), PoiseError> {
+    let table = AudioTable::new(db_pool.get()?);
+    for sound in sounds {
+        if table
+            .find_audio_row(UniqueAudioTableCol::Name(sound.name.clone()))
+            .is_some()
+        {
+            return Err(UndoError::NameTaken {
+                name: sound.name.clone(),
+            }
+            .into());
+        }
+    }
+
+    let mut failed = vec![];
+    let mut first_err = None;
+    for sound in sounds {
+        if let Err(err) = undo_delete(db_pool, sound).log_err_msg(format!(
+            "Failed bringing back purged sound `{}`",
+            sound.name
+        )) {
+            failed.push(sound.clone());
+            first_err.get_or_insert(err);
+        }
+    }
+
+    match first_err {
+        None => Ok(()),
+        // nothing changed, operation can be undone as it is
+        Some(err) if failed.len() == sounds.len() => Err(err),
+        Some(_) => {
+            let err = UndoError::PartlyRestored {
+                restored: sounds.len() - failed.len(),
+                failed: failed.iter().map(|sound| sound.name.clone()).collect(),
+            };
+            let change = LibraryChange::Purged { sounds: failed };
+            LibraryOpsTable::new(db_pool.get()?)
+                .set_changes(op_id, &serde_json::to_string(&change)?)?;
+
+            Err(err.into())
+        }
+    }
+}
+
+/// Forget operations too old to undo, deleting trashed audio of their deleted sounds.
+/// Returns number of operations forgotten
+pub fn prune(db_pool: &DbPool) -> Result<usize, PoiseError> {
+    let before = chrono::Utc::now() - chrono::Duration::minutes(UNDO_WINDOW_MINUTES);
+    let ops = LibraryOpsTable::new(db_pool.get()?).delete_before(before)?;
+
+    for op in ops.iter().filter(|op| op.undone_at.is_none()) {
+        let change = serde_json::from_str::<LibraryChange>(&op.changes)
+            .log_err_msg(format!("Invalid changes of library operation {}", op.id));
+        let sounds = match change {
+            Ok(LibraryChange::Deleted { sound }) => vec![*sound],
+            Ok(LibraryChange::Purged { sounds }) => sounds,
+            _ => vec![],
+        };
+        for trash_file in sounds.into_iter().filter_map(|sound| sound.trash_file) {
+            AudioFile::new(trash_file).delete();
+        }
+    }
+
+    Ok(ops.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use r2d2_sqlite::SqliteConnectionManager;
+
+    use super::*;
+    use crate::db::{AudioTableRowInsert, Table};
+
+    const USER_ID: UserId = UserId::new(2);
+
+    fn insert_sound(db_pool: &DbPool, dir: &path::Path, name: &str) -> AudioTableRow {
+        let path = dir.join(format!("{name}.mp3"));
+        fs::write(&path, name).unwrap();
+
+        let table = AudioTable::new(db_pool.get().unwrap());
+        table
+            .insert_audio_row(AudioTableRowInsert {
+                name: name.into(),
+                tags: Tags::from("loud"),
+                audio_file: AudioFile::new(path),
+                created_at: chrono::Utc::now(),
+                author_id: Some(1),
+                author_name: None,
+                author_global_name: None,
+                file_hash: None,
+                file_size: None,
+                file_modified_at: None,
+            })
+            .unwrap();
+        table
+            .find_audio_row(UniqueAudioTableCol::Name(name.into()))
+            .unwrap()
+    }
+
+    #[test]
+    fn undo_test() {
+        let dir = std::env::temp_dir().join(helpers::uuid_v4_str());
+        fs::create_dir(&dir).unwrap();
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        AudioTable::new(db_pool.get().unwrap()).create_table();
+        LibraryOpsTable::new(db_pool.get().unwrap()).create_table();
+        BannedSoundsTable::new(db_pool.get().unwrap()).create_table();
+        let table = AudioTable::new(db_pool.get().unwrap());
+
+        let airhorn = insert_sound(&db_pool, &dir, "airhorn");
+        let trombone = insert_sound(&db_pool, &dir, "trombone");
+
+        // names swapped by a bulk edit
+        let previous = vec![
+            SoundMetadata::from(&airhorn),
+            SoundMetadata::from(&trombone),
+        ];
+        table
+            .update_metadata(airhorn.id, "tmp", &airhorn.tags, None, None)
+            .unwrap();
+        table
+            .update_metadata(trombone.id, "airhorn", &trombone.tags, None, None)
+            .unwrap();
+        table
+            .update_metadata(airhorn.id, "trombone", &airhorn.tags, Some("📯"), None)
+            .unwrap();
+        record_edit(&db_pool, LibraryOpKind::BulkEdit, None, USER_ID, previous);
+
+        let banned_id = BannedSoundsTable::new(db_pool.get().unwrap())
+            .insert_row("trombone", None, None, USER_ID.get())
+            .unwrap();
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Name("trombone".into()))
+            .unwrap();
+        delete_sound(&db_pool, &dir, &row, None, USER_ID, Some(banned_id)).unwrap();
+        assert!(!airhorn.audio_file.as_path_buf().exists());
+        assert_eq!(fs::read_dir(dir.join(TRASH_DIR)).unwrap().count(), 1);
+
+        // newest operation is undone first
+        assert_eq!(undo_latest(&db_pool).unwrap(), "delete of `trombone`");
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Id(airhorn.id))
+            .unwrap();
+        assert_eq!(
+            (row.name.as_str(), row.emoji.as_deref()),
+            ("trombone", Some("📯"))
+        );
+        assert!(airhorn.audio_file.as_path_buf().exists());
+        assert!(BannedSoundsTable::new(db_pool.get().unwrap())
+            .rows()
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(undo_latest(&db_pool).unwrap(), "bulk edit of 2 sounds");
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Id(airhorn.id))
+            .unwrap();
+        assert_eq!((row.name.as_str(), row.emoji), ("airhorn", None));
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Id(trombone.id))
+            .unwrap();
+        assert_eq!(row.name, "trombone");
+
+        assert_eq!(
+            undo_latest(&db_pool).unwrap_err().to_string(),
+            "Nothing to undo from the last 30 minutes."
+        );
+
+        // a deleted sound's name taken by a new sound
+        let row = table
+            .find_audio_row(UniqueAudioTableCol::Id(trombone.id))
+            .unwrap();
+        delete_sound(&db_pool, &dir, &row, None, USER_ID, None).unwrap();
+        insert_sound(&db_pool, &dir, "trombone");
+        assert_eq!(
+            undo_latest(&db_pool).unwrap_err().to_string(),
+            "Can't undo - another sound is named `trombone` now. Rename it first."
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn undo_purge_test() {
+        let dir = std::env::temp_dir().join(helpers::uuid_v4_str());
+        fs::create_dir(&dir).unwrap();
+        let db_pool = r2d2::Pool::new(SqliteConnectionManager::memory()).unwrap();
+        AudioTable::new(db_pool.get().unwrap()).create_table();
+        LibraryOpsTable::new(db_pool.get().unwrap()).create_table();
+        let table = AudioTable::new(db_pool.get().unwrap());
+
+        let rows = vec![
+            insert_sound(&db_pool, &dir, "airhorn"),
+            insert_sound(&db_pool, &dir, "trombone"),
+        ];
+        insert_sound(&db_pool, &dir, "kazoo");
+
+        purge_sounds(&db_pool, &dir, &rows, None, USER_ID).unwrap();
+        assert_eq!(table.count_rows().unwrap(), 1);
+        assert!(!rows[0].audio_file.as_path_buf().exists());
+        assert_eq!(fs::read_dir(dir.join(TRASH_DIR)).unwrap().count(), 2);
+
+        // trombone's audio is in the way, so only airhorn comes back
+        fs::write(rows[1].audio_file.as_path_buf(), "new").unwrap();
+        assert_eq!(
+            undo_latest(&db_pool).unwrap_err().to_string(),
+            "Brought back 1 purged sounds, but not `trombone`. `/undo` tries them again."
+        );
+        assert_eq!(table.count_rows().unwrap(), 2);
+        assert_eq!(fs::read_dir(dir.join(TRASH_DIR)).unwrap().count(), 1);
+
+        fs::remove_file(rows[1].audio_file.as_path_buf()).unwrap();
+        assert_eq!(undo_latest(&db_pool).unwrap(), "purge of `trombone`");
+        assert_eq!(table.count_rows().unwrap(), 3);
+        assert!(rows.iter().all(|row| row.audio_file.as_path_buf().exists()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+use std::path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serenity::all::{GuildId, UserId};
+
+use crate::common::LogResult;
+use crate::errors::UploadError;
+use crate::helpers;
+
+/// How long an unfinished upload is kept after its last chunk, before it's dropped
+pub const UPLOAD_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Most unfinished uploads an API key can have at once
+pub const MAX_UPLOADS_PER_KEY: usize = 4;
+
+/// API key & member an upload belongs to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UploadOwner {
+    /// Only the key an upload was started with can continue it
+    pub key_id: i64,
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+}
+
+/// Upload sent over several requests, so large files survive dropped connections. Chunks are
+/// appended to a temp file in order, and the sound is added once the last one arrives
+#[derive(Debug)]
+pub struct UploadSession {
+    pub id: String,
+    pub owner: UploadOwner,
+    pub name: String,
+    pub tags: String,
+    /// Bytes of the whole file
+    pub size: u64,
+    /// Bytes received so far
+    pub offset: u64,
+    pub path: path::PathBuf,
+    updated_at: Instant,
+    /// Whether a chunk is being written, so chunks sent at once don't interleave
+    writing: bool,
+}
+
+impl UploadSession {
+    pub fn status(&self) -> UploadStatus {
+        UploadStatus {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            size: self.size,
+            offset: self.offset,
+        }
+    }
+
+    fn expired(&self, now: Instant) -> bool {
+        !self.writing && now.duration_since(self.updated_at) > UPLOAD_TTL
+    }
+
+    fn remove_file(&self) {
+        if self.path.exists() {
+            std::fs::remove_file(&self.path)
+                .log_err_msg("Failed removing upload temp file")
+                .ok();
+        }
+    }
+}
+
+/// Progress of an upload, as returned by the HTTP API
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UploadStatus {
+    pub id: String,
+    pub name: String,
+    pub size: u64,
+    pub offset: u64,
+}
+
+/// Chunk claimed for writing, by `Uploads::start_chunk`. Dropped without being finished, ie: when
+/// the client disconnects mid write, the upload is released without counting the chunk
+pub struct ChunkWrite<'a> {
+    uploads: &'a Uploads,
+    id: String,
+    pub path: path::PathBuf,
+    finished: bool,
+}
+
+impl ChunkWrite<'_> {
+    /// Release upload after `written` bytes were appended. Returns the finished upload once every
+    /// byte arrived, leaving its temp file to the caller
+    pub fn finish(mut self, written: u64) -> Option<UploadSession> {
+        self.finished = true;
+        self.uploads.finish_chunk(&self.id, written)
+    }
+}
+
+impl Drop for ChunkWrite<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            log::info!("Chunk of upload {} was dropped mid write", self.id);
+            self.uploads.release(&self.id);
+        }
+    }
+}
+
+/// Unfinished uploads of the HTTP API. Kept in memory, so they don't survive a restart
+#[derive(Default)]
+pub struct Uploads {
+    sessions: Mutex<HashMap<String, UploadSession>>,
+}
+
+impl Uploads {
+    /// Start an upload of `size` bytes
+    pub fn start(
+        &self,
+        owner: UploadOwner,
+        name: String,
+        tags: String,
+        size: u64,
+    ) -> Result<UploadStatus, UploadError> {
+        let key_id = owner.key_id;
+        let mut sessions = self.sessions.lock().unwrap();
+        prune(&mut sessions, Instant::now());
+        let in_progress = sessions
+            .values()
+            .filter(|session| session.owner.key_id == key_id)
+            .count();
+        if in_progress >= MAX_UPLOADS_PER_KEY {
+            return Err(UploadError::TooManyUploads {
+                max: MAX_UPLOADS_PER_KEY,
+            });
+        }
+
+        let id = helpers::uuid_v4_str();
+        log::info!("Starting upload {id} of `{name}`, {size} bytes. key: {key_id}");
+        let session = UploadSession {
+            id: id.clone(),
+            owner,
+            name,
+            tags,
+            size,
+            offset: 0,
+            path: std::env::temp_dir().join(format!("{id}.mp3")),
+            updated_at: Instant::now(),
+            writing: false,
+        };
+        let status = session.status();
+        sessions.insert(id, session);
+
+        Ok(status)
+    }
+
+    pub fn status(&self, key_id: i64, id: &str) -> Result<UploadStatus, UploadError> {
+        let sessions = self.sessions.lock().unwrap();
+        match sessions.get(id) {
+            Some(session) if session.owner.key_id == key_id && !session.expired(Instant::now()) => {
+                Ok(session.status())
+            }
+            _ => Err(UploadError::NotFound),
+        }
+    }
+
+    /// Claim upload for writing `len` bytes at `offset`. Offset must be where the upload is at,
+    /// so a chunk resent after a dropped connection isn't appended twice
+    pub fn start_chunk(
+        &self,
+        key_id: i64,
+        id: &str,
+        offset: u64,
+        len: u64,
+    ) -> Result<ChunkWrite<'_>, UploadError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions
+            .get_mut(id)
+            .filter(|session| session.owner.key_id == key_id && !session.expired(Instant::now()))
+            .ok_or(UploadError::NotFound)?;
+
+        if session.writing {
+            return Err(UploadError::Busy);
+        }
+        if offset != session.offset {
+            return Err(UploadError::OffsetMismatch {
+                offset: session.offset,
+                sent: offset,
+            });
+        }
+        if offset + len > session.size {
+            return Err(UploadError::PastEnd { size: session.size });
+        }
+
+        session.writing = true;
+        Ok(ChunkWrite {
+            uploads: self,
+            id: id.to_string(),
+            path: session.path.clone(),
+            finished: false,
+        })
+    }
+
+    fn finish_chunk(&self, id: &str, written: u64) -> Option<UploadSession> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get_mut(id)?;
+        session.writing = false;
+        session.offset += written;
+        session.updated_at = Instant::now();
+
+        match session.offset >= session.size {
+            true => sessions.remove(id),
+            false => None,
+        }
+    }
+
+    /// Release upload claimed by `start_chunk`, leaving it where it was before the chunk
+    fn release(&self, id: &str) {
+        if let Some(session) = self.sessions.lock().unwrap().get_mut(id) {
+            session.writing = false;
+            session.updated_at = Instant::now();
+        }
+    }
+
+    /// Drop upload & its temp file. Returns whether upload was found
+    pub fn cancel(&self, key_id: i64, id: &str) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        match sessions.get(id) {
+            Some(session) if session.owner.key_id == key_id && !session.writing => {
+                log::info!("Cancelling upload {id}. key: {key_id}");
+                session.remove_file();
+                sessions.remove(id);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Drop uploads no chunk arrived for in a while
+fn prune(sessions: &mut HashMap<String, UploadSession>, now: Instant) {
+    sessions.retain(|id, session| {
+        let expired = session.expired(now);
+        if expired {
+            log::info!("Dropping expired upload {id}");
+            session.remove_file();
+        }
+        !expired
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(uploads: &Uploads, key_id: i64, size: u64) -> Result<UploadStatus, UploadError> {
+        let owner = UploadOwner {
+            key_id,
+            guild_id: GuildId::new(1),
+            user_id: UserId::new(2),
+        };
+        uploads.start(owner, "airhorn".into(), "".into(), size)
+    }
+
+    #[test]
+    fn upload_chunks_test() {
+        let uploads = Uploads::default();
+        let status = start(&uploads, 1, 10).unwrap();
+        let id = status.id.as_str();
+        assert_eq!(status.offset, 0);
+
+        assert_eq!(uploads.status(2, id), Err(UploadError::NotFound));
+        assert!(uploads.start_chunk(2, id, 0, 4).is_err());
+
+        let chunk = uploads.start_chunk(1, id, 0, 4).unwrap();
+        assert_eq!(
+            uploads.start_chunk(1, id, 0, 4).err(),
+            Some(UploadError::Busy)
+        );
+        assert!(chunk.finish(4).is_none());
+        assert_eq!(uploads.status(1, id).unwrap().offset, 4);
+
+        // client disconnected mid write, the chunk can be sent again
+        drop(uploads.start_chunk(1, id, 4, 3).unwrap());
+        assert_eq!(uploads.status(1, id).unwrap().offset, 4);
+
+        // resent chunk after a dropped connection
+        assert_eq!(
+            uploads.start_chunk(1, id, 0, 4).err(),
+            Some(UploadError::OffsetMismatch { offset: 4, sent: 0 })
+        );
+        assert_eq!(
+            uploads.start_chunk(1, id, 4, 7).err(),
+            Some(UploadError::PastEnd { size: 10 })
+        );
+
+        let session = uploads.start_chunk(1, id, 4, 6).unwrap().finish(6).unwrap();
+        assert_eq!((session.offset, session.name.as_str()), (10, "airhorn"));
+        assert_eq!(uploads.status(1, id), Err(UploadError::NotFound));
+    }
+
+    #[test]
+    fn upload_limits_test() {
+        let uploads = Uploads::default();
+        let ids: Vec<_> = (0..MAX_UPLOADS_PER_KEY)
+            .map(|_| start(&uploads, 1, 10).unwrap().id)
+            .collect();
+        assert_eq!(
+            start(&uploads, 1, 10),
+            Err(UploadError::TooManyUploads {
+                max: MAX_UPLOADS_PER_KEY
+            })
+        );
+        assert!(start(&uploads, 2, 10).is_ok());
+
+        assert!(!uploads.cancel(2, &ids[0]));
+        assert!(uploads.cancel(1, &ids[0]));
+        assert!(!uploads.cancel(1, &ids[0]));
+        assert!(start(&uploads, 1, 10).is_ok());
+
+        let mut sessions = uploads.sessions.lock().unwrap();
+        let later = Instant::now() + UPLOAD_TTL + Duration::from_secs(1);
+        prune(&mut sessions, later);
+        assert!(sessions.is_empty());
+    }
+}
@@ -1,3 +1,6 @@
 pub const BTN_LABEL_MAX_LEN: usize = 80;
 pub const ACTION_ROWS_LIMIT: u64 = 25;
+pub const EMBED_FIELD_VALUE_MAX_LEN: usize = 1024;
+pub const MESSAGE_CONTENT_MAX_LEN: usize = 2000;
+pub const ATTACHMENT_MAX_SIZE: u64 = 25 * 1024 * 1024;
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Short hash of commit bot was built from. `unknown` when built outside a git checkout
+pub const GIT_COMMIT: &str = env!("SOUNDBOARD_BOT_GIT_COMMIT");
+
+/// GitHub repository releases are published to
+pub const RELEASES_REPO: &str = "krywicki/discord-soundboard-bot";
+
+pub fn build_time() -> Option<DateTime<Utc>> {
+    env!("SOUNDBOARD_BOT_BUILD_TIME")
+        .parse::<i64>()
+        .ok()
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub html_url: String,
+}
+
+impl Release {
+    /// Whether release is newer than the running version
+    pub fn is_newer(&self) -> bool {
+        is_newer_version(&self.tag_name, VERSION)
+    }
+}
+
+/// Latest published release on GitHub
+pub async fn latest_release() -> Result<Release, PoiseError> {
+    let url = format!("https://api.github.com/repos/{RELEASES_REPO}/releases/latest");
+
+    let body = reqwest::Client::new()
+        .get(url)
+        .header(
+            reqwest::header::USER_AGENT,
+            format!("soundboard-bot/{VERSION}"),
+        )
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .log_err_msg("Failed fetching latest release")?
+        .text()
+        .await?;
+
+    Ok(serde_json::from_str(&body).log_err_msg("Failed parsing latest release")?)
+}
+
+/// `major.minor.patch` of version, with or without a leading `v`. Pre-release suffixes are ignored
+fn parse_version(version: &str) -> Option<(u64, u64, u64)> {
+    let version = version.trim().trim_start_matches('v');
+    let version = version.split(['-', '+']).next()?;
+
+    let mut parts = version.split('.').map(|part| part.parse::<u64>().ok());
+    let version = (parts.next()??, parts.next()??, parts.next()??);
+
+    match parts.next() {
+        Some(_) => None,
+        None => Some(version),
+    }
+}
+
+fn is_newer_version(latest: &str, current: &str) -> bool {
+    match (parse_version(latest), parse_version(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// Human readable uptime. ie: `3d 4h 12m`
+pub fn format_uptime(uptime: Duration) -> String {
+    let mins = uptime.as_secs() / 60;
+    let (days, hours, mins) = (mins / (24 * 60), mins / 60 % 24, mins % 60);
+
+    match (days, hours) {
+        (0, 0) => format!("{mins}m"),
+        (0, _) => format!("{hours}h {mins}m"),
+        _ => format!("{days}d {hours}h {mins}m"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_newer_version_test() {
+        assert_eq!(parse_version("v1.2.3"), Some((1, 2, 3)));
+        assert_eq!(parse_version("0.1.6-beta.1"), Some((0, 1, 6)));
+        assert_eq!(parse_version("1.2"), None);
+        assert_eq!(parse_version("1.2.3.4"), None);
+
+        assert!(is_newer_version("v0.1.7", "0.1.6"));
+        assert!(is_newer_version("v0.10.0", "0.9.9"));
+        assert!(!is_newer_version("v0.1.6", "0.1.6"));
+        assert!(!is_newer_version("v0.1.5", "0.1.6"));
+        assert!(!is_newer_version("nightly", "0.1.6"));
+    }
+
+    #[test]
+    fn format_uptime_test() {
+        assert_eq!(format_uptime(Duration::from_secs(59)), "0m");
+        assert_eq!(
+            format_uptime(Duration::from_secs(2 * 60 * 60 + 5 * 60)),
+            "2h 5m"
+        );
+        assert_eq!(
+            format_uptime(Duration::from_secs(3 * 24 * 60 * 60 + 60)),
+            "3d 0h 1m"
+        );
+    }
+}
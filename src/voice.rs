@@ -0,0 +1,324 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use serenity::all::{
+    ActivityData, Cache, ChannelId, CreateAllowedMentions, CreateMessage, EditMessage, GuildId,
+    Http, MessageId, ShardMessenger, UserId,
+};
+use serenity::async_trait;
+use songbird::tracks::TrackHandle;
+use songbird::{Event, EventContext, EventHandler as VoiceEventHandler, Songbird, TrackEvent};
+
+use crate::audio::AudioFile;
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+use crate::errors::AudioError;
+use crate::helpers::SongbirdHelper;
+use crate::prefetch::SoundCache;
+use crate::retry;
+
+/// Called once a track stops playing, with the track's uuid and whether it ended with an error
+pub type TrackEndCallback = Arc<dyn Fn(uuid::Uuid, bool) -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Voice side of playback. Songbird when running as a bot, simulated with `--dry-run` & in tests
+#[async_trait]
+pub trait VoiceBackend: Send + Sync {
+    /// Start playing audio file in guild's voice call, on behalf of `requested_by`. `on_end` is
+    /// called when the track ends
+    async fn play(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        audio_file: &AudioFile,
+        requested_by: Option<UserId>,
+        on_end: TrackEndCallback,
+    ) -> Result<Arc<dyn TrackControl>, AudioError>;
+
+    /// Whether bot is in a voice call of guild
+    fn in_call(&self, guild_id: GuildId) -> bool;
+
+    /// Voice channel bot is connected to in guild
+    async fn current_channel(&self, guild_id: GuildId) -> Option<ChannelId>;
+
+    /// Move bot's voice call in guild to another channel. Playing tracks carry on in the new one
+    async fn move_to(&self, guild_id: GuildId, channel_id: ChannelId) -> Result<(), PoiseError>;
+
+    async fn leave(&self, guild_id: GuildId) -> Result<(), PoiseError>;
+}
+
+/// Handle of a playing track
+#[async_trait]
+pub trait TrackControl: Send + Sync {
+    fn uuid(&self) -> uuid::Uuid;
+
+    /// Set volume, where 1.0 is unchanged volume
+    fn set_volume(&self, volume: f32) -> Result<(), PoiseError>;
+
+    fn pause(&self) -> Result<(), PoiseError>;
+
+    fn resume(&self) -> Result<(), PoiseError>;
+
+    fn stop(&self) -> Result<(), PoiseError>;
+
+    /// Restart track from the beginning whenever it ends, until stopped
+    fn enable_loop(&self) -> Result<(), PoiseError>;
+
+    /// Time into track
+    async fn position(&self) -> Result<Duration, PoiseError>;
+
+    async fn seek(&self, position: Duration) -> Result<(), PoiseError>;
+}
+
+/// Message side of playback, ie: editing track status messages & the bot's activity
+#[async_trait]
+pub trait ChatBackend: Send + Sync {
+    async fn edit_message(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        content: String,
+    ) -> Result<(), PoiseError>;
+
+    /// Send message to channel without pinging anyone it mentions
+    async fn send_message(
+        &self,
+        channel_id: ChannelId,
+        content: String,
+    ) -> Result<MessageId, PoiseError>;
+
+    /// Send direct message to user without pinging anyone it mentions
+    async fn send_direct_message(&self, user_id: UserId, content: String)
+        -> Result<(), PoiseError>;
+
+    /// Users in voice channel of guild, other than bots
+    fn voice_channel_members(&self, guild_id: GuildId, channel_id: ChannelId) -> Vec<UserId>;
+
+    /// Show bot as playing name. None clears the activity
+    fn set_activity(&self, name: Option<String>);
+}
+
+pub struct SongbirdVoice {
+    manager: Arc<Songbird>,
+    /// Prefetched sounds, played from memory instead of their file
+    sound_cache: Arc<SoundCache>,
+}
+
+impl SongbirdVoice {
+    pub fn new(manager: Arc<Songbird>, sound_cache: Arc<SoundCache>) -> Self {
+        Self {
+            manager,
+            sound_cache,
+        }
+    }
+
+    /// Bytes of audio file, when it's prefetched
+    async fn cached_bytes(&self, audio_file: &AudioFile) -> Option<Arc<[u8]>> {
+        let path = audio_file.as_path_buf();
+        let modified = tokio::fs::metadata(&path).await.ok()?.modified().ok()?;
+        self.sound_cache.get(&path, modified)
+    }
+}
+
+#[async_trait]
+impl VoiceBackend for SongbirdVoice {
+    async fn play(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        audio_file: &AudioFile,
+        requested_by: Option<UserId>,
+        on_end: TrackEndCallback,
+    ) -> Result<Arc<dyn TrackControl>, AudioError> {
+        let handle = match self.cached_bytes(audio_file).await {
+            Some(bytes) => {
+                self.manager
+                    .play_audio_bytes(guild_id, audio_file, bytes, requested_by)
+                    .await?
+            }
+            None => {
+                self.manager
+                    .play_audio(guild_id, channel_id, audio_file, requested_by)
+                    .await?
+            }
+        };
+
+        for (event, failed) in [(TrackEvent::End, false), (TrackEvent::Error, true)] {
+            handle
+                .add_event(
+                    Event::Track(event),
+                    TrackEndNotifier {
+                        on_end: Arc::clone(&on_end),
+                        failed,
+                    },
+                )
+                .log_err_msg("Failed adding track event handler")
+                .ok();
+        }
+
+        Ok(Arc::new(handle))
+    }
+
+    fn in_call(&self, guild_id: GuildId) -> bool {
+        self.manager.get(guild_id).is_some()
+    }
+
+    async fn current_channel(&self, guild_id: GuildId) -> Option<ChannelId> {
+        let call = self.manager.get(guild_id)?;
+        let channel_id = call.lock().await.current_channel()?;
+        Some(ChannelId::new(channel_id.0.get()))
+    }
+
+    async fn move_to(&self, guild_id: GuildId, channel_id: ChannelId) -> Result<(), PoiseError> {
+        // joining while in a call switches its channel, keeping the call's tracks & event handlers
+        self.manager.join(guild_id, channel_id).await?;
+        Ok(())
+    }
+
+    async fn leave(&self, guild_id: GuildId) -> Result<(), PoiseError> {
+        self.manager.leave_voice_channel(guild_id).await
+    }
+}
+
+struct TrackEndNotifier {
+    on_end: TrackEndCallback,
+    failed: bool,
+}
+
+#[async_trait]
+impl VoiceEventHandler for TrackEndNotifier {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(track_list) = ctx {
+            for (_state, handle) in *track_list {
+                (self.on_end)(handle.uuid(), self.failed).await;
+            }
+        }
+
+        None
+    }
+}
+
+#[async_trait]
+impl TrackControl for TrackHandle {
+    fn uuid(&self) -> uuid::Uuid {
+        TrackHandle::uuid(self)
+    }
+
+    fn set_volume(&self, volume: f32) -> Result<(), PoiseError> {
+        Ok(TrackHandle::set_volume(self, volume)?)
+    }
+
+    fn pause(&self) -> Result<(), PoiseError> {
+        Ok(TrackHandle::pause(self)?)
+    }
+
+    fn resume(&self) -> Result<(), PoiseError> {
+        Ok(TrackHandle::play(self)?)
+    }
+
+    fn stop(&self) -> Result<(), PoiseError> {
+        Ok(TrackHandle::stop(self)?)
+    }
+
+    fn enable_loop(&self) -> Result<(), PoiseError> {
+        Ok(TrackHandle::enable_loop(self)?)
+    }
+
+    async fn position(&self) -> Result<Duration, PoiseError> {
+        Ok(self.get_info().await?.position)
+    }
+
+    async fn seek(&self, position: Duration) -> Result<(), PoiseError> {
+        self.seek_async(position).await?;
+        Ok(())
+    }
+}
+
+pub struct HttpChat {
+    http: Arc<Http>,
+    cache: Arc<Cache>,
+    shard: ShardMessenger,
+}
+
+impl HttpChat {
+    pub fn new(http: Arc<Http>, cache: Arc<Cache>, shard: ShardMessenger) -> Self {
+        Self { http, cache, shard }
+    }
+}
+
+#[async_trait]
+impl ChatBackend for HttpChat {
+    async fn edit_message(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        content: String,
+    ) -> Result<(), PoiseError> {
+        retry::discord_retry("edit track status message", || {
+            channel_id.edit_message(
+                &self.http,
+                message_id,
+                EditMessage::new().content(content.clone()),
+            )
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    async fn send_message(
+        &self,
+        channel_id: ChannelId,
+        content: String,
+    ) -> Result<MessageId, PoiseError> {
+        let message = CreateMessage::new()
+            .content(content)
+            .allowed_mentions(CreateAllowedMentions::new());
+        let message = retry::discord_retry("send now playing message", || {
+            channel_id.send_message(&self.http, message.clone())
+        })
+        .await?;
+
+        Ok(message.id)
+    }
+
+    async fn send_direct_message(
+        &self,
+        user_id: UserId,
+        content: String,
+    ) -> Result<(), PoiseError> {
+        let message = CreateMessage::new()
+            .content(content)
+            .allowed_mentions(CreateAllowedMentions::new());
+        retry::discord_retry("send direct message", || {
+            user_id.direct_message(&self.http, message.clone())
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    fn voice_channel_members(&self, guild_id: GuildId, channel_id: ChannelId) -> Vec<UserId> {
+        let Some(guild) = self.cache.guild(guild_id) else {
+            return vec![];
+        };
+
+        guild
+            .voice_states
+            .values()
+            .filter(|voice_state| voice_state.channel_id == Some(channel_id))
+            .filter(|voice_state| {
+                !voice_state
+                    .member
+                    .as_ref()
+                    .is_some_and(|member| member.user.bot)
+            })
+            .filter(|voice_state| voice_state.user_id != self.cache.current_user().id)
+            .map(|voice_state| voice_state.user_id)
+            .collect()
+    }
+
+    fn set_activity(&self, name: Option<String>) {
+        self.shard.set_activity(name.map(ActivityData::playing));
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use serenity::all::{Cache, ChannelId, GuildId, UserId};
+use serenity::async_trait;
+use songbird::driver::DecodeMode;
+use songbird::{Call, CoreEvent, Event, EventContext, EventHandler as VoiceEventHandler};
+
+use crate::auth;
+use crate::commands::PoiseError;
+use crate::common::{LogResult, UserData};
+use crate::config::SharedConfig;
+use crate::db::{
+    self, AudioTable, BannedUsersTable, DbPool, FeatureFlagsTable, PlayHistoryRowInsert,
+    PlayHistoryTable, SettingsTable,
+};
+use crate::features::{self, FeatureFlag};
+use crate::listen::{self, Utterances};
+use crate::playback::{Playback, QueuedTrack};
+use crate::settings;
+use crate::whisper::transcriber;
+
+/// Listen for voice commands in call, when guild has them turned on. Call must be in guild
+pub fn listen(
+    data: &UserData,
+    cache: Arc<Cache>,
+    call: &mut Call,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<(), PoiseError> {
+    if !settings::voice_commands(&data.settings_table(), guild_id)
+        || !features::enabled(data, FeatureFlag::VoiceCommands)
+    {
+        return Ok(());
+    }
+
+    let Some(model_path) = data.config.get().whisper_model_path.clone() else {
+        log::warn!("Voice commands are on, but no whisper model is set. guild_id: {guild_id}");
+        return Ok(());
+    };
+    transcriber(&model_path).ok_or("Whisper model isn't loaded")?;
+
+    let config = call.config().clone().decode_mode(DecodeMode::Decode);
+    call.set_config(config);
+
+    let listener = VoiceCommandListener {
+        playback: data.playback.clone(),
+        db_pool: data.db_pool.clone(),
+        config: data.config.clone(),
+        cache,
+        guild_id,
+        channel_id,
+        speakers: Arc::default(),
+        utterances: Arc::default(),
+    };
+    call.add_global_event(CoreEvent::SpeakingStateUpdate.into(), listener.clone());
+    call.add_global_event(CoreEvent::VoiceTick.into(), listener);
+
+    log::info!("Listening for voice commands. guild_id: {guild_id}");
+    Ok(())
+}
+
+/// Transcribes what members say in the bot's voice channel, and plays sounds they ask for
+#[derive(Clone)]
+struct VoiceCommandListener {
+    playback: Arc<Playback>,
+    db_pool: DbPool,
+    config: Arc<SharedConfig>,
+    /// Roles of speakers, for sounds restricted to roles
+    cache: Arc<Cache>,
+    guild_id: GuildId,
+    /// Text channel the bot was summoned from
+    channel_id: ChannelId,
+    /// Members by the ssrc of their voice
+    speakers: Arc<Mutex<HashMap<u32, UserId>>>,
+    utterances: Arc<Mutex<Utterances>>,
+}
+
+#[async_trait]
+impl VoiceEventHandler for VoiceCommandListener {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        match ctx {
+            EventContext::SpeakingStateUpdate(speaking) => {
+                if let Some(user_id) = speaking.user_id {
+                    self.speakers
+                        .lock()
+                        .unwrap()
+                        .insert(speaking.ssrc, UserId::new(user_id.0));
+                }
+            }
+            EventContext::VoiceTick(tick) => {
+                let mut finished = vec![];
+                {
+                    let mut utterances = self.utterances.lock().unwrap();
+                    for (ssrc, voice) in &tick.speaking {
+                        if let Some(decoded) = &voice.decoded_voice {
+                            utterances.push(*ssrc, decoded);
+                        }
+                    }
+                    for ssrc in &tick.silent {
+                        if let Some(samples) = utterances.silence(*ssrc) {
+                            finished.push((*ssrc, samples));
+                        }
+                    }
+                }
+
+                for (ssrc, samples) in finished {
+                    let Some(user_id) = self.speakers.lock().unwrap().get(&ssrc).copied() else {
+                        continue;
+                    };
+                    let listener = self.clone();
+                    tokio::spawn(async move {
+                        listener
+                            .handle_utterance(user_id, samples)
+                            .await
+                            .log_err_msg("Failed handling voice command")
+                            .ok();
+                    });
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+}
+
+impl VoiceCommandListener {
+    async fn handle_utterance(&self, user_id: UserId, samples: Vec<f32>) -> Result<(), PoiseError> {
+        let config = self.config.get();
+        let Some(transcriber) = config.whisper_model_path.as_deref().and_then(transcriber) else {
+            return Ok(());
+        };
+        if !features::table_enabled(
+            &FeatureFlagsTable::new(self.db_pool.get()?),
+            FeatureFlag::VoiceCommands,
+        ) || !settings::voice_commands(&SettingsTable::new(self.db_pool.get()?), self.guild_id)
+        {
+            return Ok(());
+        }
+
+        let transcript =
+            tokio::task::spawn_blocking(move || transcriber.transcribe(&samples)).await??;
+        let Some(name) = listen::parse_command(&transcript, &config.wake_phrase) else {
+            return Ok(());
+        };
+        log::info!(
+            "Heard voice command for `{name}`. user_id: {user_id}, guild_id: {}",
+            self.guild_id
+        );
+
+        if let Some(reason) = BannedUsersTable::new(self.db_pool.get()?)
+            .find_ban(self.guild_id.get(), user_id.get())?
+        {
+            log::info!("Ignored voice command of banned user {user_id} - {reason}");
+            return Ok(());
+        }
+
+        let Some(row) = self.find_sound(&name)? else {
+            log::info!("No sound found for voice command `{name}`");
+            return Ok(());
+        };
+
+        let play_history_table = PlayHistoryTable::new(self.db_pool.get()?);
+        let member_roles = self
+            .cache
+            .member(self.guild_id, user_id)
+            .map(|member| member.roles.clone())
+            .unwrap_or_default();
+        if let Err(err) = auth::check_can_play(
+            &self.db_pool,
+            self.guild_id,
+            self.channel_id,
+            &row,
+            Some(user_id),
+            &member_roles,
+        ) {
+            log::info!("Ignored voice command of user {user_id} - {err}");
+            return Ok(());
+        }
+
+        let track = QueuedTrack::from_row(&row, self.channel_id).requested_by(user_id);
+        self.playback.enqueue(self.guild_id, track).await?;
+
+        play_history_table.insert_row(&PlayHistoryRowInsert {
+            guild_id: self.guild_id.get(),
+            user_id: Some(user_id.get()),
+            channel_id: Some(self.channel_id.get()),
+            audio_id: Some(row.id),
+            audio_name: row.name.clone(),
+            played_at: chrono::Utc::now(),
+        })?;
+
+        Ok(())
+    }
+
+    /// Sound named like what was heard, or the closest match. Transcripts rarely match a name
+    /// exactly, ie: "air horn" for `airhorn`
+    fn find_sound(&self, name: &str) -> Result<Option<db::AudioTableRow>, PoiseError> {
+        let table = AudioTable::new(self.db_pool.get()?);
+        if let Some(row) = table.find_audio_row(db::UniqueAudioTableCol::Name(name.into())) {
+            return Ok(Some(row));
+        }
+
+        // short names autocomplete to the newest sounds, not matches
+        if name.len() < 3 {
+            return Ok(None);
+        }
+        let closest = table.fts_autocomplete_track_names(name, Some(1));
+        Ok(closest
+            .into_iter()
+            .next()
+            .and_then(|name| table.find_audio_row(db::UniqueAudioTableCol::Name(name))))
+    }
+}
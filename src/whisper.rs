@@ -0,0 +1,58 @@
+use std::path::Path;
+use std::sync::OnceLock;
+
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+use crate::commands::PoiseError;
+use crate::common::LogResult;
+
+/// Whisper model, loaded the first time speech is transcribed. None when it failed loading, so
+/// it isn't retried every time
+static TRANSCRIBER: OnceLock<Option<Transcriber>> = OnceLock::new();
+
+pub struct Transcriber {
+    context: WhisperContext,
+}
+
+impl Transcriber {
+    fn load(path: &Path) -> Result<Self, PoiseError> {
+        log::info!("Loading whisper model {}", path.to_string_lossy());
+        let path = path
+            .to_str()
+            .ok_or("Whisper model path isn't valid unicode")?;
+        let context = WhisperContext::new_with_params(path, WhisperContextParameters::default())?;
+        Ok(Self { context })
+    }
+
+    /// Text spoken in 16kHz mono samples
+    pub fn transcribe(&self, samples: &[f32]) -> Result<String, PoiseError> {
+        let mut state = self.context.create_state()?;
+
+        let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+        params.set_language(Some("en"));
+        params.set_single_segment(true);
+        params.set_no_context(true);
+        params.set_print_progress(false);
+        params.set_print_realtime(false);
+        params.set_print_special(false);
+        params.set_print_timestamps(false);
+
+        state.full(params, samples)?;
+
+        let mut text = String::new();
+        for segment in 0..state.full_n_segments()? {
+            text.push_str(&state.full_get_segment_text(segment)?);
+        }
+        Ok(text)
+    }
+}
+
+pub fn transcriber(model_path: &Path) -> Option<&'static Transcriber> {
+    TRANSCRIBER
+        .get_or_init(|| {
+            Transcriber::load(model_path)
+                .log_err_msg("Failed loading whisper model")
+                .ok()
+        })
+        .as_ref()
+}